@@ -0,0 +1,42 @@
+use std::process::{Command, Output};
+
+// Runs git in the package directory and returns its trimmed output,
+// or None if git isn't available or this isn't a repository.
+fn git(arguments: &[&str]) -> Option<String> {
+    let output: Output = Command::new("git").args(arguments).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_string())
+}
+
+// Bakes the commit the crate is built from into the binary as GIT_REVISION,
+// with a "-dirty" suffix if there are uncommitted changes, so a benchmark report
+// describes the code that ran, wherever the binary is run from.
+fn main() {
+    // Rebuild when a commit is made or checked out, or when a source file
+    // changes, which might make the working tree dirty.
+    println!("cargo:rerun-if-changed=src");
+    for path in ["HEAD", "index", "packed-refs"] {
+        if let Some(git_path) = git(&["rev-parse", "--git-path", path]) {
+            println!("cargo:rerun-if-changed={}", git_path);
+        }
+    }
+    if let Some(branch) = git(&["symbolic-ref", "-q", "HEAD"]) {
+        if let Some(git_path) = git(&["rev-parse", "--git-path", &branch]) {
+            println!("cargo:rerun-if-changed={}", git_path);
+        }
+    }
+
+    let Some(mut revision) = git(&["rev-parse", "HEAD"]) else {
+        return;
+    };
+    let dirty: bool = git(&["status", "--porcelain", "--untracked-files=no"])
+        .map(|status| !status.is_empty())
+        .unwrap_or(false);
+    if dirty {
+        revision.push_str("-dirty");
+    }
+
+    println!("cargo:rustc-env=GIT_REVISION={}", revision);
+}
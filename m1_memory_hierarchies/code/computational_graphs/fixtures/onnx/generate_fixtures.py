@@ -0,0 +1,253 @@
+#!/usr/bin/env python3
+# Writes the ONNX fixtures used by src/shared/onnx_import_test.rs and prints the
+# outputs the importer is tested against.
+# Only uses the standard library, the protobuf wire format is written by hand so
+# the fixtures don't depend on the decoder they are testing.
+import math
+import os
+import struct
+
+FLOAT = 1
+ATTRIBUTE_FLOAT = 1
+ATTRIBUTE_INT = 2
+
+
+def varint(value):
+    if value < 0:
+        value += 1 << 64
+    output = bytearray()
+    while True:
+        byte = value & 0x7F
+        value >>= 7
+        if value:
+            output.append(byte | 0x80)
+        else:
+            output.append(byte)
+            return bytes(output)
+
+
+def field_varint(number, value):
+    return varint(number << 3) + varint(value)
+
+
+def field_bytes(number, value):
+    if isinstance(value, str):
+        value = value.encode("utf-8")
+    return varint((number << 3) | 2) + varint(len(value)) + value
+
+
+def field_float(number, value):
+    return varint((number << 3) | 5) + struct.pack("<f", value)
+
+
+def packed_varints(number, values):
+    return field_bytes(number, b"".join(varint(value) for value in values))
+
+
+def packed_floats(number, values):
+    return field_bytes(number, struct.pack("<%df" % len(values), *values))
+
+
+# Large exporters write raw_data, small hand written models often use float_data
+def tensor(name, dims, values, raw):
+    message = packed_varints(1, dims) + field_varint(2, FLOAT)
+    if raw:
+        message += field_bytes(9, struct.pack("<%df" % len(values), *values))
+    else:
+        message += packed_floats(4, values)
+    return message + field_bytes(8, name)
+
+
+def attribute_int(name, value):
+    return field_bytes(1, name) + field_varint(3, value) + field_varint(20, ATTRIBUTE_INT)
+
+
+def attribute_float(name, value):
+    return field_bytes(1, name) + field_float(2, value) + field_varint(20, ATTRIBUTE_FLOAT)
+
+
+def node(op_type, inputs, outputs, attributes=()):
+    message = b"".join(field_bytes(1, name) for name in inputs)
+    message += b"".join(field_bytes(2, name) for name in outputs)
+    message += field_bytes(3, op_type.lower() + "_" + outputs[0])
+    message += field_bytes(4, op_type)
+    message += b"".join(field_bytes(5, attribute) for attribute in attributes)
+    return message
+
+
+def value_info(name, dims):
+    shape = b""
+    for dim in dims:
+        if isinstance(dim, str):
+            shape += field_bytes(1, field_bytes(2, dim))
+        else:
+            shape += field_bytes(1, field_varint(1, dim))
+    tensor_type = field_varint(1, FLOAT) + field_bytes(2, shape)
+    return field_bytes(1, name) + field_bytes(2, field_bytes(1, tensor_type))
+
+
+def model(nodes, initializers, inputs, outputs):
+    graph = b"".join(field_bytes(1, message) for message in nodes)
+    graph += field_bytes(2, "graph")
+    graph += b"".join(field_bytes(5, message) for message in initializers)
+    graph += b"".join(field_bytes(11, message) for message in inputs)
+    graph += b"".join(field_bytes(12, message) for message in outputs)
+
+    operator_set = field_bytes(1, "") + field_varint(2, 13)
+    return (
+        field_varint(1, 8)
+        + field_bytes(2, "generate_fixtures.py")
+        + field_bytes(7, graph)
+        + field_bytes(8, operator_set)
+    )
+
+
+def values(row_count, column_count, seed):
+    return [
+        ((row * 7 + column * 3 + seed) % 11 - 5) / 10.0
+        for row in range(row_count)
+        for column in range(column_count)
+    ]
+
+
+def transpose(data, row_count, column_count):
+    return [
+        data[row * column_count + column]
+        for column in range(column_count)
+        for row in range(row_count)
+    ]
+
+
+def matmul(left, right, inner):
+    return [
+        [sum(row[k] * right[k][column] for k in range(inner)) for column in range(len(right[0]))]
+        for row in left
+    ]
+
+
+def rows(data, row_count, column_count):
+    return [data[row * column_count:(row + 1) * column_count] for row in range(row_count)]
+
+
+def linear(input, weights, bias, alpha=1.0, beta=1.0):
+    product = matmul(input, weights, len(weights))
+    return [
+        [alpha * value + beta * bias[column] for column, value in enumerate(row)]
+        for row in product
+    ]
+
+
+def relu(data):
+    return [[max(value, 0.0) for value in row] for row in data]
+
+
+def softmax(data):
+    output = []
+    for row in data:
+        maximum = max(row)
+        exponentials = [math.exp(value - maximum) for value in row]
+        total = sum(exponentials)
+        output.append([value / total for value in exponentials])
+    return output
+
+
+def as_f32(data):
+    return [[struct.unpack("<f", struct.pack("<f", value))[0] for value in row] for row in data]
+
+
+def print_expected(name, input, output):
+    print(name)
+    print("  input:  %s" % ", ".join("%r" % value for row in input for value in row))
+    print("  output: %s" % ", ".join("%.7f" % value for row in output for value in row))
+
+
+def main():
+    directory = os.path.dirname(os.path.abspath(__file__))
+
+    # Gemm -> Relu -> Gemm -> Softmax, the way PyTorch exports nn.Linear with transB = 1
+    w1 = values(3, 4, 1)
+    b1 = values(1, 3, 2)
+    w2 = values(2, 3, 3)
+    b2 = values(1, 2, 4)
+    gemm = model(
+        [
+            node("Gemm", ["input", "w1", "b1"], ["hidden"], [attribute_int("transB", 1)]),
+            node("Relu", ["hidden"], ["activated"]),
+            node(
+                "Gemm",
+                ["activated", "w2", "b2"],
+                ["logits"],
+                [
+                    attribute_int("transB", 1),
+                    attribute_float("alpha", 0.5),
+                    attribute_float("beta", 2.0),
+                ],
+            ),
+            node("Softmax", ["logits"], ["output"], [attribute_int("axis", -1)]),
+        ],
+        [
+            tensor("w1", [3, 4], w1, True),
+            tensor("b1", [3], b1, True),
+            tensor("w2", [2, 3], w2, True),
+            tensor("b2", [2], b2, True),
+        ],
+        [value_info("input", ["batch", 4])],
+        [value_info("output", ["batch", 2])],
+    )
+    with open(os.path.join(directory, "mlp_gemm.onnx"), "wb") as file:
+        file.write(gemm)
+
+    input = as_f32([[0.5, -1.0, 0.25, 2.0]])
+    hidden = relu(linear(input, rows(transpose(w1, 3, 4), 4, 3), b1))
+    output = softmax(linear(hidden, rows(transpose(w2, 2, 3), 3, 2), b2, 0.5, 2.0))
+    print_expected("mlp_gemm.onnx", input, output)
+
+    # MatMul + Add -> Relu -> MatMul + Add, with the bias as the left input of the second Add
+    w1 = values(4, 3, 5)
+    b1 = values(1, 3, 6)
+    w2 = values(3, 2, 7)
+    b2 = values(1, 2, 8)
+    matmul_add = model(
+        [
+            node("MatMul", ["input", "w1"], ["product_1"]),
+            node("Add", ["product_1", "b1"], ["hidden"]),
+            node("Relu", ["hidden"], ["activated"]),
+            node("MatMul", ["activated", "w2"], ["product_2"]),
+            node("Add", ["b2", "product_2"], ["output"]),
+        ],
+        [
+            tensor("w1", [4, 3], w1, False),
+            tensor("b1", [3], b1, False),
+            tensor("w2", [3, 2], w2, False),
+            tensor("b2", [1, 2], b2, False),
+        ],
+        [value_info("input", [2, 4])],
+        [value_info("output", [2, 2])],
+    )
+    with open(os.path.join(directory, "mlp_matmul_add.onnx"), "wb") as file:
+        file.write(matmul_add)
+
+    input = as_f32([[0.5, -1.0, 0.25, 2.0], [-0.75, 1.5, 1.0, -0.5]])
+    hidden = relu(linear(input, rows(w1, 4, 3), b1))
+    output = linear(hidden, rows(w2, 3, 2), b2)
+    print_expected("mlp_matmul_add.onnx", input, output)
+
+    # Gemm -> Sigmoid, which the importer doesn't support
+    unsupported = model(
+        [
+            node("Gemm", ["input", "w1", "b1"], ["hidden"], [attribute_int("transB", 1)]),
+            node("Sigmoid", ["hidden"], ["output"]),
+        ],
+        [
+            tensor("w1", [3, 4], values(3, 4, 1), True),
+            tensor("b1", [3], values(1, 3, 2), True),
+        ],
+        [value_info("input", ["batch", 4])],
+        [value_info("output", ["batch", 3])],
+    )
+    with open(os.path.join(directory, "unsupported_operator.onnx"), "wb") as file:
+        file.write(unsupported)
+
+
+if __name__ == "__main__":
+    main()
@@ -1,8 +1,10 @@
 use crate::shared::{
     benchmark_plot::draw_benchmark_plot,
+    benchmark_report::save_benchmark_report,
     configuration::Configuration,
     performance_measurement::{benchmark_function_vector, PerformanceMeasurements},
     tensor2d::Tensor2D,
+    tensor2d_parallel::build_thread_pool,
 };
 
 fn naive_linear_benchmark(
@@ -50,6 +52,24 @@ fn optimized_linear_benchmark(
     Tensor2D::linear_optimized(input, weights, bias, output);
 }
 
+fn blocked_linear_benchmark(
+    input: &mut Tensor2D,
+    weights: &Tensor2D,
+    bias: &Tensor2D,
+    output: &mut Tensor2D,
+) {
+    Tensor2D::linear_blocked(input, weights, bias, output);
+}
+
+fn parallel_linear_benchmark(
+    input: &mut Tensor2D,
+    weights: &Tensor2D,
+    bias: &Tensor2D,
+    output: &mut Tensor2D,
+) {
+    Tensor2D::linear_parallel(input, weights, bias, output);
+}
+
 fn linear_benchmark(config: &Configuration) {
     let names: Vec<String> = vec![
         "shared::tensor2d::linear".to_string(),
@@ -57,6 +77,8 @@ fn linear_benchmark(config: &Configuration) {
         "shared::tensor2d::linear_preallocated_inline".to_string(),
         "shared::tensor2d::linear_local_accumulation".to_string(),
         "shared::tensor2d::linear_optimized".to_string(),
+        "shared::tensor2d::linear_blocked".to_string(),
+        "shared::tensor2d::linear_parallel".to_string(),
     ];
 
     let functions: Vec<fn(&mut Tensor2D, &Tensor2D, &Tensor2D, &mut Tensor2D)> = vec![
@@ -65,13 +87,25 @@ fn linear_benchmark(config: &Configuration) {
         inline_linear_benchmark,
         local_accumulation_linear_benchmark,
         optimized_linear_benchmark,
+        blocked_linear_benchmark,
+        parallel_linear_benchmark,
     ];
 
     let mut all_measurements: Vec<PerformanceMeasurements> =
         vec![PerformanceMeasurements::default(); functions.len()];
 
-    benchmark_function_vector(config, names, functions, &mut all_measurements);
+    // The parallel kernels run on config.thread_count threads
+    config
+        .thread_pool()
+        .install(|| benchmark_function_vector(config, names, functions, &mut all_measurements));
 
+    save_benchmark_report(
+        "CPU Benchmark - Linear",
+        "benchmarks/cpu/",
+        "cpu_linear_benchmark.png",
+        &all_measurements,
+        None,
+    );
     draw_benchmark_plot(
         "CPU Benchmark - Linear",
         "benchmarks/cpu/",
@@ -154,12 +188,32 @@ fn relu_inplace_inline_benchmark(
     Tensor2D::relu_inplace_inline(input);
 }
 
+fn relu_parallel_benchmark(
+    input: &mut Tensor2D,
+    _weights: &Tensor2D,
+    _bias: &Tensor2D,
+    output: &mut Tensor2D,
+) {
+    Tensor2D::relu_parallel(input, output);
+}
+
+fn relu_inplace_parallel_benchmark(
+    input: &mut Tensor2D,
+    _weights: &Tensor2D,
+    _bias: &Tensor2D,
+    _output: &mut Tensor2D,
+) {
+    Tensor2D::relu_inplace_parallel(input);
+}
+
 fn relu_benchmark(config: &Configuration) {
     let names: Vec<String> = vec![
         "shared::tensor2d::relu".to_string(),
         "shared::tensor2d::relu_preallocated".to_string(),
         "shared::tensor2d::relu_inplace".to_string(),
         "shared::tensor2d::relu_inplace_inline".to_string(),
+        "shared::tensor2d::relu_parallel".to_string(),
+        "shared::tensor2d::relu_inplace_parallel".to_string(),
     ];
 
     let functions: Vec<fn(&mut Tensor2D, &Tensor2D, &Tensor2D, &mut Tensor2D)> = vec![
@@ -167,13 +221,25 @@ fn relu_benchmark(config: &Configuration) {
         relu_preallocated_benchmark,
         relu_inplace_benchmark,
         relu_inplace_inline_benchmark,
+        relu_parallel_benchmark,
+        relu_inplace_parallel_benchmark,
     ];
 
     let mut all_measurements: Vec<PerformanceMeasurements> =
         vec![PerformanceMeasurements::default(); functions.len()];
 
-    benchmark_function_vector(config, names, functions, &mut all_measurements);
+    // The parallel kernels run on config.thread_count threads
+    config
+        .thread_pool()
+        .install(|| benchmark_function_vector(config, names, functions, &mut all_measurements));
 
+    save_benchmark_report(
+        "CPU Benchmark - ReLu",
+        "benchmarks/cpu/",
+        "cpu_relu_benchmark.png",
+        &all_measurements,
+        None,
+    );
     draw_benchmark_plot(
         "CPU Benchmark - ReLu",
         "benchmarks/cpu/",
@@ -244,12 +310,32 @@ fn softmax_inplace_inline_benchmark(
     Tensor2D::softmax_inplace_inline(input);
 }
 
+fn softmax_parallel_benchmark(
+    input: &mut Tensor2D,
+    _weights: &Tensor2D,
+    _bias: &Tensor2D,
+    output: &mut Tensor2D,
+) {
+    Tensor2D::softmax_parallel(input, output);
+}
+
+fn softmax_inplace_parallel_benchmark(
+    input: &mut Tensor2D,
+    _weights: &Tensor2D,
+    _bias: &Tensor2D,
+    _output: &mut Tensor2D,
+) {
+    Tensor2D::softmax_inplace_parallel(input);
+}
+
 fn softmax_benchmark(config: &Configuration) {
     let names: Vec<String> = vec![
         "shared::tensor2d::softmax".to_string(),
         "shared::tensor2d::softmax_preallocated".to_string(),
         "shared::tensor2d::softmax_inplace".to_string(),
         "shared::tensor2d::softmax_inplace_inline".to_string(),
+        "shared::tensor2d::softmax_parallel".to_string(),
+        "shared::tensor2d::softmax_inplace_parallel".to_string(),
     ];
 
     let functions: Vec<fn(&mut Tensor2D, &Tensor2D, &Tensor2D, &mut Tensor2D)> = vec![
@@ -257,13 +343,25 @@ fn softmax_benchmark(config: &Configuration) {
         softmax_preallocated_benchmark,
         softmax_inplace_benchmark,
         softmax_inplace_inline_benchmark,
+        softmax_parallel_benchmark,
+        softmax_inplace_parallel_benchmark,
     ];
 
     let mut all_measurements: Vec<PerformanceMeasurements> =
         vec![PerformanceMeasurements::default(); functions.len()];
 
-    benchmark_function_vector(config, names, functions, &mut all_measurements);
+    // The parallel kernels run on config.thread_count threads
+    config
+        .thread_pool()
+        .install(|| benchmark_function_vector(config, names, functions, &mut all_measurements));
 
+    save_benchmark_report(
+        "CPU Benchmark - Softmax",
+        "benchmarks/cpu/",
+        "cpu_softmax_benchmark.png",
+        &all_measurements,
+        None,
+    );
     draw_benchmark_plot(
         "CPU Benchmark - Softmax",
         "benchmarks/cpu/",
@@ -357,6 +455,15 @@ fn linear_optimized_relu_benchmark(
     Tensor2D::linear_optimized_relu(input, weights, bias, output);
 }
 
+fn linear_relu_softmax_fused_parallel_benchmark(
+    input: &mut Tensor2D,
+    weights: &Tensor2D,
+    bias: &Tensor2D,
+    output: &mut Tensor2D,
+) {
+    Tensor2D::linear_relu_softmax_fused_parallel(input, weights, bias, output);
+}
+
 fn linear_relu_softmax_benchmark(config: &Configuration) {
     let names: Vec<String> = vec![
         "shared::tensor2d::linear_relu_softmax".to_string(),
@@ -365,6 +472,7 @@ fn linear_relu_softmax_benchmark(config: &Configuration) {
         "shared::tensor2d::linear_relu_softmax_fused".to_string(),
         "shared::tensor2d::linear_local_accumulation_relu".to_string(),
         "shared::tensor2d::linear_optimized_relu".to_string(),
+        "shared::tensor2d::linear_relu_softmax_fused_parallel".to_string(),
     ];
     let functions: Vec<fn(&mut Tensor2D, &Tensor2D, &Tensor2D, &mut Tensor2D)> = vec![
         linear_relu_softmax_naive_benchmark,
@@ -373,12 +481,23 @@ fn linear_relu_softmax_benchmark(config: &Configuration) {
         linear_relu_softmax_fused_benchmark,
         linear_local_accumulation_relu_benchmark,
         linear_optimized_relu_benchmark,
+        linear_relu_softmax_fused_parallel_benchmark,
     ];
     let mut all_measurements: Vec<PerformanceMeasurements> =
         vec![PerformanceMeasurements::default(); functions.len()];
 
-    benchmark_function_vector(config, names, functions, &mut all_measurements);
+    // The parallel kernels run on config.thread_count threads
+    config
+        .thread_pool()
+        .install(|| benchmark_function_vector(config, names, functions, &mut all_measurements));
 
+    save_benchmark_report(
+        "CPU Benchmark - Fused Linear/ReLu/Softmax",
+        "benchmarks/cpu/",
+        "cpu_linear_relu_softmax_fused_benchmark.png",
+        &all_measurements,
+        None,
+    );
     draw_benchmark_plot(
         "CPU Benchmark - Fused Linear/ReLu/Softmax",
         "benchmarks/cpu/",
@@ -431,9 +550,106 @@ fn linear_relu_softmax_fused(config: &Configuration) {
     }
 }
 
+// Measures a parallel kernel with every thread count in config.thread_count_range,
+// with the single-threaded kernel as the baseline, giving one curve per thread count.
+fn thread_scaling_benchmark(
+    config: &Configuration,
+    kernel_name: &str,
+    file_name: &str,
+    single_threaded: fn(&mut Tensor2D, &Tensor2D, &Tensor2D, &mut Tensor2D),
+    parallel: fn(&mut Tensor2D, &Tensor2D, &Tensor2D, &mut Tensor2D),
+) {
+    let mut all_measurements: Vec<PerformanceMeasurements> =
+        vec![PerformanceMeasurements::default(); 1];
+    benchmark_function_vector(
+        config,
+        vec![format!("{} - single-threaded", kernel_name)],
+        vec![single_threaded],
+        &mut all_measurements,
+    );
+
+    for thread_count in &config.thread_count_range {
+        let mut measurements: Vec<PerformanceMeasurements> =
+            vec![PerformanceMeasurements::default(); 1];
+        build_thread_pool(*thread_count).install(|| {
+            benchmark_function_vector(
+                config,
+                vec![format!("{} - {} threads", kernel_name, thread_count)],
+                vec![parallel],
+                &mut measurements,
+            )
+        });
+        all_measurements.append(&mut measurements);
+    }
+
+    // Speedup over the single-threaded kernel at the largest size
+    if 1 < config.debug_level {
+        let baseline: f32 = *all_measurements[0]
+            .normalized_times
+            .last()
+            .expect("Thread scaling benchmark has no measurements");
+        for measurement in &all_measurements[1..] {
+            let time: f32 = *measurement.normalized_times.last().unwrap();
+            println!("{}: {:.2}x speedup", measurement.name, baseline / time);
+        }
+    }
+
+    let chart_name: String = format!("CPU Benchmark - {} Thread Scaling", kernel_name);
+    save_benchmark_report(
+        &chart_name,
+        "benchmarks/cpu/",
+        file_name,
+        &all_measurements,
+        None,
+    );
+    draw_benchmark_plot(
+        &chart_name,
+        "benchmarks/cpu/",
+        file_name,
+        all_measurements,
+        config.log_scale,
+    );
+}
+
+fn thread_scaling(config: &Configuration) {
+    if !config.run_performance_benchmark || config.thread_count_range.is_empty() {
+        return;
+    }
+
+    thread_scaling_benchmark(
+        config,
+        "Linear",
+        "cpu_linear_thread_scaling_benchmark.png",
+        optimized_linear_benchmark,
+        parallel_linear_benchmark,
+    );
+    thread_scaling_benchmark(
+        config,
+        "ReLU",
+        "cpu_relu_thread_scaling_benchmark.png",
+        relu_preallocated_benchmark,
+        relu_parallel_benchmark,
+    );
+    thread_scaling_benchmark(
+        config,
+        "Softmax",
+        "cpu_softmax_thread_scaling_benchmark.png",
+        softmax_preallocated_benchmark,
+        softmax_parallel_benchmark,
+    );
+    thread_scaling_benchmark(
+        config,
+        "Fused Linear/ReLU/Softmax",
+        "cpu_linear_relu_softmax_thread_scaling_benchmark.png",
+        linear_relu_softmax_fused_benchmark,
+        linear_relu_softmax_fused_parallel_benchmark,
+    );
+}
+
 pub fn execute(config: &Configuration) {
     linear(config);
     relu(config);
     softmax(config);
     linear_relu_softmax_fused(config);
+    thread_scaling(config);
 }
@@ -0,0 +1,195 @@
+use crate::shared::{
+    configuration::Configuration,
+    gpu_utilities::{initialize_gpu_with, GPUHandles},
+    graph_operators::GraphOperator,
+    tensor2d::Tensor2D,
+    tensor2d_axis::Axis,
+};
+
+use super::{graph_error::GraphError, graph_runner::GraphRunner, graph_runner_gpu::GraphRunnerGPU};
+
+// Named like GPUHandles and GraphRunnerGPU elsewhere in the crate
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Backend {
+    CPU,
+    GPU,
+}
+
+impl Backend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::CPU => "CPU",
+            Backend::GPU => "GPU",
+        }
+    }
+}
+
+// Runs graphs on the GPU if there is a usable one, and on the CPU otherwise,
+// so the same code runs on machines without a GPU.
+// With a cross-check every GPU output is compared to the output of the unfused CPU runner,
+// there is nothing to compare against when running on the CPU.
+pub struct Executor {
+    gpu_handles: Option<GPUHandles>,
+    fuse_operators: bool,
+    cross_check_tolerance: Option<f32>,
+}
+
+impl Executor {
+    // Only looks for a GPU if the self test found a compatible one,
+    // which is a software adapter if config.software_gpu is set
+    pub async fn new(config: &Configuration) -> Self {
+        let gpu_handles: Option<GPUHandles> = if config.compatible_gpu_found {
+            initialize_gpu_with(config.warmup_gpu, config.software_gpu).await
+        } else {
+            None
+        };
+
+        Self::build(gpu_handles)
+    }
+
+    pub fn cpu() -> Self {
+        Self::build(None)
+    }
+
+    pub fn gpu(gpu_handles: GPUHandles) -> Self {
+        Self::build(Some(gpu_handles))
+    }
+
+    fn build(gpu_handles: Option<GPUHandles>) -> Self {
+        Executor {
+            gpu_handles,
+            fuse_operators: true,
+            cross_check_tolerance: None,
+        }
+    }
+
+    pub fn with_fusion(mut self, fuse_operators: bool) -> Self {
+        self.fuse_operators = fuse_operators;
+        self
+    }
+
+    pub fn with_cross_check(mut self, tolerance: f32) -> Self {
+        self.cross_check_tolerance = Some(tolerance);
+        self
+    }
+
+    pub fn backend(&self) -> Backend {
+        match self.gpu_handles {
+            Some(_) => Backend::GPU,
+            None => Backend::CPU,
+        }
+    }
+
+    pub fn gpu_handles(&self) -> Option<&GPUHandles> {
+        self.gpu_handles.as_ref()
+    }
+
+    // Returns the output of the last DeviceToHost node in the graph.
+    pub fn run(&self, graph_operators: &[GraphOperator]) -> Tensor2D {
+        self.try_run(graph_operators).unwrap_or_else(|error| {
+            panic!(
+                "Failed to run a computational graph on the {}! {}",
+                self.backend().name(),
+                error
+            )
+        })
+    }
+
+    pub fn try_run(&self, graph_operators: &[GraphOperator]) -> Result<Tensor2D, GraphError> {
+        let gpu_handles: &GPUHandles = match &self.gpu_handles {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                return GraphRunner::try_new(graph_operators, self.fuse_operators)?.try_run();
+            }
+        };
+
+        let mut graph_runner: GraphRunnerGPU =
+            GraphRunnerGPU::try_new(gpu_handles, graph_operators, self.fuse_operators, true)?;
+        let output: Tensor2D = pollster::block_on(graph_runner.try_run(gpu_handles, 1))?;
+
+        if let Some(tolerance) = self.cross_check_tolerance {
+            let reference: Tensor2D = GraphRunner::try_new(graph_operators, false)?.try_run()?;
+            cross_check(&reference, &output, tolerance)?;
+        }
+
+        Ok(output)
+    }
+}
+
+// Compares the elements of two outputs. Only the number of elements has to match,
+// as the GPU flattens the output of a global Softmax.
+// A NaN matches another NaN, but nothing else.
+pub fn cross_check(
+    reference: &Tensor2D,
+    output: &Tensor2D,
+    tolerance: f32,
+) -> Result<(), GraphError> {
+    if reference.len() != output.len() {
+        return Err(GraphError::CrossCheck {
+            max_difference: f32::INFINITY,
+            tolerance,
+        });
+    }
+
+    let mut max_difference: f32 = 0.0;
+    for (reference, output) in reference.data.iter().zip(&output.data) {
+        let difference: f32 = if reference.is_nan() || output.is_nan() {
+            if reference.is_nan() && output.is_nan() {
+                0.0
+            } else {
+                f32::INFINITY
+            }
+        } else {
+            (reference - output).abs()
+        };
+        max_difference = max_difference.max(difference);
+    }
+
+    if tolerance < max_difference {
+        return Err(GraphError::CrossCheck {
+            max_difference,
+            tolerance,
+        });
+    }
+
+    Ok(())
+}
+
+// Runs a small network on whichever backend the executor picked
+pub fn execute(executor: &Executor, config: &Configuration) {
+    let graph_operators: Vec<GraphOperator> = vec![
+        GraphOperator::HostToDevice {
+            input: Tensor2D::new(0.1, 10, 10),
+        },
+        GraphOperator::Linear {
+            weights: Tensor2D::new(0.02, 10, 10),
+            bias: Tensor2D::new(0.01, 10, 10),
+        },
+        GraphOperator::ReLU,
+        GraphOperator::Linear {
+            weights: Tensor2D::new(0.04, 10, 10),
+            bias: Tensor2D::new(0.02, 10, 10),
+        },
+        GraphOperator::ReLU,
+        GraphOperator::Softmax { axis: Axis::Rows },
+        GraphOperator::DeviceToHost,
+    ];
+
+    match executor.try_run(&graph_operators) {
+        Ok(output) => {
+            if 1 < config.debug_level {
+                println!(
+                    "executor output on the {}: {:?}",
+                    executor.backend().name(),
+                    output
+                );
+            }
+        }
+        Err(error) => println!(
+            "Failed to run a graph on the {}! {}",
+            executor.backend().name(),
+            error
+        ),
+    }
+}
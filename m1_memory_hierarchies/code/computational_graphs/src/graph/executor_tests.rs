@@ -0,0 +1,134 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        graph::{
+            executor::{cross_check, Backend, Executor},
+            graph_error::GraphError,
+            graph_runner::GraphRunner,
+        },
+        shared::{
+            configuration::Configuration, graph_operators::GraphOperator, tensor2d::Tensor2D,
+            tensor2d_axis::Axis,
+        },
+    };
+
+    fn graph() -> Vec<GraphOperator> {
+        vec![
+            GraphOperator::HostToDevice {
+                input: Tensor2D::new(0.1, 4, 6),
+            },
+            GraphOperator::Linear {
+                weights: Tensor2D::new(0.2, 6, 5),
+                bias: Tensor2D::new(0.05, 4, 5),
+            },
+            GraphOperator::ReLU,
+            GraphOperator::Softmax { axis: Axis::Rows },
+            GraphOperator::DeviceToHost,
+        ]
+    }
+
+    // Without a compatible GPU the executor doesn't look for one
+    #[test]
+    fn new_without_gpu() {
+        let config: Configuration = Configuration::build(0, false, 1, vec![1], false);
+        let executor: Executor = pollster::block_on(Executor::new(&config));
+        assert_eq!(executor.backend(), Backend::CPU);
+        assert!(executor.gpu_handles().is_none());
+    }
+
+    #[test]
+    fn cpu() {
+        let expected: Tensor2D = GraphRunner::new(&graph(), false).run();
+        for fuse_operators in [false, true] {
+            // Cross-checking the CPU has nothing to compare against
+            let executor: Executor = Executor::cpu()
+                .with_fusion(fuse_operators)
+                .with_cross_check(0.0);
+            assert_eq!(executor.backend(), Backend::CPU);
+
+            let output: Tensor2D = executor.run(&graph());
+            assert_eq!(
+                (output.row_count, output.column_count),
+                (expected.row_count, expected.column_count)
+            );
+            for (expected, output) in expected.data.iter().zip(&output.data) {
+                assert!((expected - output).abs() < 0.00001);
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_graph() {
+        assert_eq!(
+            Executor::cpu().try_run(&graph()[1..]).err(),
+            Some(GraphError::MissingTransfer {
+                operator: "HostToDevice"
+            })
+        );
+    }
+
+    #[test]
+    fn cross_check_tolerance() {
+        let reference: Tensor2D = Tensor2D::new(1.0, 2, 3);
+        let mut output: Tensor2D = reference.clone();
+        assert_eq!(cross_check(&reference, &output, 0.0), Ok(()));
+
+        output.data[4] += 0.5;
+        assert_eq!(cross_check(&reference, &output, 0.5), Ok(()));
+        assert_eq!(
+            cross_check(&reference, &output, 0.25),
+            Err(GraphError::CrossCheck {
+                max_difference: 0.5,
+                tolerance: 0.25
+            })
+        );
+    }
+
+    // Only the elements are compared, as the GPU flattens a global Softmax
+    #[test]
+    fn cross_check_shapes() {
+        let reference: Tensor2D = Tensor2D::new(1.0, 2, 3);
+        let flattened: Tensor2D = Tensor2D::new(1.0, 6, 1);
+        assert_eq!(cross_check(&reference, &flattened, 0.0), Ok(()));
+
+        let shorter: Tensor2D = Tensor2D::new(1.0, 5, 1);
+        assert_eq!(
+            cross_check(&reference, &shorter, 0.0),
+            Err(GraphError::CrossCheck {
+                max_difference: f32::INFINITY,
+                tolerance: 0.0
+            })
+        );
+    }
+
+    #[test]
+    fn cross_check_nan() {
+        let mut reference: Tensor2D = Tensor2D::new(1.0, 1, 3);
+        reference.data[1] = f32::NAN;
+        let mut output: Tensor2D = reference.clone();
+        assert_eq!(cross_check(&reference, &output, 0.0), Ok(()));
+
+        output.data[1] = 1.0;
+        assert!(matches!(
+            cross_check(&reference, &output, 1000.0),
+            Err(GraphError::CrossCheck { .. })
+        ));
+        assert!(matches!(
+            cross_check(&output, &reference, 1000.0),
+            Err(GraphError::CrossCheck { .. })
+        ));
+    }
+
+    #[test]
+    fn error_message() {
+        let error: GraphError = GraphError::CrossCheck {
+            max_difference: 0.5,
+            tolerance: 0.25,
+        };
+        assert_eq!(
+            error.to_string(),
+            "The GPU and CPU outputs differ by up to 0.5, more than the tolerance of 0.25"
+        );
+        assert_eq!(error.index(), None);
+    }
+}
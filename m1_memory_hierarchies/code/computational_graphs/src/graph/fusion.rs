@@ -0,0 +1,414 @@
+use crate::shared::{
+    graph_operators::{GraphNode, GraphOperator, NodeId},
+    tensor2d::Tensor2D,
+    tensor2d_axis::{Axis, Reduction},
+};
+
+use super::graph_validation::propagate_shapes;
+
+// Operators which only ever look at one element at a time.
+// Once a producer, such as a linear layer, has computed an element we can
+// apply any number of these to it while it is still in a register, instead
+// of writing it to memory and reading it back in for every operator.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ElementwiseOperator {
+    ReLU,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl ElementwiseOperator {
+    pub fn from_graph_operator(operator: &GraphOperator) -> Option<Self> {
+        match operator {
+            GraphOperator::ReLU => Some(ElementwiseOperator::ReLU),
+            GraphOperator::Add => Some(ElementwiseOperator::Add),
+            GraphOperator::Subtract => Some(ElementwiseOperator::Subtract),
+            GraphOperator::Multiply => Some(ElementwiseOperator::Multiply),
+            GraphOperator::Divide => Some(ElementwiseOperator::Divide),
+            _ => None,
+        }
+    }
+
+    pub fn is_binary(&self) -> bool {
+        !matches!(self, ElementwiseOperator::ReLU)
+    }
+
+    // ReLU only uses the left value
+    pub fn apply(&self, left: f32, right: f32) -> f32 {
+        match self {
+            ElementwiseOperator::ReLU => left.max(0.0),
+            ElementwiseOperator::Add => left + right,
+            ElementwiseOperator::Subtract => left - right,
+            ElementwiseOperator::Multiply => left * right,
+            ElementwiseOperator::Divide => left / right,
+        }
+    }
+
+    // The partial derivatives with respect to left and right
+    pub fn derivatives(&self, left: f32, right: f32) -> (f32, f32) {
+        match self {
+            ElementwiseOperator::ReLU => (if 0.0 < left { 1.0 } else { 0.0 }, 0.0),
+            ElementwiseOperator::Add => (1.0, 1.0),
+            ElementwiseOperator::Subtract => (1.0, -1.0),
+            ElementwiseOperator::Multiply => (right, left),
+            ElementwiseOperator::Divide => (1.0 / right, -left / (right * right)),
+        }
+    }
+}
+
+// Operators which look at a whole segment of their input, such as a row, at a time.
+// Every element they output is still computed on its own, so the elementwise
+// operators following them can be applied before it is written to memory.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AxisOperator {
+    Softmax,
+    LogSoftmax,
+    Sum,
+    Max,
+    Mean,
+    ArgMax,
+}
+
+impl AxisOperator {
+    // Softmax over the whole tensor has its own multi-pass kernels, so it isn't included
+    pub fn from_graph_operator(operator: &GraphOperator) -> Option<(Self, Axis)> {
+        match operator {
+            GraphOperator::Softmax { axis } if *axis != Axis::Global => {
+                Some((AxisOperator::Softmax, *axis))
+            }
+            GraphOperator::LogSoftmax { axis } => Some((AxisOperator::LogSoftmax, *axis)),
+            GraphOperator::Sum { axis } => Some((AxisOperator::Sum, *axis)),
+            GraphOperator::Max { axis } => Some((AxisOperator::Max, *axis)),
+            GraphOperator::Mean { axis } => Some((AxisOperator::Mean, *axis)),
+            GraphOperator::ArgMax { axis } => Some((AxisOperator::ArgMax, *axis)),
+            _ => None,
+        }
+    }
+
+    pub fn reduction(&self) -> Option<Reduction> {
+        match self {
+            AxisOperator::Softmax | AxisOperator::LogSoftmax => None,
+            AxisOperator::Sum => Some(Reduction::Sum),
+            AxisOperator::Max => Some(Reduction::Max),
+            AxisOperator::Mean => Some(Reduction::Mean),
+            AxisOperator::ArgMax => Some(Reduction::ArgMax),
+        }
+    }
+
+    // The reductions keep one value per segment
+    pub fn output_shape(&self, input_shape: (usize, usize), axis: Axis) -> (usize, usize) {
+        match self.reduction() {
+            Some(_) => axis.reduced_shape(input_shape),
+            None => input_shape,
+        }
+    }
+}
+
+// Where a fused kernel gets the value it applies its steps to.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FusionBase {
+    // A linear layer reading input, weights and bias
+    Linear,
+    // A single input, which can be broadcast to the output
+    Load,
+    // An axis operator reading a single input. The kernel is the same for every axis,
+    // which only changes the layout of the segments.
+    Axis { operator: AxisOperator, axis: Axis },
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct FusedStep {
+    pub operator: ElementwiseOperator,
+    // Binary operators read their other operand from the next operand buffer.
+    // The value computed so far can be on either side, a - value is not value - a.
+    pub value_is_left: bool,
+}
+
+impl FusedStep {
+    pub fn apply(&self, value: f32, operand: f32) -> f32 {
+        if self.value_is_left {
+            self.operator.apply(value, operand)
+        } else {
+            self.operator.apply(operand, value)
+        }
+    }
+
+    // The partial derivatives with respect to the value and the operand
+    pub fn derivatives(&self, value: f32, operand: f32) -> (f32, f32) {
+        if self.value_is_left {
+            self.operator.derivatives(value, operand)
+        } else {
+            let (operand_derivative, value_derivative): (f32, f32) =
+                self.operator.derivatives(operand, value);
+            (value_derivative, operand_derivative)
+        }
+    }
+}
+
+// Describes a fused kernel. The node running it has the buffers
+// [base buffers..., one operand per binary step..., output]
+// where the base buffers are input, weights and bias for Linear and just input otherwise.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct FusedElementwise {
+    pub base: FusionBase,
+    pub steps: Vec<FusedStep>,
+}
+
+impl FusedElementwise {
+    pub fn base_buffer_count(&self) -> usize {
+        match self.base {
+            FusionBase::Linear => 3,
+            FusionBase::Load | FusionBase::Axis { .. } => 1,
+        }
+    }
+
+    // The shape of the value the steps start from, from the shapes of the base buffers
+    pub fn base_shape(&self, base_shapes: &[(usize, usize)]) -> (usize, usize) {
+        match self.base {
+            // The bias has the shape of the output of the linear layer
+            FusionBase::Linear => base_shapes[2],
+            FusionBase::Load => base_shapes[0],
+            FusionBase::Axis { operator, axis } => operator.output_shape(base_shapes[0], axis),
+        }
+    }
+
+    pub fn operand_count(&self) -> usize {
+        self.steps
+            .iter()
+            .filter(|step| step.operator.is_binary())
+            .count()
+    }
+
+    pub fn buffer_count(&self) -> usize {
+        self.base_buffer_count() + self.operand_count() + 1
+    }
+
+    // This is the same as the LinearReLU operator we already have a hand written kernel for
+    pub fn is_linear_relu(&self) -> bool {
+        self.base == FusionBase::Linear
+            && self.steps.len() == 1
+            && self.steps[0].operator == ElementwiseOperator::ReLU
+    }
+
+    // Unique for every fused kernel, used for caching compiled shaders
+    pub fn key(&self) -> String {
+        let mut key: String = match self.base {
+            FusionBase::Axis { operator, .. } => format!("FusedAxis{:?}", operator),
+            base => format!("Fused{:?}", base),
+        };
+        for step in &self.steps {
+            key.push('_');
+            if !step.value_is_left {
+                key.push_str("Reversed");
+            }
+            key.push_str(&format!("{:?}", step.operator));
+        }
+        key
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FusionPattern {
+    // Linear -> ReLU -> Softmax, which has its own hand written kernels
+    LinearReLUSoftmax,
+    // A producer followed by a chain of elementwise operators
+    Elementwise(FusedElementwise),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FusionGroup {
+    pub pattern: FusionPattern,
+    // The graph node which starts the group. The base reads from its first input
+    pub producer: NodeId,
+    // The graph nodes the binary steps read their other operand from
+    pub operands: Vec<NodeId>,
+    // The graph node whose output the group writes.
+    // The group runs in its place in the topological order, by which point
+    // every operand has been computed.
+    pub last_node: NodeId,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FusionPlan {
+    // The group run in place of each graph node, if any, indexed by its last node
+    pub groups: Vec<Option<FusionGroup>>,
+    // Graph nodes which are computed as part of a group run in place of a later node
+    pub fused_nodes: Vec<bool>,
+}
+
+impl FusionPlan {
+    // Runs every graph node on its own
+    pub fn unfused(node_count: usize) -> Self {
+        FusionPlan {
+            groups: vec![None; node_count],
+            fused_nodes: vec![false; node_count],
+        }
+    }
+}
+
+// Fusion is only legal if nothing else needs the intermediate result.
+fn get_sole_consumer(consumers: &[Vec<NodeId>], node_id: NodeId) -> Option<NodeId> {
+    if consumers[node_id].len() == 1 {
+        Some(consumers[node_id][0])
+    } else {
+        None
+    }
+}
+
+fn find_linear_relu_softmax(
+    graph_nodes: &[GraphNode],
+    consumers: &[Vec<NodeId>],
+    producer: NodeId,
+) -> Option<(NodeId, NodeId)> {
+    if !matches!(graph_nodes[producer].operator, GraphOperator::Linear { .. }) {
+        return None;
+    }
+
+    let relu_id: NodeId = get_sole_consumer(consumers, producer)?;
+    if !matches!(graph_nodes[relu_id].operator, GraphOperator::ReLU) {
+        return None;
+    }
+
+    let softmax_id: NodeId = get_sole_consumer(consumers, relu_id)?;
+    // The fused kernels normalize the whole output
+    if !matches!(
+        graph_nodes[softmax_id].operator,
+        GraphOperator::Softmax { axis: Axis::Global }
+    ) {
+        return None;
+    }
+
+    Some((relu_id, softmax_id))
+}
+
+// The graph nodes we can fuse elementwise operators into, and the steps they start with
+fn producer_steps(operator: &GraphOperator) -> Option<(FusionBase, Vec<FusedStep>)> {
+    if let Some((operator, axis)) = AxisOperator::from_graph_operator(operator) {
+        return Some((FusionBase::Axis { operator, axis }, vec![]));
+    }
+
+    match operator {
+        GraphOperator::Linear { .. } => Some((FusionBase::Linear, vec![])),
+        GraphOperator::LinearReLUFused { .. } => Some((
+            FusionBase::Linear,
+            vec![FusedStep {
+                operator: ElementwiseOperator::ReLU,
+                value_is_left: true,
+            }],
+        )),
+        _ => ElementwiseOperator::from_graph_operator(operator).map(|operator| {
+            (
+                FusionBase::Load,
+                vec![FusedStep {
+                    operator,
+                    value_is_left: true,
+                }],
+            )
+        }),
+    }
+}
+
+// Goes through the graph in topological order, and greedily grows a group from every
+// node which isn't already part of one. Patterns are tried in order, first the
+// hand written Linear -> ReLU -> Softmax, then a producer, which is a linear layer,
+// an axis operator or an elementwise operator, followed by as many elementwise
+// operators as possible.
+//
+// An elementwise operator is added to the group if
+// - it is the only consumer of the value computed so far
+// - it doesn't change the shape of the value, although the operand can be broadcast
+pub fn plan_fusion(
+    graph_nodes: &[GraphNode],
+    order: &[NodeId],
+    consumers: &[Vec<NodeId>],
+) -> FusionPlan {
+    let shapes: Vec<(usize, usize)> = propagate_shapes(graph_nodes, order)
+        .expect("Failed to find the shapes of the graph in fusion::plan_fusion");
+
+    let mut plan: FusionPlan = FusionPlan::unfused(graph_nodes.len());
+    // Graph nodes which are already part of a group
+    let mut grouped: Vec<bool> = vec![false; graph_nodes.len()];
+    for producer in order {
+        let producer: NodeId = *producer;
+        if grouped[producer] {
+            continue;
+        }
+
+        if let Some((relu_id, softmax_id)) =
+            find_linear_relu_softmax(graph_nodes, consumers, producer)
+        {
+            plan.fused_nodes[producer] = true;
+            plan.fused_nodes[relu_id] = true;
+            grouped[relu_id] = true;
+            grouped[softmax_id] = true;
+            plan.groups[softmax_id] = Some(FusionGroup {
+                pattern: FusionPattern::LinearReLUSoftmax,
+                producer,
+                operands: vec![],
+                last_node: softmax_id,
+            });
+            continue;
+        }
+
+        let (base, mut steps): (FusionBase, Vec<FusedStep>) =
+            match producer_steps(&graph_nodes[producer].operator) {
+                Some(producer_steps) => producer_steps,
+                None => continue,
+            };
+        let mut operands: Vec<NodeId> = graph_nodes[producer].inputs[1..].to_vec();
+
+        let mut tail: NodeId = producer;
+        while let Some(consumer) = get_sole_consumer(consumers, tail) {
+            let consumer_node: &GraphNode = &graph_nodes[consumer];
+            let operator: ElementwiseOperator =
+                match ElementwiseOperator::from_graph_operator(&consumer_node.operator) {
+                    Some(operator) => operator,
+                    None => break,
+                };
+            if grouped[consumer] {
+                break;
+            }
+
+            let value_is_left: bool = consumer_node.inputs[0] == tail;
+            if operator.is_binary() {
+                let operand: NodeId = if value_is_left {
+                    consumer_node.inputs[1]
+                } else {
+                    consumer_node.inputs[0]
+                };
+                if operand == tail
+                    || Tensor2D::broadcast_shape(shapes[tail], shapes[operand])
+                        != Some(shapes[tail])
+                {
+                    break;
+                }
+                operands.push(operand);
+            }
+
+            steps.push(FusedStep {
+                operator,
+                value_is_left,
+            });
+            grouped[consumer] = true;
+            tail = consumer;
+        }
+
+        if tail != producer {
+            let mut node_id: NodeId = producer;
+            while node_id != tail {
+                plan.fused_nodes[node_id] = true;
+                node_id = consumers[node_id][0];
+            }
+            plan.groups[tail] = Some(FusionGroup {
+                pattern: FusionPattern::Elementwise(FusedElementwise { base, steps }),
+                producer,
+                operands,
+                last_node: tail,
+            });
+        }
+    }
+
+    plan
+}
@@ -0,0 +1,510 @@
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::{
+        graph::{
+            fusion::{
+                plan_fusion, AxisOperator, ElementwiseOperator, FusedElementwise, FusedStep,
+                FusionBase, FusionPattern, FusionPlan,
+            },
+            graph_runner::GraphRunner,
+            graph_validation::{get_consumers, topological_sort},
+        },
+        shared::{
+            graph_operators::{GraphNode, GraphOperator, NodeId},
+            tensor2d::Tensor2D,
+            tensor2d_axis::Axis,
+        },
+    };
+
+    const ERROR_TOLERANCE: f32 = 0.0001;
+
+    fn random_tensor(rng: &mut ChaCha8Rng, row_count: usize, column_count: usize) -> Tensor2D {
+        let mut tensor: Tensor2D = Tensor2D::new(0.0, row_count, column_count);
+        for element in &mut tensor.data {
+            *element = rng.gen_range(-1.0..1.0);
+        }
+        tensor
+    }
+
+    // Keeps divisors away from zero
+    fn random_divisor(rng: &mut ChaCha8Rng, row_count: usize, column_count: usize) -> Tensor2D {
+        let mut tensor: Tensor2D = Tensor2D::new(0.0, row_count, column_count);
+        for element in &mut tensor.data {
+            *element = rng.gen_range(0.5..2.0);
+        }
+        tensor
+    }
+
+    fn input_node(rng: &mut ChaCha8Rng, row_count: usize, column_count: usize) -> GraphNode {
+        GraphNode::new(
+            GraphOperator::HostToDevice {
+                input: random_tensor(rng, row_count, column_count),
+            },
+            vec![],
+        )
+    }
+
+    fn linear_node(rng: &mut ChaCha8Rng, size: usize, input: NodeId) -> GraphNode {
+        GraphNode::new(
+            GraphOperator::Linear {
+                weights: random_tensor(rng, size, size),
+                bias: random_tensor(rng, size, size),
+            },
+            vec![input],
+        )
+    }
+
+    fn assert_tensors_match(expected: &Tensor2D, actual: &Tensor2D) {
+        assert_eq!(expected.row_count, actual.row_count);
+        assert_eq!(expected.column_count, actual.column_count);
+        for (expected, actual) in expected.data.iter().zip(actual.data.iter()) {
+            assert!((expected - actual).abs() < ERROR_TOLERANCE);
+        }
+    }
+
+    fn get_plan(graph_nodes: &[GraphNode]) -> FusionPlan {
+        let order: Vec<NodeId> = topological_sort(graph_nodes).unwrap();
+        let consumers: Vec<Vec<NodeId>> = get_consumers(graph_nodes);
+        plan_fusion(graph_nodes, &order, &consumers)
+    }
+
+    fn step(operator: ElementwiseOperator, value_is_left: bool) -> FusedStep {
+        FusedStep {
+            operator,
+            value_is_left,
+        }
+    }
+
+    // input -> linear -> relu -> (operand - value) -> value * operand -> output
+    fn chain_graph(rng: &mut ChaCha8Rng, size: usize) -> Vec<GraphNode> {
+        let mut graph_nodes: Vec<GraphNode> = vec![input_node(rng, size, size)];
+        graph_nodes.push(linear_node(rng, size, 0));
+        graph_nodes.push(GraphNode::new(GraphOperator::ReLU, vec![1]));
+        graph_nodes.push(input_node(rng, size, size));
+        graph_nodes.push(GraphNode::new(GraphOperator::Subtract, vec![3, 2]));
+        graph_nodes.push(input_node(rng, 1, size));
+        graph_nodes.push(GraphNode::new(GraphOperator::Multiply, vec![4, 5]));
+        graph_nodes.push(GraphNode::new(GraphOperator::DeviceToHost, vec![6]));
+        graph_nodes
+    }
+
+    #[test]
+    fn plan_chain() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(1);
+        let graph_nodes: Vec<GraphNode> = chain_graph(&mut rng, 4);
+
+        let plan: FusionPlan = get_plan(&graph_nodes);
+        let group = plan.groups[6]
+            .as_ref()
+            .expect("The multiply should end a group");
+        assert_eq!(group.producer, 1);
+        assert_eq!(group.last_node, 6);
+        assert_eq!(group.operands, vec![3, 5]);
+        assert_eq!(
+            group.pattern,
+            FusionPattern::Elementwise(FusedElementwise {
+                base: FusionBase::Linear,
+                steps: vec![
+                    step(ElementwiseOperator::ReLU, true),
+                    step(ElementwiseOperator::Subtract, false),
+                    step(ElementwiseOperator::Multiply, true),
+                ],
+            })
+        );
+
+        assert_eq!(
+            plan.fused_nodes,
+            vec![false, true, true, false, true, false, false, false]
+        );
+        assert_eq!(
+            plan.groups.iter().filter(|group| group.is_some()).count(),
+            1
+        );
+    }
+
+    #[test]
+    fn fused_elementwise_key() {
+        let fusion: FusedElementwise = FusedElementwise {
+            base: FusionBase::Linear,
+            steps: vec![
+                step(ElementwiseOperator::ReLU, true),
+                step(ElementwiseOperator::Subtract, false),
+            ],
+        };
+        assert_eq!(fusion.key(), "FusedLinear_ReLU_ReversedSubtract");
+        assert_eq!(fusion.operand_count(), 1);
+        assert_eq!(fusion.buffer_count(), 5);
+        assert!(!fusion.is_linear_relu());
+    }
+
+    // The result of the ReLU is needed by two nodes, so it has to be written to memory
+    #[test]
+    fn plan_branch_stops_fusion() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(2);
+        let graph_nodes: Vec<GraphNode> = vec![
+            input_node(&mut rng, 3, 3),
+            linear_node(&mut rng, 3, 0),
+            GraphNode::new(GraphOperator::ReLU, vec![1]),
+            GraphNode::new(GraphOperator::Softmax { axis: Axis::Global }, vec![2]),
+            GraphNode::new(GraphOperator::Add, vec![2, 3]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![4]),
+        ];
+
+        let plan: FusionPlan = get_plan(&graph_nodes);
+        let group = plan.groups[2].as_ref().unwrap();
+        assert_eq!(group.producer, 1);
+        assert!(plan.groups[4].is_none());
+        assert!(!plan.fused_nodes[2]);
+        assert!(!plan.fused_nodes[3]);
+        assert!(!plan.fused_nodes[4]);
+    }
+
+    // Operands which are declared after the producer are fine, as the group runs in
+    // place of its last node, but x * x needs the value in memory twice
+    #[test]
+    fn plan_operands() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(3);
+        let graph_nodes: Vec<GraphNode> = vec![
+            input_node(&mut rng, 3, 3),
+            linear_node(&mut rng, 3, 0),
+            input_node(&mut rng, 3, 3),
+            GraphNode::new(GraphOperator::Softmax { axis: Axis::Global }, vec![2]),
+            GraphNode::new(GraphOperator::Add, vec![3, 1]),
+            GraphNode::new(GraphOperator::Multiply, vec![4, 4]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![5]),
+        ];
+
+        let plan: FusionPlan = get_plan(&graph_nodes);
+        let group = plan.groups[4].as_ref().unwrap();
+        assert_eq!(group.producer, 1);
+        assert_eq!(group.operands, vec![3]);
+        assert!(!plan.fused_nodes[5]);
+        assert!(plan.groups[5].is_none());
+
+        assert_fused_matches_unfused(&graph_nodes);
+    }
+
+    // Broadcasting the operand is fine, broadcasting the value we have computed so far is not
+    #[test]
+    fn plan_broadcast_value_stops_fusion() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(4);
+        let graph_nodes: Vec<GraphNode> = vec![
+            input_node(&mut rng, 1, 3),
+            input_node(&mut rng, 1, 3),
+            GraphNode::new(GraphOperator::Add, vec![0, 1]),
+            input_node(&mut rng, 3, 3),
+            GraphNode::new(GraphOperator::Multiply, vec![2, 3]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![4]),
+        ];
+
+        let plan: FusionPlan = get_plan(&graph_nodes);
+        assert!(plan.groups[2].is_none());
+        assert!(!plan.fused_nodes[4]);
+    }
+
+    // This used to peek past the end of the graph
+    #[test]
+    fn plan_linear_before_output() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(5);
+        let graph_operators: Vec<GraphOperator> = vec![
+            GraphOperator::HostToDevice {
+                input: random_tensor(&mut rng, 3, 3),
+            },
+            GraphOperator::Linear {
+                weights: random_tensor(&mut rng, 3, 3),
+                bias: random_tensor(&mut rng, 3, 3),
+            },
+            GraphOperator::DeviceToHost,
+        ];
+
+        let mut unfused: GraphRunner = GraphRunner::new(&graph_operators, false);
+        let mut fused: GraphRunner = GraphRunner::new(&graph_operators, true);
+        assert_tensors_match(&unfused.run(), &fused.run());
+    }
+
+    #[test]
+    fn plan_linear_relu_softmax() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(6);
+        let graph_nodes: Vec<GraphNode> = vec![
+            input_node(&mut rng, 3, 3),
+            linear_node(&mut rng, 3, 0),
+            GraphNode::new(GraphOperator::ReLU, vec![1]),
+            GraphNode::new(GraphOperator::Softmax { axis: Axis::Global }, vec![2]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![3]),
+        ];
+
+        let plan: FusionPlan = get_plan(&graph_nodes);
+        let group = plan.groups[3].as_ref().unwrap();
+        assert_eq!(group.pattern, FusionPattern::LinearReLUSoftmax);
+        assert_eq!(group.producer, 1);
+        assert_eq!(plan.fused_nodes, vec![false, true, true, false, false]);
+    }
+
+    // The Sum can't fuse, as nothing elementwise follows it
+    #[test]
+    fn plan_axis_producer() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(11);
+        let graph_nodes: Vec<GraphNode> = vec![
+            input_node(&mut rng, 3, 4),
+            GraphNode::new(GraphOperator::LogSoftmax { axis: Axis::Rows }, vec![0]),
+            input_node(&mut rng, 1, 4),
+            GraphNode::new(GraphOperator::Multiply, vec![1, 2]),
+            GraphNode::new(
+                GraphOperator::Sum {
+                    axis: Axis::Columns,
+                },
+                vec![3],
+            ),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![4]),
+        ];
+
+        let plan: FusionPlan = get_plan(&graph_nodes);
+        let group = plan.groups[3].as_ref().unwrap();
+        assert_eq!(group.producer, 1);
+        assert_eq!(group.operands, vec![2]);
+        let fusion: FusedElementwise = FusedElementwise {
+            base: FusionBase::Axis {
+                operator: AxisOperator::LogSoftmax,
+                axis: Axis::Rows,
+            },
+            steps: vec![step(ElementwiseOperator::Multiply, true)],
+        };
+        assert_eq!(fusion.key(), "FusedAxisLogSoftmax_Multiply");
+        assert_eq!(fusion.buffer_count(), 3);
+        assert_eq!(group.pattern, FusionPattern::Elementwise(fusion));
+        assert_eq!(
+            plan.fused_nodes,
+            vec![false, true, false, false, false, false]
+        );
+        assert!(plan.groups[4].is_none());
+    }
+
+    // A softmax over the whole tensor needs more than one pass, so it isn't a producer
+    #[test]
+    fn plan_global_softmax_stops_fusion() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(12);
+        let graph_nodes: Vec<GraphNode> = vec![
+            input_node(&mut rng, 3, 4),
+            GraphNode::new(GraphOperator::Softmax { axis: Axis::Global }, vec![0]),
+            GraphNode::new(GraphOperator::ReLU, vec![1]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![2]),
+        ];
+
+        let plan: FusionPlan = get_plan(&graph_nodes);
+        assert!(plan.groups.iter().all(|group| group.is_none()));
+        assert!(plan.fused_nodes.iter().all(|fused| !fused));
+    }
+
+    fn assert_fused_matches_unfused(graph_nodes: &[GraphNode]) {
+        let mut unfused: GraphRunner = GraphRunner::from_graph_nodes(graph_nodes, false);
+        let mut fused: GraphRunner = GraphRunner::from_graph_nodes(graph_nodes, true);
+
+        let expected: Vec<Tensor2D> = unfused.run_outputs();
+        let actual: Vec<Tensor2D> = fused.run_outputs();
+        assert_eq!(expected.len(), actual.len());
+        for (expected, actual) in expected.iter().zip(actual.iter()) {
+            assert_tensors_match(expected, actual);
+        }
+    }
+
+    #[test]
+    fn fused_matches_unfused_chain() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(7);
+        assert_fused_matches_unfused(&chain_graph(&mut rng, 5));
+    }
+
+    // A binary producer with a broadcast first input, followed by
+    // a divide by a broadcast column and a reversed subtract
+    #[test]
+    fn fused_matches_unfused_binary_producer() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(8);
+        let graph_nodes: Vec<GraphNode> = vec![
+            input_node(&mut rng, 1, 4),
+            input_node(&mut rng, 3, 4),
+            GraphNode::new(GraphOperator::Add, vec![0, 1]),
+            GraphNode::new(GraphOperator::ReLU, vec![2]),
+            GraphNode::new(
+                GraphOperator::HostToDevice {
+                    input: random_divisor(&mut rng, 3, 1),
+                },
+                vec![],
+            ),
+            GraphNode::new(GraphOperator::Divide, vec![3, 4]),
+            input_node(&mut rng, 3, 4),
+            GraphNode::new(GraphOperator::Subtract, vec![6, 5]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![7]),
+        ];
+
+        let plan: FusionPlan = get_plan(&graph_nodes);
+        assert_eq!(plan.groups[7].as_ref().unwrap().producer, 2);
+
+        assert_fused_matches_unfused(&graph_nodes);
+    }
+
+    #[test]
+    fn fused_matches_unfused_branches() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(9);
+        let graph_nodes: Vec<GraphNode> = vec![
+            input_node(&mut rng, 4, 4),
+            GraphNode::new(
+                GraphOperator::LinearReLUFused {
+                    weights: random_tensor(&mut rng, 4, 4),
+                    bias: random_tensor(&mut rng, 4, 4),
+                },
+                vec![0],
+            ),
+            linear_node(&mut rng, 4, 1),
+            GraphNode::new(GraphOperator::Subtract, vec![2, 1]),
+            GraphNode::new(GraphOperator::ReLU, vec![3]),
+            GraphNode::new(GraphOperator::Softmax { axis: Axis::Global }, vec![4]),
+            linear_node(&mut rng, 4, 1),
+            GraphNode::new(GraphOperator::ReLU, vec![6]),
+            GraphNode::new(GraphOperator::Softmax { axis: Axis::Global }, vec![7]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![5]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![8]),
+        ];
+
+        assert_fused_matches_unfused(&graph_nodes);
+    }
+
+    // The gradients of the parameters have to be the same whether we fuse or not
+    #[test]
+    fn fused_matches_unfused_backward() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(10);
+        let mut graph_nodes: Vec<GraphNode> = chain_graph(&mut rng, 4);
+        graph_nodes.push(linear_node(&mut rng, 4, 6));
+        graph_nodes.push(input_node(&mut rng, 4, 1));
+        graph_nodes.push(GraphNode::new(GraphOperator::Add, vec![9, 8]));
+        graph_nodes.push(GraphNode::new(
+            GraphOperator::Softmax { axis: Axis::Global },
+            vec![10],
+        ));
+        graph_nodes[7] = GraphNode::new(GraphOperator::DeviceToHost, vec![11]);
+
+        let output_gradient: Tensor2D = random_tensor(&mut rng, 4, 4);
+
+        let mut unfused: GraphRunner = GraphRunner::from_graph_nodes(&graph_nodes, false);
+        let mut fused: GraphRunner = GraphRunner::from_graph_nodes(&graph_nodes, true);
+        assert_tensors_match(&unfused.run(), &fused.run());
+
+        unfused.zero_gradients();
+        unfused.backward(&output_gradient);
+        fused.zero_gradients();
+        fused.backward(&output_gradient);
+
+        let expected: Vec<&Tensor2D> = unfused.parameter_gradients();
+        let actual: Vec<&Tensor2D> = fused.parameter_gradients();
+        assert_eq!(expected.len(), 4);
+        assert_eq!(expected.len(), actual.len());
+        for (expected, actual) in expected.iter().zip(actual.iter()) {
+            assert_tensors_match(expected, actual);
+        }
+    }
+
+    fn axis_operators(axis: Axis) -> Vec<GraphOperator> {
+        vec![
+            GraphOperator::Softmax { axis },
+            GraphOperator::LogSoftmax { axis },
+            GraphOperator::Sum { axis },
+            GraphOperator::Max { axis },
+            GraphOperator::Mean { axis },
+            GraphOperator::ArgMax { axis },
+        ]
+    }
+
+    // input -> axis operator -> (operand - value) -> relu -> value * operand -> output,
+    // with the operands in the shape of the result of the axis operator
+    fn axis_graph(
+        rng: &mut ChaCha8Rng,
+        operator: GraphOperator,
+        output_shape: (usize, usize),
+    ) -> Vec<GraphNode> {
+        vec![
+            input_node(rng, 3, 4),
+            GraphNode::new(operator, vec![0]),
+            input_node(rng, output_shape.0, output_shape.1),
+            GraphNode::new(GraphOperator::Subtract, vec![2, 1]),
+            GraphNode::new(GraphOperator::ReLU, vec![3]),
+            input_node(rng, output_shape.0, output_shape.1),
+            GraphNode::new(GraphOperator::Multiply, vec![4, 5]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![6]),
+        ]
+    }
+
+    #[test]
+    fn fused_matches_unfused_axis() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(13);
+        for axis in [Axis::Rows, Axis::Columns, Axis::Global] {
+            for operator in axis_operators(axis) {
+                if matches!(operator, GraphOperator::Softmax { axis: Axis::Global }) {
+                    continue;
+                }
+                let output_shape: (usize, usize) = match operator.reduction() {
+                    Some(_) => axis.reduced_shape((3, 4)),
+                    None => (3, 4),
+                };
+                let graph_nodes: Vec<GraphNode> = axis_graph(&mut rng, operator, output_shape);
+
+                let plan: FusionPlan = get_plan(&graph_nodes);
+                assert_eq!(plan.groups[6].as_ref().unwrap().producer, 1);
+                assert_fused_matches_unfused(&graph_nodes);
+            }
+        }
+    }
+
+    // The gradient goes back through the fused axis operator to the linear layer.
+    // ArgMax has no gradient, so it is left out.
+    #[test]
+    fn fused_matches_unfused_axis_backward() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(14);
+        for axis in [Axis::Rows, Axis::Columns, Axis::Global] {
+            for operator in axis_operators(axis) {
+                if matches!(
+                    operator,
+                    GraphOperator::Softmax { axis: Axis::Global } | GraphOperator::ArgMax { .. }
+                ) {
+                    continue;
+                }
+                let output_shape: (usize, usize) = match operator.reduction() {
+                    Some(_) => axis.reduced_shape((4, 4)),
+                    None => (4, 4),
+                };
+                let graph_nodes: Vec<GraphNode> = vec![
+                    input_node(&mut rng, 4, 4),
+                    linear_node(&mut rng, 4, 0),
+                    GraphNode::new(operator, vec![1]),
+                    input_node(&mut rng, output_shape.0, output_shape.1),
+                    GraphNode::new(GraphOperator::Multiply, vec![2, 3]),
+                    input_node(&mut rng, output_shape.0, output_shape.1),
+                    GraphNode::new(GraphOperator::Subtract, vec![5, 4]),
+                    GraphNode::new(GraphOperator::DeviceToHost, vec![6]),
+                ];
+                let output_gradient: Tensor2D =
+                    random_tensor(&mut rng, output_shape.0, output_shape.1);
+
+                let plan: FusionPlan = get_plan(&graph_nodes);
+                assert_eq!(plan.groups[6].as_ref().unwrap().producer, 2);
+
+                let mut unfused: GraphRunner = GraphRunner::from_graph_nodes(&graph_nodes, false);
+                let mut fused: GraphRunner = GraphRunner::from_graph_nodes(&graph_nodes, true);
+                assert_tensors_match(&unfused.run(), &fused.run());
+
+                unfused.zero_gradients();
+                unfused.backward(&output_gradient);
+                fused.zero_gradients();
+                fused.backward(&output_gradient);
+
+                let expected: Vec<&Tensor2D> = unfused.parameter_gradients();
+                let actual: Vec<&Tensor2D> = fused.parameter_gradients();
+                assert_eq!(expected.len(), 2);
+                assert!(expected[0].data.iter().any(|gradient| *gradient != 0.0));
+                for (expected, actual) in expected.iter().zip(actual.iter()) {
+                    assert_tensors_match(expected, actual);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use std::fmt;
+
+use crate::shared::graph_operators::NodeId;
+
+// Everything which can go wrong when validating, building or running a graph.
+// index is the position of the offending operator in the Vec<GraphOperator>,
+// or the id of the offending node when the graph is described with GraphNode.
+// Shapes are (rows, columns).
+#[derive(Clone, Debug, PartialEq)]
+pub enum GraphError {
+    // HostToDevice has to be first and DeviceToHost last in a chain of operators,
+    // and a linear layer can't read from an Empty or DeviceToHost operator
+    MisplacedOperator {
+        index: usize,
+        operator: &'static str,
+        reason: &'static str,
+    },
+    MissingTransfer {
+        operator: &'static str,
+    },
+    // Elementwise operators need two inputs, which a chain of operators can't describe
+    UnsupportedOperator {
+        index: usize,
+        operator: &'static str,
+    },
+    EmptyTensor {
+        index: usize,
+        tensor: &'static str,
+        shape: (usize, usize),
+    },
+    ShapeMismatch {
+        index: usize,
+        tensor: &'static str,
+        expected: (usize, usize),
+        actual: (usize, usize),
+    },
+    BroadcastMismatch {
+        index: usize,
+        left: (usize, usize),
+        right: (usize, usize),
+    },
+    InputCount {
+        index: usize,
+        operator: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+    // The node reads from a node which doesn't exist, or hasn't produced an output
+    MissingInput {
+        index: usize,
+        input: NodeId,
+    },
+    // The node reads from an Empty or DeviceToHost node
+    InvalidInput {
+        index: usize,
+        input: NodeId,
+        operator: &'static str,
+    },
+    Cycle,
+    NoOutputs,
+    // The number of input tensors given to run_with doesn't match the HostToDevice nodes
+    GraphInputCount {
+        expected: usize,
+        actual: usize,
+    },
+    // Batches are stacked along the rows of the single HostToDevice buffer
+    BatchInputCount {
+        input_count: usize,
+    },
+    EmptyBatch,
+    // A stacked batch has to be a whole number of samples of the input shape
+    BatchShape {
+        sample: (usize, usize),
+        actual: (usize, usize),
+    },
+    // Stacking the samples along the rows turns a row vector, such as a reduction
+    // along Axis::Columns, into one row per sample, which can't be broadcast over
+    // the rows of each sample
+    BatchBroadcast {
+        node: String,
+    },
+    // Mapping the staging buffer of an output failed
+    Readback {
+        output: usize,
+    },
+    // Mapping the buffer the timestamps of a profiled graph are resolved into failed
+    ProfileReadback,
+    // The GPU and CPU outputs of a cross-checked Executor differ by more than the tolerance
+    CrossCheck {
+        max_difference: f32,
+        tolerance: f32,
+    },
+}
+
+impl GraphError {
+    pub fn index(&self) -> Option<usize> {
+        match self {
+            GraphError::MisplacedOperator { index, .. }
+            | GraphError::UnsupportedOperator { index, .. }
+            | GraphError::EmptyTensor { index, .. }
+            | GraphError::ShapeMismatch { index, .. }
+            | GraphError::BroadcastMismatch { index, .. }
+            | GraphError::InputCount { index, .. }
+            | GraphError::MissingInput { index, .. }
+            | GraphError::InvalidInput { index, .. } => Some(*index),
+            GraphError::MissingTransfer { .. }
+            | GraphError::Cycle
+            | GraphError::NoOutputs
+            | GraphError::GraphInputCount { .. }
+            | GraphError::BatchInputCount { .. }
+            | GraphError::EmptyBatch
+            | GraphError::BatchShape { .. }
+            | GraphError::BatchBroadcast { .. }
+            | GraphError::Readback { .. }
+            | GraphError::ProfileReadback
+            | GraphError::CrossCheck { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::MisplacedOperator {
+                index,
+                operator,
+                reason,
+            } => write!(formatter, "{} at index {} {}", operator, index, reason),
+            GraphError::MissingTransfer { operator } => {
+                write!(formatter, "The graph has no {} operator", operator)
+            }
+            GraphError::UnsupportedOperator { index, operator } => write!(
+                formatter,
+                "{} at index {} needs two inputs, describe the graph with GraphNode instead",
+                operator, index
+            ),
+            GraphError::EmptyTensor {
+                index,
+                tensor,
+                shape,
+            } => write!(
+                formatter,
+                "The {} of operator {} has the zero sized shape {:?}",
+                tensor, index, shape
+            ),
+            GraphError::ShapeMismatch {
+                index,
+                tensor,
+                expected,
+                actual,
+            } => write!(
+                formatter,
+                "The {} of operator {} has the shape {:?}, expected {:?}",
+                tensor, index, actual, expected
+            ),
+            GraphError::BroadcastMismatch { index, left, right } => write!(
+                formatter,
+                "The inputs of operator {} with the shapes {:?} and {:?} can't be broadcast",
+                index, left, right
+            ),
+            GraphError::InputCount {
+                index,
+                operator,
+                expected,
+                actual,
+            } => write!(
+                formatter,
+                "{} at index {} has {} inputs, expected {}",
+                operator, index, actual, expected
+            ),
+            GraphError::MissingInput { index, input } => write!(
+                formatter,
+                "Operator {} reads from node {}, which doesn't exist or has no output",
+                index, input
+            ),
+            GraphError::InvalidInput {
+                index,
+                input,
+                operator,
+            } => write!(
+                formatter,
+                "Operator {} reads from the {} node {}",
+                index, operator, input
+            ),
+            GraphError::Cycle => write!(formatter, "The graph contains a cycle"),
+            GraphError::NoOutputs => write!(formatter, "The graph has no outputs"),
+            GraphError::GraphInputCount { expected, actual } => write!(
+                formatter,
+                "The graph has {} HostToDevice nodes, but {} inputs were given",
+                expected, actual
+            ),
+            GraphError::BatchInputCount { input_count } => write!(
+                formatter,
+                "Batches need a graph with a single HostToDevice node, the graph has {}",
+                input_count
+            ),
+            GraphError::EmptyBatch => write!(formatter, "The batch has no samples"),
+            GraphError::BatchShape { sample, actual } => write!(
+                formatter,
+                "A stacked batch of samples with the shape {:?} can't have the shape {:?}",
+                sample, actual
+            ),
+            GraphError::BatchBroadcast { node } => write!(
+                formatter,
+                "{} broadcasts a single row over the rows of a sample, which can't be batched",
+                node
+            ),
+            GraphError::Readback { output } => {
+                write!(
+                    formatter,
+                    "Failed to read output {} back from the GPU",
+                    output
+                )
+            }
+            GraphError::ProfileReadback => write!(
+                formatter,
+                "Failed to read the timestamps of a profiled graph back from the GPU"
+            ),
+            GraphError::CrossCheck {
+                max_difference,
+                tolerance,
+            } => write!(
+                formatter,
+                "The GPU and CPU outputs differ by up to {}, more than the tolerance of {}",
+                max_difference, tolerance
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
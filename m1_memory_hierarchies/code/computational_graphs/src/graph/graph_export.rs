@@ -0,0 +1,298 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::shared::tensor2d_axis::Axis;
+
+use super::fusion::{FusedElementwise, FusionBase};
+
+// What a data buffer holds. A buffer can be both an input and an output,
+// in which case it is drawn as an output.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BufferRole {
+    Input,
+    Output,
+    Parameter,
+    Intermediate,
+}
+
+impl BufferRole {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BufferRole::Input => "input",
+            BufferRole::Output => "output",
+            BufferRole::Parameter => "parameter",
+            BufferRole::Intermediate => "intermediate",
+        }
+    }
+
+    fn dot_color(&self) -> &'static str {
+        match self {
+            BufferRole::Input => "lightblue",
+            BufferRole::Output => "palegreen",
+            BufferRole::Parameter => "lightyellow",
+            BufferRole::Intermediate => "white",
+        }
+    }
+
+    // Mermaid has no colors without class definitions, so the roles get their own shapes
+    fn mermaid_brackets(&self) -> (&'static str, &'static str) {
+        match self {
+            BufferRole::Input => ("[/", "/]"),
+            BufferRole::Output => ("[\\", "\\]"),
+            BufferRole::Parameter => ("[(", ")]"),
+            BufferRole::Intermediate => ("([", "])"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportBuffer {
+    pub row_count: usize,
+    pub column_count: usize,
+    pub role: BufferRole,
+}
+
+// A compiled node, with its buffer indices split into what it reads and what it writes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportNode {
+    pub name: String,
+    pub inputs: Vec<usize>,
+    pub output: Option<usize>,
+    // The operators a fused node computes, empty for every other node
+    pub fused_operators: Vec<String>,
+    pub axis: Option<Axis>,
+}
+
+impl ExportNode {
+    fn build(name: &str, inputs: Vec<usize>, output: Option<usize>) -> Self {
+        ExportNode {
+            name: name.to_string(),
+            inputs,
+            output,
+            fused_operators: Vec::<String>::new(),
+            axis: None,
+        }
+    }
+
+    // Reads every buffer but the last, which it writes
+    pub fn compute(name: &str, buffer_indices: &[usize]) -> Self {
+        match buffer_indices.split_last() {
+            Some((output, inputs)) => Self::build(name, inputs.to_vec(), Some(*output)),
+            None => Self::build(name, Vec::<usize>::new(), None),
+        }
+    }
+
+    // Brings a buffer into the graph, such as HostToDevice
+    pub fn source(name: &str, buffer_index: usize) -> Self {
+        Self::build(name, Vec::<usize>::new(), Some(buffer_index))
+    }
+
+    // Only reads its buffers, such as DeviceToHost
+    pub fn sink(name: &str, buffer_indices: &[usize]) -> Self {
+        Self::build(name, buffer_indices.to_vec(), None)
+    }
+
+    pub fn with_fused_operators(mut self, fused_operators: Vec<String>) -> Self {
+        self.fused_operators = fused_operators;
+        self
+    }
+
+    pub fn with_axis(mut self, axis: Option<Axis>) -> Self {
+        self.axis = axis;
+        self
+    }
+
+    fn label(&self, line_break: &str) -> String {
+        match self.axis {
+            Some(axis) => format!("{}{}axis {}", self.name, line_break, axis.name()),
+            None => self.name.clone(),
+        }
+    }
+}
+
+// The operators of the hand written Linear -> ReLU and Linear -> ReLU -> Softmax kernels
+pub fn linear_relu_operator_names(with_softmax: bool) -> Vec<String> {
+    let mut names: Vec<String> = vec!["Linear".to_string(), "ReLU".to_string()];
+    if with_softmax {
+        names.push("Softmax".to_string());
+    }
+    names
+}
+
+// The operators of a FusedElementwise node in the order they are applied
+pub fn fused_operator_names(fusion: &FusedElementwise) -> Vec<String> {
+    let mut names: Vec<String> = Vec::<String>::with_capacity(fusion.steps.len() + 1);
+    match fusion.base {
+        FusionBase::Linear => names.push("Linear".to_string()),
+        FusionBase::Load => {}
+        FusionBase::Axis { operator, axis } => {
+            names.push(format!("{:?} ({})", operator, axis.name()))
+        }
+    }
+    // a - value is not value - a
+    for step in &fusion.steps {
+        if step.value_is_left || !step.operator.is_binary() {
+            names.push(format!("{:?}", step.operator));
+        } else {
+            names.push(format!("{:?} (reversed)", step.operator));
+        }
+    }
+    names
+}
+
+// The nodes a runner compiled a graph into and the buffers they work on,
+// which is what to_dot and to_mermaid render. Fused nodes are drawn inside
+// a group listing the operators they replaced.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportGraph {
+    pub name: String,
+    pub nodes: Vec<ExportNode>,
+    pub buffers: Vec<ExportBuffer>,
+}
+
+impl ExportGraph {
+    // Graphviz, render with dot -Tsvg graph.dot -o graph.svg
+    pub fn to_dot(&self) -> String {
+        let mut dot: String = String::new();
+        // Writing to a String can't fail
+        writeln!(dot, "digraph {} {{", self.name).unwrap();
+        writeln!(dot, "    rankdir=TB;").unwrap();
+        writeln!(dot, "    node [fontname=\"monospace\"];").unwrap();
+
+        for (buffer_index, buffer) in self.buffers.iter().enumerate() {
+            writeln!(
+                dot,
+                "    buffer_{} [label=\"buffer {}\\n{}x{} {}\", shape=ellipse, style=filled, fillcolor={}];",
+                buffer_index,
+                buffer_index,
+                buffer.row_count,
+                buffer.column_count,
+                buffer.role.name(),
+                buffer.role.dot_color()
+            )
+            .unwrap();
+        }
+
+        for node in &self.nodes {
+            let declaration: String = format!(
+                "{} [label=\"{}\", shape=box];",
+                node.name,
+                node.label("\\n")
+            );
+            if node.fused_operators.is_empty() {
+                writeln!(dot, "    {}", declaration).unwrap();
+            } else {
+                writeln!(dot, "    subgraph cluster_{} {{", node.name).unwrap();
+                writeln!(
+                    dot,
+                    "        label=\"fused {}\";",
+                    node.fused_operators.join(" -> ")
+                )
+                .unwrap();
+                writeln!(dot, "        style=dashed;").unwrap();
+                writeln!(dot, "        {}", declaration).unwrap();
+                writeln!(dot, "    }}").unwrap();
+            }
+        }
+
+        for node in &self.nodes {
+            for input in &node.inputs {
+                writeln!(dot, "    buffer_{} -> {};", input, node.name).unwrap();
+            }
+            if let Some(output) = node.output {
+                writeln!(dot, "    {} -> buffer_{};", node.name, output).unwrap();
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    // Mermaid flowchart, which GitHub and most markdown viewers render
+    pub fn to_mermaid(&self) -> String {
+        let mut mermaid: String = String::new();
+        writeln!(mermaid, "flowchart TD").unwrap();
+
+        for (buffer_index, buffer) in self.buffers.iter().enumerate() {
+            let (open, close): (&str, &str) = buffer.role.mermaid_brackets();
+            writeln!(
+                mermaid,
+                "    buffer_{}{}\"buffer {}<br/>{}x{} {}\"{}",
+                buffer_index,
+                open,
+                buffer_index,
+                buffer.row_count,
+                buffer.column_count,
+                buffer.role.name(),
+                close
+            )
+            .unwrap();
+        }
+
+        for node in &self.nodes {
+            let declaration: String = format!("{}[\"{}\"]", node.name, node.label("<br/>"));
+            if node.fused_operators.is_empty() {
+                writeln!(mermaid, "    {}", declaration).unwrap();
+            } else {
+                writeln!(
+                    mermaid,
+                    "    subgraph fused_{} [\"fused {}\"]",
+                    node.name,
+                    node.fused_operators.join(" -> ")
+                )
+                .unwrap();
+                writeln!(mermaid, "        {}", declaration).unwrap();
+                writeln!(mermaid, "    end").unwrap();
+            }
+        }
+
+        for node in &self.nodes {
+            for input in &node.inputs {
+                writeln!(mermaid, "    buffer_{} --> {}", input, node.name).unwrap();
+            }
+            if let Some(output) = node.output {
+                writeln!(mermaid, "    {} --> buffer_{}", node.name, output).unwrap();
+            }
+        }
+
+        mermaid
+    }
+}
+
+// The role of every buffer, outputs take precedence over inputs and inputs over parameters
+pub fn buffer_roles(
+    buffer_count: usize,
+    input_buffer_indices: &[usize],
+    output_buffer_indices: &[usize],
+    parameter_buffer_indices: &[usize],
+) -> Vec<BufferRole> {
+    (0..buffer_count)
+        .map(|buffer_index| {
+            if output_buffer_indices.contains(&buffer_index) {
+                BufferRole::Output
+            } else if input_buffer_indices.contains(&buffer_index) {
+                BufferRole::Input
+            } else if parameter_buffer_indices.contains(&buffer_index) {
+                BufferRole::Parameter
+            } else {
+                BufferRole::Intermediate
+            }
+        })
+        .collect()
+}
+
+// Writes name.dot and name.mmd to directory, which is created if it doesn't exist.
+// Returns the paths of the two files.
+pub fn dump_export_graph(
+    directory: &str,
+    name: &str,
+    graph: &ExportGraph,
+) -> std::io::Result<(PathBuf, PathBuf)> {
+    fs::create_dir_all(directory)?;
+    let dot_path: PathBuf = PathBuf::from(directory).join(format!("{}.dot", name));
+    let mermaid_path: PathBuf = PathBuf::from(directory).join(format!("{}.mmd", name));
+    fs::write(&dot_path, graph.to_dot())?;
+    fs::write(&mermaid_path, graph.to_mermaid())?;
+    Ok((dot_path, mermaid_path))
+}
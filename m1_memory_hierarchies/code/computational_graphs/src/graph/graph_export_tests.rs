@@ -0,0 +1,234 @@
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::{
+        graph::{
+            graph_export::{dump_export_graph, BufferRole, ExportGraph},
+            graph_runner::GraphRunner,
+            memory_planner::MemoryPlan,
+        },
+        shared::{
+            graph_operators::{GraphNode, GraphOperator},
+            tensor2d::Tensor2D,
+            tensor2d_axis::Axis,
+        },
+    };
+
+    fn graph() -> Vec<GraphOperator> {
+        vec![
+            GraphOperator::HostToDevice {
+                input: Tensor2D::new(0.1, 2, 3),
+            },
+            GraphOperator::Linear {
+                weights: Tensor2D::new(0.2, 3, 4),
+                bias: Tensor2D::new(0.3, 2, 4),
+            },
+            GraphOperator::ReLU,
+            GraphOperator::Softmax { axis: Axis::Rows },
+            GraphOperator::DeviceToHost,
+        ]
+    }
+
+    // The linear layer and the subtraction are fused, with the linear output on the right
+    fn branch_graph() -> Vec<GraphNode> {
+        vec![
+            GraphNode::new(
+                GraphOperator::HostToDevice {
+                    input: Tensor2D::new(0.1, 2, 3),
+                },
+                vec![],
+            ),
+            GraphNode::new(
+                GraphOperator::HostToDevice {
+                    input: Tensor2D::new(0.1, 2, 4),
+                },
+                vec![],
+            ),
+            GraphNode::new(
+                GraphOperator::Linear {
+                    weights: Tensor2D::new(0.2, 3, 4),
+                    bias: Tensor2D::new(0.3, 2, 4),
+                },
+                vec![0],
+            ),
+            GraphNode::new(GraphOperator::Subtract, vec![1, 2]),
+            GraphNode::new(GraphOperator::ReLU, vec![3]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![4]),
+        ]
+    }
+
+    // If one of these fails because the output changed on purpose,
+    // check the new output renders and paste it in.
+    #[test]
+    fn dot() {
+        let runner: GraphRunner = GraphRunner::new(&graph(), false);
+        let expected: &str = r#"digraph GraphRunner {
+    rankdir=TB;
+    node [fontname="monospace"];
+    buffer_0 [label="buffer 0\n2x3 input", shape=ellipse, style=filled, fillcolor=lightblue];
+    buffer_1 [label="buffer 1\n3x4 parameter", shape=ellipse, style=filled, fillcolor=lightyellow];
+    buffer_2 [label="buffer 2\n2x4 parameter", shape=ellipse, style=filled, fillcolor=lightyellow];
+    buffer_3 [label="buffer 3\n2x4 intermediate", shape=ellipse, style=filled, fillcolor=white];
+    buffer_4 [label="buffer 4\n2x4 intermediate", shape=ellipse, style=filled, fillcolor=white];
+    buffer_5 [label="buffer 5\n2x4 output", shape=ellipse, style=filled, fillcolor=palegreen];
+    Input_0 [label="Input_0", shape=box];
+    Transfer_0 [label="Transfer_0", shape=box];
+    Linear_0 [label="Linear_0", shape=box];
+    Transfer_1 [label="Transfer_1", shape=box];
+    ReLU_0 [label="ReLU_0", shape=box];
+    Transfer_2 [label="Transfer_2", shape=box];
+    Softmax_0 [label="Softmax_0\naxis Rows", shape=box];
+    Transfer_3 [label="Transfer_3", shape=box];
+    Output_0 [label="Output_0", shape=box];
+    Input_0 -> buffer_0;
+    buffer_0 -> Transfer_0;
+    buffer_0 -> Linear_0;
+    buffer_1 -> Linear_0;
+    buffer_2 -> Linear_0;
+    Linear_0 -> buffer_3;
+    buffer_3 -> Transfer_1;
+    buffer_3 -> ReLU_0;
+    ReLU_0 -> buffer_4;
+    buffer_4 -> Transfer_2;
+    buffer_4 -> Softmax_0;
+    Softmax_0 -> buffer_5;
+    buffer_5 -> Transfer_3;
+    buffer_5 -> Output_0;
+}
+"#;
+        assert_eq!(runner.to_dot(), expected);
+    }
+
+    #[test]
+    fn mermaid_fused() {
+        let runner: GraphRunner = GraphRunner::new(&graph(), true);
+        let expected: &str = r#"flowchart TD
+    buffer_0[/"buffer 0<br/>2x3 input"/]
+    buffer_1[("buffer 1<br/>3x4 parameter")]
+    buffer_2[("buffer 2<br/>2x4 parameter")]
+    buffer_3(["buffer 3<br/>2x4 intermediate"])
+    buffer_4[\"buffer 4<br/>2x4 output"\]
+    Input_0["Input_0"]
+    Transfer_0["Transfer_0"]
+    subgraph fused_LinearReLU_0 ["fused Linear -> ReLU"]
+        LinearReLU_0["LinearReLU_0"]
+    end
+    Transfer_1["Transfer_1"]
+    Softmax_0["Softmax_0<br/>axis Rows"]
+    Transfer_2["Transfer_2"]
+    Output_0["Output_0"]
+    Input_0 --> buffer_0
+    buffer_0 --> Transfer_0
+    buffer_0 --> LinearReLU_0
+    buffer_1 --> LinearReLU_0
+    buffer_2 --> LinearReLU_0
+    LinearReLU_0 --> buffer_3
+    buffer_3 --> Transfer_1
+    buffer_3 --> Softmax_0
+    Softmax_0 --> buffer_4
+    buffer_4 --> Transfer_2
+    buffer_4 --> Output_0
+"#;
+        assert_eq!(runner.to_mermaid(), expected);
+    }
+
+    #[test]
+    fn dot_fused_elementwise() {
+        let runner: GraphRunner = GraphRunner::from_graph_nodes(&branch_graph(), true);
+        let expected: &str = r#"digraph GraphRunner {
+    rankdir=TB;
+    node [fontname="monospace"];
+    buffer_0 [label="buffer 0\n2x3 input", shape=ellipse, style=filled, fillcolor=lightblue];
+    buffer_1 [label="buffer 1\n2x4 input", shape=ellipse, style=filled, fillcolor=lightblue];
+    buffer_2 [label="buffer 2\n3x4 parameter", shape=ellipse, style=filled, fillcolor=lightyellow];
+    buffer_3 [label="buffer 3\n2x4 parameter", shape=ellipse, style=filled, fillcolor=lightyellow];
+    buffer_4 [label="buffer 4\n2x4 output", shape=ellipse, style=filled, fillcolor=palegreen];
+    Input_0 [label="Input_0", shape=box];
+    Transfer_0 [label="Transfer_0", shape=box];
+    Input_1 [label="Input_1", shape=box];
+    Transfer_1 [label="Transfer_1", shape=box];
+    subgraph cluster_FusedElementwise_0 {
+        label="fused Linear -> Subtract (reversed) -> ReLU";
+        style=dashed;
+        FusedElementwise_0 [label="FusedElementwise_0", shape=box];
+    }
+    Transfer_2 [label="Transfer_2", shape=box];
+    Output_0 [label="Output_0", shape=box];
+    Input_0 -> buffer_0;
+    buffer_0 -> Transfer_0;
+    Input_1 -> buffer_1;
+    buffer_1 -> Transfer_1;
+    buffer_0 -> FusedElementwise_0;
+    buffer_2 -> FusedElementwise_0;
+    buffer_3 -> FusedElementwise_0;
+    buffer_1 -> FusedElementwise_0;
+    FusedElementwise_0 -> buffer_4;
+    buffer_4 -> Transfer_2;
+    buffer_4 -> Output_0;
+}
+"#;
+        assert_eq!(runner.to_dot(), expected);
+    }
+
+    // After planning the nodes refer to the shared buffers
+    #[test]
+    fn memory_planned() {
+        let size: usize = 8;
+        let mut graph: Vec<GraphOperator> = vec![GraphOperator::HostToDevice {
+            input: Tensor2D::new(0.1, size, size),
+        }];
+        for _ in 0..4 {
+            graph.push(GraphOperator::ReLU);
+        }
+        graph.push(GraphOperator::DeviceToHost);
+
+        let mut runner: GraphRunner = GraphRunner::new(&graph, false);
+        let plan: MemoryPlan = runner.plan_memory();
+        assert!(plan.buffer_count_after < plan.buffer_count_before);
+
+        let export: ExportGraph = runner.export_graph();
+        assert_eq!(export.buffers.len(), plan.buffer_count_after);
+        for node in &export.nodes {
+            for buffer_index in node.inputs.iter().chain(&node.output) {
+                assert!(*buffer_index < export.buffers.len(), "{}", node.name);
+            }
+        }
+        let roles: Vec<BufferRole> = export.buffers.iter().map(|buffer| buffer.role).collect();
+        assert_eq!(
+            roles
+                .iter()
+                .filter(|role| **role == BufferRole::Input)
+                .count(),
+            1
+        );
+        assert_eq!(
+            roles
+                .iter()
+                .filter(|role| **role == BufferRole::Output)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn dump() {
+        let directory: PathBuf = std::env::temp_dir().join(format!(
+            "computational_graphs_graph_export_{}",
+            std::process::id()
+        ));
+        let runner: GraphRunner = GraphRunner::new(&graph(), true);
+        let export: ExportGraph = runner.export_graph();
+
+        let (dot_path, mermaid_path): (PathBuf, PathBuf) =
+            dump_export_graph(directory.to_str().unwrap(), "cpu", &export).unwrap();
+        let dot: String = std::fs::read_to_string(&dot_path).unwrap();
+        let mermaid: String = std::fs::read_to_string(&mermaid_path).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(dot_path.file_name().unwrap(), "cpu.dot");
+        assert_eq!(mermaid_path.file_name().unwrap(), "cpu.mmd");
+        assert_eq!(dot, runner.to_dot());
+        assert_eq!(mermaid, runner.to_mermaid());
+    }
+}
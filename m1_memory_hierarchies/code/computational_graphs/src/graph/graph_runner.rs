@@ -1,38 +1,112 @@
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use rayon::ThreadPool;
 
 use crate::shared::tensor2d::Tensor2D;
+use crate::shared::tensor2d_axis::{Axis, Reduction};
+use crate::shared::tensor2d_parallel::build_thread_pool;
+use crate::shared::tensor2d_precision::PackedTensor2D;
 
-use super::graph_validation::validate_graph_operators;
+use super::fusion::{plan_fusion, FusedElementwise, FusionGroup, FusionPattern, FusionPlan};
+use super::graph_error::GraphError;
+use super::graph_export::{
+    buffer_roles, fused_operator_names, linear_relu_operator_names, BufferRole, ExportBuffer,
+    ExportGraph, ExportNode,
+};
+use super::graph_validation::{
+    get_consumers, topological_sort, validate_graph_nodes, validate_graph_operators,
+};
+use super::memory_planner::{apply_memory_plan, plan_memory, BufferDescription, MemoryPlan};
 use super::nodes::{self, Node, NodeOperator};
+use super::profiling::{NodeTiming, ProfileReport};
+use super::training::{cross_entropy, cross_entropy_gradient, Optimizer};
 
 use crate::shared::graph_operators::GraphOperator;
 use crate::shared::graph_operators::GraphOperator::*;
+use crate::shared::graph_operators::{graph_nodes_from_operators, GraphNode, NodeId};
 
+// A GraphRunner can only be built from a valid graph, the try_ functions return
+// what is wrong with the graph instead of panicking.
 pub struct GraphRunner {
-    graph_operators_are_valid: bool,
     nodes: Vec<Node>,
     nodes_are_valid: bool,
     data_buffers: Vec<Tensor2D>,
-    data_buffers_are_valid: bool,
     fuse_operators: bool,
+    output_buffer_indices: Vec<usize>,
+    // The data buffer and graph node of every HostToDevice, which run_with writes to
+    input_buffer_indices: Vec<usize>,
+    input_node_ids: Vec<NodeId>,
+    // The weights and biases of every linear node, which are what we train
+    parameter_buffer_indices: Vec<usize>,
+    // One gradient per data buffer, allocated on the first backward pass
+    gradient_buffers: Vec<Tensor2D>,
+    // Once intermediate buffers are reused we can no longer run the backward pass
+    memory_is_planned: bool,
+    // Without a thread pool the nodes run the single-threaded kernels
+    thread_pool: Option<ThreadPool>,
+    // Batched runs use their own buffers, which hold batch_size samples stacked
+    // along the rows. The weights are shared by every sample and aren't stacked.
+    batch_size: usize,
+    batch_buffers: Vec<Tensor2D>,
 }
 
 impl GraphRunner {
-    pub fn new(graph_operators: &Vec<GraphOperator>, fuse_operators: bool) -> Self {
+    pub fn new(graph_operators: &[GraphOperator], fuse_operators: bool) -> Self {
+        Self::try_new(graph_operators, fuse_operators)
+            .unwrap_or_else(|error| panic!("Invalid graph sent to GraphRunner::new! {}", error))
+    }
+
+    pub fn try_new(
+        graph_operators: &[GraphOperator],
+        fuse_operators: bool,
+    ) -> Result<Self, GraphError> {
+        validate_graph_operators(graph_operators)?;
+        let graph_nodes: Vec<GraphNode> = graph_nodes_from_operators(graph_operators);
+
+        Self::build(&graph_nodes, fuse_operators)
+    }
+
+    // Build a runner from a graph where every node names its inputs,
+    // which allows for branches and multiple outputs.
+    pub fn from_graph_nodes(graph_nodes: &[GraphNode], fuse_operators: bool) -> Self {
+        Self::try_from_graph_nodes(graph_nodes, fuse_operators).unwrap_or_else(|error| {
+            panic!(
+                "Invalid graph sent to GraphRunner::from_graph_nodes! {}",
+                error
+            )
+        })
+    }
+
+    pub fn try_from_graph_nodes(
+        graph_nodes: &[GraphNode],
+        fuse_operators: bool,
+    ) -> Result<Self, GraphError> {
+        validate_graph_nodes(graph_nodes)?;
+
+        Self::build(graph_nodes, fuse_operators)
+    }
+
+    fn build(graph_nodes: &[GraphNode], fuse_operators: bool) -> Result<Self, GraphError> {
         let mut runner: GraphRunner = GraphRunner {
-            graph_operators_are_valid: false,
             nodes: Vec::<Node>::new(),
             nodes_are_valid: false,
             data_buffers: Vec::<Tensor2D>::new(),
-            data_buffers_are_valid: false,
             fuse_operators,
+            output_buffer_indices: Vec::<usize>::new(),
+            input_buffer_indices: Vec::<usize>::new(),
+            input_node_ids: Vec::<NodeId>::new(),
+            parameter_buffer_indices: Vec::<usize>::new(),
+            gradient_buffers: Vec::<Tensor2D>::new(),
+            memory_is_planned: false,
+            thread_pool: None,
+            batch_size: 0,
+            batch_buffers: Vec::<Tensor2D>::new(),
         };
-        runner.graph_operators_are_valid = validate_graph_operators(graph_operators);
 
-        runner.compute_nodes(graph_operators, runner.fuse_operators);
-        runner.data_buffers_are_valid = true;
+        runner.compute_nodes(graph_nodes, runner.fuse_operators)?;
 
-        runner
+        Ok(runner)
     }
 
     fn get_new_key(operator_counts: &mut HashMap<NodeOperator, u32>, key: &NodeOperator) -> String {
@@ -45,30 +119,288 @@ impl GraphRunner {
         format!("{:?}_{}", key, index)
     }
 
-    fn verify_previous_node_and_get_index(nodes: &Vec<Node>, key: &NodeOperator) -> usize {
-        let previous_node: &Node = &nodes[nodes.len() - 1];
-        match previous_node.operator {
-            NodeOperator::Transfer => {}
+    // Instead of assuming the input is whatever the previous node produced
+    // we look up the buffer written by the node named as input.
+    // input is the position in the graph node's list of inputs, which is
+    // always 0 except for the second operand of the elementwise operators.
+    fn get_input_buffer_index(
+        output_indices: &[Option<usize>],
+        node_id: NodeId,
+        graph_node: &GraphNode,
+        input: usize,
+    ) -> Result<usize, GraphError> {
+        match graph_node.inputs.get(input) {
+            Some(input_node) => Self::get_output_buffer_index(output_indices, node_id, *input_node),
+            None => Err(GraphError::InputCount {
+                index: node_id,
+                operator: graph_node.operator.name(),
+                expected: input + 1,
+                actual: graph_node.inputs.len(),
+            }),
+        }
+    }
+
+    // Fails if the input node hasn't produced an output yet
+    fn get_output_buffer_index(
+        output_indices: &[Option<usize>],
+        node_id: NodeId,
+        input_node: NodeId,
+    ) -> Result<usize, GraphError> {
+        match output_indices.get(input_node) {
+            Some(Some(buffer_index)) => Ok(*buffer_index),
+            _ => Err(GraphError::MissingInput {
+                index: node_id,
+                input: input_node,
+            }),
+        }
+    }
+
+    fn push_transfer_node(
+        &mut self,
+        operator_counts: &mut HashMap<NodeOperator, u32>,
+        output_index: usize,
+    ) {
+        let key: NodeOperator = NodeOperator::Transfer;
+        let new_key: String = Self::get_new_key(operator_counts, &key);
+        let buffer_indices: Vec<usize> = vec![output_index];
+        let node: Node = Node::new(new_key, key, buffer_indices);
+        self.nodes.push(node);
+    }
+
+    fn push_parameters(&mut self, weights: &Tensor2D, bias: &Tensor2D) -> (usize, usize) {
+        self.data_buffers.push(weights.clone());
+        let weights_index: usize = self.data_buffers.len() - 1;
+
+        self.data_buffers.push(bias.clone());
+        let bias_index: usize = self.data_buffers.len() - 1;
+        self.parameter_buffer_indices.push(weights_index);
+        self.parameter_buffer_indices.push(bias_index);
+
+        (weights_index, bias_index)
+    }
+
+    fn push_linear_node(
+        &mut self,
+        operator_counts: &mut HashMap<NodeOperator, u32>,
+        key: NodeOperator,
+        input_index: usize,
+        weights: &Tensor2D,
+        bias: &Tensor2D,
+    ) -> usize {
+        let new_key: String = Self::get_new_key(operator_counts, &key);
+        let (weights_index, bias_index): (usize, usize) = self.push_parameters(weights, bias);
+
+        // This should be more flexible, but Softmax always outputs a flattened vector
+        self.data_buffers
+            .push(Tensor2D::new(0.0, bias.row_count, bias.column_count));
+        let output_index: usize = self.data_buffers.len() - 1;
+
+        let buffer_indices: Vec<usize> = vec![input_index, weights_index, bias_index, output_index];
+        let node: Node = Node::new(new_key, key, buffer_indices);
+        self.nodes.push(node);
+
+        self.push_transfer_node(operator_counts, output_index);
+        output_index
+    }
+
+    // The packed parameters are held by the node, only the input and output are data buffers
+    fn push_quantized_linear_node(
+        &mut self,
+        operator_counts: &mut HashMap<NodeOperator, u32>,
+        input_index: usize,
+        weights: &PackedTensor2D,
+        bias: &PackedTensor2D,
+    ) -> usize {
+        let key: NodeOperator = NodeOperator::QuantizedLinear;
+        let new_key: String = Self::get_new_key(operator_counts, &key);
+
+        self.data_buffers
+            .push(Tensor2D::new(0.0, bias.row_count, bias.column_count));
+        let output_index: usize = self.data_buffers.len() - 1;
+
+        let buffer_indices: Vec<usize> = vec![input_index, output_index];
+        let node: Node =
+            Node::quantized_linear(new_key, buffer_indices, weights.clone(), bias.clone());
+        self.nodes.push(node);
+
+        self.push_transfer_node(operator_counts, output_index);
+        output_index
+    }
+
+    // Note this is not inplace
+    fn push_unary_node(
+        &mut self,
+        operator_counts: &mut HashMap<NodeOperator, u32>,
+        key: NodeOperator,
+        input_index: usize,
+    ) -> usize {
+        let new_key: String = Self::get_new_key(operator_counts, &key);
+
+        let input_buffer: &Tensor2D = &self.data_buffers[input_index];
+        self.data_buffers.push(Tensor2D::new(
+            0.0,
+            input_buffer.row_count,
+            input_buffer.column_count,
+        ));
+        let output_index: usize = self.data_buffers.len() - 1;
+
+        let buffer_indices: Vec<usize> = vec![input_index, output_index];
+        let node: Node = Node::new(new_key, key, buffer_indices);
+        self.nodes.push(node);
+
+        self.push_transfer_node(operator_counts, output_index);
+        output_index
+    }
+
+    // Softmax and LogSoftmax keep the shape of their input,
+    // the reductions keep one value per row, per column or for the whole tensor.
+    fn push_axis_node(
+        &mut self,
+        operator_counts: &mut HashMap<NodeOperator, u32>,
+        key: NodeOperator,
+        input_index: usize,
+        axis: Axis,
+    ) -> usize {
+        let new_key: String = Self::get_new_key(operator_counts, &key);
+
+        let input_buffer: &Tensor2D = &self.data_buffers[input_index];
+        let input_shape: (usize, usize) = (input_buffer.row_count, input_buffer.column_count);
+        let (row_count, column_count): (usize, usize) = match key {
+            NodeOperator::Softmax | NodeOperator::LogSoftmax => input_shape,
+            _ => axis.reduced_shape(input_shape),
+        };
+        self.data_buffers
+            .push(Tensor2D::new(0.0, row_count, column_count));
+        let output_index: usize = self.data_buffers.len() - 1;
+
+        let buffer_indices: Vec<usize> = vec![input_index, output_index];
+        let node: Node = Node::along(new_key, key, buffer_indices, axis);
+        self.nodes.push(node);
+
+        self.push_transfer_node(operator_counts, output_index);
+        output_index
+    }
+
+    // The output has the shape of the two inputs after broadcasting.
+    fn push_elementwise_node(
+        &mut self,
+        operator_counts: &mut HashMap<NodeOperator, u32>,
+        key: NodeOperator,
+        left_index: usize,
+        right_index: usize,
+    ) -> usize {
+        let new_key: String = Self::get_new_key(operator_counts, &key);
+
+        let (row_count, column_count): (usize, usize) = Tensor2D::broadcast_dimensions(
+            &self.data_buffers[left_index],
+            &self.data_buffers[right_index],
+        )
+        .expect("Invalid graph! Inputs to an elementwise operator can't be broadcast.");
+        self.data_buffers
+            .push(Tensor2D::new(0.0, row_count, column_count));
+        let output_index: usize = self.data_buffers.len() - 1;
+
+        let buffer_indices: Vec<usize> = vec![left_index, right_index, output_index];
+        let node: Node = Node::new(new_key, key, buffer_indices);
+        self.nodes.push(node);
+
+        self.push_transfer_node(operator_counts, output_index);
+        output_index
+    }
+
+    // The output has the shape of the base after broadcasting it with every operand,
+    // which the fusion pass guarantees is the shape of the last fused graph node.
+    fn push_fused_elementwise_node(
+        &mut self,
+        operator_counts: &mut HashMap<NodeOperator, u32>,
+        fusion: &FusedElementwise,
+        mut buffer_indices: Vec<usize>,
+    ) -> usize {
+        let key: NodeOperator = NodeOperator::FusedElementwise;
+        let new_key: String = Self::get_new_key(operator_counts, &key);
+
+        let base_shapes: Vec<(usize, usize)> = buffer_indices[..fusion.base_buffer_count()]
+            .iter()
+            .map(|index| {
+                let buffer: &Tensor2D = &self.data_buffers[*index];
+                (buffer.row_count, buffer.column_count)
+            })
+            .collect();
+        let mut shape: (usize, usize) = fusion.base_shape(&base_shapes);
+        for operand_index in &buffer_indices[fusion.base_buffer_count()..] {
+            let operand: &Tensor2D = &self.data_buffers[*operand_index];
+            shape = Tensor2D::broadcast_shape(shape, (operand.row_count, operand.column_count))
+                .expect("Invalid graph! Operands of a fused node can't be broadcast.");
+        }
+        self.data_buffers.push(Tensor2D::new(0.0, shape.0, shape.1));
+        let output_index: usize = self.data_buffers.len() - 1;
+
+        buffer_indices.push(output_index);
+        let node: Node = Node::fused(new_key, buffer_indices, fusion.clone());
+        self.nodes.push(node);
+
+        self.push_transfer_node(operator_counts, output_index);
+        output_index
+    }
+
+    // Linear -> ReLU -> Softmax and Linear -> ReLU are run with the hand written kernels,
+    // every other elementwise group gets a FusedElementwise node.
+    fn push_fusion_group(
+        &mut self,
+        operator_counts: &mut HashMap<NodeOperator, u32>,
+        graph_nodes: &[GraphNode],
+        output_indices: &[Option<usize>],
+        group: &FusionGroup,
+    ) -> Result<usize, GraphError> {
+        let producer: &GraphNode = &graph_nodes[group.producer];
+        let fusion: &FusedElementwise = match &group.pattern {
+            FusionPattern::Elementwise(fusion) if !fusion.is_linear_relu() => fusion,
             _ => {
-                panic!("Invalid node operator! Was expecting DeviceToDevice before {:?}, but found {:?}", *key, previous_node.operator);
+                let key: NodeOperator = match group.pattern {
+                    FusionPattern::LinearReLUSoftmax => NodeOperator::LinearReLUSoftmax,
+                    _ => NodeOperator::LinearReLU,
+                };
+                let (weights, bias): (&Tensor2D, &Tensor2D) = match &producer.operator {
+                    Linear { weights, bias } | LinearReLUFused { weights, bias } => (weights, bias),
+                    _ => panic!("Invalid fusion! {:?} needs a linear producer.", key),
+                };
+                let input_index: usize =
+                    Self::get_input_buffer_index(output_indices, group.producer, producer, 0)?;
+                return Ok(self.push_linear_node(operator_counts, key, input_index, weights, bias));
             }
-        }
+        };
 
-        if previous_node.buffer_indices.len() == 1 {
-            previous_node.buffer_indices[0]
-        } else {
-            panic!("There was more than one buffer index described in a DeviceToDevice operator. This is currently invalid behavior!");
+        let input_index: usize =
+            Self::get_input_buffer_index(output_indices, group.producer, producer, 0)?;
+        let mut buffer_indices: Vec<usize> = vec![input_index];
+        match &producer.operator {
+            Linear { weights, bias } | LinearReLUFused { weights, bias } => {
+                let (weights_index, bias_index): (usize, usize) =
+                    self.push_parameters(weights, bias);
+                buffer_indices.push(weights_index);
+                buffer_indices.push(bias_index);
+            }
+            _ => {}
+        }
+        for operand in &group.operands {
+            buffer_indices.push(Self::get_output_buffer_index(
+                output_indices,
+                group.last_node,
+                *operand,
+            )?);
         }
+
+        Ok(self.push_fused_elementwise_node(operator_counts, fusion, buffer_indices))
     }
 
     // This is made a lot more complicated by reusing buffers
     // If each node owned its own buffers with no reusage
     // We would need to keep less track of buffers
-    fn compute_nodes(&mut self, graph_operators: &Vec<GraphOperator>, fuse_operators: bool) {
-        if !self.graph_operators_are_valid {
-            panic!("Invalid graph being sent to compute_nodes!");
-        }
-
+    fn compute_nodes(
+        &mut self,
+        graph_nodes: &[GraphNode],
+        fuse_operators: bool,
+    ) -> Result<(), GraphError> {
         let mut operator_counts: HashMap<NodeOperator, u32> = HashMap::<NodeOperator, u32>::new();
         operator_counts.insert(NodeOperator::Input, 0);
         operator_counts.insert(NodeOperator::Output, 0);
@@ -78,12 +410,49 @@ impl GraphRunner {
         operator_counts.insert(NodeOperator::Softmax, 0);
         operator_counts.insert(NodeOperator::LinearReLU, 0);
         operator_counts.insert(NodeOperator::LinearReLUSoftmax, 0);
+        operator_counts.insert(NodeOperator::Add, 0);
+        operator_counts.insert(NodeOperator::Subtract, 0);
+        operator_counts.insert(NodeOperator::Multiply, 0);
+        operator_counts.insert(NodeOperator::Divide, 0);
+        operator_counts.insert(NodeOperator::FusedElementwise, 0);
+        operator_counts.insert(NodeOperator::LogSoftmax, 0);
+        operator_counts.insert(NodeOperator::Sum, 0);
+        operator_counts.insert(NodeOperator::Max, 0);
+        operator_counts.insert(NodeOperator::Mean, 0);
+        operator_counts.insert(NodeOperator::ArgMax, 0);
+        operator_counts.insert(NodeOperator::QuantizedLinear, 0);
 
-        let mut operator_index: usize = 0;
-        while operator_index < graph_operators.len() {
-            let operator: &GraphOperator = &graph_operators[operator_index];
+        let order: Vec<NodeId> = topological_sort(graph_nodes).ok_or(GraphError::Cycle)?;
+        let fusion_plan: FusionPlan = if fuse_operators {
+            let consumers: Vec<Vec<NodeId>> = get_consumers(graph_nodes);
+            plan_fusion(graph_nodes, &order, &consumers)
+        } else {
+            FusionPlan::unfused(graph_nodes.len())
+        };
 
-            match operator {
+        // The data buffer each graph node wrote its result to
+        let mut output_indices: Vec<Option<usize>> = vec![None; graph_nodes.len()];
+        let mut outputs: Vec<(NodeId, usize)> = Vec::<(NodeId, usize)>::new();
+
+        for node_id in order {
+            // Graph nodes which are computed by the group of a later node
+            if fusion_plan.fused_nodes[node_id] {
+                continue;
+            }
+
+            if let Some(group) = &fusion_plan.groups[node_id] {
+                let output_index: usize = self.push_fusion_group(
+                    &mut operator_counts,
+                    graph_nodes,
+                    &output_indices,
+                    group,
+                )?;
+                output_indices[node_id] = Some(output_index);
+                continue;
+            }
+
+            let graph_node: &GraphNode = &graph_nodes[node_id];
+            match &graph_node.operator {
                 Empty => {}
                 // Maybe put a device to device split in here for simpler code in the other operators
                 HostToDevice { input } => {
@@ -91,229 +460,853 @@ impl GraphRunner {
                     let new_key: String = Self::get_new_key(&mut operator_counts, &key);
 
                     self.data_buffers.push(input.clone());
-                    let buffer_indices: Vec<usize> = vec![self.data_buffers.len() - 1];
+                    let output_index: usize = self.data_buffers.len() - 1;
+                    self.input_buffer_indices.push(output_index);
+                    self.input_node_ids.push(node_id);
 
-                    let node: Node = Node::new(new_key, key, buffer_indices.clone());
+                    let node: Node = Node::new(new_key, key, vec![output_index]);
                     self.nodes.push(node);
 
-                    let key: NodeOperator = NodeOperator::Transfer;
-                    let new_key: String = Self::get_new_key(&mut operator_counts, &key);
-                    let node: Node = Node::new(new_key, NodeOperator::Transfer, buffer_indices);
-                    self.nodes.push(node);
+                    self.push_transfer_node(&mut operator_counts, output_index);
+                    output_indices[node_id] = Some(output_index);
                 }
                 DeviceToHost => {
                     let key: NodeOperator = NodeOperator::Output;
                     let input_index: usize =
-                        Self::verify_previous_node_and_get_index(&self.nodes, &key);
+                        Self::get_input_buffer_index(&output_indices, node_id, graph_node, 0)?;
 
                     let new_key: String = Self::get_new_key(&mut operator_counts, &key);
 
                     let buffer_indices: Vec<usize> = vec![input_index];
                     let node: Node = Node::new(new_key, key, buffer_indices);
                     self.nodes.push(node);
+                    outputs.push((node_id, input_index));
                 }
                 Linear { weights, bias } => {
-                    let mut key: NodeOperator = NodeOperator::Linear;
-
-                    if fuse_operators {
-                        if let ReLU = graph_operators[operator_index + 1] {
-                            match graph_operators[operator_index + 2] {
-                                Softmax => {
-                                    key = NodeOperator::LinearReLUSoftmax;
-                                    operator_index += 2;
-                                }
-                                _ => {
-                                    key = NodeOperator::LinearReLU;
-                                    operator_index += 1;
-                                }
-                            }
-                        }
-                    }
-
+                    let key: NodeOperator = NodeOperator::Linear;
                     let input_index: usize =
-                        Self::verify_previous_node_and_get_index(&self.nodes, &key);
-                    let new_key: String = Self::get_new_key(&mut operator_counts, &key);
+                        Self::get_input_buffer_index(&output_indices, node_id, graph_node, 0)?;
+                    let output_index: usize = self.push_linear_node(
+                        &mut operator_counts,
+                        key,
+                        input_index,
+                        weights,
+                        bias,
+                    );
+                    output_indices[node_id] = Some(output_index);
+                }
+                ReLU => {
+                    let key: NodeOperator = NodeOperator::ReLU;
+                    let input_index: usize =
+                        Self::get_input_buffer_index(&output_indices, node_id, graph_node, 0)?;
+                    let output_index: usize =
+                        self.push_unary_node(&mut operator_counts, key, input_index);
+                    output_indices[node_id] = Some(output_index);
+                }
+                Softmax { axis }
+                | LogSoftmax { axis }
+                | Sum { axis }
+                | Max { axis }
+                | Mean { axis }
+                | ArgMax { axis } => {
+                    let key: NodeOperator = match graph_node.operator {
+                        Softmax { .. } => NodeOperator::Softmax,
+                        LogSoftmax { .. } => NodeOperator::LogSoftmax,
+                        Sum { .. } => NodeOperator::Sum,
+                        Max { .. } => NodeOperator::Max,
+                        Mean { .. } => NodeOperator::Mean,
+                        _ => NodeOperator::ArgMax,
+                    };
+                    let input_index: usize =
+                        Self::get_input_buffer_index(&output_indices, node_id, graph_node, 0)?;
+                    let output_index: usize =
+                        self.push_axis_node(&mut operator_counts, key, input_index, *axis);
+                    output_indices[node_id] = Some(output_index);
+                }
+                LinearReLUFused { weights, bias } => {
+                    let key: NodeOperator = NodeOperator::LinearReLU;
+                    let input_index: usize =
+                        Self::get_input_buffer_index(&output_indices, node_id, graph_node, 0)?;
+                    let output_index: usize = self.push_linear_node(
+                        &mut operator_counts,
+                        key,
+                        input_index,
+                        weights,
+                        bias,
+                    );
+                    output_indices[node_id] = Some(output_index);
+                }
+                LinearReLUSoftmaxFused { weights, bias } => {
+                    let key: NodeOperator = NodeOperator::LinearReLUSoftmax;
+                    let input_index: usize =
+                        Self::get_input_buffer_index(&output_indices, node_id, graph_node, 0)?;
+                    let output_index: usize = self.push_linear_node(
+                        &mut operator_counts,
+                        key,
+                        input_index,
+                        weights,
+                        bias,
+                    );
+                    output_indices[node_id] = Some(output_index);
+                }
+                QuantizedLinear { weights, bias } => {
+                    let input_index: usize =
+                        Self::get_input_buffer_index(&output_indices, node_id, graph_node, 0)?;
+                    let output_index: usize = self.push_quantized_linear_node(
+                        &mut operator_counts,
+                        input_index,
+                        weights,
+                        bias,
+                    );
+                    output_indices[node_id] = Some(output_index);
+                }
+                Add | Subtract | Multiply | Divide => {
+                    let key: NodeOperator = match graph_node.operator {
+                        Add => NodeOperator::Add,
+                        Subtract => NodeOperator::Subtract,
+                        Multiply => NodeOperator::Multiply,
+                        _ => NodeOperator::Divide,
+                    };
+                    let left_index: usize =
+                        Self::get_input_buffer_index(&output_indices, node_id, graph_node, 0)?;
+                    let right_index: usize =
+                        Self::get_input_buffer_index(&output_indices, node_id, graph_node, 1)?;
+                    let output_index: usize = self.push_elementwise_node(
+                        &mut operator_counts,
+                        key,
+                        left_index,
+                        right_index,
+                    );
+                    output_indices[node_id] = Some(output_index);
+                }
+            }
+        }
 
-                    self.data_buffers.push(weights.clone());
-                    let weights_index: usize = self.data_buffers.len() - 1;
+        // Outputs are returned in the order their DeviceToHost nodes appear in the graph
+        outputs.sort_by_key(|output| output.0);
+        self.output_buffer_indices = outputs.iter().map(|output| output.1).collect();
 
-                    self.data_buffers.push(bias.clone());
-                    let bias_index: usize = self.data_buffers.len() - 1;
+        self.nodes_are_valid = true;
+        Ok(())
+    }
 
-                    self.data_buffers
-                        .push(Tensor2D::new(0.0, bias.row_count, bias.column_count));
-                    let output_index: usize = self.data_buffers.len() - 1;
+    // In a more correct system, not meant for teaching/learning
+    // we might find the correct data buffers here and pass the correct
+    // buffers explicitly to the functions. Or at the very least
+    // enforce more correctness in the data passed along to
+    // the CPUNodeOperator functions.
+    fn submit_operator_commands(
+        node_vector: &Vec<Node>,
+        data_buffers: &mut [Tensor2D],
+        parallel: bool,
+        batch_size: usize,
+    ) {
+        for node in node_vector {
+            Self::submit_node(node, data_buffers, parallel, batch_size);
+        }
+    }
 
-                    let buffer_indices: Vec<usize> =
-                        vec![input_index, weights_index, bias_index, output_index];
-                    let node: Node = Node::new(new_key.clone(), key, buffer_indices);
-                    self.nodes.push(node);
+    fn submit_node(node: &Node, data_buffers: &mut [Tensor2D], parallel: bool, batch_size: usize) {
+        match node.operator {
+            NodeOperator::Input => {}
+            NodeOperator::Output => {}
+            NodeOperator::Transfer => {}
+            NodeOperator::Linear => {
+                nodes::linear(node, data_buffers, parallel);
+            }
+            NodeOperator::ReLU => {
+                nodes::relu(node, data_buffers, parallel);
+            }
+            NodeOperator::Softmax => {
+                nodes::softmax(node, data_buffers, parallel, batch_size);
+            }
+            NodeOperator::LinearReLU => {
+                nodes::linear_relu(node, data_buffers, parallel);
+            }
+            NodeOperator::LinearReLUSoftmax => {
+                nodes::linear_relu_softmax(node, data_buffers, parallel, batch_size);
+            }
+            NodeOperator::Add => {
+                nodes::add(node, data_buffers);
+            }
+            NodeOperator::Subtract => {
+                nodes::subtract(node, data_buffers);
+            }
+            NodeOperator::Multiply => {
+                nodes::multiply(node, data_buffers);
+            }
+            NodeOperator::Divide => {
+                nodes::divide(node, data_buffers);
+            }
+            NodeOperator::FusedElementwise => {
+                nodes::fused_elementwise(node, data_buffers, batch_size);
+            }
+            NodeOperator::LogSoftmax => {
+                nodes::log_softmax(node, data_buffers, batch_size);
+            }
+            NodeOperator::Sum => {
+                nodes::reduce(node, data_buffers, Reduction::Sum, batch_size);
+            }
+            NodeOperator::Max => {
+                nodes::reduce(node, data_buffers, Reduction::Max, batch_size);
+            }
+            NodeOperator::Mean => {
+                nodes::reduce(node, data_buffers, Reduction::Mean, batch_size);
+            }
+            NodeOperator::ArgMax => {
+                nodes::reduce(node, data_buffers, Reduction::ArgMax, batch_size);
+            }
+            NodeOperator::QuantizedLinear => {
+                nodes::quantized_linear(node, data_buffers);
+            }
+        }
+    }
 
-                    let key: NodeOperator = NodeOperator::Transfer;
-                    let new_key: String = Self::get_new_key(&mut operator_counts, &key);
-                    let buffer_indices: Vec<usize> = vec![output_index];
-                    let node: Node = Node::new(new_key, key, buffer_indices);
-                    self.nodes.push(node);
+    fn submit_operations(&mut self, batched: bool) {
+        let (data_buffers, batch_size): (&mut Vec<Tensor2D>, usize) = if batched {
+            (&mut self.batch_buffers, self.batch_size)
+        } else {
+            (&mut self.data_buffers, 1)
+        };
+
+        match &self.thread_pool {
+            Some(thread_pool) => thread_pool.install(|| {
+                Self::submit_operator_commands(&self.nodes, data_buffers, true, batch_size)
+            }),
+            None => Self::submit_operator_commands(&self.nodes, data_buffers, false, batch_size),
+        }
+    }
+
+    // Runs the graph once on the input it holds, timing every node.
+    // Input, Output and Transfer nodes don't do any work and aren't timed.
+    pub fn profile(&mut self) -> ProfileReport {
+        let mut report: ProfileReport = ProfileReport::new("GraphRunner");
+        let parallel: bool = self.thread_pool.is_some();
+        let nodes: &Vec<Node> = &self.nodes;
+        let data_buffers: &mut Vec<Tensor2D> = &mut self.data_buffers;
+        let timings: &mut Vec<NodeTiming> = &mut report.timings;
+
+        let mut profile_nodes = || {
+            let mut first_start: Option<Instant> = None;
+            for node in nodes {
+                if matches!(
+                    node.operator,
+                    NodeOperator::Input | NodeOperator::Output | NodeOperator::Transfer
+                ) {
+                    continue;
                 }
-                // Note this is not inplace
-                ReLU => {
-                    let key: NodeOperator = NodeOperator::ReLU;
-                    let input_index: usize =
-                        Self::verify_previous_node_and_get_index(&self.nodes, &key);
 
-                    let new_key: String = Self::get_new_key(&mut operator_counts, &key);
+                let node_start: Instant = Instant::now();
+                let start: Instant = *first_start.get_or_insert(node_start);
+                Self::submit_node(node, data_buffers, parallel, 1);
+                let duration: Duration = node_start.elapsed();
+                timings.push(NodeTiming {
+                    name: node.name.clone(),
+                    operator: format!("{:?}", node.operator),
+                    start_nanoseconds: node_start.duration_since(start).as_nanos() as f64,
+                    duration_nanoseconds: duration.as_nanos() as f64,
+                });
+            }
+        };
+        match &self.thread_pool {
+            Some(thread_pool) => thread_pool.install(profile_nodes),
+            None => profile_nodes(),
+        }
 
-                    let input_buffer: &Tensor2D = &self.data_buffers[input_index];
-                    self.data_buffers.push(Tensor2D::new(
-                        0.0,
-                        input_buffer.row_count,
-                        input_buffer.column_count,
-                    ));
-                    let output_index: usize = self.data_buffers.len() - 1;
+        report
+    }
 
-                    let buffer_indices: Vec<usize> = vec![input_index, output_index];
-                    let node: Node = Node::new(new_key.clone(), key, buffer_indices);
-                    self.nodes.push(node);
+    // Lets the linear, ReLU and softmax nodes split their rows across a pool of
+    // thread_count threads, 0 being one thread per logical core.
+    // The nodes themselves still run one after the other.
+    pub fn set_thread_count(&mut self, thread_count: usize) {
+        self.thread_pool = Some(build_thread_pool(thread_count));
+    }
 
-                    let key: NodeOperator = NodeOperator::Transfer;
-                    let new_key: String = Self::get_new_key(&mut operator_counts, &key);
-                    let buffer_indices: Vec<usize> = vec![output_index];
-                    let node: Node = Node::new(new_key, key, buffer_indices);
-                    self.nodes.push(node);
-                }
-                Softmax => {
-                    let key: NodeOperator = NodeOperator::Softmax;
-                    let input_index: usize =
-                        Self::verify_previous_node_and_get_index(&self.nodes, &key);
+    // Returns the output of the last DeviceToHost node in the graph.
+    pub fn run(&mut self) -> Tensor2D {
+        self.try_run()
+            .unwrap_or_else(|error| panic!("Failed to run a CPU computational graph! {}", error))
+    }
 
-                    let new_key: String = Self::get_new_key(&mut operator_counts, &key);
+    pub fn try_run(&mut self) -> Result<Tensor2D, GraphError> {
+        let output_index: usize = *self
+            .output_buffer_indices
+            .last()
+            .ok_or(GraphError::NoOutputs)?;
+        self.submit_operations(false);
 
-                    let input_buffer: &Tensor2D = &self.data_buffers[input_index];
-                    // This should be more flexible, but Softmax always outputs a flattened vector
-                    self.data_buffers.push(Tensor2D::new(
-                        0.0,
-                        input_buffer.row_count,
-                        input_buffer.column_count,
-                    ));
-                    let output_index: usize = self.data_buffers.len() - 1;
+        Ok(self.data_buffers[output_index].clone())
+    }
 
-                    let buffer_indices: Vec<usize> = vec![input_index, output_index];
-                    let node: Node = Node::new(new_key.clone(), key, buffer_indices);
-                    self.nodes.push(node);
+    // Returns the outputs of every DeviceToHost node, in the order they appear in the graph.
+    pub fn run_outputs(&mut self) -> Vec<Tensor2D> {
+        self.try_run_outputs()
+            .unwrap_or_else(|error| panic!("Failed to run a CPU computational graph! {}", error))
+    }
 
-                    let key: NodeOperator = NodeOperator::Transfer;
-                    let new_key: String = Self::get_new_key(&mut operator_counts, &key);
-                    let buffer_indices: Vec<usize> = vec![output_index];
-                    let node: Node = Node::new(new_key, key, buffer_indices);
-                    self.nodes.push(node);
-                }
-                LinearReLUFused { weights, bias } => {
-                    let key: NodeOperator = NodeOperator::LinearReLU;
-                    let input_index: usize =
-                        Self::verify_previous_node_and_get_index(&self.nodes, &key);
-                    let new_key: String = Self::get_new_key(&mut operator_counts, &key);
+    pub fn try_run_outputs(&mut self) -> Result<Vec<Tensor2D>, GraphError> {
+        if self.output_buffer_indices.is_empty() {
+            return Err(GraphError::NoOutputs);
+        }
+        self.submit_operations(false);
 
-                    self.data_buffers.push(weights.clone());
-                    let weights_index: usize = self.data_buffers.len() - 1;
+        Ok(self
+            .output_buffer_indices
+            .iter()
+            .map(|output_index| self.data_buffers[*output_index].clone())
+            .collect())
+    }
 
-                    self.data_buffers.push(bias.clone());
-                    let bias_index: usize = self.data_buffers.len() - 1;
+    // Overwrites the HostToDevice buffers with new input, one tensor per HostToDevice
+    // in the order they appear in the graph. Nothing is written unless every input
+    // has the shape the graph was built with.
+    fn bind_inputs(&mut self, inputs: &[&Tensor2D]) -> Result<(), GraphError> {
+        if inputs.len() != self.input_buffer_indices.len() {
+            return Err(GraphError::GraphInputCount {
+                expected: self.input_buffer_indices.len(),
+                actual: inputs.len(),
+            });
+        }
 
-                    self.data_buffers
-                        .push(Tensor2D::new(0.0, bias.row_count, bias.column_count));
-                    let output_index: usize = self.data_buffers.len() - 1;
+        for (input, (buffer_index, node_id)) in inputs
+            .iter()
+            .zip(self.input_buffer_indices.iter().zip(&self.input_node_ids))
+        {
+            let buffer: &Tensor2D = &self.data_buffers[*buffer_index];
+            if input.row_count != buffer.row_count || input.column_count != buffer.column_count {
+                return Err(GraphError::ShapeMismatch {
+                    index: *node_id,
+                    tensor: "input",
+                    expected: (buffer.row_count, buffer.column_count),
+                    actual: (input.row_count, input.column_count),
+                });
+            }
+        }
 
-                    let buffer_indices: Vec<usize> =
-                        vec![input_index, weights_index, bias_index, output_index];
-                    let node: Node = Node::new(new_key.clone(), key, buffer_indices);
-                    self.nodes.push(node);
+        for (input, buffer_index) in inputs.iter().zip(&self.input_buffer_indices) {
+            self.data_buffers[*buffer_index]
+                .data
+                .copy_from_slice(&input.data);
+        }
 
-                    let key: NodeOperator = NodeOperator::Transfer;
-                    let new_key: String = Self::get_new_key(&mut operator_counts, &key);
-                    let buffer_indices: Vec<usize> = vec![output_index];
-                    let node: Node = Node::new(new_key, key, buffer_indices);
-                    self.nodes.push(node);
-                }
-                LinearReLUSoftmaxFused { weights, bias } => {
-                    let key: NodeOperator = NodeOperator::LinearReLUSoftmax;
-                    let input_index: usize =
-                        Self::verify_previous_node_and_get_index(&self.nodes, &key);
-                    let new_key: String = Self::get_new_key(&mut operator_counts, &key);
+        Ok(())
+    }
 
-                    self.data_buffers.push(weights.clone());
-                    let weights_index: usize = self.data_buffers.len() - 1;
+    // Runs the graph on new input without rebuilding it, the input has to
+    // have the same shape as the one the graph was built with.
+    pub fn run_with(&mut self, input: &Tensor2D) -> Tensor2D {
+        self.try_run_with(input)
+            .unwrap_or_else(|error| panic!("Failed to run a CPU computational graph! {}", error))
+    }
 
-                    self.data_buffers.push(bias.clone());
-                    let bias_index: usize = self.data_buffers.len() - 1;
+    pub fn try_run_with(&mut self, input: &Tensor2D) -> Result<Tensor2D, GraphError> {
+        self.bind_inputs(&[input])?;
+        self.try_run()
+    }
 
-                    // This should be more flexible, but Softmax always outputs a flattened vector
-                    self.data_buffers
-                        .push(Tensor2D::new(0.0, bias.row_count, bias.column_count));
-                    let output_index: usize = self.data_buffers.len() - 1;
+    // The run_with version of run_outputs for graphs with several HostToDevice nodes.
+    pub fn run_outputs_with(&mut self, inputs: &[&Tensor2D]) -> Vec<Tensor2D> {
+        self.try_run_outputs_with(inputs)
+            .unwrap_or_else(|error| panic!("Failed to run a CPU computational graph! {}", error))
+    }
 
-                    let buffer_indices: Vec<usize> =
-                        vec![input_index, weights_index, bias_index, output_index];
-                    let node: Node = Node::new(new_key.clone(), key, buffer_indices);
-                    self.nodes.push(node);
+    pub fn try_run_outputs_with(
+        &mut self,
+        inputs: &[&Tensor2D],
+    ) -> Result<Vec<Tensor2D>, GraphError> {
+        self.bind_inputs(inputs)?;
+        self.try_run_outputs()
+    }
 
-                    let key: NodeOperator = NodeOperator::Transfer;
-                    let new_key: String = Self::get_new_key(&mut operator_counts, &key);
-                    let buffer_indices: Vec<usize> = vec![output_index];
-                    let node: Node = Node::new(new_key, key, buffer_indices);
-                    self.nodes.push(node);
+    // A batch is stacked along the rows of the buffer of the only HostToDevice node.
+    // As every node keeps the rows of its input, every other buffer then holds
+    // the samples stacked along its rows as well.
+    fn batch_input(&self) -> Result<(usize, NodeId), GraphError> {
+        match (
+            self.input_buffer_indices.as_slice(),
+            self.input_node_ids.as_slice(),
+        ) {
+            ([buffer_index], [node_id]) => Ok((*buffer_index, *node_id)),
+            _ => Err(GraphError::BatchInputCount {
+                input_count: self.input_buffer_indices.len(),
+            }),
+        }
+    }
+
+    // The parameters are pushed as pairs of weights and bias
+    fn is_weights(&self, buffer_index: usize) -> bool {
+        self.parameter_buffer_indices
+            .iter()
+            .step_by(2)
+            .any(|weights_index| *weights_index == buffer_index)
+    }
+
+    // Column vectors keep one row per row of a sample when stacked, but a row vector
+    // becomes one row per sample, which the elementwise operators can't broadcast
+    // over the rows of each sample. The weights aren't stacked, so they are skipped.
+    fn check_batch_broadcast(&self) -> Result<(), GraphError> {
+        for node in &self.nodes {
+            let broadcasts: bool = matches!(
+                node.operator,
+                NodeOperator::Add
+                    | NodeOperator::Subtract
+                    | NodeOperator::Multiply
+                    | NodeOperator::Divide
+                    | NodeOperator::FusedElementwise
+            );
+            let (output_index, input_indices): (&usize, &[usize]) =
+                match node.buffer_indices.split_last() {
+                    Some(indices) if broadcasts => indices,
+                    _ => continue,
+                };
+
+            let output: &Tensor2D = &self.data_buffers[*output_index];
+            for input_index in input_indices {
+                if 1 < output.row_count
+                    && self.data_buffers[*input_index].row_count == 1
+                    && !self.is_weights(*input_index)
+                {
+                    return Err(GraphError::BatchBroadcast {
+                        node: node.name.clone(),
+                    });
                 }
             }
+        }
+
+        Ok(())
+    }
 
-            operator_index += 1;
+    fn allocate_batch_buffers(&mut self, batch_size: usize) {
+        if self.batch_size == batch_size && self.batch_buffers.len() == self.data_buffers.len() {
+            return;
         }
-        self.graph_operators_are_valid = true;
+
+        self.batch_buffers = self
+            .data_buffers
+            .iter()
+            .enumerate()
+            .map(|(buffer_index, buffer)| {
+                let row_count: usize = if self.is_weights(buffer_index) {
+                    buffer.row_count
+                } else {
+                    batch_size * buffer.row_count
+                };
+                Tensor2D::new(0.0, row_count, buffer.column_count)
+            })
+            .collect();
+        self.batch_size = batch_size;
     }
 
-    // In a more correct system, not meant for teaching/learning
-    // we might find the correct data buffers here and pass the correct
-    // buffers explicitly to the functions. Or at the very least
-    // enforce more correctness in the data passed along to
-    // the CPUNodeOperator functions.
-    fn submit_operator_commands(node_vector: &Vec<Node>, data_buffers: &mut [Tensor2D]) {
-        for node in node_vector {
+    // The parameters might have been trained since the last batch, so they are
+    // copied on every batched run. Every sample adds the same bias,
+    // which is repeated once per sample.
+    fn copy_batch_parameters(&mut self) {
+        for parameters in self.parameter_buffer_indices.chunks_exact(2) {
+            let (weights_index, bias_index): (usize, usize) = (parameters[0], parameters[1]);
+
+            let weights: &Tensor2D = &self.data_buffers[weights_index];
+            self.batch_buffers[weights_index].data[0..weights.len()]
+                .copy_from_slice(&weights.data[0..weights.len()]);
+
+            let bias: &Tensor2D = &self.data_buffers[bias_index];
+            for sample in self.batch_buffers[bias_index]
+                .data
+                .chunks_exact_mut(bias.len())
+            {
+                sample.copy_from_slice(&bias.data[0..bias.len()]);
+            }
+        }
+    }
+
+    // Every sample has to have the shape of the input the graph was built with
+    fn check_batch(&self, samples: &[Tensor2D]) -> Result<(), GraphError> {
+        let (buffer_index, node_id): (usize, NodeId) = self.batch_input()?;
+        if samples.is_empty() {
+            return Err(GraphError::EmptyBatch);
+        }
+
+        let input: &Tensor2D = &self.data_buffers[buffer_index];
+        for sample in samples {
+            if sample.row_count != input.row_count || sample.column_count != input.column_count {
+                return Err(GraphError::ShapeMismatch {
+                    index: node_id,
+                    tensor: "input",
+                    expected: (input.row_count, input.column_count),
+                    actual: (sample.row_count, sample.column_count),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    // Writes a stacked batch to the batch buffers and returns the number of samples
+    fn bind_stacked(&mut self, stacked: &Tensor2D) -> Result<usize, GraphError> {
+        let (buffer_index, _): (usize, NodeId) = self.batch_input()?;
+        if stacked.len() == 0 {
+            return Err(GraphError::EmptyBatch);
+        }
+
+        let input: &Tensor2D = &self.data_buffers[buffer_index];
+        if stacked.column_count != input.column_count
+            || !stacked.row_count.is_multiple_of(input.row_count)
+        {
+            return Err(GraphError::BatchShape {
+                sample: (input.row_count, input.column_count),
+                actual: (stacked.row_count, stacked.column_count),
+            });
+        }
+        self.check_batch_broadcast()?;
+        let batch_size: usize = stacked.row_count / input.row_count;
+
+        self.allocate_batch_buffers(batch_size);
+        self.copy_batch_parameters();
+        self.batch_buffers[buffer_index]
+            .data
+            .copy_from_slice(&stacked.data[0..stacked.len()]);
+
+        Ok(batch_size)
+    }
+
+    // Runs every sample through the graph in a single pass, returning the output
+    // of the last DeviceToHost node for each sample. Every sample has to have the
+    // shape of the input the graph was built with, and the graph can only have
+    // a single HostToDevice node. Softmax is taken per sample, but the elementwise
+    // operators can't broadcast a single row, such as a mean along Axis::Columns, per sample.
+    pub fn run_batch(&mut self, samples: &[Tensor2D]) -> Vec<Tensor2D> {
+        self.try_run_batch(samples)
+            .unwrap_or_else(|error| panic!("Failed to run a CPU computational graph! {}", error))
+    }
+
+    pub fn try_run_batch(&mut self, samples: &[Tensor2D]) -> Result<Vec<Tensor2D>, GraphError> {
+        self.check_batch(samples)?;
+        let output: Tensor2D = self.try_run_stacked(&Tensor2D::stack(samples))?;
+
+        Ok(output.unstack(samples.len()))
+    }
+
+    // The run_batch version for samples already stacked along the rows, see Tensor2D::stack.
+    // The outputs of the samples are returned stacked in the same order.
+    pub fn run_stacked(&mut self, stacked: &Tensor2D) -> Tensor2D {
+        self.try_run_stacked(stacked)
+            .unwrap_or_else(|error| panic!("Failed to run a CPU computational graph! {}", error))
+    }
+
+    pub fn try_run_stacked(&mut self, stacked: &Tensor2D) -> Result<Tensor2D, GraphError> {
+        let output_index: usize = *self
+            .output_buffer_indices
+            .last()
+            .ok_or(GraphError::NoOutputs)?;
+        self.bind_stacked(stacked)?;
+        self.submit_operations(true);
+
+        Ok(self.batch_buffers[output_index].clone())
+    }
+
+    // Lets intermediate results share data buffers once they are no longer needed.
+    // Only call this for inference, the backward pass needs every intermediate result.
+    pub fn plan_memory(&mut self) -> MemoryPlan {
+        let mut pinned_buffers: Vec<usize> = self.parameter_buffer_indices.clone();
+        pinned_buffers.extend(&self.output_buffer_indices);
+        for node in &self.nodes {
+            if node.operator == NodeOperator::Input {
+                pinned_buffers.extend(&node.buffer_indices);
+            }
+        }
+
+        let buffers: Vec<BufferDescription> = self
+            .data_buffers
+            .iter()
+            .enumerate()
+            .map(|(buffer_index, buffer)| BufferDescription {
+                row_count: buffer.row_count,
+                column_count: buffer.column_count,
+                is_pinned: pinned_buffers.contains(&buffer_index),
+            })
+            .collect();
+        let node_buffer_indices: Vec<Vec<usize>> = self
+            .nodes
+            .iter()
+            .map(|node| node.buffer_indices.clone())
+            .collect();
+
+        let plan: MemoryPlan = plan_memory(&node_buffer_indices, &buffers);
+
+        let data_buffers: Vec<Tensor2D> = std::mem::take(&mut self.data_buffers);
+        self.data_buffers = apply_memory_plan(data_buffers, &plan);
+        for node in &mut self.nodes {
+            for buffer_index in &mut node.buffer_indices {
+                *buffer_index = plan.buffer_assignments[*buffer_index];
+            }
+        }
+        for buffer_index in &mut self.output_buffer_indices {
+            *buffer_index = plan.buffer_assignments[*buffer_index];
+        }
+        for buffer_index in &mut self.input_buffer_indices {
+            *buffer_index = plan.buffer_assignments[*buffer_index];
+        }
+        for buffer_index in &mut self.parameter_buffer_indices {
+            *buffer_index = plan.buffer_assignments[*buffer_index];
+        }
+        self.gradient_buffers.clear();
+        self.batch_buffers.clear();
+        self.memory_is_planned = true;
+
+        plan
+    }
+
+    // The nodes compute_nodes produced and the buffers they use, after fusion
+    // and after memory planning if plan_memory has been called.
+    pub fn export_graph(&self) -> ExportGraph {
+        let roles: Vec<BufferRole> = buffer_roles(
+            self.data_buffers.len(),
+            &self.input_buffer_indices,
+            &self.output_buffer_indices,
+            &self.parameter_buffer_indices,
+        );
+        let buffers: Vec<ExportBuffer> = self
+            .data_buffers
+            .iter()
+            .zip(roles)
+            .map(|(buffer, role)| ExportBuffer {
+                row_count: buffer.row_count,
+                column_count: buffer.column_count,
+                role,
+            })
+            .collect();
+
+        let nodes: Vec<ExportNode> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let name: &str = &node.name;
+                let indices: &[usize] = &node.buffer_indices;
+                match node.operator {
+                    NodeOperator::Input => ExportNode::source(name, indices[0]),
+                    NodeOperator::Output | NodeOperator::Transfer => {
+                        ExportNode::sink(name, indices)
+                    }
+                    NodeOperator::LinearReLU => ExportNode::compute(name, indices)
+                        .with_fused_operators(linear_relu_operator_names(false)),
+                    NodeOperator::LinearReLUSoftmax => ExportNode::compute(name, indices)
+                        .with_fused_operators(linear_relu_operator_names(true)),
+                    NodeOperator::FusedElementwise => ExportNode::compute(name, indices)
+                        .with_fused_operators(match &node.fusion {
+                            Some(fusion) => fused_operator_names(fusion),
+                            None => Vec::<String>::new(),
+                        }),
+                    _ => ExportNode::compute(name, indices).with_axis(node.axis),
+                }
+            })
+            .collect();
+
+        ExportGraph {
+            name: "GraphRunner".to_string(),
+            nodes,
+            buffers,
+        }
+    }
+
+    // Graphviz DOT of the compiled nodes
+    pub fn to_dot(&self) -> String {
+        self.export_graph().to_dot()
+    }
+
+    // Mermaid flowchart of the compiled nodes
+    pub fn to_mermaid(&self) -> String {
+        self.export_graph().to_mermaid()
+    }
+
+    // Sets every gradient to 0, including the accumulated parameter gradients.
+    pub fn zero_gradients(&mut self) {
+        if self.gradient_buffers.len() != self.data_buffers.len() {
+            self.gradient_buffers = self
+                .data_buffers
+                .iter()
+                .map(|buffer| Tensor2D::new(0.0, buffer.row_count, buffer.column_count))
+                .collect();
+        } else {
+            for gradient in &mut self.gradient_buffers {
+                gradient.data.iter_mut().for_each(|element| *element = 0.0);
+            }
+        }
+    }
+
+    fn submit_backward_commands(
+        node_vector: &[Node],
+        data_buffers: &[Tensor2D],
+        gradient_buffers: &mut [Tensor2D],
+    ) {
+        // The nodes are in topological order, so going through them in reverse
+        // guarantees a node's output gradient is complete before it is used.
+        for node in node_vector.iter().rev() {
             match node.operator {
                 NodeOperator::Input => {}
                 NodeOperator::Output => {}
                 NodeOperator::Transfer => {}
                 NodeOperator::Linear => {
-                    nodes::linear(node, data_buffers);
+                    nodes::linear_backward(node, data_buffers, gradient_buffers);
                 }
                 NodeOperator::ReLU => {
-                    nodes::relu(node, data_buffers);
+                    nodes::relu_backward(node, data_buffers, gradient_buffers);
                 }
                 NodeOperator::Softmax => {
-                    nodes::softmax(node, data_buffers);
+                    nodes::softmax_backward(node, data_buffers, gradient_buffers);
                 }
                 NodeOperator::LinearReLU => {
-                    nodes::linear_relu(node, data_buffers);
+                    nodes::linear_relu_backward(node, data_buffers, gradient_buffers);
                 }
                 NodeOperator::LinearReLUSoftmax => {
-                    nodes::linear_relu_softmax(node, data_buffers);
+                    nodes::linear_relu_softmax_backward(node, data_buffers, gradient_buffers);
+                }
+                NodeOperator::Add => {
+                    nodes::add_backward(node, data_buffers, gradient_buffers);
+                }
+                NodeOperator::Subtract => {
+                    nodes::subtract_backward(node, data_buffers, gradient_buffers);
+                }
+                NodeOperator::Multiply => {
+                    nodes::multiply_backward(node, data_buffers, gradient_buffers);
+                }
+                NodeOperator::Divide => {
+                    nodes::divide_backward(node, data_buffers, gradient_buffers);
+                }
+                NodeOperator::FusedElementwise => {
+                    nodes::fused_elementwise_backward(node, data_buffers, gradient_buffers);
+                }
+                NodeOperator::LogSoftmax => {
+                    nodes::log_softmax_backward(node, data_buffers, gradient_buffers);
+                }
+                NodeOperator::Sum => {
+                    nodes::reduce_backward(node, data_buffers, gradient_buffers, Reduction::Sum);
+                }
+                NodeOperator::Max => {
+                    nodes::reduce_backward(node, data_buffers, gradient_buffers, Reduction::Max);
+                }
+                NodeOperator::Mean => {
+                    nodes::reduce_backward(node, data_buffers, gradient_buffers, Reduction::Mean);
+                }
+                NodeOperator::ArgMax => {
+                    nodes::reduce_backward(node, data_buffers, gradient_buffers, Reduction::ArgMax);
+                }
+                NodeOperator::QuantizedLinear => {
+                    nodes::quantized_linear_backward(node, data_buffers, gradient_buffers);
                 }
             }
         }
     }
 
-    pub fn run(&mut self) -> Tensor2D {
-        if !self.graph_operators_are_valid {
+    // Backpropagates the gradient of the loss with respect to the output of
+    // the last DeviceToHost node, using the values from the most recent run.
+    // Parameter gradients are accumulated until zero_gradients is called,
+    // every other gradient is only valid until the next backward pass.
+    pub fn backward(&mut self, output_gradient: &Tensor2D) {
+        if !self.nodes_are_valid {
             panic!(
-                "Tried to run the a CPU computational graph with an unvalidated graph_operators!"
+                "Tried to run the backward pass of a CPU computational graph with invalid nodes!"
             );
         }
 
-        if !self.data_buffers_are_valid {
-            panic!("Tried to run the a CPU computational graph with an unvalidated data_buffers!");
+        if self.memory_is_planned {
+            panic!(
+                "Tried to run the backward pass of a CPU computational graph after plan_memory, the intermediate results have been overwritten!"
+            );
+        }
+
+        let output_index: usize = *self.output_buffer_indices.last().expect(
+            "Tried to run the backward pass of a CPU computational graph without any outputs!",
+        );
+        let output: &Tensor2D = &self.data_buffers[output_index];
+        assert_eq!(
+            (output.row_count, output.column_count),
+            (output_gradient.row_count, output_gradient.column_count),
+            "\nMismatch - output & output_gradient\noutput - rows: {} columns: {}.\n output_gradient - rows: {} columns: {}.",
+            output.row_count,
+            output.column_count,
+            output_gradient.row_count,
+            output_gradient.column_count
+        );
+
+        if self.gradient_buffers.len() != self.data_buffers.len() {
+            self.zero_gradients();
+        }
+
+        // Clear everything but the parameter gradients left over from the last backward pass
+        for (buffer_index, gradient) in self.gradient_buffers.iter_mut().enumerate() {
+            if !self.parameter_buffer_indices.contains(&buffer_index) {
+                gradient.data.iter_mut().for_each(|element| *element = 0.0);
+            }
+        }
+
+        let seed: &mut Tensor2D = &mut self.gradient_buffers[output_index];
+        for index in 0..seed.len() {
+            seed.data[index] += output_gradient.data[index];
+        }
+
+        Self::submit_backward_commands(&self.nodes, &self.data_buffers, &mut self.gradient_buffers);
+    }
+
+    // The weights and bias of every linear node, in the order they appear in the graph.
+    pub fn parameters(&self) -> Vec<&Tensor2D> {
+        self.parameter_buffer_indices
+            .iter()
+            .map(|buffer_index| &self.data_buffers[*buffer_index])
+            .collect()
+    }
+
+    // Parameters are pushed to the data buffers in the order they appear in the graph,
+    // so the ordering matches parameters().
+    pub fn parameters_mut(&mut self) -> Vec<&mut Tensor2D> {
+        self.data_buffers
+            .iter_mut()
+            .enumerate()
+            .filter(|(buffer_index, _)| self.parameter_buffer_indices.contains(buffer_index))
+            .map(|(_, parameter)| parameter)
+            .collect()
+    }
+
+    // The gradients matching parameters()
+    pub fn parameter_gradients(&self) -> Vec<&Tensor2D> {
+        if self.gradient_buffers.len() != self.data_buffers.len() {
+            panic!("Tried to get the parameter gradients of a CPU computational graph before running backward!");
         }
 
-        Self::submit_operator_commands(&self.nodes, &mut self.data_buffers);
+        self.parameter_buffer_indices
+            .iter()
+            .map(|buffer_index| &self.gradient_buffers[*buffer_index])
+            .collect()
+    }
+
+    // Updates the parameters with the accumulated gradients.
+    pub fn step(&mut self, optimizer: &mut Optimizer) {
+        if self.gradient_buffers.len() != self.data_buffers.len() {
+            panic!("Tried to take an optimizer step in a CPU computational graph before running backward!");
+        }
+
+        let mut parameters: Vec<&mut Tensor2D> = Vec::<&mut Tensor2D>::new();
+        let mut gradients: Vec<&Tensor2D> = Vec::<&Tensor2D>::new();
+        for (buffer_index, parameter) in self.data_buffers.iter_mut().enumerate() {
+            if self.parameter_buffer_indices.contains(&buffer_index) {
+                parameters.push(parameter);
+                gradients.push(&self.gradient_buffers[buffer_index]);
+            }
+        }
+
+        optimizer.step(parameters, gradients);
+    }
+
+    // Runs the graph, computes the cross entropy of the last output against
+    // the target, backpropagates and updates the parameters.
+    // Returns the loss from before the update.
+    pub fn train_step(&mut self, target: &Tensor2D, optimizer: &mut Optimizer) -> f32 {
+        let prediction: Tensor2D = self.run();
+        let loss: f32 = cross_entropy(&prediction, target);
+
+        self.zero_gradients();
+        self.backward(&cross_entropy_gradient(&prediction, target));
+        self.step(optimizer);
 
-        // Based on the restrictions we have put on our graph, the last node has to be
-        // the output.
-        self.data_buffers[self.nodes[self.nodes.len() - 2].buffer_indices[0]].clone()
+        loss
     }
 }
@@ -1,73 +1,165 @@
 use std::collections::HashMap;
 
-use wgpu::{BufferSlice, CommandEncoder, ComputePipeline, ShaderModule};
+use wgpu::{Buffer, BufferSlice, CommandEncoder, ComputePipeline, QuerySet, ShaderModule};
 
 use crate::shared::graph_operators::GraphOperator::*;
 use crate::shared::tensor2d::Tensor2D;
-use crate::shared::tensor2d_gpu::Tensor2DGPU;
-use crate::shared::{gpu_utilities::GPUHandles, graph_operators::GraphOperator};
+use crate::shared::tensor2d_axis::Axis;
+use crate::shared::tensor2d_gpu::{PackedTensor2DGPU, Tensor2DGPU};
+use crate::shared::tensor2d_precision::PackedTensor2D;
+use crate::shared::{
+    gpu_utilities::GPUHandles,
+    graph_operators::{graph_nodes_from_operators, GraphNode, GraphOperator, NodeId},
+};
 
-use super::graph_validation::validate_graph_operators;
+use super::fusion::{plan_fusion, FusedElementwise, FusionGroup, FusionPattern, FusionPlan};
+use super::graph_error::GraphError;
+use super::graph_export::{
+    buffer_roles, fused_operator_names, linear_relu_operator_names, BufferRole, ExportBuffer,
+    ExportGraph, ExportNode,
+};
+use super::graph_validation::{
+    get_consumers, topological_sort, validate_graph_nodes, validate_graph_operators,
+};
+use super::linear_tuning::{tuned_linear_kernel, LinearKernel};
+use super::memory_planner::{apply_memory_plan, plan_memory, BufferDescription, MemoryPlan};
 use super::nodes_gpu::{self, NodeGPU, NodeOperatorGPU};
+use super::profiling::{NodeTiming, ProfileReport};
 
+// A GraphRunnerGPU can only be built from a valid graph, the try_ functions return
+// what is wrong with the graph instead of panicking.
 pub struct GraphRunnerGPU {
-    graph_operators_are_valid: bool,
     nodes: Vec<NodeGPU>,
     nodes_are_valid: bool,
     data_buffers: Vec<Tensor2DGPU>,
-    data_buffers_are_valid: bool,
     fuse_operators: bool,
     use_cache: bool,
     shader_cache: HashMap<String, ShaderModule>,
     pipeline_cache: HashMap<String, ComputePipeline>,
+    // The kernel Linear, LinearReLU and LinearReLUSoftmax nodes run with. Defaults to
+    // whichever kernel the autotuner cached for this adapter, see graph::linear_tuning.
+    linear_kernel: LinearKernel,
+    output_buffer_indices: Vec<usize>,
+    // The data buffer and graph node of every HostToDevice, which run_with writes to
+    input_buffer_indices: Vec<usize>,
+    input_node_ids: Vec<NodeId>,
+    // The weights and biases of every linear node
+    parameter_buffer_indices: Vec<usize>,
+    // Batched runs use their own buffers, which hold batch_size samples stacked
+    // along the rows. The weights are shared by every sample and aren't stacked.
+    batch_size: usize,
+    batch_buffers: Vec<Tensor2DGPU>,
 }
 
 impl GraphRunnerGPU {
     pub fn new(
         gpu_handles: &GPUHandles,
-        graph_operators: &Vec<GraphOperator>,
+        graph_operators: &[GraphOperator],
         fuse_operators: bool,
         use_cache: bool,
     ) -> Self {
+        Self::try_new(gpu_handles, graph_operators, fuse_operators, use_cache)
+            .unwrap_or_else(|error| panic!("Invalid graph sent to GraphRunnerGPU::new! {}", error))
+    }
+
+    pub fn try_new(
+        gpu_handles: &GPUHandles,
+        graph_operators: &[GraphOperator],
+        fuse_operators: bool,
+        use_cache: bool,
+    ) -> Result<Self, GraphError> {
+        validate_graph_operators(graph_operators)?;
+        let graph_nodes: Vec<GraphNode> = graph_nodes_from_operators(graph_operators);
+
+        Self::build(gpu_handles, &graph_nodes, fuse_operators, use_cache)
+    }
+
+    // Build a runner from a graph where every node names its inputs,
+    // which allows for branches and multiple outputs.
+    pub fn from_graph_nodes(
+        gpu_handles: &GPUHandles,
+        graph_nodes: &[GraphNode],
+        fuse_operators: bool,
+        use_cache: bool,
+    ) -> Self {
+        Self::try_from_graph_nodes(gpu_handles, graph_nodes, fuse_operators, use_cache)
+            .unwrap_or_else(|error| {
+                panic!(
+                    "Invalid graph sent to GraphRunnerGPU::from_graph_nodes! {}",
+                    error
+                )
+            })
+    }
+
+    pub fn try_from_graph_nodes(
+        gpu_handles: &GPUHandles,
+        graph_nodes: &[GraphNode],
+        fuse_operators: bool,
+        use_cache: bool,
+    ) -> Result<Self, GraphError> {
+        validate_graph_nodes(graph_nodes)?;
+
+        Self::build(gpu_handles, graph_nodes, fuse_operators, use_cache)
+    }
+
+    fn build(
+        gpu_handles: &GPUHandles,
+        graph_nodes: &[GraphNode],
+        fuse_operators: bool,
+        use_cache: bool,
+    ) -> Result<Self, GraphError> {
         let mut shader_cache: HashMap<String, ShaderModule> =
             HashMap::<String, ShaderModule>::new();
         let mut pipeline_cache: HashMap<String, ComputePipeline> =
             HashMap::<String, ComputePipeline>::new();
+        let linear_kernel: LinearKernel = tuned_linear_kernel(&gpu_handles.adapter_info);
 
         if use_cache {
             Self::populate_caches(
                 gpu_handles,
                 fuse_operators,
+                &linear_kernel,
                 &mut shader_cache,
                 &mut pipeline_cache,
             );
         }
 
         let mut runner: GraphRunnerGPU = GraphRunnerGPU {
-            graph_operators_are_valid: false,
             nodes: Vec::<NodeGPU>::new(),
             nodes_are_valid: false,
             data_buffers: Vec::<Tensor2DGPU>::new(),
-            data_buffers_are_valid: false,
             fuse_operators,
             use_cache,
             shader_cache,
             pipeline_cache,
+            linear_kernel,
+            output_buffer_indices: Vec::<usize>::new(),
+            input_buffer_indices: Vec::<usize>::new(),
+            input_node_ids: Vec::<NodeId>::new(),
+            parameter_buffer_indices: Vec::<usize>::new(),
+            batch_size: 0,
+            batch_buffers: Vec::<Tensor2DGPU>::new(),
         };
-        runner.graph_operators_are_valid = validate_graph_operators(graph_operators);
 
-        runner.compute_nodes(gpu_handles, graph_operators, fuse_operators);
-        runner
+        runner.compute_nodes(gpu_handles, graph_nodes, fuse_operators)?;
+        Ok(runner)
     }
 
     fn populate_caches(
         gpu_handles: &GPUHandles,
         fuse_operators: bool,
+        linear_kernel: &LinearKernel,
         shader_cache: &mut HashMap<String, ShaderModule>,
         pipeline_cache: &mut HashMap<String, ComputePipeline>,
     ) {
         //Linear,
-        nodes_gpu::build_linear_elements(gpu_handles, shader_cache, pipeline_cache, false);
+        nodes_gpu::build_linear_elements(
+            gpu_handles,
+            shader_cache,
+            pipeline_cache,
+            false,
+            linear_kernel,
+        );
 
         //ReLU,
         nodes_gpu::build_relu_elements(gpu_handles, shader_cache, pipeline_cache);
@@ -75,9 +167,24 @@ impl GraphRunnerGPU {
         //Softmax,
         nodes_gpu::build_softmax_elements(gpu_handles, shader_cache, pipeline_cache);
 
+        //Add, Subtract, Multiply, Divide
+        nodes_gpu::build_elementwise_elements(gpu_handles, shader_cache, pipeline_cache);
+
+        //Softmax along an axis, LogSoftmax, Sum, Max, Mean, ArgMax
+        nodes_gpu::build_axis_elements(gpu_handles, shader_cache, pipeline_cache);
+
+        //QuantizedLinear,
+        nodes_gpu::build_quantized_linear_elements(gpu_handles, shader_cache, pipeline_cache);
+
         if fuse_operators {
             //LinearReLU,
-            nodes_gpu::build_linear_elements(gpu_handles, shader_cache, pipeline_cache, true);
+            nodes_gpu::build_linear_elements(
+                gpu_handles,
+                shader_cache,
+                pipeline_cache,
+                true,
+                linear_kernel,
+            );
         }
     }
 
@@ -94,20 +201,347 @@ impl GraphRunnerGPU {
         format!("{:?}_{}", key, index)
     }
 
-    fn verify_previous_node_and_get_index(nodes: &Vec<NodeGPU>, key: &NodeOperatorGPU) -> usize {
-        let previous_node: &NodeGPU = &nodes[nodes.len() - 1];
-        match previous_node.operator {
-            NodeOperatorGPU::DeviceToDevice => {}
-            _ => {
-                panic!("Invalid node operator! Was expecting DeviceToDevice before {:?}, but found {:?}", *key, previous_node.operator);
-            }
+    // Instead of assuming the input is whatever the previous node produced
+    // we look up the buffer written by the node named as input.
+    // input is the position in the graph node's list of inputs, which is
+    // always 0 except for the second operand of the elementwise operators.
+    fn get_input_buffer_index(
+        output_indices: &[Option<usize>],
+        node_id: NodeId,
+        graph_node: &GraphNode,
+        input: usize,
+    ) -> Result<usize, GraphError> {
+        match graph_node.inputs.get(input) {
+            Some(input_node) => Self::get_output_buffer_index(output_indices, node_id, *input_node),
+            None => Err(GraphError::InputCount {
+                index: node_id,
+                operator: graph_node.operator.name(),
+                expected: input + 1,
+                actual: graph_node.inputs.len(),
+            }),
+        }
+    }
+
+    // Fails if the input node hasn't produced an output yet
+    fn get_output_buffer_index(
+        output_indices: &[Option<usize>],
+        node_id: NodeId,
+        input_node: NodeId,
+    ) -> Result<usize, GraphError> {
+        match output_indices.get(input_node) {
+            Some(Some(buffer_index)) => Ok(*buffer_index),
+            _ => Err(GraphError::MissingInput {
+                index: node_id,
+                input: input_node,
+            }),
         }
+    }
+
+    fn push_device_to_device_node(
+        &mut self,
+        operator_counts: &mut HashMap<NodeOperatorGPU, u32>,
+        output_index: usize,
+    ) {
+        let key: NodeOperatorGPU = NodeOperatorGPU::DeviceToDevice;
+        let new_key: String = Self::get_new_key(operator_counts, &key);
+        let buffer_indices: Vec<usize> = vec![output_index];
+        let node: NodeGPU = NodeGPU::new(new_key, key, buffer_indices);
+        self.nodes.push(node);
+    }
+
+    fn push_parameters(
+        &mut self,
+        gpu_handles: &GPUHandles,
+        new_key: &str,
+        weights: &Tensor2D,
+        bias: &Tensor2D,
+    ) -> (usize, usize) {
+        self.data_buffers.push(Tensor2DGPU::from_tensor2d(
+            gpu_handles,
+            &format!("{}_{}", new_key, "weights"),
+            weights,
+        ));
+        let weights_index: usize = self.data_buffers.len() - 1;
+
+        self.data_buffers.push(Tensor2DGPU::from_tensor2d(
+            gpu_handles,
+            &format!("{}_{}", new_key, "bias"),
+            bias,
+        ));
+        let bias_index: usize = self.data_buffers.len() - 1;
+        self.parameter_buffer_indices.push(weights_index);
+        self.parameter_buffer_indices.push(bias_index);
+
+        (weights_index, bias_index)
+    }
+
+    fn push_linear_node(
+        &mut self,
+        gpu_handles: &GPUHandles,
+        operator_counts: &mut HashMap<NodeOperatorGPU, u32>,
+        key: NodeOperatorGPU,
+        input_index: usize,
+        weights: &Tensor2D,
+        bias: &Tensor2D,
+    ) -> usize {
+        let new_key: String = Self::get_new_key(operator_counts, &key);
+        let (weights_index, bias_index): (usize, usize) =
+            self.push_parameters(gpu_handles, &new_key, weights, bias);
+
+        self.data_buffers.push(Tensor2DGPU::new(
+            gpu_handles,
+            &format!("{}_{}", new_key, "output"),
+            0.0,
+            bias.row_count,
+            bias.column_count,
+        ));
+        let output_index: usize = self.data_buffers.len() - 1;
+
+        let buffer_indices: Vec<usize> = vec![input_index, weights_index, bias_index, output_index];
+        let node: NodeGPU = NodeGPU::new(new_key, key, buffer_indices);
+        self.nodes.push(node);
+
+        self.push_device_to_device_node(operator_counts, output_index);
+        output_index
+    }
 
-        if previous_node.buffer_indices.len() == 1 {
-            previous_node.buffer_indices[0]
+    // The packed parameters are held by the node, only the input and output are data buffers
+    fn push_quantized_linear_node(
+        &mut self,
+        gpu_handles: &GPUHandles,
+        operator_counts: &mut HashMap<NodeOperatorGPU, u32>,
+        input_index: usize,
+        weights: &PackedTensor2D,
+        bias: &PackedTensor2D,
+    ) -> usize {
+        let key: NodeOperatorGPU = NodeOperatorGPU::QuantizedLinear;
+        let new_key: String = Self::get_new_key(operator_counts, &key);
+        let packed_weights: PackedTensor2DGPU = PackedTensor2DGPU::from_packed_tensor2d(
+            gpu_handles,
+            &format!("{}_{}", new_key, "weights"),
+            weights,
+        );
+        let packed_bias: PackedTensor2DGPU = PackedTensor2DGPU::from_packed_tensor2d(
+            gpu_handles,
+            &format!("{}_{}", new_key, "bias"),
+            bias,
+        );
+
+        self.data_buffers.push(Tensor2DGPU::new(
+            gpu_handles,
+            &format!("{}_{}", new_key, "output"),
+            0.0,
+            bias.row_count,
+            bias.column_count,
+        ));
+        let output_index: usize = self.data_buffers.len() - 1;
+
+        let buffer_indices: Vec<usize> = vec![input_index, output_index];
+        let node: NodeGPU =
+            NodeGPU::quantized_linear(new_key, buffer_indices, packed_weights, packed_bias);
+        self.nodes.push(node);
+
+        self.push_device_to_device_node(operator_counts, output_index);
+        output_index
+    }
+
+    // Note this is not inplace
+    fn push_unary_node(
+        &mut self,
+        gpu_handles: &GPUHandles,
+        operator_counts: &mut HashMap<NodeOperatorGPU, u32>,
+        key: NodeOperatorGPU,
+        input_index: usize,
+        flatten_output: bool,
+    ) -> usize {
+        let new_key: String = Self::get_new_key(operator_counts, &key);
+
+        let input_buffer: &Tensor2DGPU = &self.data_buffers[input_index];
+        let (row_count, column_count): (usize, usize) = if flatten_output {
+            (input_buffer.row_count * input_buffer.column_count, 1)
         } else {
-            panic!("There was more than one buffer index described in a DeviceToDevice operator. This is currently invalid behavior!");
+            (input_buffer.row_count, input_buffer.column_count)
+        };
+        self.data_buffers.push(Tensor2DGPU::new(
+            gpu_handles,
+            &format!("{}_{}", new_key, "output"),
+            0.0,
+            row_count,
+            column_count,
+        ));
+        let output_index: usize = self.data_buffers.len() - 1;
+
+        let buffer_indices: Vec<usize> = vec![input_index, output_index];
+        let node: NodeGPU = NodeGPU::new(new_key, key, buffer_indices);
+        self.nodes.push(node);
+
+        self.push_device_to_device_node(operator_counts, output_index);
+        output_index
+    }
+
+    // Unlike Softmax over the whole tensor, the axis operators don't flatten their output.
+    // The reductions keep one value per row, per column or for the whole tensor.
+    fn push_axis_node(
+        &mut self,
+        gpu_handles: &GPUHandles,
+        operator_counts: &mut HashMap<NodeOperatorGPU, u32>,
+        key: NodeOperatorGPU,
+        input_index: usize,
+        axis: Axis,
+    ) -> usize {
+        let new_key: String = Self::get_new_key(operator_counts, &key);
+
+        let input_buffer: &Tensor2DGPU = &self.data_buffers[input_index];
+        let input_shape: (usize, usize) = (input_buffer.row_count, input_buffer.column_count);
+        let (row_count, column_count): (usize, usize) = match key {
+            NodeOperatorGPU::Softmax | NodeOperatorGPU::LogSoftmax => input_shape,
+            _ => axis.reduced_shape(input_shape),
+        };
+        self.data_buffers.push(Tensor2DGPU::new(
+            gpu_handles,
+            &format!("{}_{}", new_key, "output"),
+            0.0,
+            row_count,
+            column_count,
+        ));
+        let output_index: usize = self.data_buffers.len() - 1;
+
+        let buffer_indices: Vec<usize> = vec![input_index, output_index];
+        let node: NodeGPU = NodeGPU::along(new_key, key, buffer_indices, axis);
+        self.nodes.push(node);
+
+        self.push_device_to_device_node(operator_counts, output_index);
+        output_index
+    }
+
+    // The output has the shape of the two inputs after broadcasting.
+    fn push_elementwise_node(
+        &mut self,
+        gpu_handles: &GPUHandles,
+        operator_counts: &mut HashMap<NodeOperatorGPU, u32>,
+        key: NodeOperatorGPU,
+        left_index: usize,
+        right_index: usize,
+    ) -> usize {
+        let new_key: String = Self::get_new_key(operator_counts, &key);
+
+        let left: &Tensor2DGPU = &self.data_buffers[left_index];
+        let right: &Tensor2DGPU = &self.data_buffers[right_index];
+        let (row_count, column_count): (usize, usize) = Tensor2D::broadcast_shape(
+            (left.row_count, left.column_count),
+            (right.row_count, right.column_count),
+        )
+        .expect("Invalid graph! Inputs to an elementwise operator can't be broadcast.");
+        self.data_buffers.push(Tensor2DGPU::new(
+            gpu_handles,
+            &format!("{}_{}", new_key, "output"),
+            0.0,
+            row_count,
+            column_count,
+        ));
+        let output_index: usize = self.data_buffers.len() - 1;
+
+        let buffer_indices: Vec<usize> = vec![left_index, right_index, output_index];
+        let node: NodeGPU = NodeGPU::new(new_key, key, buffer_indices);
+        self.nodes.push(node);
+
+        self.push_device_to_device_node(operator_counts, output_index);
+        output_index
+    }
+
+    // Linear -> ReLU -> Softmax is run with the hand written kernels, every other
+    // group gets a FusedElementwise node running a shader from the op_code_compiler.
+    fn push_fusion_group(
+        &mut self,
+        gpu_handles: &GPUHandles,
+        operator_counts: &mut HashMap<NodeOperatorGPU, u32>,
+        graph_nodes: &[GraphNode],
+        output_indices: &[Option<usize>],
+        group: &FusionGroup,
+    ) -> Result<usize, GraphError> {
+        let producer: &GraphNode = &graph_nodes[group.producer];
+        let fusion: &FusedElementwise = match &group.pattern {
+            FusionPattern::Elementwise(fusion) => fusion,
+            FusionPattern::LinearReLUSoftmax => {
+                let key: NodeOperatorGPU = NodeOperatorGPU::LinearReLUSoftmax;
+                let (weights, bias): (&Tensor2D, &Tensor2D) = match &producer.operator {
+                    Linear { weights, bias } => (weights, bias),
+                    _ => panic!("Invalid fusion! {:?} needs a linear producer.", key),
+                };
+                let input_index: usize =
+                    Self::get_input_buffer_index(output_indices, group.producer, producer, 0)?;
+                return Ok(self.push_linear_node(
+                    gpu_handles,
+                    operator_counts,
+                    key,
+                    input_index,
+                    weights,
+                    bias,
+                ));
+            }
+        };
+
+        let key: NodeOperatorGPU = NodeOperatorGPU::FusedElementwise;
+        let new_key: String = Self::get_new_key(operator_counts, &key);
+        let input_index: usize =
+            Self::get_input_buffer_index(output_indices, group.producer, producer, 0)?;
+
+        let mut buffer_indices: Vec<usize> = vec![input_index];
+        match &producer.operator {
+            Linear { weights, bias } | LinearReLUFused { weights, bias } => {
+                let (weights_index, bias_index): (usize, usize) =
+                    self.push_parameters(gpu_handles, &new_key, weights, bias);
+                buffer_indices.push(weights_index);
+                buffer_indices.push(bias_index);
+            }
+            _ => {}
+        }
+        for operand in &group.operands {
+            buffer_indices.push(Self::get_output_buffer_index(
+                output_indices,
+                group.last_node,
+                *operand,
+            )?);
+        }
+
+        // The output has the shape of the base after broadcasting it with every operand
+        let base_shapes: Vec<(usize, usize)> = buffer_indices[..fusion.base_buffer_count()]
+            .iter()
+            .map(|index| {
+                let buffer: &Tensor2DGPU = &self.data_buffers[*index];
+                (buffer.row_count, buffer.column_count)
+            })
+            .collect();
+        let mut shape: (usize, usize) = fusion.base_shape(&base_shapes);
+        for operand_index in &buffer_indices[fusion.base_buffer_count()..] {
+            let operand: &Tensor2DGPU = &self.data_buffers[*operand_index];
+            shape = Tensor2D::broadcast_shape(shape, (operand.row_count, operand.column_count))
+                .expect("Invalid graph! Operands of a fused node can't be broadcast.");
         }
+        self.data_buffers.push(Tensor2DGPU::new(
+            gpu_handles,
+            &format!("{}_{}", new_key, "output"),
+            0.0,
+            shape.0,
+            shape.1,
+        ));
+        let output_index: usize = self.data_buffers.len() - 1;
+        buffer_indices.push(output_index);
+
+        if self.use_cache {
+            nodes_gpu::build_fused_elementwise_elements(
+                gpu_handles,
+                &mut self.shader_cache,
+                &mut self.pipeline_cache,
+                fusion,
+            );
+        }
+
+        let node: NodeGPU = NodeGPU::fused(new_key, buffer_indices, fusion.clone());
+        self.nodes.push(node);
+
+        self.push_device_to_device_node(operator_counts, output_index);
+        Ok(output_index)
     }
 
     // This is made a lot more complicated by reusing buffers
@@ -116,13 +550,9 @@ impl GraphRunnerGPU {
     fn compute_nodes(
         &mut self,
         gpu_handles: &GPUHandles,
-        graph_operators: &Vec<GraphOperator>,
+        graph_nodes: &[GraphNode],
         fuse_operators: bool,
-    ) {
-        if !self.graph_operators_are_valid {
-            panic!("Invalid graph being sent to compute_nodes!");
-        }
-
+    ) -> Result<(), GraphError> {
         let mut operator_counts: HashMap<NodeOperatorGPU, u32> =
             HashMap::<NodeOperatorGPU, u32>::new();
         operator_counts.insert(NodeOperatorGPU::HostToDevice, 0);
@@ -133,12 +563,50 @@ impl GraphRunnerGPU {
         operator_counts.insert(NodeOperatorGPU::Softmax, 0);
         operator_counts.insert(NodeOperatorGPU::LinearReLU, 0);
         operator_counts.insert(NodeOperatorGPU::LinearReLUSoftmax, 0);
+        operator_counts.insert(NodeOperatorGPU::Add, 0);
+        operator_counts.insert(NodeOperatorGPU::Subtract, 0);
+        operator_counts.insert(NodeOperatorGPU::Multiply, 0);
+        operator_counts.insert(NodeOperatorGPU::Divide, 0);
+        operator_counts.insert(NodeOperatorGPU::FusedElementwise, 0);
+        operator_counts.insert(NodeOperatorGPU::LogSoftmax, 0);
+        operator_counts.insert(NodeOperatorGPU::Sum, 0);
+        operator_counts.insert(NodeOperatorGPU::Max, 0);
+        operator_counts.insert(NodeOperatorGPU::Mean, 0);
+        operator_counts.insert(NodeOperatorGPU::ArgMax, 0);
+        operator_counts.insert(NodeOperatorGPU::QuantizedLinear, 0);
 
-        let mut operator_index: usize = 0;
-        while operator_index < graph_operators.len() {
-            let operator: &GraphOperator = &graph_operators[operator_index];
+        let order: Vec<NodeId> = topological_sort(graph_nodes).ok_or(GraphError::Cycle)?;
+        let fusion_plan: FusionPlan = if fuse_operators {
+            let consumers: Vec<Vec<NodeId>> = get_consumers(graph_nodes);
+            plan_fusion(graph_nodes, &order, &consumers)
+        } else {
+            FusionPlan::unfused(graph_nodes.len())
+        };
+
+        // The data buffer each graph node wrote its result to
+        let mut output_indices: Vec<Option<usize>> = vec![None; graph_nodes.len()];
+        let mut outputs: Vec<(NodeId, usize)> = Vec::<(NodeId, usize)>::new();
+
+        for node_id in order {
+            // Graph nodes which are computed by the group of a later node
+            if fusion_plan.fused_nodes[node_id] {
+                continue;
+            }
+
+            if let Some(group) = &fusion_plan.groups[node_id] {
+                let output_index: usize = self.push_fusion_group(
+                    gpu_handles,
+                    &mut operator_counts,
+                    graph_nodes,
+                    &output_indices,
+                    group,
+                )?;
+                output_indices[node_id] = Some(output_index);
+                continue;
+            }
 
-            match operator {
+            let graph_node: &GraphNode = &graph_nodes[node_id];
+            match &graph_node.operator {
                 Empty => {}
                 // Maybe put a device to device split in here for simpler code in the other operators
                 HostToDevice { input } => {
@@ -150,226 +618,331 @@ impl GraphRunnerGPU {
                         &format!("{}_{}", new_key, "input"),
                         input,
                     ));
-                    let buffer_indices: Vec<usize> = vec![self.data_buffers.len() - 1];
+                    let output_index: usize = self.data_buffers.len() - 1;
+                    self.input_buffer_indices.push(output_index);
+                    self.input_node_ids.push(node_id);
 
-                    let node: NodeGPU = NodeGPU::new(new_key, key, buffer_indices.clone());
+                    let node: NodeGPU = NodeGPU::new(new_key, key, vec![output_index]);
                     self.nodes.push(node);
 
-                    let key: NodeOperatorGPU = NodeOperatorGPU::DeviceToDevice;
-                    let new_key: String = Self::get_new_key(&mut operator_counts, &key);
-                    let node: NodeGPU = NodeGPU::new(new_key, key, buffer_indices);
-                    self.nodes.push(node);
+                    self.push_device_to_device_node(&mut operator_counts, output_index);
+                    output_indices[node_id] = Some(output_index);
                 }
                 DeviceToHost => {
                     let key: NodeOperatorGPU = NodeOperatorGPU::DeviceToHost;
                     let input_index: usize =
-                        Self::verify_previous_node_and_get_index(&self.nodes, &key);
+                        Self::get_input_buffer_index(&output_indices, node_id, graph_node, 0)?;
 
                     let new_key: String = Self::get_new_key(&mut operator_counts, &key);
 
                     let buffer_indices: Vec<usize> = vec![input_index];
-                    let node: NodeGPU =
-                        NodeGPU::new(new_key, NodeOperatorGPU::DeviceToHost, buffer_indices);
+                    let node: NodeGPU = NodeGPU::new(new_key, key, buffer_indices);
                     self.nodes.push(node);
+                    outputs.push((node_id, input_index));
                 }
                 Linear { weights, bias } => {
-                    let mut key: NodeOperatorGPU = NodeOperatorGPU::Linear;
-
-                    if fuse_operators {
-                        if let ReLU = graph_operators[operator_index + 1] {
-                            match graph_operators[operator_index + 2] {
-                                Softmax => {
-                                    key = NodeOperatorGPU::LinearReLUSoftmax;
-                                    operator_index += 2;
-                                }
-                                _ => {
-                                    key = NodeOperatorGPU::LinearReLU;
-                                    operator_index += 1;
-                                }
-                            }
-                        }
-                    }
-
+                    let key: NodeOperatorGPU = NodeOperatorGPU::Linear;
                     let input_index: usize =
-                        Self::verify_previous_node_and_get_index(&self.nodes, &key);
-                    let new_key: String = Self::get_new_key(&mut operator_counts, &key);
-
-                    self.data_buffers.push(Tensor2DGPU::from_tensor2d(
+                        Self::get_input_buffer_index(&output_indices, node_id, graph_node, 0)?;
+                    let output_index: usize = self.push_linear_node(
                         gpu_handles,
-                        &format!("{}_{}", new_key, "weights"),
+                        &mut operator_counts,
+                        key,
+                        input_index,
                         weights,
-                    ));
-                    let weights_index: usize = self.data_buffers.len() - 1;
-
-                    self.data_buffers.push(Tensor2DGPU::from_tensor2d(
-                        gpu_handles,
-                        &format!("{}_{}", new_key, "bias"),
                         bias,
-                    ));
-                    let bias_index: usize = self.data_buffers.len() - 1;
-
-                    self.data_buffers.push(Tensor2DGPU::new(
-                        gpu_handles,
-                        &format!("{}_{}", new_key, "output"),
-                        0.0,
-                        bias.row_count,
-                        bias.column_count,
-                    ));
-                    let output_index: usize = self.data_buffers.len() - 1;
-
-                    let buffer_indices: Vec<usize> =
-                        vec![input_index, weights_index, bias_index, output_index];
-                    let node: NodeGPU = NodeGPU::new(new_key.clone(), key, buffer_indices);
-                    self.nodes.push(node);
-
-                    let key: NodeOperatorGPU = NodeOperatorGPU::DeviceToDevice;
-                    let new_key: String = Self::get_new_key(&mut operator_counts, &key);
-                    let buffer_indices: Vec<usize> = vec![output_index];
-                    let node: NodeGPU = NodeGPU::new(new_key, key, buffer_indices);
-                    self.nodes.push(node);
+                    );
+                    output_indices[node_id] = Some(output_index);
                 }
-                // Note this is not inplace
                 ReLU => {
                     let key: NodeOperatorGPU = NodeOperatorGPU::ReLU;
                     let input_index: usize =
-                        Self::verify_previous_node_and_get_index(&self.nodes, &key);
-
-                    let new_key: String = Self::get_new_key(&mut operator_counts, &key);
-
-                    let input_buffer: &Tensor2DGPU = &self.data_buffers[input_index];
-                    self.data_buffers.push(Tensor2DGPU::new(
+                        Self::get_input_buffer_index(&output_indices, node_id, graph_node, 0)?;
+                    let output_index: usize = self.push_unary_node(
                         gpu_handles,
-                        &format!("{}_{}", new_key, "output"),
-                        0.0,
-                        input_buffer.row_count,
-                        input_buffer.column_count,
-                    ));
-                    let output_index: usize = self.data_buffers.len() - 1;
-
-                    let buffer_indices: Vec<usize> = vec![input_index, output_index];
-                    let node: NodeGPU = NodeGPU::new(new_key.clone(), key, buffer_indices);
-                    self.nodes.push(node);
-
-                    let key: NodeOperatorGPU = NodeOperatorGPU::DeviceToDevice;
-                    let new_key: String = Self::get_new_key(&mut operator_counts, &key);
-                    let buffer_indices: Vec<usize> = vec![output_index];
-                    let node: NodeGPU = NodeGPU::new(new_key, key, buffer_indices);
-                    self.nodes.push(node);
+                        &mut operator_counts,
+                        key,
+                        input_index,
+                        false,
+                    );
+                    output_indices[node_id] = Some(output_index);
                 }
-                Softmax => {
+                Softmax { axis: Axis::Global } => {
                     let key: NodeOperatorGPU = NodeOperatorGPU::Softmax;
                     let input_index: usize =
-                        Self::verify_previous_node_and_get_index(&self.nodes, &key);
-
-                    let new_key: String = Self::get_new_key(&mut operator_counts, &key);
-
-                    let input_buffer: &Tensor2DGPU = &self.data_buffers[input_index];
-                    // This should be more flexible, but Softmax always outputs a flattened vector
-                    self.data_buffers.push(Tensor2DGPU::new(
+                        Self::get_input_buffer_index(&output_indices, node_id, graph_node, 0)?;
+                    // Softmax over the whole tensor outputs a flattened vector
+                    let output_index: usize = self.push_unary_node(
                         gpu_handles,
-                        &format!("{}_{}", new_key, "output"),
-                        0.0,
-                        input_buffer.row_count * input_buffer.column_count,
-                        1,
-                    ));
-                    let output_index: usize = self.data_buffers.len() - 1;
-
-                    let buffer_indices: Vec<usize> = vec![input_index, output_index];
-                    let node: NodeGPU = NodeGPU::new(new_key.clone(), key, buffer_indices);
-                    self.nodes.push(node);
-
-                    let key: NodeOperatorGPU = NodeOperatorGPU::DeviceToDevice;
-                    let new_key: String = Self::get_new_key(&mut operator_counts, &key);
-                    let buffer_indices: Vec<usize> = vec![output_index];
-                    let node: NodeGPU = NodeGPU::new(new_key, key, buffer_indices);
-                    self.nodes.push(node);
+                        &mut operator_counts,
+                        key,
+                        input_index,
+                        true,
+                    );
+                    output_indices[node_id] = Some(output_index);
+                }
+                Softmax { axis }
+                | LogSoftmax { axis }
+                | Sum { axis }
+                | Max { axis }
+                | Mean { axis }
+                | ArgMax { axis } => {
+                    let key: NodeOperatorGPU = match graph_node.operator {
+                        Softmax { .. } => NodeOperatorGPU::Softmax,
+                        LogSoftmax { .. } => NodeOperatorGPU::LogSoftmax,
+                        Sum { .. } => NodeOperatorGPU::Sum,
+                        Max { .. } => NodeOperatorGPU::Max,
+                        Mean { .. } => NodeOperatorGPU::Mean,
+                        _ => NodeOperatorGPU::ArgMax,
+                    };
+                    let input_index: usize =
+                        Self::get_input_buffer_index(&output_indices, node_id, graph_node, 0)?;
+                    let output_index: usize = self.push_axis_node(
+                        gpu_handles,
+                        &mut operator_counts,
+                        key,
+                        input_index,
+                        *axis,
+                    );
+                    output_indices[node_id] = Some(output_index);
                 }
                 LinearReLUFused { weights, bias } => {
                     let key: NodeOperatorGPU = NodeOperatorGPU::LinearReLU;
                     let input_index: usize =
-                        Self::verify_previous_node_and_get_index(&self.nodes, &key);
-                    let new_key: String = Self::get_new_key(&mut operator_counts, &key);
-
-                    self.data_buffers.push(Tensor2DGPU::from_tensor2d(
+                        Self::get_input_buffer_index(&output_indices, node_id, graph_node, 0)?;
+                    let output_index: usize = self.push_linear_node(
                         gpu_handles,
-                        &format!("{}_{}", new_key, "weights"),
+                        &mut operator_counts,
+                        key,
+                        input_index,
                         weights,
-                    ));
-                    let weights_index: usize = self.data_buffers.len() - 1;
-
-                    self.data_buffers.push(Tensor2DGPU::from_tensor2d(
-                        gpu_handles,
-                        &format!("{}_{}", new_key, "bias"),
                         bias,
-                    ));
-                    let bias_index: usize = self.data_buffers.len() - 1;
-
-                    self.data_buffers.push(Tensor2DGPU::new(
-                        gpu_handles,
-                        &format!("{}_{}", new_key, "output"),
-                        0.0,
-                        bias.row_count,
-                        bias.column_count,
-                    ));
-                    let output_index: usize = self.data_buffers.len() - 1;
-
-                    let buffer_indices: Vec<usize> =
-                        vec![input_index, weights_index, bias_index, output_index];
-                    let node: NodeGPU = NodeGPU::new(new_key.clone(), key, buffer_indices);
-                    self.nodes.push(node);
-
-                    let key: NodeOperatorGPU = NodeOperatorGPU::DeviceToDevice;
-                    let new_key: String = Self::get_new_key(&mut operator_counts, &key);
-                    let buffer_indices: Vec<usize> = vec![output_index];
-                    let node: NodeGPU = NodeGPU::new(new_key, key, buffer_indices);
-                    self.nodes.push(node);
+                    );
+                    output_indices[node_id] = Some(output_index);
                 }
                 LinearReLUSoftmaxFused { weights, bias } => {
                     let key: NodeOperatorGPU = NodeOperatorGPU::LinearReLUSoftmax;
                     let input_index: usize =
-                        Self::verify_previous_node_and_get_index(&self.nodes, &key);
-                    let new_key: String = Self::get_new_key(&mut operator_counts, &key);
-
-                    self.data_buffers.push(Tensor2DGPU::from_tensor2d(
+                        Self::get_input_buffer_index(&output_indices, node_id, graph_node, 0)?;
+                    let output_index: usize = self.push_linear_node(
                         gpu_handles,
-                        &format!("{}_{}", new_key, "weights"),
+                        &mut operator_counts,
+                        key,
+                        input_index,
                         weights,
-                    ));
-                    let weights_index: usize = self.data_buffers.len() - 1;
-
-                    self.data_buffers.push(Tensor2DGPU::from_tensor2d(
+                        bias,
+                    );
+                    output_indices[node_id] = Some(output_index);
+                }
+                QuantizedLinear { weights, bias } => {
+                    let input_index: usize =
+                        Self::get_input_buffer_index(&output_indices, node_id, graph_node, 0)?;
+                    let output_index: usize = self.push_quantized_linear_node(
                         gpu_handles,
-                        &format!("{}_{}", new_key, "bias"),
+                        &mut operator_counts,
+                        input_index,
+                        weights,
                         bias,
-                    ));
-                    let bias_index: usize = self.data_buffers.len() - 1;
-
-                    // This should be more flexible, but Softmax always outputs a flattened vector
-                    self.data_buffers.push(Tensor2DGPU::new(
+                    );
+                    output_indices[node_id] = Some(output_index);
+                }
+                Add | Subtract | Multiply | Divide => {
+                    let key: NodeOperatorGPU = match graph_node.operator {
+                        Add => NodeOperatorGPU::Add,
+                        Subtract => NodeOperatorGPU::Subtract,
+                        Multiply => NodeOperatorGPU::Multiply,
+                        _ => NodeOperatorGPU::Divide,
+                    };
+                    let left_index: usize =
+                        Self::get_input_buffer_index(&output_indices, node_id, graph_node, 0)?;
+                    let right_index: usize =
+                        Self::get_input_buffer_index(&output_indices, node_id, graph_node, 1)?;
+                    let output_index: usize = self.push_elementwise_node(
                         gpu_handles,
-                        &format!("{}_{}", new_key, "output"),
-                        0.0,
-                        bias.row_count,
-                        bias.column_count,
-                    ));
-                    let output_index: usize = self.data_buffers.len() - 1;
+                        &mut operator_counts,
+                        key,
+                        left_index,
+                        right_index,
+                    );
+                    output_indices[node_id] = Some(output_index);
+                }
+            }
+        }
 
-                    let buffer_indices: Vec<usize> =
-                        vec![input_index, weights_index, bias_index, output_index];
-                    let node: NodeGPU = NodeGPU::new(new_key.clone(), key, buffer_indices);
-                    self.nodes.push(node);
+        // Outputs are returned in the order their DeviceToHost nodes appear in the graph
+        outputs.sort_by_key(|output| output.0);
+        self.output_buffer_indices = outputs.iter().map(|output| output.1).collect();
 
-                    let key: NodeOperatorGPU = NodeOperatorGPU::DeviceToDevice;
-                    let new_key: String = Self::get_new_key(&mut operator_counts, &key);
-                    let buffer_indices: Vec<usize> = vec![output_index];
-                    let node: NodeGPU = NodeGPU::new(new_key, key, buffer_indices);
-                    self.nodes.push(node);
+        self.nodes_are_valid = true;
+        Ok(())
+    }
+
+    pub fn linear_kernel(&self) -> LinearKernel {
+        self.linear_kernel
+    }
+
+    // Runs the Linear nodes with another kernel, replacing the cached
+    // Linear and LinearReLU pipelines if the runner uses the cache.
+    pub fn set_linear_kernel(&mut self, gpu_handles: &GPUHandles, linear_kernel: LinearKernel) {
+        self.linear_kernel = linear_kernel;
+        if self.use_cache {
+            nodes_gpu::build_linear_elements(
+                gpu_handles,
+                &mut self.shader_cache,
+                &mut self.pipeline_cache,
+                self.fuse_operators,
+                &self.linear_kernel,
+            );
+        }
+    }
+
+    // Lets intermediate results share GPU buffers once they are no longer needed.
+    // The buffers which are no longer used are dropped, freeing the GPU memory.
+    pub fn plan_memory(&mut self) -> MemoryPlan {
+        let mut pinned_buffers: Vec<usize> = self.parameter_buffer_indices.clone();
+        pinned_buffers.extend(&self.output_buffer_indices);
+        for node in &self.nodes {
+            if node.operator == NodeOperatorGPU::HostToDevice {
+                pinned_buffers.extend(&node.buffer_indices);
+            }
+        }
+
+        let buffers: Vec<BufferDescription> = self
+            .data_buffers
+            .iter()
+            .enumerate()
+            .map(|(buffer_index, buffer)| BufferDescription {
+                row_count: buffer.row_count,
+                column_count: buffer.column_count,
+                is_pinned: pinned_buffers.contains(&buffer_index),
+            })
+            .collect();
+        let node_buffer_indices: Vec<Vec<usize>> = self
+            .nodes
+            .iter()
+            .map(|node| node.buffer_indices.clone())
+            .collect();
+
+        let plan: MemoryPlan = plan_memory(&node_buffer_indices, &buffers);
+
+        let data_buffers: Vec<Tensor2DGPU> = std::mem::take(&mut self.data_buffers);
+        self.data_buffers = apply_memory_plan(data_buffers, &plan);
+        for node in &mut self.nodes {
+            for buffer_index in &mut node.buffer_indices {
+                *buffer_index = plan.buffer_assignments[*buffer_index];
+            }
+        }
+        for buffer_index in &mut self.output_buffer_indices {
+            *buffer_index = plan.buffer_assignments[*buffer_index];
+        }
+        for buffer_index in &mut self.input_buffer_indices {
+            *buffer_index = plan.buffer_assignments[*buffer_index];
+        }
+        for buffer_index in &mut self.parameter_buffer_indices {
+            *buffer_index = plan.buffer_assignments[*buffer_index];
+        }
+        self.batch_buffers.clear();
+
+        plan
+    }
+
+    // The nodes compute_nodes produced and the buffers they use, after fusion
+    // and after memory planning if plan_memory has been called.
+    pub fn export_graph(&self) -> ExportGraph {
+        let roles: Vec<BufferRole> = buffer_roles(
+            self.data_buffers.len(),
+            &self.input_buffer_indices,
+            &self.output_buffer_indices,
+            &self.parameter_buffer_indices,
+        );
+        let buffers: Vec<ExportBuffer> = self
+            .data_buffers
+            .iter()
+            .zip(roles)
+            .map(|(buffer, role)| ExportBuffer {
+                row_count: buffer.row_count,
+                column_count: buffer.column_count,
+                role,
+            })
+            .collect();
+
+        let nodes: Vec<ExportNode> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let name: &str = &node.name;
+                let indices: &[usize] = &node.buffer_indices;
+                match node.operator {
+                    NodeOperatorGPU::HostToDevice => ExportNode::source(name, indices[0]),
+                    NodeOperatorGPU::DeviceToHost | NodeOperatorGPU::DeviceToDevice => {
+                        ExportNode::sink(name, indices)
+                    }
+                    NodeOperatorGPU::LinearReLU => ExportNode::compute(name, indices)
+                        .with_fused_operators(linear_relu_operator_names(false)),
+                    NodeOperatorGPU::LinearReLUSoftmax => ExportNode::compute(name, indices)
+                        .with_fused_operators(linear_relu_operator_names(true)),
+                    NodeOperatorGPU::FusedElementwise => ExportNode::compute(name, indices)
+                        .with_fused_operators(match &node.fusion {
+                            Some(fusion) => fused_operator_names(fusion),
+                            None => Vec::<String>::new(),
+                        }),
+                    _ => ExportNode::compute(name, indices).with_axis(node.axis),
                 }
+            })
+            .collect();
+
+        ExportGraph {
+            name: "GraphRunnerGPU".to_string(),
+            nodes,
+            buffers,
+        }
+    }
+
+    // Graphviz DOT of the compiled nodes
+    pub fn to_dot(&self) -> String {
+        self.export_graph().to_dot()
+    }
+
+    // Mermaid flowchart of the compiled nodes
+    pub fn to_mermaid(&self) -> String {
+        self.export_graph().to_mermaid()
+    }
+
+    // Uploads new input to the HostToDevice buffers, one tensor per HostToDevice
+    // in the order they appear in the graph. Nothing is uploaded unless every input
+    // has the shape the graph was built with.
+    fn bind_inputs(
+        &mut self,
+        gpu_handles: &GPUHandles,
+        inputs: &[&Tensor2D],
+    ) -> Result<(), GraphError> {
+        if inputs.len() != self.input_buffer_indices.len() {
+            return Err(GraphError::GraphInputCount {
+                expected: self.input_buffer_indices.len(),
+                actual: inputs.len(),
+            });
+        }
+
+        for (input, (buffer_index, node_id)) in inputs
+            .iter()
+            .zip(self.input_buffer_indices.iter().zip(&self.input_node_ids))
+        {
+            let buffer: &Tensor2DGPU = &self.data_buffers[*buffer_index];
+            if input.row_count != buffer.row_count || input.column_count != buffer.column_count {
+                return Err(GraphError::ShapeMismatch {
+                    index: *node_id,
+                    tensor: "input",
+                    expected: (buffer.row_count, buffer.column_count),
+                    actual: (input.row_count, input.column_count),
+                });
             }
+        }
 
-            operator_index += 1;
+        for (input, buffer_index) in inputs.iter().zip(&self.input_buffer_indices) {
+            self.data_buffers[*buffer_index].upload(gpu_handles, input);
         }
-        self.graph_operators_are_valid = true;
+
+        Ok(())
     }
 
     fn submit_operator_commands(
@@ -377,9 +950,11 @@ impl GraphRunnerGPU {
         use_cache: bool,
         shader_cache: &HashMap<String, ShaderModule>,
         pipeline_cache: &HashMap<String, ComputePipeline>,
+        linear_kernel: &LinearKernel,
         node_vector: &[NodeGPU],
         data_buffers: &[Tensor2DGPU],
         encoder: &mut CommandEncoder,
+        batch_size: usize,
     ) {
         for node in node_vector {
             match node.operator {
@@ -402,6 +977,7 @@ impl GraphRunnerGPU {
                         data_buffers,
                         encoder,
                         false,
+                        linear_kernel,
                     );
                 }
                 NodeOperatorGPU::ReLU => {
@@ -415,7 +991,8 @@ impl GraphRunnerGPU {
                         encoder,
                     );
                 }
-                NodeOperatorGPU::Softmax => {
+                // Nodes without an axis normalize the whole tensor
+                NodeOperatorGPU::Softmax if node.axis.unwrap_or(Axis::Global) == Axis::Global => {
                     nodes_gpu::softmax(
                         gpu_handles,
                         use_cache,
@@ -424,6 +1001,24 @@ impl GraphRunnerGPU {
                         node,
                         data_buffers,
                         encoder,
+                        batch_size,
+                    );
+                }
+                NodeOperatorGPU::Softmax
+                | NodeOperatorGPU::LogSoftmax
+                | NodeOperatorGPU::Sum
+                | NodeOperatorGPU::Max
+                | NodeOperatorGPU::Mean
+                | NodeOperatorGPU::ArgMax => {
+                    nodes_gpu::axis_operator(
+                        gpu_handles,
+                        use_cache,
+                        shader_cache,
+                        pipeline_cache,
+                        node,
+                        data_buffers,
+                        encoder,
+                        batch_size,
                     );
                 }
                 NodeOperatorGPU::LinearReLU => {
@@ -436,6 +1031,7 @@ impl GraphRunnerGPU {
                         data_buffers,
                         encoder,
                         true,
+                        linear_kernel,
                     );
                 }
                 NodeOperatorGPU::LinearReLUSoftmax => {
@@ -447,13 +1043,58 @@ impl GraphRunnerGPU {
                         node,
                         data_buffers,
                         encoder,
+                        batch_size,
+                        linear_kernel,
+                    );
+                }
+                NodeOperatorGPU::Add
+                | NodeOperatorGPU::Subtract
+                | NodeOperatorGPU::Multiply
+                | NodeOperatorGPU::Divide => {
+                    nodes_gpu::elementwise(
+                        gpu_handles,
+                        use_cache,
+                        shader_cache,
+                        pipeline_cache,
+                        node,
+                        data_buffers,
+                        encoder,
+                    );
+                }
+                NodeOperatorGPU::FusedElementwise => {
+                    nodes_gpu::fused_elementwise(
+                        gpu_handles,
+                        use_cache,
+                        shader_cache,
+                        pipeline_cache,
+                        node,
+                        data_buffers,
+                        encoder,
+                        batch_size,
+                    );
+                }
+                NodeOperatorGPU::QuantizedLinear => {
+                    nodes_gpu::quantized_linear(
+                        gpu_handles,
+                        use_cache,
+                        shader_cache,
+                        pipeline_cache,
+                        node,
+                        data_buffers,
+                        encoder,
                     );
                 }
             }
         }
     }
 
-    fn submit_operations(&mut self, gpu_handles: &GPUHandles) {
+    fn submit_operations(&mut self, gpu_handles: &GPUHandles, batched: bool) {
+        let (data_buffers, batch_size): (&mut Vec<Tensor2DGPU>, usize) = if batched {
+            (&mut self.batch_buffers, self.batch_size)
+        } else {
+            (&mut self.data_buffers, 1)
+        };
+
         let mut encoder: CommandEncoder = gpu_handles
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
@@ -464,42 +1105,478 @@ impl GraphRunnerGPU {
                 self.use_cache,
                 &self.shader_cache,
                 &self.pipeline_cache,
+                &self.linear_kernel,
                 &self.nodes,
-                &self.data_buffers,
+                data_buffers,
                 &mut encoder,
+                batch_size,
             );
 
+            // Copy every output to its staging buffer, ready to be mapped
+            for output_index in &self.output_buffer_indices {
+                data_buffers[*output_index].copy_from_gpu_mut(&mut encoder);
+            }
+
             // Submit commands
             gpu_handles.queue.submit(Some(encoder.finish()));
         }
     }
 
-    async fn retrieve_output(&mut self, gpu_handles: &GPUHandles) -> Tensor2D {
-        // Transfer result back
-        let last_index: usize = self.data_buffers.len() - 1;
-        let output: &mut Tensor2DGPU = &mut self.data_buffers[last_index];
-        let buffer_slice: BufferSlice = output.staging_buffer.slice(..);
-        let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
-        buffer_slice.map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
-        output.receiver = Some(receiver);
+    async fn retrieve_outputs(
+        &mut self,
+        gpu_handles: &GPUHandles,
+        batched: bool,
+    ) -> Result<Vec<Tensor2D>, GraphError> {
+        let data_buffers: &mut Vec<Tensor2DGPU> = if batched {
+            &mut self.batch_buffers
+        } else {
+            &mut self.data_buffers
+        };
+
+        // Transfer results back. Several outputs might read the same buffer,
+        // but each buffer can only be mapped once.
+        for output_index in &self.output_buffer_indices {
+            let output: &mut Tensor2DGPU = &mut data_buffers[*output_index];
+            if !output.live_data_on_device || output.receiver.is_some() {
+                continue;
+            }
+
+            let buffer_slice: BufferSlice = output.staging_buffer.slice(..);
+            let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
+            buffer_slice.map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
+            output.receiver = Some(receiver);
+        }
 
         gpu_handles.device.poll(wgpu::Maintain::Wait);
 
-        if output.live_data_on_device {
-            output.retrieve_results().await;
+        let mut outputs: Vec<Tensor2D> = Vec::<Tensor2D>::new();
+        for (output, output_index) in self.output_buffer_indices.iter().enumerate() {
+            let buffer: &mut Tensor2DGPU = &mut data_buffers[*output_index];
+            if buffer.live_data_on_device && buffer.try_retrieve_results().await.is_err() {
+                return Err(GraphError::Readback { output });
+            }
+            outputs.push(buffer.data.clone());
+        }
+
+        Ok(outputs)
+    }
+
+    // Runs the graph once on the input it holds, timing every node with timestamp queries
+    // written between the compute passes. Without Features::TIMESTAMP_QUERY on the device
+    // nothing is run and None is returned. The transfer nodes don't do any work and aren't timed.
+    pub async fn profile(
+        &mut self,
+        gpu_handles: &GPUHandles,
+    ) -> Result<Option<ProfileReport>, GraphError> {
+        if !gpu_handles
+            .device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+        {
+            return Ok(None);
+        }
+
+        let timed_nodes: Vec<&NodeGPU> = self
+            .nodes
+            .iter()
+            .filter(|node| {
+                !matches!(
+                    node.operator,
+                    NodeOperatorGPU::HostToDevice
+                        | NodeOperatorGPU::DeviceToHost
+                        | NodeOperatorGPU::DeviceToDevice
+                )
+            })
+            .collect();
+        let mut report: ProfileReport = ProfileReport::new("GraphRunnerGPU");
+        if timed_nodes.is_empty() {
+            return Ok(Some(report));
+        }
+
+        // A query set holds a timestamp before and after at most QUERY_SET_MAX_QUERIES / 2 nodes,
+        // larger graphs are timed in chunks, submitted in order on the same queue
+        let mut ticks: Vec<u64> = Vec::<u64>::with_capacity(timed_nodes.len() * 2);
+        for chunk in timed_nodes.chunks((wgpu::QUERY_SET_MAX_QUERIES / 2) as usize) {
+            ticks.extend(self.profile_chunk(gpu_handles, chunk).await?);
+        }
+
+        // The timestamps are in ticks of timestamp_period nanoseconds
+        let timestamp_period: f64 = gpu_handles.queue.get_timestamp_period() as f64;
+        for (index, node) in timed_nodes.iter().enumerate() {
+            let start: u64 = ticks[index * 2];
+            let end: u64 = ticks[index * 2 + 1];
+            report.timings.push(NodeTiming {
+                name: node.name.clone(),
+                operator: format!("{:?}", node.operator),
+                start_nanoseconds: start.saturating_sub(ticks[0]) as f64 * timestamp_period,
+                duration_nanoseconds: end.saturating_sub(start) as f64 * timestamp_period,
+            });
+        }
+
+        Ok(Some(report))
+    }
+
+    // Runs the nodes in a single submission and returns the timestamps
+    // before and after each of them
+    async fn profile_chunk(
+        &self,
+        gpu_handles: &GPUHandles,
+        nodes: &[&NodeGPU],
+    ) -> Result<Vec<u64>, GraphError> {
+        let query_count: u32 = (nodes.len() * 2) as u32;
+        let query_set: QuerySet = gpu_handles
+            .device
+            .create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("profile"),
+                ty: wgpu::QueryType::Timestamp,
+                count: query_count,
+            });
+        let size: u64 = query_count as u64 * std::mem::size_of::<u64>() as u64;
+        let resolve_buffer: Buffer = gpu_handles.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("profile_resolve"),
+            size,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let staging_buffer: Buffer = gpu_handles.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("profile_staging"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder: CommandEncoder = gpu_handles
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        for (index, node) in nodes.iter().enumerate() {
+            encoder.write_timestamp(&query_set, index as u32 * 2);
+            Self::submit_operator_commands(
+                gpu_handles,
+                self.use_cache,
+                &self.shader_cache,
+                &self.pipeline_cache,
+                &self.linear_kernel,
+                std::slice::from_ref(*node),
+                &self.data_buffers,
+                &mut encoder,
+                1,
+            );
+            encoder.write_timestamp(&query_set, index as u32 * 2 + 1);
         }
+        encoder.resolve_query_set(&query_set, 0..query_count, &resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&resolve_buffer, 0, &staging_buffer, 0, size);
+        gpu_handles.queue.submit(Some(encoder.finish()));
 
-        output.data.clone()
+        let buffer_slice: BufferSlice = staging_buffer.slice(..);
+        let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
+        gpu_handles.device.poll(wgpu::Maintain::Wait);
+        match receiver.receive().await {
+            Some(Ok(())) => {}
+            _ => return Err(GraphError::ProfileReadback),
+        }
+        let ticks: Vec<u64> = bytemuck::cast_slice(&buffer_slice.get_mapped_range()).to_vec();
+        staging_buffer.unmap();
+
+        Ok(ticks)
     }
 
+    // Returns the output of the last DeviceToHost node in the graph.
     pub async fn run(&mut self, gpu_handles: &GPUHandles, iteration_count: usize) -> Tensor2D {
-        if !self.graph_operators_are_valid {
-            panic!("Failed to validate the computational graph!");
+        self.try_run(gpu_handles, iteration_count)
+            .await
+            .unwrap_or_else(|error| panic!("Failed to run a GPU computational graph! {}", error))
+    }
+
+    pub async fn try_run(
+        &mut self,
+        gpu_handles: &GPUHandles,
+        iteration_count: usize,
+    ) -> Result<Tensor2D, GraphError> {
+        self.try_run_outputs(gpu_handles, iteration_count)
+            .await?
+            .pop()
+            .ok_or(GraphError::NoOutputs)
+    }
+
+    // Returns the outputs of every DeviceToHost node, in the order they appear in the graph.
+    pub async fn run_outputs(
+        &mut self,
+        gpu_handles: &GPUHandles,
+        iteration_count: usize,
+    ) -> Vec<Tensor2D> {
+        self.try_run_outputs(gpu_handles, iteration_count)
+            .await
+            .unwrap_or_else(|error| panic!("Failed to run a GPU computational graph! {}", error))
+    }
+
+    pub async fn try_run_outputs(
+        &mut self,
+        gpu_handles: &GPUHandles,
+        iteration_count: usize,
+    ) -> Result<Vec<Tensor2D>, GraphError> {
+        if self.output_buffer_indices.is_empty() {
+            return Err(GraphError::NoOutputs);
+        }
+
+        for _ in 0..iteration_count {
+            self.submit_operations(gpu_handles, false);
+        }
+        self.retrieve_outputs(gpu_handles, false).await
+    }
+
+    // Runs the graph on new input, reusing the nodes, pipelines and buffers.
+    // Only the input is uploaded, it has to have the same shape as the one
+    // the graph was built with.
+    pub async fn run_with(
+        &mut self,
+        gpu_handles: &GPUHandles,
+        input: &Tensor2D,
+        iteration_count: usize,
+    ) -> Tensor2D {
+        self.try_run_with(gpu_handles, input, iteration_count)
+            .await
+            .unwrap_or_else(|error| panic!("Failed to run a GPU computational graph! {}", error))
+    }
+
+    pub async fn try_run_with(
+        &mut self,
+        gpu_handles: &GPUHandles,
+        input: &Tensor2D,
+        iteration_count: usize,
+    ) -> Result<Tensor2D, GraphError> {
+        self.bind_inputs(gpu_handles, &[input])?;
+        self.try_run(gpu_handles, iteration_count).await
+    }
+
+    // The run_with version of run_outputs for graphs with several HostToDevice nodes.
+    pub async fn run_outputs_with(
+        &mut self,
+        gpu_handles: &GPUHandles,
+        inputs: &[&Tensor2D],
+        iteration_count: usize,
+    ) -> Vec<Tensor2D> {
+        self.try_run_outputs_with(gpu_handles, inputs, iteration_count)
+            .await
+            .unwrap_or_else(|error| panic!("Failed to run a GPU computational graph! {}", error))
+    }
+
+    pub async fn try_run_outputs_with(
+        &mut self,
+        gpu_handles: &GPUHandles,
+        inputs: &[&Tensor2D],
+        iteration_count: usize,
+    ) -> Result<Vec<Tensor2D>, GraphError> {
+        self.bind_inputs(gpu_handles, inputs)?;
+        self.try_run_outputs(gpu_handles, iteration_count).await
+    }
+
+    // A batch is stacked along the rows of the buffer of the only HostToDevice node.
+    // As every node keeps the rows of its input, every other buffer then holds
+    // the samples stacked along its rows as well.
+    fn batch_input(&self) -> Result<(usize, NodeId), GraphError> {
+        match (
+            self.input_buffer_indices.as_slice(),
+            self.input_node_ids.as_slice(),
+        ) {
+            ([buffer_index], [node_id]) => Ok((*buffer_index, *node_id)),
+            _ => Err(GraphError::BatchInputCount {
+                input_count: self.input_buffer_indices.len(),
+            }),
+        }
+    }
+
+    // Column vectors keep one row per row of a sample when stacked, but a row vector
+    // becomes one row per sample, which the elementwise shaders can't broadcast
+    // over the rows of each sample. The weights aren't stacked, so they are skipped.
+    fn check_batch_broadcast(&self) -> Result<(), GraphError> {
+        let weights_indices: Vec<usize> = self
+            .parameter_buffer_indices
+            .iter()
+            .step_by(2)
+            .copied()
+            .collect();
+        for node in &self.nodes {
+            let broadcasts: bool = matches!(
+                node.operator,
+                NodeOperatorGPU::Add
+                    | NodeOperatorGPU::Subtract
+                    | NodeOperatorGPU::Multiply
+                    | NodeOperatorGPU::Divide
+                    | NodeOperatorGPU::FusedElementwise
+            );
+            let (output_index, input_indices): (&usize, &[usize]) =
+                match node.buffer_indices.split_last() {
+                    Some(indices) if broadcasts => indices,
+                    _ => continue,
+                };
+
+            let output: &Tensor2DGPU = &self.data_buffers[*output_index];
+            for input_index in input_indices {
+                if 1 < output.row_count
+                    && self.data_buffers[*input_index].row_count == 1
+                    && !weights_indices.contains(input_index)
+                {
+                    return Err(GraphError::BatchBroadcast {
+                        node: node.name.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // The parameters are pushed as pairs of weights and bias. They don't change
+    // after the runner is built, so they are uploaded when the buffers are allocated,
+    // with the bias repeated once per sample as every sample adds the same bias.
+    fn allocate_batch_buffers(&mut self, gpu_handles: &GPUHandles, batch_size: usize) {
+        if self.batch_size == batch_size && self.batch_buffers.len() == self.data_buffers.len() {
+            return;
+        }
+
+        let mut batch_buffers: Vec<Tensor2DGPU> =
+            Vec::<Tensor2DGPU>::with_capacity(self.data_buffers.len());
+        for (buffer_index, buffer) in self.data_buffers.iter().enumerate() {
+            let label: String = format!("batch_{}", buffer_index);
+            let parameter: Option<usize> = self
+                .parameter_buffer_indices
+                .iter()
+                .position(|parameter_index| *parameter_index == buffer_index);
+            let batch_buffer: Tensor2DGPU = match parameter {
+                Some(position) if position % 2 == 0 => {
+                    Tensor2DGPU::from_tensor2d(gpu_handles, &label, &buffer.data)
+                }
+                Some(_) => {
+                    let samples: Vec<Tensor2D> = vec![buffer.data.clone(); batch_size];
+                    Tensor2DGPU::from_tensor2d(gpu_handles, &label, &Tensor2D::stack(&samples))
+                }
+                None => Tensor2DGPU::new(
+                    gpu_handles,
+                    &label,
+                    0.0,
+                    batch_size * buffer.row_count,
+                    buffer.column_count,
+                ),
+            };
+            batch_buffers.push(batch_buffer);
+        }
+
+        self.batch_buffers = batch_buffers;
+        self.batch_size = batch_size;
+    }
+
+    // Every sample has to have the shape of the input the graph was built with
+    fn check_batch(&self, samples: &[Tensor2D]) -> Result<(), GraphError> {
+        let (buffer_index, node_id): (usize, NodeId) = self.batch_input()?;
+        if samples.is_empty() {
+            return Err(GraphError::EmptyBatch);
+        }
+
+        let input: &Tensor2DGPU = &self.data_buffers[buffer_index];
+        for sample in samples {
+            if sample.row_count != input.row_count || sample.column_count != input.column_count {
+                return Err(GraphError::ShapeMismatch {
+                    index: node_id,
+                    tensor: "input",
+                    expected: (input.row_count, input.column_count),
+                    actual: (sample.row_count, sample.column_count),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    // Uploads a stacked batch to the batch buffers
+    fn bind_stacked(
+        &mut self,
+        gpu_handles: &GPUHandles,
+        stacked: &Tensor2D,
+    ) -> Result<(), GraphError> {
+        let (buffer_index, _): (usize, NodeId) = self.batch_input()?;
+        if stacked.len() == 0 {
+            return Err(GraphError::EmptyBatch);
+        }
+
+        let input: &Tensor2DGPU = &self.data_buffers[buffer_index];
+        if stacked.column_count != input.column_count
+            || !stacked.row_count.is_multiple_of(input.row_count)
+        {
+            return Err(GraphError::BatchShape {
+                sample: (input.row_count, input.column_count),
+                actual: (stacked.row_count, stacked.column_count),
+            });
+        }
+        self.check_batch_broadcast()?;
+        let batch_size: usize = stacked.row_count / input.row_count;
+
+        self.allocate_batch_buffers(gpu_handles, batch_size);
+        self.batch_buffers[buffer_index].upload(gpu_handles, stacked);
+
+        Ok(())
+    }
+
+    // Runs every sample through the graph in a single pass, with one dispatch per node
+    // for the whole batch, returning the output of the last DeviceToHost node for each
+    // sample. Every sample has to have the shape of the input the graph was built with,
+    // and the graph can only have a single HostToDevice node. Softmax is taken per sample,
+    // but the elementwise operators can't broadcast a single row per sample.
+    pub async fn run_batch(
+        &mut self,
+        gpu_handles: &GPUHandles,
+        samples: &[Tensor2D],
+        iteration_count: usize,
+    ) -> Vec<Tensor2D> {
+        self.try_run_batch(gpu_handles, samples, iteration_count)
+            .await
+            .unwrap_or_else(|error| panic!("Failed to run a GPU computational graph! {}", error))
+    }
+
+    pub async fn try_run_batch(
+        &mut self,
+        gpu_handles: &GPUHandles,
+        samples: &[Tensor2D],
+        iteration_count: usize,
+    ) -> Result<Vec<Tensor2D>, GraphError> {
+        self.check_batch(samples)?;
+        let output: Tensor2D = self
+            .try_run_stacked(gpu_handles, &Tensor2D::stack(samples), iteration_count)
+            .await?;
+
+        Ok(output.unstack(samples.len()))
+    }
+
+    // The run_batch version for samples already stacked along the rows, see Tensor2D::stack.
+    // The outputs of the samples are returned stacked in the same order.
+    pub async fn run_stacked(
+        &mut self,
+        gpu_handles: &GPUHandles,
+        stacked: &Tensor2D,
+        iteration_count: usize,
+    ) -> Tensor2D {
+        self.try_run_stacked(gpu_handles, stacked, iteration_count)
+            .await
+            .unwrap_or_else(|error| panic!("Failed to run a GPU computational graph! {}", error))
+    }
+
+    pub async fn try_run_stacked(
+        &mut self,
+        gpu_handles: &GPUHandles,
+        stacked: &Tensor2D,
+        iteration_count: usize,
+    ) -> Result<Tensor2D, GraphError> {
+        if self.output_buffer_indices.is_empty() {
+            return Err(GraphError::NoOutputs);
         }
+        self.bind_stacked(gpu_handles, stacked)?;
 
         for _ in 0..iteration_count {
-            self.submit_operations(gpu_handles);
+            self.submit_operations(gpu_handles, true);
         }
-        self.retrieve_output(gpu_handles).await
+        self.retrieve_outputs(gpu_handles, true)
+            .await?
+            .pop()
+            .ok_or(GraphError::NoOutputs)
     }
 }
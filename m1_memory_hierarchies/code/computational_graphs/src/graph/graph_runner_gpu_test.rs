@@ -1,11 +1,27 @@
 #[cfg(test)]
 mod tests {
     use crate::{
-        graph::graph_runner_gpu::GraphRunnerGPU,
+        graph::{
+            executor::{Backend, Executor},
+            graph_error::GraphError,
+            graph_runner::GraphRunner,
+            graph_runner_gpu::GraphRunnerGPU,
+            linear_tuning::{
+                autotune_and_cache, autotune_linear, candidate_kernels, LinearKernel,
+                LinearTuningCache, TuningResult,
+            },
+            memory_planner::MemoryPlan,
+            profiling::ProfileReport,
+        },
+        immediate::nodes::{
+            linear_from_tensor_2d_blocking, relu_from_tensor_2d, softmax_from_tensor_2d,
+        },
         shared::{
             gpu_utilities::{initialize_gpu, GPUHandles},
-            graph_operators::GraphOperator,
+            graph_operators::{GraphNode, GraphOperator},
             tensor2d::Tensor2D,
+            tensor2d_axis::Axis,
+            tensor2d_precision::{PackedTensor2D, Precision},
         },
     };
 
@@ -36,8 +52,13 @@ mod tests {
 
     #[test]
     fn linear() {
-        let gpu_handles: GPUHandles = pollster::block_on(initialize_gpu(true))
-            .expect("Failed to get GPU handles in graph_runner_test::linear() test");
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping linear test: no usable GPU found");
+                return;
+            }
+        };
 
         let outer_dimension_range: usize = 8;
         let inner_dimension_range: usize = 8;
@@ -80,8 +101,13 @@ mod tests {
 
     #[test]
     fn relu() {
-        let gpu_handles: GPUHandles = pollster::block_on(initialize_gpu(true))
-            .expect("Failed to get GPU handles in graph_runner_test::relu() test");
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping relu test: no usable GPU found");
+                return;
+            }
+        };
 
         let outer_dimension_range: usize = 8;
         let inner_dimension_range: usize = 8;
@@ -117,8 +143,13 @@ mod tests {
 
     #[test]
     fn softmax() {
-        let gpu_handles: GPUHandles = pollster::block_on(initialize_gpu(true))
-            .expect("Failed to get GPU handles in graph_runner_test::softmax() test");
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping softmax test: no usable GPU found");
+                return;
+            }
+        };
 
         let outer_dimension_range: usize = 8;
         let inner_dimension_range: usize = 8;
@@ -131,7 +162,7 @@ mod tests {
 
                 let graph_operators: Vec<GraphOperator> = vec![
                     GraphOperator::HostToDevice { input },
-                    GraphOperator::Softmax,
+                    GraphOperator::Softmax { axis: Axis::Global },
                     GraphOperator::DeviceToHost,
                 ];
 
@@ -154,8 +185,13 @@ mod tests {
 
     #[test]
     fn linear_relu() {
-        let gpu_handles: GPUHandles = pollster::block_on(initialize_gpu(true))
-            .expect("Failed to get GPU handles in graph_runner_test::linear_relu() test");
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping linear_relu test: no usable GPU found");
+                return;
+            }
+        };
 
         let outer_dimension_range: usize = 8;
         let inner_dimension_range: usize = 8;
@@ -199,8 +235,13 @@ mod tests {
 
     #[test]
     fn linear_relu_softmax() {
-        let gpu_handles: GPUHandles = pollster::block_on(initialize_gpu(true))
-            .expect("Failed to get GPU handles in graph_runner_test::linear_relu_softmax() test");
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping linear_relu_softmax test: no usable GPU found");
+                return;
+            }
+        };
 
         let outer_dimension_range: usize = 8;
         let inner_dimension_range: usize = 8;
@@ -245,8 +286,13 @@ mod tests {
 
     #[test]
     fn transfers() {
-        let gpu_handles: GPUHandles = pollster::block_on(initialize_gpu(true))
-            .expect("Failed to get GPU handles in graph_runner_test::linear_relu_softmax() test");
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping transfers test: no usable GPU found");
+                return;
+            }
+        };
 
         let outer_dimension_range: usize = 8;
         let inner_dimension_range: usize = 8;
@@ -278,4 +324,1175 @@ mod tests {
             }
         }
     }
+
+    // Compared per element and relative to the magnitude, so long chains of
+    // operators, and adapters with less precise exp and log, don't add up to a failure
+    fn assert_tensors_match(expected: &Tensor2D, actual: &Tensor2D) {
+        assert_eq!(expected.len(), actual.len());
+        for (index, (expected_value, actual_value)) in
+            expected.data.iter().zip(&actual.data).enumerate()
+        {
+            assert!(
+                (expected_value - actual_value).abs()
+                    <= ERROR_TOLERANCE * expected_value.abs().max(1.0),
+                "Mismatch at index {}, expected {} got {}\nexpected: {:?}\nactual: {:?}",
+                index,
+                expected_value,
+                actual_value,
+                expected,
+                actual
+            );
+        }
+    }
+
+    #[test]
+    fn branches() {
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping branches test: no usable GPU found");
+                return;
+            }
+        };
+
+        for outer_dimension in 1..6 {
+            for inner_dimension in 1..6 {
+                let input: Tensor2D = Tensor2D::new(0.1, outer_dimension, inner_dimension);
+                let weights_a: Tensor2D = Tensor2D::new(0.01, inner_dimension, 3);
+                let bias_a: Tensor2D = Tensor2D::new(-0.1, outer_dimension, 3);
+                let weights_b: Tensor2D = Tensor2D::new(-0.02, inner_dimension, 4);
+                let bias_b: Tensor2D = Tensor2D::new(0.05, outer_dimension, 4);
+
+                // The immediate mode results are the reference
+                let mut linear_a: Tensor2D = Tensor2D::new(0.0, outer_dimension, 3);
+                linear_from_tensor_2d_blocking(
+                    &gpu_handles,
+                    &input,
+                    &weights_a,
+                    &bias_a,
+                    &mut linear_a,
+                );
+                let mut expected_a: Tensor2D = Tensor2D::new(0.0, outer_dimension, 3);
+                pollster::block_on(relu_from_tensor_2d(
+                    &gpu_handles,
+                    &linear_a,
+                    &mut expected_a,
+                ));
+
+                let mut linear_b: Tensor2D = Tensor2D::new(0.0, outer_dimension, 4);
+                linear_from_tensor_2d_blocking(
+                    &gpu_handles,
+                    &input,
+                    &weights_b,
+                    &bias_b,
+                    &mut linear_b,
+                );
+                let mut expected_b: Tensor2D = Tensor2D::new(0.0, outer_dimension, 4);
+                pollster::block_on(softmax_from_tensor_2d(
+                    &gpu_handles,
+                    &linear_b,
+                    &mut expected_b,
+                ));
+
+                let graph_nodes: Vec<GraphNode> = vec![
+                    GraphNode::new(GraphOperator::HostToDevice { input }, vec![]),
+                    GraphNode::new(
+                        GraphOperator::Linear {
+                            weights: weights_a,
+                            bias: bias_a,
+                        },
+                        vec![0],
+                    ),
+                    GraphNode::new(
+                        GraphOperator::Linear {
+                            weights: weights_b,
+                            bias: bias_b,
+                        },
+                        vec![0],
+                    ),
+                    GraphNode::new(GraphOperator::Softmax { axis: Axis::Global }, vec![2]),
+                    GraphNode::new(GraphOperator::ReLU, vec![1]),
+                    GraphNode::new(GraphOperator::DeviceToHost, vec![4]),
+                    GraphNode::new(GraphOperator::DeviceToHost, vec![3]),
+                ];
+
+                for fuse_operators in [false, true] {
+                    let cache_elements: bool = fuse_operators;
+                    let mut graph_runner: GraphRunnerGPU = GraphRunnerGPU::from_graph_nodes(
+                        &gpu_handles,
+                        &graph_nodes,
+                        fuse_operators,
+                        cache_elements,
+                    );
+                    let outputs: Vec<Tensor2D> =
+                        pollster::block_on(graph_runner.run_outputs(&gpu_handles, 1));
+
+                    assert_eq!(outputs.len(), 2);
+                    assert_tensors_match(&expected_a, &outputs[0]);
+                    assert_tensors_match(&expected_b, &outputs[1]);
+                }
+            }
+        }
+    }
+
+    // The Linear output is read by two operators, so it must not be fused into either.
+    #[test]
+    fn shared_intermediate() {
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping shared_intermediate test: no usable GPU found");
+                return;
+            }
+        };
+
+        let input: Tensor2D = Tensor2D::new(0.1, 3, 4);
+        let weights: Tensor2D = Tensor2D::new(-0.01, 4, 5);
+        let bias: Tensor2D = Tensor2D::new(0.02, 3, 5);
+
+        let mut linear_output: Tensor2D = Tensor2D::new(0.0, 3, 5);
+        linear_from_tensor_2d_blocking(&gpu_handles, &input, &weights, &bias, &mut linear_output);
+        let mut expected_relu: Tensor2D = Tensor2D::new(0.0, 3, 5);
+        pollster::block_on(relu_from_tensor_2d(
+            &gpu_handles,
+            &linear_output,
+            &mut expected_relu,
+        ));
+
+        let graph_nodes: Vec<GraphNode> = vec![
+            GraphNode::new(GraphOperator::HostToDevice { input }, vec![]),
+            GraphNode::new(GraphOperator::Linear { weights, bias }, vec![0]),
+            GraphNode::new(GraphOperator::ReLU, vec![1]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![1]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![2]),
+        ];
+
+        for fuse_operators in [false, true] {
+            let cache_elements: bool = true;
+            let mut graph_runner: GraphRunnerGPU = GraphRunnerGPU::from_graph_nodes(
+                &gpu_handles,
+                &graph_nodes,
+                fuse_operators,
+                cache_elements,
+            );
+            let outputs: Vec<Tensor2D> =
+                pollster::block_on(graph_runner.run_outputs(&gpu_handles, 1));
+
+            assert_eq!(outputs.len(), 2);
+            assert_tensors_match(&linear_output, &outputs[0]);
+            assert_tensors_match(&expected_relu, &outputs[1]);
+        }
+    }
+
+    // Row and column vectors are broadcast, and every operator is checked
+    // with the smaller input on either side.
+    #[test]
+    fn elementwise_broadcast() {
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping elementwise_broadcast test: no usable GPU found");
+                return;
+            }
+        };
+
+        let operators: [(GraphOperator, fn(&Tensor2D, &Tensor2D) -> Tensor2D); 4] = [
+            (GraphOperator::Add, Tensor2D::add),
+            (GraphOperator::Subtract, Tensor2D::subtract),
+            (GraphOperator::Multiply, Tensor2D::multiply),
+            (GraphOperator::Divide, Tensor2D::divide),
+        ];
+
+        for (operator, reference) in operators {
+            for row_count in 1..5 {
+                for column_count in 1..5 {
+                    let mut matrix: Tensor2D = Tensor2D::new(0.1, row_count, column_count);
+                    matrix.data.iter_mut().for_each(|x| *x += 1.0);
+                    let mut row_vector: Tensor2D = Tensor2D::new(0.2, 1, column_count);
+                    row_vector.data.iter_mut().for_each(|x| *x += 0.5);
+                    let mut column_vector: Tensor2D = Tensor2D::new(0.3, row_count, 1);
+                    column_vector.data.iter_mut().for_each(|x| *x += 0.5);
+
+                    let pairs: [(&Tensor2D, &Tensor2D); 5] = [
+                        (&matrix, &matrix),
+                        (&matrix, &row_vector),
+                        (&row_vector, &matrix),
+                        (&matrix, &column_vector),
+                        (&column_vector, &matrix),
+                    ];
+
+                    for (left, right) in pairs {
+                        let expected: Tensor2D = reference(left, right);
+
+                        let graph_nodes: Vec<GraphNode> = vec![
+                            GraphNode::new(
+                                GraphOperator::HostToDevice {
+                                    input: left.clone(),
+                                },
+                                vec![],
+                            ),
+                            GraphNode::new(
+                                GraphOperator::HostToDevice {
+                                    input: right.clone(),
+                                },
+                                vec![],
+                            ),
+                            GraphNode::new(operator.clone(), vec![0, 1]),
+                            GraphNode::new(GraphOperator::DeviceToHost, vec![2]),
+                        ];
+
+                        for cache_elements in [false, true] {
+                            let mut graph_runner: GraphRunnerGPU = GraphRunnerGPU::from_graph_nodes(
+                                &gpu_handles,
+                                &graph_nodes,
+                                false,
+                                cache_elements,
+                            );
+                            let output: Tensor2D =
+                                pollster::block_on(graph_runner.run(&gpu_handles, 1));
+                            assert_tensors_match(&expected, &output);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // A residual connection, the input is added to the output of the ReLU
+    #[test]
+    fn residual() {
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping residual test: no usable GPU found");
+                return;
+            }
+        };
+
+        let input: Tensor2D = Tensor2D::new(0.1, 3, 4);
+        let weights: Tensor2D = Tensor2D::new(0.01, 4, 4);
+        let bias: Tensor2D = Tensor2D::new(0.02, 3, 4);
+
+        let mut linear_output: Tensor2D = Tensor2D::new(0.0, 3, 4);
+        linear_from_tensor_2d_blocking(&gpu_handles, &input, &weights, &bias, &mut linear_output);
+        let mut relu_output: Tensor2D = Tensor2D::new(0.0, 3, 4);
+        pollster::block_on(relu_from_tensor_2d(
+            &gpu_handles,
+            &linear_output,
+            &mut relu_output,
+        ));
+        let expected: Tensor2D = Tensor2D::add(&relu_output, &input);
+
+        let graph_nodes: Vec<GraphNode> = vec![
+            GraphNode::new(GraphOperator::HostToDevice { input }, vec![]),
+            GraphNode::new(GraphOperator::Linear { weights, bias }, vec![0]),
+            GraphNode::new(GraphOperator::ReLU, vec![1]),
+            GraphNode::new(GraphOperator::Add, vec![2, 0]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![3]),
+        ];
+
+        for fuse_operators in [false, true] {
+            let cache_elements: bool = true;
+            let mut graph_runner: GraphRunnerGPU = GraphRunnerGPU::from_graph_nodes(
+                &gpu_handles,
+                &graph_nodes,
+                fuse_operators,
+                cache_elements,
+            );
+            let output: Tensor2D = pollster::block_on(graph_runner.run(&gpu_handles, 1));
+            assert_tensors_match(&expected, &output);
+        }
+    }
+
+    // The planned graph has to give the same result as the unplanned one
+    #[test]
+    fn planned_memory() {
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping planned_memory test: no usable GPU found");
+                return;
+            }
+        };
+
+        let mut graph_operators: Vec<GraphOperator> = vec![GraphOperator::HostToDevice {
+            input: Tensor2D::new(0.1, 8, 8),
+        }];
+        for layer in 0..8 {
+            graph_operators.push(GraphOperator::Linear {
+                weights: Tensor2D::new(0.01 / (layer + 1) as f32, 8, 8),
+                bias: Tensor2D::new(0.02, 8, 8),
+            });
+            graph_operators.push(GraphOperator::ReLU);
+        }
+        graph_operators.push(GraphOperator::DeviceToHost);
+
+        for fuse_operators in [false, true] {
+            let cache_elements: bool = true;
+            let mut unplanned: GraphRunnerGPU = GraphRunnerGPU::new(
+                &gpu_handles,
+                &graph_operators,
+                fuse_operators,
+                cache_elements,
+            );
+            let expected: Tensor2D = pollster::block_on(unplanned.run(&gpu_handles, 1));
+
+            let mut planned: GraphRunnerGPU = GraphRunnerGPU::new(
+                &gpu_handles,
+                &graph_operators,
+                fuse_operators,
+                cache_elements,
+            );
+            let plan: MemoryPlan = planned.plan_memory();
+            assert!(plan.buffer_count_after < plan.buffer_count_before);
+
+            let output: Tensor2D = pollster::block_on(planned.run(&gpu_handles, 2));
+            assert_tensors_match(&expected, &output);
+        }
+    }
+
+    // Chains of elementwise operators are fused into a kernel generated by the op_code_compiler.
+    // The fused graph has to give the same result as the unfused one and the CPU.
+    #[test]
+    fn fused_elementwise() {
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping fused_elementwise test: no usable GPU found");
+                return;
+            }
+        };
+
+        let graph_nodes: Vec<GraphNode> = vec![
+            GraphNode::new(
+                GraphOperator::HostToDevice {
+                    input: Tensor2D::new(0.1, 6, 5),
+                },
+                vec![],
+            ),
+            GraphNode::new(
+                GraphOperator::Linear {
+                    weights: Tensor2D::new(0.3, 5, 5),
+                    bias: Tensor2D::new(-0.1, 6, 5),
+                },
+                vec![0],
+            ),
+            GraphNode::new(GraphOperator::ReLU, vec![1]),
+            GraphNode::new(
+                GraphOperator::HostToDevice {
+                    input: Tensor2D::new(0.7, 1, 5),
+                },
+                vec![],
+            ),
+            GraphNode::new(GraphOperator::Subtract, vec![3, 2]),
+            // Starts at 1.0, dividing by 0.0 gives NaN and inf on both sides
+            GraphNode::new(
+                GraphOperator::HostToDevice {
+                    input: Tensor2D {
+                        data: (1..=6).map(|value| value as f32 * 2.0).collect(),
+                        row_count: 6,
+                        column_count: 1,
+                    },
+                },
+                vec![],
+            ),
+            GraphNode::new(GraphOperator::Divide, vec![4, 5]),
+            GraphNode::new(GraphOperator::Add, vec![6, 3]),
+            GraphNode::new(GraphOperator::Multiply, vec![7, 0]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![8]),
+        ];
+
+        let expected: Tensor2D = GraphRunner::from_graph_nodes(&graph_nodes, false).run();
+
+        for fuse_operators in [false, true] {
+            for cache_elements in [false, true] {
+                let mut graph_runner: GraphRunnerGPU = GraphRunnerGPU::from_graph_nodes(
+                    &gpu_handles,
+                    &graph_nodes,
+                    fuse_operators,
+                    cache_elements,
+                );
+                let output: Tensor2D = pollster::block_on(graph_runner.run(&gpu_handles, 1));
+                assert_tensors_match(&expected, &output);
+            }
+        }
+    }
+
+    #[test]
+    fn try_new_invalid_graph() {
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping try_new_invalid_graph test: no usable GPU found");
+                return;
+            }
+        };
+        let graph_operators: Vec<GraphOperator> = vec![
+            GraphOperator::HostToDevice {
+                input: Tensor2D::new(0.5, 3, 4),
+            },
+            GraphOperator::Add,
+            GraphOperator::DeviceToHost,
+        ];
+
+        for use_cache in [false, true] {
+            assert!(matches!(
+                GraphRunnerGPU::try_new(&gpu_handles, &graph_operators, true, use_cache),
+                Err(GraphError::UnsupportedOperator { index: 1, .. })
+            ));
+        }
+
+        let input: Tensor2D = Tensor2D::new(0.1, 4, 3);
+        let expected: Tensor2D = Tensor2D::relu(&input);
+        let graph_operators: Vec<GraphOperator> = vec![
+            GraphOperator::HostToDevice { input },
+            GraphOperator::ReLU,
+            GraphOperator::DeviceToHost,
+        ];
+        let mut graph_runner: GraphRunnerGPU =
+            GraphRunnerGPU::try_new(&gpu_handles, &graph_operators, false, true).unwrap();
+        let output: Tensor2D = pollster::block_on(graph_runner.try_run(&gpu_handles, 1)).unwrap();
+        let difference: Tensor2D = subtract_tensors(&expected, &output);
+        assert!(difference
+            .data
+            .iter()
+            .all(|value| value.abs() < ERROR_TOLERANCE));
+    }
+
+    // Only the input is uploaded, the result has to match a CPU graph built for every input
+    #[test]
+    fn run_with() {
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping run_with test: no usable GPU found");
+                return;
+            }
+        };
+
+        let graph_operators = |input: Tensor2D| -> Vec<GraphOperator> {
+            vec![
+                GraphOperator::HostToDevice { input },
+                GraphOperator::Linear {
+                    weights: Tensor2D::new(0.03, 5, 4),
+                    bias: Tensor2D::new(-0.1, 3, 4),
+                },
+                GraphOperator::ReLU,
+                GraphOperator::Linear {
+                    weights: Tensor2D::new(-0.02, 4, 6),
+                    bias: Tensor2D::new(0.05, 3, 6),
+                },
+                GraphOperator::DeviceToHost,
+            ]
+        };
+
+        for fuse_operators in [false, true] {
+            for planned_memory in [false, true] {
+                let mut graph_runner: GraphRunnerGPU = GraphRunnerGPU::new(
+                    &gpu_handles,
+                    &graph_operators(Tensor2D::new(0.5, 3, 5)),
+                    fuse_operators,
+                    true,
+                );
+                if planned_memory {
+                    graph_runner.plan_memory();
+                }
+
+                for scale in 1..5 {
+                    let input: Tensor2D = Tensor2D::new(0.1 * scale as f32, 3, 5);
+                    let expected: Tensor2D =
+                        GraphRunner::new(&graph_operators(input.clone()), false).run();
+                    let output: Tensor2D =
+                        pollster::block_on(graph_runner.run_with(&gpu_handles, &input, 1));
+                    assert_tensors_match(&expected, &output);
+                }
+
+                assert_eq!(
+                    pollster::block_on(graph_runner.try_run_with(
+                        &gpu_handles,
+                        &Tensor2D::new(0.1, 5, 3),
+                        1
+                    ))
+                    .err(),
+                    Some(GraphError::ShapeMismatch {
+                        index: 0,
+                        tensor: "input",
+                        expected: (3, 5),
+                        actual: (5, 3),
+                    })
+                );
+            }
+        }
+    }
+
+    // The whole batch runs with one dispatch per node, every sample
+    // has to get the output, softmax included, it gets on its own
+    #[test]
+    fn run_batch() {
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping run_batch test: no usable GPU found");
+                return;
+            }
+        };
+
+        let graph_operators = |input: Tensor2D, relu_before_softmax: bool| -> Vec<GraphOperator> {
+            let mut graph_operators: Vec<GraphOperator> = vec![
+                GraphOperator::HostToDevice { input },
+                GraphOperator::Linear {
+                    weights: Tensor2D::new(0.03, 5, 4),
+                    bias: Tensor2D::new(-0.1, 3, 4),
+                },
+                GraphOperator::ReLU,
+                GraphOperator::Linear {
+                    weights: Tensor2D::new(-0.02, 4, 6),
+                    bias: Tensor2D::new(0.05, 3, 6),
+                },
+            ];
+            if relu_before_softmax {
+                graph_operators.push(GraphOperator::ReLU);
+            }
+            graph_operators.push(GraphOperator::Softmax { axis: Axis::Global });
+            graph_operators.push(GraphOperator::DeviceToHost);
+            graph_operators
+        };
+
+        for relu_before_softmax in [false, true] {
+            for fuse_operators in [false, true] {
+                for planned_memory in [false, true] {
+                    let mut graph_runner: GraphRunnerGPU = GraphRunnerGPU::new(
+                        &gpu_handles,
+                        &graph_operators(Tensor2D::new(0.5, 3, 5), relu_before_softmax),
+                        fuse_operators,
+                        true,
+                    );
+                    if planned_memory {
+                        graph_runner.plan_memory();
+                    }
+
+                    for batch_size in [1, 5, 2] {
+                        let samples: Vec<Tensor2D> = (0..batch_size)
+                            .map(|sample| Tensor2D::new(0.4 - 0.15 * sample as f32, 3, 5))
+                            .collect();
+                        let outputs: Vec<Tensor2D> =
+                            pollster::block_on(graph_runner.run_batch(&gpu_handles, &samples, 1));
+                        assert_eq!(outputs.len(), batch_size);
+
+                        for (sample, output) in samples.iter().zip(&outputs) {
+                            let expected: Tensor2D = GraphRunner::new(
+                                &graph_operators(sample.clone(), relu_before_softmax),
+                                false,
+                            )
+                            .run();
+                            assert_tensors_match(&expected, output);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut graph_runner: GraphRunnerGPU = GraphRunnerGPU::new(
+            &gpu_handles,
+            &graph_operators(Tensor2D::new(0.5, 3, 5), false),
+            true,
+            true,
+        );
+        assert_eq!(
+            pollster::block_on(graph_runner.try_run_stacked(
+                &gpu_handles,
+                &Tensor2D::new(0.1, 4, 5),
+                1
+            ))
+            .err(),
+            Some(GraphError::BatchShape {
+                sample: (3, 5),
+                actual: (4, 5),
+            })
+        );
+    }
+
+    // A mean along Axis::Rows is a column vector, which is stacked along with the samples,
+    // while a mean along Axis::Columns is a single row per sample, which the elementwise
+    // shaders can't broadcast over the rows of each sample once the samples are stacked.
+    #[test]
+    fn run_batch_broadcast() {
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping run_batch_broadcast test: no usable GPU found");
+                return;
+            }
+        };
+
+        let graph_nodes = |input: Tensor2D, axis: Axis| -> Vec<GraphNode> {
+            vec![
+                GraphNode::new(GraphOperator::HostToDevice { input }, vec![]),
+                GraphNode::new(GraphOperator::Mean { axis }, vec![0]),
+                GraphNode::new(GraphOperator::Subtract, vec![0, 1]),
+                GraphNode::new(GraphOperator::DeviceToHost, vec![2]),
+            ]
+        };
+
+        let samples: Vec<Tensor2D> = (0..3)
+            .map(|sample| Tensor2D::new(0.4 - 0.15 * sample as f32, 3, 5))
+            .collect();
+        for fuse_operators in [false, true] {
+            let mut graph_runner: GraphRunnerGPU = GraphRunnerGPU::from_graph_nodes(
+                &gpu_handles,
+                &graph_nodes(samples[0].clone(), Axis::Rows),
+                fuse_operators,
+                true,
+            );
+            let outputs: Vec<Tensor2D> =
+                pollster::block_on(graph_runner.run_batch(&gpu_handles, &samples, 1));
+            for (sample, output) in samples.iter().zip(&outputs) {
+                let expected: Tensor2D =
+                    GraphRunner::from_graph_nodes(&graph_nodes(sample.clone(), Axis::Rows), false)
+                        .run();
+                assert_tensors_match(&expected, output);
+            }
+
+            let mut graph_runner: GraphRunnerGPU = GraphRunnerGPU::from_graph_nodes(
+                &gpu_handles,
+                &graph_nodes(samples[0].clone(), Axis::Columns),
+                fuse_operators,
+                true,
+            );
+            assert!(matches!(
+                pollster::block_on(graph_runner.try_run_batch(&gpu_handles, &samples, 1)),
+                Err(GraphError::BatchBroadcast { .. })
+            ));
+
+            // A single sample still broadcasts the row
+            let expected: Tensor2D = GraphRunner::from_graph_nodes(
+                &graph_nodes(samples[0].clone(), Axis::Columns),
+                false,
+            )
+            .run();
+            let output: Tensor2D = pollster::block_on(graph_runner.run(&gpu_handles, 1));
+            assert_tensors_match(&expected, &output);
+        }
+    }
+
+    // Values which aren't sorted along either axis, so every row and column
+    // has its maximum somewhere else
+    fn unsorted_tensor(row_count: usize, column_count: usize) -> Tensor2D {
+        let mut tensor: Tensor2D = Tensor2D::new(0.0, row_count, column_count);
+        for (index, value) in tensor.data.iter_mut().enumerate() {
+            *value = ((index * 7) % 11) as f32 * 0.3 - 1.5;
+        }
+        tensor
+    }
+
+    // Softmax along an axis, LogSoftmax and the reductions against the CPU graph runner,
+    // on their own and for a batch
+    #[test]
+    fn axis_operators() {
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping axis_operators test: no usable GPU found");
+                return;
+            }
+        };
+
+        let axes: [Axis; 3] = [Axis::Global, Axis::Rows, Axis::Columns];
+        let operators: Vec<GraphOperator> = axes
+            .iter()
+            .flat_map(|axis| {
+                let axis: Axis = *axis;
+                vec![
+                    GraphOperator::LogSoftmax { axis },
+                    GraphOperator::Sum { axis },
+                    GraphOperator::Max { axis },
+                    GraphOperator::Mean { axis },
+                    GraphOperator::ArgMax { axis },
+                ]
+            })
+            .chain([
+                GraphOperator::Softmax { axis: Axis::Rows },
+                GraphOperator::Softmax {
+                    axis: Axis::Columns,
+                },
+            ])
+            .collect();
+
+        for (row_count, column_count) in [(1, 1), (1, 40), (40, 1), (3, 5), (37, 33)] {
+            for operator in &operators {
+                let graph_operators = |input: Tensor2D| -> Vec<GraphOperator> {
+                    vec![
+                        GraphOperator::HostToDevice { input },
+                        operator.clone(),
+                        GraphOperator::DeviceToHost,
+                    ]
+                };
+                let input: Tensor2D = unsorted_tensor(row_count, column_count);
+                let expected: Tensor2D =
+                    GraphRunner::new(&graph_operators(input.clone()), false).run();
+
+                let mut graph_runner: GraphRunnerGPU =
+                    GraphRunnerGPU::new(&gpu_handles, &graph_operators(input), true, true);
+                let output: Tensor2D = pollster::block_on(graph_runner.run(&gpu_handles, 1));
+                assert_eq!(
+                    (expected.row_count, expected.column_count),
+                    (output.row_count, output.column_count),
+                    "{:?}",
+                    operator
+                );
+                assert_tensors_match(&expected, &output);
+
+                let samples: Vec<Tensor2D> = (0..3)
+                    .map(|sample| {
+                        Tensor2D::new(0.4 - 0.15 * sample as f32, row_count, column_count)
+                    })
+                    .collect();
+                let outputs: Vec<Tensor2D> =
+                    pollster::block_on(graph_runner.run_batch(&gpu_handles, &samples, 1));
+                for (sample, output) in samples.iter().zip(&outputs) {
+                    let expected: Tensor2D =
+                        GraphRunner::new(&graph_operators(sample.clone()), false).run();
+                    assert_tensors_match(&expected, output);
+                }
+            }
+        }
+    }
+
+    // The GPU runner has its own transfers, but fuses the same groups as the CPU runner
+    // The axis operators followed by elementwise operators run as a single generated kernel,
+    // which has to give the same result as the unfused graph and the CPU, batched too
+    #[test]
+    fn fused_axis_operators() {
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping fused_axis_operators test: no usable GPU found");
+                return;
+            }
+        };
+
+        let axes: [Axis; 3] = [Axis::Global, Axis::Rows, Axis::Columns];
+        let operators: Vec<GraphOperator> = axes
+            .iter()
+            .flat_map(|axis| {
+                let axis: Axis = *axis;
+                vec![
+                    GraphOperator::LogSoftmax { axis },
+                    GraphOperator::Sum { axis },
+                    GraphOperator::Max { axis },
+                    GraphOperator::Mean { axis },
+                    GraphOperator::ArgMax { axis },
+                ]
+            })
+            .chain([
+                GraphOperator::Softmax { axis: Axis::Rows },
+                GraphOperator::Softmax {
+                    axis: Axis::Columns,
+                },
+            ])
+            .collect();
+
+        for (row_count, column_count) in [(1, 40), (3, 5), (37, 33)] {
+            for operator in &operators {
+                let (axis, output_shape): (Axis, (usize, usize)) = match operator {
+                    GraphOperator::Softmax { axis } | GraphOperator::LogSoftmax { axis } => {
+                        (*axis, (row_count, column_count))
+                    }
+                    _ => {
+                        let (_, axis): (_, Axis) = operator.reduction().unwrap();
+                        (axis, axis.reduced_shape((row_count, column_count)))
+                    }
+                };
+                // (operand - value) is relu'ed and scaled by a row vector
+                let graph_nodes = |input: Tensor2D| -> Vec<GraphNode> {
+                    vec![
+                        GraphNode::new(GraphOperator::HostToDevice { input }, vec![]),
+                        GraphNode::new(operator.clone(), vec![0]),
+                        GraphNode::new(
+                            GraphOperator::HostToDevice {
+                                input: unsorted_tensor(output_shape.0, output_shape.1),
+                            },
+                            vec![],
+                        ),
+                        GraphNode::new(GraphOperator::Subtract, vec![2, 1]),
+                        GraphNode::new(GraphOperator::ReLU, vec![3]),
+                        GraphNode::new(
+                            GraphOperator::HostToDevice {
+                                input: Tensor2D::new(0.5, 1, output_shape.1),
+                            },
+                            vec![],
+                        ),
+                        GraphNode::new(GraphOperator::Multiply, vec![4, 5]),
+                        GraphNode::new(GraphOperator::DeviceToHost, vec![6]),
+                    ]
+                };
+                let input: Tensor2D = unsorted_tensor(row_count, column_count);
+                let expected: Tensor2D =
+                    GraphRunner::from_graph_nodes(&graph_nodes(input.clone()), false).run();
+
+                for fuse_operators in [false, true] {
+                    for cache_elements in [false, true] {
+                        let mut graph_runner: GraphRunnerGPU = GraphRunnerGPU::from_graph_nodes(
+                            &gpu_handles,
+                            &graph_nodes(input.clone()),
+                            fuse_operators,
+                            cache_elements,
+                        );
+                        if fuse_operators {
+                            assert!(graph_runner.to_mermaid().contains(&format!(
+                                "fused {} ({}) -> Subtract (reversed) -> ReLU -> Multiply",
+                                operator.name(),
+                                axis.name()
+                            )));
+                        }
+                        let output: Tensor2D =
+                            pollster::block_on(graph_runner.run(&gpu_handles, 1));
+                        assert_tensors_match(&expected, &output);
+                    }
+                }
+
+                // Batches have a single input, so the axis operator is only followed by a ReLU
+                let graph_operators = |input: Tensor2D| -> Vec<GraphOperator> {
+                    vec![
+                        GraphOperator::HostToDevice { input },
+                        operator.clone(),
+                        GraphOperator::ReLU,
+                        GraphOperator::DeviceToHost,
+                    ]
+                };
+                let mut graph_runner: GraphRunnerGPU =
+                    GraphRunnerGPU::new(&gpu_handles, &graph_operators(input), true, true);
+                let samples: Vec<Tensor2D> = (0..3)
+                    .map(|sample| {
+                        let mut tensor: Tensor2D = unsorted_tensor(row_count, column_count);
+                        for value in &mut tensor.data {
+                            *value *= 1.0 + sample as f32;
+                        }
+                        tensor
+                    })
+                    .collect();
+                let outputs: Vec<Tensor2D> =
+                    pollster::block_on(graph_runner.run_batch(&gpu_handles, &samples, 1));
+                for (sample, output) in samples.iter().zip(&outputs) {
+                    let expected: Tensor2D =
+                        GraphRunner::new(&graph_operators(sample.clone()), false).run();
+                    assert_tensors_match(&expected, output);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn export() {
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping export test: no usable GPU found");
+                return;
+            }
+        };
+
+        let graph_operators: Vec<GraphOperator> = vec![
+            GraphOperator::HostToDevice {
+                input: Tensor2D::new(0.1, 2, 3),
+            },
+            GraphOperator::Linear {
+                weights: Tensor2D::new(0.2, 3, 4),
+                bias: Tensor2D::new(0.3, 2, 4),
+            },
+            GraphOperator::ReLU,
+            GraphOperator::Softmax { axis: Axis::Rows },
+            GraphOperator::DeviceToHost,
+        ];
+        let graph_runner: GraphRunnerGPU =
+            GraphRunnerGPU::new(&gpu_handles, &graph_operators, true, false);
+
+        let dot: String = graph_runner.to_dot();
+        assert!(dot.starts_with("digraph GraphRunnerGPU {"));
+        assert!(dot.contains("    HostToDevice_0 -> buffer_0;\n"));
+        assert!(dot.contains("    subgraph cluster_FusedElementwise_0 {\n"));
+        assert!(dot.contains("        label=\"fused Linear -> ReLU\";\n"));
+        assert!(dot.contains("    Softmax_0 [label=\"Softmax_0\\naxis Rows\", shape=box];\n"));
+        assert!(dot.contains("    buffer_4 -> DeviceToHost_0;\n"));
+
+        let mermaid: String = graph_runner.to_mermaid();
+        assert!(mermaid.starts_with("flowchart TD\n"));
+        assert!(
+            mermaid.contains("    subgraph fused_FusedElementwise_0 [\"fused Linear -> ReLU\"]\n")
+        );
+    }
+
+    // Adapters without timestamp queries give no report instead of failing
+    #[test]
+    fn profile() {
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping profile test: no usable GPU found");
+                return;
+            }
+        };
+
+        // Small enough values that the logits stay in a range where softmax isn't
+        // dominated by rounding differences in the Linear kernel
+        let graph_operators: Vec<GraphOperator> = vec![
+            GraphOperator::HostToDevice {
+                input: Tensor2D::new(0.001, 16, 32),
+            },
+            GraphOperator::Linear {
+                weights: Tensor2D::new(0.001, 32, 64),
+                bias: Tensor2D::new(0.001, 16, 64),
+            },
+            GraphOperator::ReLU,
+            GraphOperator::Softmax { axis: Axis::Rows },
+            GraphOperator::DeviceToHost,
+        ];
+        let supports_timestamps: bool = gpu_handles
+            .device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY);
+
+        for (fuse_operators, expected_names) in [
+            (false, vec!["Linear_0", "ReLU_0", "Softmax_0"]),
+            (true, vec!["FusedElementwise_0", "Softmax_0"]),
+        ] {
+            let mut graph_runner: GraphRunnerGPU =
+                GraphRunnerGPU::new(&gpu_handles, &graph_operators, fuse_operators, true);
+            let report: Option<ProfileReport> =
+                pollster::block_on(graph_runner.profile(&gpu_handles))
+                    .expect("Failed to profile the graph in graph_runner_test::profile() test");
+            assert_eq!(report.is_some(), supports_timestamps);
+
+            if let Some(report) = report {
+                let names: Vec<&str> = report
+                    .timings
+                    .iter()
+                    .map(|timing| timing.name.as_str())
+                    .collect();
+                assert_eq!(names, expected_names);
+                assert_eq!(report.timings[0].start_nanoseconds, 0.0);
+            }
+
+            // The graph still runs as before
+            let expected: Tensor2D = GraphRunner::new(&graph_operators, false).run();
+            let output: Tensor2D = pollster::block_on(graph_runner.run(&gpu_handles, 1));
+            assert_tensors_match(&expected, &output);
+        }
+    }
+
+    // A query set only has room for QUERY_SET_MAX_QUERIES / 2 nodes,
+    // larger graphs are profiled in chunks
+    #[test]
+    fn profile_large_graph() {
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping profile_large_graph test: no usable GPU found");
+                return;
+            }
+        };
+
+        let relu_count: usize = (wgpu::QUERY_SET_MAX_QUERIES / 2) as usize + 100;
+        let mut graph_operators: Vec<GraphOperator> = vec![GraphOperator::HostToDevice {
+            input: Tensor2D::new(-0.5, 4, 8),
+        }];
+        graph_operators.extend((0..relu_count).map(|_| GraphOperator::ReLU));
+        graph_operators.push(GraphOperator::DeviceToHost);
+
+        let mut graph_runner: GraphRunnerGPU =
+            GraphRunnerGPU::new(&gpu_handles, &graph_operators, false, true);
+        let report: Option<ProfileReport> = pollster::block_on(graph_runner.profile(&gpu_handles))
+            .expect("Failed to profile the graph in graph_runner_test::profile_large_graph() test");
+
+        if let Some(report) = report {
+            assert_eq!(report.timings.len(), relu_count);
+            assert_eq!(report.timings[0].start_nanoseconds, 0.0);
+            assert_eq!(
+                report.timings[relu_count - 1].name,
+                format!("ReLU_{}", relu_count - 1)
+            );
+        }
+
+        // The graph still runs as before
+        let expected: Tensor2D = GraphRunner::new(&graph_operators, false).run();
+        let output: Tensor2D = pollster::block_on(graph_runner.run(&gpu_handles, 1));
+        assert_tensors_match(&expected, &output);
+    }
+
+    // The GPU output of every graph is compared to the CPU output
+    #[test]
+    fn executor_cross_check() {
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping executor_cross_check test: no usable GPU found");
+                return;
+            }
+        };
+        let executor: Executor = Executor::gpu(gpu_handles).with_cross_check(0.0001);
+        assert_eq!(executor.backend(), Backend::GPU);
+
+        for axis in [Axis::Global, Axis::Rows, Axis::Columns] {
+            let graph_operators: Vec<GraphOperator> = vec![
+                GraphOperator::HostToDevice {
+                    input: Tensor2D::new(0.1, 5, 7),
+                },
+                GraphOperator::Linear {
+                    weights: Tensor2D::new(0.02, 7, 3),
+                    bias: Tensor2D::new(0.01, 5, 3),
+                },
+                GraphOperator::ReLU,
+                GraphOperator::Softmax { axis },
+                GraphOperator::DeviceToHost,
+            ];
+            let output: Result<Tensor2D, GraphError> = executor.try_run(&graph_operators);
+            assert!(output.is_ok(), "{:?}: {:?}", axis, output.err());
+        }
+    }
+
+    // Every candidate the autotuner can pick has to give the same output,
+    // with and without the cache and fused with ReLU
+    #[test]
+    fn linear_kernels() {
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping linear_kernels test: no usable GPU found");
+                return;
+            }
+        };
+
+        for (rows, inner, columns) in [(1, 1, 1), (3, 5, 7), (17, 33, 9), (40, 70, 65)] {
+            let input: Tensor2D = Tensor2D::new(0.01, rows, inner);
+            let weights: Tensor2D = Tensor2D::new(0.02, inner, columns);
+            let bias: Tensor2D = Tensor2D::new(0.1, rows, columns);
+            let graph_operators: Vec<GraphOperator> = vec![
+                GraphOperator::HostToDevice { input },
+                GraphOperator::Linear { weights, bias },
+                GraphOperator::ReLU,
+                GraphOperator::DeviceToHost,
+            ];
+            let expected: Tensor2D = GraphRunner::new(&graph_operators, false).run();
+
+            for kernel in candidate_kernels(&gpu_handles.device.limits()) {
+                for (fuse_operators, use_cache) in [(false, false), (true, true)] {
+                    let mut graph_runner: GraphRunnerGPU = GraphRunnerGPU::new(
+                        &gpu_handles,
+                        &graph_operators,
+                        fuse_operators,
+                        use_cache,
+                    );
+                    graph_runner.set_linear_kernel(&gpu_handles, kernel);
+                    assert_eq!(graph_runner.linear_kernel(), kernel);
+                    let output: Tensor2D = pollster::block_on(graph_runner.run(&gpu_handles, 1));
+                    for (expected, output) in expected.data.iter().zip(&output.data) {
+                        assert!(
+                            (expected - output).abs() <= expected.abs().max(1.0) * 0.0001,
+                            "{} on {}x{}x{}",
+                            kernel.name(),
+                            rows,
+                            inner,
+                            columns
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn linear_autotuning() {
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping linear_autotuning test: no usable GPU found");
+                return;
+            }
+        };
+
+        let results: Vec<TuningResult> =
+            pollster::block_on(autotune_linear(&gpu_handles, 37, 51, 29, 2));
+        assert_eq!(
+            results.len(),
+            candidate_kernels(&gpu_handles.device.limits()).len()
+        );
+        for pair in results.windows(2) {
+            assert!(pair[0].nanoseconds <= pair[1].nanoseconds);
+        }
+
+        let path: std::path::PathBuf = std::env::temp_dir().join(format!(
+            "computational_graphs_autotune_{}/linear_kernels.json",
+            std::process::id()
+        ));
+        let path_string: &str = path.to_str().unwrap();
+        let best: LinearKernel = autotune_and_cache(&gpu_handles, path_string, 64, 2, 0)
+            .expect("Every Linear kernel differed from the CPU");
+        let cache: LinearTuningCache = LinearTuningCache::load(path_string);
+        assert_eq!(cache.kernel(&gpu_handles.adapter_info), Some(best));
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    // The packed parameters are decoded in the shader, which has to give what the
+    // packed CPU kernel gives. The element counts aren't multiples of 4, so the last
+    // word of every packed buffer is only partially used.
+    #[test]
+    fn quantized_linear() {
+        let gpu_handles: GPUHandles = match pollster::block_on(initialize_gpu(true)) {
+            Some(gpu_handles) => gpu_handles,
+            None => {
+                println!("Skipping quantized_linear test: no usable GPU found");
+                return;
+            }
+        };
+
+        let input: Tensor2D = unsorted_tensor(3, 7);
+        let weights: Tensor2D = unsorted_tensor(7, 5);
+        let mut bias: Tensor2D = unsorted_tensor(3, 5);
+        bias.data.reverse();
+
+        for precision in [
+            Precision::F32,
+            Precision::F16,
+            Precision::BF16,
+            Precision::Int8,
+        ] {
+            let packed_weights: PackedTensor2D = PackedTensor2D::from_tensor2d(&weights, precision);
+            let packed_bias: PackedTensor2D = PackedTensor2D::from_tensor2d(&bias, precision);
+            let graph_operators: Vec<GraphOperator> = vec![
+                GraphOperator::HostToDevice {
+                    input: input.clone(),
+                },
+                GraphOperator::QuantizedLinear {
+                    weights: packed_weights.clone(),
+                    bias: packed_bias.clone(),
+                },
+                GraphOperator::DeviceToHost,
+            ];
+
+            for use_cache in [false, true] {
+                let mut graph_runner: GraphRunnerGPU =
+                    GraphRunnerGPU::new(&gpu_handles, &graph_operators, false, use_cache);
+                let output: Tensor2D = pollster::block_on(graph_runner.run(&gpu_handles, 1));
+                let expected: Tensor2D =
+                    PackedTensor2D::linear(&input, &packed_weights, &packed_bias);
+                assert_tensors_match(&expected, &output);
+
+                let samples: Vec<Tensor2D> = (0..3)
+                    .map(|sample| {
+                        let mut sample_input: Tensor2D = input.clone();
+                        sample_input.data.rotate_left(sample * 2);
+                        sample_input
+                    })
+                    .collect();
+                let outputs: Vec<Tensor2D> =
+                    pollster::block_on(graph_runner.run_batch(&gpu_handles, &samples, 1));
+                for (sample, output) in samples.iter().zip(&outputs) {
+                    let expected: Tensor2D =
+                        PackedTensor2D::linear(sample, &packed_weights, &packed_bias);
+                    assert_tensors_match(&expected, output);
+                }
+            }
+        }
+    }
 }
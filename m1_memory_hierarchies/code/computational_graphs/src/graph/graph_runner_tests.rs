@@ -2,8 +2,12 @@
 mod tests {
 
     use crate::{
-        graph::graph_runner::GraphRunner,
-        shared::{graph_operators::GraphOperator, tensor2d::Tensor2D},
+        graph::{graph_error::GraphError, graph_runner::GraphRunner},
+        shared::{
+            graph_operators::{GraphNode, GraphOperator},
+            tensor2d::Tensor2D,
+            tensor2d_axis::{Axis, Reduction},
+        },
     };
 
     const ERROR_TOLERANCE: f32 = 0.00001;
@@ -109,7 +113,7 @@ mod tests {
 
                 let graph_operators: Vec<GraphOperator> = vec![
                     GraphOperator::HostToDevice { input },
-                    GraphOperator::Softmax,
+                    GraphOperator::Softmax { axis: Axis::Global },
                     GraphOperator::DeviceToHost,
                 ];
 
@@ -227,4 +231,753 @@ mod tests {
             }
         }
     }
+
+    fn assert_tensors_match(expected: &Tensor2D, actual: &Tensor2D) {
+        assert_eq!(expected.len(), actual.len());
+        let difference: Tensor2D = Tensor2D::subtraction(expected, actual);
+        let abs_difference: f32 = difference.data.iter().map(|x| x.abs()).sum::<f32>();
+        assert!(
+            abs_difference < ERROR_TOLERANCE,
+            "\nexpected: {:?}\nactual: {:?}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn branches() {
+        for outer_dimension in 1..6 {
+            for inner_dimension in 1..6 {
+                let input: Tensor2D = Tensor2D::new(0.1, outer_dimension, inner_dimension);
+                let weights_a: Tensor2D = Tensor2D::new(0.01, inner_dimension, 3);
+                let bias_a: Tensor2D = Tensor2D::new(-0.1, outer_dimension, 3);
+                let weights_b: Tensor2D = Tensor2D::new(-0.02, inner_dimension, 4);
+                let bias_b: Tensor2D = Tensor2D::new(0.05, outer_dimension, 4);
+
+                let expected_a: Tensor2D =
+                    Tensor2D::relu(&Tensor2D::linear(&input, &weights_a, &bias_a));
+                let expected_b: Tensor2D =
+                    Tensor2D::softmax(&Tensor2D::linear(&input, &weights_b, &bias_b));
+
+                let graph_nodes: Vec<GraphNode> = vec![
+                    GraphNode::new(GraphOperator::HostToDevice { input }, vec![]),
+                    GraphNode::new(
+                        GraphOperator::Linear {
+                            weights: weights_a,
+                            bias: bias_a,
+                        },
+                        vec![0],
+                    ),
+                    GraphNode::new(
+                        GraphOperator::Linear {
+                            weights: weights_b,
+                            bias: bias_b,
+                        },
+                        vec![0],
+                    ),
+                    GraphNode::new(GraphOperator::Softmax { axis: Axis::Global }, vec![2]),
+                    GraphNode::new(GraphOperator::ReLU, vec![1]),
+                    GraphNode::new(GraphOperator::DeviceToHost, vec![4]),
+                    GraphNode::new(GraphOperator::DeviceToHost, vec![3]),
+                ];
+
+                for fuse_operators in [false, true] {
+                    let mut graph_runner: GraphRunner =
+                        GraphRunner::from_graph_nodes(&graph_nodes, fuse_operators);
+                    let outputs: Vec<Tensor2D> = graph_runner.run_outputs();
+
+                    assert_eq!(outputs.len(), 2);
+                    assert_tensors_match(&expected_a, &outputs[0]);
+                    assert_tensors_match(&expected_b, &outputs[1]);
+                }
+            }
+        }
+    }
+
+    // The Linear output is read by two operators, so it must not be fused into either.
+    #[test]
+    fn shared_intermediate() {
+        let input: Tensor2D = Tensor2D::new(0.1, 3, 4);
+        let weights: Tensor2D = Tensor2D::new(-0.01, 4, 5);
+        let bias: Tensor2D = Tensor2D::new(0.02, 3, 5);
+
+        let linear_output: Tensor2D = Tensor2D::linear(&input, &weights, &bias);
+        let expected_relu: Tensor2D = Tensor2D::relu(&linear_output);
+        let expected_softmax: Tensor2D = Tensor2D::softmax(&expected_relu);
+
+        let graph_nodes: Vec<GraphNode> = vec![
+            GraphNode::new(GraphOperator::HostToDevice { input }, vec![]),
+            GraphNode::new(GraphOperator::Linear { weights, bias }, vec![0]),
+            GraphNode::new(GraphOperator::ReLU, vec![1]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![1]),
+            GraphNode::new(GraphOperator::Softmax { axis: Axis::Global }, vec![2]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![4]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![2]),
+        ];
+
+        for fuse_operators in [false, true] {
+            let mut graph_runner: GraphRunner =
+                GraphRunner::from_graph_nodes(&graph_nodes, fuse_operators);
+            let outputs: Vec<Tensor2D> = graph_runner.run_outputs();
+
+            assert_eq!(outputs.len(), 3);
+            assert_tensors_match(&linear_output, &outputs[0]);
+            assert_tensors_match(&expected_softmax, &outputs[1]);
+            assert_tensors_match(&expected_relu, &outputs[2]);
+        }
+    }
+
+    // Node ids don't have to be in execution order
+    #[test]
+    fn unordered_nodes() {
+        let input: Tensor2D = Tensor2D::new(0.1, 2, 3);
+        let weights: Tensor2D = Tensor2D::new(0.01, 3, 2);
+        let bias: Tensor2D = Tensor2D::new(0.02, 2, 2);
+
+        let expected: Tensor2D = Tensor2D::relu(&Tensor2D::linear(&input, &weights, &bias));
+
+        let graph_nodes: Vec<GraphNode> = vec![
+            GraphNode::new(GraphOperator::DeviceToHost, vec![3]),
+            GraphNode::new(GraphOperator::Linear { weights, bias }, vec![2]),
+            GraphNode::new(GraphOperator::HostToDevice { input }, vec![]),
+            GraphNode::new(GraphOperator::ReLU, vec![1]),
+        ];
+
+        let mut graph_runner: GraphRunner = GraphRunner::from_graph_nodes(&graph_nodes, true);
+        assert_tensors_match(&expected, &graph_runner.run());
+    }
+
+    // A residual connection, the input is added to the output of the ReLU
+    #[test]
+    fn residual() {
+        let input: Tensor2D = Tensor2D::new(0.1, 3, 4);
+        let weights: Tensor2D = Tensor2D::new(0.01, 4, 4);
+        let bias: Tensor2D = Tensor2D::new(0.02, 3, 4);
+
+        let expected: Tensor2D = Tensor2D::add(
+            &Tensor2D::relu(&Tensor2D::linear(&input, &weights, &bias)),
+            &input,
+        );
+
+        let graph_nodes: Vec<GraphNode> = vec![
+            GraphNode::new(GraphOperator::HostToDevice { input }, vec![]),
+            GraphNode::new(GraphOperator::Linear { weights, bias }, vec![0]),
+            GraphNode::new(GraphOperator::ReLU, vec![1]),
+            GraphNode::new(GraphOperator::Add, vec![2, 0]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![3]),
+        ];
+
+        for fuse_operators in [false, true] {
+            let mut graph_runner: GraphRunner =
+                GraphRunner::from_graph_nodes(&graph_nodes, fuse_operators);
+            assert_tensors_match(&expected, &graph_runner.run());
+        }
+    }
+
+    // Row and column vectors are broadcast, and every operator is checked
+    // with the smaller input on either side.
+    #[test]
+    fn elementwise_broadcast() {
+        let operators: [(GraphOperator, fn(&Tensor2D, &Tensor2D) -> Tensor2D); 4] = [
+            (GraphOperator::Add, Tensor2D::add),
+            (GraphOperator::Subtract, Tensor2D::subtract),
+            (GraphOperator::Multiply, Tensor2D::multiply),
+            (GraphOperator::Divide, Tensor2D::divide),
+        ];
+
+        for (operator, reference) in operators {
+            for row_count in 1..5 {
+                for column_count in 1..5 {
+                    let mut matrix: Tensor2D = Tensor2D::new(0.1, row_count, column_count);
+                    matrix.data.iter_mut().for_each(|x| *x += 1.0);
+                    let mut row_vector: Tensor2D = Tensor2D::new(0.2, 1, column_count);
+                    row_vector.data.iter_mut().for_each(|x| *x += 0.5);
+                    let mut column_vector: Tensor2D = Tensor2D::new(0.3, row_count, 1);
+                    column_vector.data.iter_mut().for_each(|x| *x += 0.5);
+
+                    let pairs: [(&Tensor2D, &Tensor2D); 5] = [
+                        (&matrix, &matrix),
+                        (&matrix, &row_vector),
+                        (&row_vector, &matrix),
+                        (&matrix, &column_vector),
+                        (&column_vector, &matrix),
+                    ];
+
+                    for (left, right) in pairs {
+                        let expected: Tensor2D = reference(left, right);
+
+                        let graph_nodes: Vec<GraphNode> = vec![
+                            GraphNode::new(
+                                GraphOperator::HostToDevice {
+                                    input: left.clone(),
+                                },
+                                vec![],
+                            ),
+                            GraphNode::new(
+                                GraphOperator::HostToDevice {
+                                    input: right.clone(),
+                                },
+                                vec![],
+                            ),
+                            GraphNode::new(operator.clone(), vec![0, 1]),
+                            GraphNode::new(GraphOperator::DeviceToHost, vec![2]),
+                        ];
+
+                        let mut graph_runner: GraphRunner =
+                            GraphRunner::from_graph_nodes(&graph_nodes, false);
+                        let output: Tensor2D = graph_runner.run();
+                        assert_eq!(output.row_count, expected.row_count);
+                        assert_eq!(output.column_count, expected.column_count);
+                        assert_tensors_match(&expected, &output);
+                    }
+                }
+            }
+        }
+    }
+
+    // The same node can be used as both inputs, such as when squaring a tensor.
+    #[test]
+    fn square() {
+        let input: Tensor2D = Tensor2D::new(0.1, 4, 3);
+        let expected: Tensor2D = Tensor2D::multiply(&input, &input);
+
+        let graph_nodes: Vec<GraphNode> = vec![
+            GraphNode::new(GraphOperator::HostToDevice { input }, vec![]),
+            GraphNode::new(GraphOperator::Multiply, vec![0, 0]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![1]),
+        ];
+
+        let mut graph_runner: GraphRunner = GraphRunner::from_graph_nodes(&graph_nodes, true);
+        assert_tensors_match(&expected, &graph_runner.run());
+    }
+
+    // A malformed graph is reported instead of crashing
+    #[test]
+    fn try_new_invalid_graph() {
+        let graph_operators: Vec<GraphOperator> = vec![
+            GraphOperator::HostToDevice {
+                input: Tensor2D::new(0.5, 3, 4),
+            },
+            GraphOperator::Linear {
+                weights: Tensor2D::new(0.5, 5, 2),
+                bias: Tensor2D::new(0.5, 3, 2),
+            },
+            GraphOperator::DeviceToHost,
+        ];
+
+        let error: GraphError = GraphRunner::try_new(&graph_operators, false)
+            .err()
+            .expect("Built a runner from an invalid graph");
+        assert_eq!(
+            error,
+            GraphError::ShapeMismatch {
+                index: 1,
+                tensor: "weights",
+                expected: (4, 2),
+                actual: (5, 2),
+            }
+        );
+        assert_eq!(error.index(), Some(1));
+        assert_eq!(
+            error.to_string(),
+            "The weights of operator 1 has the shape (5, 2), expected (4, 2)"
+        );
+
+        let graph_nodes: Vec<GraphNode> = vec![
+            GraphNode::new(
+                GraphOperator::HostToDevice {
+                    input: Tensor2D::new(0.5, 3, 4),
+                },
+                vec![],
+            ),
+            GraphNode::new(GraphOperator::ReLU, vec![2]),
+            GraphNode::new(GraphOperator::ReLU, vec![1]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![2]),
+        ];
+        assert!(matches!(
+            GraphRunner::try_from_graph_nodes(&graph_nodes, true),
+            Err(GraphError::Cycle)
+        ));
+    }
+
+    #[test]
+    #[should_panic]
+    fn new_invalid_graph() {
+        let graph_operators: Vec<GraphOperator> =
+            vec![GraphOperator::ReLU, GraphOperator::DeviceToHost];
+        GraphRunner::new(&graph_operators, false);
+    }
+
+    #[test]
+    fn try_run() {
+        let input: Tensor2D = Tensor2D::new(0.1, 4, 3);
+        let expected: Tensor2D = Tensor2D::relu(&input);
+        let graph_operators: Vec<GraphOperator> = vec![
+            GraphOperator::HostToDevice { input },
+            GraphOperator::ReLU,
+            GraphOperator::DeviceToHost,
+        ];
+
+        let mut graph_runner: GraphRunner = GraphRunner::try_new(&graph_operators, true).unwrap();
+        assert_tensors_match(&expected, &graph_runner.try_run().unwrap());
+        let outputs: Vec<Tensor2D> = graph_runner.try_run_outputs().unwrap();
+        assert_eq!(outputs.len(), 1);
+        assert_tensors_match(&expected, &outputs[0]);
+    }
+
+    fn mlp(input: Tensor2D) -> Vec<GraphOperator> {
+        vec![
+            GraphOperator::HostToDevice { input },
+            GraphOperator::Linear {
+                weights: Tensor2D::new(0.03, 5, 4),
+                bias: Tensor2D::new(-0.1, 3, 4),
+            },
+            GraphOperator::ReLU,
+            GraphOperator::Linear {
+                weights: Tensor2D::new(-0.02, 4, 6),
+                bias: Tensor2D::new(0.05, 3, 6),
+            },
+            GraphOperator::Softmax { axis: Axis::Global },
+            GraphOperator::DeviceToHost,
+        ]
+    }
+
+    // Running a built graph on new input has to give the same result as building
+    // a new graph for every input, including after the memory has been planned
+    #[test]
+    fn run_with() {
+        let inputs: Vec<Tensor2D> = (1..5)
+            .map(|scale| Tensor2D::new(0.1 * scale as f32, 3, 5))
+            .collect();
+
+        for fuse_operators in [false, true] {
+            for planned_memory in [false, true] {
+                let mut graph_runner: GraphRunner =
+                    GraphRunner::new(&mlp(Tensor2D::new(0.5, 3, 5)), fuse_operators);
+                if planned_memory {
+                    graph_runner.plan_memory();
+                }
+
+                for input in &inputs {
+                    let expected: Tensor2D =
+                        GraphRunner::new(&mlp(input.clone()), fuse_operators).run();
+                    assert_tensors_match(&expected, &graph_runner.run_with(input));
+                    // The new input stays bound for the following runs
+                    assert_tensors_match(&expected, &graph_runner.run());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn run_outputs_with() {
+        let graph_nodes = |left: Tensor2D, right: Tensor2D| -> Vec<GraphNode> {
+            vec![
+                GraphNode::new(GraphOperator::HostToDevice { input: left }, vec![]),
+                GraphNode::new(GraphOperator::HostToDevice { input: right }, vec![]),
+                GraphNode::new(GraphOperator::Subtract, vec![0, 1]),
+                GraphNode::new(GraphOperator::ReLU, vec![2]),
+                GraphNode::new(GraphOperator::DeviceToHost, vec![3]),
+                GraphNode::new(GraphOperator::DeviceToHost, vec![2]),
+            ]
+        };
+
+        for fuse_operators in [false, true] {
+            let mut graph_runner: GraphRunner = GraphRunner::from_graph_nodes(
+                &graph_nodes(Tensor2D::new(0.5, 2, 3), Tensor2D::new(0.5, 1, 3)),
+                fuse_operators,
+            );
+
+            for scale in 1..4 {
+                let left: Tensor2D = Tensor2D::new(0.2 * scale as f32, 2, 3);
+                let right: Tensor2D = Tensor2D::new(0.3, 1, 3);
+                let expected: Vec<Tensor2D> = GraphRunner::from_graph_nodes(
+                    &graph_nodes(left.clone(), right.clone()),
+                    fuse_operators,
+                )
+                .run_outputs();
+
+                let outputs: Vec<Tensor2D> = graph_runner.run_outputs_with(&[&left, &right]);
+                assert_eq!(outputs.len(), 2);
+                assert_tensors_match(&expected[0], &outputs[0]);
+                assert_tensors_match(&expected[1], &outputs[1]);
+            }
+
+            // A graph with two inputs can't be run with a single one
+            assert_eq!(
+                graph_runner.try_run_with(&Tensor2D::new(0.5, 2, 3)).err(),
+                Some(GraphError::GraphInputCount {
+                    expected: 2,
+                    actual: 1
+                })
+            );
+        }
+    }
+
+    // Input of the wrong shape is rejected and the previous input stays bound
+    #[test]
+    fn run_with_shape_mismatch() {
+        let input: Tensor2D = Tensor2D::new(0.5, 3, 5);
+        let mut graph_runner: GraphRunner = GraphRunner::new(&mlp(input.clone()), true);
+        let expected: Tensor2D = graph_runner.run();
+
+        for (row_count, column_count) in [(3, 4), (2, 5), (15, 1)] {
+            let error: GraphError = graph_runner
+                .try_run_with(&Tensor2D::new(0.1, row_count, column_count))
+                .expect_err("Ran a graph with input of the wrong shape");
+            assert_eq!(
+                error,
+                GraphError::ShapeMismatch {
+                    index: 0,
+                    tensor: "input",
+                    expected: (3, 5),
+                    actual: (row_count, column_count),
+                }
+            );
+        }
+
+        assert_tensors_match(&expected, &graph_runner.run());
+    }
+
+    #[test]
+    #[should_panic]
+    fn run_with_wrong_shape() {
+        let mut graph_runner: GraphRunner = GraphRunner::new(&mlp(Tensor2D::new(0.5, 3, 5)), false);
+        graph_runner.run_with(&Tensor2D::new(0.5, 5, 3));
+    }
+
+    // Splitting the rows of the kernels across threads has to give the same result
+    #[test]
+    fn thread_count() {
+        let input: Tensor2D = Tensor2D::new(0.1, 3, 5);
+        for fuse_operators in [false, true] {
+            let expected: Tensor2D = GraphRunner::new(&mlp(input.clone()), fuse_operators).run();
+
+            for thread_count in [0, 1, 2, 5] {
+                let mut graph_runner: GraphRunner =
+                    GraphRunner::new(&mlp(input.clone()), fuse_operators);
+                graph_runner.set_thread_count(thread_count);
+                assert_tensors_match(&expected, &graph_runner.run());
+            }
+        }
+    }
+
+    // mlp with a ReLU before the softmax, which fuses into LinearReLUSoftmax
+    fn mlp_relu_softmax(input: Tensor2D) -> Vec<GraphOperator> {
+        let mut graph_operators: Vec<GraphOperator> = mlp(input);
+        graph_operators.insert(4, GraphOperator::ReLU);
+        graph_operators
+    }
+
+    fn batch_samples(batch_size: usize) -> Vec<Tensor2D> {
+        (0..batch_size)
+            .map(|sample| Tensor2D::new(0.4 - 0.15 * sample as f32, 3, 5))
+            .collect()
+    }
+
+    // A batch has to give every sample the output it gets on its own,
+    // softmax included, with and without fusion, planned memory and threads
+    #[test]
+    fn run_batch() {
+        for graph in [mlp, mlp_relu_softmax] {
+            for fuse_operators in [false, true] {
+                for planned_memory in [false, true] {
+                    for thread_count in [None, Some(3)] {
+                        let mut graph_runner: GraphRunner =
+                            GraphRunner::new(&graph(Tensor2D::new(0.5, 3, 5)), fuse_operators);
+                        if planned_memory {
+                            graph_runner.plan_memory();
+                        }
+                        if let Some(thread_count) = thread_count {
+                            graph_runner.set_thread_count(thread_count);
+                        }
+
+                        for batch_size in [1, 4, 2] {
+                            let samples: Vec<Tensor2D> = batch_samples(batch_size);
+                            let expected: Vec<Tensor2D> = samples
+                                .iter()
+                                .map(|sample| GraphRunner::new(&graph(sample.clone()), false).run())
+                                .collect();
+
+                            let outputs: Vec<Tensor2D> = graph_runner.run_batch(&samples);
+                            assert_eq!(outputs.len(), batch_size);
+                            for (expected, output) in expected.iter().zip(&outputs) {
+                                assert_eq!(
+                                    (expected.row_count, expected.column_count),
+                                    (output.row_count, output.column_count)
+                                );
+                                assert_tensors_match(expected, output);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn run_stacked() {
+        let samples: Vec<Tensor2D> = batch_samples(3);
+        let mut graph_runner: GraphRunner =
+            GraphRunner::new(&mlp_relu_softmax(samples[0].clone()), true);
+
+        let expected: Tensor2D = Tensor2D::stack(&graph_runner.run_batch(&samples));
+        let output: Tensor2D = graph_runner.run_stacked(&Tensor2D::stack(&samples));
+        assert_eq!((output.row_count, output.column_count), (9, 6));
+        assert_tensors_match(&expected, &output);
+    }
+
+    // Batches have their own buffers, so the input bound with run_with is kept,
+    // while the parameters are shared, so a batch sees the latest parameters
+    #[test]
+    fn run_batch_shares_parameters() {
+        let samples: Vec<Tensor2D> = batch_samples(2);
+        let mut graph_runner: GraphRunner = GraphRunner::new(&mlp(samples[0].clone()), true);
+        let expected: Tensor2D = graph_runner.run();
+        graph_runner.run_batch(&samples);
+        assert_tensors_match(&expected, &graph_runner.run());
+
+        for parameter in graph_runner.parameters_mut() {
+            parameter.data.iter_mut().for_each(|value| *value *= 0.5);
+        }
+        let outputs: Vec<Tensor2D> = graph_runner.run_batch(&samples);
+        for (sample, output) in samples.iter().zip(&outputs) {
+            assert_tensors_match(&graph_runner.run_with(sample), output);
+        }
+    }
+
+    #[test]
+    fn run_batch_errors() {
+        let mut graph_runner: GraphRunner = GraphRunner::new(&mlp(Tensor2D::new(0.5, 3, 5)), true);
+
+        assert_eq!(
+            graph_runner.try_run_batch(&[]).err(),
+            Some(GraphError::EmptyBatch)
+        );
+        assert_eq!(
+            graph_runner
+                .try_run_batch(&[Tensor2D::new(0.1, 3, 5), Tensor2D::new(0.1, 5, 3)])
+                .err(),
+            Some(GraphError::ShapeMismatch {
+                index: 0,
+                tensor: "input",
+                expected: (3, 5),
+                actual: (5, 3),
+            })
+        );
+        assert_eq!(
+            graph_runner
+                .try_run_stacked(&Tensor2D::new(0.1, 7, 5))
+                .err(),
+            Some(GraphError::BatchShape {
+                sample: (3, 5),
+                actual: (7, 5),
+            })
+        );
+
+        let mut graph_runner: GraphRunner = GraphRunner::from_graph_nodes(
+            &[
+                GraphNode::new(
+                    GraphOperator::HostToDevice {
+                        input: Tensor2D::new(0.5, 2, 3),
+                    },
+                    vec![],
+                ),
+                GraphNode::new(
+                    GraphOperator::HostToDevice {
+                        input: Tensor2D::new(0.5, 2, 3),
+                    },
+                    vec![],
+                ),
+                GraphNode::new(GraphOperator::Add, vec![0, 1]),
+                GraphNode::new(GraphOperator::DeviceToHost, vec![2]),
+            ],
+            true,
+        );
+        assert_eq!(
+            graph_runner
+                .try_run_batch(&[Tensor2D::new(0.1, 2, 3)])
+                .err(),
+            Some(GraphError::BatchInputCount { input_count: 2 })
+        );
+    }
+
+    // A mean along Axis::Rows is a column vector, which is stacked along with the samples.
+    // A mean along Axis::Columns is a single row per sample, which can't be broadcast
+    // over the rows of each sample once the samples are stacked.
+    #[test]
+    fn run_batch_broadcast() {
+        let graph_nodes = |input: Tensor2D, axis: Axis| -> Vec<GraphNode> {
+            vec![
+                GraphNode::new(GraphOperator::HostToDevice { input }, vec![]),
+                GraphNode::new(GraphOperator::Mean { axis }, vec![0]),
+                GraphNode::new(GraphOperator::Subtract, vec![0, 1]),
+                GraphNode::new(GraphOperator::DeviceToHost, vec![2]),
+            ]
+        };
+
+        let samples: Vec<Tensor2D> = batch_samples(3);
+        for fuse_operators in [false, true] {
+            let mut graph_runner: GraphRunner = GraphRunner::from_graph_nodes(
+                &graph_nodes(samples[0].clone(), Axis::Rows),
+                fuse_operators,
+            );
+            let outputs: Vec<Tensor2D> = graph_runner.run_batch(&samples);
+            for (sample, output) in samples.iter().zip(&outputs) {
+                let expected: Tensor2D =
+                    GraphRunner::from_graph_nodes(&graph_nodes(sample.clone(), Axis::Rows), false)
+                        .run();
+                assert_tensors_match(&expected, output);
+            }
+
+            let mut graph_runner: GraphRunner = GraphRunner::from_graph_nodes(
+                &graph_nodes(samples[0].clone(), Axis::Columns),
+                fuse_operators,
+            );
+            assert!(matches!(
+                graph_runner.try_run_batch(&samples),
+                Err(GraphError::BatchBroadcast { .. })
+            ));
+            assert!(matches!(
+                graph_runner.try_run_stacked(&Tensor2D::stack(&samples)),
+                Err(GraphError::BatchBroadcast { .. })
+            ));
+
+            // A single sample still broadcasts the row
+            let expected: Tensor2D = Tensor2D::subtract(
+                &samples[0],
+                &Tensor2D::reduce(&samples[0], Reduction::Mean, Axis::Columns),
+            );
+            assert_tensors_match(&expected, &graph_runner.run());
+        }
+    }
+
+    // Values which aren't sorted along either axis, so every row and column
+    // has its maximum somewhere else
+    fn unsorted_tensor(row_count: usize, column_count: usize) -> Tensor2D {
+        let mut tensor: Tensor2D = Tensor2D::new(0.0, row_count, column_count);
+        for (index, value) in tensor.data.iter_mut().enumerate() {
+            *value = ((index * 7) % 11) as f32 * 0.3 - 1.5;
+        }
+        tensor
+    }
+
+    fn axis_operator(operator: &str, axis: Axis) -> GraphOperator {
+        match operator {
+            "Softmax" => GraphOperator::Softmax { axis },
+            "LogSoftmax" => GraphOperator::LogSoftmax { axis },
+            "Sum" => GraphOperator::Sum { axis },
+            "Max" => GraphOperator::Max { axis },
+            "Mean" => GraphOperator::Mean { axis },
+            _ => GraphOperator::ArgMax { axis },
+        }
+    }
+
+    const AXIS_OPERATORS: [&str; 6] = ["Softmax", "LogSoftmax", "Sum", "Max", "Mean", "ArgMax"];
+    const AXES: [Axis; 3] = [Axis::Global, Axis::Rows, Axis::Columns];
+
+    fn expected_along(input: &Tensor2D, operator: &GraphOperator) -> Tensor2D {
+        match operator {
+            GraphOperator::Softmax { axis } => Tensor2D::softmax_along(input, *axis),
+            GraphOperator::LogSoftmax { axis } => Tensor2D::log_softmax_along(input, *axis),
+            _ => {
+                let (reduction, axis) = operator.reduction().unwrap();
+                Tensor2D::reduce(input, reduction, axis)
+            }
+        }
+    }
+
+    #[test]
+    fn axis_operators() {
+        for (row_count, column_count) in [(1, 1), (1, 5), (4, 1), (3, 5), (6, 4)] {
+            let input: Tensor2D = unsorted_tensor(row_count, column_count);
+            for operator in AXIS_OPERATORS {
+                for axis in AXES {
+                    let operator: GraphOperator = axis_operator(operator, axis);
+                    let expected: Tensor2D = expected_along(&input, &operator);
+
+                    let graph_operators: Vec<GraphOperator> = vec![
+                        GraphOperator::HostToDevice {
+                            input: input.clone(),
+                        },
+                        operator.clone(),
+                        GraphOperator::DeviceToHost,
+                    ];
+                    for fuse_operators in [false, true] {
+                        let output: Tensor2D =
+                            GraphRunner::new(&graph_operators, fuse_operators).run();
+                        assert_eq!(
+                            (expected.row_count, expected.column_count),
+                            (output.row_count, output.column_count),
+                            "{:?}",
+                            operator
+                        );
+                        assert_tensors_match(&expected, &output);
+                    }
+                }
+            }
+        }
+    }
+
+    // A classifier with one sample per row, a softmax over the classes of every sample
+    // and the predicted class of every sample, and a linear layer after a reduction.
+    // A batch only returns its last output, so each is checked in a graph of its own.
+    #[test]
+    fn axis_operators_batched() {
+        let graph = |input: Tensor2D, output: usize| -> Vec<GraphNode> {
+            vec![
+                GraphNode::new(GraphOperator::HostToDevice { input }, vec![]),
+                GraphNode::new(
+                    GraphOperator::Linear {
+                        weights: unsorted_tensor(5, 4),
+                        bias: unsorted_tensor(3, 4),
+                    },
+                    vec![0],
+                ),
+                GraphNode::new(GraphOperator::LogSoftmax { axis: Axis::Rows }, vec![1]),
+                GraphNode::new(GraphOperator::ArgMax { axis: Axis::Rows }, vec![1]),
+                GraphNode::new(
+                    GraphOperator::Mean {
+                        axis: Axis::Columns,
+                    },
+                    vec![1],
+                ),
+                GraphNode::new(
+                    GraphOperator::Linear {
+                        weights: unsorted_tensor(4, 2),
+                        bias: unsorted_tensor(1, 2),
+                    },
+                    vec![4],
+                ),
+                GraphNode::new(GraphOperator::DeviceToHost, vec![output]),
+            ]
+        };
+
+        let samples: Vec<Tensor2D> = batch_samples(3);
+        for output in [2, 3, 5] {
+            for fuse_operators in [false, true] {
+                for planned_memory in [false, true] {
+                    let mut graph_runner: GraphRunner = GraphRunner::from_graph_nodes(
+                        &graph(samples[0].clone(), output),
+                        fuse_operators,
+                    );
+                    if planned_memory {
+                        graph_runner.plan_memory();
+                    }
+
+                    let outputs: Vec<Tensor2D> = graph_runner.run_batch(&samples);
+                    for (sample, output_tensor) in samples.iter().zip(&outputs) {
+                        let expected: Tensor2D =
+                            GraphRunner::from_graph_nodes(&graph(sample.clone(), output), false)
+                                .run();
+                        assert_eq!(
+                            (expected.row_count, expected.column_count),
+                            (output_tensor.row_count, output_tensor.column_count)
+                        );
+                        assert_tensors_match(&expected, output_tensor);
+                    }
+                }
+            }
+        }
+    }
 }
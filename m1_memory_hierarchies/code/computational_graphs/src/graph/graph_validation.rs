@@ -1,117 +1,97 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
 use crate::shared::graph_operators::GraphOperator;
 use crate::shared::graph_operators::GraphOperator::*;
+use crate::shared::graph_operators::{GraphNode, NodeId};
 use crate::shared::tensor2d::Tensor2D;
+use crate::shared::tensor2d_precision::PackedTensor2D;
+
+use super::graph_error::GraphError;
 
-pub fn linear_dimension_check(input: &Tensor2D, weights: &Tensor2D, bias: &Tensor2D) {
-    assert!(
-        0 < input.row_count,
-        "\ninput.row_count must be larger than 0. Current value: {}.",
-        input.row_count
-    );
-
-    assert!(
-        0 < input.column_count,
-        "\ninput.column_count must be larger than 0. Current value: {}.",
-        input.column_count
-    );
-
-    assert!(
-        0 < weights.row_count,
-        "\nweights.row_count must be larger than 0. Current value: {}.",
-        weights.row_count
-    );
-
-    assert!(
-        0 < weights.column_count,
-        "\nweights.column_count must be larger than 0. Current value: {}.",
-        weights.column_count
-    );
-
-    assert!(
-        0 < bias.row_count,
-        "\nbias.row_count must be larger than 0. Current value: {}.",
-        bias.row_count
-    );
-
-    assert!(
-        0 < bias.column_count,
-        "\nbias.column_count must be larger than 0. Current value: {}.",
-        bias.column_count
-    );
-
-    assert_eq!(
-        input.column_count,
-        weights.row_count,
-        "\nMismatch - input.column_count & weights.row_count\ninput - rows: {} columns: {}.\n weights - rows: {} columns: {}.", 
-        input.row_count,
-        input.column_count,
-        weights.row_count,
-        weights.column_count
-    );
+fn shape(tensor: &Tensor2D) -> (usize, usize) {
+    (tensor.row_count, tensor.column_count)
 }
 
-// Every dimension is legal in this operator, it is up to the other operators to reject
-fn validate_host_to_device(current_index: usize, graph: &[GraphOperator]) -> bool {
-    if let HostToDevice { input: _ } = &graph[current_index] {
-        if current_index != 0 {
-            println!("Something went wrong in validate_host_to_device. The HostToDevice operator was not at the beginning of the graph.");
-            return false;
-        }
-    } else {
-        println!("Something went wrong in validate_host_to_device. Current operator was not HostToDevice");
-        return false;
-    }
+fn packed_shape(tensor: &PackedTensor2D) -> (usize, usize) {
+    (tensor.row_count, tensor.column_count)
+}
 
-    true
+fn non_empty_check(
+    index: usize,
+    tensor_name: &'static str,
+    tensor_shape: (usize, usize),
+) -> Result<(), GraphError> {
+    if tensor_shape.0 == 0 || tensor_shape.1 == 0 {
+        return Err(GraphError::EmptyTensor {
+            index,
+            tensor: tensor_name,
+            shape: tensor_shape,
+        });
+    }
+    Ok(())
 }
 
-// Every dimension is legal in this operator, it is up to the other operators to reject.
-// Normally this wouldn't be, but we have elected to overwrite the existing data whenever
-// an output is transferred back to the host.
-fn validate_device_to_host(current_index: usize, graph: &Vec<GraphOperator>) -> bool {
-    if let DeviceToHost = &graph[current_index] {
-        if current_index != (graph.len() - 1) {
-            println!("Something went wrong in validate_device_to_host. Current operator was not DeviceToHost");
-            return false;
-        }
-    } else {
-        println!("Something went wrong in validate_device_to_host. Current operator was not DeviceToHost");
-        return false;
+// Checks a linear layer at index reading an input of input_shape
+pub fn linear_dimension_check(
+    index: usize,
+    input_shape: (usize, usize),
+    weights_shape: (usize, usize),
+    bias_shape: (usize, usize),
+) -> Result<(), GraphError> {
+    non_empty_check(index, "weights", weights_shape)?;
+    non_empty_check(index, "bias", bias_shape)?;
+
+    if input_shape.1 != weights_shape.0 {
+        return Err(GraphError::ShapeMismatch {
+            index,
+            tensor: "weights",
+            expected: (input_shape.1, weights_shape.1),
+            actual: weights_shape,
+        });
+    }
+
+    if bias_shape != (input_shape.0, weights_shape.1) {
+        return Err(GraphError::ShapeMismatch {
+            index,
+            tensor: "bias",
+            expected: (input_shape.0, weights_shape.1),
+            actual: bias_shape,
+        });
     }
 
-    true
+    Ok(())
 }
 
-fn validate_linear_dimensions(
-    current_index: usize,
+// The shape of the output of the operator at index in a linear chain, found by
+// searching back for the nearest operator which dictates it. The reductions
+// dictate the shape by reducing whatever shape their own input has.
+// Returns None if the search reaches the start of the graph.
+fn chain_output_shape(
     graph: &[GraphOperator],
-    current_weights: &Tensor2D,
-    current_bias: &Tensor2D,
-) -> bool {
-    // Search for nearest dimension dictating operation
-    for predecessor_index in (0..current_index).rev() {
-        match &graph[predecessor_index] {
-            HostToDevice { input } => {
-                linear_dimension_check(input, current_weights, current_bias);
-                return true;
-            }
-            Linear { weights: _, bias } => {
-                linear_dimension_check(bias, current_weights, current_bias);
-                return true;
-            }
-            LinearReLUFused { weights: _, bias } => {
-                linear_dimension_check(bias, current_weights, current_bias);
-                return true;
-            }
-            LinearReLUSoftmaxFused { weights: _, bias } => {
-                linear_dimension_check(bias, current_weights, current_bias);
-                return true;
-            }
-            DeviceToHost => {
-                panic!("Found a DeviceToHost node before a linear layer node. This wasn't part of the contrived example!");
+    index: usize,
+) -> Result<Option<(usize, usize)>, GraphError> {
+    for predecessor_index in (0..=index).rev() {
+        let predecessor: &GraphOperator = &graph[predecessor_index];
+        match predecessor {
+            HostToDevice { input } => return Ok(Some(shape(input))),
+            Linear { weights: _, bias }
+            | LinearReLUFused { weights: _, bias }
+            | LinearReLUSoftmaxFused { weights: _, bias } => return Ok(Some(shape(bias))),
+            QuantizedLinear { weights: _, bias } => return Ok(Some(packed_shape(bias))),
+            Sum { axis } | Max { axis } | Mean { axis } | ArgMax { axis } => {
+                if predecessor_index == 0 {
+                    return Ok(None);
+                }
+                return Ok(chain_output_shape(graph, predecessor_index - 1)?
+                    .map(|shape| axis.reduced_shape(shape)));
             }
-            Empty => {
-                panic!("Found an Empty node before a linear layer node. This wasn't part of the contrived example!");
+            DeviceToHost | Empty => {
+                return Err(GraphError::MisplacedOperator {
+                    index: predecessor_index,
+                    operator: predecessor.name(),
+                    reason: "comes before a linear layer",
+                });
             }
             _ => {
                 //Predecessor operator was probably ReLU or Softmax
@@ -119,99 +99,290 @@ fn validate_linear_dimensions(
         }
     }
 
-    true
+    Ok(None)
 }
 
-fn validate_relu(current_index: usize, graph: &[GraphOperator]) -> bool {
-    if let ReLU {} = &graph[current_index] {
-    } else {
-        println!("Something went wrong in validate_relu. Current operator was not HostToDevice");
-        return false;
+fn validate_linear_dimensions(
+    current_index: usize,
+    graph: &[GraphOperator],
+    weights_shape: (usize, usize),
+    bias_shape: (usize, usize),
+) -> Result<(), GraphError> {
+    if current_index == 0 {
+        return Ok(());
     }
 
-    true
-}
-
-fn validate_softmax(current_index: usize, graph: &[GraphOperator]) -> bool {
-    if let Softmax {} = &graph[current_index] {
-    } else {
-        println!("Something went wrong in validate_softmax. Current operator was not HostToDevice");
-        return false;
+    match chain_output_shape(graph, current_index - 1)? {
+        Some(input_shape) => {
+            linear_dimension_check(current_index, input_shape, weights_shape, bias_shape)
+        }
+        None => Ok(()),
     }
-
-    true
 }
 
 // For our contrived example, for a graph to be valid it has to begin
-// with HostToDevice and end with DeviceToHost, perhaps later
-// we will support running the same input in a loop, or
-// running a graph with new input every time.
-fn validate_transfers(graph: &Vec<GraphOperator>) -> bool {
-    let mut found_valid_host_to_device: bool = false;
-    let mut found_valid_device_to_host: bool = false;
-
-    for index in 0..graph.len() {
-        let operator: &GraphOperator = &graph[index];
+// with HostToDevice and end with DeviceToHost. The input of the HostToDevice
+// fixes the input shape, the runners can be run with new input of that shape.
+fn validate_transfers(graph: &[GraphOperator]) -> Result<(), GraphError> {
+    let mut found_host_to_device: bool = false;
+    let mut found_device_to_host: bool = false;
+
+    for (index, operator) in graph.iter().enumerate() {
         match operator {
             HostToDevice { input } => {
-                if index == 0 {
-                    if 0 < input.row_count && 0 < input.column_count {
-                        found_valid_host_to_device = true;
-                    }
-                } else {
-                    return false;
+                if index != 0 {
+                    return Err(GraphError::MisplacedOperator {
+                        index,
+                        operator: operator.name(),
+                        reason: "has to be the first operator",
+                    });
                 }
+                non_empty_check(index, "input", shape(input))?;
+                found_host_to_device = true;
             }
             DeviceToHost => {
-                if index == graph.len() - 1 {
-                    found_valid_device_to_host = true;
-                } else {
-                    return false;
+                if index != graph.len() - 1 {
+                    return Err(GraphError::MisplacedOperator {
+                        index,
+                        operator: operator.name(),
+                        reason: "has to be the last operator",
+                    });
                 }
+                found_device_to_host = true;
             }
             _ => {}
         }
     }
 
-    found_valid_device_to_host && found_valid_host_to_device
+    if !found_host_to_device {
+        return Err(GraphError::MissingTransfer {
+            operator: "HostToDevice",
+        });
+    }
+    if !found_device_to_host {
+        return Err(GraphError::MissingTransfer {
+            operator: "DeviceToHost",
+        });
+    }
+
+    Ok(())
 }
 
 // Just for learning purposes the only real requirements we will have will be
 // matching dimensions and each graph beginning with a transfer to device
 // and ending with a transfer from device
 // All validation is retrospective, each operator will look for valid predecessors.
-pub fn validate_graph_operators(graph: &Vec<GraphOperator>) -> bool {
-    let graph_length: usize = graph.len();
-    let mut graph_is_validated: bool = true;
-
-    graph_is_validated = graph_is_validated && validate_transfers(graph);
+// Returns the first problem found.
+pub fn validate_graph_operators(graph: &[GraphOperator]) -> Result<(), GraphError> {
+    validate_transfers(graph)?;
 
     // Scanning graph for valid sizes
-    for current_index in 0..graph_length {
-        let current: &GraphOperator = &graph[current_index];
+    for (current_index, current) in graph.iter().enumerate() {
+        match current {
+            Empty | HostToDevice { input: _ } | DeviceToHost | ReLU | Softmax { .. } => {}
+            LogSoftmax { .. } | Sum { .. } | Max { .. } | Mean { .. } | ArgMax { .. } => {}
+            Linear { weights, bias }
+            | LinearReLUFused { weights, bias }
+            | LinearReLUSoftmaxFused { weights, bias } => {
+                validate_linear_dimensions(current_index, graph, shape(weights), shape(bias))?
+            }
+            QuantizedLinear { weights, bias } => validate_linear_dimensions(
+                current_index,
+                graph,
+                packed_shape(weights),
+                packed_shape(bias),
+            )?,
+            Add | Subtract | Multiply | Divide => {
+                return Err(GraphError::UnsupportedOperator {
+                    index: current_index,
+                    operator: current.name(),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// For every node, collect the nodes which read its output.
+pub fn get_consumers(graph: &[GraphNode]) -> Vec<Vec<NodeId>> {
+    let mut consumers: Vec<Vec<NodeId>> = vec![Vec::<NodeId>::new(); graph.len()];
+    for (node_id, node) in graph.iter().enumerate() {
+        for input in &node.inputs {
+            if *input < graph.len() {
+                consumers[*input].push(node_id);
+            }
+        }
+    }
+
+    consumers
+}
+
+// Kahn's algorithm. Nodes which are ready at the same time are visited
+// by ascending id, which means a linear chain keeps its original ordering.
+// Returns None if the graph contains a cycle or refers to nodes which don't exist.
+pub fn topological_sort(graph: &[GraphNode]) -> Option<Vec<NodeId>> {
+    let mut missing_inputs: Vec<usize> = vec![0; graph.len()];
+    for (node_id, node) in graph.iter().enumerate() {
+        for input in &node.inputs {
+            if graph.len() <= *input {
+                return None;
+            }
+        }
+        missing_inputs[node_id] = node.inputs.len();
+    }
+
+    let consumers: Vec<Vec<NodeId>> = get_consumers(graph);
+    let mut ready: BinaryHeap<Reverse<NodeId>> = BinaryHeap::<Reverse<NodeId>>::new();
+    for (node_id, count) in missing_inputs.iter().enumerate() {
+        if *count == 0 {
+            ready.push(Reverse(node_id));
+        }
+    }
 
-        let valid_operator: bool = match current {
-            GraphOperator::Empty => {
-                continue;
+    let mut order: Vec<NodeId> = Vec::<NodeId>::with_capacity(graph.len());
+    while let Some(Reverse(node_id)) = ready.pop() {
+        order.push(node_id);
+        for consumer in &consumers[node_id] {
+            missing_inputs[*consumer] -= 1;
+            if missing_inputs[*consumer] == 0 {
+                ready.push(Reverse(*consumer));
+            }
+        }
+    }
+
+    // Any node which never got all of its inputs is part of,
+    // or downstream from, a cycle.
+    if order.len() == graph.len() {
+        Some(order)
+    } else {
+        None
+    }
+}
+
+pub fn has_cycle(graph: &[GraphNode]) -> bool {
+    topological_sort(graph).is_none()
+}
+
+fn expected_input_count(operator: &GraphOperator) -> usize {
+    match operator {
+        Empty => 0,
+        HostToDevice { input: _ } => 0,
+        Add | Subtract | Multiply | Divide => 2,
+        _ => 1,
+    }
+}
+
+// The graph node version of validate_graph_operators.
+// A valid graph has every input referring to an existing, non-Empty node,
+// no cycles, at least one HostToDevice and DeviceToHost, no operator reading from a
+// DeviceToHost and matching dimensions along every edge.
+pub fn validate_graph_nodes(graph: &[GraphNode]) -> Result<(), GraphError> {
+    let mut found_host_to_device: bool = false;
+    let mut found_device_to_host: bool = false;
+
+    for (node_id, node) in graph.iter().enumerate() {
+        match node.operator {
+            HostToDevice { input: _ } => found_host_to_device = true,
+            DeviceToHost => found_device_to_host = true,
+            _ => {}
+        }
+
+        let expected_count: usize = expected_input_count(&node.operator);
+        if node.inputs.len() != expected_count {
+            return Err(GraphError::InputCount {
+                index: node_id,
+                operator: node.operator.name(),
+                expected: expected_count,
+                actual: node.inputs.len(),
+            });
+        }
+
+        for input in &node.inputs {
+            if graph.len() <= *input {
+                return Err(GraphError::MissingInput {
+                    index: node_id,
+                    input: *input,
+                });
+            }
+
+            // Outputs have to be leaves
+            if let Empty | DeviceToHost = graph[*input].operator {
+                return Err(GraphError::InvalidInput {
+                    index: node_id,
+                    input: *input,
+                    operator: graph[*input].operator.name(),
+                });
+            }
+        }
+    }
+
+    if !found_host_to_device {
+        return Err(GraphError::MissingTransfer {
+            operator: "HostToDevice",
+        });
+    }
+    if !found_device_to_host {
+        return Err(GraphError::MissingTransfer {
+            operator: "DeviceToHost",
+        });
+    }
+
+    let order: Vec<NodeId> = topological_sort(graph).ok_or(GraphError::Cycle)?;
+    propagate_shapes(graph, &order)?;
+
+    Ok(())
+}
+
+// Propagate the (rows, columns) of every node through the graph, visiting the nodes
+// in the given topological order. Fails if the dimensions don't match along an edge.
+// Note that this is the shape of the graph, the GPU runner flattens the output of
+// Softmax along Axis::Global.
+pub fn propagate_shapes(
+    graph: &[GraphNode],
+    order: &[NodeId],
+) -> Result<Vec<(usize, usize)>, GraphError> {
+    let mut shapes: Vec<(usize, usize)> = vec![(0, 0); graph.len()];
+    for node_id in order {
+        let node: &GraphNode = &graph[*node_id];
+        shapes[*node_id] = match &node.operator {
+            Empty => (0, 0),
+            HostToDevice { input } => {
+                non_empty_check(*node_id, "input", shape(input))?;
+                shape(input)
             }
-            GraphOperator::HostToDevice { input: _ } => {
-                validate_host_to_device(current_index, graph)
+            DeviceToHost | ReLU | Softmax { .. } | LogSoftmax { .. } => shapes[node.inputs[0]],
+            Sum { axis } | Max { axis } | Mean { axis } | ArgMax { axis } => {
+                axis.reduced_shape(shapes[node.inputs[0]])
             }
-            GraphOperator::DeviceToHost => validate_device_to_host(current_index, graph),
-            GraphOperator::Linear { weights, bias } => {
-                validate_linear_dimensions(current_index, graph, weights, bias)
+            Linear { weights, bias }
+            | LinearReLUFused { weights, bias }
+            | LinearReLUSoftmaxFused { weights, bias } => {
+                let input_shape: (usize, usize) = shapes[node.inputs[0]];
+                linear_dimension_check(*node_id, input_shape, shape(weights), shape(bias))?;
+                shape(bias)
             }
-            GraphOperator::ReLU => validate_relu(current_index, graph),
-            GraphOperator::Softmax => validate_softmax(current_index, graph),
-            GraphOperator::LinearReLUFused { weights, bias } => {
-                validate_linear_dimensions(current_index, graph, weights, bias)
+            QuantizedLinear { weights, bias } => {
+                let input_shape: (usize, usize) = shapes[node.inputs[0]];
+                linear_dimension_check(
+                    *node_id,
+                    input_shape,
+                    packed_shape(weights),
+                    packed_shape(bias),
+                )?;
+                packed_shape(bias)
             }
-            GraphOperator::LinearReLUSoftmaxFused { weights, bias } => {
-                validate_linear_dimensions(current_index, graph, weights, bias)
+            Add | Subtract | Multiply | Divide => {
+                let left: (usize, usize) = shapes[node.inputs[0]];
+                let right: (usize, usize) = shapes[node.inputs[1]];
+                Tensor2D::broadcast_shape(left, right).ok_or(GraphError::BroadcastMismatch {
+                    index: *node_id,
+                    left,
+                    right,
+                })?
             }
         };
-        graph_is_validated = graph_is_validated && valid_operator;
     }
 
-    graph_is_validated
+    Ok(shapes)
 }
@@ -0,0 +1,417 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        graph::{
+            graph_error::GraphError,
+            graph_validation::{
+                has_cycle, topological_sort, validate_graph_nodes, validate_graph_operators,
+            },
+        },
+        shared::{
+            graph_operators::{graph_nodes_from_operators, GraphNode, GraphOperator, NodeId},
+            tensor2d::Tensor2D,
+            tensor2d_axis::Axis,
+        },
+    };
+
+    #[test]
+    fn linear_chain_keeps_order() {
+        let graph_operators: Vec<GraphOperator> = vec![
+            GraphOperator::HostToDevice {
+                input: Tensor2D::new(0.5, 3, 4),
+            },
+            GraphOperator::ReLU,
+            GraphOperator::Empty,
+            GraphOperator::Softmax { axis: Axis::Global },
+            GraphOperator::DeviceToHost,
+        ];
+        let graph_nodes: Vec<GraphNode> = graph_nodes_from_operators(&graph_operators);
+
+        assert_eq!(graph_nodes[3].inputs, vec![1]);
+        assert!(graph_nodes[2].inputs.is_empty());
+
+        let order: Vec<NodeId> = topological_sort(&graph_nodes).unwrap();
+        assert_eq!(order, vec![0, 1, 2, 3, 4]);
+        assert_eq!(validate_graph_nodes(&graph_nodes), Ok(()));
+    }
+
+    #[test]
+    fn topological_order() {
+        let graph_nodes: Vec<GraphNode> = vec![
+            GraphNode::new(GraphOperator::DeviceToHost, vec![3]),
+            GraphNode::new(GraphOperator::ReLU, vec![2]),
+            GraphNode::new(
+                GraphOperator::HostToDevice {
+                    input: Tensor2D::new(0.5, 3, 4),
+                },
+                vec![],
+            ),
+            GraphNode::new(GraphOperator::Softmax { axis: Axis::Global }, vec![1]),
+        ];
+
+        let order: Vec<NodeId> = topological_sort(&graph_nodes).unwrap();
+        assert_eq!(order, vec![2, 1, 3, 0]);
+        assert_eq!(validate_graph_nodes(&graph_nodes), Ok(()));
+    }
+
+    #[test]
+    fn cycle() {
+        let graph_nodes: Vec<GraphNode> = vec![
+            GraphNode::new(
+                GraphOperator::HostToDevice {
+                    input: Tensor2D::new(0.5, 3, 4),
+                },
+                vec![],
+            ),
+            GraphNode::new(GraphOperator::ReLU, vec![2]),
+            GraphNode::new(GraphOperator::Softmax { axis: Axis::Global }, vec![1]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![0]),
+        ];
+
+        assert!(has_cycle(&graph_nodes));
+        assert_eq!(validate_graph_nodes(&graph_nodes), Err(GraphError::Cycle));
+    }
+
+    #[test]
+    fn self_loop() {
+        let graph_nodes: Vec<GraphNode> = vec![
+            GraphNode::new(
+                GraphOperator::HostToDevice {
+                    input: Tensor2D::new(0.5, 3, 4),
+                },
+                vec![],
+            ),
+            GraphNode::new(GraphOperator::ReLU, vec![1]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![0]),
+        ];
+
+        assert!(has_cycle(&graph_nodes));
+        assert_eq!(validate_graph_nodes(&graph_nodes), Err(GraphError::Cycle));
+    }
+
+    #[test]
+    fn invalid_inputs() {
+        let input: Tensor2D = Tensor2D::new(0.5, 3, 4);
+
+        // Refers to a node which doesn't exist
+        let graph_nodes: Vec<GraphNode> = vec![
+            GraphNode::new(
+                GraphOperator::HostToDevice {
+                    input: input.clone(),
+                },
+                vec![],
+            ),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![7]),
+        ];
+        assert!(topological_sort(&graph_nodes).is_none());
+        assert_eq!(
+            validate_graph_nodes(&graph_nodes),
+            Err(GraphError::MissingInput { index: 1, input: 7 })
+        );
+
+        // Reads from an output
+        let graph_nodes: Vec<GraphNode> = vec![
+            GraphNode::new(
+                GraphOperator::HostToDevice {
+                    input: input.clone(),
+                },
+                vec![],
+            ),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![0]),
+            GraphNode::new(GraphOperator::ReLU, vec![1]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![2]),
+        ];
+        assert_eq!(
+            validate_graph_nodes(&graph_nodes),
+            Err(GraphError::InvalidInput {
+                index: 2,
+                input: 1,
+                operator: "DeviceToHost"
+            })
+        );
+
+        // Wrong number of inputs
+        let graph_nodes: Vec<GraphNode> = vec![
+            GraphNode::new(GraphOperator::HostToDevice { input }, vec![]),
+            GraphNode::new(GraphOperator::ReLU, vec![0, 0]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![1]),
+        ];
+        assert_eq!(
+            validate_graph_nodes(&graph_nodes),
+            Err(GraphError::InputCount {
+                index: 1,
+                operator: "ReLU",
+                expected: 1,
+                actual: 2
+            })
+        );
+    }
+
+    #[test]
+    fn mismatched_dimensions() {
+        let graph_nodes: Vec<GraphNode> = vec![
+            GraphNode::new(
+                GraphOperator::HostToDevice {
+                    input: Tensor2D::new(0.5, 3, 4),
+                },
+                vec![],
+            ),
+            GraphNode::new(
+                GraphOperator::Linear {
+                    weights: Tensor2D::new(0.5, 4, 2),
+                    bias: Tensor2D::new(0.5, 3, 2),
+                },
+                vec![0],
+            ),
+            // The second branch expects 5 input columns
+            GraphNode::new(
+                GraphOperator::Linear {
+                    weights: Tensor2D::new(0.5, 5, 2),
+                    bias: Tensor2D::new(0.5, 3, 2),
+                },
+                vec![0],
+            ),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![1]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![2]),
+        ];
+
+        assert_eq!(
+            validate_graph_nodes(&graph_nodes),
+            Err(GraphError::ShapeMismatch {
+                index: 2,
+                tensor: "weights",
+                expected: (4, 2),
+                actual: (5, 2)
+            })
+        );
+    }
+
+    fn elementwise_graph(left: Tensor2D, right: Tensor2D) -> Vec<GraphNode> {
+        vec![
+            GraphNode::new(GraphOperator::HostToDevice { input: left }, vec![]),
+            GraphNode::new(GraphOperator::HostToDevice { input: right }, vec![]),
+            GraphNode::new(GraphOperator::Subtract, vec![0, 1]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![2]),
+        ]
+    }
+
+    #[test]
+    fn elementwise_dimensions() {
+        let valid_shapes: [((usize, usize), (usize, usize)); 5] = [
+            ((3, 4), (3, 4)),
+            ((3, 4), (1, 4)),
+            ((3, 1), (3, 4)),
+            ((3, 4), (1, 1)),
+            ((3, 1), (1, 4)),
+        ];
+        for (left, right) in valid_shapes {
+            let graph_nodes: Vec<GraphNode> = elementwise_graph(
+                Tensor2D::new(0.5, left.0, left.1),
+                Tensor2D::new(0.5, right.0, right.1),
+            );
+            assert_eq!(validate_graph_nodes(&graph_nodes), Ok(()));
+        }
+
+        let invalid_shapes: [((usize, usize), (usize, usize)); 3] =
+            [((3, 4), (4, 3)), ((3, 4), (2, 4)), ((3, 4), (3, 2))];
+        for (left, right) in invalid_shapes {
+            let graph_nodes: Vec<GraphNode> = elementwise_graph(
+                Tensor2D::new(0.5, left.0, left.1),
+                Tensor2D::new(0.5, right.0, right.1),
+            );
+            assert_eq!(
+                validate_graph_nodes(&graph_nodes),
+                Err(GraphError::BroadcastMismatch {
+                    index: 2,
+                    left,
+                    right
+                })
+            );
+        }
+
+        // Elementwise operators need exactly two inputs
+        let graph_nodes: Vec<GraphNode> = vec![
+            GraphNode::new(
+                GraphOperator::HostToDevice {
+                    input: Tensor2D::new(0.5, 3, 4),
+                },
+                vec![],
+            ),
+            GraphNode::new(GraphOperator::Add, vec![0]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![1]),
+        ];
+        assert!(matches!(
+            validate_graph_nodes(&graph_nodes),
+            Err(GraphError::InputCount {
+                index: 1,
+                expected: 2,
+                actual: 1,
+                ..
+            })
+        ));
+
+        // and can't be expressed as a linear chain of operators
+        let graph_operators: Vec<GraphOperator> = vec![
+            GraphOperator::HostToDevice {
+                input: Tensor2D::new(0.5, 3, 4),
+            },
+            GraphOperator::Add,
+            GraphOperator::DeviceToHost,
+        ];
+        assert_eq!(
+            validate_graph_operators(&graph_operators),
+            Err(GraphError::UnsupportedOperator {
+                index: 1,
+                operator: "Add"
+            })
+        );
+    }
+
+    fn chain(operators: Vec<GraphOperator>) -> Vec<GraphOperator> {
+        let mut graph_operators: Vec<GraphOperator> = vec![GraphOperator::HostToDevice {
+            input: Tensor2D::new(0.5, 3, 4),
+        }];
+        graph_operators.extend(operators);
+        graph_operators.push(GraphOperator::DeviceToHost);
+        graph_operators
+    }
+
+    // A linear layer after a reduction sees the reduced shape, in a chain and in a graph
+    #[test]
+    fn reduction_dimensions() {
+        assert_eq!(
+            validate_graph_operators(&chain(vec![
+                GraphOperator::Sum {
+                    axis: Axis::Columns
+                },
+                GraphOperator::Linear {
+                    weights: Tensor2D::new(0.5, 4, 2),
+                    bias: Tensor2D::new(0.5, 1, 2),
+                },
+            ])),
+            Ok(())
+        );
+        assert_eq!(
+            validate_graph_operators(&chain(vec![
+                GraphOperator::Max { axis: Axis::Rows },
+                GraphOperator::ReLU,
+                GraphOperator::Linear {
+                    weights: Tensor2D::new(0.5, 4, 2),
+                    bias: Tensor2D::new(0.5, 3, 2),
+                },
+            ])),
+            Err(GraphError::ShapeMismatch {
+                index: 3,
+                tensor: "weights",
+                expected: (1, 2),
+                actual: (4, 2)
+            })
+        );
+
+        let graph_nodes: Vec<GraphNode> = vec![
+            GraphNode::new(
+                GraphOperator::HostToDevice {
+                    input: Tensor2D::new(0.5, 3, 4),
+                },
+                vec![],
+            ),
+            GraphNode::new(GraphOperator::Mean { axis: Axis::Global }, vec![0]),
+            GraphNode::new(
+                GraphOperator::Linear {
+                    weights: Tensor2D::new(0.5, 1, 2),
+                    bias: Tensor2D::new(0.5, 3, 2),
+                },
+                vec![1],
+            ),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![2]),
+        ];
+        assert_eq!(
+            validate_graph_nodes(&graph_nodes),
+            Err(GraphError::ShapeMismatch {
+                index: 2,
+                tensor: "bias",
+                expected: (1, 2),
+                actual: (3, 2)
+            })
+        );
+    }
+
+    // Every malformed chain of operators names the operator at fault
+    #[test]
+    fn operator_errors() {
+        let linear: GraphOperator = GraphOperator::Linear {
+            weights: Tensor2D::new(0.5, 4, 2),
+            bias: Tensor2D::new(0.5, 3, 2),
+        };
+        assert_eq!(
+            validate_graph_operators(&chain(vec![linear.clone()])),
+            Ok(())
+        );
+
+        assert_eq!(
+            validate_graph_operators(&chain(vec![
+                GraphOperator::ReLU,
+                GraphOperator::Empty,
+                linear.clone()
+            ])),
+            Err(GraphError::MisplacedOperator {
+                index: 2,
+                operator: "Empty",
+                reason: "comes before a linear layer"
+            })
+        );
+
+        assert_eq!(
+            validate_graph_operators(&chain(vec![
+                GraphOperator::ReLU,
+                GraphOperator::Linear {
+                    weights: Tensor2D::new(0.5, 4, 2),
+                    bias: Tensor2D::new(0.5, 1, 2),
+                },
+            ])),
+            Err(GraphError::ShapeMismatch {
+                index: 2,
+                tensor: "bias",
+                expected: (3, 2),
+                actual: (1, 2)
+            })
+        );
+
+        let mut misplaced: Vec<GraphOperator> = chain(vec![linear.clone()]);
+        misplaced.swap(0, 1);
+        assert!(matches!(
+            validate_graph_operators(&misplaced),
+            Err(GraphError::MisplacedOperator {
+                index: 1,
+                operator: "HostToDevice",
+                ..
+            })
+        ));
+
+        let mut missing_output: Vec<GraphOperator> = chain(vec![linear]);
+        missing_output.pop();
+        assert_eq!(
+            validate_graph_operators(&missing_output),
+            Err(GraphError::MissingTransfer {
+                operator: "DeviceToHost"
+            })
+        );
+
+        let empty_input: Vec<GraphOperator> = vec![
+            GraphOperator::HostToDevice {
+                input: Tensor2D::new(0.5, 0, 4),
+            },
+            GraphOperator::DeviceToHost,
+        ];
+        let error: GraphError = validate_graph_operators(&empty_input).unwrap_err();
+        assert_eq!(
+            error,
+            GraphError::EmptyTensor {
+                index: 0,
+                tensor: "input",
+                shape: (0, 4)
+            }
+        );
+        assert_eq!(error.index(), Some(0));
+    }
+}
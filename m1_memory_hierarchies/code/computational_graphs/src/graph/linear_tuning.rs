@@ -0,0 +1,452 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::Path,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use wgpu::{
+    AdapterInfo, BindGroup, BindGroupLayout, BindingResource, BufferSlice, CommandEncoder,
+    ComputePass, ComputePipeline, Limits, ShaderModule,
+};
+
+use crate::shared::{
+    gpu_utilities::{create_bind_group, create_compute_pipeline, create_shader_module, GPUHandles},
+    tensor2d::Tensor2D,
+    tensor2d_gpu::{LinearUniform, Tensor2DGPU},
+};
+
+// Where the autotuner keeps the fastest Linear kernel of every adapter it has tuned
+pub const LINEAR_TUNING_CACHE_PATH: &str = "autotune/linear_kernels.json";
+// Kernels cached with another version are ignored. Bump it whenever linear_tiled.wgsl
+// or the candidates change, so old measurements don't pick the kernel.
+pub const LINEAR_TUNING_CACHE_VERSION: u32 = 1;
+
+// The sizes linear_tiled.wgsl is compiled with. A workgroup of workgroup_x * workgroup_y
+// threads computes a tile_rows() x tile_columns() tile of the output, every thread
+// thread_rows x thread_columns of it, tile_inner elements of the inner dimension at a time.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct LinearTileConfig {
+    pub workgroup_x: u32,
+    pub workgroup_y: u32,
+    pub thread_rows: u32,
+    pub thread_columns: u32,
+    pub tile_inner: u32,
+}
+
+impl LinearTileConfig {
+    // Fits within the default limits of every adapter wgpu supports
+    pub const DEFAULT: LinearTileConfig = LinearTileConfig {
+        workgroup_x: 16,
+        workgroup_y: 16,
+        thread_rows: 2,
+        thread_columns: 2,
+        tile_inner: 16,
+    };
+
+    pub fn tile_rows(&self) -> u32 {
+        self.workgroup_y * self.thread_rows
+    }
+
+    pub fn tile_columns(&self) -> u32 {
+        self.workgroup_x * self.thread_columns
+    }
+
+    pub fn thread_count(&self) -> u32 {
+        self.workgroup_x * self.workgroup_y
+    }
+
+    pub fn workgroup_memory_bytes(&self) -> u32 {
+        let element_count: u32 =
+            self.tile_rows() * self.tile_inner + self.tile_inner * self.tile_columns();
+        element_count * std::mem::size_of::<f32>() as u32
+    }
+
+    pub fn fits(&self, limits: &Limits) -> bool {
+        self.workgroup_x <= limits.max_compute_workgroup_size_x
+            && self.workgroup_y <= limits.max_compute_workgroup_size_y
+            && self.thread_count() <= limits.max_compute_invocations_per_workgroup
+            && self.workgroup_memory_bytes() <= limits.max_compute_workgroup_storage_size
+    }
+
+    // The x dimension walks the columns of the output and the y dimension the rows
+    pub fn workgroup_count(&self, row_count: usize, column_count: usize) -> (u32, u32) {
+        (
+            column_count.div_ceil(self.tile_columns() as usize) as u32,
+            row_count.div_ceil(self.tile_rows() as usize) as u32,
+        )
+    }
+
+    pub fn shader_source(&self) -> String {
+        let sizes: [(&str, u32); 10] = [
+            ("WORKGROUP_X", self.workgroup_x),
+            ("WORKGROUP_Y", self.workgroup_y),
+            ("THREAD_COUNT", self.thread_count()),
+            ("THREAD_ROWS", self.thread_rows),
+            ("THREAD_COLUMNS", self.thread_columns),
+            ("TILE_ROWS", self.tile_rows()),
+            ("TILE_COLUMNS", self.tile_columns()),
+            ("TILE_INNER", self.tile_inner),
+            ("INPUT_TILE_SIZE", self.tile_rows() * self.tile_inner),
+            ("WEIGHTS_TILE_SIZE", self.tile_inner * self.tile_columns()),
+        ];
+
+        let mut source: String = include_str!("../shared/shaders/linear_tiled.wgsl").to_string();
+        for (name, value) in sizes {
+            source = source.replace(&format!("{{{{{}}}}}", name), &value.to_string());
+        }
+        source.replace(
+            "{{ACCUMULATOR_COUNT}}",
+            &(self.thread_rows * self.thread_columns).to_string(),
+        )
+    }
+}
+
+// The kernel GraphRunnerGPU runs Linear and LinearReLU nodes with
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum LinearKernel {
+    // linear.wgsl, one output per thread straight from global memory
+    Naive,
+    Tiled(LinearTileConfig),
+}
+
+impl Default for LinearKernel {
+    fn default() -> Self {
+        LinearKernel::Tiled(LinearTileConfig::DEFAULT)
+    }
+}
+
+impl LinearKernel {
+    pub fn name(&self) -> String {
+        match self {
+            LinearKernel::Naive => "naive 8x8".to_string(),
+            LinearKernel::Tiled(config) => format!(
+                "tiled {}x{}x{} - {}x{} threads, {}x{} outputs per thread",
+                config.tile_rows(),
+                config.tile_columns(),
+                config.tile_inner,
+                config.workgroup_x,
+                config.workgroup_y,
+                config.thread_rows,
+                config.thread_columns
+            ),
+        }
+    }
+
+    pub fn shader_source(&self) -> String {
+        match self {
+            LinearKernel::Naive => include_str!("../shared/shaders/linear.wgsl").to_string(),
+            LinearKernel::Tiled(config) => config.shader_source(),
+        }
+    }
+
+    pub fn workgroup_count(&self, row_count: usize, column_count: usize) -> (u32, u32) {
+        match self {
+            // linear.wgsl has the rows along x
+            LinearKernel::Naive => {
+                let block_size: usize = 8;
+                (
+                    row_count.div_ceil(block_size) as u32,
+                    column_count.div_ceil(block_size) as u32,
+                )
+            }
+            LinearKernel::Tiled(config) => config.workgroup_count(row_count, column_count),
+        }
+    }
+}
+
+// Every kernel the autotuner measures which fits within the limits
+pub fn candidate_kernels(limits: &Limits) -> Vec<LinearKernel> {
+    // (workgroup_x, workgroup_y, thread_rows, thread_columns, tile_inner)
+    let sizes: [(u32, u32, u32, u32, u32); 10] = [
+        (8, 8, 1, 1, 8),
+        (16, 16, 1, 1, 16),
+        (8, 8, 2, 2, 8),
+        (8, 8, 2, 2, 16),
+        (16, 16, 2, 2, 8),
+        (16, 16, 2, 2, 16),
+        (8, 8, 4, 4, 8),
+        (8, 8, 4, 4, 16),
+        (16, 16, 4, 4, 8),
+        (16, 8, 4, 4, 8),
+    ];
+
+    let mut kernels: Vec<LinearKernel> = vec![LinearKernel::Naive];
+    for (workgroup_x, workgroup_y, thread_rows, thread_columns, tile_inner) in sizes {
+        let config: LinearTileConfig = LinearTileConfig {
+            workgroup_x,
+            workgroup_y,
+            thread_rows,
+            thread_columns,
+            tile_inner,
+        };
+        if config.fits(limits) {
+            kernels.push(LinearKernel::Tiled(config));
+        }
+    }
+
+    kernels
+}
+
+// Identifies an adapter and its driver, a new driver can change which kernel is fastest
+pub fn adapter_key(adapter_info: &AdapterInfo) -> String {
+    format!(
+        "{} - {:?} - vendor {:#x} device {:#x} - {} {}",
+        adapter_info.name,
+        adapter_info.backend,
+        adapter_info.vendor,
+        adapter_info.device,
+        adapter_info.driver,
+        adapter_info.driver_info
+    )
+    .trim()
+    .to_string()
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LinearTuningCache {
+    pub version: u32,
+    pub kernels: BTreeMap<String, LinearKernel>,
+}
+
+impl Default for LinearTuningCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LinearTuningCache {
+    pub fn new() -> Self {
+        LinearTuningCache {
+            version: LINEAR_TUNING_CACHE_VERSION,
+            kernels: BTreeMap::<String, LinearKernel>::new(),
+        }
+    }
+
+    // A missing, unreadable or outdated cache is the same as an empty one
+    pub fn load(path: &str) -> Self {
+        let cache: Option<LinearTuningCache> = fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok());
+        match cache {
+            Some(cache) if cache.version == LINEAR_TUNING_CACHE_VERSION => cache,
+            _ => Self::new(),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        if let Some(directory) = Path::new(path).parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let text: String = serde_json::to_string_pretty(self)
+            .expect("Failed to serialize the Linear tuning cache");
+        fs::write(path, text)
+    }
+
+    pub fn kernel(&self, adapter_info: &AdapterInfo) -> Option<LinearKernel> {
+        self.kernels.get(&adapter_key(adapter_info)).copied()
+    }
+
+    pub fn insert(&mut self, adapter_info: &AdapterInfo, kernel: LinearKernel) {
+        self.kernels.insert(adapter_key(adapter_info), kernel);
+    }
+}
+
+// The cache at LINEAR_TUNING_CACHE_PATH, read the first time a runner needs it
+// so building runners in a loop doesn't read the file again every time.
+static LOADED_CACHE: Mutex<Option<LinearTuningCache>> = Mutex::new(None);
+
+// The kernel the autotuner found fastest for this adapter, or the default tiled kernel
+// if it hasn't been tuned.
+pub fn tuned_linear_kernel(adapter_info: &AdapterInfo) -> LinearKernel {
+    let mut loaded_cache = LOADED_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    loaded_cache
+        .get_or_insert_with(|| LinearTuningCache::load(LINEAR_TUNING_CACHE_PATH))
+        .kernel(adapter_info)
+        .unwrap_or_default()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TuningResult {
+    pub kernel: LinearKernel,
+    pub nanoseconds: f64,
+}
+
+// Runs every candidate kernel on a row_count x inner_count times inner_count x column_count
+// linear layer. Kernels whose output doesn't match the CPU are left out.
+// The fastest kernel comes first.
+pub async fn autotune_linear(
+    gpu_handles: &GPUHandles,
+    row_count: usize,
+    inner_count: usize,
+    column_count: usize,
+    iteration_count: usize,
+) -> Vec<TuningResult> {
+    let input: Tensor2D = Tensor2D::new(0.001, row_count, inner_count);
+    let weights: Tensor2D = Tensor2D::new(0.002, inner_count, column_count);
+    let bias: Tensor2D = Tensor2D::new(0.1, row_count, column_count);
+    let expected: Tensor2D = Tensor2D::linear(&input, &weights, &bias);
+
+    let input_device: Tensor2DGPU = Tensor2DGPU::from_tensor2d(gpu_handles, "input", &input);
+    let weights_device: Tensor2DGPU = Tensor2DGPU::from_tensor2d(gpu_handles, "weights", &weights);
+    let bias_device: Tensor2DGPU = Tensor2DGPU::from_tensor2d(gpu_handles, "bias", &bias);
+
+    let mut results: Vec<TuningResult> = Vec::<TuningResult>::new();
+    for kernel in candidate_kernels(&gpu_handles.device.limits()) {
+        let mut output_device: Tensor2DGPU =
+            Tensor2DGPU::new(gpu_handles, "output", 0.0, row_count, column_count);
+        let elapsed_time: Duration = time_kernel(
+            gpu_handles,
+            &kernel,
+            &input_device,
+            &weights_device,
+            &bias_device,
+            &mut output_device,
+            iteration_count,
+        )
+        .await;
+
+        let max_difference: f32 = expected
+            .data
+            .iter()
+            .zip(&output_device.data.data)
+            .map(|(expected, output)| (expected - output).abs() / expected.abs().max(1.0))
+            .fold(0.0, f32::max);
+        if 0.001 < max_difference {
+            println!(
+                "Linear kernel {} differs from the CPU by {}, leaving it out",
+                kernel.name(),
+                max_difference
+            );
+            continue;
+        }
+
+        results.push(TuningResult {
+            kernel,
+            nanoseconds: elapsed_time.as_nanos() as f64 / iteration_count.max(1) as f64,
+        });
+    }
+
+    results.sort_by(|a, b| a.nanoseconds.total_cmp(&b.nanoseconds));
+    results
+}
+
+// Runs the kernel once, reads the output back for checking,
+// then times iteration_count runs submitted together.
+async fn time_kernel(
+    gpu_handles: &GPUHandles,
+    kernel: &LinearKernel,
+    input: &Tensor2DGPU,
+    weights: &Tensor2DGPU,
+    bias: &Tensor2DGPU,
+    output: &mut Tensor2DGPU,
+    iteration_count: usize,
+) -> Duration {
+    let uniform_device: LinearUniform = LinearUniform::from_tensor_2d_gpu(
+        gpu_handles,
+        "Linear Layer Uniform",
+        input,
+        weights,
+        bias,
+        output,
+    );
+    let cs_module: ShaderModule = create_shader_module(gpu_handles, &kernel.shader_source());
+    let compute_pipeline: ComputePipeline =
+        create_compute_pipeline(gpu_handles, &cs_module, "main");
+    let bind_group_layout: BindGroupLayout = compute_pipeline.get_bind_group_layout(0);
+    let to_be_bound: Vec<(u32, BindingResource)> = vec![
+        (0, uniform_device.storage_buffer.as_entire_binding()),
+        (1, input.storage_buffer.as_entire_binding()),
+        (2, weights.storage_buffer.as_entire_binding()),
+        (3, bias.storage_buffer.as_entire_binding()),
+        (4, output.storage_buffer.as_entire_binding()),
+    ];
+    let bind_group: BindGroup = create_bind_group(gpu_handles, &bind_group_layout, to_be_bound);
+    let (launch_blocks_x, launch_blocks_y): (u32, u32) =
+        kernel.workgroup_count(output.row_count, output.column_count);
+
+    let encode = |encoder: &mut CommandEncoder, dispatch_count: usize| {
+        let mut cpass: ComputePass =
+            encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+        cpass.set_pipeline(&compute_pipeline);
+        cpass.set_bind_group(0, &bind_group, &[]);
+        cpass.insert_debug_marker("linear_autotune");
+        for _ in 0..dispatch_count {
+            cpass.dispatch_workgroups(launch_blocks_x, launch_blocks_y, 1);
+        }
+    };
+
+    // Warm up, which also gives the output to check
+    let mut encoder: CommandEncoder = gpu_handles
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    encode(&mut encoder, 1);
+    output.copy_from_gpu_mut(&mut encoder);
+    gpu_handles.queue.submit(Some(encoder.finish()));
+
+    let buffer_slice: BufferSlice = output.staging_buffer.slice(..);
+    let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
+    buffer_slice.map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
+    output.receiver = Some(receiver);
+    gpu_handles.device.poll(wgpu::Maintain::Wait);
+    output.retrieve_results().await;
+
+    let mut encoder: CommandEncoder = gpu_handles
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    encode(&mut encoder, iteration_count);
+    let now: Instant = Instant::now();
+    gpu_handles.queue.submit(Some(encoder.finish()));
+    gpu_handles.device.poll(wgpu::Maintain::Wait);
+    now.elapsed()
+}
+
+// Tunes the Linear kernel for this adapter and stores the fastest one in the cache at path,
+// where every GraphRunnerGPU built afterwards, in this run or later ones, picks it up.
+pub fn autotune_and_cache(
+    gpu_handles: &GPUHandles,
+    path: &str,
+    size: usize,
+    iteration_count: usize,
+    debug_level: u32,
+) -> Option<LinearKernel> {
+    let results: Vec<TuningResult> = pollster::block_on(autotune_linear(
+        gpu_handles,
+        size,
+        size,
+        size,
+        iteration_count,
+    ));
+    if 1 < debug_level {
+        for result in &results {
+            println!(
+                "Linear autotuning {}x{}: {:>12.0} ns - {}",
+                size,
+                size,
+                result.nanoseconds,
+                result.kernel.name()
+            );
+        }
+    }
+    let best: LinearKernel = results.first()?.kernel;
+
+    let mut cache: LinearTuningCache = LinearTuningCache::load(path);
+    cache.insert(&gpu_handles.adapter_info, best);
+    if let Err(error) = cache.save(path) {
+        println!(
+            "Failed to write the Linear tuning cache to {}! {}",
+            path, error
+        );
+    }
+    if path == LINEAR_TUNING_CACHE_PATH {
+        *LOADED_CACHE
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(cache);
+    }
+
+    Some(best)
+}
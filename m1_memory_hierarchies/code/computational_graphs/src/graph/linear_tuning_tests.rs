@@ -0,0 +1,342 @@
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use wgpu::{AdapterInfo, Backend, DeviceType, Limits};
+
+    use crate::{
+        graph::linear_tuning::{
+            adapter_key, candidate_kernels, LinearKernel, LinearTileConfig, LinearTuningCache,
+            LINEAR_TUNING_CACHE_VERSION,
+        },
+        shared::tensor2d::Tensor2D,
+    };
+
+    // Shapes which none of the tile sizes divide
+    const SHAPES: [(usize, usize, usize); 5] = [
+        (1, 1, 1),
+        (3, 5, 7),
+        (17, 33, 9),
+        (40, 70, 65),
+        (64, 16, 32),
+    ];
+
+    fn adapter_info(name: &str, driver_info: &str) -> AdapterInfo {
+        AdapterInfo {
+            name: name.to_string(),
+            vendor: 0x10de,
+            device: 0x2684,
+            device_type: DeviceType::DiscreteGpu,
+            driver: "driver".to_string(),
+            driver_info: driver_info.to_string(),
+            backend: Backend::Vulkan,
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "computational_graphs_{}_{}/linear_kernels.json",
+            name,
+            std::process::id()
+        ))
+    }
+
+    fn tiled_candidates() -> Vec<LinearTileConfig> {
+        candidate_kernels(&Limits::default())
+            .into_iter()
+            .filter_map(|kernel| match kernel {
+                LinearKernel::Naive => None,
+                LinearKernel::Tiled(config) => Some(config),
+            })
+            .collect()
+    }
+
+    // Does what linear_tiled.wgsl does, one workgroup at a time, so the tiling can be
+    // checked for every candidate without a GPU.
+    fn emulate(
+        config: &LinearTileConfig,
+        input: &Tensor2D,
+        weights: &Tensor2D,
+        bias: &Tensor2D,
+    ) -> Tensor2D {
+        let mut output: Tensor2D = Tensor2D::new(0.0, input.row_count, weights.column_count);
+        let inner_count: usize = input.column_count;
+        let tile_rows: usize = config.tile_rows() as usize;
+        let tile_columns: usize = config.tile_columns() as usize;
+        let tile_inner: usize = config.tile_inner as usize;
+        let workgroup_x: usize = config.workgroup_x as usize;
+        let workgroup_y: usize = config.workgroup_y as usize;
+        let thread_rows: usize = config.thread_rows as usize;
+        let thread_columns: usize = config.thread_columns as usize;
+
+        let (group_count_x, group_count_y): (u32, u32) =
+            config.workgroup_count(output.row_count, output.column_count);
+        let mut input_tile: Vec<f32> = vec![0.0; tile_rows * tile_inner];
+        let mut weights_tile: Vec<f32> = vec![0.0; tile_inner * tile_columns];
+        for group_y in 0..group_count_y as usize {
+            for group_x in 0..group_count_x as usize {
+                let tile_row: usize = group_y * tile_rows;
+                let tile_column: usize = group_x * tile_columns;
+                let mut accumulators: Vec<f32> =
+                    vec![0.0; workgroup_x * workgroup_y * thread_rows * thread_columns];
+
+                for inner_start in (0..inner_count).step_by(tile_inner) {
+                    for (index, value) in input_tile.iter_mut().enumerate() {
+                        let row: usize = tile_row + index / tile_inner;
+                        let inner: usize = inner_start + index % tile_inner;
+                        *value = if row < input.row_count && inner < inner_count {
+                            input.data[row * inner_count + inner]
+                        } else {
+                            0.0
+                        };
+                    }
+                    for (index, value) in weights_tile.iter_mut().enumerate() {
+                        let inner: usize = inner_start + index / tile_columns;
+                        let column: usize = tile_column + index % tile_columns;
+                        *value = if inner < inner_count && column < weights.column_count {
+                            weights.data[inner * weights.column_count + column]
+                        } else {
+                            0.0
+                        };
+                    }
+
+                    for local_y in 0..workgroup_y {
+                        for local_x in 0..workgroup_x {
+                            let thread_index: usize = local_y * workgroup_x + local_x;
+                            let thread_accumulators: &mut [f32] =
+                                &mut accumulators[thread_index * thread_rows * thread_columns
+                                    ..(thread_index + 1) * thread_rows * thread_columns];
+                            for inner in 0..tile_inner {
+                                for row in 0..thread_rows {
+                                    let input_value: f32 = input_tile
+                                        [(row * workgroup_y + local_y) * tile_inner + inner];
+                                    for column in 0..thread_columns {
+                                        thread_accumulators[row * thread_columns + column] +=
+                                            input_value
+                                                * weights_tile[inner * tile_columns
+                                                    + column * workgroup_x
+                                                    + local_x];
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+
+                for local_y in 0..workgroup_y {
+                    for local_x in 0..workgroup_x {
+                        let thread_index: usize = local_y * workgroup_x + local_x;
+                        for row in 0..thread_rows {
+                            let output_row: usize = tile_row + row * workgroup_y + local_y;
+                            for column in 0..thread_columns {
+                                let output_column: usize =
+                                    tile_column + column * workgroup_x + local_x;
+                                if output_row < output.row_count
+                                    && output_column < output.column_count
+                                {
+                                    let output_index: usize =
+                                        output_row * output.column_count + output_column;
+                                    output.data[output_index] =
+                                        accumulators[thread_index * thread_rows * thread_columns
+                                            + row * thread_columns
+                                            + column]
+                                            + bias.data[output_index];
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        output
+    }
+
+    #[test]
+    fn default_fits_default_limits() {
+        let config: LinearTileConfig = LinearTileConfig::DEFAULT;
+        assert!(config.fits(&Limits::default()));
+        assert!(config.fits(&Limits::downlevel_defaults()));
+        assert_eq!(config.tile_rows(), 32);
+        assert_eq!(config.tile_columns(), 32);
+        assert_eq!(config.thread_count(), 256);
+        assert_eq!(config.workgroup_memory_bytes(), 2 * 32 * 16 * 4);
+        assert_eq!(
+            LinearKernel::default(),
+            LinearKernel::Tiled(LinearTileConfig::DEFAULT)
+        );
+    }
+
+    #[test]
+    fn candidates_respect_limits() {
+        let candidates: Vec<LinearKernel> = candidate_kernels(&Limits::default());
+        assert_eq!(candidates[0], LinearKernel::Naive);
+        assert!(candidates.contains(&LinearKernel::default()));
+
+        // Small adapters lose the candidates which don't fit, but keep the naive kernel
+        let limits: Limits = Limits {
+            max_compute_invocations_per_workgroup: 64,
+            max_compute_workgroup_storage_size: 2048,
+            ..Limits::default()
+        };
+        let candidates: Vec<LinearKernel> = candidate_kernels(&limits);
+        assert_eq!(candidates[0], LinearKernel::Naive);
+        assert!(1 < candidates.len());
+        for kernel in &candidates[1..] {
+            match kernel {
+                LinearKernel::Naive => panic!("The naive kernel is listed twice"),
+                LinearKernel::Tiled(config) => {
+                    assert!(config.thread_count() <= 64);
+                    assert!(config.workgroup_memory_bytes() <= 2048);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn workgroup_counts_cover_the_output() {
+        for config in tiled_candidates() {
+            for (rows, _, columns) in SHAPES {
+                let (x, y): (u32, u32) = config.workgroup_count(rows, columns);
+                assert!(columns <= (x * config.tile_columns()) as usize);
+                assert!(((x - 1) * config.tile_columns()) < columns as u32);
+                assert!(rows <= (y * config.tile_rows()) as usize);
+                assert!(((y - 1) * config.tile_rows()) < rows as u32);
+            }
+        }
+        assert_eq!(LinearKernel::Naive.workgroup_count(17, 9), (3, 2));
+    }
+
+    #[test]
+    fn emulated_candidates_match_cpu() {
+        for config in tiled_candidates() {
+            for (rows, inner, columns) in SHAPES {
+                let input: Tensor2D = Tensor2D::new(0.1, rows, inner);
+                let weights: Tensor2D = Tensor2D::new(0.2, inner, columns);
+                let bias: Tensor2D = Tensor2D::new(0.3, rows, columns);
+                let expected: Tensor2D = Tensor2D::linear(&input, &weights, &bias);
+                let output: Tensor2D = emulate(&config, &input, &weights, &bias);
+                for (expected, output) in expected.data.iter().zip(&output.data) {
+                    assert!(
+                        (expected - output).abs() <= expected.abs().max(1.0) * 1e-5,
+                        "{:?} on {}x{}x{}: {} != {}",
+                        config,
+                        rows,
+                        inner,
+                        columns,
+                        expected,
+                        output
+                    );
+                }
+            }
+        }
+    }
+
+    // The GPU kernels can't run without a GPU, but every candidate can still be validated
+    #[test]
+    fn candidate_shaders_validate() {
+        for kernel in candidate_kernels(&Limits::default()) {
+            let source: String = kernel.shader_source();
+            assert!(
+                !source.contains("{{"),
+                "{} left a placeholder",
+                kernel.name()
+            );
+            let module: naga::Module = naga::front::wgsl::parse_str(&source)
+                .unwrap_or_else(|error| panic!("{}", error.emit_to_string(&source)));
+            naga::valid::Validator::new(
+                naga::valid::ValidationFlags::all(),
+                naga::valid::Capabilities::empty(),
+            )
+            .validate(&module)
+            .unwrap_or_else(|error| panic!("{}: {:?}", kernel.name(), error));
+
+            let names: Vec<&str> = module
+                .entry_points
+                .iter()
+                .map(|entry_point| entry_point.name.as_str())
+                .collect();
+            assert_eq!(names, vec!["main", "main_with_relu"]);
+            if let LinearKernel::Tiled(config) = kernel {
+                assert_eq!(
+                    module.entry_points[0].workgroup_size,
+                    [config.workgroup_x, config.workgroup_y, 1]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn adapter_keys() {
+        let adapter: AdapterInfo = adapter_info("GPU", "1.0");
+        assert_eq!(
+            adapter_key(&adapter),
+            adapter_key(&adapter_info("GPU", "1.0"))
+        );
+        assert_ne!(
+            adapter_key(&adapter),
+            adapter_key(&adapter_info("GPU", "2.0"))
+        );
+        assert_ne!(
+            adapter_key(&adapter),
+            adapter_key(&adapter_info("Other", "1.0"))
+        );
+        assert!(adapter_key(&adapter).contains("GPU"));
+    }
+
+    #[test]
+    fn cache_round_trip() {
+        let path: PathBuf = temp_path("round_trip");
+        let path_string: &str = path.to_str().unwrap();
+        let first: AdapterInfo = adapter_info("GPU", "1.0");
+        let second: AdapterInfo = adapter_info("Other", "1.0");
+
+        // Nothing has been written yet
+        let mut cache: LinearTuningCache = LinearTuningCache::load(path_string);
+        assert_eq!(cache, LinearTuningCache::new());
+        assert_eq!(cache.kernel(&first), None);
+
+        let tiled: LinearKernel = LinearKernel::Tiled(LinearTileConfig {
+            workgroup_x: 8,
+            workgroup_y: 8,
+            thread_rows: 4,
+            thread_columns: 4,
+            tile_inner: 8,
+        });
+        cache.insert(&first, tiled);
+        cache.insert(&second, LinearKernel::Naive);
+        cache.save(path_string).unwrap();
+
+        let loaded: LinearTuningCache = LinearTuningCache::load(path_string);
+        assert_eq!(loaded, cache);
+        assert_eq!(loaded.kernel(&first), Some(tiled));
+        assert_eq!(loaded.kernel(&second), Some(LinearKernel::Naive));
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    // Kernels tuned for an older shader, or a damaged file, are thrown away
+    #[test]
+    fn outdated_or_invalid_cache_is_empty() {
+        let path: PathBuf = temp_path("outdated");
+        let path_string: &str = path.to_str().unwrap();
+        let adapter: AdapterInfo = adapter_info("GPU", "1.0");
+
+        let mut cache: LinearTuningCache = LinearTuningCache::new();
+        cache.version = LINEAR_TUNING_CACHE_VERSION + 1;
+        cache.insert(&adapter, LinearKernel::Naive);
+        cache.save(path_string).unwrap();
+        assert_eq!(
+            LinearTuningCache::load(path_string),
+            LinearTuningCache::new()
+        );
+
+        std::fs::write(&path, "{ not json").unwrap();
+        assert_eq!(
+            LinearTuningCache::load(path_string),
+            LinearTuningCache::new()
+        );
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
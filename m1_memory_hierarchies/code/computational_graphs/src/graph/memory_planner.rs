@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+// What the memory planner needs to know about each data buffer
+#[derive(Clone, Debug)]
+pub struct BufferDescription {
+    pub row_count: usize,
+    pub column_count: usize,
+    // Pinned buffers keep their own memory for the lifetime of the runner.
+    // This is the case for inputs, weights, biases and outputs, which have
+    // to still be there the next time the graph is run.
+    pub is_pinned: bool,
+}
+
+impl BufferDescription {
+    pub fn bytes(&self) -> usize {
+        self.row_count * self.column_count * std::mem::size_of::<f32>()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MemoryPlan {
+    // The new buffer each of the old buffers was assigned to
+    pub buffer_assignments: Vec<usize>,
+    // For each new buffer, the old buffer whose memory it keeps
+    pub kept_buffers: Vec<usize>,
+    pub buffer_count_before: usize,
+    pub buffer_count_after: usize,
+    // Every buffer is allocated when the runner is built and is kept
+    // until the runner is dropped, so the peak memory is all of the buffers
+    pub peak_bytes_before: usize,
+    pub peak_bytes_after: usize,
+}
+
+// A buffer is live from the first node which uses it, usually the node writing it,
+// until the last node which reads it. Once a buffer is dead its memory can be handed
+// to a buffer which becomes live later on. We go through the nodes in execution
+// order and greedily reuse any dead buffer with the same shape.
+//
+// A buffer which dies at a node is only released after that node, so a node never
+// writes its output to the memory of one of its inputs.
+//
+// node_buffer_indices are the buffer indices of every node, in execution order.
+pub fn plan_memory(
+    node_buffer_indices: &[Vec<usize>],
+    buffers: &[BufferDescription],
+) -> MemoryPlan {
+    let mut first_use: Vec<Option<usize>> = vec![None; buffers.len()];
+    let mut last_use: Vec<usize> = vec![0; buffers.len()];
+    for (node_index, buffer_indices) in node_buffer_indices.iter().enumerate() {
+        for buffer_index in buffer_indices {
+            first_use[*buffer_index].get_or_insert(node_index);
+            last_use[*buffer_index] = node_index;
+        }
+    }
+
+    let mut released_after: Vec<Vec<usize>> = vec![Vec::<usize>::new(); node_buffer_indices.len()];
+    for (buffer_index, buffer) in buffers.iter().enumerate() {
+        if !buffer.is_pinned && first_use[buffer_index].is_some() {
+            released_after[last_use[buffer_index]].push(buffer_index);
+        }
+    }
+
+    let mut assignments: Vec<Option<usize>> = vec![None; buffers.len()];
+    let mut kept_buffers: Vec<usize> = Vec::<usize>::new();
+    // Dead buffers ready to be reused, sorted by shape
+    let mut free_buffers: HashMap<(usize, usize), Vec<usize>> =
+        HashMap::<(usize, usize), Vec<usize>>::new();
+
+    for (node_index, buffer_indices) in node_buffer_indices.iter().enumerate() {
+        for buffer_index in buffer_indices {
+            if assignments[*buffer_index].is_some() {
+                continue;
+            }
+
+            let buffer: &BufferDescription = &buffers[*buffer_index];
+            let reused: Option<usize> = if buffer.is_pinned {
+                None
+            } else {
+                free_buffers
+                    .get_mut(&(buffer.row_count, buffer.column_count))
+                    .and_then(|free| free.pop())
+            };
+
+            assignments[*buffer_index] = match reused {
+                Some(new_index) => Some(new_index),
+                None => {
+                    kept_buffers.push(*buffer_index);
+                    Some(kept_buffers.len() - 1)
+                }
+            };
+        }
+
+        for buffer_index in &released_after[node_index] {
+            let buffer: &BufferDescription = &buffers[*buffer_index];
+            free_buffers
+                .entry((buffer.row_count, buffer.column_count))
+                .or_default()
+                .push(assignments[*buffer_index].unwrap());
+        }
+    }
+
+    // Buffers which aren't used by any node just keep their memory
+    for (buffer_index, assignment) in assignments.iter_mut().enumerate() {
+        if assignment.is_none() {
+            kept_buffers.push(buffer_index);
+            *assignment = Some(kept_buffers.len() - 1);
+        }
+    }
+
+    MemoryPlan {
+        buffer_assignments: assignments.into_iter().map(|x| x.unwrap()).collect(),
+        buffer_count_before: buffers.len(),
+        buffer_count_after: kept_buffers.len(),
+        peak_bytes_before: buffers.iter().map(|buffer| buffer.bytes()).sum(),
+        peak_bytes_after: kept_buffers
+            .iter()
+            .map(|buffer_index| buffers[*buffer_index].bytes())
+            .sum(),
+        kept_buffers,
+    }
+}
+
+// Keeps the buffers listed in the plan, in the new order, and drops the rest.
+// Works for both Tensor2D and Tensor2DGPU buffers.
+pub fn apply_memory_plan<T>(buffers: Vec<T>, plan: &MemoryPlan) -> Vec<T> {
+    let mut old_buffers: Vec<Option<T>> = buffers.into_iter().map(Some).collect();
+    plan.kept_buffers
+        .iter()
+        .map(|buffer_index| {
+            old_buffers[*buffer_index]
+                .take()
+                .expect("A buffer was kept twice in memory_planner::apply_memory_plan")
+        })
+        .collect()
+}
+
+pub fn print_memory_plan(name: &str, plan: &MemoryPlan) {
+    println!(
+        "{} memory plan - buffers: {} -> {} peak memory: {} bytes -> {} bytes",
+        name,
+        plan.buffer_count_before,
+        plan.buffer_count_after,
+        plan.peak_bytes_before,
+        plan.peak_bytes_after
+    );
+}
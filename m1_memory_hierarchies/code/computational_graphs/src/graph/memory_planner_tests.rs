@@ -0,0 +1,181 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        graph::{
+            graph_runner::GraphRunner,
+            memory_planner::{apply_memory_plan, plan_memory, BufferDescription, MemoryPlan},
+        },
+        shared::{
+            graph_operators::{GraphNode, GraphOperator},
+            tensor2d::Tensor2D,
+            tensor2d_axis::Axis,
+        },
+    };
+
+    const ERROR_TOLERANCE: f32 = 0.00001;
+
+    fn buffer(row_count: usize, column_count: usize, is_pinned: bool) -> BufferDescription {
+        BufferDescription {
+            row_count,
+            column_count,
+            is_pinned,
+        }
+    }
+
+    fn assert_tensors_match(expected: &Tensor2D, actual: &Tensor2D) {
+        assert_eq!(expected.len(), actual.len());
+        let difference: Tensor2D = Tensor2D::subtraction(expected, actual);
+        let abs_difference: f32 = difference.data.iter().map(|x| x.abs()).sum::<f32>();
+        assert!(abs_difference < ERROR_TOLERANCE);
+    }
+
+    // input -> a -> b -> c -> output, only two intermediate buffers are ever live at once
+    #[test]
+    fn chain() {
+        let buffers: Vec<BufferDescription> = vec![
+            buffer(4, 4, true),
+            buffer(4, 4, false),
+            buffer(4, 4, false),
+            buffer(4, 4, false),
+            buffer(4, 4, true),
+        ];
+        let node_buffer_indices: Vec<Vec<usize>> =
+            vec![vec![0, 1], vec![1, 2], vec![2, 3], vec![3, 4]];
+
+        let plan: MemoryPlan = plan_memory(&node_buffer_indices, &buffers);
+
+        assert_eq!(plan.buffer_count_before, 5);
+        assert_eq!(plan.buffer_count_after, 4);
+        assert_eq!(plan.buffer_assignments[1], plan.buffer_assignments[3]);
+        assert_ne!(plan.buffer_assignments[1], plan.buffer_assignments[2]);
+        assert_eq!(plan.peak_bytes_before, 5 * 16 * 4);
+        assert_eq!(plan.peak_bytes_after, 4 * 16 * 4);
+    }
+
+    // A node must never write to the memory of one of its own inputs
+    #[test]
+    fn no_aliasing_within_node() {
+        let buffers: Vec<BufferDescription> =
+            vec![buffer(2, 2, true), buffer(2, 2, false), buffer(2, 2, false)];
+        let node_buffer_indices: Vec<Vec<usize>> = vec![vec![0, 1], vec![1, 2]];
+
+        let plan: MemoryPlan = plan_memory(&node_buffer_indices, &buffers);
+        assert_ne!(plan.buffer_assignments[1], plan.buffer_assignments[2]);
+    }
+
+    #[test]
+    fn pinned_and_shapes() {
+        let buffers: Vec<BufferDescription> = vec![
+            buffer(2, 2, false),
+            buffer(2, 2, true),
+            buffer(2, 3, false),
+            buffer(2, 2, false),
+            buffer(2, 2, false),
+        ];
+        // 0 dies after node 0, 1 is pinned and 2 has another shape
+        let node_buffer_indices: Vec<Vec<usize>> =
+            vec![vec![0], vec![1], vec![2], vec![3], vec![4]];
+
+        let plan: MemoryPlan = plan_memory(&node_buffer_indices, &buffers);
+
+        assert_ne!(plan.buffer_assignments[0], plan.buffer_assignments[1]);
+        assert_ne!(plan.buffer_assignments[0], plan.buffer_assignments[2]);
+        assert_eq!(plan.buffer_assignments[0], plan.buffer_assignments[3]);
+        assert_eq!(plan.buffer_assignments[0], plan.buffer_assignments[4]);
+        assert_eq!(plan.buffer_count_after, 3);
+
+        let kept: Vec<usize> = apply_memory_plan(vec![0, 1, 2, 3, 4], &plan);
+        assert_eq!(kept, plan.kept_buffers);
+        for (new_index, old_index) in plan.kept_buffers.iter().enumerate() {
+            assert_eq!(plan.buffer_assignments[*old_index], new_index);
+        }
+    }
+
+    fn deep_graph(depth: usize, size: usize) -> Vec<GraphOperator> {
+        let mut graph: Vec<GraphOperator> = vec![GraphOperator::HostToDevice {
+            input: Tensor2D::new(0.1, size, size),
+        }];
+        for layer in 0..depth {
+            graph.push(GraphOperator::Linear {
+                weights: Tensor2D::new(0.01 / (layer + 1) as f32, size, size),
+                bias: Tensor2D::new(0.02, size, size),
+            });
+            if layer % 2 == 0 {
+                graph.push(GraphOperator::ReLU);
+            }
+        }
+        graph.push(GraphOperator::Softmax { axis: Axis::Global });
+        graph.push(GraphOperator::DeviceToHost);
+        graph
+    }
+
+    #[test]
+    fn graph_runner_deep_graph() {
+        for fuse_operators in [false, true] {
+            let graph: Vec<GraphOperator> = deep_graph(16, 8);
+
+            let mut unplanned: GraphRunner = GraphRunner::new(&graph, fuse_operators);
+            let expected: Tensor2D = unplanned.run();
+
+            let mut planned: GraphRunner = GraphRunner::new(&graph, fuse_operators);
+            let plan: MemoryPlan = planned.plan_memory();
+
+            // Input, output, 16 weights and 16 biases are pinned,
+            // at most two intermediate results are needed at a time
+            assert!(plan.buffer_count_after <= 1 + 32 + 1 + 2);
+            assert!(plan.peak_bytes_after < plan.peak_bytes_before);
+
+            // Running more than once makes sure nothing pinned was overwritten
+            assert_tensors_match(&expected, &planned.run());
+            assert_tensors_match(&expected, &planned.run());
+        }
+    }
+
+    #[test]
+    fn graph_runner_branches() {
+        let input: Tensor2D = Tensor2D::new(0.1, 3, 3);
+        let weights: Tensor2D = Tensor2D::new(0.01, 3, 3);
+        let bias: Tensor2D = Tensor2D::new(0.02, 3, 3);
+
+        let graph_nodes: Vec<GraphNode> = vec![
+            GraphNode::new(GraphOperator::HostToDevice { input }, vec![]),
+            GraphNode::new(
+                GraphOperator::Linear {
+                    weights: weights.clone(),
+                    bias: bias.clone(),
+                },
+                vec![0],
+            ),
+            GraphNode::new(GraphOperator::ReLU, vec![1]),
+            GraphNode::new(GraphOperator::Softmax { axis: Axis::Global }, vec![1]),
+            GraphNode::new(GraphOperator::Multiply, vec![2, 3]),
+            GraphNode::new(GraphOperator::ReLU, vec![4]),
+            GraphNode::new(GraphOperator::Linear { weights, bias }, vec![5]),
+            GraphNode::new(GraphOperator::Add, vec![6, 2]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![7]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![3]),
+        ];
+
+        let mut unplanned: GraphRunner = GraphRunner::from_graph_nodes(&graph_nodes, false);
+        let expected: Vec<Tensor2D> = unplanned.run_outputs();
+
+        let mut planned: GraphRunner = GraphRunner::from_graph_nodes(&graph_nodes, false);
+        let plan: MemoryPlan = planned.plan_memory();
+        assert!(plan.buffer_count_after < plan.buffer_count_before);
+
+        let outputs: Vec<Tensor2D> = planned.run_outputs();
+        assert_eq!(outputs.len(), expected.len());
+        for (expected, output) in expected.iter().zip(&outputs) {
+            assert_tensors_match(expected, output);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn backward_after_planning() {
+        let mut graph_runner: GraphRunner = GraphRunner::new(&deep_graph(2, 4), false);
+        graph_runner.plan_memory();
+        let output: Tensor2D = graph_runner.run();
+        graph_runner.backward(&output);
+    }
+}
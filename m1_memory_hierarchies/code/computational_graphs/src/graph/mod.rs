@@ -1,8 +1,26 @@
+pub mod executor;
+pub mod executor_tests;
+pub mod fusion;
+pub mod fusion_tests;
+pub mod graph_error;
+pub mod graph_export;
+pub mod graph_export_tests;
 pub mod graph_runner;
 pub mod graph_runner_gpu;
 pub mod graph_runner_gpu_test;
 pub mod graph_runner_tests;
 pub mod graph_validation;
+pub mod graph_validation_test;
+pub mod linear_tuning;
+pub mod linear_tuning_tests;
+pub mod memory_planner;
+pub mod memory_planner_tests;
 pub mod nodes;
 pub mod nodes_gpu;
+pub mod profiling;
+pub mod profiling_tests;
+pub mod quantization;
+pub mod quantization_tests;
 pub mod runner;
+pub mod training;
+pub mod training_tests;
@@ -1,6 +1,10 @@
 use std::vec::Drain;
 
 use crate::shared::tensor2d::Tensor2D;
+use crate::shared::tensor2d_axis::{Axis, AxisLayout, Reduction};
+use crate::shared::tensor2d_precision::PackedTensor2D;
+
+use super::fusion::{AxisOperator, FusedElementwise, FusionBase};
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum NodeOperator {
@@ -12,6 +16,17 @@ pub enum NodeOperator {
     Softmax,
     LinearReLU,
     LinearReLUSoftmax,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    FusedElementwise,
+    LogSoftmax,
+    Sum,
+    Max,
+    Mean,
+    ArgMax,
+    QuantizedLinear,
 }
 
 #[derive(Debug)]
@@ -19,6 +34,13 @@ pub struct Node {
     pub name: String,
     pub operator: NodeOperator,
     pub buffer_indices: Vec<usize>,
+    // Only set for FusedElementwise nodes
+    pub fusion: Option<FusedElementwise>,
+    // Only set for Softmax, LogSoftmax and the reductions
+    pub axis: Option<Axis>,
+    // Only set for QuantizedLinear nodes. The weights and bias stay packed
+    // and aren't data buffers, as they are neither batched nor trained.
+    pub packed_parameters: Option<(PackedTensor2D, PackedTensor2D)>,
 }
 
 impl Node {
@@ -27,6 +49,52 @@ impl Node {
             name,
             operator,
             buffer_indices,
+            fusion: None,
+            axis: None,
+            packed_parameters: None,
+        }
+    }
+
+    pub fn along(
+        name: String,
+        operator: NodeOperator,
+        buffer_indices: Vec<usize>,
+        axis: Axis,
+    ) -> Self {
+        Node {
+            name,
+            operator,
+            buffer_indices,
+            fusion: None,
+            axis: Some(axis),
+            packed_parameters: None,
+        }
+    }
+
+    pub fn fused(name: String, buffer_indices: Vec<usize>, fusion: FusedElementwise) -> Self {
+        Node {
+            name,
+            operator: NodeOperator::FusedElementwise,
+            buffer_indices,
+            fusion: Some(fusion),
+            axis: None,
+            packed_parameters: None,
+        }
+    }
+
+    pub fn quantized_linear(
+        name: String,
+        buffer_indices: Vec<usize>,
+        weights: PackedTensor2D,
+        bias: PackedTensor2D,
+    ) -> Self {
+        Node {
+            name,
+            operator: NodeOperator::QuantizedLinear,
+            buffer_indices,
+            fusion: None,
+            axis: None,
+            packed_parameters: Some((weights, bias)),
         }
     }
 }
@@ -71,11 +139,11 @@ fn sorted_mutable_references<'a>(
     }
 
     // Resort the references by their original ordering to match it to the correct buffer name
-    references.sort_by(|a, b| a.0.cmp(&b.0));
+    references.sort_by_key(|a| a.0);
     references
 }
 
-pub fn linear(node: &Node, data_buffers: &mut [Tensor2D]) {
+pub fn linear(node: &Node, data_buffers: &mut [Tensor2D], parallel: bool) {
     if node.buffer_indices.len() != 4 {
         panic!(
             "cpu_nodes::linear function expected 1 input buffer, received {}",
@@ -91,10 +159,44 @@ pub fn linear(node: &Node, data_buffers: &mut [Tensor2D]) {
     let bias: &Tensor2D = drain.next().unwrap().1;
     let output: &mut Tensor2D = drain.next().unwrap().1;
 
-    Tensor2D::linear_optimized(input, weights, bias, output)
+    if parallel {
+        Tensor2D::linear_parallel(input, weights, bias, output);
+    } else {
+        Tensor2D::linear_blocked(input, weights, bias, output);
+    }
+}
+
+fn packed_parameters_of(node: &Node) -> (&PackedTensor2D, &PackedTensor2D) {
+    match &node.packed_parameters {
+        Some((weights, bias)) => (weights, bias),
+        None => panic!(
+            "nodes::packed_parameters_of function received {}, which has no packed parameters",
+            node.name
+        ),
+    }
 }
 
-pub fn relu(node: &Node, data_buffers: &mut [Tensor2D]) {
+// The weights are decoded as they are read, see PackedTensor2D::linear.
+// There is no parallel version of the packed kernel.
+pub fn quantized_linear(node: &Node, data_buffers: &mut [Tensor2D]) {
+    if node.buffer_indices.len() != 2 {
+        panic!(
+            "nodes::quantized_linear function expected 1 input buffer, received {}",
+            node.buffer_indices.len()
+        );
+    }
+
+    let mut references: Vec<(usize, &mut Tensor2D)> = sorted_mutable_references(node, data_buffers);
+    let mut drain: Drain<(usize, &mut Tensor2D)> = references.drain(0..references.len());
+
+    let input: &Tensor2D = drain.next().unwrap().1;
+    let output: &mut Tensor2D = drain.next().unwrap().1;
+
+    let (weights, bias): (&PackedTensor2D, &PackedTensor2D) = packed_parameters_of(node);
+    PackedTensor2D::linear_preallocated(input, weights, bias, output);
+}
+
+pub fn relu(node: &Node, data_buffers: &mut [Tensor2D], parallel: bool) {
     if node.buffer_indices.len() != 2 {
         panic!(
             "nodes::relu function expected 1 input buffer, received {}",
@@ -112,10 +214,26 @@ pub fn relu(node: &Node, data_buffers: &mut [Tensor2D]) {
     // which has better performance and works on directly on the given input, which has to be mutable.
     // Due to the way the graph is currently setup, this isn't implemented for the CPU graph,
     // but it could be a possible optimization. Wink. Wink.
-    Tensor2D::relu_preallocated(input, output);
+    if parallel {
+        Tensor2D::relu_parallel(input, output);
+    } else {
+        Tensor2D::relu_preallocated(input, output);
+    }
+}
+
+fn axis_of(node: &Node) -> Axis {
+    node.axis.unwrap_or_else(|| {
+        panic!(
+            "nodes::axis_of function received {}, which has no axis",
+            node.name
+        )
+    })
 }
 
-pub fn softmax(node: &Node, data_buffers: &mut [Tensor2D]) {
+// With a batch_size larger than 1 the buffers hold batch_size samples stacked
+// along the rows, and every sample gets its own softmax.
+// Softmax along Axis::Global has its own, parallel, kernels.
+pub fn softmax(node: &Node, data_buffers: &mut [Tensor2D], parallel: bool, batch_size: usize) {
     if node.buffer_indices.len() != 2 {
         panic!(
             "nodes::softmax function expected 1 input buffer, received {}",
@@ -133,10 +251,55 @@ pub fn softmax(node: &Node, data_buffers: &mut [Tensor2D]) {
     // which has better performance and works on directly on the given input, which has to be mutable.
     // Due to the way the graph is currently setup, this isn't implemented for the CPU graph,
     // but it could be a possible optimization. Wink. Wink.
-    Tensor2D::softmax_preallocated(input, output);
+    let axis: Axis = axis_of(node);
+    if axis != Axis::Global {
+        Tensor2D::softmax_along_preallocated(input, output, axis, batch_size);
+        return;
+    }
+
+    match (parallel, 1 < batch_size) {
+        (true, true) => Tensor2D::softmax_batched_parallel(input, output, batch_size),
+        (true, false) => Tensor2D::softmax_parallel(input, output),
+        (false, true) => Tensor2D::softmax_batched_preallocated(input, output, batch_size),
+        (false, false) => Tensor2D::softmax_preallocated(input, output),
+    }
+}
+
+pub fn log_softmax(node: &Node, data_buffers: &mut [Tensor2D], batch_size: usize) {
+    if node.buffer_indices.len() != 2 {
+        panic!(
+            "nodes::log_softmax function expected 1 input buffer, received {}",
+            node.buffer_indices.len()
+        );
+    }
+
+    let mut references: Vec<(usize, &mut Tensor2D)> = sorted_mutable_references(node, data_buffers);
+    let mut drain: Drain<(usize, &mut Tensor2D)> = references.drain(0..references.len());
+
+    let input: &Tensor2D = drain.next().unwrap().1;
+    let output: &mut Tensor2D = drain.next().unwrap().1;
+
+    Tensor2D::log_softmax_along_preallocated(input, output, axis_of(node), batch_size);
+}
+
+pub fn reduce(node: &Node, data_buffers: &mut [Tensor2D], reduction: Reduction, batch_size: usize) {
+    if node.buffer_indices.len() != 2 {
+        panic!(
+            "nodes::reduce function expected 1 input buffer, received {}",
+            node.buffer_indices.len()
+        );
+    }
+
+    let mut references: Vec<(usize, &mut Tensor2D)> = sorted_mutable_references(node, data_buffers);
+    let mut drain: Drain<(usize, &mut Tensor2D)> = references.drain(0..references.len());
+
+    let input: &Tensor2D = drain.next().unwrap().1;
+    let output: &mut Tensor2D = drain.next().unwrap().1;
+
+    Tensor2D::reduce_preallocated(input, output, reduction, axis_of(node), batch_size);
 }
 
-pub fn linear_relu(node: &Node, data_buffers: &mut [Tensor2D]) {
+pub fn linear_relu(node: &Node, data_buffers: &mut [Tensor2D], parallel: bool) {
     if node.buffer_indices.len() != 4 {
         panic!(
             "cpu_nodes::linear_relu function expected 1 input buffer, received {}",
@@ -152,10 +315,19 @@ pub fn linear_relu(node: &Node, data_buffers: &mut [Tensor2D]) {
     let bias: &Tensor2D = drain.next().unwrap().1;
     let output: &mut Tensor2D = drain.next().unwrap().1;
 
-    Tensor2D::linear_local_accumulation_relu(input, weights, bias, output);
+    if parallel {
+        Tensor2D::linear_relu_parallel(input, weights, bias, output);
+    } else {
+        Tensor2D::linear_local_accumulation_relu(input, weights, bias, output);
+    }
 }
 
-pub fn linear_relu_softmax(node: &Node, data_buffers: &mut [Tensor2D]) {
+pub fn linear_relu_softmax(
+    node: &Node,
+    data_buffers: &mut [Tensor2D],
+    parallel: bool,
+    batch_size: usize,
+) {
     if node.buffer_indices.len() != 4 {
         panic!(
             "cpu_nodes::linear_relu_softmax function expected 1 input buffer, received {}",
@@ -171,5 +343,691 @@ pub fn linear_relu_softmax(node: &Node, data_buffers: &mut [Tensor2D]) {
     let bias: &Tensor2D = drain.next().unwrap().1;
     let output: &mut Tensor2D = drain.next().unwrap().1;
 
-    Tensor2D::linear_relu_softmax_fused_fission(input, weights, bias, output);
+    // The fused kernels take the softmax over the whole output,
+    // so a batch runs the linear layer first and then a softmax per sample
+    match (parallel, 1 < batch_size) {
+        (true, true) => {
+            Tensor2D::linear_relu_parallel(input, weights, bias, output);
+            Tensor2D::softmax_batched_inplace_parallel(output, batch_size);
+        }
+        (true, false) => {
+            Tensor2D::linear_relu_softmax_fused_parallel(input, weights, bias, output);
+        }
+        (false, true) => {
+            Tensor2D::linear_local_accumulation_relu(input, weights, bias, output);
+            Tensor2D::softmax_batched_inplace(output, batch_size);
+        }
+        (false, false) => {
+            Tensor2D::linear_relu_softmax_fused_fission(input, weights, bias, output);
+        }
+    }
+}
+
+// The elementwise operators may read the same buffer twice, such as when
+// squaring a tensor by multiplying it with itself, which sorted_mutable_references
+// can't handle. Instead we split the buffers around the output, the last buffer
+// of the node, which is the only buffer we need a mutable reference to.
+fn output_split_references<'a>(
+    node: &Node,
+    data_buffers: &'a mut [Tensor2D],
+) -> (Vec<&'a Tensor2D>, &'a mut Tensor2D) {
+    let (output_index, input_indices): (&usize, &[usize]) = node
+        .buffer_indices
+        .split_last()
+        .expect("nodes::output_split_references function received a node without buffers");
+    let output_index: usize = *output_index;
+    assert!(
+        !input_indices.contains(&output_index),
+        "nodes::output_split_references function can't write to one of the inputs of {}",
+        node.name
+    );
+
+    let (before, rest): (&'a mut [Tensor2D], &'a mut [Tensor2D]) =
+        data_buffers.split_at_mut(output_index);
+    let (output, after): (&'a mut Tensor2D, &'a mut [Tensor2D]) = rest.split_first_mut().unwrap();
+    let before: &'a [Tensor2D] = before;
+    let after: &'a [Tensor2D] = after;
+
+    let inputs: Vec<&'a Tensor2D> = input_indices
+        .iter()
+        .map(|index| {
+            if *index < output_index {
+                &before[*index]
+            } else {
+                &after[*index - output_index - 1]
+            }
+        })
+        .collect();
+
+    (inputs, output)
+}
+
+fn elementwise_references<'a>(
+    node: &Node,
+    data_buffers: &'a mut [Tensor2D],
+) -> (&'a Tensor2D, &'a Tensor2D, &'a mut Tensor2D) {
+    if node.buffer_indices.len() != 3 {
+        panic!(
+            "nodes::elementwise function expected 2 input buffers, received {}",
+            node.buffer_indices.len()
+        );
+    }
+
+    let (inputs, output): (Vec<&Tensor2D>, &mut Tensor2D) =
+        output_split_references(node, data_buffers);
+    (inputs[0], inputs[1], output)
+}
+
+pub fn add(node: &Node, data_buffers: &mut [Tensor2D]) {
+    let (left, right, output): (&Tensor2D, &Tensor2D, &mut Tensor2D) =
+        elementwise_references(node, data_buffers);
+    Tensor2D::add_preallocated(left, right, output);
+}
+
+pub fn subtract(node: &Node, data_buffers: &mut [Tensor2D]) {
+    let (left, right, output): (&Tensor2D, &Tensor2D, &mut Tensor2D) =
+        elementwise_references(node, data_buffers);
+    Tensor2D::subtract_preallocated(left, right, output);
+}
+
+pub fn multiply(node: &Node, data_buffers: &mut [Tensor2D]) {
+    let (left, right, output): (&Tensor2D, &Tensor2D, &mut Tensor2D) =
+        elementwise_references(node, data_buffers);
+    Tensor2D::multiply_preallocated(left, right, output);
+}
+
+pub fn divide(node: &Node, data_buffers: &mut [Tensor2D]) {
+    let (left, right, output): (&Tensor2D, &Tensor2D, &mut Tensor2D) =
+        elementwise_references(node, data_buffers);
+    Tensor2D::divide_preallocated(left, right, output);
+}
+
+fn fusion_of(node: &Node) -> &FusedElementwise {
+    let fusion: &FusedElementwise = node.fusion.as_ref().unwrap_or_else(|| {
+        panic!(
+            "nodes::fused_elementwise function received {}, which has no fusion",
+            node.name
+        )
+    });
+    if node.buffer_indices.len() != fusion.buffer_count() {
+        panic!(
+            "nodes::fused_elementwise function expected {} buffers, received {}",
+            fusion.buffer_count(),
+            node.buffer_indices.len()
+        );
+    }
+    fusion
+}
+
+// The output has the shape the axis operator gives its input
+fn axis_operator_preallocated(
+    operator: AxisOperator,
+    input: &Tensor2D,
+    output: &mut Tensor2D,
+    axis: Axis,
+    batch_size: usize,
+) {
+    match operator.reduction() {
+        Some(reduction) => {
+            Tensor2D::reduce_preallocated(input, output, reduction, axis, batch_size);
+        }
+        None if operator == AxisOperator::Softmax => {
+            Tensor2D::softmax_along_preallocated(input, output, axis, batch_size);
+        }
+        None => Tensor2D::log_softmax_along_preallocated(input, output, axis, batch_size),
+    }
+}
+
+// Computes the base for every element, then applies every step to each element in turn.
+// The operands are read with broadcasting, just like the unfused elementwise operators.
+pub fn fused_elementwise(node: &Node, data_buffers: &mut [Tensor2D], batch_size: usize) {
+    let fusion: &FusedElementwise = fusion_of(node);
+    let (inputs, output): (Vec<&Tensor2D>, &mut Tensor2D) =
+        output_split_references(node, data_buffers);
+    let operands: &[&Tensor2D] = &inputs[fusion.base_buffer_count()..];
+
+    match fusion.base {
+        FusionBase::Linear => Tensor2D::linear_optimized(inputs[0], inputs[1], inputs[2], output),
+        FusionBase::Load => {
+            for row in 0..output.row_count {
+                for column in 0..output.column_count {
+                    output.data[row * output.column_count + column] =
+                        inputs[0].data[inputs[0].broadcast_index(row, column)];
+                }
+            }
+        }
+        FusionBase::Axis { operator, axis } => {
+            axis_operator_preallocated(operator, inputs[0], output, axis, batch_size);
+        }
+    }
+
+    for row in 0..output.row_count {
+        for column in 0..output.column_count {
+            let index: usize = row * output.column_count + column;
+            let mut value: f32 = output.data[index];
+            let mut operand_index: usize = 0;
+            for step in &fusion.steps {
+                let operand: f32 = if step.operator.is_binary() {
+                    let operand: &Tensor2D = operands[operand_index];
+                    operand_index += 1;
+                    operand.data[operand.broadcast_index(row, column)]
+                } else {
+                    0.0
+                };
+                value = step.apply(value, operand);
+            }
+            output.data[index] = value;
+        }
+    }
+}
+
+// Backward pass
+// Each backward function reads the gradient of the node's output from
+// gradient_buffers and adds the gradients of everything the node read from.
+// The gradient buffers mirror the data buffers, so buffer index N holds the
+// gradient of data buffer N. Adding instead of overwriting is what allows
+// a buffer to be read by more than one node.
+fn linear_gradients(
+    node: &Node,
+    data_buffers: &[Tensor2D],
+    output_gradient: &Tensor2D,
+    gradient_buffers: &mut [Tensor2D],
+) {
+    let input_index: usize = node.buffer_indices[0];
+    let weights_index: usize = node.buffer_indices[1];
+    let bias_index: usize = node.buffer_indices[2];
+
+    let input: &Tensor2D = &data_buffers[input_index];
+    let weights: &Tensor2D = &data_buffers[weights_index];
+
+    // input gradient = output gradient * weights^T
+    let input_gradient: &mut Tensor2D = &mut gradient_buffers[input_index];
+    for row in 0..input.row_count {
+        for inner_dimension in 0..input.column_count {
+            let mut result: f32 = 0.0;
+            for column in 0..output_gradient.column_count {
+                result += output_gradient.data[row * output_gradient.column_count + column]
+                    * weights.data[inner_dimension * weights.column_count + column];
+            }
+            input_gradient.data[row * input.column_count + inner_dimension] += result;
+        }
+    }
+
+    // weights gradient = input^T * output gradient
+    let weights_gradient: &mut Tensor2D = &mut gradient_buffers[weights_index];
+    for inner_dimension in 0..weights.row_count {
+        for column in 0..weights.column_count {
+            let mut result: f32 = 0.0;
+            for row in 0..input.row_count {
+                result += input.data[row * input.column_count + inner_dimension]
+                    * output_gradient.data[row * output_gradient.column_count + column];
+            }
+            weights_gradient.data[inner_dimension * weights.column_count + column] += result;
+        }
+    }
+
+    // The bias is the same shape as the output, so its gradient is just the output gradient
+    let bias_gradient: &mut Tensor2D = &mut gradient_buffers[bias_index];
+    for index in 0..output_gradient.len() {
+        bias_gradient.data[index] += output_gradient.data[index];
+    }
+}
+
+// Every output depends on every input in the same segment, which is the whole
+// tensor for Axis::Global.
+// input gradient = output * (output gradient - sum(output gradient * output))
+fn softmax_gradient(output: &Tensor2D, output_gradient: &Tensor2D, axis: Axis) -> Tensor2D {
+    let layout: AxisLayout = AxisLayout::new(output.row_count, output.column_count, axis, 1);
+
+    let mut input_gradient: Tensor2D = Tensor2D::new(0.0, output.row_count, output.column_count);
+    for segment in 0..layout.segment_count {
+        let mut dot: f32 = 0.0;
+        for element in 0..layout.segment_length {
+            let index: usize = layout.index(segment, element);
+            dot += output_gradient.data[index] * output.data[index];
+        }
+
+        for element in 0..layout.segment_length {
+            let index: usize = layout.index(segment, element);
+            input_gradient.data[index] = output.data[index] * (output_gradient.data[index] - dot);
+        }
+    }
+
+    input_gradient
+}
+
+pub fn linear_backward(node: &Node, data_buffers: &[Tensor2D], gradient_buffers: &mut [Tensor2D]) {
+    if node.buffer_indices.len() != 4 {
+        panic!(
+            "nodes::linear_backward function expected 1 input buffer, received {}",
+            node.buffer_indices.len()
+        );
+    }
+
+    let output_gradient: Tensor2D = gradient_buffers[node.buffer_indices[3]].clone();
+    linear_gradients(node, data_buffers, &output_gradient, gradient_buffers);
+}
+
+// The packed parameters are frozen, so only the input gets a gradient.
+// input gradient = output gradient * weights^T, with the weights decoded as they are read
+pub fn quantized_linear_backward(
+    node: &Node,
+    data_buffers: &[Tensor2D],
+    gradient_buffers: &mut [Tensor2D],
+) {
+    if node.buffer_indices.len() != 2 {
+        panic!(
+            "nodes::quantized_linear_backward function expected 1 input buffer, received {}",
+            node.buffer_indices.len()
+        );
+    }
+
+    let input_index: usize = node.buffer_indices[0];
+    let output_gradient: Tensor2D = gradient_buffers[node.buffer_indices[1]].clone();
+    let input: &Tensor2D = &data_buffers[input_index];
+    let (weights, _): (&PackedTensor2D, &PackedTensor2D) = packed_parameters_of(node);
+
+    let input_gradient: &mut Tensor2D = &mut gradient_buffers[input_index];
+    for row in 0..input.row_count {
+        for inner_dimension in 0..input.column_count {
+            let mut result: f32 = 0.0;
+            for column in 0..output_gradient.column_count {
+                result += output_gradient.data[row * output_gradient.column_count + column]
+                    * weights.get(inner_dimension * weights.column_count + column);
+            }
+            input_gradient.data[row * input.column_count + inner_dimension] += result;
+        }
+    }
+}
+
+pub fn relu_backward(node: &Node, data_buffers: &[Tensor2D], gradient_buffers: &mut [Tensor2D]) {
+    if node.buffer_indices.len() != 2 {
+        panic!(
+            "nodes::relu_backward function expected 1 input buffer, received {}",
+            node.buffer_indices.len()
+        );
+    }
+
+    let input: &Tensor2D = &data_buffers[node.buffer_indices[0]];
+    let output_gradient: Tensor2D = gradient_buffers[node.buffer_indices[1]].clone();
+
+    let input_gradient: &mut Tensor2D = &mut gradient_buffers[node.buffer_indices[0]];
+    for index in 0..input.len() {
+        if 0.0 < input.data[index] {
+            input_gradient.data[index] += output_gradient.data[index];
+        }
+    }
+}
+
+pub fn softmax_backward(node: &Node, data_buffers: &[Tensor2D], gradient_buffers: &mut [Tensor2D]) {
+    if node.buffer_indices.len() != 2 {
+        panic!(
+            "nodes::softmax_backward function expected 1 input buffer, received {}",
+            node.buffer_indices.len()
+        );
+    }
+
+    let output: &Tensor2D = &data_buffers[node.buffer_indices[1]];
+    let gradient: Tensor2D = softmax_gradient(
+        output,
+        &gradient_buffers[node.buffer_indices[1]],
+        axis_of(node),
+    );
+
+    let input_gradient: &mut Tensor2D = &mut gradient_buffers[node.buffer_indices[0]];
+    for index in 0..gradient.len() {
+        input_gradient.data[index] += gradient.data[index];
+    }
+}
+
+// The softmax is exp(output), so
+// input gradient = output gradient - softmax * sum(output gradient)
+fn log_softmax_gradient(output: &Tensor2D, output_gradient: &Tensor2D, axis: Axis) -> Tensor2D {
+    let layout: AxisLayout = AxisLayout::new(output.row_count, output.column_count, axis, 1);
+
+    let mut input_gradient: Tensor2D = Tensor2D::new(0.0, output.row_count, output.column_count);
+    for segment in 0..layout.segment_count {
+        let mut sum: f32 = 0.0;
+        for element in 0..layout.segment_length {
+            sum += output_gradient.data[layout.index(segment, element)];
+        }
+
+        for element in 0..layout.segment_length {
+            let index: usize = layout.index(segment, element);
+            input_gradient.data[index] =
+                output_gradient.data[index] - output.data[index].exp() * sum;
+        }
+    }
+
+    input_gradient
+}
+
+pub fn log_softmax_backward(
+    node: &Node,
+    data_buffers: &[Tensor2D],
+    gradient_buffers: &mut [Tensor2D],
+) {
+    if node.buffer_indices.len() != 2 {
+        panic!(
+            "nodes::log_softmax_backward function expected 1 input buffer, received {}",
+            node.buffer_indices.len()
+        );
+    }
+
+    let output: &Tensor2D = &data_buffers[node.buffer_indices[1]];
+    let gradient: Tensor2D = log_softmax_gradient(
+        output,
+        &gradient_buffers[node.buffer_indices[1]],
+        axis_of(node),
+    );
+
+    let input_gradient: &mut Tensor2D = &mut gradient_buffers[node.buffer_indices[0]];
+    for index in 0..gradient.len() {
+        input_gradient.data[index] += gradient.data[index];
+    }
+}
+
+// Sum passes the gradient of a segment on to every element in it, and Mean
+// divides it between them. Max passes it on to the element which was the maximum,
+// the first one if there are several. ArgMax is a step function, so its gradient is 0.
+fn reduce_gradient(
+    input: &Tensor2D,
+    output_gradient: &Tensor2D,
+    reduction: Reduction,
+    axis: Axis,
+) -> Tensor2D {
+    let layout: AxisLayout = AxisLayout::new(input.row_count, input.column_count, axis, 1);
+
+    let mut input_gradient: Tensor2D = Tensor2D::new(0.0, input.row_count, input.column_count);
+    for segment in 0..layout.segment_count {
+        let gradient: f32 = output_gradient.data[segment];
+        match reduction {
+            Reduction::Sum | Reduction::Mean => {
+                let gradient: f32 = if reduction == Reduction::Mean {
+                    gradient / layout.segment_length as f32
+                } else {
+                    gradient
+                };
+                for element in 0..layout.segment_length {
+                    input_gradient.data[layout.index(segment, element)] += gradient;
+                }
+            }
+            Reduction::Max => {
+                let mut max: f32 = f32::NEG_INFINITY;
+                let mut max_index: usize = layout.index(segment, 0);
+                for element in 0..layout.segment_length {
+                    let index: usize = layout.index(segment, element);
+                    if max < input.data[index] {
+                        max = input.data[index];
+                        max_index = index;
+                    }
+                }
+                input_gradient.data[max_index] += gradient;
+            }
+            Reduction::ArgMax => {}
+        }
+    }
+
+    input_gradient
+}
+
+pub fn reduce_backward(
+    node: &Node,
+    data_buffers: &[Tensor2D],
+    gradient_buffers: &mut [Tensor2D],
+    reduction: Reduction,
+) {
+    if node.buffer_indices.len() != 2 {
+        panic!(
+            "nodes::reduce_backward function expected 1 input buffer, received {}",
+            node.buffer_indices.len()
+        );
+    }
+
+    let input: &Tensor2D = &data_buffers[node.buffer_indices[0]];
+    let gradient: Tensor2D = reduce_gradient(
+        input,
+        &gradient_buffers[node.buffer_indices[1]],
+        reduction,
+        axis_of(node),
+    );
+
+    let input_gradient: &mut Tensor2D = &mut gradient_buffers[node.buffer_indices[0]];
+    for index in 0..gradient.len() {
+        input_gradient.data[index] += gradient.data[index];
+    }
+}
+
+pub fn linear_relu_backward(
+    node: &Node,
+    data_buffers: &[Tensor2D],
+    gradient_buffers: &mut [Tensor2D],
+) {
+    if node.buffer_indices.len() != 4 {
+        panic!(
+            "nodes::linear_relu_backward function expected 1 input buffer, received {}",
+            node.buffer_indices.len()
+        );
+    }
+
+    // The output of the ReLU is only positive where its input was positive
+    let output: &Tensor2D = &data_buffers[node.buffer_indices[3]];
+    let mut output_gradient: Tensor2D = gradient_buffers[node.buffer_indices[3]].clone();
+    for index in 0..output.len() {
+        if output.data[index] <= 0.0 {
+            output_gradient.data[index] = 0.0;
+        }
+    }
+
+    linear_gradients(node, data_buffers, &output_gradient, gradient_buffers);
+}
+
+pub fn linear_relu_softmax_backward(
+    node: &Node,
+    data_buffers: &[Tensor2D],
+    gradient_buffers: &mut [Tensor2D],
+) {
+    if node.buffer_indices.len() != 4 {
+        panic!(
+            "nodes::linear_relu_softmax_backward function expected 1 input buffer, received {}",
+            node.buffer_indices.len()
+        );
+    }
+
+    let input: &Tensor2D = &data_buffers[node.buffer_indices[0]];
+    let weights: &Tensor2D = &data_buffers[node.buffer_indices[1]];
+    let bias: &Tensor2D = &data_buffers[node.buffer_indices[2]];
+    let output: &Tensor2D = &data_buffers[node.buffer_indices[3]];
+
+    let mut output_gradient: Tensor2D = softmax_gradient(
+        output,
+        &gradient_buffers[node.buffer_indices[3]],
+        Axis::Global,
+    );
+
+    // The fused node never stored the input to the softmax, so we have to
+    // recompute the linear layer to find out where the ReLU was active.
+    // Trading compute for memory like this is called rematerialization.
+    let mut linear_output: Tensor2D = Tensor2D::new(0.0, bias.row_count, bias.column_count);
+    Tensor2D::linear_optimized(input, weights, bias, &mut linear_output);
+    for index in 0..linear_output.len() {
+        if linear_output.data[index] <= 0.0 {
+            output_gradient.data[index] = 0.0;
+        }
+    }
+
+    linear_gradients(node, data_buffers, &output_gradient, gradient_buffers);
+}
+
+// The gradient of a broadcast input is summed over the dimension it was broadcast along.
+// derivatives returns the partial derivatives of the operator with respect to left and right.
+fn elementwise_backward(
+    node: &Node,
+    data_buffers: &[Tensor2D],
+    gradient_buffers: &mut [Tensor2D],
+    derivatives: fn(f32, f32) -> (f32, f32),
+) {
+    if node.buffer_indices.len() != 3 {
+        panic!(
+            "nodes::elementwise_backward function expected 2 input buffers, received {}",
+            node.buffer_indices.len()
+        );
+    }
+
+    let left_index: usize = node.buffer_indices[0];
+    let right_index: usize = node.buffer_indices[1];
+    let left: &Tensor2D = &data_buffers[left_index];
+    let right: &Tensor2D = &data_buffers[right_index];
+    let output_gradient: Tensor2D = gradient_buffers[node.buffer_indices[2]].clone();
+
+    for row in 0..output_gradient.row_count {
+        let left_row: usize = if left.row_count == 1 { 0 } else { row };
+        let right_row: usize = if right.row_count == 1 { 0 } else { row };
+        for column in 0..output_gradient.column_count {
+            let left_column: usize = if left.column_count == 1 { 0 } else { column };
+            let right_column: usize = if right.column_count == 1 { 0 } else { column };
+            let left_element: usize = left_row * left.column_count + left_column;
+            let right_element: usize = right_row * right.column_count + right_column;
+
+            let (left_derivative, right_derivative): (f32, f32) =
+                derivatives(left.data[left_element], right.data[right_element]);
+            let gradient: f32 = output_gradient.data[row * output_gradient.column_count + column];
+
+            // If both inputs are the same buffer, such as x * x, both contributions
+            // end up in the same gradient buffer, which is exactly what we want.
+            gradient_buffers[left_index].data[left_element] += gradient * left_derivative;
+            gradient_buffers[right_index].data[right_element] += gradient * right_derivative;
+        }
+    }
+}
+
+pub fn add_backward(node: &Node, data_buffers: &[Tensor2D], gradient_buffers: &mut [Tensor2D]) {
+    elementwise_backward(node, data_buffers, gradient_buffers, |_, _| (1.0, 1.0));
+}
+
+pub fn subtract_backward(
+    node: &Node,
+    data_buffers: &[Tensor2D],
+    gradient_buffers: &mut [Tensor2D],
+) {
+    elementwise_backward(node, data_buffers, gradient_buffers, |_, _| (1.0, -1.0));
+}
+
+pub fn multiply_backward(
+    node: &Node,
+    data_buffers: &[Tensor2D],
+    gradient_buffers: &mut [Tensor2D],
+) {
+    elementwise_backward(node, data_buffers, gradient_buffers, |a, b| (b, a));
+}
+
+pub fn divide_backward(node: &Node, data_buffers: &[Tensor2D], gradient_buffers: &mut [Tensor2D]) {
+    elementwise_backward(node, data_buffers, gradient_buffers, |a, b| {
+        (1.0 / b, -a / (b * b))
+    });
+}
+
+// The fused node only stores its final output, so we recompute the value
+// before every step to find the derivatives, then go through the steps backwards.
+pub fn fused_elementwise_backward(
+    node: &Node,
+    data_buffers: &[Tensor2D],
+    gradient_buffers: &mut [Tensor2D],
+) {
+    let fusion: &FusedElementwise = fusion_of(node);
+    let base_buffer_count: usize = fusion.base_buffer_count();
+    let output_index: usize = node.buffer_indices[node.buffer_indices.len() - 1];
+    let operand_indices: &[usize] =
+        &node.buffer_indices[base_buffer_count..node.buffer_indices.len() - 1];
+    let input_index: usize = node.buffer_indices[0];
+    let input: &Tensor2D = &data_buffers[input_index];
+    let output_gradient: Tensor2D = gradient_buffers[output_index].clone();
+    let (row_count, column_count): (usize, usize) =
+        (output_gradient.row_count, output_gradient.column_count);
+
+    let mut base_output: Tensor2D = Tensor2D::new(0.0, row_count, column_count);
+    match fusion.base {
+        FusionBase::Linear => {
+            let weights: &Tensor2D = &data_buffers[node.buffer_indices[1]];
+            let bias: &Tensor2D = &data_buffers[node.buffer_indices[2]];
+            Tensor2D::linear_optimized(input, weights, bias, &mut base_output);
+        }
+        FusionBase::Load => {}
+        FusionBase::Axis { operator, axis } => {
+            axis_operator_preallocated(operator, input, &mut base_output, axis, 1);
+        }
+    }
+
+    let mut base_gradient: Tensor2D = Tensor2D::new(0.0, row_count, column_count);
+    let mut values: Vec<f32> = Vec::<f32>::with_capacity(fusion.steps.len());
+    let mut operand_values: Vec<f32> = Vec::<f32>::with_capacity(fusion.steps.len());
+    for row in 0..row_count {
+        for column in 0..column_count {
+            let index: usize = row * column_count + column;
+            let mut value: f32 = match fusion.base {
+                FusionBase::Linear | FusionBase::Axis { .. } => base_output.data[index],
+                FusionBase::Load => input.data[input.broadcast_index(row, column)],
+            };
+
+            values.clear();
+            operand_values.clear();
+            let mut operand_index: usize = 0;
+            for step in &fusion.steps {
+                let operand: f32 = if step.operator.is_binary() {
+                    let operand: &Tensor2D = &data_buffers[operand_indices[operand_index]];
+                    operand_index += 1;
+                    operand.data[operand.broadcast_index(row, column)]
+                } else {
+                    0.0
+                };
+                values.push(value);
+                operand_values.push(operand);
+                value = step.apply(value, operand);
+            }
+
+            let mut gradient: f32 = output_gradient.data[index];
+            for (step_index, step) in fusion.steps.iter().enumerate().rev() {
+                let (value_derivative, operand_derivative): (f32, f32) =
+                    step.derivatives(values[step_index], operand_values[step_index]);
+                if step.operator.is_binary() {
+                    operand_index -= 1;
+                    let operand_buffer: usize = operand_indices[operand_index];
+                    let operand_element: usize =
+                        data_buffers[operand_buffer].broadcast_index(row, column);
+                    gradient_buffers[operand_buffer].data[operand_element] +=
+                        gradient * operand_derivative;
+                }
+                gradient *= value_derivative;
+            }
+            base_gradient.data[index] = gradient;
+        }
+    }
+
+    match fusion.base {
+        FusionBase::Linear => {
+            linear_gradients(node, data_buffers, &base_gradient, gradient_buffers);
+        }
+        FusionBase::Load => {
+            let input_gradient: &mut Tensor2D = &mut gradient_buffers[input_index];
+            for row in 0..row_count {
+                for column in 0..column_count {
+                    input_gradient.data[input.broadcast_index(row, column)] +=
+                        base_gradient.data[row * column_count + column];
+                }
+            }
+        }
+        FusionBase::Axis { operator, axis } => {
+            let gradient: Tensor2D = match operator.reduction() {
+                Some(reduction) => reduce_gradient(input, &base_gradient, reduction, axis),
+                None if operator == AxisOperator::Softmax => {
+                    softmax_gradient(&base_output, &base_gradient, axis)
+                }
+                None => log_softmax_gradient(&base_output, &base_gradient, axis),
+            };
+            let input_gradient: &mut Tensor2D = &mut gradient_buffers[input_index];
+            for index in 0..gradient.len() {
+                input_gradient.data[index] += gradient.data[index];
+            }
+        }
+    }
 }
@@ -6,9 +6,23 @@ use wgpu::{
     ShaderModule,
 };
 
-use crate::shared::{
-    gpu_utilities::{create_bind_group, create_compute_pipeline, create_shader_module, GPUHandles},
-    tensor2d_gpu::{LinearUniform, ReluUniform, SoftmaxUniform, Tensor2DGPU},
+use crate::{
+    op_code_compiler::runner::{compile_axis_shader, compile_fused_elementwise_shader},
+    shared::{
+        gpu_utilities::{
+            create_bind_group, create_compute_pipeline, create_shader_module, GPUHandles,
+        },
+        tensor2d_axis::{Axis, AxisLayout},
+        tensor2d_gpu::{
+            AxisUniform, ElementwiseUniform, FusedElementwiseUniform, LinearUniform,
+            PackedLinearUniform, PackedTensor2DGPU, ReluUniform, SoftmaxUniform, Tensor2DGPU,
+        },
+    },
+};
+
+use super::{
+    fusion::{FusedElementwise, FusionBase},
+    linear_tuning::LinearKernel,
 };
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -21,6 +35,17 @@ pub enum NodeOperatorGPU {
     Softmax,
     LinearReLU,
     LinearReLUSoftmax,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    FusedElementwise,
+    LogSoftmax,
+    Sum,
+    Max,
+    Mean,
+    ArgMax,
+    QuantizedLinear,
 }
 
 #[derive(Debug)]
@@ -28,6 +53,13 @@ pub struct NodeGPU {
     pub name: String,
     pub operator: NodeOperatorGPU,
     pub buffer_indices: Vec<usize>,
+    // Only set for FusedElementwise nodes
+    pub fusion: Option<FusedElementwise>,
+    // Only set for Softmax, LogSoftmax and the reductions
+    pub axis: Option<Axis>,
+    // Only set for QuantizedLinear nodes. The weights and bias stay packed
+    // and aren't data buffers, as they are neither batched nor trained.
+    pub packed_parameters: Option<(PackedTensor2DGPU, PackedTensor2DGPU)>,
 }
 
 impl NodeGPU {
@@ -36,23 +68,70 @@ impl NodeGPU {
             name,
             operator,
             buffer_indices,
+            fusion: None,
+            axis: None,
+            packed_parameters: None,
+        }
+    }
+
+    pub fn along(
+        name: String,
+        operator: NodeOperatorGPU,
+        buffer_indices: Vec<usize>,
+        axis: Axis,
+    ) -> Self {
+        NodeGPU {
+            name,
+            operator,
+            buffer_indices,
+            fusion: None,
+            axis: Some(axis),
+            packed_parameters: None,
+        }
+    }
+
+    pub fn fused(name: String, buffer_indices: Vec<usize>, fusion: FusedElementwise) -> Self {
+        NodeGPU {
+            name,
+            operator: NodeOperatorGPU::FusedElementwise,
+            buffer_indices,
+            fusion: Some(fusion),
+            axis: None,
+            packed_parameters: None,
+        }
+    }
+
+    pub fn quantized_linear(
+        name: String,
+        buffer_indices: Vec<usize>,
+        weights: PackedTensor2DGPU,
+        bias: PackedTensor2DGPU,
+    ) -> Self {
+        NodeGPU {
+            name,
+            operator: NodeOperatorGPU::QuantizedLinear,
+            buffer_indices,
+            fusion: None,
+            axis: None,
+            packed_parameters: Some((weights, bias)),
         }
     }
 }
 
 // Linear Layer
+// linear_kernel decides which shader the Linear and LinearReLU entries are compiled from,
+// see graph::linear_tuning.
 pub fn build_linear_elements(
     gpu_handles: &GPUHandles,
     shader_cache: &mut HashMap<String, ShaderModule>,
     pipeline_cache: &mut HashMap<String, ComputePipeline>,
     use_fused_with_relu: bool,
+    linear_kernel: &LinearKernel,
 ) {
     let key: String = "Linear".to_string();
 
-    let cs_module: ShaderModule = create_shader_module(
-        gpu_handles,
-        include_str!("../shared/shaders/linear.wgsl"),
-    );
+    let cs_module: ShaderModule =
+        create_shader_module(gpu_handles, &linear_kernel.shader_source());
 
     let entry_point: &str = "main";
     let compute_pipeline: ComputePipeline =
@@ -62,10 +141,8 @@ pub fn build_linear_elements(
     pipeline_cache.insert(key, compute_pipeline);
 
     if use_fused_with_relu {
-        let cs_module: ShaderModule = create_shader_module(
-            gpu_handles,
-            include_str!("../shared/shaders/linear.wgsl"),
-        );
+        let cs_module: ShaderModule =
+            create_shader_module(gpu_handles, &linear_kernel.shader_source());
         let key: String = "LinearReLU".to_string();
         let entry_point: &str = "main_with_relu";
         let compute_pipeline: ComputePipeline =
@@ -85,6 +162,7 @@ pub fn linear(
     data_buffers: &[Tensor2DGPU],
     encoder: &mut CommandEncoder,
     use_fused_with_relu: bool,
+    linear_kernel: &LinearKernel,
 ) {
     if node.buffer_indices.len() != 4 {
         panic!(
@@ -101,9 +179,8 @@ pub fn linear(
     // Normally these would be right next to the lines where they are used
     // but this section is based on user input and can cause errors.
     // It is placed here for visibility.
-    let block_size: usize = 8;
-    let launch_blocks_x: u32 = ((output.row_count + block_size - 1) / block_size) as u32;
-    let launch_blocks_y: u32 = ((output.column_count + block_size - 1) / block_size) as u32;
+    let (launch_blocks_x, launch_blocks_y): (u32, u32) =
+        linear_kernel.workgroup_count(output.row_count, output.column_count);
 
     let uniform_device: LinearUniform = LinearUniform::from_tensor_2d_gpu(
         gpu_handles,
@@ -117,10 +194,7 @@ pub fn linear(
     let shader_module: Option<ShaderModule> = if use_cache {
         None
     } else {
-        Some(create_shader_module(
-            gpu_handles,
-            include_str!("../shared/shaders/linear.wgsl"),
-        ))
+        Some(create_shader_module(gpu_handles, &linear_kernel.shader_source()))
     };
 
     let cs_module: &ShaderModule = if use_cache {
@@ -194,6 +268,128 @@ pub fn linear(
     }
 }
 
+// Quantized Linear Layer
+// The weights and bias are read from their packed buffers and decoded
+// in the shader, there is only the one kernel.
+pub fn build_quantized_linear_elements(
+    gpu_handles: &GPUHandles,
+    shader_cache: &mut HashMap<String, ShaderModule>,
+    pipeline_cache: &mut HashMap<String, ComputePipeline>,
+) {
+    let key: String = "QuantizedLinear".to_string();
+
+    let cs_module: ShaderModule = create_shader_module(
+        gpu_handles,
+        include_str!("../shared/shaders/linear_packed.wgsl"),
+    );
+
+    let entry_point: &str = "main";
+    let compute_pipeline: ComputePipeline =
+        create_compute_pipeline(gpu_handles, &cs_module, entry_point);
+
+    shader_cache.insert(key.clone(), cs_module);
+    pipeline_cache.insert(key, compute_pipeline);
+}
+
+pub fn quantized_linear(
+    gpu_handles: &GPUHandles,
+    use_cache: bool,
+    shader_cache: &HashMap<String, ShaderModule>,
+    pipeline_cache: &HashMap<String, ComputePipeline>,
+    node: &NodeGPU,
+    data_buffers: &[Tensor2DGPU],
+    encoder: &mut CommandEncoder,
+) {
+    if node.buffer_indices.len() != 2 {
+        panic!(
+            "nodes::quantized_linear function expected 1 input buffer, received {}",
+            node.buffer_indices.len()
+        );
+    }
+
+    let input: &Tensor2DGPU = &data_buffers[node.buffer_indices[0]];
+    let output: &Tensor2DGPU = &data_buffers[node.buffer_indices[1]];
+    let (weights, bias): (&PackedTensor2DGPU, &PackedTensor2DGPU) = match &node.packed_parameters {
+        Some((weights, bias)) => (weights, bias),
+        None => panic!(
+            "nodes::quantized_linear function received {}, which has no packed parameters",
+            node.name
+        ),
+    };
+
+    let block_size: usize = 8;
+    let launch_blocks_x: u32 = output.row_count.div_ceil(block_size) as u32;
+    let launch_blocks_y: u32 = output.column_count.div_ceil(block_size) as u32;
+
+    let uniform: PackedLinearUniform = PackedLinearUniform::new(
+        gpu_handles,
+        "Quantized Linear Layer Uniform",
+        input,
+        weights,
+        bias,
+        output,
+    );
+
+    let shader_module: Option<ShaderModule> = if use_cache {
+        None
+    } else {
+        Some(create_shader_module(
+            gpu_handles,
+            include_str!("../shared/shaders/linear_packed.wgsl"),
+        ))
+    };
+
+    let key: &str = "QuantizedLinear";
+    let cs_module: &ShaderModule = if use_cache {
+        if shader_cache.contains_key(key) {
+            &shader_cache[key]
+        } else {
+            panic!("Tried to get a cached {} shader in graph::nodes::quantized_linear(), but failed to find it in the shader cache!", key);
+        }
+    } else {
+        shader_module.as_ref().expect(
+            "Failed to get a reference to compute shader module in graph::nodes::quantized_linear",
+        )
+    };
+
+    let pipeline: Option<ComputePipeline> = if use_cache {
+        None
+    } else {
+        Some(create_compute_pipeline(gpu_handles, cs_module, "main"))
+    };
+    let compute_pipeline: &ComputePipeline = if use_cache {
+        if pipeline_cache.contains_key(key) {
+            &pipeline_cache[key]
+        } else {
+            panic!("Tried to get a cached {} pipeline in graph::nodes::quantized_linear(), but failed to find it in the pipeline cache!", key);
+        }
+    } else {
+        pipeline.as_ref().expect(
+            "Failed to get a reference to compute pipeline in graph::nodes::quantized_linear",
+        )
+    };
+
+    let bind_group_layout: BindGroupLayout = compute_pipeline.get_bind_group_layout(0);
+    let to_be_bound: Vec<(u32, BindingResource)> = vec![
+        (0, uniform.storage_buffer.as_entire_binding()),
+        (1, input.storage_buffer.as_entire_binding()),
+        (2, weights.storage_buffer.as_entire_binding()),
+        (3, bias.storage_buffer.as_entire_binding()),
+        (4, output.storage_buffer.as_entire_binding()),
+    ];
+    let bind_group: BindGroup = create_bind_group(gpu_handles, &bind_group_layout, to_be_bound);
+
+    {
+        let mut cpass: ComputePass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("quantized_linear_graph"),
+        });
+        cpass.set_pipeline(compute_pipeline);
+        cpass.set_bind_group(0, &bind_group, &[]);
+        cpass.insert_debug_marker("quantized_linear_graph");
+        cpass.dispatch_workgroups(launch_blocks_x, launch_blocks_y, 1);
+    }
+}
+
 // ReLU
 pub fn build_relu_elements(
     gpu_handles: &GPUHandles,
@@ -334,6 +530,9 @@ pub fn build_softmax_elements(
     shader_cache.insert("Softmax".to_string(), cs_module);
 }
 
+// With a batch_size larger than 1 the buffers hold batch_size samples stacked
+// along the rows. Every sample gets its own softmax, with one workgroup per
+// sample in the reductions, so the whole batch is still a single dispatch per pass.
 pub fn softmax(
     gpu_handles: &GPUHandles,
     use_cache: bool,
@@ -342,6 +541,7 @@ pub fn softmax(
     node: &NodeGPU,
     data_buffers: &[Tensor2DGPU],
     encoder: &mut CommandEncoder,
+    batch_size: usize,
 ) {
     if node.buffer_indices.len() != 2 {
         panic!(
@@ -353,10 +553,16 @@ pub fn softmax(
     let input: &Tensor2DGPU = &data_buffers[node.buffer_indices[0]];
     let output: &Tensor2DGPU = &data_buffers[node.buffer_indices[1]];
 
-    let uniform: SoftmaxUniform = SoftmaxUniform::new(gpu_handles, "Softmax Uniform", input.len());
-    let global_max: Tensor2DGPU = Tensor2DGPU::new(gpu_handles, "Softmax Global Max", 0.0, 1, 1);
+    let uniform: SoftmaxUniform = SoftmaxUniform::segmented(
+        gpu_handles,
+        "Softmax Uniform",
+        input.len(),
+        input.len() / batch_size,
+    );
+    let global_max: Tensor2DGPU =
+        Tensor2DGPU::new(gpu_handles, "Softmax Global Max", 0.0, batch_size, 1);
     let global_offset: Tensor2DGPU =
-        Tensor2DGPU::new(gpu_handles, "Softmax Global Offset", 0.0, 1, 1);
+        Tensor2DGPU::new(gpu_handles, "Softmax Global Offset", 0.0, batch_size, 1);
 
     let shader_module: Option<ShaderModule> = if use_cache {
         None
@@ -419,7 +625,7 @@ pub fn softmax(
         cpass.set_pipeline(max_compute_pipeline);
         cpass.set_bind_group(0, &max_bind_group, &[]);
         cpass.insert_debug_marker("Softmax Immediate - Max");
-        cpass.dispatch_workgroups(1, 1, 1); // Number of cells to run, the (x,y,z) size of item being processed
+        cpass.dispatch_workgroups(batch_size as u32, 1, 1); // Number of cells to run, the (x,y,z) size of item being processed
     }
 
     let to_be_bound: Vec<(u32, BindingResource)> = vec![
@@ -461,7 +667,7 @@ pub fn softmax(
         cpass.set_pipeline(sum_compute_pipeline);
         cpass.set_bind_group(0, &sum_bind_group, &[]);
         cpass.insert_debug_marker("Softmax Immediate - Sum");
-        cpass.dispatch_workgroups(1, 1, 1); // Number of cells to run, the (x,y,z) size of item being processed
+        cpass.dispatch_workgroups(batch_size as u32, 1, 1); // Number of cells to run, the (x,y,z) size of item being processed
     }
 
     let to_be_bound: Vec<(u32, BindingResource)> = vec![
@@ -503,7 +709,7 @@ pub fn softmax(
         cpass.set_pipeline(map_compute_pipeline);
         cpass.set_bind_group(0, &map_bind_group, &[]);
         cpass.insert_debug_marker("Softmax Immediate - Map");
-        cpass.dispatch_workgroups(((input.len() + block_size - 1) / block_size) as u32, 1, 1);
+        cpass.dispatch_workgroups(input.len().div_ceil(block_size) as u32, 1, 1);
         // Number of cells to run, the (x,y,z) size of item being processed
     }
 }
@@ -517,6 +723,8 @@ pub fn linear_relu_softmax(
     node: &NodeGPU,
     data_buffers: &[Tensor2DGPU],
     encoder: &mut CommandEncoder,
+    batch_size: usize,
+    linear_kernel: &LinearKernel,
 ) {
     if node.buffer_indices.len() != 4 {
         panic!(
@@ -538,11 +746,8 @@ pub fn linear_relu_softmax(
         bias.column_count,
     );
 
-    let linear_block_size: usize = 8;
-    let linear_launch_blocks_x: u32 =
-        ((intermediate.row_count + linear_block_size - 1) / linear_block_size) as u32;
-    let linear_launch_blocks_y: u32 =
-        ((intermediate.column_count + linear_block_size - 1) / linear_block_size) as u32;
+    let (linear_launch_blocks_x, linear_launch_blocks_y): (u32, u32) =
+        linear_kernel.workgroup_count(intermediate.row_count, intermediate.column_count);
 
     let linear_uniform: LinearUniform = LinearUniform::from_tensor_2d_gpu(
         gpu_handles,
@@ -552,20 +757,21 @@ pub fn linear_relu_softmax(
         bias,
         &intermediate,
     );
-    let softmax_uniform: SoftmaxUniform =
-        SoftmaxUniform::new(gpu_handles, "Softmax Uniform", intermediate.len());
+    let softmax_uniform: SoftmaxUniform = SoftmaxUniform::segmented(
+        gpu_handles,
+        "Softmax Uniform",
+        intermediate.len(),
+        intermediate.len() / batch_size,
+    );
     let softmax_global_max: Tensor2DGPU =
-        Tensor2DGPU::new(gpu_handles, "Softmax Global Max", 0.0, 1, 1);
+        Tensor2DGPU::new(gpu_handles, "Softmax Global Max", 0.0, batch_size, 1);
     let softmax_global_offset: Tensor2DGPU =
-        Tensor2DGPU::new(gpu_handles, "Softmax Global Offset", 0.0, 1, 1);
+        Tensor2DGPU::new(gpu_handles, "Softmax Global Offset", 0.0, batch_size, 1);
 
     let linear_shader_module: Option<ShaderModule> = if use_cache {
         None
     } else {
-        Some(create_shader_module(
-            gpu_handles,
-            include_str!("../shared/shaders/linear.wgsl"),
-        ))
+        Some(create_shader_module(gpu_handles, &linear_kernel.shader_source()))
     };
 
     let linear_cs_module: &ShaderModule = if use_cache {
@@ -684,7 +890,7 @@ pub fn linear_relu_softmax(
         cpass.set_pipeline(max_compute_pipeline);
         cpass.set_bind_group(0, &max_bind_group, &[]);
         cpass.insert_debug_marker("Softmax Immediate - Max");
-        cpass.dispatch_workgroups(1, 1, 1); // Number of cells to run, the (x,y,z) size of item being processed
+        cpass.dispatch_workgroups(batch_size as u32, 1, 1); // Number of cells to run, the (x,y,z) size of item being processed
     }
 
     {
@@ -707,7 +913,7 @@ pub fn linear_relu_softmax(
         cpass.set_pipeline(&sum_compute_pipeline);
         cpass.set_bind_group(0, &sum_bind_group, &[]);
         cpass.insert_debug_marker("Softmax Immediate - Sum");
-        cpass.dispatch_workgroups(1, 1, 1); // Number of cells to run, the (x,y,z) size of item being processed
+        cpass.dispatch_workgroups(batch_size as u32, 1, 1); // Number of cells to run, the (x,y,z) size of item being processed
     }
 
     let block_size: usize = 32;
@@ -752,9 +958,418 @@ pub fn linear_relu_softmax(
         cpass.set_bind_group(0, &map_bind_group, &[]);
         cpass.insert_debug_marker("Softmax Immediate - Map");
         cpass.dispatch_workgroups(
-            ((intermediate.len() + block_size - 1) / block_size) as u32,
+            intermediate.len().div_ceil(block_size) as u32,
             1,
             1,
         ); // Number of cells to run, the (x,y,z) size of item being processed
     }
 }
+
+// Elementwise
+// All four operators live in the same shader, each with its own entry point.
+// The pipelines are cached under the name of the operator.
+fn elementwise_entry_point(operator: &NodeOperatorGPU) -> (&'static str, &'static str) {
+    match operator {
+        NodeOperatorGPU::Add => ("Add", "add"),
+        NodeOperatorGPU::Subtract => ("Subtract", "subtract"),
+        NodeOperatorGPU::Multiply => ("Multiply", "multiply"),
+        NodeOperatorGPU::Divide => ("Divide", "divide"),
+        _ => panic!(
+            "{:?} is not an elementwise operator in graph::nodes::elementwise_entry_point()",
+            operator
+        ),
+    }
+}
+
+pub fn build_elementwise_elements(
+    gpu_handles: &GPUHandles,
+    shader_cache: &mut HashMap<String, ShaderModule>,
+    pipeline_cache: &mut HashMap<String, ComputePipeline>,
+) {
+    let key: String = "Elementwise".to_string();
+
+    let cs_module: ShaderModule = create_shader_module(
+        gpu_handles,
+        include_str!("../shared/shaders/elementwise.wgsl"),
+    );
+
+    let operators: [NodeOperatorGPU; 4] = [
+        NodeOperatorGPU::Add,
+        NodeOperatorGPU::Subtract,
+        NodeOperatorGPU::Multiply,
+        NodeOperatorGPU::Divide,
+    ];
+    for operator in &operators {
+        let (pipeline_key, entry_point): (&str, &str) = elementwise_entry_point(operator);
+        let compute_pipeline: ComputePipeline =
+            create_compute_pipeline(gpu_handles, &cs_module, entry_point);
+        pipeline_cache.insert(pipeline_key.to_string(), compute_pipeline);
+    }
+
+    shader_cache.insert(key, cs_module);
+}
+
+pub fn elementwise(
+    gpu_handles: &GPUHandles,
+    use_cache: bool,
+    shader_cache: &HashMap<String, ShaderModule>,
+    pipeline_cache: &HashMap<String, ComputePipeline>,
+    node: &NodeGPU,
+    data_buffers: &[Tensor2DGPU],
+    encoder: &mut CommandEncoder,
+) {
+    if node.buffer_indices.len() != 3 {
+        panic!(
+            "nodes::elementwise function expected 2 input buffers, received {}",
+            node.buffer_indices.len()
+        );
+    }
+
+    // Unlike the CPU version, reading the same buffer as both left and right is fine
+    // as we only need shared references to the buffers.
+    let left: &Tensor2DGPU = &data_buffers[node.buffer_indices[0]];
+    let right: &Tensor2DGPU = &data_buffers[node.buffer_indices[1]];
+    let output: &Tensor2DGPU = &data_buffers[node.buffer_indices[2]];
+
+    let (pipeline_key, entry_point): (&str, &str) = elementwise_entry_point(&node.operator);
+
+    let block_size: usize = 32;
+    let launch_blocks_x: u32 = (output.row_count * output.column_count).div_ceil(block_size) as u32;
+
+    let uniform: ElementwiseUniform =
+        ElementwiseUniform::new(gpu_handles, "Elementwise Uniform", left, right, output);
+
+    let shader_module: Option<ShaderModule> = if use_cache {
+        None
+    } else {
+        Some(create_shader_module(
+            gpu_handles,
+            include_str!("../shared/shaders/elementwise.wgsl"),
+        ))
+    };
+
+    let cs_module: &ShaderModule = if use_cache {
+        let key: &str = "Elementwise";
+        if shader_cache.contains_key(key) {
+            &shader_cache[key]
+        } else {
+            panic!("Tried to get a cached {} shader in graph::nodes::elementwise(), but failed to find it in the shader cache!", key);
+        }
+    } else {
+        shader_module.as_ref().expect(
+            "Failed to get a reference to compute shader module in graph::nodes::elementwise",
+        )
+    };
+
+    let pipeline: Option<ComputePipeline> = if use_cache {
+        None
+    } else {
+        Some(create_compute_pipeline(gpu_handles, cs_module, entry_point))
+    };
+    let compute_pipeline: &ComputePipeline = if use_cache {
+        if pipeline_cache.contains_key(pipeline_key) {
+            &pipeline_cache[pipeline_key]
+        } else {
+            panic!("Tried to get a cached {} pipeline in graph::nodes::elementwise(), but failed to find it in the pipeline cache!", pipeline_key);
+        }
+    } else {
+        pipeline
+            .as_ref()
+            .expect("Failed to get a reference to compute pipeline in graph::nodes::elementwise")
+    };
+
+    let bind_group_layout: BindGroupLayout = compute_pipeline.get_bind_group_layout(0);
+    let to_be_bound: Vec<(u32, BindingResource)> = vec![
+        (0, uniform.storage_buffer.as_entire_binding()),
+        (1, left.storage_buffer.as_entire_binding()),
+        (2, right.storage_buffer.as_entire_binding()),
+        (3, output.storage_buffer.as_entire_binding()),
+    ];
+    let bind_group: BindGroup = create_bind_group(gpu_handles, &bind_group_layout, to_be_bound);
+
+    {
+        let mut cpass: ComputePass =
+            encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+        cpass.set_pipeline(compute_pipeline);
+        cpass.set_bind_group(0, &bind_group, &[]);
+        cpass.insert_debug_marker("Elementwise Graph");
+        cpass.dispatch_workgroups(launch_blocks_x, 1, 1);
+    }
+}
+
+// Axis
+// Softmax along the rows or the columns, LogSoftmax and the reductions all live
+// in the same shader, with one invocation per segment of the axis. The shader is
+// generated by the op_code_compiler, and is the same as shared::shaders::axis.wgsl.
+// Softmax over the whole tensor keeps using the multi-pass softmax shader.
+fn axis_entry_point(operator: &NodeOperatorGPU) -> (&'static str, &'static str) {
+    match operator {
+        NodeOperatorGPU::Softmax => ("AxisSoftmax", "softmax"),
+        NodeOperatorGPU::LogSoftmax => ("LogSoftmax", "log_softmax"),
+        NodeOperatorGPU::Sum => ("Sum", "reduce_sum"),
+        NodeOperatorGPU::Max => ("Max", "reduce_max"),
+        NodeOperatorGPU::Mean => ("Mean", "reduce_mean"),
+        NodeOperatorGPU::ArgMax => ("ArgMax", "reduce_argmax"),
+        _ => panic!(
+            "{:?} is not an axis operator in graph::nodes::axis_entry_point()",
+            operator
+        ),
+    }
+}
+
+pub fn build_axis_elements(
+    gpu_handles: &GPUHandles,
+    shader_cache: &mut HashMap<String, ShaderModule>,
+    pipeline_cache: &mut HashMap<String, ComputePipeline>,
+) {
+    let key: String = "Axis".to_string();
+
+    let cs_module: ShaderModule = compile_axis_shader(gpu_handles);
+
+    let operators: [NodeOperatorGPU; 6] = [
+        NodeOperatorGPU::Softmax,
+        NodeOperatorGPU::LogSoftmax,
+        NodeOperatorGPU::Sum,
+        NodeOperatorGPU::Max,
+        NodeOperatorGPU::Mean,
+        NodeOperatorGPU::ArgMax,
+    ];
+    for operator in &operators {
+        let (pipeline_key, entry_point): (&str, &str) = axis_entry_point(operator);
+        let compute_pipeline: ComputePipeline =
+            create_compute_pipeline(gpu_handles, &cs_module, entry_point);
+        pipeline_cache.insert(pipeline_key.to_string(), compute_pipeline);
+    }
+
+    shader_cache.insert(key, cs_module);
+}
+
+// With a batch_size larger than 1 the buffers hold batch_size samples stacked
+// along the rows, and the axis is relative to a single sample.
+pub fn axis_operator(
+    gpu_handles: &GPUHandles,
+    use_cache: bool,
+    shader_cache: &HashMap<String, ShaderModule>,
+    pipeline_cache: &HashMap<String, ComputePipeline>,
+    node: &NodeGPU,
+    data_buffers: &[Tensor2DGPU],
+    encoder: &mut CommandEncoder,
+    batch_size: usize,
+) {
+    if node.buffer_indices.len() != 2 {
+        panic!(
+            "nodes::axis_operator function expected 1 input buffer, received {}",
+            node.buffer_indices.len()
+        );
+    }
+
+    let axis: Axis = node.axis.unwrap_or_else(|| {
+        panic!(
+            "graph::nodes::axis_operator() got the node {} without an axis",
+            node.name
+        )
+    });
+    let input: &Tensor2DGPU = &data_buffers[node.buffer_indices[0]];
+    let output: &Tensor2DGPU = &data_buffers[node.buffer_indices[1]];
+
+    let (pipeline_key, entry_point): (&str, &str) = axis_entry_point(&node.operator);
+
+    let layout: AxisLayout = AxisLayout::new(input.row_count, input.column_count, axis, batch_size);
+    let block_size: usize = 32;
+    let launch_blocks_x: u32 = layout.segment_count.div_ceil(block_size) as u32;
+
+    let uniform: AxisUniform = AxisUniform::new(gpu_handles, "Axis Uniform", &layout);
+
+    let shader_module: Option<ShaderModule> = if use_cache {
+        None
+    } else {
+        Some(compile_axis_shader(gpu_handles))
+    };
+
+    let cs_module: &ShaderModule = if use_cache {
+        let key: &str = "Axis";
+        if shader_cache.contains_key(key) {
+            &shader_cache[key]
+        } else {
+            panic!("Tried to get a cached {} shader in graph::nodes::axis_operator(), but failed to find it in the shader cache!", key);
+        }
+    } else {
+        shader_module.as_ref().expect(
+            "Failed to get a reference to compute shader module in graph::nodes::axis_operator",
+        )
+    };
+
+    let pipeline: Option<ComputePipeline> = if use_cache {
+        None
+    } else {
+        Some(create_compute_pipeline(gpu_handles, cs_module, entry_point))
+    };
+    let compute_pipeline: &ComputePipeline = if use_cache {
+        if pipeline_cache.contains_key(pipeline_key) {
+            &pipeline_cache[pipeline_key]
+        } else {
+            panic!("Tried to get a cached {} pipeline in graph::nodes::axis_operator(), but failed to find it in the pipeline cache!", pipeline_key);
+        }
+    } else {
+        pipeline
+            .as_ref()
+            .expect("Failed to get a reference to compute pipeline in graph::nodes::axis_operator")
+    };
+
+    let bind_group_layout: BindGroupLayout = compute_pipeline.get_bind_group_layout(0);
+    let to_be_bound: Vec<(u32, BindingResource)> = vec![
+        (0, uniform.storage_buffer.as_entire_binding()),
+        (1, input.storage_buffer.as_entire_binding()),
+        (2, output.storage_buffer.as_entire_binding()),
+    ];
+    let bind_group: BindGroup = create_bind_group(gpu_handles, &bind_group_layout, to_be_bound);
+
+    {
+        let mut cpass: ComputePass =
+            encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+        cpass.set_pipeline(compute_pipeline);
+        cpass.set_bind_group(0, &bind_group, &[]);
+        cpass.insert_debug_marker("Axis Graph");
+        cpass.dispatch_workgroups(launch_blocks_x, 1, 1);
+    }
+}
+
+// Fused Elementwise
+// Unlike the other operators, the shaders are generated by the op_code_compiler,
+// one for every combination of base and steps, so they can't all be built up front.
+// Instead the graph runner builds them once it has found the fused nodes.
+pub fn build_fused_elementwise_elements(
+    gpu_handles: &GPUHandles,
+    shader_cache: &mut HashMap<String, ShaderModule>,
+    pipeline_cache: &mut HashMap<String, ComputePipeline>,
+    fusion: &FusedElementwise,
+) {
+    let key: String = fusion.key();
+    if shader_cache.contains_key(&key) {
+        return;
+    }
+
+    let cs_module: ShaderModule = compile_fused_elementwise_shader(gpu_handles, fusion);
+
+    let entry_point: &str = "main";
+    let compute_pipeline: ComputePipeline =
+        create_compute_pipeline(gpu_handles, &cs_module, entry_point);
+
+    shader_cache.insert(key.clone(), cs_module);
+    pipeline_cache.insert(key, compute_pipeline);
+}
+
+// With a batch_size larger than 1 the buffers hold batch_size samples stacked
+// along the rows, which only matters to an axis base, whose axis is relative to a single sample.
+pub fn fused_elementwise(
+    gpu_handles: &GPUHandles,
+    use_cache: bool,
+    shader_cache: &HashMap<String, ShaderModule>,
+    pipeline_cache: &HashMap<String, ComputePipeline>,
+    node: &NodeGPU,
+    data_buffers: &[Tensor2DGPU],
+    encoder: &mut CommandEncoder,
+    batch_size: usize,
+) {
+    let fusion: &FusedElementwise = node.fusion.as_ref().unwrap_or_else(|| {
+        panic!(
+            "nodes::fused_elementwise function received {}, which has no fusion",
+            node.name
+        )
+    });
+    if node.buffer_indices.len() != fusion.buffer_count() {
+        panic!(
+            "nodes::fused_elementwise function expected {} buffers, received {}",
+            fusion.buffer_count(),
+            node.buffer_indices.len()
+        );
+    }
+
+    let tensors: Vec<&Tensor2DGPU> = node
+        .buffer_indices
+        .iter()
+        .map(|index| &data_buffers[*index])
+        .collect();
+    let output: &Tensor2DGPU = tensors[tensors.len() - 1];
+
+    // The generated shaders use the same 8x8 blocks as the linear shader,
+    // except for an axis base, which uses one invocation per segment like the axis shader
+    let (launch_blocks_x, launch_blocks_y, layout): (u32, u32, Option<AxisLayout>) =
+        match fusion.base {
+            FusionBase::Axis { axis, .. } => {
+                let input: &Tensor2DGPU = tensors[0];
+                let layout: AxisLayout =
+                    AxisLayout::new(input.row_count, input.column_count, axis, batch_size);
+                (layout.segment_count.div_ceil(32) as u32, 1, Some(layout))
+            }
+            FusionBase::Linear | FusionBase::Load => {
+                let block_size: usize = 8;
+                (
+                    output.row_count.div_ceil(block_size) as u32,
+                    output.column_count.div_ceil(block_size) as u32,
+                    None,
+                )
+            }
+        };
+
+    let uniform: FusedElementwiseUniform = FusedElementwiseUniform::new(
+        gpu_handles,
+        "Fused Elementwise Uniform",
+        &tensors,
+        layout.as_ref(),
+    );
+
+    let key: String = fusion.key();
+    let shader_module: Option<ShaderModule> = if use_cache {
+        None
+    } else {
+        Some(compile_fused_elementwise_shader(gpu_handles, fusion))
+    };
+
+    let cs_module: &ShaderModule = if use_cache {
+        if shader_cache.contains_key(&key) {
+            &shader_cache[&key]
+        } else {
+            panic!("Tried to get a cached {} shader in graph::nodes::fused_elementwise(), but failed to find it in the shader cache!", key);
+        }
+    } else {
+        shader_module.as_ref().expect(
+            "Failed to get a reference to compute shader module in graph::nodes::fused_elementwise",
+        )
+    };
+
+    let pipeline: Option<ComputePipeline> = if use_cache {
+        None
+    } else {
+        Some(create_compute_pipeline(gpu_handles, cs_module, "main"))
+    };
+    let compute_pipeline: &ComputePipeline = if use_cache {
+        if pipeline_cache.contains_key(&key) {
+            &pipeline_cache[&key]
+        } else {
+            panic!("Tried to get a cached {} pipeline in graph::nodes::fused_elementwise(), but failed to find it in the pipeline cache!", key);
+        }
+    } else {
+        pipeline.as_ref().expect(
+            "Failed to get a reference to compute pipeline in graph::nodes::fused_elementwise",
+        )
+    };
+
+    let bind_group_layout: BindGroupLayout = compute_pipeline.get_bind_group_layout(0);
+    let mut to_be_bound: Vec<(u32, BindingResource)> =
+        vec![(0, uniform.storage_buffer.as_entire_binding())];
+    for (index, tensor) in tensors.iter().enumerate() {
+        to_be_bound.push(((index + 1) as u32, tensor.storage_buffer.as_entire_binding()));
+    }
+    let bind_group: BindGroup = create_bind_group(gpu_handles, &bind_group_layout, to_be_bound);
+
+    {
+        let mut cpass: ComputePass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("fused_elementwise_graph"),
+        });
+        cpass.set_pipeline(compute_pipeline);
+        cpass.set_bind_group(0, &bind_group, &[]);
+        cpass.insert_debug_marker("Fused Elementwise Graph");
+        cpass.dispatch_workgroups(launch_blocks_x, launch_blocks_y, 1);
+    }
+}
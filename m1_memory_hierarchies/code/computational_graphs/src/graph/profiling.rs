@@ -0,0 +1,135 @@
+use std::fmt;
+
+use serde::Serialize;
+
+// How long a single compiled node took. The start is relative to the start
+// of the first node, so the nodes of a report can be laid out on a timeline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeTiming {
+    pub name: String,
+    pub operator: String,
+    pub start_nanoseconds: f64,
+    pub duration_nanoseconds: f64,
+}
+
+// The timings of one profiled run of a graph, in the order the nodes ran.
+// Transfers don't do any work in the runners, so they aren't timed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProfileReport {
+    pub runner: String,
+    pub timings: Vec<NodeTiming>,
+}
+
+// A complete event in the Chrome trace event format, which chrome://tracing
+// and https://ui.perfetto.dev can open. Times are in microseconds.
+#[derive(Serialize)]
+struct TraceEvent<'a> {
+    name: &'a str,
+    cat: &'a str,
+    ph: &'static str,
+    ts: f64,
+    dur: f64,
+    pid: u32,
+    tid: u32,
+}
+
+#[derive(Serialize)]
+struct ChromeTrace<'a> {
+    #[serde(rename = "traceEvents")]
+    trace_events: Vec<TraceEvent<'a>>,
+    #[serde(rename = "displayTimeUnit")]
+    display_time_unit: &'static str,
+}
+
+impl ProfileReport {
+    pub fn new(runner: &str) -> Self {
+        ProfileReport {
+            runner: runner.to_string(),
+            timings: Vec::<NodeTiming>::new(),
+        }
+    }
+
+    pub fn total_nanoseconds(&self) -> f64 {
+        self.timings
+            .iter()
+            .map(|timing| timing.duration_nanoseconds)
+            .sum()
+    }
+
+    // The slowest node first
+    pub fn sort_by_duration(&mut self) {
+        self.timings.sort_by(|a, b| {
+            b.duration_nanoseconds
+                .total_cmp(&a.duration_nanoseconds)
+                .then_with(|| a.start_nanoseconds.total_cmp(&b.start_nanoseconds))
+        });
+    }
+
+    // Back to the order the nodes ran in
+    pub fn sort_by_start(&mut self) {
+        self.timings
+            .sort_by(|a, b| a.start_nanoseconds.total_cmp(&b.start_nanoseconds));
+    }
+
+    // The summed duration of every operator, the slowest first
+    pub fn operator_totals(&self) -> Vec<(String, f64)> {
+        let mut totals: Vec<(String, f64)> = Vec::<(String, f64)>::new();
+        for timing in &self.timings {
+            match totals
+                .iter_mut()
+                .find(|(operator, _)| *operator == timing.operator)
+            {
+                Some((_, total)) => *total += timing.duration_nanoseconds,
+                None => totals.push((timing.operator.clone(), timing.duration_nanoseconds)),
+            }
+        }
+        totals.sort_by(|a, b| b.1.total_cmp(&a.1));
+        totals
+    }
+
+    pub fn to_chrome_trace(&self) -> String {
+        let trace: ChromeTrace = ChromeTrace {
+            trace_events: self
+                .timings
+                .iter()
+                .map(|timing| TraceEvent {
+                    name: &timing.name,
+                    cat: &timing.operator,
+                    ph: "X",
+                    ts: timing.start_nanoseconds / 1000.0,
+                    dur: timing.duration_nanoseconds / 1000.0,
+                    pid: 1,
+                    tid: 1,
+                })
+                .collect(),
+            display_time_unit: "ns",
+        };
+        serde_json::to_string_pretty(&trace).expect("Failed to serialize a Chrome trace")
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total: f64 = self.total_nanoseconds();
+        writeln!(
+            f,
+            "{} profile - {} nodes, {:.0} ns",
+            self.runner,
+            self.timings.len(),
+            total
+        )?;
+        for timing in &self.timings {
+            let share: f64 = if 0.0 < total {
+                timing.duration_nanoseconds / total * 100.0
+            } else {
+                0.0
+            };
+            writeln!(
+                f,
+                "{:>32} {:>14.0} ns {:>6.2}%",
+                timing.name, timing.duration_nanoseconds, share
+            )?;
+        }
+        Ok(())
+    }
+}
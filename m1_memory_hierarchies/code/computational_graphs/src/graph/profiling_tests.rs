@@ -0,0 +1,155 @@
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::{
+        graph::{
+            graph_runner::GraphRunner,
+            profiling::{NodeTiming, ProfileReport},
+        },
+        shared::{graph_operators::GraphOperator, tensor2d::Tensor2D, tensor2d_axis::Axis},
+    };
+
+    fn graph() -> Vec<GraphOperator> {
+        vec![
+            GraphOperator::HostToDevice {
+                input: Tensor2D::new(0.1, 16, 32),
+            },
+            GraphOperator::Linear {
+                weights: Tensor2D::new(0.02, 32, 64),
+                bias: Tensor2D::new(0.01, 16, 64),
+            },
+            GraphOperator::ReLU,
+            GraphOperator::Linear {
+                weights: Tensor2D::new(0.03, 64, 8),
+                bias: Tensor2D::new(0.02, 16, 8),
+            },
+            GraphOperator::ReLU,
+            GraphOperator::Softmax { axis: Axis::Global },
+            GraphOperator::DeviceToHost,
+        ]
+    }
+
+    fn timing(name: &str, operator: &str, start: f64, duration: f64) -> NodeTiming {
+        NodeTiming {
+            name: name.to_string(),
+            operator: operator.to_string(),
+            start_nanoseconds: start,
+            duration_nanoseconds: duration,
+        }
+    }
+
+    fn report() -> ProfileReport {
+        let mut report: ProfileReport = ProfileReport::new("GraphRunner");
+        report.timings = vec![
+            timing("Linear_0", "Linear", 0.0, 3000.0),
+            timing("ReLU_0", "ReLU", 3000.0, 500.0),
+            timing("Linear_1", "Linear", 3500.0, 1500.0),
+            timing("Softmax_0", "Softmax", 5000.0, 5000.0),
+        ];
+        report
+    }
+
+    fn names(report: &ProfileReport) -> Vec<&str> {
+        report
+            .timings
+            .iter()
+            .map(|timing| timing.name.as_str())
+            .collect()
+    }
+
+    // Every node doing work is timed in the order it ran, the transfers aren't
+    #[test]
+    fn cpu_profile() {
+        let expected_names: [(bool, Vec<&str>); 2] = [
+            (
+                false,
+                vec!["Linear_0", "ReLU_0", "Linear_1", "ReLU_1", "Softmax_0"],
+            ),
+            (true, vec!["LinearReLU_0", "LinearReLUSoftmax_0"]),
+        ];
+        for (fuse_operators, expected_names) in expected_names {
+            let mut runner: GraphRunner = GraphRunner::new(&graph(), fuse_operators);
+            let report: ProfileReport = runner.profile();
+            assert_eq!(report.runner, "GraphRunner");
+            assert_eq!(names(&report), expected_names);
+
+            for pair in report.timings.windows(2) {
+                assert!(
+                    pair[0].start_nanoseconds + pair[0].duration_nanoseconds
+                        <= pair[1].start_nanoseconds
+                );
+            }
+            assert_eq!(report.timings[0].start_nanoseconds, 0.0);
+
+            // Profiling runs the graph, so the output is the same as for run
+            let expected: Tensor2D = GraphRunner::new(&graph(), false).run();
+            assert_eq!(runner.run().data, expected.data);
+        }
+    }
+
+    #[test]
+    fn cpu_profile_parallel() {
+        let mut runner: GraphRunner = GraphRunner::new(&graph(), true);
+        runner.set_thread_count(2);
+        let report: ProfileReport = runner.profile();
+        assert_eq!(names(&report), ["LinearReLU_0", "LinearReLUSoftmax_0"]);
+    }
+
+    #[test]
+    fn sorting() {
+        let mut report: ProfileReport = report();
+        report.sort_by_duration();
+        assert_eq!(
+            names(&report),
+            ["Softmax_0", "Linear_0", "Linear_1", "ReLU_0"]
+        );
+
+        report.sort_by_start();
+        assert_eq!(names(&report), names(&self::report()));
+    }
+
+    #[test]
+    fn totals() {
+        let report: ProfileReport = report();
+        assert_eq!(report.total_nanoseconds(), 10000.0);
+        assert_eq!(
+            report.operator_totals(),
+            vec![
+                ("Softmax".to_string(), 5000.0),
+                ("Linear".to_string(), 4500.0),
+                ("ReLU".to_string(), 500.0),
+            ]
+        );
+        assert_eq!(ProfileReport::new("GraphRunner").total_nanoseconds(), 0.0);
+    }
+
+    #[test]
+    fn chrome_trace() {
+        let trace: Value = serde_json::from_str(&report().to_chrome_trace()).unwrap();
+        assert_eq!(trace["displayTimeUnit"], "ns");
+
+        let events: &Vec<Value> = trace["traceEvents"].as_array().unwrap();
+        assert_eq!(events.len(), 4);
+        // Chrome traces are in microseconds
+        assert_eq!(events[1]["name"], "ReLU_0");
+        assert_eq!(events[1]["cat"], "ReLU");
+        assert_eq!(events[1]["ph"], "X");
+        assert_eq!(events[1]["ts"], 3.0);
+        assert_eq!(events[1]["dur"], 0.5);
+        for event in events {
+            assert_eq!(event["pid"], 1);
+            assert_eq!(event["tid"], 1);
+        }
+    }
+
+    #[test]
+    fn display() {
+        let text: String = report().to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], "GraphRunner profile - 4 nodes, 10000 ns");
+        assert!(lines[4].trim_start().starts_with("Softmax_0"));
+        assert!(lines[4].ends_with("5000 ns  50.00%"), "{}", lines[4]);
+    }
+}
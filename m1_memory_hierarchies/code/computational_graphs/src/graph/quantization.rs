@@ -0,0 +1,161 @@
+use std::fmt;
+
+use crate::shared::{
+    graph_operators::GraphOperator,
+    tensor2d::Tensor2D,
+    tensor2d_axis::{Axis, Reduction},
+    tensor2d_precision::{PackedTensor2D, Precision},
+};
+
+use super::{graph_error::GraphError, graph_runner::GraphRunner};
+
+// How much a graph loses by storing its parameters with a smaller element type.
+// The errors are over every element of the outputs for every sample.
+// The predicted class of a row is the column with its largest value, so for a
+// classifier with one sample per row accuracy_delta is the share of predictions
+// which changed, which is the most the accuracy can drop compared to the f32 graph.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuantizationReport {
+    pub precision: Precision,
+    pub reference_bytes: usize,
+    pub quantized_bytes: usize,
+    pub max_absolute_error: f32,
+    pub mean_absolute_error: f32,
+    pub accuracy_delta: f32,
+}
+
+impl fmt::Display for QuantizationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: parameters {} -> {} bytes, max error {:e}, mean error {:e}, {:.2}% of predictions changed",
+            self.precision.name(),
+            self.reference_bytes,
+            self.quantized_bytes,
+            self.max_absolute_error,
+            self.mean_absolute_error,
+            self.accuracy_delta * 100.0
+        )
+    }
+}
+
+// Post-training quantization of every weight and bias in the graph.
+// Every linear layer becomes a QuantizedLinear, which keeps its parameters packed
+// and decodes them as it reads them, accumulating in f32. The fused linear layers
+// are split back into QuantizedLinear followed by their ReLU and Softmax.
+// The inputs of HostToDevice are activations and are kept as f32.
+// Also returns the bytes of the parameters, before and after.
+pub fn quantize_graph_operators(
+    graph_operators: &[GraphOperator],
+    precision: Precision,
+) -> (Vec<GraphOperator>, usize, usize) {
+    let mut reference_bytes: usize = 0;
+    let mut quantized_bytes: usize = 0;
+
+    let mut quantized: Vec<GraphOperator> =
+        Vec::<GraphOperator>::with_capacity(graph_operators.len());
+    for operator in graph_operators {
+        let (weights, bias): (&Tensor2D, &Tensor2D) = match operator {
+            GraphOperator::Linear { weights, bias }
+            | GraphOperator::LinearReLUFused { weights, bias }
+            | GraphOperator::LinearReLUSoftmaxFused { weights, bias } => (weights, bias),
+            _ => {
+                quantized.push(operator.clone());
+                continue;
+            }
+        };
+
+        let weights: PackedTensor2D = PackedTensor2D::from_tensor2d(weights, precision);
+        let bias: PackedTensor2D = PackedTensor2D::from_tensor2d(bias, precision);
+        reference_bytes += (weights.len() + bias.len()) * std::mem::size_of::<f32>();
+        quantized_bytes += weights.size_in_bytes() + bias.size_in_bytes();
+
+        quantized.push(GraphOperator::QuantizedLinear { weights, bias });
+        match operator {
+            GraphOperator::LinearReLUFused { .. } => quantized.push(GraphOperator::ReLU),
+            GraphOperator::LinearReLUSoftmaxFused { .. } => {
+                quantized.push(GraphOperator::ReLU);
+                quantized.push(GraphOperator::Softmax { axis: Axis::Global });
+            }
+            _ => {}
+        }
+    }
+
+    (quantized, reference_bytes, quantized_bytes)
+}
+
+// Quantizes the graph and runs both it and the f32 reference on every sample.
+// Without samples the input the graph was built with is used.
+pub fn post_training_quantization(
+    graph_operators: &[GraphOperator],
+    precision: Precision,
+    samples: &[Tensor2D],
+) -> (Vec<GraphOperator>, QuantizationReport) {
+    try_post_training_quantization(graph_operators, precision, samples)
+        .unwrap_or_else(|error| panic!("Failed to quantize a computational graph! {}", error))
+}
+
+pub fn try_post_training_quantization(
+    graph_operators: &[GraphOperator],
+    precision: Precision,
+    samples: &[Tensor2D],
+) -> Result<(Vec<GraphOperator>, QuantizationReport), GraphError> {
+    let (quantized, reference_bytes, quantized_bytes): (Vec<GraphOperator>, usize, usize) =
+        quantize_graph_operators(graph_operators, precision);
+
+    let mut reference_runner: GraphRunner = GraphRunner::try_new(graph_operators, false)?;
+    let mut quantized_runner: GraphRunner = GraphRunner::try_new(&quantized, false)?;
+    let (reference_outputs, quantized_outputs): (Vec<Tensor2D>, Vec<Tensor2D>) = if samples
+        .is_empty()
+    {
+        (
+            vec![reference_runner.try_run()?],
+            vec![quantized_runner.try_run()?],
+        )
+    } else {
+        let mut reference_outputs: Vec<Tensor2D> = Vec::<Tensor2D>::with_capacity(samples.len());
+        let mut quantized_outputs: Vec<Tensor2D> = Vec::<Tensor2D>::with_capacity(samples.len());
+        for sample in samples {
+            reference_outputs.push(reference_runner.try_run_with(sample)?);
+            quantized_outputs.push(quantized_runner.try_run_with(sample)?);
+        }
+        (reference_outputs, quantized_outputs)
+    };
+
+    let mut max_absolute_error: f32 = 0.0;
+    let mut error_sum: f32 = 0.0;
+    let mut element_count: usize = 0;
+    let mut changed_predictions: usize = 0;
+    let mut prediction_count: usize = 0;
+    for (reference, quantized) in reference_outputs.iter().zip(&quantized_outputs) {
+        for (reference, quantized) in reference.data.iter().zip(&quantized.data) {
+            let error: f32 = (reference - quantized).abs();
+            max_absolute_error = max_absolute_error.max(error);
+            error_sum += error;
+        }
+        element_count += reference.len();
+
+        let reference_classes: Tensor2D =
+            Tensor2D::reduce(reference, Reduction::ArgMax, Axis::Rows);
+        let quantized_classes: Tensor2D =
+            Tensor2D::reduce(quantized, Reduction::ArgMax, Axis::Rows);
+        changed_predictions += reference_classes
+            .data
+            .iter()
+            .zip(&quantized_classes.data)
+            .filter(|(reference, quantized)| reference != quantized)
+            .count();
+        prediction_count += reference_classes.len();
+    }
+
+    let report: QuantizationReport = QuantizationReport {
+        precision,
+        reference_bytes,
+        quantized_bytes,
+        max_absolute_error,
+        mean_absolute_error: error_sum / element_count.max(1) as f32,
+        accuracy_delta: changed_predictions as f32 / prediction_count.max(1) as f32,
+    };
+
+    Ok((quantized, report))
+}
@@ -0,0 +1,266 @@
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::{
+        graph::{
+            graph_error::GraphError,
+            graph_runner::GraphRunner,
+            quantization::{
+                post_training_quantization, quantize_graph_operators,
+                try_post_training_quantization, QuantizationReport,
+            },
+        },
+        shared::{
+            graph_operators::GraphOperator,
+            tensor2d::Tensor2D,
+            tensor2d_axis::Axis,
+            tensor2d_precision::{PackedTensor2D, Precision},
+        },
+    };
+
+    fn random_tensor(rng: &mut ChaCha8Rng, row_count: usize, column_count: usize) -> Tensor2D {
+        let mut tensor: Tensor2D = Tensor2D::new(0.0, row_count, column_count);
+        for element in &mut tensor.data {
+            *element = rng.gen_range(-1.0..1.0);
+        }
+        tensor
+    }
+
+    // A classifier with one sample per row and 10 classes
+    fn classifier(rng: &mut ChaCha8Rng, row_count: usize) -> Vec<GraphOperator> {
+        vec![
+            GraphOperator::HostToDevice {
+                input: random_tensor(rng, row_count, 32),
+            },
+            GraphOperator::Linear {
+                weights: random_tensor(rng, 32, 64),
+                bias: random_tensor(rng, row_count, 64),
+            },
+            GraphOperator::ReLU,
+            GraphOperator::LinearReLUFused {
+                weights: random_tensor(rng, 64, 32),
+                bias: random_tensor(rng, row_count, 32),
+            },
+            GraphOperator::Linear {
+                weights: random_tensor(rng, 32, 10),
+                bias: random_tensor(rng, row_count, 10),
+            },
+            GraphOperator::Softmax { axis: Axis::Rows },
+            GraphOperator::DeviceToHost,
+        ]
+    }
+
+    fn samples(rng: &mut ChaCha8Rng, row_count: usize) -> Vec<Tensor2D> {
+        (0..8).map(|_| random_tensor(rng, row_count, 32)).collect()
+    }
+
+    #[test]
+    fn f32_is_exact() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(1);
+        let graph: Vec<GraphOperator> = classifier(&mut rng, 4);
+        let samples: Vec<Tensor2D> = samples(&mut rng, 4);
+
+        let (quantized, report): (Vec<GraphOperator>, QuantizationReport) =
+            post_training_quantization(&graph, Precision::F32, &samples);
+        // The fused layer is split back into its operators
+        let names: Vec<&str> = quantized.iter().map(|operator| operator.name()).collect();
+        assert_eq!(
+            names,
+            [
+                "HostToDevice",
+                "QuantizedLinear",
+                "ReLU",
+                "QuantizedLinear",
+                "ReLU",
+                "QuantizedLinear",
+                "Softmax",
+                "DeviceToHost"
+            ]
+        );
+        assert_eq!(report.reference_bytes, report.quantized_bytes);
+        // The parameters are exact, only the packed kernel sums in another order
+        // than the blocked f32 kernel
+        assert!(report.max_absolute_error < 0.00001, "{}", report);
+        assert_eq!(report.accuracy_delta, 0.0);
+    }
+
+    // Fewer bits give a larger error, while the predictions barely change
+    #[test]
+    fn reduced_precision() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(2);
+        let graph: Vec<GraphOperator> = classifier(&mut rng, 4);
+        let samples: Vec<Tensor2D> = samples(&mut rng, 4);
+
+        let reports: Vec<QuantizationReport> = [Precision::F16, Precision::BF16, Precision::Int8]
+            .iter()
+            .map(|precision| post_training_quantization(&graph, *precision, &samples).1)
+            .collect();
+
+        let parameter_count: usize = (32 * 64 + 4 * 64) + (64 * 32 + 4 * 32) + (32 * 10 + 4 * 10);
+        for report in &reports {
+            assert_eq!(report.reference_bytes, parameter_count * 4);
+            assert!(0.0 < report.max_absolute_error, "{}", report);
+            assert!(report.mean_absolute_error <= report.max_absolute_error);
+            assert!(report.accuracy_delta <= 0.1, "{}", report);
+        }
+        assert_eq!(reports[0].quantized_bytes, parameter_count * 2);
+        assert_eq!(reports[1].quantized_bytes, parameter_count * 2);
+        // Every int8 tensor also stores its scale and zero point
+        assert_eq!(reports[2].quantized_bytes, parameter_count + 6 * 5);
+
+        // F16 has 3 more mantissa bits than BF16
+        assert!(reports[0].max_absolute_error < reports[1].max_absolute_error);
+        assert!(reports[0].mean_absolute_error < reports[1].mean_absolute_error);
+    }
+
+    // The quantized graph keeps the packed parameters and computes what the packed kernel
+    // computes, for a single input and for a batch, which repeats the bias per sample
+    #[test]
+    fn matches_packed_linear() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(3);
+        let input: Tensor2D = random_tensor(&mut rng, 3, 7);
+        let weights: Tensor2D = random_tensor(&mut rng, 7, 5);
+        let bias: Tensor2D = random_tensor(&mut rng, 3, 5);
+        let graph: Vec<GraphOperator> = vec![
+            GraphOperator::HostToDevice {
+                input: input.clone(),
+            },
+            GraphOperator::Linear {
+                weights: weights.clone(),
+                bias: bias.clone(),
+            },
+            GraphOperator::DeviceToHost,
+        ];
+
+        for precision in [Precision::F16, Precision::BF16, Precision::Int8] {
+            let (quantized, _, _): (Vec<GraphOperator>, usize, usize) =
+                quantize_graph_operators(&graph, precision);
+            // The input is an activation and stays f32
+            assert_eq!(format!("{:?}", graph[0]), format!("{:?}", quantized[0]));
+            let packed_weights: PackedTensor2D = PackedTensor2D::from_tensor2d(&weights, precision);
+            let packed_bias: PackedTensor2D = PackedTensor2D::from_tensor2d(&bias, precision);
+            match &quantized[1] {
+                GraphOperator::QuantizedLinear { weights, bias } => {
+                    assert_eq!(*weights, packed_weights);
+                    assert_eq!(*bias, packed_bias);
+                }
+                operator => panic!("Expected QuantizedLinear, got {}", operator.name()),
+            }
+
+            let mut graph_runner: GraphRunner = GraphRunner::new(&quantized, false);
+            let output: Tensor2D = graph_runner.run();
+            let expected: Tensor2D = PackedTensor2D::linear(&input, &packed_weights, &packed_bias);
+            assert_eq!(output.data, expected.data);
+
+            let samples: Vec<Tensor2D> = (0..4).map(|_| random_tensor(&mut rng, 3, 7)).collect();
+            let outputs: Vec<Tensor2D> = graph_runner.run_batch(&samples);
+            for (sample, output) in samples.iter().zip(&outputs) {
+                let expected: Tensor2D =
+                    PackedTensor2D::linear(sample, &packed_weights, &packed_bias);
+                assert_eq!(output.data, expected.data);
+            }
+        }
+    }
+
+    // The packed parameters are frozen, but the gradient still flows through them
+    // to the layers before, as it would through a Linear with the decoded parameters
+    #[test]
+    fn backward() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(6);
+        let packed_weights: PackedTensor2D =
+            PackedTensor2D::from_tensor2d(&random_tensor(&mut rng, 6, 4), Precision::Int8);
+        let packed_bias: PackedTensor2D =
+            PackedTensor2D::from_tensor2d(&random_tensor(&mut rng, 2, 4), Precision::Int8);
+        let graph = |last_layer: GraphOperator| -> Vec<GraphOperator> {
+            vec![
+                GraphOperator::HostToDevice {
+                    input: Tensor2D::new(0.3, 2, 5),
+                },
+                GraphOperator::Linear {
+                    weights: Tensor2D::new(0.1, 5, 6),
+                    bias: Tensor2D::new(0.2, 2, 6),
+                },
+                GraphOperator::ReLU,
+                last_layer,
+                GraphOperator::Softmax { axis: Axis::Rows },
+                GraphOperator::DeviceToHost,
+            ]
+        };
+        let target: Tensor2D = random_tensor(&mut rng, 2, 4);
+
+        let mut quantized_runner: GraphRunner = GraphRunner::new(
+            &graph(GraphOperator::QuantizedLinear {
+                weights: packed_weights.clone(),
+                bias: packed_bias.clone(),
+            }),
+            false,
+        );
+        let mut reference_runner: GraphRunner = GraphRunner::new(
+            &graph(GraphOperator::Linear {
+                weights: packed_weights.to_tensor2d(),
+                bias: packed_bias.to_tensor2d(),
+            }),
+            false,
+        );
+        assert_eq!(quantized_runner.parameters().len(), 2);
+
+        for runner in [&mut quantized_runner, &mut reference_runner] {
+            let output: Tensor2D = runner.run();
+            runner.zero_gradients();
+            runner.backward(&Tensor2D {
+                data: output
+                    .data
+                    .iter()
+                    .zip(&target.data)
+                    .map(|(output, target)| output - target)
+                    .collect(),
+                row_count: 2,
+                column_count: 4,
+            });
+        }
+
+        let quantized_gradients: Vec<&Tensor2D> = quantized_runner.parameter_gradients();
+        let reference_gradients: Vec<&Tensor2D> = reference_runner.parameter_gradients();
+        for (quantized, reference) in quantized_gradients.iter().zip(&reference_gradients) {
+            assert!(quantized.data.iter().any(|gradient| *gradient != 0.0));
+            for (quantized, reference) in quantized.data.iter().zip(&reference.data) {
+                assert!((quantized - reference).abs() < 0.0001);
+            }
+        }
+    }
+
+    #[test]
+    fn without_samples() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(4);
+        let graph: Vec<GraphOperator> = classifier(&mut rng, 2);
+
+        let report: QuantizationReport = post_training_quantization(&graph, Precision::Int8, &[]).1;
+        assert!(0.0 < report.mean_absolute_error);
+        assert!(report.to_string().starts_with("Int8: parameters"));
+    }
+
+    #[test]
+    fn errors() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(5);
+        let graph: Vec<GraphOperator> = classifier(&mut rng, 2);
+
+        assert_eq!(
+            try_post_training_quantization(&graph, Precision::F16, &[Tensor2D::new(0.1, 2, 31)])
+                .err(),
+            Some(GraphError::ShapeMismatch {
+                index: 0,
+                tensor: "input",
+                expected: (2, 32),
+                actual: (2, 31),
+            })
+        );
+        assert_eq!(
+            try_post_training_quantization(&graph[1..], Precision::F16, &[]).err(),
+            Some(GraphError::MissingTransfer {
+                operator: "HostToDevice"
+            })
+        );
+    }
+}
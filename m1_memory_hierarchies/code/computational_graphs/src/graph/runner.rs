@@ -1,9 +1,18 @@
+use std::time::{Duration, Instant};
+
 use crate::shared::graph_operators::GraphOperator::*;
 use crate::{
-    graph::graph_runner::GraphRunner,
+    graph::{
+        graph_export::{dump_export_graph, ExportGraph},
+        graph_runner::GraphRunner,
+        linear_tuning::{autotune_and_cache, LinearKernel, LINEAR_TUNING_CACHE_PATH},
+        memory_planner::{print_memory_plan, MemoryPlan},
+        profiling::ProfileReport,
+    },
     immediate,
     shared::{
         benchmark_plot::draw_benchmark_plot,
+        benchmark_report::save_benchmark_report,
         configuration::Configuration,
         gpu_utilities::GPUHandles,
         graph_operators::GraphOperator,
@@ -11,6 +20,8 @@ use crate::{
             benchmark_function_vector_gpu_graph, GraphFunction, PerformanceMeasurements,
         },
         tensor2d::Tensor2D,
+        tensor2d_axis::{Axis, Reduction},
+        tensor2d_precision::PackedTensor2D,
     },
 };
 
@@ -18,13 +29,13 @@ use super::{graph_runner_gpu::GraphRunnerGPU, graph_validation};
 
 fn cpu_benchmark(
     _gpu_handles: &GPUHandles,
-    graph: &Vec<GraphOperator>,
+    graph: &[GraphOperator],
     _iteration_count: usize,
     output: &mut Tensor2D,
 ) {
     let mut intermediate_output: Tensor2D = Tensor2D::default();
-    if !graph_validation::validate_graph_operators(graph) {
-        panic!("graph::runner::cpu_benchmark() was given an invalid graph!");
+    if let Err(error) = graph_validation::validate_graph_operators(graph) {
+        panic!("graph::runner::cpu_benchmark() was given an invalid graph! {}", error);
     }
 
     for operator in graph {
@@ -48,9 +59,19 @@ fn cpu_benchmark(
             ReLU => {
                 Tensor2D::relu_inplace_inline(&mut intermediate_output);
             }
-            Softmax => {
+            Softmax { axis: Axis::Global } => {
                 Tensor2D::softmax_inplace_inline(&mut intermediate_output);
             }
+            Softmax { axis } => {
+                intermediate_output = Tensor2D::softmax_along(&intermediate_output, *axis);
+            }
+            LogSoftmax { axis } => {
+                intermediate_output = Tensor2D::log_softmax_along(&intermediate_output, *axis);
+            }
+            Sum { .. } | Max { .. } | Mean { .. } | ArgMax { .. } => {
+                let (reduction, axis): (Reduction, Axis) = operator.reduction().unwrap();
+                intermediate_output = Tensor2D::reduce(&intermediate_output, reduction, axis);
+            }
             LinearReLUFused { weights, bias } => {
                 let mut temp_output: Tensor2D =
                     Tensor2D::new(0.0, bias.row_count, bias.column_count);
@@ -73,6 +94,12 @@ fn cpu_benchmark(
                 );
                 intermediate_output = temp_output;
             }
+            QuantizedLinear { weights, bias } => {
+                intermediate_output = PackedTensor2D::linear(&intermediate_output, weights, bias);
+            }
+            Add | Subtract | Multiply | Divide => {
+                panic!("graph::runner::cpu_benchmark() only supports linear chains of operators!");
+            }
         }
     }
 
@@ -81,7 +108,7 @@ fn cpu_benchmark(
 
 fn cpu_graph_benchmark(
     _gpu_handles: &GPUHandles,
-    graph: &Vec<GraphOperator>,
+    graph: &[GraphOperator],
     _iteration_count: usize,
     output: &mut Tensor2D,
 ) {
@@ -90,15 +117,27 @@ fn cpu_graph_benchmark(
     *output = graph_runner.run();
 }
 
+fn cpu_graph_planned_benchmark(
+    _gpu_handles: &GPUHandles,
+    graph: &[GraphOperator],
+    _iteration_count: usize,
+    output: &mut Tensor2D,
+) {
+    let fuse_operators: bool = true;
+    let mut graph_runner: GraphRunner = GraphRunner::new(graph, fuse_operators);
+    graph_runner.plan_memory();
+    *output = graph_runner.run();
+}
+
 fn immediate_benchmark(
     gpu_handles: &GPUHandles,
-    graph: &Vec<GraphOperator>,
+    graph: &[GraphOperator],
     _iteration_count: usize,
     output: &mut Tensor2D,
 ) {
     let mut intermediate_output: Tensor2D = Tensor2D::default();
-    if !graph_validation::validate_graph_operators(graph) {
-        panic!("graph::runner::immediate_benchmark() was given an invalid graph!");
+    if let Err(error) = graph_validation::validate_graph_operators(graph) {
+        panic!("graph::runner::immediate_benchmark() was given an invalid graph! {}", error);
     }
 
     for operator in graph {
@@ -126,7 +165,7 @@ fn immediate_benchmark(
                     &mut intermediate_output,
                 ));
             }
-            Softmax => {
+            Softmax { axis: Axis::Global } => {
                 let mut temp_output: Tensor2D = Tensor2D::new(
                     0.0,
                     intermediate_output.row_count,
@@ -163,6 +202,15 @@ fn immediate_benchmark(
                 ));
                 intermediate_output = temp_output;
             }
+            Add | Subtract | Multiply | Divide => {
+                panic!("graph::runner::immediate_benchmark() only supports linear chains of operators!");
+            }
+            Softmax { .. } | LogSoftmax { .. } | Sum { .. } | Max { .. } | Mean { .. } | ArgMax { .. } => {
+                panic!("graph::runner::immediate_benchmark() only supports Softmax over the whole tensor!");
+            }
+            QuantizedLinear { .. } => {
+                panic!("graph::runner::immediate_benchmark() doesn't support quantized parameters!");
+            }
         }
     }
 
@@ -171,7 +219,7 @@ fn immediate_benchmark(
 
 fn graph_benchmark(
     gpu_handles: &GPUHandles,
-    graph: &Vec<GraphOperator>,
+    graph: &[GraphOperator],
     _iteration_count: usize,
     output: &mut Tensor2D,
 ) {
@@ -184,7 +232,7 @@ fn graph_benchmark(
 
 fn graph_fused_benchmark(
     gpu_handles: &GPUHandles,
-    graph: &Vec<GraphOperator>,
+    graph: &[GraphOperator],
     _iteration_count: usize,
     output: &mut Tensor2D,
 ) {
@@ -197,7 +245,7 @@ fn graph_fused_benchmark(
 
 fn graph_cached_benchmark(
     gpu_handles: &GPUHandles,
-    graph: &Vec<GraphOperator>,
+    graph: &[GraphOperator],
     _iteration_count: usize,
     output: &mut Tensor2D,
 ) {
@@ -210,7 +258,7 @@ fn graph_cached_benchmark(
 
 fn graph_cached_fused_benchmark(
     gpu_handles: &GPUHandles,
-    graph: &Vec<GraphOperator>,
+    graph: &[GraphOperator],
     _iteration_count: usize,
     output: &mut Tensor2D,
 ) {
@@ -223,7 +271,7 @@ fn graph_cached_fused_benchmark(
 
 fn graph_loop_benchmark(
     gpu_handles: &GPUHandles,
-    graph: &Vec<GraphOperator>,
+    graph: &[GraphOperator],
     iteration_count: usize,
     output: &mut Tensor2D,
 ) {
@@ -236,7 +284,7 @@ fn graph_loop_benchmark(
 
 fn graph_loop_fused_benchmark(
     gpu_handles: &GPUHandles,
-    graph: &Vec<GraphOperator>,
+    graph: &[GraphOperator],
     iteration_count: usize,
     output: &mut Tensor2D,
 ) {
@@ -249,7 +297,7 @@ fn graph_loop_fused_benchmark(
 
 fn graph_loop_cached_benchmark(
     gpu_handles: &GPUHandles,
-    graph: &Vec<GraphOperator>,
+    graph: &[GraphOperator],
     iteration_count: usize,
     output: &mut Tensor2D,
 ) {
@@ -262,7 +310,7 @@ fn graph_loop_cached_benchmark(
 
 fn graph_loop_cached_fused_benchmark(
     gpu_handles: &GPUHandles,
-    graph: &Vec<GraphOperator>,
+    graph: &[GraphOperator],
     iteration_count: usize,
     output: &mut Tensor2D,
 ) {
@@ -273,10 +321,205 @@ fn graph_loop_cached_fused_benchmark(
     *output = pollster::block_on(graph_runner.run(gpu_handles, iteration_count));
 }
 
+fn graph_loop_cached_fused_planned_benchmark(
+    gpu_handles: &GPUHandles,
+    graph: &[GraphOperator],
+    iteration_count: usize,
+    output: &mut Tensor2D,
+) {
+    let fuse_operators: bool = true;
+    let cache_elements: bool = true;
+    let mut graph_runner: GraphRunnerGPU =
+        GraphRunnerGPU::new(gpu_handles, graph, fuse_operators, cache_elements);
+    graph_runner.plan_memory();
+    *output = pollster::block_on(graph_runner.run(gpu_handles, iteration_count));
+}
+
+// The throughput benchmarks build their runner before starting the clock,
+// and return the time it took to run every sample loop_count times.
+fn cpu_graph_per_sample_throughput(
+    _gpu_handles: &GPUHandles,
+    graph: &[GraphOperator],
+    samples: &[Tensor2D],
+    loop_count: usize,
+) -> Duration {
+    let fuse_operators: bool = true;
+    let mut graph_runner: GraphRunner = GraphRunner::new(graph, fuse_operators);
+
+    let now: Instant = Instant::now();
+    for _ in 0..loop_count {
+        for sample in samples {
+            graph_runner.run_with(sample);
+        }
+    }
+    now.elapsed()
+}
+
+fn cpu_graph_batched_throughput(
+    _gpu_handles: &GPUHandles,
+    graph: &[GraphOperator],
+    samples: &[Tensor2D],
+    loop_count: usize,
+) -> Duration {
+    let fuse_operators: bool = true;
+    let mut graph_runner: GraphRunner = GraphRunner::new(graph, fuse_operators);
+
+    let now: Instant = Instant::now();
+    for _ in 0..loop_count {
+        graph_runner.run_batch(samples);
+    }
+    now.elapsed()
+}
+
+fn cpu_graph_batched_parallel_throughput(
+    _gpu_handles: &GPUHandles,
+    graph: &[GraphOperator],
+    samples: &[Tensor2D],
+    loop_count: usize,
+) -> Duration {
+    let fuse_operators: bool = true;
+    let mut graph_runner: GraphRunner = GraphRunner::new(graph, fuse_operators);
+    graph_runner.set_thread_count(0);
+
+    let now: Instant = Instant::now();
+    for _ in 0..loop_count {
+        graph_runner.run_batch(samples);
+    }
+    now.elapsed()
+}
+
+fn graph_per_sample_throughput(
+    gpu_handles: &GPUHandles,
+    graph: &[GraphOperator],
+    samples: &[Tensor2D],
+    loop_count: usize,
+) -> Duration {
+    let fuse_operators: bool = true;
+    let cache_elements: bool = true;
+    let mut graph_runner: GraphRunnerGPU =
+        GraphRunnerGPU::new(gpu_handles, graph, fuse_operators, cache_elements);
+
+    let now: Instant = Instant::now();
+    for _ in 0..loop_count {
+        for sample in samples {
+            pollster::block_on(graph_runner.run_with(gpu_handles, sample, 1));
+        }
+    }
+    now.elapsed()
+}
+
+fn graph_batched_throughput(
+    gpu_handles: &GPUHandles,
+    graph: &[GraphOperator],
+    samples: &[Tensor2D],
+    loop_count: usize,
+) -> Duration {
+    let fuse_operators: bool = true;
+    let cache_elements: bool = true;
+    let mut graph_runner: GraphRunnerGPU =
+        GraphRunnerGPU::new(gpu_handles, graph, fuse_operators, cache_elements);
+
+    let now: Instant = Instant::now();
+    for _ in 0..loop_count {
+        pollster::block_on(graph_runner.run_batch(gpu_handles, samples, 1));
+    }
+    now.elapsed()
+}
+
+// A graph of default_graph_layer_count layers, run on samples of a single row
+// with default_graph_operator_size elements, as in batched inference.
+fn batch_throughput_graph(config: &Configuration) -> Vec<GraphOperator> {
+    let size: usize = config.default_graph_operator_size;
+    let mut graph: Vec<GraphOperator> = vec![HostToDevice {
+        input: Tensor2D::new(0.5, 1, size),
+    }];
+    for _ in 0..config.default_graph_layer_count {
+        graph.push(Linear {
+            weights: Tensor2D::new(0.5, size, size),
+            bias: Tensor2D::new(0.1, 1, size),
+        });
+        graph.push(ReLU);
+    }
+    graph.push(Softmax { axis: Axis::Global });
+    graph.push(DeviceToHost);
+
+    graph
+}
+
+// Measures the time per sample of running batches of every size in config.batch_size_range,
+// either one sample at a time or as a single batch.
+fn batch_throughput_benchmarks(config: &Configuration, gpu_handles: &GPUHandles) {
+    let names: Vec<String> = vec![
+        "graph::runner::cpu_graph_per_sample".to_string(),
+        "graph::runner::cpu_graph_batched".to_string(),
+        "graph::runner::cpu_graph_batched_parallel".to_string(),
+        "graph::runner::graph_per_sample".to_string(),
+        "graph::runner::graph_batched".to_string(),
+    ];
+
+    let functions: Vec<fn(&GPUHandles, &[GraphOperator], &[Tensor2D], usize) -> Duration> = vec![
+        cpu_graph_per_sample_throughput,
+        cpu_graph_batched_throughput,
+        cpu_graph_batched_parallel_throughput,
+        graph_per_sample_throughput,
+        graph_batched_throughput,
+    ];
+
+    let graph: Vec<GraphOperator> = batch_throughput_graph(config);
+    let sample: Tensor2D = Tensor2D::new(0.5, 1, config.default_graph_operator_size);
+
+    let mut all_measurements: Vec<PerformanceMeasurements> = Vec::<PerformanceMeasurements>::new();
+    for (name, function) in names.iter().zip(&functions) {
+        let mut times: Vec<(u128, usize)> = Vec::<(u128, usize)>::new();
+        for batch_size in &config.batch_size_range {
+            let samples: Vec<Tensor2D> = vec![sample.clone(); *batch_size];
+            let elapsed_time: Duration = function(gpu_handles, &graph, &samples, config.loop_count);
+            times.push((elapsed_time.as_micros(), config.loop_count * batch_size));
+        }
+
+        let measurements: PerformanceMeasurements = PerformanceMeasurements::build_from_measurements(
+            name.clone(),
+            config.batch_size_range.clone(),
+            times,
+        );
+        if 1 < config.debug_level {
+            for (batch_size, time) in measurements.zipped() {
+                println!(
+                    "{} - batch size {}: {:.0} samples per second",
+                    name,
+                    batch_size,
+                    1.0e6 / time
+                );
+            }
+        }
+        all_measurements.push(measurements);
+    }
+
+    let chart_name: String = format!(
+        "Graphs Batch Throughput - Batch Size(x) - Depth {} - Size {}",
+        config.default_graph_layer_count, config.default_graph_operator_size
+    );
+    save_benchmark_report(
+        &chart_name,
+        "benchmarks/graphs/",
+        "graphs_batch_throughput_benchmark.png",
+        &all_measurements,
+        Some(&gpu_handles.adapter_info),
+    );
+    draw_benchmark_plot(
+        &chart_name,
+        "benchmarks/graphs/",
+        "graphs_batch_throughput_benchmark.png",
+        all_measurements,
+        config.log_scale,
+    );
+}
+
 fn graph_benchmarks(config: &Configuration, gpu_handles: &GPUHandles) {
     let names: Vec<String> = vec![
         "graph::runner::cpu".to_string(),
         "graph::runner::cpu_graph".to_string(),
+        "graph::runner::cpu_graph_planned".to_string(),
         "graph::runner::immediate".to_string(),
         "graph::runner::graph".to_string(),
         "graph::runner::graph_fused".to_string(),
@@ -286,14 +529,16 @@ fn graph_benchmarks(config: &Configuration, gpu_handles: &GPUHandles) {
         "graph::runner::graph_loop_fused".to_string(),
         "graph::runner::graph_loop_cached".to_string(),
         "graph::runner::graph_loop_cached_fused".to_string(),
+        "graph::runner::graph_loop_cached_fused_planned".to_string(),
     ];
 
     let functions: Vec<(
         GraphFunction,
-        fn(&GPUHandles, &Vec<GraphOperator>, usize, &mut Tensor2D),
+        fn(&GPUHandles, &[GraphOperator], usize, &mut Tensor2D),
     )> = vec![
         (GraphFunction::Cpu, cpu_benchmark),
         (GraphFunction::Cpu, cpu_graph_benchmark),
+        (GraphFunction::Cpu, cpu_graph_planned_benchmark),
         (GraphFunction::Immediate, immediate_benchmark),
         (GraphFunction::Graph, graph_benchmark),
         (GraphFunction::Graph, graph_fused_benchmark),
@@ -303,6 +548,7 @@ fn graph_benchmarks(config: &Configuration, gpu_handles: &GPUHandles) {
         (GraphFunction::GraphLoop, graph_loop_fused_benchmark),
         (GraphFunction::GraphLoop, graph_loop_cached_benchmark),
         (GraphFunction::GraphLoop, graph_loop_cached_fused_benchmark),
+        (GraphFunction::GraphLoop, graph_loop_cached_fused_planned_benchmark),
     ];
 
     let mut all_measurements: Vec<PerformanceMeasurements> =
@@ -318,12 +564,19 @@ fn graph_benchmarks(config: &Configuration, gpu_handles: &GPUHandles) {
         measure_depth,
     );
 
+    let chart_name: String = format!(
+        "Graphs Benchmark - Size(x) - Depth {}",
+        config.default_graph_layer_count
+    );
+    save_benchmark_report(
+        &chart_name,
+        "benchmarks/graphs/",
+        "graphs_size_benchmark.png",
+        &all_measurements,
+        Some(&gpu_handles.adapter_info),
+    );
     draw_benchmark_plot(
-        format!(
-            "Graphs Benchmark - Size(x) - Depth {}",
-            config.default_graph_layer_count
-        )
-        .as_str(),
+        &chart_name,
         "benchmarks/graphs/",
         "graphs_size_benchmark.png",
         all_measurements,
@@ -343,12 +596,19 @@ fn graph_benchmarks(config: &Configuration, gpu_handles: &GPUHandles) {
         measure_depth,
     );
 
+    let chart_name: String = format!(
+        "Graphs Benchmark - Depth(x) - Size {}",
+        config.default_graph_operator_size
+    );
+    save_benchmark_report(
+        &chart_name,
+        "benchmarks/graphs/",
+        "graphs_depth_benchmark.png",
+        &all_measurements,
+        Some(&gpu_handles.adapter_info),
+    );
     draw_benchmark_plot(
-        format!(
-            "Graphs Benchmark - Depth(x) - Size {}",
-            config.default_graph_operator_size
-        )
-        .as_str(),
+        &chart_name,
         "benchmarks/graphs/",
         "graphs_depth_benchmark.png",
         all_measurements,
@@ -366,11 +626,12 @@ fn graph_benchmarks(config: &Configuration, gpu_handles: &GPUHandles) {
         "graph::runner::graph_loop_fused".to_string(),
         "graph::runner::graph_loop_cached".to_string(),
         "graph::runner::graph_loop_cached_fused".to_string(),
+        "graph::runner::graph_loop_cached_fused_planned".to_string(),
     ];
 
     let functions: Vec<(
         GraphFunction,
-        fn(&GPUHandles, &Vec<GraphOperator>, usize, &mut Tensor2D),
+        fn(&GPUHandles, &[GraphOperator], usize, &mut Tensor2D),
     )> = vec![
         (GraphFunction::Graph, graph_benchmark),
         (GraphFunction::Graph, graph_fused_benchmark),
@@ -380,6 +641,7 @@ fn graph_benchmarks(config: &Configuration, gpu_handles: &GPUHandles) {
         (GraphFunction::GraphLoop, graph_loop_fused_benchmark),
         (GraphFunction::GraphLoop, graph_loop_cached_benchmark),
         (GraphFunction::GraphLoop, graph_loop_cached_fused_benchmark),
+        (GraphFunction::GraphLoop, graph_loop_cached_fused_planned_benchmark),
     ];
 
     let mut all_measurements: Vec<PerformanceMeasurements> =
@@ -395,12 +657,19 @@ fn graph_benchmarks(config: &Configuration, gpu_handles: &GPUHandles) {
         measure_depth,
     );
 
+    let chart_name: String = format!(
+        "Graphs Only Benchmark - Size(x) - Depth {}",
+        config.default_graph_layer_count
+    );
+    save_benchmark_report(
+        &chart_name,
+        "benchmarks/graphs/",
+        "graphs_only_size_benchmark.png",
+        &all_measurements,
+        Some(&gpu_handles.adapter_info),
+    );
     draw_benchmark_plot(
-        format!(
-            "Graphs Only Benchmark - Size(x) - Depth {}",
-            config.default_graph_layer_count
-        )
-        .as_str(),
+        &chart_name,
         "benchmarks/graphs/",
         "graphs_only_size_benchmark.png",
         all_measurements,
@@ -420,21 +689,91 @@ fn graph_benchmarks(config: &Configuration, gpu_handles: &GPUHandles) {
         measure_depth,
     );
 
+    let chart_name: String = format!(
+        "Graphs Only Benchmark - Depth(x) - Size {}",
+        config.default_graph_operator_size
+    );
+    save_benchmark_report(
+        &chart_name,
+        "benchmarks/graphs/",
+        "graphs_only_depth_benchmark.png",
+        &all_measurements,
+        Some(&gpu_handles.adapter_info),
+    );
     draw_benchmark_plot(
-        format!(
-            "Graphs Only Benchmark - Depth(x) - Size {}",
-            config.default_graph_operator_size
-        )
-        .as_str(),
+        &chart_name,
         "benchmarks/graphs/",
         "graphs_only_depth_benchmark.png",
         all_measurements,
         config.log_scale,
     );
 
+    if !config.batch_size_range.is_empty() {
+        batch_throughput_benchmarks(config, gpu_handles);
+    }
+
+}
+
+// Writes the compiled nodes of a runner to graph_exports/ if the configuration asks for it
+fn dump_graph(config: &Configuration, name: &str, graph: &ExportGraph) {
+    if !config.dump_graphs {
+        return;
+    }
+
+    match dump_export_graph("graph_exports/", name, graph) {
+        Ok((dot_path, mermaid_path)) => {
+            if 1 < config.debug_level {
+                println!(
+                    "{} graph written to {} and {}",
+                    name,
+                    dot_path.display(),
+                    mermaid_path.display()
+                );
+            }
+        }
+        Err(error) => println!("Failed to write the {} graph! {}", name, error),
+    }
+}
+
+// Prints the slowest nodes first and writes a Chrome trace next to the graph dumps
+fn report_profile(config: &Configuration, name: &str, mut report: ProfileReport) {
+    if config.dump_graphs {
+        let path: String = format!("graph_exports/{}_trace.json", name);
+        match std::fs::create_dir_all("graph_exports/")
+            .and_then(|_| std::fs::write(&path, report.to_chrome_trace()))
+        {
+            Ok(()) => {
+                if 1 < config.debug_level {
+                    println!("{} trace written to {}", name, path);
+                }
+            }
+            Err(error) => println!("Failed to write the {} trace! {}", name, error),
+        }
+    }
+
+    if 1 < config.debug_level {
+        report.sort_by_duration();
+        print!("{}", report);
+    }
 }
 
 pub async fn execute(gpu_handles: &GPUHandles, config: &Configuration) {
+    // Tuned before the benchmarks, so they run with the fastest Linear kernel
+    if config.autotune_linear {
+        let size: usize = config.default_graph_operator_size.max(64);
+        let kernel: Option<LinearKernel> = autotune_and_cache(
+            gpu_handles,
+            LINEAR_TUNING_CACHE_PATH,
+            size,
+            config.loop_count.max(1),
+            config.debug_level,
+        );
+        match kernel {
+            Some(kernel) => println!("Linear autotuning picked the {} kernel", kernel.name()),
+            None => println!("Linear autotuning found no kernel which matches the CPU"),
+        }
+    }
+
     if config.run_performance_benchmark {
         graph_benchmarks(config, gpu_handles);
         return;
@@ -476,7 +815,7 @@ pub async fn execute(gpu_handles: &GPUHandles, config: &Configuration) {
             bias: bias_c,
         },
         ReLU,
-        Softmax,
+        Softmax { axis: Axis::Global },
         DeviceToHost,
     ];
 
@@ -485,8 +824,13 @@ pub async fn execute(gpu_handles: &GPUHandles, config: &Configuration) {
     // let mut graph_runner: GraphRunner = GraphRunner::new(&gpu_handles, graph_operators, fuse_operators, cache_elements);
     // let output: Tensor2D = graph_runner.run(&gpu_handles).await;
     let mut graph_runner: GraphRunner = GraphRunner::new(&graph_operators, fuse_operators);
+    dump_graph(config, "cpu", &graph_runner.export_graph());
+    let plan: MemoryPlan = graph_runner.plan_memory();
+    print_memory_plan("cpu", &plan);
+    dump_graph(config, "cpu_planned", &graph_runner.export_graph());
     let output: Tensor2D = graph_runner.run();
     println!("cpu output: {:?}", output);
+    report_profile(config, "cpu", graph_runner.profile());
 
     let difference: Tensor2D = Tensor2D::subtraction(&output_cpu, &output);
     println!("cpu difference: {:?}", difference);
@@ -501,8 +845,17 @@ pub async fn execute(gpu_handles: &GPUHandles, config: &Configuration) {
         fuse_operators,
         cache_elements,
     );
+    dump_graph(config, "gpu", &graph_runner.export_graph());
+    let plan: MemoryPlan = graph_runner.plan_memory();
+    print_memory_plan("gpu", &plan);
+    dump_graph(config, "gpu_planned", &graph_runner.export_graph());
     let output: Tensor2D = graph_runner.run(gpu_handles, 1).await;
     println!("gpu output: {:?}", output);
+    match graph_runner.profile(gpu_handles).await {
+        Ok(Some(report)) => report_profile(config, "gpu", report),
+        Ok(None) => println!("gpu profile: the adapter doesn't support timestamp queries"),
+        Err(error) => println!("gpu profile: {}", error),
+    }
 
     let difference: Tensor2D = Tensor2D::subtraction(&output_cpu, &output);
     println!("gpu difference: {:?}", difference);
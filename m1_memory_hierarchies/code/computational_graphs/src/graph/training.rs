@@ -0,0 +1,154 @@
+use crate::shared::tensor2d::Tensor2D;
+
+// Keeps us from taking the logarithm of 0 or dividing by 0
+// when the prediction for an element underflows.
+const CROSS_ENTROPY_EPSILON: f32 = 1e-7;
+
+fn cross_entropy_assert(prediction: &Tensor2D, target: &Tensor2D) {
+    assert_eq!(
+        (prediction.row_count, prediction.column_count),
+        (target.row_count, target.column_count),
+        "\nMismatch - prediction & target\nprediction - rows: {} columns: {}.\n target - rows: {} columns: {}.",
+        prediction.row_count,
+        prediction.column_count,
+        target.row_count,
+        target.column_count
+    );
+}
+
+// The prediction is expected to be the output of a softmax, and the target
+// a probability distribution of the same shape, usually one-hot.
+// loss = -sum(target * ln(prediction))
+pub fn cross_entropy(prediction: &Tensor2D, target: &Tensor2D) -> f32 {
+    cross_entropy_assert(prediction, target);
+
+    let mut loss: f32 = 0.0;
+    for index in 0..prediction.len() {
+        loss -= target.data[index] * prediction.data[index].max(CROSS_ENTROPY_EPSILON).ln();
+    }
+
+    loss
+}
+
+// The gradient of the cross entropy with respect to the prediction.
+// This is what gets fed to the backward pass of the graph.
+pub fn cross_entropy_gradient(prediction: &Tensor2D, target: &Tensor2D) -> Tensor2D {
+    cross_entropy_assert(prediction, target);
+
+    let mut gradient: Tensor2D = Tensor2D::new(0.0, prediction.row_count, prediction.column_count);
+    for index in 0..prediction.len() {
+        gradient.data[index] =
+            -target.data[index] / prediction.data[index].max(CROSS_ENTROPY_EPSILON);
+    }
+
+    gradient
+}
+
+// SGD just takes a step against the gradient.
+// Adam keeps a running average of the gradient and the squared gradient
+// for every parameter, which it uses to scale the step for each element.
+// The moments are allocated on the first step, once we know the shapes
+// of the parameters.
+#[derive(Clone, Debug)]
+pub enum Optimizer {
+    Sgd {
+        learning_rate: f32,
+    },
+    Adam {
+        learning_rate: f32,
+        beta_1: f32,
+        beta_2: f32,
+        epsilon: f32,
+        step_count: i32,
+        first_moments: Vec<Tensor2D>,
+        second_moments: Vec<Tensor2D>,
+    },
+}
+
+impl Optimizer {
+    pub fn sgd(learning_rate: f32) -> Self {
+        Optimizer::Sgd { learning_rate }
+    }
+
+    // Uses the default values from the Adam paper
+    pub fn adam(learning_rate: f32) -> Self {
+        Optimizer::Adam {
+            learning_rate,
+            beta_1: 0.9,
+            beta_2: 0.999,
+            epsilon: 1e-8,
+            step_count: 0,
+            first_moments: Vec::<Tensor2D>::new(),
+            second_moments: Vec::<Tensor2D>::new(),
+        }
+    }
+
+    pub fn step(&mut self, parameters: Vec<&mut Tensor2D>, gradients: Vec<&Tensor2D>) {
+        assert_eq!(
+            parameters.len(),
+            gradients.len(),
+            "Optimizer::step received {} parameters but {} gradients",
+            parameters.len(),
+            gradients.len()
+        );
+
+        match self {
+            Optimizer::Sgd { learning_rate } => {
+                for (parameter, gradient) in parameters.into_iter().zip(gradients) {
+                    for index in 0..parameter.len() {
+                        parameter.data[index] -= *learning_rate * gradient.data[index];
+                    }
+                }
+            }
+            Optimizer::Adam {
+                learning_rate,
+                beta_1,
+                beta_2,
+                epsilon,
+                step_count,
+                first_moments,
+                second_moments,
+            } => {
+                if first_moments.is_empty() {
+                    for parameter in &parameters {
+                        first_moments.push(Tensor2D::new(
+                            0.0,
+                            parameter.row_count,
+                            parameter.column_count,
+                        ));
+                        second_moments.push(Tensor2D::new(
+                            0.0,
+                            parameter.row_count,
+                            parameter.column_count,
+                        ));
+                    }
+                }
+
+                // The moments start at 0, so early on they are biased towards 0.
+                // Dividing by (1 - beta^t) corrects for this.
+                *step_count += 1;
+                let first_correction: f32 = 1.0 - beta_1.powi(*step_count);
+                let second_correction: f32 = 1.0 - beta_2.powi(*step_count);
+
+                for (parameter_index, (parameter, gradient)) in
+                    parameters.into_iter().zip(gradients).enumerate()
+                {
+                    let first_moment: &mut Tensor2D = &mut first_moments[parameter_index];
+                    let second_moment: &mut Tensor2D = &mut second_moments[parameter_index];
+                    for index in 0..parameter.len() {
+                        let gradient: f32 = gradient.data[index];
+                        first_moment.data[index] =
+                            *beta_1 * first_moment.data[index] + (1.0 - *beta_1) * gradient;
+                        second_moment.data[index] = *beta_2 * second_moment.data[index]
+                            + (1.0 - *beta_2) * gradient * gradient;
+
+                        let first: f32 = first_moment.data[index] / first_correction;
+                        let second: f32 = second_moment.data[index] / second_correction;
+                        parameter.data[index] -=
+                            *learning_rate * first / (second.sqrt() + *epsilon);
+                    }
+                }
+            }
+        }
+    }
+}
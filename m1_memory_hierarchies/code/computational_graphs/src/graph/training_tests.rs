@@ -0,0 +1,357 @@
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::{
+        graph::{
+            graph_runner::GraphRunner,
+            training::{cross_entropy, cross_entropy_gradient, Optimizer},
+        },
+        shared::{
+            graph_operators::{GraphNode, GraphOperator},
+            tensor2d::Tensor2D,
+            tensor2d_axis::Axis,
+        },
+    };
+
+    // Central differences in f32 aren't very precise, so the
+    // tolerance is a lot larger than in the other tests
+    const FINITE_DIFFERENCE_STEP: f32 = 0.01;
+    const GRADIENT_TOLERANCE: f32 = 0.005;
+
+    fn random_tensor(rng: &mut ChaCha8Rng, row_count: usize, column_count: usize) -> Tensor2D {
+        let mut tensor: Tensor2D = Tensor2D::new(0.0, row_count, column_count);
+        for element in &mut tensor.data {
+            *element = rng.gen_range(-1.0..1.0);
+        }
+        tensor
+    }
+
+    fn one_hot(row_count: usize, column_count: usize, index: usize) -> Tensor2D {
+        let mut tensor: Tensor2D = Tensor2D::new(0.0, row_count, column_count);
+        tensor.data[index] = 1.0;
+        tensor
+    }
+
+    // Compares the gradient of every parameter element from the backward pass
+    // against the central difference (loss(p + h) - loss(p - h)) / 2h.
+    fn gradient_check(graph_nodes: &[GraphNode], target: &Tensor2D, fuse_operators: bool) {
+        let mut graph_runner: GraphRunner =
+            GraphRunner::from_graph_nodes(graph_nodes, fuse_operators);
+
+        let prediction: Tensor2D = graph_runner.run();
+        graph_runner.zero_gradients();
+        graph_runner.backward(&cross_entropy_gradient(&prediction, target));
+        let analytic_gradients: Vec<Tensor2D> = graph_runner
+            .parameter_gradients()
+            .into_iter()
+            .cloned()
+            .collect();
+        assert!(!analytic_gradients.is_empty());
+
+        for (parameter_index, analytic_gradient) in analytic_gradients.iter().enumerate() {
+            for element in 0..analytic_gradient.len() {
+                let original: f32 = graph_runner.parameters()[parameter_index].data[element];
+
+                graph_runner.parameters_mut()[parameter_index].data[element] =
+                    original + FINITE_DIFFERENCE_STEP;
+                let loss_plus: f32 = cross_entropy(&graph_runner.run(), target);
+
+                graph_runner.parameters_mut()[parameter_index].data[element] =
+                    original - FINITE_DIFFERENCE_STEP;
+                let loss_minus: f32 = cross_entropy(&graph_runner.run(), target);
+
+                graph_runner.parameters_mut()[parameter_index].data[element] = original;
+
+                let numerical: f32 = (loss_plus - loss_minus) / (2.0 * FINITE_DIFFERENCE_STEP);
+                let analytic: f32 = analytic_gradient.data[element];
+                assert!(
+                    (numerical - analytic).abs() < GRADIENT_TOLERANCE * (1.0 + analytic.abs()),
+                    "\nparameter: {} element: {} fused: {}\nanalytic: {} numerical: {}",
+                    parameter_index,
+                    element,
+                    fuse_operators,
+                    analytic,
+                    numerical
+                );
+            }
+        }
+    }
+
+    fn mlp_graph(rng: &mut ChaCha8Rng, row_count: usize) -> Vec<GraphNode> {
+        vec![
+            GraphNode::new(
+                GraphOperator::HostToDevice {
+                    input: random_tensor(rng, row_count, 3),
+                },
+                vec![],
+            ),
+            GraphNode::new(
+                GraphOperator::Linear {
+                    weights: random_tensor(rng, 3, 4),
+                    bias: random_tensor(rng, row_count, 4),
+                },
+                vec![0],
+            ),
+            GraphNode::new(GraphOperator::ReLU, vec![1]),
+            GraphNode::new(
+                GraphOperator::Linear {
+                    weights: random_tensor(rng, 4, 5),
+                    bias: random_tensor(rng, row_count, 5),
+                },
+                vec![2],
+            ),
+            GraphNode::new(GraphOperator::ReLU, vec![3]),
+            GraphNode::new(GraphOperator::Softmax { axis: Axis::Global }, vec![4]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![5]),
+        ]
+    }
+
+    #[test]
+    fn cross_entropy_of_one_hot() {
+        let prediction: Tensor2D = Tensor2D {
+            data: vec![0.25, 0.5, 0.25],
+            row_count: 1,
+            column_count: 3,
+        };
+        let target: Tensor2D = one_hot(1, 3, 1);
+
+        assert!((cross_entropy(&prediction, &target) - 2.0_f32.ln()).abs() < 0.00001);
+
+        let gradient: Tensor2D = cross_entropy_gradient(&prediction, &target);
+        assert_eq!(gradient.data, vec![0.0, -2.0, 0.0]);
+    }
+
+    // Without fusion every operator gets its own backward pass.
+    // With fusion the Linear-ReLU and Linear-ReLU-Softmax nodes are checked.
+    #[test]
+    fn gradient_check_mlp() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(3);
+        for row_count in 1..3 {
+            let graph_nodes: Vec<GraphNode> = mlp_graph(&mut rng, row_count);
+            let target: Tensor2D = one_hot(row_count, 5, 2);
+
+            for fuse_operators in [false, true] {
+                gradient_check(&graph_nodes, &target, fuse_operators);
+            }
+        }
+    }
+
+    #[test]
+    fn gradient_check_fused_operators() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(17);
+        let graph_nodes: Vec<GraphNode> = vec![
+            GraphNode::new(
+                GraphOperator::HostToDevice {
+                    input: random_tensor(&mut rng, 2, 3),
+                },
+                vec![],
+            ),
+            GraphNode::new(
+                GraphOperator::LinearReLUFused {
+                    weights: random_tensor(&mut rng, 3, 4),
+                    bias: random_tensor(&mut rng, 2, 4),
+                },
+                vec![0],
+            ),
+            GraphNode::new(
+                GraphOperator::LinearReLUSoftmaxFused {
+                    weights: random_tensor(&mut rng, 4, 3),
+                    bias: random_tensor(&mut rng, 2, 3),
+                },
+                vec![1],
+            ),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![2]),
+        ];
+        let target: Tensor2D = one_hot(2, 3, 4);
+
+        gradient_check(&graph_nodes, &target, false);
+    }
+
+    // Branches which are joined by every elementwise operator, including broadcasting
+    // and a node reading the same input twice, so gradients have to be accumulated.
+    #[test]
+    fn gradient_check_elementwise() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(7);
+
+        // Keep the denominator away from 0
+        let mut denominator: Tensor2D = random_tensor(&mut rng, 1, 4);
+        denominator.data.iter_mut().for_each(|x| *x = x.abs() + 1.0);
+
+        let graph_nodes: Vec<GraphNode> = vec![
+            GraphNode::new(
+                GraphOperator::HostToDevice {
+                    input: random_tensor(&mut rng, 3, 2),
+                },
+                vec![],
+            ),
+            GraphNode::new(
+                GraphOperator::Linear {
+                    weights: random_tensor(&mut rng, 2, 4),
+                    bias: random_tensor(&mut rng, 3, 4),
+                },
+                vec![0],
+            ),
+            GraphNode::new(
+                GraphOperator::Linear {
+                    weights: random_tensor(&mut rng, 2, 1),
+                    bias: random_tensor(&mut rng, 3, 1),
+                },
+                vec![0],
+            ),
+            // Column vector broadcast along the columns
+            GraphNode::new(GraphOperator::Multiply, vec![1, 2]),
+            GraphNode::new(GraphOperator::Multiply, vec![1, 1]),
+            GraphNode::new(GraphOperator::Subtract, vec![3, 4]),
+            GraphNode::new(GraphOperator::HostToDevice { input: denominator }, vec![]),
+            // Row vector broadcast along the rows
+            GraphNode::new(GraphOperator::Divide, vec![5, 6]),
+            GraphNode::new(GraphOperator::Add, vec![7, 2]),
+            GraphNode::new(GraphOperator::Softmax { axis: Axis::Global }, vec![8]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![9]),
+        ];
+        let target: Tensor2D = one_hot(3, 4, 6);
+
+        gradient_check(&graph_nodes, &target, false);
+    }
+
+    // Every reduction with a gradient, broadcast back onto the tensor it reduced,
+    // followed by a log-softmax and a softmax along different axes.
+    // ArgMax has no gradient, and its step would break the finite differences.
+    #[test]
+    fn gradient_check_axis_operators() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(11);
+        let graph_nodes: Vec<GraphNode> = vec![
+            GraphNode::new(
+                GraphOperator::HostToDevice {
+                    input: random_tensor(&mut rng, 3, 2),
+                },
+                vec![],
+            ),
+            GraphNode::new(
+                GraphOperator::Linear {
+                    weights: random_tensor(&mut rng, 2, 4),
+                    bias: random_tensor(&mut rng, 3, 4),
+                },
+                vec![0],
+            ),
+            GraphNode::new(
+                GraphOperator::Sum {
+                    axis: Axis::Columns,
+                },
+                vec![1],
+            ),
+            GraphNode::new(GraphOperator::Max { axis: Axis::Rows }, vec![1]),
+            GraphNode::new(GraphOperator::Mean { axis: Axis::Global }, vec![1]),
+            GraphNode::new(GraphOperator::Add, vec![1, 2]),
+            GraphNode::new(GraphOperator::Multiply, vec![5, 3]),
+            GraphNode::new(GraphOperator::Subtract, vec![6, 4]),
+            GraphNode::new(
+                GraphOperator::LogSoftmax {
+                    axis: Axis::Columns,
+                },
+                vec![7],
+            ),
+            GraphNode::new(GraphOperator::Softmax { axis: Axis::Rows }, vec![8]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![9]),
+        ];
+        let target: Tensor2D = one_hot(3, 4, 9);
+
+        gradient_check(&graph_nodes, &target, false);
+    }
+
+    // Running backward twice without zeroing doubles the parameter gradients
+    #[test]
+    fn gradients_accumulate() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(11);
+        let graph_nodes: Vec<GraphNode> = mlp_graph(&mut rng, 2);
+        let target: Tensor2D = one_hot(2, 5, 7);
+
+        let mut graph_runner: GraphRunner = GraphRunner::from_graph_nodes(&graph_nodes, true);
+        let prediction: Tensor2D = graph_runner.run();
+        let output_gradient: Tensor2D = cross_entropy_gradient(&prediction, &target);
+
+        graph_runner.zero_gradients();
+        graph_runner.backward(&output_gradient);
+        let single: Vec<Tensor2D> = graph_runner
+            .parameter_gradients()
+            .into_iter()
+            .cloned()
+            .collect();
+
+        graph_runner.backward(&output_gradient);
+        for (single, double) in single.iter().zip(graph_runner.parameter_gradients()) {
+            for index in 0..single.len() {
+                assert!((2.0 * single.data[index] - double.data[index]).abs() < 0.00001);
+            }
+        }
+    }
+
+    #[test]
+    fn sgd_step() {
+        let mut parameter: Tensor2D = Tensor2D::new(1.0, 2, 2);
+        let gradient: Tensor2D = Tensor2D::new(0.5, 2, 2);
+
+        let mut optimizer: Optimizer = Optimizer::sgd(0.1);
+        optimizer.step(vec![&mut parameter], vec![&gradient]);
+
+        for index in 0..parameter.len() {
+            let expected: f32 = index as f32 - 0.1 * 0.5 * index as f32;
+            assert!((parameter.data[index] - expected).abs() < 0.00001);
+        }
+    }
+
+    // The bias correction makes the first Adam step learning_rate * sign(gradient)
+    #[test]
+    fn adam_first_step() {
+        let mut parameter: Tensor2D = Tensor2D::new(0.0, 1, 3);
+        let gradient: Tensor2D = Tensor2D {
+            data: vec![2.0, -0.5, 0.0],
+            row_count: 1,
+            column_count: 3,
+        };
+
+        let mut optimizer: Optimizer = Optimizer::adam(0.01);
+        optimizer.step(vec![&mut parameter], vec![&gradient]);
+
+        assert!((parameter.data[0] + 0.01).abs() < 0.00001);
+        assert!((parameter.data[1] - 0.01).abs() < 0.00001);
+        assert_eq!(parameter.data[2], 0.0);
+    }
+
+    fn train(optimizer: &mut Optimizer, fuse_operators: bool) {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(13);
+        let graph_nodes: Vec<GraphNode> = mlp_graph(&mut rng, 1);
+        let target: Tensor2D = one_hot(1, 5, 3);
+
+        let mut graph_runner: GraphRunner =
+            GraphRunner::from_graph_nodes(&graph_nodes, fuse_operators);
+        let initial_loss: f32 = graph_runner.train_step(&target, optimizer);
+        for _ in 0..200 {
+            graph_runner.train_step(&target, optimizer);
+        }
+        let final_loss: f32 = cross_entropy(&graph_runner.run(), &target);
+
+        assert!(
+            final_loss < 0.1 * initial_loss,
+            "\ninitial loss: {} final loss: {}",
+            initial_loss,
+            final_loss
+        );
+    }
+
+    #[test]
+    fn train_mlp_sgd() {
+        for fuse_operators in [false, true] {
+            train(&mut Optimizer::sgd(0.1), fuse_operators);
+        }
+    }
+
+    #[test]
+    fn train_mlp_adam() {
+        for fuse_operators in [false, true] {
+            train(&mut Optimizer::adam(0.01), fuse_operators);
+        }
+    }
+}
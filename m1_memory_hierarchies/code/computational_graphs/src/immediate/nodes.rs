@@ -4,9 +4,10 @@ use wgpu::{
 };
 
 use crate::shared::{
+    gpu_reduction::{plan_reduction, GlobalReduction, ReductionPass},
     gpu_utilities::{create_bind_group, create_compute_pipeline, create_shader_module, GPUHandles},
     tensor2d::Tensor2D,
-    tensor2d_gpu::{LinearUniform, ReluUniform, SoftmaxUniform, SumUniform, Tensor2DGPU},
+    tensor2d_gpu::{LinearUniform, ReductionUniform, ReluUniform, SoftmaxUniform, Tensor2DGPU},
 };
 
 pub async fn linear(
@@ -18,8 +19,8 @@ pub async fn linear(
     output: &mut Tensor2DGPU,
 ) {
     let block_size: usize = 8;
-    let launch_blocks_x: u32 = ((output.row_count + block_size - 1) / block_size) as u32;
-    let launch_blocks_y: u32 = ((output.column_count + block_size - 1) / block_size) as u32;
+    let launch_blocks_x: u32 = output.row_count.div_ceil(block_size) as u32;
+    let launch_blocks_y: u32 = output.column_count.div_ceil(block_size) as u32;
 
     let uniform_device: LinearUniform = LinearUniform::from_tensor_2d_gpu(
         gpu_handles,
@@ -270,39 +271,111 @@ pub async fn relu_inplace(gpu_handles: &GPUHandles, data_device: &mut Tensor2DGP
     data_device.retrieve_results().await;
 }
 
-// Do an alternative version that does tree reduction with a loop, but with a single shader.
 pub async fn sum(
     gpu_handles: &GPUHandles,
     input_device: &Tensor2DGPU,
     output_device: &mut Tensor2DGPU,
 ) -> f32 {
-    let uniform_device: SumUniform =
-        SumUniform::new(gpu_handles, "Sum Uniform", input_device.len(), 1);
+    reduce(
+        gpu_handles,
+        GlobalReduction::Sum,
+        input_device,
+        output_device,
+    )
+    .await
+}
+
+pub async fn sum_from_tensor_2d(gpu_handles: &GPUHandles, input: &Tensor2D) -> f32 {
+    reduce_from_tensor_2d(gpu_handles, GlobalReduction::Sum, input).await
+}
+
+pub async fn reduce_from_tensor_2d(
+    gpu_handles: &GPUHandles,
+    reduction: GlobalReduction,
+    input: &Tensor2D,
+) -> f32 {
+    let input_device: Tensor2DGPU = Tensor2DGPU::from_tensor2d(gpu_handles, "input", input);
+    let output_element_count: usize = 1;
+    let mut output_device: Tensor2DGPU =
+        Tensor2DGPU::new(gpu_handles, "output", 0.0, output_element_count, 1);
+    reduce(gpu_handles, reduction, &input_device, &mut output_device).await
+}
+
+// Reduces every element of the input to a single value, which is written to
+// the first element of the output. The passes come from plan_reduction, with
+// the partial results of every pass but the last in buffers of their own.
+pub async fn reduce(
+    gpu_handles: &GPUHandles,
+    reduction: GlobalReduction,
+    input_device: &Tensor2DGPU,
+    output_device: &mut Tensor2DGPU,
+) -> f32 {
+    let passes: Vec<ReductionPass> = plan_reduction(input_device.len());
+    if passes.is_empty() {
+        return reduction.empty_value();
+    }
 
     let cs_module: ShaderModule =
         create_shader_module(gpu_handles, include_str!("../shared/shaders/sum.wgsl"));
     let compute_pipeline: ComputePipeline =
-        create_compute_pipeline(gpu_handles, &cs_module, "single_pass_sum");
-
-    // Instantiates the bind group, once again specifying the binding of buffers.
+        create_compute_pipeline(gpu_handles, &cs_module, reduction.entry_point());
     let bind_group_layout: BindGroupLayout = compute_pipeline.get_bind_group_layout(0);
-    let to_be_bound: Vec<(u32, BindingResource)> = vec![
-        (0, uniform_device.storage_buffer.as_entire_binding()),
-        (1, input_device.storage_buffer.as_entire_binding()),
-        (2, output_device.storage_buffer.as_entire_binding()),
-    ];
-    let bind_group: BindGroup = create_bind_group(gpu_handles, &bind_group_layout, to_be_bound);
+
+    let mut partial_results: Vec<Tensor2DGPU> = Vec::<Tensor2DGPU>::new();
+    for pass in &passes[..passes.len() - 1] {
+        partial_results.push(Tensor2DGPU::new(
+            gpu_handles,
+            "Reduction Partial Results",
+            0.0,
+            pass.workgroup_count,
+            1,
+        ));
+    }
 
     let mut encoder: CommandEncoder = gpu_handles
         .device
         .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-    {
+    for (pass_index, pass) in passes.iter().enumerate() {
+        let last_pass: bool = pass_index + 1 == passes.len();
+        let divisor: f32 = if last_pass && reduction == GlobalReduction::Mean {
+            input_device.len() as f32
+        } else {
+            1.0
+        };
+        let uniform_device: ReductionUniform = ReductionUniform::new(
+            gpu_handles,
+            "Reduction Uniform",
+            pass.element_count,
+            pass.workgroup_count,
+            divisor,
+        );
+
+        let input: &Tensor2DGPU = if pass_index == 0 {
+            input_device
+        } else {
+            &partial_results[pass_index - 1]
+        };
+        let output: &Tensor2DGPU = if last_pass {
+            output_device
+        } else {
+            &partial_results[pass_index]
+        };
+
+        // Instantiates the bind group, once again specifying the binding of buffers.
+        let to_be_bound: Vec<(u32, BindingResource)> = vec![
+            (0, uniform_device.storage_buffer.as_entire_binding()),
+            (1, input.storage_buffer.as_entire_binding()),
+            (2, output.storage_buffer.as_entire_binding()),
+        ];
+        let bind_group: BindGroup =
+            create_bind_group(gpu_handles, &bind_group_layout, to_be_bound);
+
         let mut cpass: ComputePass =
             encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
         cpass.set_pipeline(&compute_pipeline);
         cpass.set_bind_group(0, &bind_group, &[]);
-        cpass.insert_debug_marker("Sum Immediate");
-        cpass.dispatch_workgroups(1, 1, 1); // Number of cells to run, the (x,y,z) size of item being processed
+        cpass.insert_debug_marker("Reduction Immediate");
+        cpass.dispatch_workgroups(pass.workgroup_count as u32, 1, 1);
     }
     output_device.copy_from_gpu_mut(&mut encoder);
 
@@ -320,14 +393,6 @@ pub async fn sum(
     output_device.data.data[0]
 }
 
-pub async fn sum_from_tensor_2d(gpu_handles: &GPUHandles, input: &Tensor2D) -> f32 {
-    let input_device: Tensor2DGPU = Tensor2DGPU::from_tensor2d(gpu_handles, "input", input);
-    let output_element_count: usize = 1;
-    let mut output_device: Tensor2DGPU =
-        Tensor2DGPU::new(gpu_handles, "output", 0.0, output_element_count, 1);
-    sum(gpu_handles, &input_device, &mut output_device).await
-}
-
 pub async fn softmax_from_tensor_2d(
     gpu_handles: &GPUHandles,
     input: &Tensor2D,
@@ -426,7 +491,7 @@ pub async fn softmax(
         cpass.set_bind_group(0, &map_bind_group, &[]);
         cpass.insert_debug_marker("Softmax Immediate - Map");
         cpass.dispatch_workgroups(
-            ((input_device.len() + block_size - 1) / block_size) as u32,
+            input_device.len().div_ceil(block_size) as u32,
             1,
             1,
         ); // Number of cells to run, the (x,y,z) size of item being processed
@@ -584,10 +649,8 @@ pub async fn linear_relu_softmax_fused(
     let linear_entry_point: &str = "main_with_relu";
 
     let linear_block_size: usize = 8;
-    let linear_launch_blocks_x: u32 =
-        ((intermediate.row_count + linear_block_size - 1) / linear_block_size) as u32;
-    let linear_launch_blocks_y: u32 =
-        ((intermediate.column_count + linear_block_size - 1) / linear_block_size) as u32;
+    let linear_launch_blocks_x: u32 = intermediate.row_count.div_ceil(linear_block_size) as u32;
+    let linear_launch_blocks_y: u32 = intermediate.column_count.div_ceil(linear_block_size) as u32;
 
     let linear_uniform: LinearUniform = LinearUniform::from_tensor_2d_gpu(
         gpu_handles,
@@ -707,7 +770,7 @@ pub async fn linear_relu_softmax_fused(
         cpass.set_bind_group(0, &map_bind_group, &[]);
         cpass.insert_debug_marker("Softmax Immediate - Map");
         cpass.dispatch_workgroups(
-            ((intermediate.len() + block_size - 1) / block_size) as u32,
+            intermediate.len().div_ceil(block_size) as u32,
             1,
             1,
         ); // Number of cells to run, the (x,y,z) size of item being processed
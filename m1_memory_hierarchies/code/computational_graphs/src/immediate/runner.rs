@@ -2,6 +2,7 @@
 
 use crate::shared::{
     benchmark_plot::draw_benchmark_plot,
+    benchmark_report::save_benchmark_report,
     configuration::Configuration,
     gpu_utilities::GPUHandles,
     performance_measurement::{benchmark_function_vector_gpu, PerformanceMeasurements},
@@ -88,6 +89,13 @@ fn linear_benchmark(config: &Configuration, gpu_handles: &GPUHandles) {
 
     benchmark_function_vector_gpu(config, names, gpu_handles, functions, &mut all_measurements);
 
+    save_benchmark_report(
+        "Immediate Benchmark - Linear",
+        "benchmarks/immediate/",
+        "immediate_linear_benchmark.png",
+        &all_measurements,
+        Some(&gpu_handles.adapter_info),
+    );
     draw_benchmark_plot(
         "Immediate Benchmark - Linear",
         "benchmarks/immediate/",
@@ -195,6 +203,13 @@ fn relu_benchmark(config: &Configuration, gpu_handles: &GPUHandles) {
 
     benchmark_function_vector_gpu(config, names, gpu_handles, functions, &mut all_measurements);
 
+    save_benchmark_report(
+        "Immediate Benchmark - ReLu",
+        "benchmarks/immediate/",
+        "immediate_relu_benchmark.png",
+        &all_measurements,
+        Some(&gpu_handles.adapter_info),
+    );
     draw_benchmark_plot(
         "Immediate Benchmark - ReLu",
         "benchmarks/immediate/",
@@ -266,6 +281,13 @@ fn sum_benchmark(config: &Configuration, gpu_handles: &GPUHandles) {
 
     benchmark_function_vector_gpu(config, names, gpu_handles, functions, &mut all_measurements);
 
+    save_benchmark_report(
+        "Immediate Benchmark - Sum",
+        "benchmarks/immediate/",
+        "immediate_sum_benchmark.png",
+        &all_measurements,
+        Some(&gpu_handles.adapter_info),
+    );
     draw_benchmark_plot(
         "Immediate Benchmark - Sum",
         "benchmarks/immediate/",
@@ -333,6 +355,13 @@ fn softmax_benchmark(config: &Configuration, gpu_handles: &GPUHandles) {
 
     benchmark_function_vector_gpu(config, names, gpu_handles, functions, &mut all_measurements);
 
+    save_benchmark_report(
+        "Immediate Benchmark - Softmax",
+        "benchmarks/immediate/",
+        "immediate_softmax_benchmark.png",
+        &all_measurements,
+        Some(&gpu_handles.adapter_info),
+    );
     draw_benchmark_plot(
         "Immediate Benchmark - Softmax",
         "benchmarks/immediate/",
@@ -452,6 +481,13 @@ fn linear_relu_softmax_fused_benchmark(config: &Configuration, gpu_handles: &GPU
 
     benchmark_function_vector_gpu(config, names, gpu_handles, functions, &mut all_measurements);
 
+    save_benchmark_report(
+        "Immediate Benchmark - Linear/ReLU/Softmax Fused",
+        "benchmarks/immediate/",
+        "immediate_linear_relu_softmax_fused_benchmark.png",
+        &all_measurements,
+        Some(&gpu_handles.adapter_info),
+    );
     draw_benchmark_plot(
         "Immediate Benchmark - Linear/ReLU/Softmax Fused",
         "benchmarks/immediate/",
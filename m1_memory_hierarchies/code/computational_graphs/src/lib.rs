@@ -11,10 +11,8 @@ mod op_code_compiler;
 mod shared;
 mod cpu;
 
-use shared::{
-    configuration::Configuration,
-    gpu_utilities::{self, initialize_gpu, GPUHandles},
-};
+use graph::executor::Executor;
+use shared::{configuration::Configuration, gpu_utilities};
 
 pub async fn run() {
     env_logger::init();
@@ -25,11 +23,17 @@ pub async fn run() {
     let loop_count: usize = 25;
     let loop_range: Vec<usize> = (2u32..8u32).map(|x| 2usize.pow(x)).collect();
     let log_scale: bool = false;
-    let compatible_gpu_found: bool = pollster::block_on(gpu_utilities::self_test());
+    let software_gpu: bool = false; // Uses a software adapter such as LavaPipe, for machines without a GPU
+    let compatible_gpu_found: bool = pollster::block_on(gpu_utilities::self_test(software_gpu));
     let warmup_gpu: bool = true;
     let default_graph_layer_count: usize = 64; // Only used for benchmarking graph functions
     let default_graph_operator_size: usize = 256; // Only used for benchmarking graph functions
     let graph_depth_range: Vec<usize> = (2u32..8u32).map(|x| 2usize.pow(x)).collect();
+    let thread_count: usize = 0; // 0 uses one thread per logical core
+    let thread_count_range: Vec<usize> = (0u32..5u32).map(|x| 2usize.pow(x)).collect();
+    let batch_size_range: Vec<usize> = (0u32..8u32).map(|x| 2usize.pow(x)).collect(); // Only used for benchmarking batched graphs
+    let dump_graphs: bool = false; // Writes the compiled graphs as DOT and Mermaid to graph_exports/
+    let autotune_linear: bool = false; // Caches the fastest Linear kernel for this GPU in autotune/
 
     let configuration: Configuration = Configuration::build_gpu(
         debug_level,
@@ -42,16 +46,21 @@ pub async fn run() {
         default_graph_layer_count,
         default_graph_operator_size,
         graph_depth_range,
-    );
+    )
+    .with_thread_counts(thread_count, thread_count_range)
+    .with_batch_sizes(batch_size_range)
+    .with_graph_dumps(dump_graphs)
+    .with_linear_autotuning(autotune_linear)
+    .with_software_gpu(software_gpu);
     cpu::runner::execute(&configuration);
 
-    if configuration.compatible_gpu_found {
-        let gpu_handles: GPUHandles = initialize_gpu(configuration.warmup_gpu)
-            .await
-            .expect("Failed to acquire GPU Handles");
+    // Falls back to the CPU if the GPU can't be used, such as for LavaPipe without software_gpu
+    let executor: Executor = Executor::new(&configuration).await.with_cross_check(0.0001);
+    graph::executor::execute(&executor, &configuration);
 
-        pollster::block_on(immediate::runner::execute(&gpu_handles, &configuration));
-        pollster::block_on(graph::runner::execute(&gpu_handles, &configuration));
-        op_code_compiler::runner::compile_linear_shader(&gpu_handles, true);
+    if let Some(gpu_handles) = executor.gpu_handles() {
+        pollster::block_on(immediate::runner::execute(gpu_handles, &configuration));
+        pollster::block_on(graph::runner::execute(gpu_handles, &configuration));
+        op_code_compiler::runner::compile_linear_shader(gpu_handles, true);
     }
 }
@@ -1 +1,3 @@
+pub mod op_codes;
 pub mod runner;
+pub mod runner_tests;
@@ -0,0 +1,943 @@
+use crate::{
+    graph::{
+        fusion::{AxisOperator, ElementwiseOperator, FusedElementwise, FusedStep, FusionBase},
+        nodes_gpu::NodeOperatorGPU,
+    },
+    shared::tensor2d_axis::{Axis, AxisLayout},
+};
+
+// The building blocks of the generated shaders. Each op code is a snippet of WGSL
+// and the runner assembles them into a shader, in the order they are listed.
+// Op codes refer to dimensions by their plain name, such as output_row_count,
+// which is either read from the uniform at the start of every entry point,
+// or baked into the shader as a constant.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum OpCode {
+    // Module scope
+    Constants,
+    SharedMemory,
+    Helpers,
+    // Entry points
+    ThreadID,
+    IndexCheck,
+    OutputIndexCalculation,
+    ResultInstation,
+    MatrixMultiplicationLoop,
+    AddBias,
+    LoadInput,
+    ElementwiseOperation,
+    ReLU,
+    StoreResult,
+    IndexCheckClose,
+    Reduction,
+    Map,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShaderBinding {
+    pub name: String,
+    pub read_write: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShaderEntryPoint {
+    pub name: String,
+    pub workgroup_size: (u32, u32, u32),
+    pub op_codes: Vec<(OpCode, String)>,
+}
+
+// Everything the runner needs to generate a shader.
+// The uniform is always at binding 0 and the buffers are bound from binding 1 and up,
+// in the order they are listed, which is the same layout as the hand written shaders.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShaderDescription {
+    // Every field of the uniform is a u32
+    pub dimensions: Vec<String>,
+    pub bindings: Vec<ShaderBinding>,
+    pub module_op_codes: Vec<(OpCode, String)>,
+    pub entry_points: Vec<ShaderEntryPoint>,
+}
+
+fn binding(name: &str, read_write: bool) -> ShaderBinding {
+    ShaderBinding {
+        name: name.to_string(),
+        read_write,
+    }
+}
+
+fn op_code(op_code: OpCode, source: &str) -> (OpCode, String) {
+    (op_code, source.to_string())
+}
+
+// The row and column count of every tensor, in order
+pub fn tensor_dimensions(tensor_names: &[&str]) -> Vec<String> {
+    let mut dimensions: Vec<String> = Vec::<String>::new();
+    for name in tensor_names {
+        dimensions.push(format!("{}_row_count", name));
+        dimensions.push(format!("{}_column_count", name));
+    }
+    dimensions
+}
+
+// The values to bake into a shader created with tensor_dimensions
+pub fn tensor_dimension_values(shapes: &[(usize, usize)]) -> Vec<u32> {
+    let mut values: Vec<u32> = Vec::<u32>::new();
+    for (row_count, column_count) in shapes {
+        values.push(*row_count as u32);
+        values.push(*column_count as u32);
+    }
+    values
+}
+
+// Where an 8x8 block of threads computes an 8x8 block of the output
+fn matrix_thread_id() -> Vec<(OpCode, String)> {
+    vec![
+        op_code(
+            OpCode::ThreadID,
+            "    let output_row_index: u32 = global_id.x;
+    let output_column_index: u32 = global_id.y;
+",
+        ),
+        op_code(
+            OpCode::IndexCheck,
+            "    if (output_row_index < output_row_count && output_column_index < output_column_count) {
+",
+        ),
+        op_code(
+            OpCode::OutputIndexCalculation,
+            "        let output_index: u32 = output_row_index * output_column_count + output_column_index;
+",
+        ),
+    ]
+}
+
+fn linear_base() -> Vec<(OpCode, String)> {
+    vec![
+        op_code(OpCode::ResultInstation, "        var result: f32 = 0.0;\n"),
+        op_code(
+            OpCode::MatrixMultiplicationLoop,
+            "        for (var inner_dimension: u32 = 0u; inner_dimension < input_column_count; inner_dimension += 1u) {
+            result += input[output_row_index * input_column_count + inner_dimension] * weights[inner_dimension * weights_column_count + output_column_index];
+        }
+",
+        ),
+        op_code(OpCode::AddBias, "        result = result + bias[output_index];\n"),
+    ]
+}
+
+fn store_result() -> Vec<(OpCode, String)> {
+    vec![
+        op_code(
+            OpCode::StoreResult,
+            "        output[output_index] = result;\n",
+        ),
+        op_code(OpCode::IndexCheckClose, "    }\n"),
+    ]
+}
+
+fn relu_op_code() -> (OpCode, String) {
+    op_code(OpCode::ReLU, "        result = max(0.0, result);\n")
+}
+
+fn linear_entry_point(name: &str, with_relu: bool) -> ShaderEntryPoint {
+    let mut op_codes: Vec<(OpCode, String)> = matrix_thread_id();
+    op_codes.extend(linear_base());
+    if with_relu {
+        op_codes.push(relu_op_code());
+    }
+    op_codes.extend(store_result());
+
+    ShaderEntryPoint {
+        name: name.to_string(),
+        workgroup_size: (8, 8, 1),
+        op_codes,
+    }
+}
+
+// The represented shader is the same as found in shared::shaders::linear.wgsl
+pub fn linear(with_relu: bool) -> ShaderDescription {
+    ShaderDescription {
+        dimensions: tensor_dimensions(&["input", "weights", "bias", "output"]),
+        bindings: vec![
+            binding("input", false),
+            binding("weights", false),
+            binding("bias", false),
+            binding("output", true),
+        ],
+        module_op_codes: vec![op_code(OpCode::Constants, "const BLOCK_SIZE: u32 = 8u;\n")],
+        entry_points: vec![linear_entry_point("main", with_relu)],
+    }
+}
+
+// The represented shader is the same as found in shared::shaders::relu.wgsl
+pub fn relu() -> ShaderDescription {
+    ShaderDescription {
+        dimensions: tensor_dimensions(&["data"]),
+        bindings: vec![binding("input", false), binding("output", true)],
+        module_op_codes: vec![],
+        entry_points: vec![ShaderEntryPoint {
+            name: "main".to_string(),
+            workgroup_size: (32, 1, 1),
+            op_codes: vec![
+                op_code(
+                    OpCode::ThreadID,
+                    "    let data_row_index: u32 = global_id.x;
+    let data_column_index: u32 = global_id.y;
+",
+                ),
+                op_code(
+                    OpCode::IndexCheck,
+                    "    if (data_row_index < data_row_count && data_column_index < data_column_count) {
+",
+                ),
+                op_code(
+                    OpCode::OutputIndexCalculation,
+                    "        let output_index: u32 = data_row_index * data_column_count + data_column_index;
+",
+                ),
+                op_code(OpCode::LoadInput, "        var result: f32 = input[output_index];\n"),
+                relu_op_code(),
+                op_code(OpCode::StoreResult, "        output[output_index] = result;\n"),
+                op_code(OpCode::IndexCheckClose, "    }\n"),
+            ],
+        }],
+    }
+}
+
+fn reduction_module_op_codes() -> Vec<(OpCode, String)> {
+    vec![
+        op_code(OpCode::Constants, "const BLOCK_SIZE: u32 = 32u;\n"),
+        op_code(
+            OpCode::SharedMemory,
+            "var<workgroup> shared_data: array<f32, BLOCK_SIZE>;\n",
+        ),
+    ]
+}
+
+// A single workgroup strides through every element, then thread 0 combines the
+// partial results from shared memory. The initialization declares the partial
+// result as value, which accumulate then updates with the element at index.
+fn reduction_entry_point(
+    name: &str,
+    element_count: &str,
+    initialization: &str,
+    accumulate: &str,
+    combine: &str,
+) -> ShaderEntryPoint {
+    let stride: String = format!(
+        "    let tid: u32 = local_id.x;
+    var elements_left: u32 = {};
+    var index: u32 = tid;
+{}    while (BLOCK_SIZE < elements_left) {{
+        value = {};
+        elements_left -= BLOCK_SIZE;
+        index += BLOCK_SIZE;
+    }}
+    if (tid < elements_left) {{
+        value = {};
+    }}
+
+    shared_data[tid] = value;
+    workgroupBarrier();
+",
+        element_count, initialization, accumulate, accumulate
+    );
+
+    ShaderEntryPoint {
+        name: name.to_string(),
+        workgroup_size: (32, 1, 1),
+        op_codes: vec![
+            (OpCode::Reduction, stride),
+            op_code(OpCode::StoreResult, combine),
+        ],
+    }
+}
+
+// One workgroup reduces each segment of segment_length elements,
+// so a batch of samples stacked one after the other gets a softmax per sample
+fn softmax_entry_points(input: &str, output: &str) -> Vec<ShaderEntryPoint> {
+    let segment_start: &str = "    let segment_start: u32 = group_id.x * segment_length;\n";
+
+    vec![
+        reduction_entry_point(
+            "single_pass_max",
+            "segment_length",
+            &format!(
+                "{}    var value: f32 = -3.00282346638528859812e+37f;\n",
+                segment_start
+            ),
+            &format!("max(value, {}[segment_start + index])", input),
+            "    if (tid == 0u) {
+        var max_value: f32 = shared_data[0];
+        var index: u32 = 1u;
+        while (index < BLOCK_SIZE) {
+            max_value = max(max_value, shared_data[index]);
+            index++;
+        }
+        global_max[group_id.x] = max_value;
+    }
+",
+        ),
+        reduction_entry_point(
+            "single_pass_sum",
+            "segment_length",
+            &format!(
+                "{}    var value: f32 = 0.0;\n    let max_value: f32 = global_max[group_id.x];\n",
+                segment_start
+            ),
+            &format!("value + exp({}[segment_start + index] - max_value)", input),
+            "    if (tid == 0u) {
+        var sum_value: f32 = 0.0;
+        var index: u32 = 0u;
+        while (index < BLOCK_SIZE) {
+            sum_value += shared_data[index];
+            index++;
+        }
+        global_offset[group_id.x] = max_value + log(sum_value);
+    }
+",
+        ),
+        ShaderEntryPoint {
+            name: "map".to_string(),
+            workgroup_size: (32, 1, 1),
+            op_codes: vec![
+                op_code(
+                    OpCode::ThreadID,
+                    "    let index: u32 = group_id.x * BLOCK_SIZE + local_id.x;\n",
+                ),
+                op_code(OpCode::IndexCheck, "    if (index < element_count) {\n"),
+                (
+                    OpCode::Map,
+                    format!(
+                        "        {}[index] = exp({}[index] - global_offset[index / segment_length]);\n",
+                        output, input
+                    ),
+                ),
+                op_code(OpCode::IndexCheckClose, "    }\n"),
+            ],
+        },
+    ]
+}
+
+// The represented shader is the same as found in shared::shaders::softmax.wgsl
+pub fn softmax() -> ShaderDescription {
+    ShaderDescription {
+        dimensions: vec!["element_count".to_string(), "segment_length".to_string()],
+        bindings: vec![
+            binding("input", false),
+            binding("global_max", true),
+            binding("global_offset", true),
+            binding("output", true),
+        ],
+        module_op_codes: reduction_module_op_codes(),
+        entry_points: softmax_entry_points("input", "output"),
+    }
+}
+
+// The represented shader is the same as found in shared::shaders::sum.wgsl
+pub fn sum() -> ShaderDescription {
+    let sum_combine: &str = "    if (tid == 0u) {
+        var sum_value: f32 = 0.0;
+        var index: u32 = 0u;
+        while (index < BLOCK_SIZE) {
+            sum_value += shared_data[index];
+            index++;
+        }
+        output[0] = sum_value;
+    }
+";
+
+    // Every workgroup sums two blocks of 32 elements into output[group_id.x]
+    let global_phase: ShaderEntryPoint = ShaderEntryPoint {
+        name: "global_phase".to_string(),
+        workgroup_size: (32, 1, 1),
+        op_codes: vec![
+            op_code(
+                OpCode::ThreadID,
+                "    let tid: u32 = local_id.x;
+    var index: u32 = group_id.x * BLOCK_SIZE + local_id.x;
+",
+            ),
+            op_code(OpCode::IndexCheck, "    if (index < element_count) {\n"),
+            op_code(
+                OpCode::Reduction,
+                "        shared_data[tid] = data[index];
+        index += block_count * BLOCK_SIZE;
+        if (index < element_count) {
+            shared_data[tid] += data[index];
+        }
+",
+            ),
+            op_code(
+                OpCode::StoreResult,
+                "        if (tid == 0u) {
+            var sum: f32 = 0.0;
+            for (var index: u32 = 0u; index < BLOCK_SIZE; index += 1u) {
+                sum += shared_data[index];
+            }
+            output[group_id.x] = sum;
+        }
+",
+            ),
+            op_code(OpCode::IndexCheckClose, "    }\n"),
+        ],
+    };
+
+    ShaderDescription {
+        dimensions: vec!["element_count".to_string(), "block_count".to_string()],
+        bindings: vec![binding("data", false), binding("output", true)],
+        module_op_codes: reduction_module_op_codes(),
+        entry_points: vec![
+            global_phase,
+            reduction_entry_point(
+                "workgroup_phase",
+                "block_count",
+                "    var value: f32 = 0.0;\n",
+                "value + data[index]",
+                sum_combine,
+            ),
+            reduction_entry_point(
+                "single_pass_sum",
+                "element_count",
+                "    var value: f32 = 0.0;\n",
+                "value + data[index]",
+                sum_combine,
+            ),
+        ],
+    }
+}
+
+// The represented shader is the same as found in shared::shaders::subtraction.wgsl
+pub fn subtraction() -> ShaderDescription {
+    let mut op_codes: Vec<(OpCode, String)> = matrix_thread_id();
+    op_codes.push(op_code(
+        OpCode::ElementwiseOperation,
+        "        var result: f32 = tensor_a[output_index] - tensor_b[output_index];\n",
+    ));
+    op_codes.extend(store_result());
+
+    ShaderDescription {
+        dimensions: tensor_dimensions(&["tensor_a", "tensor_b", "output"]),
+        bindings: vec![
+            binding("tensor_a", false),
+            binding("tensor_b", false),
+            binding("output", true),
+        ],
+        module_op_codes: vec![],
+        entry_points: vec![ShaderEntryPoint {
+            name: "main".to_string(),
+            workgroup_size: (32, 1, 1),
+            op_codes,
+        }],
+    }
+}
+
+// The same broadcasting as in shared::shaders::elementwise.wgsl
+fn broadcast_helper() -> (OpCode, String) {
+    op_code(
+        OpCode::Helpers,
+        "fn broadcast_index(row_index: u32, column_index: u32, row_count: u32, column_count: u32) -> u32 {
+    let row: u32 = select(row_index, 0u, row_count == 1u);
+    let column: u32 = select(column_index, 0u, column_count == 1u);
+    return row * column_count + column;
+}
+",
+    )
+}
+
+// Reads the element of a possibly broadcast tensor for the current thread
+fn broadcast_read(name: &str) -> String {
+    format!(
+        "{}[broadcast_index(output_row_index, output_column_index, {}_row_count, {}_column_count)]",
+        name, name, name
+    )
+}
+
+fn operator_symbol(operator: ElementwiseOperator) -> &'static str {
+    match operator {
+        ElementwiseOperator::ReLU => panic!("ReLU has no symbol in op_codes::operator_symbol"),
+        ElementwiseOperator::Add => "+",
+        ElementwiseOperator::Subtract => "-",
+        ElementwiseOperator::Multiply => "*",
+        ElementwiseOperator::Divide => "/",
+    }
+}
+
+// The entry point names of shared::shaders::elementwise.wgsl
+pub fn elementwise_entry_point_name(operator: ElementwiseOperator) -> &'static str {
+    match operator {
+        ElementwiseOperator::ReLU => "relu",
+        ElementwiseOperator::Add => "add",
+        ElementwiseOperator::Subtract => "subtract",
+        ElementwiseOperator::Multiply => "multiply",
+        ElementwiseOperator::Divide => "divide",
+    }
+}
+
+// The represented shader is the same as found in shared::shaders::elementwise.wgsl,
+// with an entry point for each of the binary operators.
+pub fn elementwise(operators: &[ElementwiseOperator]) -> ShaderDescription {
+    let mut entry_points: Vec<ShaderEntryPoint> = Vec::<ShaderEntryPoint>::new();
+    for operator in operators {
+        if !operator.is_binary() {
+            panic!(
+                "op_codes::elementwise received {:?}, which is not a binary operator",
+                operator
+            );
+        }
+
+        entry_points.push(ShaderEntryPoint {
+            name: elementwise_entry_point_name(*operator).to_string(),
+            workgroup_size: (32, 1, 1),
+            op_codes: vec![
+                op_code(
+                    OpCode::ThreadID,
+                    "    let output_index: u32 = global_id.x;\n",
+                ),
+                op_code(
+                    OpCode::IndexCheck,
+                    "    if (output_index < output_row_count * output_column_count) {\n",
+                ),
+                op_code(
+                    OpCode::OutputIndexCalculation,
+                    "        let output_row_index: u32 = output_index / output_column_count;
+        let output_column_index: u32 = output_index % output_column_count;
+",
+                ),
+                (
+                    OpCode::ElementwiseOperation,
+                    format!(
+                        "        var result: f32 = {} {} {};\n",
+                        broadcast_read("left"),
+                        operator_symbol(*operator),
+                        broadcast_read("right")
+                    ),
+                ),
+                op_code(
+                    OpCode::StoreResult,
+                    "        output[output_index] = result;\n",
+                ),
+                op_code(OpCode::IndexCheckClose, "    }\n"),
+            ],
+        });
+    }
+
+    ShaderDescription {
+        dimensions: tensor_dimensions(&["left", "right", "output"]),
+        bindings: vec![
+            binding("left", false),
+            binding("right", false),
+            binding("output", true),
+        ],
+        module_op_codes: vec![broadcast_helper()],
+        entry_points,
+    }
+}
+
+// The values of an AxisLayout, in the same order as the uniform of shared::shaders::axis.wgsl
+pub fn axis_dimensions() -> Vec<String> {
+    [
+        "segment_count",
+        "segment_length",
+        "inner_count",
+        "outer_stride",
+        "inner_stride",
+        "element_stride",
+    ]
+    .iter()
+    .map(|dimension| dimension.to_string())
+    .collect()
+}
+
+pub fn axis_dimension_values(layout: &AxisLayout) -> Vec<u32> {
+    vec![
+        layout.segment_count as u32,
+        layout.segment_length as u32,
+        layout.inner_count as u32,
+        layout.outer_stride as u32,
+        layout.inner_stride as u32,
+        layout.element_stride as u32,
+    ]
+}
+
+// The entry point names of shared::shaders::axis.wgsl
+pub fn axis_entry_point_name(operator: AxisOperator) -> &'static str {
+    match operator {
+        AxisOperator::Softmax => "softmax",
+        AxisOperator::LogSoftmax => "log_softmax",
+        AxisOperator::Sum => "reduce_sum",
+        AxisOperator::Max => "reduce_max",
+        AxisOperator::Mean => "reduce_mean",
+        AxisOperator::ArgMax => "reduce_argmax",
+    }
+}
+
+// Goes through the elements of the segment, reading each one as value
+fn segment_loop(first_element: &str, body: &str) -> String {
+    format!(
+        "        for (var element: u32 = {}; element < segment_length; element += 1u) {{
+            let value: f32 = input[segment_start + element * element_stride];
+{}        }}
+",
+        first_element, body
+    )
+}
+
+// Softmax and LogSoftmax compute the offset of the segment, max + ln(sum(exp(value - max))),
+// while the reductions compute their result right away.
+fn axis_reduction(operator: AxisOperator) -> (OpCode, String) {
+    let source: String = match operator {
+        AxisOperator::Softmax | AxisOperator::LogSoftmax => format!(
+            "        var max_value: f32 = input[segment_start];
+{}        var sum: f32 = 0.0;
+{}        let offset: f32 = max_value + log(sum);
+",
+            segment_loop("1u", "            max_value = max(max_value, value);\n"),
+            segment_loop("0u", "            sum += exp(value - max_value);\n")
+        ),
+        AxisOperator::Sum | AxisOperator::Mean => {
+            let mut source: String = format!(
+                "        var result: f32 = 0.0;\n{}",
+                segment_loop("0u", "            result += value;\n")
+            );
+            if operator == AxisOperator::Mean {
+                source.push_str("        result = result / f32(segment_length);\n");
+            }
+            source
+        }
+        AxisOperator::Max => format!(
+            "        var result: f32 = input[segment_start];\n{}",
+            segment_loop("1u", "            result = max(result, value);\n")
+        ),
+        // The position of the first maximum in the segment
+        AxisOperator::ArgMax => format!(
+            "        var max_value: f32 = input[segment_start];
+        var max_element: u32 = 0u;
+{}        var result: f32 = f32(max_element);
+",
+            segment_loop(
+                "1u",
+                "            if (max_value < value) {
+                max_value = value;
+                max_element = element;
+            }
+"
+            )
+        ),
+    };
+    (OpCode::Reduction, source)
+}
+
+// One invocation per segment, as in shared::shaders::axis.wgsl. Softmax and LogSoftmax
+// go through the segment once more to map every element, while the reductions
+// store a single value at the index of the segment. The steps are applied to
+// every value before it is stored.
+fn axis_entry_point(
+    name: &str,
+    operator: AxisOperator,
+    steps: Vec<(OpCode, String)>,
+) -> ShaderEntryPoint {
+    let mut op_codes: Vec<(OpCode, String)> = vec![
+        op_code(OpCode::ThreadID, "    let segment: u32 = global_id.x;\n"),
+        op_code(OpCode::IndexCheck, "    if (segment < segment_count) {\n"),
+        op_code(
+            OpCode::OutputIndexCalculation,
+            "        let segment_start: u32 = (segment / inner_count) * outer_stride + (segment % inner_count) * inner_stride;
+",
+        ),
+        axis_reduction(operator),
+    ];
+
+    let store: &str = match operator {
+        AxisOperator::Softmax | AxisOperator::LogSoftmax => {
+            let mapped: &str = if operator == AxisOperator::Softmax {
+                "exp(input[output_index] - offset)"
+            } else {
+                "input[output_index] - offset"
+            };
+            op_codes.push((
+                OpCode::Map,
+                format!(
+                    "        for (var element: u32 = 0u; element < segment_length; element += 1u) {{
+            let output_index: u32 = segment_start + element * element_stride;
+            var result: f32 = {};
+",
+                    mapped
+                ),
+            ));
+            "            output[output_index] = result;\n        }\n"
+        }
+        _ => {
+            op_codes.push(op_code(
+                OpCode::OutputIndexCalculation,
+                "        let output_index: u32 = segment;\n",
+            ));
+            "        output[output_index] = result;\n"
+        }
+    };
+
+    op_codes.extend(steps);
+    op_codes.push(op_code(OpCode::StoreResult, store));
+    op_codes.push(op_code(OpCode::IndexCheckClose, "    }\n"));
+
+    ShaderEntryPoint {
+        name: name.to_string(),
+        workgroup_size: (32, 1, 1),
+        op_codes,
+    }
+}
+
+// The represented shader is the same as found in shared::shaders::axis.wgsl,
+// with an entry point for each of the operators.
+pub fn axis_operators(operators: &[AxisOperator]) -> ShaderDescription {
+    ShaderDescription {
+        dimensions: axis_dimensions(),
+        bindings: vec![binding("input", false), binding("output", true)],
+        module_op_codes: vec![],
+        entry_points: operators
+            .iter()
+            .map(|operator| axis_entry_point(axis_entry_point_name(*operator), *operator, vec![]))
+            .collect(),
+    }
+}
+
+fn fused_step(step: &FusedStep, operand_name: &str) -> (OpCode, String) {
+    if step.operator == ElementwiseOperator::ReLU {
+        return relu_op_code();
+    }
+
+    let symbol: &str = operator_symbol(step.operator);
+    let operand: String = broadcast_read(operand_name);
+    let source: String = if step.value_is_left {
+        format!("        result = result {} {};\n", symbol, operand)
+    } else {
+        format!("        result = {} {} result;\n", operand, symbol)
+    };
+    (OpCode::ElementwiseOperation, source)
+}
+
+// The tensors bound by a fused elementwise shader, in the same order as the buffers
+// of the node running it.
+fn fused_elementwise_tensor_names(fusion: &FusedElementwise) -> Vec<String> {
+    let mut names: Vec<String> = vec!["input".to_string()];
+    if fusion.base == FusionBase::Linear {
+        names.push("weights".to_string());
+        names.push("bias".to_string());
+    }
+    for operand_index in 0..fusion.operand_count() {
+        names.push(format!("operand_{}", operand_index));
+    }
+    names.push("output".to_string());
+    names
+}
+
+// Computes the base of the fusion, followed by every step, for each element of the output.
+// This reuses the op codes of the linear shader, handing the result over to the steps
+// before storing it. A Load base skips the matrix multiplication and reads its input instead.
+// An Axis base reuses the op codes of the axis shader instead, with one invocation per segment,
+// and has the values of its layout after the dimensions of the tensors.
+pub fn fused_elementwise(fusion: &FusedElementwise) -> ShaderDescription {
+    let tensor_names: Vec<String> = fused_elementwise_tensor_names(fusion);
+
+    let mut steps: Vec<(OpCode, String)> = Vec::<(OpCode, String)>::new();
+    let mut operand_index: usize = 0;
+    for step in &fusion.steps {
+        steps.push(fused_step(step, &format!("operand_{}", operand_index)));
+        if step.operator.is_binary() {
+            operand_index += 1;
+        }
+    }
+
+    let names: Vec<&str> = tensor_names.iter().map(|name| name.as_str()).collect();
+    let bindings: Vec<ShaderBinding> = names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| binding(name, index == names.len() - 1))
+        .collect();
+
+    let mut dimensions: Vec<String> = tensor_dimensions(&names);
+    let (module_op_codes, entry_point): (Vec<(OpCode, String)>, ShaderEntryPoint) =
+        match fusion.base {
+            FusionBase::Linear | FusionBase::Load => {
+                let mut op_codes: Vec<(OpCode, String)> = matrix_thread_id();
+                if fusion.base == FusionBase::Linear {
+                    op_codes.extend(linear_base());
+                } else {
+                    op_codes.push((
+                        OpCode::LoadInput,
+                        format!("        var result: f32 = {};\n", broadcast_read("input")),
+                    ));
+                }
+                op_codes.extend(steps);
+                op_codes.extend(store_result());
+
+                (
+                    vec![
+                        op_code(OpCode::Constants, "const BLOCK_SIZE: u32 = 8u;\n"),
+                        broadcast_helper(),
+                    ],
+                    ShaderEntryPoint {
+                        name: "main".to_string(),
+                        workgroup_size: (8, 8, 1),
+                        op_codes,
+                    },
+                )
+            }
+            FusionBase::Axis { operator, .. } => {
+                dimensions.extend(axis_dimensions());
+
+                // The operands are read for the row and column the value is stored at
+                let mut axis_steps: Vec<(OpCode, String)> = vec![op_code(
+                    OpCode::OutputIndexCalculation,
+                    "        let output_row_index: u32 = output_index / output_column_count;
+        let output_column_index: u32 = output_index % output_column_count;
+",
+                )];
+                axis_steps.extend(steps);
+
+                (
+                    vec![broadcast_helper()],
+                    axis_entry_point("main", operator, axis_steps),
+                )
+            }
+        };
+
+    ShaderDescription {
+        dimensions,
+        bindings,
+        module_op_codes,
+        entry_points: vec![entry_point],
+    }
+}
+
+// The linear layer with ReLU and the three passes of softmax in a single module.
+// The linear layer writes to output, which softmax then reads from.
+// Note that the softmax reductions use a block size of 32 while the linear layer uses 8x8.
+pub fn linear_relu_softmax() -> ShaderDescription {
+    let mut dimensions: Vec<String> = tensor_dimensions(&["input", "weights", "bias", "output"]);
+    dimensions.push("element_count".to_string());
+    dimensions.push("segment_length".to_string());
+
+    let mut entry_points: Vec<ShaderEntryPoint> = vec![linear_entry_point("linear_relu", true)];
+    entry_points.extend(softmax_entry_points("output", "softmax_output"));
+
+    ShaderDescription {
+        dimensions,
+        bindings: vec![
+            binding("input", false),
+            binding("weights", false),
+            binding("bias", false),
+            binding("output", true),
+            binding("global_max", true),
+            binding("global_offset", true),
+            binding("softmax_output", true),
+        ],
+        module_op_codes: reduction_module_op_codes(),
+        entry_points,
+    }
+}
+
+// The description of the shader running each operator. Transfers are
+// buffer copies and don't have a shader. QuantizedLinear runs the handwritten
+// linear_packed.wgsl, which isn't described here. FusedElementwise needs its fusion.
+// The axis is that of the node, Softmax without one normalizes the whole tensor.
+pub fn describe_operator(
+    operator: &NodeOperatorGPU,
+    fusion: Option<&FusedElementwise>,
+    axis: Option<Axis>,
+) -> Option<ShaderDescription> {
+    match operator {
+        NodeOperatorGPU::HostToDevice
+        | NodeOperatorGPU::DeviceToHost
+        | NodeOperatorGPU::DeviceToDevice
+        | NodeOperatorGPU::QuantizedLinear => None,
+        NodeOperatorGPU::Linear => Some(linear(false)),
+        NodeOperatorGPU::LinearReLU => Some(linear(true)),
+        NodeOperatorGPU::ReLU => Some(relu()),
+        NodeOperatorGPU::Softmax => match axis {
+            Some(Axis::Rows) | Some(Axis::Columns) => {
+                Some(axis_operators(&[AxisOperator::Softmax]))
+            }
+            Some(Axis::Global) | None => Some(softmax()),
+        },
+        NodeOperatorGPU::LogSoftmax => Some(axis_operators(&[AxisOperator::LogSoftmax])),
+        NodeOperatorGPU::Sum => Some(axis_operators(&[AxisOperator::Sum])),
+        NodeOperatorGPU::Max => Some(axis_operators(&[AxisOperator::Max])),
+        NodeOperatorGPU::Mean => Some(axis_operators(&[AxisOperator::Mean])),
+        NodeOperatorGPU::ArgMax => Some(axis_operators(&[AxisOperator::ArgMax])),
+        NodeOperatorGPU::LinearReLUSoftmax => Some(linear_relu_softmax()),
+        NodeOperatorGPU::Add => Some(elementwise(&[ElementwiseOperator::Add])),
+        NodeOperatorGPU::Subtract => Some(elementwise(&[ElementwiseOperator::Subtract])),
+        NodeOperatorGPU::Multiply => Some(elementwise(&[ElementwiseOperator::Multiply])),
+        NodeOperatorGPU::Divide => Some(elementwise(&[ElementwiseOperator::Divide])),
+        NodeOperatorGPU::FusedElementwise => match fusion {
+            Some(fusion) => Some(fused_elementwise(fusion)),
+            None => panic!("op_codes::describe_operator needs the fusion of a FusedElementwise"),
+        },
+    }
+}
+
+// A node runs a single sample
+fn single_sample_layout(axis: Option<Axis>, shape: (usize, usize)) -> AxisLayout {
+    let axis: Axis = axis
+        .unwrap_or_else(|| panic!("op_codes::dimension_values needs the axis of an axis operator"));
+    AxisLayout::new(shape.0, shape.1, axis, 1)
+}
+
+// The values of the dimensions of describe_operator, from the shapes of the buffers
+// of the node running the operator, in the same order as its buffer indices.
+pub fn dimension_values(
+    operator: &NodeOperatorGPU,
+    fusion: Option<&FusedElementwise>,
+    axis: Option<Axis>,
+    shapes: &[(usize, usize)],
+) -> Vec<u32> {
+    match operator {
+        NodeOperatorGPU::HostToDevice
+        | NodeOperatorGPU::DeviceToHost
+        | NodeOperatorGPU::DeviceToDevice
+        | NodeOperatorGPU::QuantizedLinear => vec![],
+        NodeOperatorGPU::ReLU => tensor_dimension_values(&shapes[..1]),
+        NodeOperatorGPU::Softmax if matches!(axis, Some(Axis::Rows) | Some(Axis::Columns)) => {
+            axis_dimension_values(&single_sample_layout(axis, shapes[0]))
+        }
+        // A node runs a single sample, which is a single segment
+        NodeOperatorGPU::Softmax => {
+            let element_count: u32 = (shapes[0].0 * shapes[0].1) as u32;
+            vec![element_count, element_count]
+        }
+        NodeOperatorGPU::LogSoftmax
+        | NodeOperatorGPU::Sum
+        | NodeOperatorGPU::Max
+        | NodeOperatorGPU::Mean
+        | NodeOperatorGPU::ArgMax => axis_dimension_values(&single_sample_layout(axis, shapes[0])),
+        // The linear layer writes to an intermediate buffer with the shape of the bias
+        NodeOperatorGPU::LinearReLUSoftmax => {
+            let mut values: Vec<u32> =
+                tensor_dimension_values(&[shapes[0], shapes[1], shapes[2], shapes[2]]);
+            let element_count: u32 = (shapes[2].0 * shapes[2].1) as u32;
+            values.push(element_count);
+            values.push(element_count);
+            values
+        }
+        NodeOperatorGPU::Linear
+        | NodeOperatorGPU::LinearReLU
+        | NodeOperatorGPU::Add
+        | NodeOperatorGPU::Subtract
+        | NodeOperatorGPU::Multiply
+        | NodeOperatorGPU::Divide => tensor_dimension_values(shapes),
+        NodeOperatorGPU::FusedElementwise => {
+            let fusion: &FusedElementwise = fusion.unwrap_or_else(|| {
+                panic!("op_codes::dimension_values needs the fusion of a FusedElementwise")
+            });
+            let mut values: Vec<u32> = tensor_dimension_values(shapes);
+            if let FusionBase::Axis { axis, .. } = fusion.base {
+                values.extend(axis_dimension_values(&single_sample_layout(
+                    Some(axis),
+                    shapes[0],
+                )));
+            }
+            values
+        }
+    }
+}
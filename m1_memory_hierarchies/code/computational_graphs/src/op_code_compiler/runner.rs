@@ -1,166 +1,161 @@
 use wgpu::ShaderModule;
 
-use crate::shared::gpu_utilities::{create_shader_module, GPUHandles};
-
-enum LinearOpCodes {
-    Uniform,
-    Bindings,
-    FunctionDefinition,
-    ThreadID,
-    IndexCheck,
-    OutputIndexCalculation,
-    ResultInstation,
-    MatrixMultiplicationLoop,
-    AddBias,
-    StoreResult,
-    IndexCheckClose,
-    FunctionClose,
+use crate::{
+    graph::{
+        fusion::{AxisOperator, FusedElementwise},
+        nodes_gpu::NodeOperatorGPU,
+    },
+    shared::{
+        gpu_utilities::{create_shader_module, GPUHandles},
+        tensor2d_axis::Axis,
+    },
+};
+
+use super::op_codes::{self, OpCode, ShaderDescription, ShaderEntryPoint};
+
+fn generate_uniform(description: &ShaderDescription) -> String {
+    let mut source: String = "struct TensorDimensions {\n".to_string();
+    for dimension in &description.dimensions {
+        source.push_str(&format!("    {}: u32,\n", dimension));
+    }
+    source.push_str("};\n\n@group(0) @binding(0)\nvar<uniform> dimensions: TensorDimensions;\n\n");
+    source
 }
 
-struct Linear {
-    op_codes: Vec<(LinearOpCodes, String)>,
+// Every dimension becomes a constant, which lets the shader compiler unroll loops and
+// fold the index calculations. The uniform is no longer used, so binding 0 is left out.
+fn generate_constants(description: &ShaderDescription, values: &[u32]) -> String {
+    if description.dimensions.len() != values.len() {
+        panic!(
+            "op_code_compiler::runner::generate_constants expected {} dimensions, received {}",
+            description.dimensions.len(),
+            values.len()
+        );
+    }
+
+    let mut source: String = "".to_string();
+    for (dimension, value) in description.dimensions.iter().zip(values) {
+        source.push_str(&format!("const {}: u32 = {}u;\n", dimension, value));
+    }
+    source.push('\n');
+    source
 }
 
-// The represented shader is the same as found in shared::shaders::linear.wgsl
-impl Linear {
-    pub fn new() -> Self {
-        let mut op_codes: Vec<(LinearOpCodes, String)> = vec![];
-        let uniform: (LinearOpCodes, String) = (
-            LinearOpCodes::Uniform,
-            "        
-            struct TensorDimensions {
-                input_row_count: u32,
-                input_column_count: u32,
-                weights_row_count: u32,
-                weights_column_count: u32,
-                bias_row_count: u32,
-                bias_column_count: u32,
-                output_row_count: u32,
-                output_column_count: u32,
-            };
-            "
-            .to_string(),
-        );
-        op_codes.push(uniform);
-
-        let bindings: (LinearOpCodes, String) = (
-            LinearOpCodes::Bindings,
-            "        
-            @group(0) @binding(0)
-            var<uniform> dimensions: TensorDimensions;
-            
-            @group(0) @binding(1)
-            var<storage, read> input: array<f32>;
-            
-            @group(0) @binding(2)
-            var<storage, read> weights: array<f32>;
-            
-            @group(0) @binding(3)
-            var<storage, read> bias: array<f32>;
-            
-            @group(0) @binding(4)
-            var<storage, read_write> output: array<f32>;
-            
-            "
-            .to_string(),
-        );
-        op_codes.push(bindings);
-
-        let function_definition: (LinearOpCodes, String) = (
-            LinearOpCodes::FunctionDefinition,
-            "        
-            const BLOCK_SIZE: u32 = 8u;
-            @compute @workgroup_size(8, 8, 1) 
-            fn main(
-                @builtin(global_invocation_id) global_id: vec3<u32>,
-                @builtin(workgroup_id) group_id: vec3<u32>, 
-                @builtin(local_invocation_id) local_id: vec3<u32>
-                ) {
-            "
-            .to_string(),
-        );
-        op_codes.push(function_definition);
-
-        let thread_id: (LinearOpCodes, String) = (
-            LinearOpCodes::ThreadID,
-            "        
-                let output_row_index: u32 = global_id.x;
-                let output_column_index: u32 = global_id.y;
-            "
-            .to_string(),
-        );
-        op_codes.push(thread_id);
+fn generate_bindings(description: &ShaderDescription) -> String {
+    let mut source: String = "".to_string();
+    for (index, binding) in description.bindings.iter().enumerate() {
+        let access: &str = if binding.read_write {
+            "read_write"
+        } else {
+            "read"
+        };
+        source.push_str(&format!(
+            "@group(0) @binding({})\nvar<storage, {}> {}: array<f32>;\n\n",
+            index + 1,
+            access,
+            binding.name
+        ));
+    }
+    source
+}
 
-        let index_check: (LinearOpCodes, String) = (LinearOpCodes::IndexCheck,
-            "        
-                if (output_row_index < dimensions.output_row_count && output_column_index < dimensions.output_column_count) {
-            ".to_string()
-        );
-        op_codes.push(index_check);
+fn generate_entry_point(
+    description: &ShaderDescription,
+    entry_point: &ShaderEntryPoint,
+    baked: bool,
+) -> String {
+    let (x, y, z): (u32, u32, u32) = entry_point.workgroup_size;
+    let mut source: String = format!(
+        "@compute @workgroup_size({}, {}, {})
+fn {}(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(workgroup_id) group_id: vec3<u32>,
+    @builtin(local_invocation_id) local_id: vec3<u32>
+    ) {{
+",
+        x, y, z, entry_point.name
+    );
+
+    // The op codes refer to the dimensions by name, without baking they come from the uniform
+    if !baked {
+        for dimension in &description.dimensions {
+            source.push_str(&format!(
+                "    let {}: u32 = dimensions.{};\n",
+                dimension, dimension
+            ));
+        }
+    }
 
-        let ouput_index_calculation: (LinearOpCodes, String) = (LinearOpCodes::OutputIndexCalculation,
-            "        
-                    let output_index: u32 = output_row_index * dimensions.output_column_count + output_column_index;
-            ".to_string()
-        );
-        op_codes.push(ouput_index_calculation);
-
-        let result_instantiation: (LinearOpCodes, String) = (
-            LinearOpCodes::ResultInstation,
-            "        
-                    var result: f32 = 0.0;
-            "
-            .to_string(),
-        );
-        op_codes.push(result_instantiation);
-
-        let matrix_multiplication_loop: (LinearOpCodes, String) = (LinearOpCodes::MatrixMultiplicationLoop,
-            "        
-                    for (var inner_dimension: u32 = 0u; inner_dimension < dimensions.input_column_count; inner_dimension += 1u) {
-                            result += input[output_row_index * dimensions.input_column_count + inner_dimension] * weights[inner_dimension * dimensions.weights_column_count + output_column_index];
-                    }
-            ".to_string()
-        );
-        op_codes.push(matrix_multiplication_loop);
-
-        let add_bias: (LinearOpCodes, String) = (
-            LinearOpCodes::AddBias,
-            "        
-                    result = result + bias[output_index];
-            "
-            .to_string(),
-        );
-        op_codes.push(add_bias);
-
-        let store_result: (LinearOpCodes, String) = (
-            LinearOpCodes::StoreResult,
-            "        
-                    output[output_index] = result;
-            "
-            .to_string(),
-        );
-        op_codes.push(store_result);
-
-        let index_check_close: (LinearOpCodes, String) = (
-            LinearOpCodes::IndexCheckClose,
-            "        
-                }
-            "
-            .to_string(),
-        );
-        op_codes.push(index_check_close);
-
-        let function_close: (LinearOpCodes, String) = (
-            LinearOpCodes::FunctionClose,
-            "        
-            }
-            "
-            .to_string(),
-        );
-        op_codes.push(function_close);
+    for op_code in &entry_point.op_codes {
+        source.push_str(op_code.1.as_str());
+    }
+    source.push_str("}\n\n");
+    source
+}
 
-        Self { op_codes }
+// Assembles the op codes of a description into a shader.
+// If baked_dimensions is given, the dimensions are compiled into the shader as constants,
+// which means the shader can only be used with tensors of exactly those dimensions.
+pub fn generate_shader(
+    description: &ShaderDescription,
+    baked_dimensions: Option<&[u32]>,
+) -> String {
+    let mut string_builder: String = "".to_string();
+
+    for op_code in &description.module_op_codes {
+        if let OpCode::Constants = op_code.0 {
+            string_builder.push_str(op_code.1.as_str());
+            string_builder.push('\n');
+        }
+    }
+
+    match baked_dimensions {
+        Some(values) => string_builder.push_str(&generate_constants(description, values)),
+        None => string_builder.push_str(&generate_uniform(description)),
+    }
+    string_builder.push_str(&generate_bindings(description));
+
+    for op_code in &description.module_op_codes {
+        if let OpCode::Constants = op_code.0 {
+            continue;
+        }
+        string_builder.push_str(op_code.1.as_str());
+        string_builder.push('\n');
     }
+
+    for entry_point in &description.entry_points {
+        string_builder.push_str(&generate_entry_point(
+            description,
+            entry_point,
+            baked_dimensions.is_some(),
+        ));
+    }
+
+    string_builder
+}
+
+pub fn compile_shader(
+    gpu_handles: &GPUHandles,
+    description: &ShaderDescription,
+    baked_dimensions: Option<&[u32]>,
+) -> ShaderModule {
+    create_shader_module(
+        gpu_handles,
+        generate_shader(description, baked_dimensions).as_str(),
+    )
+}
+
+// Emits a shader for a single node, specialized for the shapes of its buffers.
+// Returns None for the transfers, which don't run a shader.
+pub fn generate_specialized_shader(
+    operator: &NodeOperatorGPU,
+    fusion: Option<&FusedElementwise>,
+    axis: Option<Axis>,
+    shapes: &[(usize, usize)],
+) -> Option<String> {
+    let description: ShaderDescription = op_codes::describe_operator(operator, fusion, axis)?;
+    let values: Vec<u32> = op_codes::dimension_values(operator, fusion, axis, shapes);
+    Some(generate_shader(&description, Some(&values)))
 }
 
 // Note that the handover between linear layer and relu could be done in a more generalized
@@ -168,18 +163,30 @@ impl Linear {
 // this has been omitted, but it would look something like the Transfer and DeviceToDevice
 // operators from the graph sections.
 pub fn compile_linear_shader(gpu_handles: &GPUHandles, with_relu: bool) -> ShaderModule {
-    let linear_op_codes: Linear = Linear::new();
+    compile_shader(gpu_handles, &op_codes::linear(with_relu), None)
+}
 
-    let mut string_builder: String = "".to_string();
-    for op_code in linear_op_codes.op_codes {
-        if with_relu {
-            if let LinearOpCodes::StoreResult = op_code.0 {
-                string_builder.push_str("        result = max(0.0, result);\n")
-            }
-        }
+// Softmax along an axis, LogSoftmax and the reductions in a single module,
+// with the same entry points as shared::shaders::axis.wgsl
+pub fn compile_axis_shader(gpu_handles: &GPUHandles) -> ShaderModule {
+    let operators: [AxisOperator; 6] = [
+        AxisOperator::Softmax,
+        AxisOperator::LogSoftmax,
+        AxisOperator::Sum,
+        AxisOperator::Max,
+        AxisOperator::Mean,
+        AxisOperator::ArgMax,
+    ];
+    compile_shader(gpu_handles, &op_codes::axis_operators(&operators), None)
+}
 
-        string_builder.push_str(op_code.1.as_str());
-    }
+pub fn generate_fused_elementwise_shader(fusion: &FusedElementwise) -> String {
+    generate_shader(&op_codes::fused_elementwise(fusion), None)
+}
 
-    create_shader_module(gpu_handles, string_builder.as_str())
+pub fn compile_fused_elementwise_shader(
+    gpu_handles: &GPUHandles,
+    fusion: &FusedElementwise,
+) -> ShaderModule {
+    compile_shader(gpu_handles, &op_codes::fused_elementwise(fusion), None)
 }
@@ -0,0 +1,367 @@
+#[cfg(test)]
+mod tests {
+    use naga::valid::{Capabilities, ValidationFlags, Validator};
+
+    use crate::{
+        graph::{
+            fusion::{AxisOperator, ElementwiseOperator, FusedElementwise, FusedStep, FusionBase},
+            nodes_gpu::NodeOperatorGPU,
+        },
+        op_code_compiler::{
+            op_codes::{self, tensor_dimension_values, ShaderDescription},
+            runner::{
+                generate_fused_elementwise_shader, generate_shader, generate_specialized_shader,
+            },
+        },
+        shared::tensor2d_axis::Axis,
+    };
+
+    const OPERATORS: [NodeOperatorGPU; 19] = [
+        NodeOperatorGPU::HostToDevice,
+        NodeOperatorGPU::DeviceToHost,
+        NodeOperatorGPU::DeviceToDevice,
+        NodeOperatorGPU::Linear,
+        NodeOperatorGPU::ReLU,
+        NodeOperatorGPU::Softmax,
+        NodeOperatorGPU::LinearReLU,
+        NodeOperatorGPU::LinearReLUSoftmax,
+        NodeOperatorGPU::Add,
+        NodeOperatorGPU::Subtract,
+        NodeOperatorGPU::Multiply,
+        NodeOperatorGPU::Divide,
+        NodeOperatorGPU::FusedElementwise,
+        NodeOperatorGPU::LogSoftmax,
+        NodeOperatorGPU::Sum,
+        NodeOperatorGPU::Max,
+        NodeOperatorGPU::Mean,
+        NodeOperatorGPU::ArgMax,
+        NodeOperatorGPU::QuantizedLinear,
+    ];
+
+    const AXIS_OPERATORS: [AxisOperator; 6] = [
+        AxisOperator::Softmax,
+        AxisOperator::LogSoftmax,
+        AxisOperator::Sum,
+        AxisOperator::Max,
+        AxisOperator::Mean,
+        AxisOperator::ArgMax,
+    ];
+
+    // Parses and validates the generated WGSL without needing a GPU
+    fn validate_shader(source: &str) -> naga::Module {
+        let module: naga::Module = match naga::front::wgsl::parse_str(source) {
+            Ok(module) => module,
+            Err(error) => panic!("{}\n{}", error.emit_to_string(source), source),
+        };
+
+        let mut validator: Validator =
+            Validator::new(ValidationFlags::all(), Capabilities::empty());
+        if let Err(error) = validator.validate(&module) {
+            panic!("{:?}\n{}", error, source);
+        }
+
+        module
+    }
+
+    // Validates the shader both with the uniform and with the dimensions baked in,
+    // and checks that it has the entry points of the description
+    fn validate_description(description: &ShaderDescription) {
+        let values: Vec<u32> = (0..description.dimensions.len())
+            .map(|index| index as u32 + 3)
+            .collect();
+
+        for baked_dimensions in [None, Some(values.as_slice())] {
+            let module: naga::Module =
+                validate_shader(&generate_shader(description, baked_dimensions));
+
+            let names: Vec<&str> = module
+                .entry_points
+                .iter()
+                .map(|entry_point| entry_point.name.as_str())
+                .collect();
+            let expected: Vec<&str> = description
+                .entry_points
+                .iter()
+                .map(|entry_point| entry_point.name.as_str())
+                .collect();
+            assert_eq!(names, expected);
+        }
+    }
+
+    fn step(operator: ElementwiseOperator, value_is_left: bool) -> FusedStep {
+        FusedStep {
+            operator,
+            value_is_left,
+        }
+    }
+
+    fn chain_fusion() -> FusedElementwise {
+        FusedElementwise {
+            base: FusionBase::Linear,
+            steps: vec![
+                step(ElementwiseOperator::ReLU, true),
+                step(ElementwiseOperator::Subtract, false),
+                step(ElementwiseOperator::Multiply, true),
+                step(ElementwiseOperator::Divide, false),
+            ],
+        }
+    }
+
+    // Only the transfers and the hand written packed linear layer aren't compiled
+    fn has_no_description(operator: &NodeOperatorGPU) -> bool {
+        matches!(
+            operator,
+            NodeOperatorGPU::HostToDevice
+                | NodeOperatorGPU::DeviceToHost
+                | NodeOperatorGPU::DeviceToDevice
+                | NodeOperatorGPU::QuantizedLinear
+        )
+    }
+
+    #[test]
+    fn every_operator_validates() {
+        let fusion: FusedElementwise = chain_fusion();
+        for operator in &OPERATORS {
+            for axis in [Axis::Global, Axis::Rows, Axis::Columns] {
+                match op_codes::describe_operator(operator, Some(&fusion), Some(axis)) {
+                    Some(description) => validate_description(&description),
+                    None => assert!(has_no_description(operator)),
+                }
+            }
+        }
+    }
+
+    // The generated axis shader replaces the hand written one, so it needs the same
+    // entry points and the same uniform
+    #[test]
+    fn axis_shaders_validate() {
+        let description: ShaderDescription = op_codes::axis_operators(&AXIS_OPERATORS);
+        validate_description(&description);
+
+        let hand_written: naga::Module =
+            validate_shader(include_str!("../shared/shaders/axis.wgsl"));
+        let generated: naga::Module = validate_shader(&generate_shader(&description, None));
+        for (hand_written, generated) in hand_written
+            .entry_points
+            .iter()
+            .zip(generated.entry_points.iter())
+        {
+            assert_eq!(hand_written.name, generated.name);
+            assert_eq!(hand_written.workgroup_size, generated.workgroup_size);
+        }
+        assert_eq!(
+            hand_written.entry_points.len(),
+            generated.entry_points.len()
+        );
+        assert_eq!(
+            description.dimensions,
+            [
+                "segment_count",
+                "segment_length",
+                "inner_count",
+                "outer_stride",
+                "inner_stride",
+                "element_stride"
+            ]
+        );
+    }
+
+    // Every axis operator can be followed by any of the elementwise operators
+    #[test]
+    fn fused_axis_shaders_validate() {
+        for operator in AXIS_OPERATORS {
+            for value_is_left in [true, false] {
+                let fusion: FusedElementwise = FusedElementwise {
+                    base: FusionBase::Axis {
+                        operator,
+                        axis: Axis::Rows,
+                    },
+                    steps: vec![
+                        step(ElementwiseOperator::Subtract, value_is_left),
+                        step(ElementwiseOperator::ReLU, true),
+                        step(ElementwiseOperator::Multiply, true),
+                        step(ElementwiseOperator::Divide, value_is_left),
+                        step(ElementwiseOperator::Add, true),
+                    ],
+                };
+                let description: ShaderDescription = op_codes::fused_elementwise(&fusion);
+                validate_description(&description);
+
+                // input, four operands and the output, then the layout
+                assert_eq!(description.bindings.len(), 6);
+                assert_eq!(description.dimensions.len(), 6 * 2 + 6);
+            }
+        }
+    }
+
+    #[test]
+    fn immediate_shaders_validate() {
+        validate_description(&op_codes::sum());
+        validate_description(&op_codes::subtraction());
+        validate_description(&op_codes::elementwise(&[
+            ElementwiseOperator::Add,
+            ElementwiseOperator::Subtract,
+            ElementwiseOperator::Multiply,
+            ElementwiseOperator::Divide,
+        ]));
+    }
+
+    #[test]
+    fn fused_linear_shaders_validate() {
+        let fusions: Vec<FusedElementwise> = vec![
+            FusedElementwise {
+                base: FusionBase::Linear,
+                steps: vec![step(ElementwiseOperator::ReLU, true)],
+            },
+            chain_fusion(),
+        ];
+
+        for fusion in &fusions {
+            validate_shader(&generate_fused_elementwise_shader(fusion));
+            validate_description(&op_codes::fused_elementwise(fusion));
+        }
+    }
+
+    #[test]
+    fn fused_load_shaders_validate() {
+        let operators: [ElementwiseOperator; 5] = [
+            ElementwiseOperator::ReLU,
+            ElementwiseOperator::Add,
+            ElementwiseOperator::Subtract,
+            ElementwiseOperator::Multiply,
+            ElementwiseOperator::Divide,
+        ];
+
+        for operator in operators {
+            for value_is_left in [true, false] {
+                let fusion: FusedElementwise = FusedElementwise {
+                    base: FusionBase::Load,
+                    steps: vec![
+                        step(ElementwiseOperator::Add, true),
+                        step(operator, value_is_left),
+                    ],
+                };
+                validate_description(&op_codes::fused_elementwise(&fusion));
+            }
+        }
+    }
+
+    // Every operand gets its own binding after the base tensors, and the output is last
+    #[test]
+    fn fused_shader_bindings() {
+        let fusion: FusedElementwise = FusedElementwise {
+            base: FusionBase::Linear,
+            steps: vec![
+                step(ElementwiseOperator::Add, true),
+                step(ElementwiseOperator::ReLU, true),
+                step(ElementwiseOperator::Multiply, false),
+            ],
+        };
+        let source: String = generate_fused_elementwise_shader(&fusion);
+
+        for binding in 0..=6 {
+            assert!(source.contains(&format!("@binding({})", binding)));
+        }
+        assert!(!source.contains("@binding(7)"));
+    }
+
+    // A shader specialized for a fused subgraph has its dimensions as constants
+    // and no uniform, but keeps the buffers at the same bindings
+    #[test]
+    fn baked_dimensions() {
+        let description: ShaderDescription = op_codes::fused_elementwise(&chain_fusion());
+        // input, weights, bias, three operands and the output
+        let values: Vec<u32> =
+            tensor_dimension_values(&[(6, 5), (5, 5), (6, 5), (1, 5), (6, 1), (6, 5), (6, 5)]);
+        let source: String = generate_shader(&description, Some(&values));
+        validate_shader(&source);
+
+        assert!(!source.contains("var<uniform>"));
+        assert!(!source.contains("@binding(0)"));
+        assert!(source.contains("@binding(7)"));
+        assert!(source.contains("const input_row_count: u32 = 6u;"));
+        assert!(source.contains("const operand_0_row_count: u32 = 1u;"));
+        assert!(source.contains("const operand_1_column_count: u32 = 1u;"));
+    }
+
+    // Every operator can be specialized from the shapes of the buffers of its node
+    #[test]
+    fn specialized_shaders() {
+        let fusion: FusedElementwise = chain_fusion();
+        let linear_shapes: Vec<(usize, usize)> = vec![(6, 4), (4, 5), (6, 5), (6, 5)];
+        let elementwise_shapes: Vec<(usize, usize)> = vec![(6, 5), (1, 5), (6, 5)];
+        let fused_shapes: Vec<(usize, usize)> =
+            vec![(6, 4), (4, 5), (6, 5), (1, 5), (6, 1), (6, 5), (6, 5)];
+        let axis: Option<Axis> = Some(Axis::Columns);
+
+        for operator in &OPERATORS {
+            let shapes: &[(usize, usize)] = match operator {
+                NodeOperatorGPU::Linear
+                | NodeOperatorGPU::LinearReLU
+                | NodeOperatorGPU::LinearReLUSoftmax => &linear_shapes,
+                NodeOperatorGPU::FusedElementwise => &fused_shapes,
+                _ => &elementwise_shapes,
+            };
+
+            match generate_specialized_shader(operator, Some(&fusion), axis, shapes) {
+                Some(source) => {
+                    validate_shader(&source);
+                    assert!(!source.contains("var<uniform>"));
+                }
+                None => assert!(has_no_description(operator)),
+            }
+        }
+
+        let source: String = generate_specialized_shader(
+            &NodeOperatorGPU::LinearReLUSoftmax,
+            None,
+            None,
+            &linear_shapes,
+        )
+        .unwrap();
+        assert!(source.contains("const input_column_count: u32 = 4u;"));
+        assert!(source.contains("const output_column_count: u32 = 5u;"));
+        assert!(source.contains("const element_count: u32 = 30u;"));
+
+        // One segment per column, each of them 6 elements, 5 apart
+        let source: String =
+            generate_specialized_shader(&NodeOperatorGPU::Mean, None, axis, &elementwise_shapes)
+                .unwrap();
+        assert!(source.contains("const segment_count: u32 = 5u;"));
+        assert!(source.contains("const segment_length: u32 = 6u;"));
+        assert!(source.contains("const element_stride: u32 = 5u;"));
+
+        // A fused axis base has the layout after the shapes of its tensors
+        let fusion: FusedElementwise = FusedElementwise {
+            base: FusionBase::Axis {
+                operator: AxisOperator::LogSoftmax,
+                axis: Axis::Rows,
+            },
+            steps: vec![step(ElementwiseOperator::Multiply, true)],
+        };
+        let source: String = generate_specialized_shader(
+            &NodeOperatorGPU::FusedElementwise,
+            Some(&fusion),
+            None,
+            &[(6, 5), (1, 5), (6, 5)],
+        )
+        .unwrap();
+        validate_shader(&source);
+        assert!(source.contains("const operand_0_row_count: u32 = 1u;"));
+        assert!(source.contains("const segment_count: u32 = 6u;"));
+        assert!(source.contains("const segment_length: u32 = 5u;"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn baked_dimensions_count() {
+        let description: ShaderDescription = op_codes::linear(false);
+        generate_shader(&description, Some(&[4, 4, 4, 4]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn fused_elementwise_needs_fusion() {
+        op_codes::describe_operator(&NodeOperatorGPU::FusedElementwise, None, None);
+    }
+}
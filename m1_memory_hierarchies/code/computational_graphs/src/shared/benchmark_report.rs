@@ -0,0 +1,217 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use wgpu::AdapterInfo;
+
+use super::performance_measurement::{PerformanceMeasurements, TimingStatistics};
+
+// The GPU a benchmark ran on, from GPUHandles::adapter_info
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AdapterReport {
+    pub name: String,
+    pub vendor: usize,
+    pub device: usize,
+    pub device_type: String,
+    pub driver: String,
+    pub driver_info: String,
+    pub backend: String,
+}
+
+impl From<&AdapterInfo> for AdapterReport {
+    fn from(adapter_info: &AdapterInfo) -> Self {
+        AdapterReport {
+            name: adapter_info.name.clone(),
+            vendor: adapter_info.vendor,
+            device: adapter_info.device,
+            device_type: format!("{:?}", adapter_info.device_type),
+            driver: adapter_info.driver.clone(),
+            driver_info: adapter_info.driver_info.clone(),
+            backend: format!("{:?}", adapter_info.backend),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MachineReport {
+    pub os: String,
+    pub arch: String,
+    pub cpu_count: usize,
+    // None for the benchmarks which only run on the CPU
+    pub adapter: Option<AdapterReport>,
+}
+
+impl MachineReport {
+    pub fn current(adapter_info: Option<&AdapterInfo>) -> Self {
+        MachineReport {
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            cpu_count: std::thread::available_parallelism()
+                .map(|count| count.get())
+                .unwrap_or(1),
+            adapter: adapter_info.map(AdapterReport::from),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SizeReport {
+    pub size: usize,
+    #[serde(flatten)]
+    pub statistics: TimingStatistics,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MeasurementReport {
+    pub name: String,
+    pub results: Vec<SizeReport>,
+}
+
+impl From<&PerformanceMeasurements> for MeasurementReport {
+    fn from(measurements: &PerformanceMeasurements) -> Self {
+        MeasurementReport {
+            name: measurements.name.clone(),
+            results: measurements
+                .sizes
+                .iter()
+                .zip(&measurements.statistics)
+                .map(|(size, statistics)| SizeReport {
+                    size: *size,
+                    statistics: statistics.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+// One benchmark run with everything needed to compare it to runs
+// on other machines or at other revisions.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BenchmarkReport {
+    pub benchmark: String,
+    pub unix_time_seconds: u64,
+    pub git_revision: Option<String>,
+    pub machine: MachineReport,
+    pub measurements: Vec<MeasurementReport>,
+}
+
+impl BenchmarkReport {
+    pub fn new(
+        benchmark: &str,
+        measurements: &[PerformanceMeasurements],
+        adapter_info: Option<&AdapterInfo>,
+    ) -> Self {
+        BenchmarkReport {
+            benchmark: benchmark.to_string(),
+            unix_time_seconds: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or(0),
+            git_revision: git_revision(),
+            machine: MachineReport::current(adapter_info),
+            measurements: measurements.iter().map(MeasurementReport::from).collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Failed to serialize a benchmark report")
+    }
+
+    // One row per measured size, with the run information repeated on every row
+    // so the rows of several runs can be concatenated.
+    pub fn to_csv(&self) -> String {
+        let revision: &str = self.git_revision.as_deref().unwrap_or("");
+        let adapter: &str = self
+            .machine
+            .adapter
+            .as_ref()
+            .map(|adapter| adapter.name.as_str())
+            .unwrap_or("");
+
+        let mut csv: String = "benchmark,name,size,sample_count,iterations_per_sample,\
+            mean_nanoseconds,min_nanoseconds,median_nanoseconds,p95_nanoseconds,\
+            standard_deviation_nanoseconds,unix_time_seconds,git_revision,os,arch,adapter\n"
+            .to_string();
+        for measurement in &self.measurements {
+            for result in &measurement.results {
+                let statistics: &TimingStatistics = &result.statistics;
+                let fields: [String; 15] = [
+                    csv_field(&self.benchmark),
+                    csv_field(&measurement.name),
+                    result.size.to_string(),
+                    statistics.sample_count.to_string(),
+                    statistics.iterations_per_sample.to_string(),
+                    statistics.mean_nanoseconds.to_string(),
+                    statistics.min_nanoseconds.to_string(),
+                    statistics.median_nanoseconds.to_string(),
+                    statistics.p95_nanoseconds.to_string(),
+                    statistics.standard_deviation_nanoseconds.to_string(),
+                    self.unix_time_seconds.to_string(),
+                    csv_field(revision),
+                    csv_field(&self.machine.os),
+                    csv_field(&self.machine.arch),
+                    csv_field(adapter),
+                ];
+                csv.push_str(&fields.join(","));
+                csv.push('\n');
+            }
+        }
+        csv
+    }
+}
+
+// Quotes a field if it contains anything with a meaning in CSV
+pub fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// The commit the benchmarks were built from, with a "-dirty" suffix if there were
+// uncommitted changes, captured by build.rs. None if the crate wasn't built
+// from a git repository.
+pub fn git_revision() -> Option<String> {
+    option_env!("GIT_REVISION").map(|revision| revision.to_string())
+}
+
+// Writes the report next to the plot of the same benchmark, so
+// outputs/{path}{file_name} gets a .json and a .csv file.
+pub fn write_benchmark_report(
+    path: &str,
+    file_name: &str,
+    report: &BenchmarkReport,
+) -> io::Result<(PathBuf, PathBuf)> {
+    let directory: PathBuf = Path::new("outputs").join(path);
+    fs::create_dir_all(&directory)?;
+
+    let stem: &Path = Path::new(file_name);
+    let json_path: PathBuf = directory.join(stem.with_extension("json"));
+    let csv_path: PathBuf = directory.join(stem.with_extension("csv"));
+    fs::write(&json_path, report.to_json())?;
+    fs::write(&csv_path, report.to_csv())?;
+
+    Ok((json_path, csv_path))
+}
+
+// Called next to draw_benchmark_plot with the same arguments.
+// A benchmark shouldn't fail because its results couldn't be written.
+pub fn save_benchmark_report(
+    benchmark: &str,
+    path: &str,
+    file_name: &str,
+    measurements: &[PerformanceMeasurements],
+    adapter_info: Option<&AdapterInfo>,
+) {
+    let report: BenchmarkReport = BenchmarkReport::new(benchmark, measurements, adapter_info);
+    if let Err(error) = write_benchmark_report(path, file_name, &report) {
+        println!(
+            "Failed to write the benchmark report for {}! {}",
+            benchmark, error
+        );
+    }
+}
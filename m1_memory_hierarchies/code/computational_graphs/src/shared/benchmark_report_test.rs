@@ -0,0 +1,124 @@
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use serde_json::Value;
+
+    use crate::shared::{
+        benchmark_report::{csv_field, write_benchmark_report, BenchmarkReport},
+        performance_measurement::{PerformanceMeasurements, TimingStatistics},
+    };
+
+    fn measurements() -> Vec<PerformanceMeasurements> {
+        vec![
+            PerformanceMeasurements::build_from_samples(
+                "cpu::linear".to_string(),
+                vec![16, 64],
+                vec![vec![30.0, 10.0, 20.0], vec![100.0, 300.0]],
+            ),
+            PerformanceMeasurements::build_from_measurements(
+                "graph, looped".to_string(),
+                vec![16, 64],
+                vec![(1000, 10), (4000, 10)],
+            ),
+        ]
+    }
+
+    #[test]
+    fn statistics() {
+        let samples: Vec<f64> = (1..=20).rev().map(|sample| sample as f64).collect();
+        let statistics: TimingStatistics = TimingStatistics::from_samples(&samples, 1);
+        assert_eq!(statistics.sample_count, 20);
+        assert_eq!(statistics.iterations_per_sample, 1);
+        assert_eq!(statistics.mean_nanoseconds, 10.5);
+        assert_eq!(statistics.min_nanoseconds, 1.0);
+        assert_eq!(statistics.median_nanoseconds, 10.5);
+        assert_eq!(statistics.p95_nanoseconds, 19.0);
+        assert!((statistics.standard_deviation_nanoseconds - 35.0f64.sqrt()).abs() < 1e-9);
+
+        let odd: TimingStatistics = TimingStatistics::from_samples(&[5.0, 1.0, 3.0], 1);
+        assert_eq!(odd.median_nanoseconds, 3.0);
+        assert_eq!(odd.p95_nanoseconds, 5.0);
+    }
+
+    #[test]
+    fn statistics_few_samples() {
+        let single: TimingStatistics = TimingStatistics::from_samples(&[42.0], 8);
+        assert_eq!(single.sample_count, 1);
+        assert_eq!(single.iterations_per_sample, 8);
+        assert_eq!(single.min_nanoseconds, 42.0);
+        assert_eq!(single.median_nanoseconds, 42.0);
+        assert_eq!(single.p95_nanoseconds, 42.0);
+        assert_eq!(single.standard_deviation_nanoseconds, 0.0);
+
+        let empty: TimingStatistics = TimingStatistics::from_samples(&[], 1);
+        assert_eq!(empty.sample_count, 0);
+        assert_eq!(empty.mean_nanoseconds, 0.0);
+    }
+
+    // The plots still use the means
+    #[test]
+    fn normalized_times() {
+        let measurements: Vec<PerformanceMeasurements> = measurements();
+        assert_eq!(measurements[0].normalized_times, vec![20.0, 200.0]);
+        assert_eq!(measurements[1].normalized_times, vec![100.0, 400.0]);
+        assert_eq!(measurements[1].statistics[1].sample_count, 1);
+        assert_eq!(measurements[1].statistics[1].iterations_per_sample, 10);
+    }
+
+    #[test]
+    fn json() {
+        let report: BenchmarkReport = BenchmarkReport::new("CPU Benchmark", &measurements(), None);
+        let json: Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["benchmark"], "CPU Benchmark");
+        assert!(json["machine"]["adapter"].is_null());
+        assert!(0 < json["machine"]["cpu_count"].as_u64().unwrap());
+
+        let result: &Value = &json["measurements"][0]["results"][1];
+        assert_eq!(result["size"], 64);
+        assert_eq!(result["sample_count"], 2);
+        assert_eq!(result["min_nanoseconds"], 100.0);
+        assert_eq!(result["median_nanoseconds"], 200.0);
+        assert_eq!(result["p95_nanoseconds"], 300.0);
+    }
+
+    #[test]
+    fn csv() {
+        let mut report: BenchmarkReport =
+            BenchmarkReport::new("CPU Benchmark", &measurements(), None);
+        report.git_revision = Some("abc123".to_string());
+        report.unix_time_seconds = 1000;
+
+        let csv: String = report.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0].split(',').count(), 15);
+        assert!(
+            lines[1].starts_with("CPU Benchmark,cpu::linear,16,3,1,20,10,20,30,10,1000,abc123,")
+        );
+        assert!(lines[3].starts_with("CPU Benchmark,\"graph, looped\",16,1,10,100,"));
+    }
+
+    #[test]
+    fn csv_escaping() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn write() {
+        let directory: String = format!("benchmark_report_test_{}/", std::process::id());
+        let report: BenchmarkReport = BenchmarkReport::new("CPU Benchmark", &measurements(), None);
+        let (json_path, csv_path): (PathBuf, PathBuf) =
+            write_benchmark_report(&directory, "cpu_benchmark.png", &report).unwrap();
+        let json: String = std::fs::read_to_string(&json_path).unwrap();
+        let csv: String = std::fs::read_to_string(&csv_path).unwrap();
+        std::fs::remove_dir_all(json_path.parent().unwrap()).unwrap();
+
+        assert_eq!(json_path.file_name().unwrap(), "cpu_benchmark.json");
+        assert_eq!(csv_path.file_name().unwrap(), "cpu_benchmark.csv");
+        assert_eq!(json, report.to_json());
+        assert_eq!(csv, report.to_csv());
+    }
+}
@@ -1,3 +1,7 @@
+use rayon::ThreadPool;
+
+use super::tensor2d_parallel::build_thread_pool;
+
 #[derive(Clone, Debug, Default)]
 pub struct Configuration {
     pub debug_level: u32,
@@ -10,6 +14,21 @@ pub struct Configuration {
     pub default_graph_layer_count: usize,
     pub default_graph_operator_size: usize,
     pub graph_depth_range: Vec<usize>,
+    // The parallel CPU kernels run on thread_count threads, 0 is one per logical core
+    pub thread_count: usize,
+    // The thread counts the CPU benchmarks measure the scaling of the parallel kernels with
+    pub thread_count_range: Vec<usize>,
+    // The batch sizes the graph throughput benchmarks run
+    pub batch_size_range: Vec<usize>,
+    // Writes the nodes the graph runners compile to DOT and Mermaid files in graph_exports/,
+    // along with Chrome traces of their profiles
+    pub dump_graphs: bool,
+    // Times every Linear kernel candidate before running the graphs and caches the fastest
+    // for this adapter, where every GraphRunnerGPU built from then on picks it up
+    pub autotune_linear: bool,
+    // Runs the GPU code on a software adapter, such as LavaPipe, when there is no GPU.
+    // Setting gpu_utilities::SOFTWARE_GPU_VARIABLE does the same
+    pub software_gpu: bool,
 }
 
 impl Configuration {
@@ -31,6 +50,12 @@ impl Configuration {
             default_graph_layer_count: 0,
             default_graph_operator_size: 0,
             graph_depth_range: Vec::<usize>::new(),
+            thread_count: 0,
+            thread_count_range: Vec::<usize>::new(),
+            batch_size_range: Vec::<usize>::new(),
+            dump_graphs: false,
+            autotune_linear: false,
+            software_gpu: false,
         }
     }
 
@@ -59,6 +84,46 @@ impl Configuration {
             default_graph_layer_count,
             default_graph_operator_size,
             graph_depth_range,
+            thread_count: 0,
+            thread_count_range: Vec::<usize>::new(),
+            batch_size_range: Vec::<usize>::new(),
+            dump_graphs: false,
+            autotune_linear: false,
+            software_gpu: false,
         }
     }
+
+    pub fn with_thread_counts(
+        mut self,
+        thread_count: usize,
+        thread_count_range: Vec<usize>,
+    ) -> Self {
+        self.thread_count = thread_count;
+        self.thread_count_range = thread_count_range;
+        self
+    }
+
+    pub fn with_batch_sizes(mut self, batch_size_range: Vec<usize>) -> Self {
+        self.batch_size_range = batch_size_range;
+        self
+    }
+
+    pub fn with_graph_dumps(mut self, dump_graphs: bool) -> Self {
+        self.dump_graphs = dump_graphs;
+        self
+    }
+
+    pub fn with_linear_autotuning(mut self, autotune_linear: bool) -> Self {
+        self.autotune_linear = autotune_linear;
+        self
+    }
+
+    pub fn with_software_gpu(mut self, software_gpu: bool) -> Self {
+        self.software_gpu = software_gpu;
+        self
+    }
+
+    pub fn thread_pool(&self) -> ThreadPool {
+        build_thread_pool(self.thread_count)
+    }
 }
@@ -0,0 +1,84 @@
+// The CPU side of the multi-pass reduction in shaders/sum.wgsl.
+// Every pass reduces ELEMENTS_PER_WORKGROUP elements per workgroup to a single value,
+// so a pass over n elements leaves ceil(n / ELEMENTS_PER_WORKGROUP) partial results
+// for the next pass, until a pass with a single workgroup writes the final value.
+pub const BLOCK_SIZE: usize = 256;
+pub const ELEMENTS_PER_WORKGROUP: usize = 2 * BLOCK_SIZE;
+// The most workgroups a dispatch can have in one dimension. Inputs needing more
+// than that are handled by each workgroup striding over several chunks.
+pub const MAX_WORKGROUP_COUNT: usize = 65535;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GlobalReduction {
+    Sum,
+    Max,
+    Min,
+    Mean,
+}
+
+impl GlobalReduction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GlobalReduction::Sum => "Sum",
+            GlobalReduction::Max => "Max",
+            GlobalReduction::Min => "Min",
+            GlobalReduction::Mean => "Mean",
+        }
+    }
+
+    pub fn entry_point(&self) -> &'static str {
+        match self {
+            GlobalReduction::Sum | GlobalReduction::Mean => "reduce_sum",
+            GlobalReduction::Max => "reduce_max",
+            GlobalReduction::Min => "reduce_min",
+        }
+    }
+
+    // What the reduction of no elements at all is, no passes are launched for it
+    pub fn empty_value(&self) -> f32 {
+        match self {
+            GlobalReduction::Sum => 0.0,
+            GlobalReduction::Max | GlobalReduction::Min | GlobalReduction::Mean => f32::NAN,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ReductionPass {
+    pub element_count: usize,
+    pub workgroup_count: usize,
+}
+
+// The passes needed to reduce element_count elements, the first pass reads the input
+// and every following pass reads the partial results of the one before it.
+pub fn plan_reduction(element_count: usize) -> Vec<ReductionPass> {
+    let mut passes: Vec<ReductionPass> = Vec::<ReductionPass>::new();
+    let mut remaining: usize = element_count;
+    while 0 < remaining {
+        let workgroup_count: usize = remaining
+            .div_ceil(ELEMENTS_PER_WORKGROUP)
+            .min(MAX_WORKGROUP_COUNT);
+        passes.push(ReductionPass {
+            element_count: remaining,
+            workgroup_count,
+        });
+        if workgroup_count == 1 {
+            break;
+        }
+        remaining = workgroup_count;
+    }
+
+    passes
+}
+
+// Pairwise summation, the error grows with log(n) instead of n as it does
+// when adding the elements one by one. Used as the reference for the GPU sums.
+pub fn pairwise_sum(data: &[f32]) -> f32 {
+    const SEQUENTIAL_LENGTH: usize = 8;
+    if data.len() <= SEQUENTIAL_LENGTH {
+        return data.iter().sum();
+    }
+
+    let (left, right): (&[f32], &[f32]) = data.split_at(data.len() / 2);
+    pairwise_sum(left) + pairwise_sum(right)
+}
@@ -0,0 +1,247 @@
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::shared::gpu_reduction::{
+        pairwise_sum, plan_reduction, GlobalReduction, ReductionPass, BLOCK_SIZE,
+        ELEMENTS_PER_WORKGROUP, MAX_WORKGROUP_COUNT,
+    };
+
+    const MAX_SIZE: usize = 10_000;
+
+    fn random_data(count: usize) -> Vec<f32> {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(21);
+        (0..count).map(|_| rng.gen_range(-1.0..1.0)).collect()
+    }
+
+    fn combine(reduction: GlobalReduction, left: f32, right: f32) -> f32 {
+        match reduction {
+            GlobalReduction::Sum | GlobalReduction::Mean => left + right,
+            GlobalReduction::Max => left.max(right),
+            GlobalReduction::Min => left.min(right),
+        }
+    }
+
+    // Does what sum.wgsl does, one workgroup at a time, so the plan and the
+    // padding can be checked for every size without a GPU.
+    fn emulate(data: &[f32], reduction: GlobalReduction) -> f32 {
+        let passes: Vec<ReductionPass> = plan_reduction(data.len());
+        if passes.is_empty() {
+            return reduction.empty_value();
+        }
+
+        let mut input: Vec<f32> = data.to_vec();
+        let mut shared_data: Vec<f32> = vec![0.0; BLOCK_SIZE];
+        for (pass_index, pass) in passes.iter().enumerate() {
+            let divisor: f32 =
+                if pass_index + 1 == passes.len() && reduction == GlobalReduction::Mean {
+                    data.len() as f32
+                } else {
+                    1.0
+                };
+            let stride: usize = pass.workgroup_count * ELEMENTS_PER_WORKGROUP;
+
+            let mut output: Vec<f32> = vec![0.0; pass.workgroup_count];
+            for (group_id, group_output) in output.iter_mut().enumerate() {
+                for (tid, shared) in shared_data.iter_mut().enumerate() {
+                    let mut value: f32 = match reduction {
+                        GlobalReduction::Sum | GlobalReduction::Mean => 0.0,
+                        GlobalReduction::Max | GlobalReduction::Min => input[0],
+                    };
+                    let mut index: usize = group_id * ELEMENTS_PER_WORKGROUP + tid;
+                    while index < pass.element_count {
+                        value = combine(reduction, value, input[index]);
+                        if index + BLOCK_SIZE < pass.element_count {
+                            value = combine(reduction, value, input[index + BLOCK_SIZE]);
+                        }
+                        index += stride;
+                    }
+                    *shared = value;
+                }
+
+                let mut half: usize = BLOCK_SIZE / 2;
+                while 0 < half {
+                    for tid in 0..half {
+                        shared_data[tid] =
+                            combine(reduction, shared_data[tid], shared_data[tid + half]);
+                    }
+                    half /= 2;
+                }
+                *group_output = shared_data[0] / divisor;
+            }
+            input = output;
+        }
+
+        input[0]
+    }
+
+    // The error of a pairwise or tree sum is bounded by about log2(n) * epsilon * sum(|x|),
+    // the sequential base cases of the pairwise sum add a few more roundings.
+    fn sum_tolerance(data: &[f32]) -> f32 {
+        let absolute_sum: f32 = data.iter().map(|value| value.abs()).sum();
+        let rounding_count: f32 = (data.len() as f32).log2().ceil() + 8.0;
+        2.0 * rounding_count * f32::EPSILON * absolute_sum
+    }
+
+    #[test]
+    fn plan_small() {
+        assert_eq!(plan_reduction(0), vec![]);
+        assert_eq!(
+            plan_reduction(1),
+            vec![ReductionPass {
+                element_count: 1,
+                workgroup_count: 1
+            }]
+        );
+        assert_eq!(
+            plan_reduction(ELEMENTS_PER_WORKGROUP),
+            vec![ReductionPass {
+                element_count: ELEMENTS_PER_WORKGROUP,
+                workgroup_count: 1
+            }]
+        );
+        assert_eq!(
+            plan_reduction(ELEMENTS_PER_WORKGROUP + 1),
+            vec![
+                ReductionPass {
+                    element_count: ELEMENTS_PER_WORKGROUP + 1,
+                    workgroup_count: 2
+                },
+                ReductionPass {
+                    element_count: 2,
+                    workgroup_count: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn plan_every_size() {
+        for element_count in 1..=MAX_SIZE {
+            let passes: Vec<ReductionPass> = plan_reduction(element_count);
+            assert_eq!(passes[0].element_count, element_count);
+            assert_eq!(passes.last().unwrap().workgroup_count, 1);
+            for pass in &passes {
+                // Every element is covered, and no workgroup is left without an element
+                assert!(pass.element_count <= pass.workgroup_count * ELEMENTS_PER_WORKGROUP);
+                assert!((pass.workgroup_count - 1) * ELEMENTS_PER_WORKGROUP < pass.element_count);
+            }
+            for pair in passes.windows(2) {
+                assert_eq!(pair[1].element_count, pair[0].workgroup_count);
+            }
+        }
+    }
+
+    // Past the dispatch limit the workgroups stride over the input instead
+    #[test]
+    fn plan_large() {
+        let element_count: usize = 2 * MAX_WORKGROUP_COUNT * ELEMENTS_PER_WORKGROUP + 3;
+        let passes: Vec<ReductionPass> = plan_reduction(element_count);
+        assert_eq!(passes[0].workgroup_count, MAX_WORKGROUP_COUNT);
+        assert_eq!(passes[1].element_count, MAX_WORKGROUP_COUNT);
+        assert_eq!(passes.len(), 3);
+
+        let data: Vec<f32> = vec![1.0; MAX_WORKGROUP_COUNT * ELEMENTS_PER_WORKGROUP + 5];
+        assert_eq!(emulate(&data, GlobalReduction::Sum), data.len() as f32);
+    }
+
+    #[test]
+    fn pairwise() {
+        assert_eq!(pairwise_sum(&[]), 0.0);
+        assert_eq!(pairwise_sum(&[1.5]), 1.5);
+        let data: Vec<f32> = (1..=100).map(|value| value as f32).collect();
+        assert_eq!(pairwise_sum(&data), 5050.0);
+
+        // Adding 0.1 a million times one by one drifts far from the exact sum
+        let data: Vec<f32> = vec![0.1; 1_000_000];
+        let exact: f64 = data.iter().map(|value| *value as f64).sum();
+        let sequential: f32 = data.iter().sum();
+        let pairwise: f32 = pairwise_sum(&data);
+        assert!((pairwise as f64 - exact).abs() < 0.01);
+        assert!((pairwise as f64 - exact).abs() < (sequential as f64 - exact).abs());
+    }
+
+    #[test]
+    fn emulated_every_size() {
+        let all_data: Vec<f32> = random_data(MAX_SIZE);
+        for element_count in 1..=MAX_SIZE {
+            let data: &[f32] = &all_data[..element_count];
+            let reference: f32 = pairwise_sum(data);
+            let tolerance: f32 = sum_tolerance(data);
+
+            let sum: f32 = emulate(data, GlobalReduction::Sum);
+            assert!(
+                (sum - reference).abs() <= tolerance,
+                "{} elements: {} != {}",
+                element_count,
+                sum,
+                reference
+            );
+            let mean: f32 = emulate(data, GlobalReduction::Mean);
+            assert!(
+                (mean - reference / element_count as f32).abs()
+                    <= tolerance / element_count as f32 + f32::EPSILON,
+                "{} elements",
+                element_count
+            );
+
+            let max: f32 = data.iter().copied().fold(f32::MIN, f32::max);
+            let min: f32 = data.iter().copied().fold(f32::MAX, f32::min);
+            assert_eq!(emulate(data, GlobalReduction::Max), max);
+            assert_eq!(emulate(data, GlobalReduction::Min), min);
+        }
+    }
+
+    // Zero padding would win a max of negative numbers
+    #[test]
+    fn emulated_all_negative() {
+        let data: Vec<f32> = vec![-3.0; 700];
+        assert_eq!(emulate(&data, GlobalReduction::Max), -3.0);
+        assert_eq!(emulate(&[2.0, 5.0, 1.0], GlobalReduction::Min), 1.0);
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(emulate(&[], GlobalReduction::Sum), 0.0);
+        assert!(emulate(&[], GlobalReduction::Max).is_nan());
+        assert!(emulate(&[], GlobalReduction::Min).is_nan());
+        assert!(emulate(&[], GlobalReduction::Mean).is_nan());
+    }
+
+    // The GPU kernels can't run without a GPU, but the shader can still be validated
+    #[test]
+    fn reduction_shader_validates() {
+        let source: &str = include_str!("shaders/sum.wgsl");
+        let module: naga::Module = naga::front::wgsl::parse_str(source)
+            .unwrap_or_else(|error| panic!("{}", error.emit_to_string(source)));
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        )
+        .validate(&module)
+        .unwrap_or_else(|error| panic!("{:?}", error));
+
+        let names: Vec<&str> = module
+            .entry_points
+            .iter()
+            .map(|entry_point| entry_point.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["reduce_sum", "reduce_max", "reduce_min"]);
+        for reduction in [
+            GlobalReduction::Sum,
+            GlobalReduction::Max,
+            GlobalReduction::Min,
+            GlobalReduction::Mean,
+        ] {
+            assert!(names.contains(&reduction.entry_point()));
+        }
+
+        // The workgroup size in the shader has to match the planner
+        assert!(source.contains(&format!("const BLOCK_SIZE: u32 = {}u;", BLOCK_SIZE)));
+        assert!(source.contains(&format!(
+            "const ELEMENTS_PER_WORKGROUP: u32 = {}u;",
+            ELEMENTS_PER_WORKGROUP
+        )));
+    }
+}
@@ -1,95 +1,68 @@
-use std::borrow::Cow;
-
-use wgpu::{
-    Adapter, AdapterInfo, BindGroup, BindGroupEntry, BindGroupLayout, BindingResource,
-    ComputePipeline, Device, Instance, Queue, RequestAdapterOptions, ShaderModule,
-};
+use gpu_common::{request_gpu, GPUOptions, LAVAPIPE_VENDOR_ID};
+use wgpu::Features;
 
 use crate::immediate::nodes::sum_from_tensor_2d;
 
 use super::tensor2d::Tensor2D;
 
-pub struct GPUHandles {
-    pub queue: Queue,
-    pub device: Device,
-    pub adapter: Adapter,
-    pub adapter_info: AdapterInfo,
-}
+pub use gpu_common::{
+    create_bind_group, create_compute_pipeline, create_shader_module, GPUHandles,
+};
 
-pub async fn self_test() -> bool {
-    println!("Performing self test to check system for compatibility.");
-    // Instantiates instance of wgpu
-    let instance: Instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-        backends: wgpu::Backends::all(),
-        dx12_shader_compiler: Default::default(),
-    });
+// Setting this to anything but 0 or false asks for a software adapter, such as LavaPipe
+// or WARP, which emulates a GPU on the CPU. Lets the GPU code and the GPU tests run
+// on machines without a GPU, such as CI.
+pub const SOFTWARE_GPU_VARIABLE: &str = "COMPUTATIONAL_GRAPHS_SOFTWARE_GPU";
 
-    let adapter_request: RequestAdapterOptions = RequestAdapterOptions {
-        power_preference: wgpu::PowerPreference::HighPerformance,
-        compatible_surface: None,
-        force_fallback_adapter: false,
-    };
+pub fn software_gpu_requested() -> bool {
+    match std::env::var(SOFTWARE_GPU_VARIABLE) {
+        Ok(value) => is_switched_on(&value),
+        Err(_) => false,
+    }
+}
 
-    // `request_adapter` instantiates the general connection to the GPU
-    let adapter_option: Option<Adapter> = instance.request_adapter(&adapter_request).await;
+pub fn is_switched_on(value: &str) -> bool {
+    !matches!(value.trim().to_lowercase().as_str(), "" | "0" | "false")
+}
 
-    match adapter_option {
-        Some(adapter) => {
-            let info: AdapterInfo = adapter.get_info();
-            println!("Found GPU: {:?}", info);
-            true
-        }
-        None => {
-            println!("Failed to find a usable GPU. This framework will only run CPU code.");
-            false
-        }
-    }
+// The adapter can also be picked with WGPU_ADAPTER_NAME and WGPU_BACKEND.
+// Timestamp queries are only used for profiling and are enabled if the adapter has them.
+fn gpu_options(use_software_gpu: bool) -> GPUOptions {
+    GPUOptions::from_env()
+        .with_software_gpu(use_software_gpu || software_gpu_requested())
+        .with_optional_features(Features::TIMESTAMP_QUERY)
 }
 
-pub async fn initialize_gpu(warmup_gpu: bool) -> Option<GPUHandles> {
-    // Instantiates instance of wgpu
-    let instance: Instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-        backends: wgpu::Backends::all(),
-        dx12_shader_compiler: Default::default(),
-    });
+// use_software_gpu, or SOFTWARE_GPU_VARIABLE, looks for a software adapter instead of a GPU
+pub async fn self_test(use_software_gpu: bool) -> bool {
+    gpu_common::self_test(&gpu_options(use_software_gpu)).await
+}
 
-    // `request_adapter` instantiates the general connection to the GPU
-    let adapter: Adapter = instance
-        .request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::HighPerformance,
-            compatible_surface: None, // We aren't doing any graphics
-            force_fallback_adapter: false,
-        })
-        .await
-        .expect("Failed to find a usable GPU!");
+// Returns None if there is no usable GPU, in which case everything has to run on the CPU.
+// Uses a software adapter if SOFTWARE_GPU_VARIABLE is set.
+pub async fn initialize_gpu(warmup_gpu: bool) -> Option<GPUHandles> {
+    initialize_gpu_with(warmup_gpu, false).await
+}
 
-    // `request_device` instantiates the feature specific connection to the GPU, defining some parameters,
-    //  `features` being the available features.
-    let (device, queue): (Device, Queue) = adapter
-        .request_device(
-            &wgpu::DeviceDescriptor {
-                label: None,
-                features: wgpu::Features::empty(),
-                limits: wgpu::Limits::default(),
-            },
-            None,
-        )
-        .await
-        .unwrap();
+// use_software_gpu, or SOFTWARE_GPU_VARIABLE, asks for a software adapter instead of a GPU
+pub async fn initialize_gpu_with(warmup_gpu: bool, use_software_gpu: bool) -> Option<GPUHandles> {
+    let options: GPUOptions = gpu_options(use_software_gpu);
+    let gpu_handles: GPUHandles = match request_gpu(&options).await {
+        Ok(gpu_handles) => gpu_handles,
+        Err(error) => {
+            println!("{}", error);
+            return None;
+        }
+    };
 
-    let adapter_info: AdapterInfo = adapter.get_info();
-    // skip this on LavaPipe temporarily
-    if adapter_info.vendor == 0x10005 {
+    // LavaPipe is only used when asked for, either as a software adapter or by name
+    if gpu_handles.adapter_info.vendor == LAVAPIPE_VENDOR_ID
+        && !options.use_software_gpu
+        && options.adapter_name.is_none()
+    {
         return None;
     }
 
-    let gpu_handles: GPUHandles = GPUHandles {
-        queue,
-        device,
-        adapter,
-        adapter_info,
-    };
-
     if warmup_gpu {
         let input: Tensor2D = Tensor2D::new(-0.5, 4, 3);
         let output: f32 = sum_from_tensor_2d(&gpu_handles, &input).await;
@@ -98,48 +71,3 @@ pub async fn initialize_gpu(warmup_gpu: bool) -> Option<GPUHandles> {
 
     Some(gpu_handles)
 }
-
-pub fn create_shader_module(gpu_handles: &GPUHandles, shader: &str) -> ShaderModule {
-    gpu_handles
-        .device
-        .create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: None,
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(shader)),
-        })
-}
-
-pub fn create_compute_pipeline(
-    gpu_handles: &GPUHandles,
-    module: &ShaderModule,
-    entry_point: &str,
-) -> ComputePipeline {
-    gpu_handles
-        .device
-        .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: None,
-            layout: None,
-            module,
-            entry_point,
-        })
-}
-
-pub fn create_bind_group(
-    gpu_handles: &GPUHandles,
-    bind_group_layout: &BindGroupLayout,
-    to_be_bound: Vec<(u32, BindingResource)>,
-) -> BindGroup {
-    let mut entries: Vec<BindGroupEntry> = vec![];
-
-    for (binding, resource) in to_be_bound {
-        let entry: BindGroupEntry = BindGroupEntry { binding, resource };
-        entries.push(entry);
-    }
-
-    gpu_handles
-        .device
-        .create_bind_group(&wgpu::BindGroupDescriptor {
-            label: None,
-            layout: bind_group_layout,
-            entries: entries.as_slice(),
-        })
-}
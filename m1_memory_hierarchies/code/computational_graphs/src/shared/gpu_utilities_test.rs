@@ -0,0 +1,48 @@
+#[cfg(test)]
+mod tests {
+    use gpu_common::is_software_adapter;
+    use wgpu::{AdapterInfo, Backend, DeviceType};
+
+    use crate::shared::gpu_utilities::is_switched_on;
+
+    fn adapter_info(vendor: usize, device_type: DeviceType) -> AdapterInfo {
+        AdapterInfo {
+            name: "adapter".to_string(),
+            vendor,
+            device: 0,
+            device_type,
+            driver: String::new(),
+            driver_info: String::new(),
+            backend: Backend::Vulkan,
+        }
+    }
+
+    #[test]
+    fn switched_on() {
+        for value in ["1", "true", "TRUE", "yes", " 1 "] {
+            assert!(is_switched_on(value), "{:?}", value);
+        }
+        for value in ["", "0", "false", "False", " 0 "] {
+            assert!(!is_switched_on(value), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn software_adapters() {
+        assert!(is_software_adapter(&adapter_info(0x10005, DeviceType::Cpu)));
+        // LavaPipe is recognized by its vendor id, whatever it reports as its type
+        assert!(is_software_adapter(&adapter_info(
+            0x10005,
+            DeviceType::Other
+        )));
+        assert!(is_software_adapter(&adapter_info(0x1414, DeviceType::Cpu)));
+        assert!(!is_software_adapter(&adapter_info(
+            0x10de,
+            DeviceType::DiscreteGpu
+        )));
+        assert!(!is_software_adapter(&adapter_info(
+            0x8086,
+            DeviceType::IntegratedGpu
+        )));
+    }
+}
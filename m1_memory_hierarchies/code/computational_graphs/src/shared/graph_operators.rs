@@ -1,13 +1,149 @@
-use super::tensor2d::Tensor2D;
+use super::{
+    tensor2d::Tensor2D,
+    tensor2d_axis::{Axis, Reduction},
+    tensor2d_precision::PackedTensor2D,
+};
 
 #[derive(Clone, Debug)]
 pub enum GraphOperator {
     Empty,
-    HostToDevice { input: Tensor2D },
+    HostToDevice {
+        input: Tensor2D,
+    },
     DeviceToHost,
-    Linear { weights: Tensor2D, bias: Tensor2D },
+    Linear {
+        weights: Tensor2D,
+        bias: Tensor2D,
+    },
     ReLU,
-    Softmax,
-    LinearReLUFused { weights: Tensor2D, bias: Tensor2D },
-    LinearReLUSoftmaxFused { weights: Tensor2D, bias: Tensor2D },
+    // Axis::Global normalizes the whole tensor
+    Softmax {
+        axis: Axis,
+    },
+    LinearReLUFused {
+        weights: Tensor2D,
+        bias: Tensor2D,
+    },
+    LinearReLUSoftmaxFused {
+        weights: Tensor2D,
+        bias: Tensor2D,
+    },
+    // Elementwise operators taking two inputs, left and right.
+    // Row and column vectors are broadcast to the shape of the other input.
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    LogSoftmax {
+        axis: Axis,
+    },
+    // Reductions keep one value per row, per column or for the whole tensor
+    Sum {
+        axis: Axis,
+    },
+    Max {
+        axis: Axis,
+    },
+    Mean {
+        axis: Axis,
+    },
+    ArgMax {
+        axis: Axis,
+    },
+    // A linear layer with its parameters kept in a smaller element type, see
+    // graph::quantization. The parameters are decoded as they are read and
+    // aren't trained.
+    QuantizedLinear {
+        weights: PackedTensor2D,
+        bias: PackedTensor2D,
+    },
+}
+
+impl GraphOperator {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GraphOperator::Empty => "Empty",
+            GraphOperator::HostToDevice { .. } => "HostToDevice",
+            GraphOperator::DeviceToHost => "DeviceToHost",
+            GraphOperator::Linear { .. } => "Linear",
+            GraphOperator::ReLU => "ReLU",
+            GraphOperator::Softmax { .. } => "Softmax",
+            GraphOperator::LinearReLUFused { .. } => "LinearReLUFused",
+            GraphOperator::LinearReLUSoftmaxFused { .. } => "LinearReLUSoftmaxFused",
+            GraphOperator::Add => "Add",
+            GraphOperator::Subtract => "Subtract",
+            GraphOperator::Multiply => "Multiply",
+            GraphOperator::Divide => "Divide",
+            GraphOperator::LogSoftmax { .. } => "LogSoftmax",
+            GraphOperator::Sum { .. } => "Sum",
+            GraphOperator::Max { .. } => "Max",
+            GraphOperator::Mean { .. } => "Mean",
+            GraphOperator::ArgMax { .. } => "ArgMax",
+            GraphOperator::QuantizedLinear { .. } => "QuantizedLinear",
+        }
+    }
+
+    // The reduction and axis of Sum, Max, Mean and ArgMax
+    pub fn reduction(&self) -> Option<(Reduction, Axis)> {
+        match self {
+            GraphOperator::Sum { axis } => Some((Reduction::Sum, *axis)),
+            GraphOperator::Max { axis } => Some((Reduction::Max, *axis)),
+            GraphOperator::Mean { axis } => Some((Reduction::Mean, *axis)),
+            GraphOperator::ArgMax { axis } => Some((Reduction::ArgMax, *axis)),
+            _ => None,
+        }
+    }
+}
+
+// A node id is just the index of the node in the Vec<GraphNode>
+// describing the graph.
+pub type NodeId = usize;
+
+// A GraphOperator which names the nodes it gets its input from.
+// This allows us to describe graphs which aren't just a linear chain,
+// such as multiple branches reading from the same input.
+#[derive(Clone, Debug)]
+pub struct GraphNode {
+    pub operator: GraphOperator,
+    pub inputs: Vec<NodeId>,
+}
+
+impl GraphNode {
+    pub fn new(operator: GraphOperator, inputs: Vec<NodeId>) -> Self {
+        GraphNode { operator, inputs }
+    }
+}
+
+// The inputs of the linear chain of operators used throughout the tutorial,
+// where every operator reads from the closest preceding operator which isn't Empty.
+pub fn chain_inputs(graph_operators: &[GraphOperator]) -> Vec<Vec<NodeId>> {
+    let mut chain_inputs: Vec<Vec<NodeId>> =
+        Vec::<Vec<NodeId>>::with_capacity(graph_operators.len());
+    let mut previous_node: Option<NodeId> = None;
+
+    for (node_id, operator) in graph_operators.iter().enumerate() {
+        let inputs: Vec<NodeId> = match operator {
+            GraphOperator::Empty | GraphOperator::HostToDevice { input: _ } => vec![],
+            _ => previous_node.into_iter().collect(),
+        };
+
+        if !matches!(operator, GraphOperator::Empty) {
+            previous_node = Some(node_id);
+        }
+
+        chain_inputs.push(inputs);
+    }
+
+    chain_inputs
+}
+
+// Converts the linear chain of operators used throughout the tutorial
+// into graph nodes, where every operator reads from the closest
+// preceding operator which isn't Empty.
+pub fn graph_nodes_from_operators(graph_operators: &[GraphOperator]) -> Vec<GraphNode> {
+    graph_operators
+        .iter()
+        .zip(chain_inputs(graph_operators))
+        .map(|(operator, inputs)| GraphNode::new(operator.clone(), inputs))
+        .collect()
 }
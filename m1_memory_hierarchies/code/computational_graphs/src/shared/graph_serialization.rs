@@ -0,0 +1,704 @@
+use std::{fmt, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    graph_operators::{chain_inputs, GraphNode, GraphOperator, NodeId},
+    tensor2d::Tensor2D,
+    tensor2d_axis::Axis,
+    tensor2d_precision::{PackedData, PackedTensor2D, Precision},
+};
+use crate::graph::{
+    graph_error::GraphError,
+    graph_validation::{validate_graph_nodes, validate_graph_operators},
+};
+
+// A graph file is laid out as
+// magic      - 8 bytes, GRAPH_FILE_MAGIC
+// version    - u32, GRAPH_FILE_VERSION
+// header     - u32 length in bytes, followed by that many bytes of JSON describing
+//              the operators, the nodes they read from and the shape of every tensor
+// payload    - the data of every tensor, in the order they appear in the header.
+//              Packed tensors keep their element type, everything else is f32.
+// checksum   - u32, CRC-32 of every byte before it
+// All numbers are little-endian, so a file can be moved between machines.
+pub const GRAPH_FILE_MAGIC: &[u8; 8] = b"CGRAPH\r\n";
+pub const GRAPH_FILE_VERSION: u32 = 1;
+
+const PREFIX_SIZE: usize = 8 + 4 + 4;
+const CHECKSUM_SIZE: usize = 4;
+
+#[derive(Debug)]
+pub enum GraphFileError {
+    Io(std::io::Error),
+    InvalidMagic,
+    UnsupportedVersion { found: u32, supported: u32 },
+    Truncated { expected: usize, found: usize },
+    TrailingBytes { expected: usize, found: usize },
+    ChecksumMismatch { expected: u32, found: u32 },
+    InvalidHeader(String),
+    UnknownOperator { index: usize, operator: String },
+    // The file is intact, but the operators don't make up a valid graph.
+    // Also returned when saving a graph which couldn't be loaded again.
+    InvalidGraph(GraphError),
+    // The file holds a graph with branches, which can't be loaded as a chain of operators
+    NotAChain { index: usize },
+}
+
+impl fmt::Display for GraphFileError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphFileError::Io(error) => {
+                write!(formatter, "Failed to access graph file: {}", error)
+            }
+            GraphFileError::InvalidMagic => {
+                write!(formatter, "Not a graph file, the magic number is wrong")
+            }
+            GraphFileError::UnsupportedVersion { found, supported } => write!(
+                formatter,
+                "Graph file has version {}, only version {} is supported",
+                found, supported
+            ),
+            GraphFileError::Truncated { expected, found } => write!(
+                formatter,
+                "Graph file is truncated, expected {} bytes, found {}",
+                expected, found
+            ),
+            GraphFileError::TrailingBytes { expected, found } => write!(
+                formatter,
+                "Graph file has trailing bytes, expected {} bytes, found {}",
+                expected, found
+            ),
+            GraphFileError::ChecksumMismatch { expected, found } => write!(
+                formatter,
+                "Graph file is corrupt, expected checksum {:#010x}, found {:#010x}",
+                expected, found
+            ),
+            GraphFileError::InvalidHeader(reason) => {
+                write!(formatter, "Graph file has an invalid header: {}", reason)
+            }
+            GraphFileError::UnknownOperator { index, operator } => write!(
+                formatter,
+                "Graph file has the unknown operator {} at index {}",
+                operator, index
+            ),
+            GraphFileError::InvalidGraph(error) => {
+                write!(formatter, "Graph file holds an invalid graph: {}", error)
+            }
+            GraphFileError::NotAChain { index } => write!(
+                formatter,
+                "Graph file holds a graph with branches, node {} doesn't read from the node before it",
+                index
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GraphFileError {}
+
+impl From<std::io::Error> for GraphFileError {
+    fn from(error: std::io::Error) -> Self {
+        GraphFileError::Io(error)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct TensorRecord {
+    name: String,
+    row_count: usize,
+    column_count: usize,
+    // Only written for packed tensors, the rest are f32
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    precision: Option<String>,
+    // Only written for Int8 tensors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scale: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    zero_point: Option<i8>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct OperatorRecord {
+    operator: String,
+    tensors: Vec<TensorRecord>,
+    // Only written for the operators with an axis. Files written before
+    // Softmax had an axis don't have one, which means Axis::Global.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    axis: Option<String>,
+    // The nodes this operator reads from. Not written for a chain of operators,
+    // where every operator reads from the closest preceding operator which isn't Empty.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    inputs: Option<Vec<NodeId>>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct GraphFileHeader {
+    operators: Vec<OperatorRecord>,
+    // The size of the payload in bytes
+    payload_length: usize,
+}
+
+// Table driven CRC-32 with the polynomial used by zip and png.
+// The table holds the remainder of every possible byte, computed at compile time.
+const fn crc32_table() -> [u32; 256] {
+    let mut table: [u32; 256] = [0; 256];
+    let mut index: usize = 0;
+    while index < 256 {
+        let mut value: u32 = index as u32;
+        let mut bit: usize = 0;
+        while bit < 8 {
+            value = if value & 1 == 1 {
+                0xEDB88320 ^ (value >> 1)
+            } else {
+                value >> 1
+            };
+            bit += 1;
+        }
+        table[index] = value;
+        index += 1;
+    }
+    table
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc: u32 = 0xFFFFFFFF;
+    for byte in bytes {
+        crc = CRC32_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+// The tensors of an operator as they are stored in a graph file
+enum StoredTensor<'a> {
+    Full(&'a Tensor2D),
+    Packed(&'a PackedTensor2D),
+}
+
+fn operator_tensors(operator: &GraphOperator) -> Vec<(&'static str, StoredTensor<'_>)> {
+    match operator {
+        GraphOperator::HostToDevice { input } => vec![("input", StoredTensor::Full(input))],
+        GraphOperator::Linear { weights, bias }
+        | GraphOperator::LinearReLUFused { weights, bias }
+        | GraphOperator::LinearReLUSoftmaxFused { weights, bias } => vec![
+            ("weights", StoredTensor::Full(weights)),
+            ("bias", StoredTensor::Full(bias)),
+        ],
+        GraphOperator::QuantizedLinear { weights, bias } => vec![
+            ("weights", StoredTensor::Packed(weights)),
+            ("bias", StoredTensor::Packed(bias)),
+        ],
+        GraphOperator::Empty
+        | GraphOperator::DeviceToHost
+        | GraphOperator::ReLU
+        | GraphOperator::Softmax { .. }
+        | GraphOperator::Add
+        | GraphOperator::Subtract
+        | GraphOperator::Multiply
+        | GraphOperator::Divide
+        | GraphOperator::LogSoftmax { .. }
+        | GraphOperator::Sum { .. }
+        | GraphOperator::Max { .. }
+        | GraphOperator::Mean { .. }
+        | GraphOperator::ArgMax { .. } => vec![],
+    }
+}
+
+fn operator_axis(operator: &GraphOperator) -> Option<Axis> {
+    match operator {
+        GraphOperator::Softmax { axis }
+        | GraphOperator::LogSoftmax { axis }
+        | GraphOperator::Sum { axis }
+        | GraphOperator::Max { axis }
+        | GraphOperator::Mean { axis }
+        | GraphOperator::ArgMax { axis } => Some(*axis),
+        GraphOperator::Empty
+        | GraphOperator::HostToDevice { .. }
+        | GraphOperator::DeviceToHost
+        | GraphOperator::Linear { .. }
+        | GraphOperator::ReLU
+        | GraphOperator::LinearReLUFused { .. }
+        | GraphOperator::LinearReLUSoftmaxFused { .. }
+        | GraphOperator::Add
+        | GraphOperator::Subtract
+        | GraphOperator::Multiply
+        | GraphOperator::Divide
+        | GraphOperator::QuantizedLinear { .. } => None,
+    }
+}
+
+// The precision a tensor record says its elements are stored with
+fn precision_from_record(tensor: &TensorRecord) -> Result<Precision, GraphFileError> {
+    match &tensor.precision {
+        None => Ok(Precision::F32),
+        Some(name) => Precision::from_name(name).ok_or_else(|| {
+            GraphFileError::InvalidHeader(format!(
+                "tensor {} has the unknown precision {}",
+                tensor.name, name
+            ))
+        }),
+    }
+}
+
+// Decodes the elements of a tensor from the payload. Every tensor is read as
+// a PackedTensor2D, the operators which compute in f32 unwrap them again.
+fn tensor_from_record(
+    tensor: &TensorRecord,
+    precision: Precision,
+    bytes: &[u8],
+) -> Result<PackedTensor2D, GraphFileError> {
+    let data: PackedData = match precision {
+        Precision::F32 => PackedData::F32(
+            bytes
+                .chunks_exact(4)
+                .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+                .collect(),
+        ),
+        Precision::F16 | Precision::BF16 => {
+            let values: Vec<u16> = bytes
+                .chunks_exact(2)
+                .map(|value| u16::from_le_bytes([value[0], value[1]]))
+                .collect();
+            if precision == Precision::F16 {
+                PackedData::F16(values)
+            } else {
+                PackedData::BF16(values)
+            }
+        }
+        Precision::Int8 => match (tensor.scale, tensor.zero_point) {
+            (Some(scale), Some(zero_point)) => PackedData::Int8 {
+                values: bytes.iter().map(|value| *value as i8).collect(),
+                scale,
+                zero_point,
+            },
+            _ => {
+                return Err(GraphFileError::InvalidHeader(format!(
+                    "Int8 tensor {} has no scale and zero point",
+                    tensor.name
+                )))
+            }
+        },
+    };
+
+    Ok(PackedTensor2D {
+        data,
+        row_count: tensor.row_count,
+        column_count: tensor.column_count,
+    })
+}
+
+fn write_packed(tensor: &PackedTensor2D, payload: &mut Vec<u8>) {
+    match &tensor.data {
+        PackedData::F32(values) => {
+            for value in values {
+                payload.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        PackedData::F16(values) | PackedData::BF16(values) => {
+            for value in values {
+                payload.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        PackedData::Int8 { values, .. } => {
+            payload.extend(values.iter().map(|value| *value as u8));
+        }
+    }
+}
+
+fn axis_from_record(
+    operator_index: usize,
+    record: &OperatorRecord,
+) -> Result<Axis, GraphFileError> {
+    match &record.axis {
+        None => Ok(Axis::Global),
+        Some(name) => Axis::from_name(name).ok_or_else(|| {
+            GraphFileError::InvalidHeader(format!(
+                "operator {} ({}) has the unknown axis {}",
+                operator_index, record.operator, name
+            ))
+        }),
+    }
+}
+
+// Rebuilds an operator from its record and the tensors the record describes
+fn operator_from_record(
+    operator_index: usize,
+    record: &OperatorRecord,
+    tensors: Vec<PackedTensor2D>,
+) -> Result<GraphOperator, GraphFileError> {
+    let names: Vec<&str> = record
+        .tensors
+        .iter()
+        .map(|tensor| tensor.name.as_str())
+        .collect();
+    let invalid_tensors = |expected_names: &[&str]| {
+        GraphFileError::InvalidHeader(format!(
+            "operator {} ({}) has the tensors {:?}, expected {:?}",
+            operator_index, record.operator, names, expected_names
+        ))
+    };
+    // Too few tensors are caught here, the wrong ones or too many once the operator is built
+    let mut tensors = tensors.into_iter();
+    let mut next_tensor = |expected_names: &[&str]| {
+        tensors
+            .next()
+            .ok_or_else(|| invalid_tensors(expected_names))
+    };
+    // Only QuantizedLinear keeps its tensors packed
+    let full = |tensor: PackedTensor2D| match tensor.data {
+        PackedData::F32(data) => Ok(Tensor2D {
+            data,
+            row_count: tensor.row_count,
+            column_count: tensor.column_count,
+        }),
+        _ => Err(GraphFileError::InvalidHeader(format!(
+            "operator {} ({}) has a packed tensor, but computes in f32",
+            operator_index, record.operator
+        ))),
+    };
+
+    let operator: GraphOperator = match record.operator.as_str() {
+        "Empty" => GraphOperator::Empty,
+        "HostToDevice" => GraphOperator::HostToDevice {
+            input: full(next_tensor(&["input"])?)?,
+        },
+        "DeviceToHost" => GraphOperator::DeviceToHost,
+        "Linear" => GraphOperator::Linear {
+            weights: full(next_tensor(&["weights", "bias"])?)?,
+            bias: full(next_tensor(&["weights", "bias"])?)?,
+        },
+        "ReLU" => GraphOperator::ReLU,
+        "Softmax" => GraphOperator::Softmax {
+            axis: axis_from_record(operator_index, record)?,
+        },
+        "LinearReLUFused" => GraphOperator::LinearReLUFused {
+            weights: full(next_tensor(&["weights", "bias"])?)?,
+            bias: full(next_tensor(&["weights", "bias"])?)?,
+        },
+        "LinearReLUSoftmaxFused" => GraphOperator::LinearReLUSoftmaxFused {
+            weights: full(next_tensor(&["weights", "bias"])?)?,
+            bias: full(next_tensor(&["weights", "bias"])?)?,
+        },
+        "Add" => GraphOperator::Add,
+        "Subtract" => GraphOperator::Subtract,
+        "Multiply" => GraphOperator::Multiply,
+        "Divide" => GraphOperator::Divide,
+        "LogSoftmax" => GraphOperator::LogSoftmax {
+            axis: axis_from_record(operator_index, record)?,
+        },
+        "Sum" => GraphOperator::Sum {
+            axis: axis_from_record(operator_index, record)?,
+        },
+        "Max" => GraphOperator::Max {
+            axis: axis_from_record(operator_index, record)?,
+        },
+        "Mean" => GraphOperator::Mean {
+            axis: axis_from_record(operator_index, record)?,
+        },
+        "ArgMax" => GraphOperator::ArgMax {
+            axis: axis_from_record(operator_index, record)?,
+        },
+        "QuantizedLinear" => GraphOperator::QuantizedLinear {
+            weights: next_tensor(&["weights", "bias"])?,
+            bias: next_tensor(&["weights", "bias"])?,
+        },
+        unknown => {
+            return Err(GraphFileError::UnknownOperator {
+                index: operator_index,
+                operator: unknown.to_string(),
+            })
+        }
+    };
+
+    let expected_names: Vec<&str> = operator_tensors(&operator)
+        .iter()
+        .map(|(name, _)| *name)
+        .collect();
+    if names != expected_names {
+        return Err(invalid_tensors(&expected_names));
+    }
+
+    Ok(operator)
+}
+
+// Every operator with the inputs to write for it, None for a chain of operators
+fn graph_to_bytes<'a>(
+    operators_and_inputs: impl Iterator<Item = (&'a GraphOperator, Option<&'a [NodeId]>)>,
+) -> Vec<u8> {
+    let mut operators: Vec<OperatorRecord> = Vec::<OperatorRecord>::new();
+    let mut payload: Vec<u8> = Vec::<u8>::new();
+    for (operator, inputs) in operators_and_inputs {
+        let mut tensors: Vec<TensorRecord> = Vec::<TensorRecord>::new();
+        for (name, tensor) in operator_tensors(operator) {
+            let record: TensorRecord = match tensor {
+                StoredTensor::Full(tensor) => {
+                    // Only the active part of the tensor is stored
+                    for value in &tensor.data[0..tensor.len()] {
+                        payload.extend_from_slice(&value.to_le_bytes());
+                    }
+                    TensorRecord {
+                        name: name.to_string(),
+                        row_count: tensor.row_count,
+                        column_count: tensor.column_count,
+                        precision: None,
+                        scale: None,
+                        zero_point: None,
+                    }
+                }
+                StoredTensor::Packed(tensor) => {
+                    write_packed(tensor, &mut payload);
+                    let (scale, zero_point): (Option<f32>, Option<i8>) = match tensor.data {
+                        PackedData::Int8 {
+                            scale, zero_point, ..
+                        } => (Some(scale), Some(zero_point)),
+                        _ => (None, None),
+                    };
+                    TensorRecord {
+                        name: name.to_string(),
+                        row_count: tensor.row_count,
+                        column_count: tensor.column_count,
+                        precision: Some(tensor.precision().name().to_string()),
+                        scale,
+                        zero_point,
+                    }
+                }
+            };
+            tensors.push(record);
+        }
+        operators.push(OperatorRecord {
+            operator: operator.name().to_string(),
+            tensors,
+            axis: operator_axis(operator).map(|axis| axis.name().to_string()),
+            inputs: inputs.map(|inputs| inputs.to_vec()),
+        });
+    }
+
+    let header: GraphFileHeader = GraphFileHeader {
+        operators,
+        payload_length: payload.len(),
+    };
+    let header: Vec<u8> =
+        serde_json::to_vec(&header).expect("Failed to serialize the graph file header");
+
+    let mut bytes: Vec<u8> =
+        Vec::<u8>::with_capacity(PREFIX_SIZE + header.len() + payload.len() + CHECKSUM_SIZE);
+    bytes.extend_from_slice(GRAPH_FILE_MAGIC);
+    bytes.extend_from_slice(&GRAPH_FILE_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&header);
+    bytes.extend_from_slice(&payload);
+    let checksum: u32 = crc32(&bytes);
+    bytes.extend_from_slice(&checksum.to_le_bytes());
+
+    bytes
+}
+
+pub fn graph_operators_to_bytes(graph_operators: &[GraphOperator]) -> Vec<u8> {
+    graph_to_bytes(graph_operators.iter().map(|operator| (operator, None)))
+}
+
+pub fn graph_nodes_to_bytes(graph_nodes: &[GraphNode]) -> Vec<u8> {
+    graph_to_bytes(
+        graph_nodes
+            .iter()
+            .map(|node| (&node.operator, Some(node.inputs.as_slice()))),
+    )
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+fn expect_length(bytes: &[u8], expected: usize) -> Result<(), GraphFileError> {
+    if bytes.len() < expected {
+        Err(GraphFileError::Truncated {
+            expected,
+            found: bytes.len(),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+// Everything is checked before any tensor is read, the magic number and version first,
+// as a file of another version might not even have the same layout.
+// Returns the operators and the inputs written for each of them, if any.
+fn graph_from_bytes(
+    bytes: &[u8],
+) -> Result<(Vec<GraphOperator>, Vec<Option<Vec<NodeId>>>), GraphFileError> {
+    if bytes.len() < GRAPH_FILE_MAGIC.len() || &bytes[0..GRAPH_FILE_MAGIC.len()] != GRAPH_FILE_MAGIC
+    {
+        return Err(GraphFileError::InvalidMagic);
+    }
+    expect_length(bytes, PREFIX_SIZE)?;
+
+    let version: u32 = read_u32(bytes, 8);
+    if version != GRAPH_FILE_VERSION {
+        return Err(GraphFileError::UnsupportedVersion {
+            found: version,
+            supported: GRAPH_FILE_VERSION,
+        });
+    }
+
+    let header_length: usize = read_u32(bytes, 12) as usize;
+    let header_end: usize = PREFIX_SIZE + header_length;
+    expect_length(bytes, header_end + CHECKSUM_SIZE)?;
+
+    // Without a valid header we don't know how long the file should be,
+    // so the checksum is verified against whatever is there.
+    let header: Result<GraphFileHeader, serde_json::Error> =
+        serde_json::from_slice(&bytes[PREFIX_SIZE..header_end]);
+    let expected_length: usize = match &header {
+        Ok(header) => header_end
+            .saturating_add(header.payload_length)
+            .saturating_add(CHECKSUM_SIZE),
+        Err(_) => bytes.len(),
+    };
+    expect_length(bytes, expected_length)?;
+    if expected_length < bytes.len() {
+        return Err(GraphFileError::TrailingBytes {
+            expected: expected_length,
+            found: bytes.len(),
+        });
+    }
+
+    let checksum_offset: usize = expected_length - CHECKSUM_SIZE;
+    let expected_checksum: u32 = read_u32(bytes, checksum_offset);
+    let checksum: u32 = crc32(&bytes[0..checksum_offset]);
+    if checksum != expected_checksum {
+        return Err(GraphFileError::ChecksumMismatch {
+            expected: expected_checksum,
+            found: checksum,
+        });
+    }
+
+    let header: GraphFileHeader =
+        header.map_err(|error| GraphFileError::InvalidHeader(error.to_string()))?;
+
+    // The shapes come from the file, so they are checked for overflow
+    let mut tensor_bytes: usize = 0;
+    for tensor in header
+        .operators
+        .iter()
+        .flat_map(|operator| operator.tensors.iter())
+    {
+        let element_size: usize = precision_from_record(tensor)?.element_size();
+        tensor_bytes = tensor
+            .row_count
+            .checked_mul(tensor.column_count)
+            .and_then(|length| length.checked_mul(element_size))
+            .and_then(|length| length.checked_add(tensor_bytes))
+            .ok_or_else(|| {
+                GraphFileError::InvalidHeader(format!(
+                    "tensor {} has an impossible shape of {}x{}",
+                    tensor.name, tensor.row_count, tensor.column_count
+                ))
+            })?;
+    }
+    if tensor_bytes != header.payload_length {
+        return Err(GraphFileError::InvalidHeader(format!(
+            "the tensors need {} bytes, but the payload is {} bytes",
+            tensor_bytes, header.payload_length
+        )));
+    }
+
+    let mut offset: usize = header_end;
+    let mut graph_operators: Vec<GraphOperator> =
+        Vec::<GraphOperator>::with_capacity(header.operators.len());
+    for (operator_index, record) in header.operators.iter().enumerate() {
+        let mut tensors: Vec<PackedTensor2D> = Vec::<PackedTensor2D>::new();
+        for tensor in &record.tensors {
+            let precision: Precision = precision_from_record(tensor)?;
+            let length: usize = tensor.row_count * tensor.column_count * precision.element_size();
+            tensors.push(tensor_from_record(
+                tensor,
+                precision,
+                &bytes[offset..offset + length],
+            )?);
+            offset += length;
+        }
+        graph_operators.push(operator_from_record(operator_index, record, tensors)?);
+    }
+    let inputs: Vec<Option<Vec<NodeId>>> = header
+        .operators
+        .into_iter()
+        .map(|record| record.inputs)
+        .collect();
+
+    Ok((graph_operators, inputs))
+}
+
+// A file holding a graph with branches is rejected, instead of dropping its edges
+pub fn graph_operators_from_bytes(bytes: &[u8]) -> Result<Vec<GraphOperator>, GraphFileError> {
+    let (graph_operators, inputs) = graph_from_bytes(bytes)?;
+    for (index, (inputs, chain_inputs)) in inputs
+        .iter()
+        .zip(chain_inputs(&graph_operators))
+        .enumerate()
+    {
+        if inputs
+            .as_ref()
+            .is_some_and(|inputs| *inputs != chain_inputs)
+        {
+            return Err(GraphFileError::NotAChain { index });
+        }
+    }
+
+    Ok(graph_operators)
+}
+
+// Operators written without inputs were written as part of a chain
+pub fn graph_nodes_from_bytes(bytes: &[u8]) -> Result<Vec<GraphNode>, GraphFileError> {
+    let (graph_operators, inputs) = graph_from_bytes(bytes)?;
+    let chain_inputs: Vec<Vec<NodeId>> = chain_inputs(&graph_operators);
+    let graph_nodes: Vec<GraphNode> = graph_operators
+        .into_iter()
+        .zip(inputs.into_iter().zip(chain_inputs))
+        .map(|(operator, (inputs, chain_inputs))| {
+            GraphNode::new(operator, inputs.unwrap_or(chain_inputs))
+        })
+        .collect();
+
+    Ok(graph_nodes)
+}
+
+// A graph which couldn't be loaded again is rejected before anything is written
+pub fn save_graph_operators(
+    path: &Path,
+    graph_operators: &[GraphOperator],
+) -> Result<(), GraphFileError> {
+    validate_graph_operators(graph_operators).map_err(GraphFileError::InvalidGraph)?;
+    fs::write(path, graph_operators_to_bytes(graph_operators))?;
+    Ok(())
+}
+
+pub fn save_graph_nodes(path: &Path, graph_nodes: &[GraphNode]) -> Result<(), GraphFileError> {
+    validate_graph_nodes(graph_nodes).map_err(GraphFileError::InvalidGraph)?;
+    fs::write(path, graph_nodes_to_bytes(graph_nodes))?;
+    Ok(())
+}
+
+// Unlike graph_operators_from_bytes, which only checks the format, a loaded graph
+// is validated, so it can be handed straight to a runner
+pub fn load_graph_operators(path: &Path) -> Result<Vec<GraphOperator>, GraphFileError> {
+    let bytes: Vec<u8> = fs::read(path)?;
+    let graph_operators: Vec<GraphOperator> = graph_operators_from_bytes(&bytes)?;
+    validate_graph_operators(&graph_operators).map_err(GraphFileError::InvalidGraph)?;
+
+    Ok(graph_operators)
+}
+
+// Loads chains of operators as well, which can then be run like any other graph of nodes
+pub fn load_graph_nodes(path: &Path) -> Result<Vec<GraphNode>, GraphFileError> {
+    let bytes: Vec<u8> = fs::read(path)?;
+    let graph_nodes: Vec<GraphNode> = graph_nodes_from_bytes(&bytes)?;
+    validate_graph_nodes(&graph_nodes).map_err(GraphFileError::InvalidGraph)?;
+
+    Ok(graph_nodes)
+}
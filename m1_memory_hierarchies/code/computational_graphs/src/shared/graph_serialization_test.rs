@@ -0,0 +1,457 @@
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::{
+        graph::{
+            graph_error::GraphError,
+            graph_validation::{validate_graph_nodes, validate_graph_operators},
+        },
+        shared::{
+            graph_operators::{graph_nodes_from_operators, GraphNode, GraphOperator},
+            graph_serialization::{
+                crc32, graph_nodes_from_bytes, graph_nodes_to_bytes, graph_operators_from_bytes,
+                graph_operators_to_bytes, load_graph_nodes, load_graph_operators, save_graph_nodes,
+                save_graph_operators, GraphFileError, GRAPH_FILE_VERSION,
+            },
+            tensor2d::Tensor2D,
+            tensor2d_axis::Axis,
+            tensor2d_precision::{PackedTensor2D, Precision},
+        },
+    };
+
+    fn graph() -> Vec<GraphOperator> {
+        let mut weights: Tensor2D = Tensor2D::new(0.01, 3, 4);
+        // Values which don't survive a round trip through text
+        weights.data[0] = -0.1;
+        weights.data[1] = f32::MIN_POSITIVE;
+        weights.data[2] = 1.0e30;
+
+        vec![
+            GraphOperator::HostToDevice {
+                input: Tensor2D::new(0.1, 2, 3),
+            },
+            GraphOperator::Linear {
+                weights,
+                bias: Tensor2D::new(0.2, 2, 4),
+            },
+            GraphOperator::ReLU,
+            GraphOperator::LinearReLUFused {
+                weights: Tensor2D::new(0.3, 4, 4),
+                bias: Tensor2D::new(0.4, 2, 4),
+            },
+            GraphOperator::LinearReLUSoftmaxFused {
+                weights: Tensor2D::new(0.5, 4, 2),
+                bias: Tensor2D::new(0.6, 2, 2),
+            },
+            GraphOperator::Softmax { axis: Axis::Global },
+            GraphOperator::DeviceToHost,
+        ]
+    }
+
+    // A residual connection around a linear layer, which a chain of operators can't describe
+    fn graph_nodes() -> Vec<GraphNode> {
+        vec![
+            GraphNode::new(
+                GraphOperator::HostToDevice {
+                    input: Tensor2D::new(0.1, 2, 3),
+                },
+                vec![],
+            ),
+            GraphNode::new(
+                GraphOperator::Linear {
+                    weights: Tensor2D::new(0.2, 3, 3),
+                    bias: Tensor2D::new(0.3, 2, 3),
+                },
+                vec![0],
+            ),
+            GraphNode::new(GraphOperator::ReLU, vec![1]),
+            GraphNode::new(GraphOperator::Add, vec![2, 0]),
+            GraphNode::new(GraphOperator::DeviceToHost, vec![3]),
+        ]
+    }
+
+    // GraphOperator and GraphNode don't implement PartialEq,
+    // but their Debug output contains everything
+    fn assert_graphs_match<T: std::fmt::Debug>(expected: &[T], actual: &[T]) {
+        assert_eq!(format!("{:?}", expected), format!("{:?}", actual));
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "computational_graphs_{}_{}.graph",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn round_trip() {
+        let graph: Vec<GraphOperator> = graph();
+        assert_eq!(validate_graph_operators(&graph), Ok(()));
+
+        let loaded: Vec<GraphOperator> =
+            graph_operators_from_bytes(&graph_operators_to_bytes(&graph)).unwrap();
+        assert_graphs_match(&graph, &loaded);
+        assert_eq!(validate_graph_operators(&loaded), Ok(()));
+    }
+
+    // Operators which can't be part of a valid linear chain still have to round trip
+    #[test]
+    fn round_trip_every_operator() {
+        let mut graph: Vec<GraphOperator> = graph();
+        graph.extend(vec![
+            GraphOperator::Empty,
+            GraphOperator::Add,
+            GraphOperator::Subtract,
+            GraphOperator::Multiply,
+            GraphOperator::Divide,
+            GraphOperator::Softmax { axis: Axis::Rows },
+            GraphOperator::LogSoftmax {
+                axis: Axis::Columns,
+            },
+            GraphOperator::Sum { axis: Axis::Global },
+            GraphOperator::Max { axis: Axis::Rows },
+            GraphOperator::Mean {
+                axis: Axis::Columns,
+            },
+            GraphOperator::ArgMax { axis: Axis::Rows },
+        ]);
+
+        let loaded: Vec<GraphOperator> =
+            graph_operators_from_bytes(&graph_operators_to_bytes(&graph)).unwrap();
+        assert_graphs_match(&graph, &loaded);
+    }
+
+    // Packed parameters are stored in their own element type and come back bit for bit
+    #[test]
+    fn round_trip_quantized() {
+        let mut payload_lengths: Vec<usize> = Vec::<usize>::new();
+        for precision in [
+            Precision::F32,
+            Precision::F16,
+            Precision::BF16,
+            Precision::Int8,
+        ] {
+            let mut weights: Tensor2D = Tensor2D::new(0.25, 3, 5);
+            weights.data[0] = -1.5;
+            weights.data[14] = 3.0;
+            let graph: Vec<GraphOperator> = vec![
+                GraphOperator::HostToDevice {
+                    input: Tensor2D::new(0.1, 2, 3),
+                },
+                GraphOperator::QuantizedLinear {
+                    weights: PackedTensor2D::from_tensor2d(&weights, precision),
+                    bias: PackedTensor2D::from_tensor2d(&Tensor2D::new(-0.5, 2, 5), precision),
+                },
+                GraphOperator::DeviceToHost,
+            ];
+
+            let bytes: Vec<u8> = graph_operators_to_bytes(&graph);
+            let loaded: Vec<GraphOperator> = graph_operators_from_bytes(&bytes).unwrap();
+            assert_graphs_match(&graph, &loaded);
+            // The header length follows the 12 byte preamble and the checksum ends the file
+            let header_length: usize =
+                u32::from_le_bytes(bytes[12..16].try_into().unwrap()) as usize;
+            payload_lengths.push(bytes.len() - 16 - header_length - 4);
+        }
+
+        // The 6 input elements stay f32, while the 25 parameters shrink with their element type
+        assert_eq!(
+            payload_lengths,
+            [6 * 4 + 25 * 4, 6 * 4 + 25 * 2, 6 * 4 + 25 * 2, 6 * 4 + 25]
+        );
+    }
+
+    #[test]
+    fn round_trip_file() {
+        let path: PathBuf = temp_path("round_trip_file");
+        let graph: Vec<GraphOperator> = graph();
+
+        save_graph_operators(&path, &graph).unwrap();
+        let loaded: Result<Vec<GraphOperator>, GraphFileError> = load_graph_operators(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_graphs_match(&graph, &loaded.unwrap());
+    }
+
+    #[test]
+    fn round_trip_graph_nodes() {
+        let graph: Vec<GraphNode> = graph_nodes();
+        assert_eq!(validate_graph_nodes(&graph), Ok(()));
+
+        let loaded: Vec<GraphNode> = graph_nodes_from_bytes(&graph_nodes_to_bytes(&graph)).unwrap();
+        assert_graphs_match(&graph, &loaded);
+
+        let path: PathBuf = temp_path("round_trip_graph_nodes");
+        save_graph_nodes(&path, &graph).unwrap();
+        let loaded: Result<Vec<GraphNode>, GraphFileError> = load_graph_nodes(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_graphs_match(&graph, &loaded.unwrap());
+    }
+
+    // Files holding a chain of operators don't store the inputs
+    #[test]
+    fn chain_as_graph_nodes() {
+        let graph: Vec<GraphOperator> = graph();
+        let loaded: Vec<GraphNode> =
+            graph_nodes_from_bytes(&graph_operators_to_bytes(&graph)).unwrap();
+        assert_graphs_match(&graph_nodes_from_operators(&graph), &loaded);
+
+        // A chain written as nodes can still be read as operators
+        let loaded: Vec<GraphOperator> =
+            graph_operators_from_bytes(&graph_nodes_to_bytes(&graph_nodes_from_operators(&graph)))
+                .unwrap();
+        assert_graphs_match(&graph, &loaded);
+    }
+
+    // Reading a graph with branches as a chain would lose its edges
+    #[test]
+    fn graph_nodes_not_a_chain() {
+        let bytes: Vec<u8> = graph_nodes_to_bytes(&graph_nodes());
+        match graph_operators_from_bytes(&bytes) {
+            Err(error @ GraphFileError::NotAChain { index: 3 }) => {
+                assert!(error
+                    .to_string()
+                    .starts_with("Graph file holds a graph with branches"));
+            }
+            result => panic!("Expected a graph which isn't a chain, got {:?}", result),
+        }
+    }
+
+    // A graph which would be written intact, but can't be run
+    #[test]
+    fn save_invalid_graph() {
+        let path: PathBuf = temp_path("save_invalid_graph");
+        let mut graph: Vec<GraphOperator> = graph();
+        graph[1] = GraphOperator::Linear {
+            weights: Tensor2D::new(0.01, 4, 4),
+            bias: Tensor2D::new(0.2, 2, 4),
+        };
+        let expected: GraphError = validate_graph_operators(&graph).unwrap_err();
+
+        match save_graph_operators(&path, &graph) {
+            Err(GraphFileError::InvalidGraph(error)) => assert_eq!(error, expected),
+            result => panic!("Expected an invalid graph, got {:?}", result),
+        }
+        assert!(!path.exists());
+
+        // Elementwise operators need the inputs of a graph of nodes
+        let graph: Vec<GraphOperator> = graph_nodes()
+            .into_iter()
+            .map(|node| node.operator)
+            .collect();
+        assert!(matches!(
+            save_graph_operators(&path, &graph),
+            Err(GraphFileError::InvalidGraph(
+                GraphError::UnsupportedOperator { index: 3, .. }
+            ))
+        ));
+        assert!(!path.exists());
+
+        let mut graph: Vec<GraphNode> = graph_nodes();
+        graph[3].inputs = vec![2, 5];
+        assert!(matches!(
+            save_graph_nodes(&path, &graph),
+            Err(GraphFileError::InvalidGraph(GraphError::MissingInput {
+                index: 3,
+                input: 5
+            }))
+        ));
+        assert!(!path.exists());
+    }
+
+    // A graph which is written and read back intact, but can't be run
+    #[test]
+    fn load_invalid_graph() {
+        let path: PathBuf = temp_path("load_invalid_graph");
+        let mut graph: Vec<GraphOperator> = graph();
+        graph[1] = GraphOperator::Linear {
+            weights: Tensor2D::new(0.01, 4, 4),
+            bias: Tensor2D::new(0.2, 2, 4),
+        };
+        let expected: GraphError = validate_graph_operators(&graph).unwrap_err();
+
+        // Saving checks the graph, so the file is written by hand
+        std::fs::write(&path, graph_operators_to_bytes(&graph)).unwrap();
+        let loaded: Result<Vec<GraphOperator>, GraphFileError> = load_graph_operators(&path);
+        let loaded_nodes: Result<Vec<GraphNode>, GraphFileError> = load_graph_nodes(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        match loaded {
+            Err(GraphFileError::InvalidGraph(error)) => {
+                assert_eq!(error, expected);
+                assert!(GraphFileError::InvalidGraph(error)
+                    .to_string()
+                    .starts_with("Graph file holds an invalid graph"));
+            }
+            result => panic!("Expected an invalid graph, got {:?}", result),
+        }
+        assert!(matches!(loaded_nodes, Err(GraphFileError::InvalidGraph(_))));
+    }
+
+    #[test]
+    fn missing_file() {
+        let path: PathBuf = temp_path("missing_file");
+        assert!(matches!(
+            load_graph_operators(&path),
+            Err(GraphFileError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn little_endian_payload() {
+        let graph: Vec<GraphOperator> = vec![GraphOperator::HostToDevice {
+            input: Tensor2D {
+                data: vec![1.0, -2.5],
+                row_count: 1,
+                column_count: 2,
+            },
+        }];
+        let bytes: Vec<u8> = graph_operators_to_bytes(&graph);
+
+        // The payload is right before the checksum
+        let payload: &[u8] = &bytes[bytes.len() - 4 - 8..bytes.len() - 4];
+        assert_eq!(payload[0..4], [0x00, 0x00, 0x80, 0x3F]);
+        assert_eq!(payload[4..8], [0x00, 0x00, 0x20, 0xC0]);
+    }
+
+    #[test]
+    fn invalid_magic() {
+        let mut bytes: Vec<u8> = graph_operators_to_bytes(&graph());
+        bytes[0] = b'X';
+        assert!(matches!(
+            graph_operators_from_bytes(&bytes),
+            Err(GraphFileError::InvalidMagic)
+        ));
+        assert!(matches!(
+            graph_operators_from_bytes(&[]),
+            Err(GraphFileError::InvalidMagic)
+        ));
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let mut bytes: Vec<u8> = graph_operators_to_bytes(&graph());
+        bytes[8..12].copy_from_slice(&(GRAPH_FILE_VERSION + 1).to_le_bytes());
+        match graph_operators_from_bytes(&bytes) {
+            Err(GraphFileError::UnsupportedVersion { found, supported }) => {
+                assert_eq!(found, GRAPH_FILE_VERSION + 1);
+                assert_eq!(supported, GRAPH_FILE_VERSION);
+            }
+            result => panic!("Expected an unsupported version, got {:?}", result),
+        }
+    }
+
+    // Cutting the file anywhere has to give an error, never a panic
+    #[test]
+    fn truncated() {
+        let bytes: Vec<u8> = graph_operators_to_bytes(&graph());
+        for length in 8..bytes.len() {
+            match graph_operators_from_bytes(&bytes[0..length]) {
+                Err(GraphFileError::Truncated { expected, found }) => {
+                    assert_eq!(found, length);
+                    assert!(length < expected);
+                }
+                // Cutting into the header leaves JSON we can't read, which only the checksum catches
+                Err(GraphFileError::ChecksumMismatch { .. }) => {}
+                result => panic!("Expected a truncated file at {}, got {:?}", length, result),
+            }
+        }
+    }
+
+    #[test]
+    fn trailing_bytes() {
+        let mut bytes: Vec<u8> = graph_operators_to_bytes(&graph());
+        bytes.push(0);
+        assert!(matches!(
+            graph_operators_from_bytes(&bytes),
+            Err(GraphFileError::TrailingBytes { .. })
+        ));
+    }
+
+    // Flipping a bit anywhere after the magic number and version has to be caught
+    #[test]
+    fn corrupt() {
+        let bytes: Vec<u8> = graph_operators_to_bytes(&graph());
+        for index in 16..bytes.len() {
+            let mut corrupt: Vec<u8> = bytes.clone();
+            corrupt[index] ^= 0x10;
+            assert!(
+                graph_operators_from_bytes(&corrupt).is_err(),
+                "Flipping a bit in byte {} wasn't caught",
+                index
+            );
+        }
+    }
+
+    // A file with a valid checksum, but a header which doesn't make sense
+    fn with_header(header: &str, payload: &[u8]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = graph_operators_to_bytes(&[])[0..12].to_vec();
+        bytes.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(header.as_bytes());
+        bytes.extend_from_slice(payload);
+        let checksum: u32 = crc32(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    // Files written before Softmax had an axis don't store one
+    #[test]
+    fn softmax_defaults_to_global() {
+        let header: &str =
+            r#"{"operators":[{"operator":"Softmax","tensors":[]}],"payload_length":0}"#;
+        let loaded: Vec<GraphOperator> =
+            graph_operators_from_bytes(&with_header(header, &[])).unwrap();
+        assert_graphs_match(&[GraphOperator::Softmax { axis: Axis::Global }], &loaded);
+    }
+
+    #[test]
+    fn invalid_header() {
+        let headers: [&str; 8] = [
+            "not json",
+            r#"{"operators":[{"operator":"ReLU","tensors":[{"name":"input","row_count":1,"column_count":1}]}],"payload_length":4}"#,
+            r#"{"operators":[{"operator":"HostToDevice","tensors":[{"name":"input","row_count":2,"column_count":1}]}],"payload_length":4}"#,
+            r#"{"operators":[{"operator":"HostToDevice","tensors":[{"name":"input","row_count":18446744073709551615,"column_count":2}]}],"payload_length":4}"#,
+            r#"{"operators":[{"operator":"Sum","axis":"Diagonal","tensors":[]}],"payload_length":4}"#,
+            r#"{"operators":[{"operator":"HostToDevice","tensors":[{"name":"input","row_count":2,"column_count":1,"precision":"F16"}]}],"payload_length":4}"#,
+            r#"{"operators":[{"operator":"QuantizedLinear","tensors":[{"name":"weights","row_count":1,"column_count":1,"precision":"F8"}]}],"payload_length":4}"#,
+            r#"{"operators":[{"operator":"QuantizedLinear","tensors":[{"name":"weights","row_count":2,"column_count":2,"precision":"Int8"}]}],"payload_length":4}"#,
+        ];
+
+        for header in headers {
+            let bytes: Vec<u8> = with_header(header, &1.0f32.to_le_bytes());
+            match graph_operators_from_bytes(&bytes) {
+                Err(error @ GraphFileError::InvalidHeader(_)) => {
+                    assert!(error
+                        .to_string()
+                        .starts_with("Graph file has an invalid header"));
+                }
+                result => panic!(
+                    "Expected an invalid header for {}, got {:?}",
+                    header, result
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_operator() {
+        let header: &str = r#"{"operators":[{"operator":"ReLU","tensors":[]},{"operator":"Convolution","tensors":[]}],"payload_length":0}"#;
+        match graph_operators_from_bytes(&with_header(header, &[])) {
+            Err(error @ GraphFileError::UnknownOperator { index: 1, .. }) => {
+                assert_eq!(
+                    error.to_string(),
+                    "Graph file has the unknown operator Convolution at index 1"
+                );
+            }
+            result => panic!("Expected an unknown operator, got {:?}", result),
+        }
+    }
+}
@@ -1,8 +1,27 @@
 pub mod benchmark_plot;
+pub mod benchmark_report;
+pub mod benchmark_report_test;
 pub mod configuration;
+pub mod gpu_reduction;
+pub mod gpu_reduction_test;
 pub mod gpu_utilities;
+pub mod gpu_utilities_test;
 pub mod graph_operators;
+pub mod graph_serialization;
+pub mod graph_serialization_test;
+pub mod onnx_import;
+pub mod onnx_import_test;
 pub mod performance_measurement;
 pub mod tensor2d;
+pub mod tensor2d_axis;
+pub mod tensor2d_axis_test;
+pub mod tensor2d_gemm;
+pub mod tensor2d_gemm_test;
 pub mod tensor2d_gpu;
+pub mod tensor2d_parallel;
+pub mod tensor2d_parallel_test;
+pub mod tensor2d_precision;
+pub mod tensor2d_precision_test;
 pub mod tensor2d_test;
+pub mod tensor_nd;
+pub mod tensor_nd_test;
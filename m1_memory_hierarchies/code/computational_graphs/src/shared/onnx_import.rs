@@ -0,0 +1,649 @@
+use std::{collections::HashMap, fmt, fs, path::Path};
+
+use prost::Message;
+
+use super::{graph_operators::GraphOperator, tensor2d::Tensor2D, tensor2d_axis::Axis};
+
+// The subset of onnx.proto needed to import a multilayer perceptron.
+// Field tags are the ones from the ONNX specification, anything not
+// declared here is skipped when decoding.
+#[derive(Clone, PartialEq, Message)]
+pub struct ModelProto {
+    #[prost(int64, tag = "1")]
+    pub ir_version: i64,
+    #[prost(string, tag = "2")]
+    pub producer_name: String,
+    #[prost(message, optional, tag = "7")]
+    pub graph: Option<GraphProto>,
+    #[prost(message, repeated, tag = "8")]
+    pub opset_import: Vec<OperatorSetIdProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct OperatorSetIdProto {
+    #[prost(string, tag = "1")]
+    pub domain: String,
+    #[prost(int64, tag = "2")]
+    pub version: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct GraphProto {
+    #[prost(message, repeated, tag = "1")]
+    pub node: Vec<NodeProto>,
+    #[prost(string, tag = "2")]
+    pub name: String,
+    #[prost(message, repeated, tag = "5")]
+    pub initializer: Vec<TensorProto>,
+    #[prost(message, repeated, tag = "11")]
+    pub input: Vec<ValueInfoProto>,
+    #[prost(message, repeated, tag = "12")]
+    pub output: Vec<ValueInfoProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct NodeProto {
+    #[prost(string, repeated, tag = "1")]
+    pub input: Vec<String>,
+    #[prost(string, repeated, tag = "2")]
+    pub output: Vec<String>,
+    #[prost(string, tag = "3")]
+    pub name: String,
+    #[prost(string, tag = "4")]
+    pub op_type: String,
+    #[prost(message, repeated, tag = "5")]
+    pub attribute: Vec<AttributeProto>,
+    #[prost(string, tag = "7")]
+    pub domain: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct AttributeProto {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(float, tag = "2")]
+    pub f: f32,
+    #[prost(int64, tag = "3")]
+    pub i: i64,
+    // AttributeType, 1 is FLOAT and 2 is INT
+    #[prost(int32, tag = "20")]
+    pub r#type: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct TensorProto {
+    #[prost(int64, repeated, tag = "1")]
+    pub dims: Vec<i64>,
+    // DataType, 1 is FLOAT
+    #[prost(int32, tag = "2")]
+    pub data_type: i32,
+    #[prost(float, repeated, tag = "4")]
+    pub float_data: Vec<f32>,
+    #[prost(string, tag = "8")]
+    pub name: String,
+    #[prost(bytes = "vec", tag = "9")]
+    pub raw_data: Vec<u8>,
+    // DataLocation, 1 is EXTERNAL
+    #[prost(int32, tag = "14")]
+    pub data_location: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ValueInfoProto {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(message, optional, tag = "2")]
+    pub r#type: Option<TypeProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct TypeProto {
+    #[prost(message, optional, tag = "1")]
+    pub tensor_type: Option<TensorTypeProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct TensorTypeProto {
+    #[prost(int32, tag = "1")]
+    pub elem_type: i32,
+    #[prost(message, optional, tag = "2")]
+    pub shape: Option<TensorShapeProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct TensorShapeProto {
+    #[prost(message, repeated, tag = "1")]
+    pub dim: Vec<DimensionProto>,
+}
+
+// Either a fixed size or a symbolic one, such as "batch"
+#[derive(Clone, PartialEq, Message)]
+pub struct DimensionProto {
+    #[prost(int64, optional, tag = "1")]
+    pub dim_value: Option<i64>,
+    #[prost(string, optional, tag = "2")]
+    pub dim_param: Option<String>,
+}
+
+const DATA_TYPE_FLOAT: i32 = 1;
+const DATA_LOCATION_EXTERNAL: i32 = 1;
+
+#[derive(Debug)]
+pub enum OnnxImportError {
+    Io(std::io::Error),
+    Decode(prost::DecodeError),
+    // An operator which has no GraphOperator counterpart
+    UnsupportedOperator { node: String, op_type: String },
+    // A supported operator used in a way the importer can't map, such as transposed input
+    Unsupported { node: String, reason: String },
+    InvalidModel(String),
+}
+
+impl fmt::Display for OnnxImportError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OnnxImportError::Io(error) => {
+                write!(formatter, "Failed to read ONNX model: {}", error)
+            }
+            OnnxImportError::Decode(error) => {
+                write!(formatter, "Failed to decode ONNX model: {}", error)
+            }
+            OnnxImportError::UnsupportedOperator { node, op_type } => write!(
+                formatter,
+                "ONNX node {} uses the unsupported operator {}, only Gemm, MatMul, Add, Relu and Softmax are supported",
+                node, op_type
+            ),
+            OnnxImportError::Unsupported { node, reason } => {
+                write!(formatter, "ONNX node {} is unsupported: {}", node, reason)
+            }
+            OnnxImportError::InvalidModel(reason) => {
+                write!(formatter, "Invalid ONNX model: {}", reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for OnnxImportError {}
+
+impl From<std::io::Error> for OnnxImportError {
+    fn from(error: std::io::Error) -> Self {
+        OnnxImportError::Io(error)
+    }
+}
+
+impl From<prost::DecodeError> for OnnxImportError {
+    fn from(error: prost::DecodeError) -> Self {
+        OnnxImportError::Decode(error)
+    }
+}
+
+fn node_label(node: &NodeProto) -> String {
+    if node.name.is_empty() {
+        node.op_type.clone()
+    } else {
+        node.name.clone()
+    }
+}
+
+fn unsupported(node: &NodeProto, reason: String) -> OnnxImportError {
+    OnnxImportError::Unsupported {
+        node: node_label(node),
+        reason,
+    }
+}
+
+// Converts an initializer to a Tensor2D. Vectors become a single row.
+fn tensor_from_initializer(tensor: &TensorProto) -> Result<Tensor2D, OnnxImportError> {
+    if tensor.data_type != DATA_TYPE_FLOAT {
+        return Err(OnnxImportError::InvalidModel(format!(
+            "initializer {} has data type {}, only FLOAT (1) is supported",
+            tensor.name, tensor.data_type
+        )));
+    }
+    if tensor.data_location == DATA_LOCATION_EXTERNAL {
+        return Err(OnnxImportError::InvalidModel(format!(
+            "initializer {} is stored in an external file, which isn't supported",
+            tensor.name
+        )));
+    }
+
+    let (row_count, column_count): (usize, usize) = match tensor.dims.as_slice() {
+        [column_count] if 0 < *column_count => (1, *column_count as usize),
+        [row_count, column_count] if 0 < *row_count && 0 < *column_count => {
+            (*row_count as usize, *column_count as usize)
+        }
+        dims => {
+            return Err(OnnxImportError::InvalidModel(format!(
+                "initializer {} has the dimensions {:?}, only vectors and matrices are supported",
+                tensor.name, dims
+            )))
+        }
+    };
+
+    // Exporters write the data either as raw little-endian bytes or as a list of floats
+    let value_count: usize = if tensor.raw_data.is_empty() {
+        tensor.float_data.len()
+    } else {
+        tensor.raw_data.len() / 4
+    };
+    if row_count.checked_mul(column_count) != Some(value_count)
+        || !tensor.raw_data.len().is_multiple_of(4)
+    {
+        return Err(OnnxImportError::InvalidModel(format!(
+            "initializer {} has the dimensions {:?}, but {} values",
+            tensor.name, tensor.dims, value_count
+        )));
+    }
+
+    let data: Vec<f32> = if tensor.raw_data.is_empty() {
+        tensor.float_data.clone()
+    } else {
+        tensor
+            .raw_data
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect()
+    };
+
+    Ok(Tensor2D {
+        data,
+        row_count,
+        column_count,
+    })
+}
+
+fn transpose(tensor: &Tensor2D) -> Tensor2D {
+    let mut output: Tensor2D = Tensor2D::new(0.0, tensor.column_count, tensor.row_count);
+    for row in 0..tensor.row_count {
+        for column in 0..tensor.column_count {
+            output.data[column * tensor.row_count + row] =
+                tensor.data[row * tensor.column_count + column];
+        }
+    }
+    output
+}
+
+// GraphOperator::Linear needs a bias with the same shape as its output,
+// while ONNX broadcasts a vector across the rows
+fn broadcast_bias(
+    node: &NodeProto,
+    bias: &Tensor2D,
+    row_count: usize,
+    column_count: usize,
+) -> Result<Tensor2D, OnnxImportError> {
+    if bias.column_count != column_count || (bias.row_count != 1 && bias.row_count != row_count) {
+        return Err(unsupported(
+            node,
+            format!(
+                "bias of shape ({}, {}) can't be broadcast to the output of shape ({}, {})",
+                bias.row_count, bias.column_count, row_count, column_count
+            ),
+        ));
+    }
+
+    let mut output: Tensor2D = Tensor2D::new(0.0, row_count, column_count);
+    for row in 0..row_count {
+        let source_row: usize = if bias.row_count == 1 { 0 } else { row };
+        output.data[row * column_count..(row + 1) * column_count].copy_from_slice(
+            &bias.data[source_row * column_count..(source_row + 1) * column_count],
+        );
+    }
+    Ok(output)
+}
+
+struct Importer<'a> {
+    initializers: HashMap<&'a str, &'a TensorProto>,
+    row_count: usize,
+    // The name and column count of the tensor the next node has to read from
+    current: &'a str,
+    column_count: usize,
+    graph_operators: Vec<GraphOperator>,
+}
+
+impl<'a> Importer<'a> {
+    fn initializer(&self, node: &NodeProto, name: &str) -> Result<Tensor2D, OnnxImportError> {
+        match self.initializers.get(name) {
+            Some(tensor) => tensor_from_initializer(tensor),
+            None => Err(unsupported(
+                node,
+                format!(
+                    "{} has to be an initializer, weights computed by the graph aren't supported",
+                    name
+                ),
+            )),
+        }
+    }
+
+    // Every node has to read from the output of the node before it,
+    // which is all a chain of GraphOperators can express
+    fn check_chain(&self, node: &NodeProto) -> Result<(), OnnxImportError> {
+        if node.output.len() != 1 {
+            return Err(unsupported(
+                node,
+                format!("it has {} outputs, expected 1", node.output.len()),
+            ));
+        }
+        if !node.input.iter().any(|input| input == self.current) {
+            return Err(unsupported(
+                node,
+                format!(
+                    "it doesn't read from {}, only chains of operators are supported",
+                    self.current
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn advance(&mut self, node: &'a NodeProto, operator: GraphOperator, column_count: usize) {
+        self.graph_operators.push(operator);
+        self.current = node.output[0].as_str();
+        self.column_count = column_count;
+    }
+
+    fn push_linear(
+        &mut self,
+        node: &'a NodeProto,
+        weights: Tensor2D,
+        bias: Option<Tensor2D>,
+    ) -> Result<(), OnnxImportError> {
+        if weights.row_count != self.column_count {
+            return Err(unsupported(
+                node,
+                format!(
+                    "weights of shape ({}, {}) can't be multiplied with the input of shape ({}, {})",
+                    weights.row_count, weights.column_count, self.row_count, self.column_count
+                ),
+            ));
+        }
+
+        let column_count: usize = weights.column_count;
+        let bias: Tensor2D = match bias {
+            Some(bias) => broadcast_bias(node, &bias, self.row_count, column_count)?,
+            None => Tensor2D::new(0.0, self.row_count, column_count),
+        };
+        self.advance(node, GraphOperator::Linear { weights, bias }, column_count);
+        Ok(())
+    }
+
+    // Gemm computes alpha * A' * B' + beta * C, where ' is an optional transpose
+    fn gemm(&mut self, node: &'a NodeProto) -> Result<(), OnnxImportError> {
+        let mut alpha: f32 = 1.0;
+        let mut beta: f32 = 1.0;
+        let mut transpose_b: bool = false;
+        for attribute in &node.attribute {
+            match attribute.name.as_str() {
+                "alpha" => alpha = attribute.f,
+                "beta" => beta = attribute.f,
+                "transA" if attribute.i != 0 => {
+                    return Err(unsupported(
+                        node,
+                        "transposing the input (transA) isn't supported".to_string(),
+                    ))
+                }
+                "transA" => {}
+                "transB" => transpose_b = attribute.i != 0,
+                name => {
+                    return Err(unsupported(
+                        node,
+                        format!("the attribute {} isn't supported", name),
+                    ))
+                }
+            }
+        }
+
+        if node.input.len() < 2 || node.input[0] != self.current {
+            return Err(unsupported(
+                node,
+                format!(
+                    "A has to be {}, the output of the previous node",
+                    self.current
+                ),
+            ));
+        }
+
+        let mut weights: Tensor2D = self.initializer(node, &node.input[1])?;
+        if transpose_b {
+            weights = transpose(&weights);
+        }
+        for value in &mut weights.data {
+            *value *= alpha;
+        }
+
+        // C is optional, an empty name also means it isn't there
+        let bias: Option<Tensor2D> = match node.input.get(2) {
+            Some(name) if !name.is_empty() => {
+                let mut bias: Tensor2D = self.initializer(node, name)?;
+                for value in &mut bias.data {
+                    *value *= beta;
+                }
+                Some(bias)
+            }
+            _ => None,
+        };
+
+        self.push_linear(node, weights, bias)
+    }
+
+    // A MatMul followed by an Add of an initializer is how some exporters write a linear layer
+    fn matmul(
+        &mut self,
+        node: &'a NodeProto,
+        add: Option<&'a NodeProto>,
+    ) -> Result<bool, OnnxImportError> {
+        if node.input.len() != 2 || node.input[0] != self.current {
+            return Err(unsupported(
+                node,
+                format!(
+                    "the left input has to be {}, the output of the previous node",
+                    self.current
+                ),
+            ));
+        }
+        let weights: Tensor2D = self.initializer(node, &node.input[1])?;
+
+        let bias_name: Option<&str> = add.and_then(|add| {
+            if add.input.len() != 2 || add.output.len() != 1 {
+                return None;
+            }
+            let (left, right): (&str, &str) = (&add.input[0], &add.input[1]);
+            if left == node.output[0] && self.initializers.contains_key(right) {
+                Some(right)
+            } else if right == node.output[0] && self.initializers.contains_key(left) {
+                Some(left)
+            } else {
+                None
+            }
+        });
+
+        match (bias_name, add) {
+            (Some(bias_name), Some(add)) => {
+                let bias: Tensor2D = self.initializer(add, bias_name)?;
+                self.push_linear(node, weights, Some(bias))?;
+                self.current = add.output[0].as_str();
+                Ok(true)
+            }
+            _ => {
+                self.push_linear(node, weights, None)?;
+                Ok(false)
+            }
+        }
+    }
+
+    fn softmax(&mut self, node: &'a NodeProto) -> Result<(), OnnxImportError> {
+        for attribute in &node.attribute {
+            match attribute.name.as_str() {
+                // For a matrix both mean softmax across every row
+                "axis" if attribute.i == -1 || attribute.i == 1 => {}
+                name => {
+                    return Err(unsupported(
+                        node,
+                        format!("the attribute {} = {} isn't supported", name, attribute.i),
+                    ))
+                }
+            }
+        }
+
+        // With a single row the softmax over the whole tensor is the same, and
+        // unlike a softmax along the rows it can be fused with a preceding Linear and ReLU
+        let axis: Axis = if self.row_count == 1 {
+            Axis::Global
+        } else {
+            Axis::Rows
+        };
+        self.advance(node, GraphOperator::Softmax { axis }, self.column_count);
+        Ok(())
+    }
+}
+
+// Checks the dimensions declared for the graph input against the input we were given.
+// Symbolic dimensions, like a batch size, match anything.
+fn check_input_shape(value_info: &ValueInfoProto, input: &Tensor2D) -> Result<(), OnnxImportError> {
+    let dims: &[DimensionProto] = match value_info
+        .r#type
+        .as_ref()
+        .and_then(|r#type| r#type.tensor_type.as_ref())
+        .and_then(|tensor_type| tensor_type.shape.as_ref())
+    {
+        Some(shape) => &shape.dim,
+        None => return Ok(()),
+    };
+
+    if dims.len() != 2 {
+        return Err(OnnxImportError::InvalidModel(format!(
+            "the input {} has {} dimensions, only matrices are supported",
+            value_info.name,
+            dims.len()
+        )));
+    }
+
+    let expected: [usize; 2] = [input.row_count, input.column_count];
+    for (dim, expected) in dims.iter().zip(expected) {
+        if let Some(value) = dim.dim_value {
+            if value != expected as i64 {
+                return Err(OnnxImportError::InvalidModel(format!(
+                    "the input {} has the shape ({:?}, {:?}), which doesn't match the given input of shape ({}, {})",
+                    value_info.name,
+                    dims[0].dim_value,
+                    dims[1].dim_value,
+                    input.row_count,
+                    input.column_count
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Maps an ONNX model of Gemm, MatMul + Add, Relu and Softmax nodes onto a chain of
+// GraphOperators, starting with HostToDevice of input and ending with DeviceToHost.
+// The input is needed as every bias is broadcast to the number of rows of the input.
+pub fn graph_operators_from_onnx_bytes(
+    bytes: &[u8],
+    input: Tensor2D,
+) -> Result<Vec<GraphOperator>, OnnxImportError> {
+    let model: ModelProto = ModelProto::decode(bytes)?;
+    let graph: &GraphProto = match &model.graph {
+        Some(graph) => graph,
+        None => return Err(OnnxImportError::InvalidModel("it has no graph".to_string())),
+    };
+
+    let initializers: HashMap<&str, &TensorProto> = graph
+        .initializer
+        .iter()
+        .map(|tensor| (tensor.name.as_str(), tensor))
+        .collect();
+
+    // Older exporters also list the initializers as inputs
+    let inputs: Vec<&ValueInfoProto> = graph
+        .input
+        .iter()
+        .filter(|value_info| !initializers.contains_key(value_info.name.as_str()))
+        .collect();
+    if inputs.len() != 1 || graph.output.len() != 1 {
+        return Err(OnnxImportError::InvalidModel(format!(
+            "the graph has {} inputs and {} outputs, expected 1 of each",
+            inputs.len(),
+            graph.output.len()
+        )));
+    }
+    check_input_shape(inputs[0], &input)?;
+
+    let mut importer: Importer = Importer {
+        initializers,
+        row_count: input.row_count,
+        current: inputs[0].name.as_str(),
+        column_count: input.column_count,
+        graph_operators: vec![GraphOperator::HostToDevice { input }],
+    };
+
+    let mut node_index: usize = 0;
+    while node_index < graph.node.len() {
+        let node: &NodeProto = &graph.node[node_index];
+        if !node.domain.is_empty() && node.domain != "ai.onnx" {
+            return Err(OnnxImportError::UnsupportedOperator {
+                node: node_label(node),
+                op_type: format!("{}.{}", node.domain, node.op_type),
+            });
+        }
+
+        match node.op_type.as_str() {
+            "Gemm" | "MatMul" | "Relu" | "Softmax" | "Add" => importer.check_chain(node)?,
+            op_type => {
+                return Err(OnnxImportError::UnsupportedOperator {
+                    node: node_label(node),
+                    op_type: op_type.to_string(),
+                })
+            }
+        }
+
+        match node.op_type.as_str() {
+            "Gemm" => importer.gemm(node)?,
+            "MatMul" => {
+                let add: Option<&NodeProto> = graph
+                    .node
+                    .get(node_index + 1)
+                    .filter(|next| next.op_type == "Add");
+                if importer.matmul(node, add)? {
+                    node_index += 1;
+                }
+            }
+            "Relu" => {
+                if !node.attribute.is_empty() || node.input.len() != 1 {
+                    return Err(unsupported(node, "Relu takes a single input".to_string()));
+                }
+                let column_count: usize = importer.column_count;
+                importer.advance(node, GraphOperator::ReLU, column_count);
+            }
+            "Softmax" => importer.softmax(node)?,
+            _ => {
+                return Err(unsupported(
+                    node,
+                    "Add is only supported as the bias of a MatMul".to_string(),
+                ))
+            }
+        }
+
+        node_index += 1;
+    }
+
+    if importer.current != graph.output[0].name {
+        return Err(OnnxImportError::InvalidModel(format!(
+            "the chain of nodes ends in {}, but the graph output is {}",
+            importer.current, graph.output[0].name
+        )));
+    }
+
+    let mut graph_operators: Vec<GraphOperator> = importer.graph_operators;
+    graph_operators.push(GraphOperator::DeviceToHost);
+    Ok(graph_operators)
+}
+
+pub fn load_onnx_graph_operators(
+    path: &Path,
+    input: Tensor2D,
+) -> Result<Vec<GraphOperator>, OnnxImportError> {
+    let bytes: Vec<u8> = fs::read(path)?;
+    graph_operators_from_onnx_bytes(&bytes, input)
+}
@@ -0,0 +1,327 @@
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use prost::Message;
+
+    use crate::{
+        graph::{graph_runner::GraphRunner, graph_validation::validate_graph_operators},
+        shared::{
+            graph_operators::GraphOperator,
+            onnx_import::{
+                graph_operators_from_onnx_bytes, load_onnx_graph_operators, AttributeProto,
+                GraphProto, ModelProto, NodeProto, OnnxImportError, TensorProto, ValueInfoProto,
+            },
+            tensor2d::Tensor2D,
+            tensor2d_axis::Axis,
+        },
+    };
+
+    const ERROR_TOLERANCE: f32 = 0.00001;
+
+    // The fixtures and their expected outputs are written by fixtures/onnx/generate_fixtures.py
+    fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join("onnx")
+            .join(name)
+    }
+
+    fn tensor(data: &[f32], row_count: usize, column_count: usize) -> Tensor2D {
+        Tensor2D {
+            data: data.to_vec(),
+            row_count,
+            column_count,
+        }
+    }
+
+    fn assert_output(graph_operators: &[GraphOperator], expected: &Tensor2D) {
+        assert_eq!(validate_graph_operators(graph_operators), Ok(()));
+
+        for fuse_operators in [false, true] {
+            let mut runner: GraphRunner = GraphRunner::new(graph_operators, fuse_operators);
+            let output: Tensor2D = runner.run();
+
+            assert_eq!(output.row_count, expected.row_count);
+            assert_eq!(output.column_count, expected.column_count);
+            for (actual, expected) in output.data.iter().zip(&expected.data) {
+                assert!(
+                    (actual - expected).abs() < ERROR_TOLERANCE,
+                    "expected {:?}, got {:?}",
+                    expected,
+                    output.data
+                );
+            }
+        }
+    }
+
+    fn operator_names(graph_operators: &[GraphOperator]) -> Vec<&'static str> {
+        graph_operators
+            .iter()
+            .map(|operator| match operator {
+                GraphOperator::HostToDevice { .. } => "HostToDevice",
+                GraphOperator::DeviceToHost => "DeviceToHost",
+                GraphOperator::Linear { .. } => "Linear",
+                GraphOperator::ReLU => "ReLU",
+                GraphOperator::Softmax { .. } => "Softmax",
+                _ => "Other",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn gemm_relu_softmax() {
+        let input: Tensor2D = tensor(&[0.5, -1.0, 0.25, 2.0], 1, 4);
+        let graph_operators: Vec<GraphOperator> =
+            load_onnx_graph_operators(&fixture("mlp_gemm.onnx"), input).unwrap();
+
+        assert_eq!(
+            operator_names(&graph_operators),
+            vec![
+                "HostToDevice",
+                "Linear",
+                "ReLU",
+                "Linear",
+                "Softmax",
+                "DeviceToHost"
+            ]
+        );
+        // transB = 1 means the weights are stored as (output, input)
+        if let GraphOperator::Linear { weights, bias } = &graph_operators[1] {
+            assert_eq!((weights.row_count, weights.column_count), (4, 3));
+            assert_eq!((bias.row_count, bias.column_count), (1, 3));
+        }
+
+        assert_output(&graph_operators, &tensor(&[0.3390569, 0.6609431], 1, 2));
+    }
+
+    #[test]
+    fn matmul_add_relu() {
+        let input: Tensor2D = tensor(&[0.5, -1.0, 0.25, 2.0, -0.75, 1.5, 1.0, -0.5], 2, 4);
+        let graph_operators: Vec<GraphOperator> =
+            load_onnx_graph_operators(&fixture("mlp_matmul_add.onnx"), input).unwrap();
+
+        assert_eq!(
+            operator_names(&graph_operators),
+            vec!["HostToDevice", "Linear", "ReLU", "Linear", "DeviceToHost"]
+        );
+        // The bias vector is broadcast to every row of the input
+        if let GraphOperator::Linear { weights: _, bias } = &graph_operators[3] {
+            assert_eq!((bias.row_count, bias.column_count), (2, 2));
+            assert_eq!(bias.data[0..2], bias.data[2..4]);
+        }
+
+        assert_output(&graph_operators, &tensor(&[0.24, -0.25, 0.3, -0.5], 2, 2));
+    }
+
+    #[test]
+    fn unsupported_operator() {
+        let input: Tensor2D = tensor(&[0.5, -1.0, 0.25, 2.0], 1, 4);
+        match load_onnx_graph_operators(&fixture("unsupported_operator.onnx"), input) {
+            Err(error @ OnnxImportError::UnsupportedOperator { .. }) => {
+                if let OnnxImportError::UnsupportedOperator { node, op_type } = &error {
+                    assert_eq!(node, "sigmoid_output");
+                    assert_eq!(op_type, "Sigmoid");
+                }
+                assert!(error.to_string().contains("unsupported operator Sigmoid"));
+            }
+            result => panic!("Expected an unsupported operator, got {:?}", result),
+        }
+    }
+
+    // ONNX Softmax is per row, so a batch of several rows gets a softmax along the rows
+    #[test]
+    fn softmax_along_rows() {
+        let input: Tensor2D = tensor(&[0.5, -1.0, 0.25, 2.0, 0.5, -1.0, 0.25, 2.0], 2, 4);
+        let graph_operators: Vec<GraphOperator> =
+            load_onnx_graph_operators(&fixture("mlp_gemm.onnx"), input).unwrap();
+
+        assert!(matches!(
+            graph_operators[4],
+            GraphOperator::Softmax { axis: Axis::Rows }
+        ));
+        assert_output(
+            &graph_operators,
+            &tensor(&[0.3390569, 0.6609431, 0.3390569, 0.6609431], 2, 2),
+        );
+    }
+
+    // mlp_matmul_add.onnx declares its input as (2, 4)
+    #[test]
+    fn input_shape_mismatch() {
+        for (row_count, column_count) in [(1, 4), (2, 3)] {
+            let input: Tensor2D = Tensor2D::new(0.5, row_count, column_count);
+            assert!(matches!(
+                load_onnx_graph_operators(&fixture("mlp_matmul_add.onnx"), input),
+                Err(OnnxImportError::InvalidModel(_))
+            ));
+        }
+
+        // The batch size of mlp_gemm.onnx is symbolic, but the input width isn't
+        assert!(matches!(
+            load_onnx_graph_operators(&fixture("mlp_gemm.onnx"), Tensor2D::new(0.5, 1, 5)),
+            Err(OnnxImportError::InvalidModel(_))
+        ));
+    }
+
+    #[test]
+    fn missing_file() {
+        assert!(matches!(
+            load_onnx_graph_operators(&fixture("missing.onnx"), Tensor2D::new(0.5, 1, 4)),
+            Err(OnnxImportError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn not_protobuf() {
+        assert!(matches!(
+            graph_operators_from_onnx_bytes(&[0xFF; 8], Tensor2D::new(0.5, 1, 4)),
+            Err(OnnxImportError::Decode(_))
+        ));
+    }
+
+    fn node(op_type: &str, inputs: &[&str], output: &str) -> NodeProto {
+        NodeProto {
+            input: inputs.iter().map(|input| input.to_string()).collect(),
+            output: vec![output.to_string()],
+            name: output.to_string(),
+            op_type: op_type.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn initializer(name: &str, dims: &[i64], data: &[f32]) -> TensorProto {
+        TensorProto {
+            dims: dims.to_vec(),
+            data_type: 1,
+            float_data: data.to_vec(),
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn value_info(name: &str) -> ValueInfoProto {
+        ValueInfoProto {
+            name: name.to_string(),
+            r#type: None,
+        }
+    }
+
+    // A model reading from "input" and writing to "output"
+    fn model_bytes(nodes: Vec<NodeProto>, initializers: Vec<TensorProto>) -> Vec<u8> {
+        ModelProto {
+            ir_version: 8,
+            graph: Some(GraphProto {
+                node: nodes,
+                initializer: initializers,
+                input: vec![value_info("input")],
+                output: vec![value_info("output")],
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+        .encode_to_vec()
+    }
+
+    fn weights() -> TensorProto {
+        initializer("weights", &[2, 2], &[1.0, 2.0, 3.0, 4.0])
+    }
+
+    // Without an Add the linear layer gets a zero bias
+    #[test]
+    fn matmul_without_add() {
+        let bytes: Vec<u8> = model_bytes(
+            vec![
+                node("MatMul", &["input", "weights"], "product"),
+                node("Relu", &["product"], "output"),
+            ],
+            vec![weights()],
+        );
+        let graph_operators: Vec<GraphOperator> =
+            graph_operators_from_onnx_bytes(&bytes, tensor(&[1.0, -1.0], 1, 2)).unwrap();
+
+        assert_output(&graph_operators, &tensor(&[0.0, 0.0], 1, 2));
+        if let GraphOperator::Linear { weights: _, bias } = &graph_operators[1] {
+            assert!(bias.data.iter().all(|value| *value == 0.0));
+        }
+    }
+
+    #[test]
+    fn unsupported_usage() {
+        let transposed_input: NodeProto = NodeProto {
+            attribute: vec![AttributeProto {
+                name: "transA".to_string(),
+                i: 1,
+                r#type: 2,
+                ..Default::default()
+            }],
+            ..node("Gemm", &["input", "weights"], "output")
+        };
+
+        let models: Vec<Vec<u8>> = vec![
+            // Add without a MatMul in front of it
+            model_bytes(
+                vec![node("Add", &["input", "weights"], "output")],
+                vec![weights()],
+            ),
+            // Weights computed by the graph
+            model_bytes(
+                vec![
+                    node("Relu", &["input"], "activated"),
+                    node("MatMul", &["activated", "activated"], "output"),
+                ],
+                vec![],
+            ),
+            // A node which doesn't continue the chain
+            model_bytes(
+                vec![
+                    node("Relu", &["input"], "activated"),
+                    node("Relu", &["input"], "output"),
+                ],
+                vec![],
+            ),
+            model_bytes(vec![transposed_input], vec![weights()]),
+            // Weights which don't fit the input
+            model_bytes(
+                vec![node("MatMul", &["input", "weights"], "output")],
+                vec![initializer("weights", &[3, 2], &[0.0; 6])],
+            ),
+        ];
+
+        for bytes in models {
+            match graph_operators_from_onnx_bytes(&bytes, Tensor2D::new(0.5, 1, 2)) {
+                Err(error @ OnnxImportError::Unsupported { .. }) => {
+                    assert!(error.to_string().starts_with("ONNX node"));
+                }
+                result => panic!("Expected an unsupported node, got {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_model() {
+        let mut integer_weights: TensorProto = weights();
+        integer_weights.data_type = 7;
+
+        let models: Vec<Vec<u8>> = vec![
+            ModelProto::default().encode_to_vec(),
+            model_bytes(
+                vec![node("MatMul", &["input", "weights"], "output")],
+                vec![integer_weights],
+            ),
+            model_bytes(
+                vec![node("MatMul", &["input", "weights"], "output")],
+                vec![initializer("weights", &[2, 2], &[1.0, 2.0, 3.0])],
+            ),
+            // The chain doesn't end in the graph output
+            model_bytes(vec![node("Relu", &["input"], "activated")], vec![]),
+        ];
+
+        for bytes in models {
+            assert!(matches!(
+                graph_operators_from_onnx_bytes(&bytes, Tensor2D::new(0.5, 1, 2)),
+                Err(OnnxImportError::InvalidModel(_))
+            ));
+        }
+    }
+}
@@ -2,17 +2,75 @@ use std::time::{Duration, Instant};
 
 use rand::prelude::*;
 use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
 
 use super::{
     configuration::Configuration, gpu_utilities::GPUHandles, graph_operators::GraphOperator,
-    tensor2d::Tensor2D,
+    tensor2d::Tensor2D, tensor2d_axis::Axis,
 };
 
+// Statistics of the timed samples of one size, in nanoseconds per iteration.
+// A sample is normally a single iteration, but benchmarks which can only time
+// a whole loop record one sample averaged over iterations_per_sample iterations.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct TimingStatistics {
+    pub sample_count: usize,
+    pub iterations_per_sample: usize,
+    pub mean_nanoseconds: f64,
+    pub min_nanoseconds: f64,
+    pub median_nanoseconds: f64,
+    pub p95_nanoseconds: f64,
+    pub standard_deviation_nanoseconds: f64,
+}
+
+impl TimingStatistics {
+    pub fn from_samples(samples_nanoseconds: &[f64], iterations_per_sample: usize) -> Self {
+        if samples_nanoseconds.is_empty() {
+            return TimingStatistics {
+                iterations_per_sample,
+                ..Default::default()
+            };
+        }
+
+        let mut sorted: Vec<f64> = samples_nanoseconds.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let count: usize = sorted.len();
+
+        let mean: f64 = sorted.iter().sum::<f64>() / count as f64;
+        let median: f64 = if count.is_multiple_of(2) {
+            (sorted[count / 2 - 1] + sorted[count / 2]) / 2.0
+        } else {
+            sorted[count / 2]
+        };
+        // Nearest rank, so the p95 is always one of the samples
+        let p95_rank: usize = (0.95 * count as f64).ceil() as usize;
+        let p95: f64 = sorted[p95_rank.max(1) - 1];
+        // Sample standard deviation, a single sample doesn't have any spread
+        let standard_deviation: f64 = if 1 < count {
+            let squared_differences: f64 = sorted.iter().map(|time| (time - mean).powi(2)).sum();
+            (squared_differences / (count - 1) as f64).sqrt()
+        } else {
+            0.0
+        };
+
+        TimingStatistics {
+            sample_count: count,
+            iterations_per_sample,
+            mean_nanoseconds: mean,
+            min_nanoseconds: sorted[0],
+            median_nanoseconds: median,
+            p95_nanoseconds: p95,
+            standard_deviation_nanoseconds: standard_deviation,
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct PerformanceMeasurements {
     pub name: String,
     pub sizes: Vec<usize>,
     pub normalized_times: Vec<f32>,
+    pub statistics: Vec<TimingStatistics>,
 }
 
 impl PerformanceMeasurements {
@@ -25,16 +83,56 @@ impl PerformanceMeasurements {
         debug_assert_eq!(sizes.len(), times_microseconds.len());
 
         let mut normalized_times: Vec<f32> = vec![0.0; sizes.len()];
+        let mut statistics: Vec<TimingStatistics> = vec![TimingStatistics::default(); sizes.len()];
 
         for size_index in 0..times_microseconds.len() {
             let (timing, iterations): (u128, usize) = times_microseconds[size_index];
-            normalized_times[size_index] = (timing as f64 / iterations as f64) as f32;
+            let normalized_time: f64 = timing as f64 / iterations as f64;
+            normalized_times[size_index] = normalized_time as f32;
+            statistics[size_index] = TimingStatistics::from_samples(&[normalized_time], iterations);
         }
 
         Self {
             name,
             sizes,
             normalized_times,
+            statistics,
+        }
+    }
+
+    // Every sample is the time of a single iteration in nanoseconds
+    pub fn build_from_samples(
+        name: String,
+        sizes: Vec<usize>,
+        samples_nanoseconds: Vec<Vec<f64>>,
+    ) -> Self {
+        debug_assert_eq!(sizes.len(), samples_nanoseconds.len());
+
+        let statistics: Vec<TimingStatistics> = samples_nanoseconds
+            .iter()
+            .map(|samples| TimingStatistics::from_samples(samples, 1))
+            .collect();
+
+        Self::build_from_statistics(name, sizes, statistics)
+    }
+
+    pub fn build_from_statistics(
+        name: String,
+        sizes: Vec<usize>,
+        statistics: Vec<TimingStatistics>,
+    ) -> Self {
+        debug_assert_eq!(sizes.len(), statistics.len());
+
+        let normalized_times: Vec<f32> = statistics
+            .iter()
+            .map(|statistics| statistics.mean_nanoseconds as f32)
+            .collect();
+
+        Self {
+            name,
+            sizes,
+            normalized_times,
+            statistics,
         }
     }
 
@@ -57,7 +155,7 @@ pub fn benchmark_function_vector(
     config: &Configuration,
     names: Vec<String>,
     functions: Vec<fn(&mut Tensor2D, &Tensor2D, &Tensor2D, &mut Tensor2D)>,
-    all_measurements: &mut Vec<PerformanceMeasurements>,
+    all_measurements: &mut [PerformanceMeasurements],
 ) {
     assert!(functions.len() == all_measurements.len());
     assert!(names.len() == all_measurements.len());
@@ -66,7 +164,7 @@ pub fn benchmark_function_vector(
 
     let test_count: usize = all_measurements.len();
     for test_index in 0..test_count {
-        let mut performance_measurements: Vec<(u128, usize)> = vec![(0, 0); range_count];
+        let mut performance_measurements: Vec<Vec<f64>> = vec![Vec::<f64>::new(); range_count];
         let mut total_elements_per_measurement: Vec<usize> = vec![0; range_count];
         let function = functions[test_index];
         for (size_index, size) in config.loop_range.iter().enumerate() {
@@ -76,16 +174,17 @@ pub fn benchmark_function_vector(
             let bias: Tensor2D = Tensor2D::new(0.1, size, size);
             let mut out: Tensor2D = Tensor2D::new(0.0, size, size);
 
-            let now: Instant = Instant::now();
+            let mut samples: Vec<f64> = Vec::<f64>::with_capacity(config.loop_count);
             for _ in 0..config.loop_count {
+                let now: Instant = Instant::now();
                 function(&mut input, &weights, &bias, &mut out);
+                samples.push(now.elapsed().as_nanos() as f64);
             }
-            let elapsed_time: Duration = now.elapsed();
-            performance_measurements[size_index] = (elapsed_time.as_nanos(), config.loop_count);
+            performance_measurements[size_index] = samples;
             total_elements_per_measurement[size_index] = size * size;
         }
         let normalized_measurements: PerformanceMeasurements =
-            PerformanceMeasurements::build_from_measurements(
+            PerformanceMeasurements::build_from_samples(
                 names[test_index].clone(),
                 total_elements_per_measurement,
                 performance_measurements,
@@ -99,7 +198,7 @@ pub fn benchmark_function_vector_gpu(
     names: Vec<String>,
     gpu_handles: &GPUHandles,
     functions: Vec<fn(&GPUHandles, &mut Tensor2D, &Tensor2D, &Tensor2D, &mut Tensor2D)>,
-    all_measurements: &mut Vec<PerformanceMeasurements>,
+    all_measurements: &mut [PerformanceMeasurements],
 ) {
     assert!(functions.len() == all_measurements.len());
     assert!(names.len() == all_measurements.len());
@@ -108,7 +207,7 @@ pub fn benchmark_function_vector_gpu(
 
     let test_count: usize = all_measurements.len();
     for test_index in 0..test_count {
-        let mut performance_measurements: Vec<(u128, usize)> = vec![(0, 0); range_count];
+        let mut performance_measurements: Vec<Vec<f64>> = vec![Vec::<f64>::new(); range_count];
         let mut total_elements_per_measurement: Vec<usize> = vec![0; range_count];
         let function = functions[test_index];
         for (size_index, size) in config.loop_range.iter().enumerate() {
@@ -118,16 +217,17 @@ pub fn benchmark_function_vector_gpu(
             let bias: Tensor2D = Tensor2D::new(0.1, size, size);
             let mut out: Tensor2D = Tensor2D::new(0.0, size, size);
 
-            let now: Instant = Instant::now();
+            let mut samples: Vec<f64> = Vec::<f64>::with_capacity(config.loop_count);
             for _ in 0..config.loop_count {
+                let now: Instant = Instant::now();
                 function(gpu_handles, &mut input, &weights, &bias, &mut out);
+                samples.push(now.elapsed().as_nanos() as f64);
             }
-            let elapsed_time: Duration = now.elapsed();
-            performance_measurements[size_index] = (elapsed_time.as_nanos(), config.loop_count);
+            performance_measurements[size_index] = samples;
             total_elements_per_measurement[size_index] = size * size;
         }
         let normalized_measurements: PerformanceMeasurements =
-            PerformanceMeasurements::build_from_measurements(
+            PerformanceMeasurements::build_from_samples(
                 names[test_index].clone(),
                 total_elements_per_measurement,
                 performance_measurements,
@@ -151,8 +251,8 @@ fn benchmark_function_vector_gpu_graph_inner_loop(
     size: usize,
     depth: usize,
     function_type: &GraphFunction,
-    function: fn(&GPUHandles, &Vec<GraphOperator>, usize, &mut Tensor2D),
-    performance_measurements: &mut [(u128, usize)],
+    function: fn(&GPUHandles, &[GraphOperator], usize, &mut Tensor2D),
+    performance_measurements: &mut [TimingStatistics],
     total_elements_per_measurement: &mut [usize],
     measure_depth: bool,
 ) {
@@ -175,26 +275,30 @@ fn benchmark_function_vector_gpu_graph_inner_loop(
         _ => graph.push(GraphOperator::ReLU),
     };
 
-    graph.push(GraphOperator::Softmax);
+    graph.push(GraphOperator::Softmax { axis: Axis::Global });
     graph.push(GraphOperator::DeviceToHost);
 
     let mut out: Tensor2D = Tensor2D::new(0.0, size, size);
     match function_type {
         GraphFunction::Cpu | GraphFunction::Immediate | GraphFunction::Graph => {
-            let now: Instant = Instant::now();
+            let mut samples: Vec<f64> = Vec::<f64>::with_capacity(config.loop_count);
             for _ in 0..config.loop_count {
+                let now: Instant = Instant::now();
                 function(gpu_handles, &graph, config.loop_count, &mut out);
+                samples.push(now.elapsed().as_nanos() as f64);
             }
-            let elapsed_time: Duration = now.elapsed();
             performance_measurements[measurement_index] =
-                (elapsed_time.as_nanos(), config.loop_count);
+                TimingStatistics::from_samples(&samples, 1);
         }
+        // The loop runs inside the function, so only the whole loop can be timed
         GraphFunction::GraphLoop => {
             let now: Instant = Instant::now();
             function(gpu_handles, &graph, config.loop_count, &mut out);
             let elapsed_time: Duration = now.elapsed();
-            performance_measurements[measurement_index] =
-                (elapsed_time.as_nanos(), config.loop_count);
+            performance_measurements[measurement_index] = TimingStatistics::from_samples(
+                &[elapsed_time.as_nanos() as f64 / config.loop_count as f64],
+                config.loop_count,
+            );
         }
     }
     if measure_depth {
@@ -208,11 +312,11 @@ pub fn benchmark_function_vector_gpu_graph(
     config: &Configuration,
     names: Vec<String>,
     gpu_handles: &GPUHandles,
-    functions: &Vec<(
+    functions: &[(
         GraphFunction,
-        fn(&GPUHandles, &Vec<GraphOperator>, usize, &mut Tensor2D),
-    )>,
-    all_measurements: &mut Vec<PerformanceMeasurements>,
+        fn(&GPUHandles, &[GraphOperator], usize, &mut Tensor2D),
+    )],
+    all_measurements: &mut [PerformanceMeasurements],
     measure_depth: bool,
 ) {
     assert!(functions.len() == all_measurements.len());
@@ -224,11 +328,12 @@ pub fn benchmark_function_vector_gpu_graph(
     let range_count: usize = config.loop_range.len();
 
     for test_index in 0..functions.len() {
-        let mut performance_measurements: Vec<(u128, usize)> = vec![(0, 0); range_count];
+        let mut performance_measurements: Vec<TimingStatistics> =
+            vec![TimingStatistics::default(); range_count];
         let mut total_elements_per_measurement: Vec<usize> = vec![0; range_count];
         let (function_type, function): (
             &GraphFunction,
-            fn(&GPUHandles, &Vec<GraphOperator>, usize, &mut Tensor2D),
+            fn(&GPUHandles, &[GraphOperator], usize, &mut Tensor2D),
         ) = (&functions[test_index].0, functions[test_index].1);
 
         if measure_depth {
@@ -266,7 +371,7 @@ pub fn benchmark_function_vector_gpu_graph(
             }
         }
         let normalized_measurements: PerformanceMeasurements =
-            PerformanceMeasurements::build_from_measurements(
+            PerformanceMeasurements::build_from_statistics(
                 names[test_index].clone(),
                 total_elements_per_measurement,
                 performance_measurements,
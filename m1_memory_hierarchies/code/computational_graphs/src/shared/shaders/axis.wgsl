@@ -0,0 +1,121 @@
+// Softmax, log-softmax and the reductions along an axis.
+// The tensor is split into segments, such as its rows or its columns,
+// and every invocation handles one segment on its own.
+// Element e of segment s is found at
+// (s / inner_count) * outer_stride + (s % inner_count) * inner_stride + e * element_stride
+// A reduction writes the value of segment s to element s of the output.
+struct AxisLayout {
+    segment_count: u32,
+    segment_length: u32,
+    inner_count: u32,
+    outer_stride: u32,
+    inner_stride: u32,
+    element_stride: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> axis_layout: AxisLayout;
+
+@group(0) @binding(1)
+var<storage, read> input: array<f32>;
+
+@group(0) @binding(2)
+var<storage, read_write> output: array<f32>;
+
+fn element_index(segment: u32, element: u32) -> u32 {
+    return (segment / axis_layout.inner_count) * axis_layout.outer_stride
+        + (segment % axis_layout.inner_count) * axis_layout.inner_stride
+        + element * axis_layout.element_stride;
+}
+
+fn segment_max(segment: u32) -> f32 {
+    var max_value: f32 = input[element_index(segment, 0u)];
+    for (var element: u32 = 1u; element < axis_layout.segment_length; element += 1u) {
+        max_value = max(max_value, input[element_index(segment, element)]);
+    }
+    return max_value;
+}
+
+fn segment_sum(segment: u32) -> f32 {
+    var sum: f32 = 0.0;
+    for (var element: u32 = 0u; element < axis_layout.segment_length; element += 1u) {
+        sum += input[element_index(segment, element)];
+    }
+    return sum;
+}
+
+// max + ln(sum(exp(value - max))), subtracting the maximum keeps exp from overflowing
+fn segment_offset(segment: u32) -> f32 {
+    let max_value: f32 = segment_max(segment);
+    var sum: f32 = 0.0;
+    for (var element: u32 = 0u; element < axis_layout.segment_length; element += 1u) {
+        sum += exp(input[element_index(segment, element)] - max_value);
+    }
+    return max_value + log(sum);
+}
+
+@compute @workgroup_size(32, 1, 1)
+fn softmax(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let segment: u32 = global_id.x;
+    if (segment < axis_layout.segment_count) {
+        let offset: f32 = segment_offset(segment);
+        for (var element: u32 = 0u; element < axis_layout.segment_length; element += 1u) {
+            let index: u32 = element_index(segment, element);
+            output[index] = exp(input[index] - offset);
+        }
+    }
+}
+
+@compute @workgroup_size(32, 1, 1)
+fn log_softmax(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let segment: u32 = global_id.x;
+    if (segment < axis_layout.segment_count) {
+        let offset: f32 = segment_offset(segment);
+        for (var element: u32 = 0u; element < axis_layout.segment_length; element += 1u) {
+            let index: u32 = element_index(segment, element);
+            output[index] = input[index] - offset;
+        }
+    }
+}
+
+@compute @workgroup_size(32, 1, 1)
+fn reduce_sum(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let segment: u32 = global_id.x;
+    if (segment < axis_layout.segment_count) {
+        output[segment] = segment_sum(segment);
+    }
+}
+
+@compute @workgroup_size(32, 1, 1)
+fn reduce_max(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let segment: u32 = global_id.x;
+    if (segment < axis_layout.segment_count) {
+        output[segment] = segment_max(segment);
+    }
+}
+
+@compute @workgroup_size(32, 1, 1)
+fn reduce_mean(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let segment: u32 = global_id.x;
+    if (segment < axis_layout.segment_count) {
+        output[segment] = segment_sum(segment) / f32(axis_layout.segment_length);
+    }
+}
+
+// The position of the first maximum in the segment
+@compute @workgroup_size(32, 1, 1)
+fn reduce_argmax(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let segment: u32 = global_id.x;
+    if (segment < axis_layout.segment_count) {
+        var max_value: f32 = input[element_index(segment, 0u)];
+        var max_element: u32 = 0u;
+        for (var element: u32 = 1u; element < axis_layout.segment_length; element += 1u) {
+            let value: f32 = input[element_index(segment, element)];
+            if (max_value < value) {
+                max_value = value;
+                max_element = element;
+            }
+        }
+        output[segment] = f32(max_element);
+    }
+}
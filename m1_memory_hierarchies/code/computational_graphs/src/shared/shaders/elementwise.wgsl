@@ -0,0 +1,76 @@
+struct TensorDimensions {
+    left_row_count: u32,
+    left_column_count: u32,
+    right_row_count: u32,
+    right_column_count: u32,
+    output_row_count: u32,
+    output_column_count: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> dimensions: TensorDimensions;
+
+@group(0) @binding(1)
+var<storage, read> left: array<f32>;
+
+@group(0) @binding(2)
+var<storage, read> right: array<f32>;
+
+@group(0) @binding(3)
+var<storage, read_write> output: array<f32>;
+
+// A dimension of size 1 is broadcast by always reading index 0 along it.
+// This lets us add a row vector or a column vector to a matrix.
+fn broadcast_index(row_index: u32, column_index: u32, row_count: u32, column_count: u32) -> u32 {
+    let row: u32 = select(row_index, 0u, row_count == 1u);
+    let column: u32 = select(column_index, 0u, column_count == 1u);
+    return row * column_count + column;
+}
+
+fn left_value(row_index: u32, column_index: u32) -> f32 {
+    return left[broadcast_index(row_index, column_index, dimensions.left_row_count, dimensions.left_column_count)];
+}
+
+fn right_value(row_index: u32, column_index: u32) -> f32 {
+    return right[broadcast_index(row_index, column_index, dimensions.right_row_count, dimensions.right_column_count)];
+}
+
+@compute @workgroup_size(32, 1, 1) 
+fn add(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index: u32 = global_id.x;
+    if (index < dimensions.output_row_count * dimensions.output_column_count) {
+        let row_index: u32 = index / dimensions.output_column_count;
+        let column_index: u32 = index % dimensions.output_column_count;
+        output[index] = left_value(row_index, column_index) + right_value(row_index, column_index);
+    }
+}
+
+@compute @workgroup_size(32, 1, 1) 
+fn subtract(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index: u32 = global_id.x;
+    if (index < dimensions.output_row_count * dimensions.output_column_count) {
+        let row_index: u32 = index / dimensions.output_column_count;
+        let column_index: u32 = index % dimensions.output_column_count;
+        output[index] = left_value(row_index, column_index) - right_value(row_index, column_index);
+    }
+}
+
+@compute @workgroup_size(32, 1, 1) 
+fn multiply(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index: u32 = global_id.x;
+    if (index < dimensions.output_row_count * dimensions.output_column_count) {
+        let row_index: u32 = index / dimensions.output_column_count;
+        let column_index: u32 = index % dimensions.output_column_count;
+        output[index] = left_value(row_index, column_index) * right_value(row_index, column_index);
+    }
+}
+
+@compute @workgroup_size(32, 1, 1) 
+fn divide(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index: u32 = global_id.x;
+    if (index < dimensions.output_row_count * dimensions.output_column_count) {
+        let row_index: u32 = index / dimensions.output_column_count;
+        let column_index: u32 = index % dimensions.output_column_count;
+        output[index] = left_value(row_index, column_index) / right_value(row_index, column_index);
+    }
+}
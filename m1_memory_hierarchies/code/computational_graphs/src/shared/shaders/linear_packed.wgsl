@@ -0,0 +1,106 @@
+struct PackedLinearDimensions {
+    input_row_count: u32,
+    input_column_count: u32,
+    weights_column_count: u32,
+    bias_length: u32,
+    output_row_count: u32,
+    output_column_count: u32,
+    weights_precision: u32,
+    bias_precision: u32,
+    weights_scale: f32,
+    weights_zero_point: i32,
+    bias_scale: f32,
+    bias_zero_point: i32,
+};
+
+@group(0) @binding(0)
+var<uniform> dimensions: PackedLinearDimensions;
+
+@group(0) @binding(1)
+var<storage, read> input: array<f32>;
+
+// The weights and bias are packed into u32 words, lowest bits first
+@group(0) @binding(2)
+var<storage, read> weights: array<u32>;
+
+@group(0) @binding(3)
+var<storage, read> bias: array<u32>;
+
+@group(0) @binding(4)
+var<storage, read_write> output: array<f32>;
+
+// Matches PackedTensor2DGPU::precision_code
+const F32: u32 = 0u;
+const F16: u32 = 1u;
+const BF16: u32 = 2u;
+const INT8: u32 = 3u;
+
+fn word_index(index: u32, element_type: u32) -> u32 {
+    if (element_type == F16 || element_type == BF16) {
+        return index / 2u;
+    }
+    if (element_type == INT8) {
+        return index / 4u;
+    }
+    return index;
+}
+
+// Picks element index out of the word it is packed into and decodes it to f32.
+// Int8 elements are returned as q - zero_point, without the scale.
+fn decode(word: u32, index: u32, element_type: u32, zero_point: i32) -> f32 {
+    if (element_type == F16) {
+        let pair: vec2<f32> = unpack2x16float(word);
+        return select(pair.x, pair.y, (index & 1u) == 1u);
+    }
+    if (element_type == BF16) {
+        // bf16 is the top half of an f32
+        let shift: u32 = (index & 1u) * 16u;
+        return bitcast<f32>(((word >> shift) & 0xffffu) << 16u);
+    }
+    if (element_type == INT8) {
+        // Moving the byte to the top and shifting it back down sign extends it
+        let shift: u32 = (index & 3u) * 8u;
+        let value: i32 = bitcast<i32>(word << (24u - shift)) >> 24u;
+        return f32(value - zero_point);
+    }
+    return bitcast<f32>(word);
+}
+
+fn weight(index: u32) -> f32 {
+    let word: u32 = weights[word_index(index, dimensions.weights_precision)];
+    return decode(word, index, dimensions.weights_precision, dimensions.weights_zero_point);
+}
+
+fn bias_value(index: u32) -> f32 {
+    let word: u32 = bias[word_index(index, dimensions.bias_precision)];
+    let value: f32 = decode(word, index, dimensions.bias_precision, dimensions.bias_zero_point);
+    if (dimensions.bias_precision == INT8) {
+        return value * dimensions.bias_scale;
+    }
+    return value;
+}
+
+// The same as PackedTensor2D::linear, Int8 weights are accumulated as q - zero_point
+// and scaled once per output element. A batch of samples stacked along the rows
+// repeats the bias once per sample.
+@compute @workgroup_size(8, 8, 1)
+fn main(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    ) {
+    let output_row_index: u32 = global_id.x;
+    let output_column_index: u32 = global_id.y;
+
+    if (output_row_index < dimensions.output_row_count && output_column_index < dimensions.output_column_count) {
+        let output_index: u32 = output_row_index * dimensions.output_column_count + output_column_index;
+        var result: f32 = 0.0;
+        for (var inner_dimension: u32 = 0u; inner_dimension < dimensions.input_column_count; inner_dimension += 1u) {
+            result += input[output_row_index * dimensions.input_column_count + inner_dimension] * weight(inner_dimension * dimensions.weights_column_count + output_column_index);
+        }
+
+        var weights_scale: f32 = 1.0;
+        if (dimensions.weights_precision == INT8) {
+            weights_scale = dimensions.weights_scale;
+        }
+        output[output_index] = result * weights_scale + bias_value(output_index % dimensions.bias_length);
+    }
+}
@@ -0,0 +1,136 @@
+// A tiled linear layer, output = input * weights + bias.
+// Every workgroup computes a TILE_ROWS x TILE_COLUMNS tile of the output and walks
+// through the inner dimension TILE_INNER elements at a time. For every step the workgroup
+// loads a tile of the input and a tile of the weights into workgroup memory, where every
+// element is reused by a whole row or column of threads instead of being read from
+// global memory again. Every thread keeps THREAD_ROWS x THREAD_COLUMNS outputs in registers.
+//
+// This is a template. The sizes in double braces are filled in by
+// LinearTileConfig::shader_source, as the sizes of workgroup arrays and the workgroup size
+// have to be known when the shader is compiled.
+struct TensorDimensions {
+    input_row_count: u32,
+    input_column_count: u32,
+    weights_row_count: u32,
+    weights_column_count: u32,
+    bias_row_count: u32,
+    bias_column_count: u32,
+    output_row_count: u32,
+    output_column_count: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> dimensions: TensorDimensions;
+
+@group(0) @binding(1)
+var<storage, read> input: array<f32>;
+
+@group(0) @binding(2)
+var<storage, read> weights: array<f32>;
+
+@group(0) @binding(3)
+var<storage, read> bias: array<f32>;
+
+@group(0) @binding(4)
+var<storage, read_write> output: array<f32>;
+
+// Threads along the columns and the rows of the output tile
+const WORKGROUP_X: u32 = {{WORKGROUP_X}}u;
+const WORKGROUP_Y: u32 = {{WORKGROUP_Y}}u;
+const THREAD_COUNT: u32 = {{THREAD_COUNT}}u;
+// Outputs per thread
+const THREAD_ROWS: u32 = {{THREAD_ROWS}}u;
+const THREAD_COLUMNS: u32 = {{THREAD_COLUMNS}}u;
+// TILE_ROWS = WORKGROUP_Y * THREAD_ROWS, TILE_COLUMNS = WORKGROUP_X * THREAD_COLUMNS
+const TILE_ROWS: u32 = {{TILE_ROWS}}u;
+const TILE_COLUMNS: u32 = {{TILE_COLUMNS}}u;
+const TILE_INNER: u32 = {{TILE_INNER}}u;
+const INPUT_TILE_SIZE: u32 = {{INPUT_TILE_SIZE}}u;
+const WEIGHTS_TILE_SIZE: u32 = {{WEIGHTS_TILE_SIZE}}u;
+
+var<workgroup> input_tile: array<f32, {{INPUT_TILE_SIZE}}>;
+var<workgroup> weights_tile: array<f32, {{WEIGHTS_TILE_SIZE}}>;
+
+fn tiled_linear(group_id: vec3<u32>, local_id: vec3<u32>, local_index: u32, apply_relu: bool) {
+    let tile_row: u32 = group_id.y * TILE_ROWS;
+    let tile_column: u32 = group_id.x * TILE_COLUMNS;
+    let inner_count: u32 = dimensions.input_column_count;
+
+    var accumulators: array<f32, {{ACCUMULATOR_COUNT}}>;
+    var weight_values: array<f32, {{THREAD_COLUMNS}}>;
+
+    for (var inner_start: u32 = 0u; inner_start < inner_count; inner_start += TILE_INNER) {
+        // Every thread loads every THREAD_COUNT'th element of the tiles.
+        // Elements outside of the tensors are loaded as 0.0, which doesn't change the sums,
+        // so the tiles don't have to divide the tensor dimensions.
+        for (var index: u32 = local_index; index < INPUT_TILE_SIZE; index += THREAD_COUNT) {
+            let row: u32 = tile_row + index / TILE_INNER;
+            let inner: u32 = inner_start + index % TILE_INNER;
+            var value: f32 = 0.0;
+            if (row < dimensions.input_row_count && inner < inner_count) {
+                value = input[row * inner_count + inner];
+            }
+            input_tile[index] = value;
+        }
+        for (var index: u32 = local_index; index < WEIGHTS_TILE_SIZE; index += THREAD_COUNT) {
+            let inner: u32 = inner_start + index / TILE_COLUMNS;
+            let column: u32 = tile_column + index % TILE_COLUMNS;
+            var value: f32 = 0.0;
+            if (inner < inner_count && column < dimensions.weights_column_count) {
+                value = weights[inner * dimensions.weights_column_count + column];
+            }
+            weights_tile[index] = value;
+        }
+        workgroupBarrier();
+
+        // The outputs of a thread are WORKGROUP_Y rows and WORKGROUP_X columns apart,
+        // so neighbouring threads read neighbouring elements of the tiles.
+        for (var inner: u32 = 0u; inner < TILE_INNER; inner += 1u) {
+            for (var column: u32 = 0u; column < THREAD_COLUMNS; column += 1u) {
+                weight_values[column] =
+                    weights_tile[inner * TILE_COLUMNS + column * WORKGROUP_X + local_id.x];
+            }
+            for (var row: u32 = 0u; row < THREAD_ROWS; row += 1u) {
+                let input_value: f32 =
+                    input_tile[(row * WORKGROUP_Y + local_id.y) * TILE_INNER + inner];
+                for (var column: u32 = 0u; column < THREAD_COLUMNS; column += 1u) {
+                    accumulators[row * THREAD_COLUMNS + column] += input_value * weight_values[column];
+                }
+            }
+        }
+        workgroupBarrier();
+    }
+
+    for (var row: u32 = 0u; row < THREAD_ROWS; row += 1u) {
+        let output_row: u32 = tile_row + row * WORKGROUP_Y + local_id.y;
+        for (var column: u32 = 0u; column < THREAD_COLUMNS; column += 1u) {
+            let output_column: u32 = tile_column + column * WORKGROUP_X + local_id.x;
+            if (output_row < dimensions.output_row_count && output_column < dimensions.output_column_count) {
+                let output_index: u32 = output_row * dimensions.output_column_count + output_column;
+                var result: f32 = accumulators[row * THREAD_COLUMNS + column] + bias[output_index];
+                if (apply_relu) {
+                    result = max(0.0, result);
+                }
+                output[output_index] = result;
+            }
+        }
+    }
+}
+
+@compute @workgroup_size({{WORKGROUP_X}}, {{WORKGROUP_Y}}, 1)
+fn main(
+    @builtin(workgroup_id) group_id: vec3<u32>,
+    @builtin(local_invocation_id) local_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+    ) {
+    tiled_linear(group_id, local_id, local_index, false);
+}
+
+@compute @workgroup_size({{WORKGROUP_X}}, {{WORKGROUP_Y}}, 1)
+fn main_with_relu(
+    @builtin(workgroup_id) group_id: vec3<u32>,
+    @builtin(local_invocation_id) local_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+    ) {
+    tiled_linear(group_id, local_id, local_index, true);
+}
@@ -1,7 +1,10 @@
 const BLOCK_SIZE: u32 = 32u;
 
+// Every segment of segment_length elements gets its own softmax,
+// the whole tensor being a single segment unless it holds a batch of samples
 struct SoftmaxUniform {
     element_count: u32,
+    segment_length: u32,
 };
 
 @group(0) @binding(0)
@@ -27,18 +30,20 @@ fn single_pass_max(
     @builtin(local_invocation_id) local_id: vec3<u32>,
     ) {
     let tid: u32 = local_id.x;
+    // One workgroup per segment
+    let segment_start: u32 = group_id.x * softmax_uniform.segment_length;
     // In this first section we can use all 32 threads
-    var elements_left: u32 = softmax_uniform.element_count;
+    var elements_left: u32 = softmax_uniform.segment_length;
     var i: u32 = tid;
     var max_value: f32 = -3.00282346638528859812e+37f;
     // How do we handle the odd case?
     while (BLOCK_SIZE < elements_left) {
-        max_value = max(max_value, input[i]);
+        max_value = max(max_value, input[segment_start + i]);
         elements_left -= BLOCK_SIZE;
         i += BLOCK_SIZE;
     }
     if(tid < elements_left) {
-        max_value = max(max_value, input[i]);
+        max_value = max(max_value, input[segment_start + i]);
     }
 
     shared_data[tid] = max_value;
@@ -52,7 +57,7 @@ fn single_pass_max(
             max_value = max(max_value, shared_data[index]);
             index++;
         }
-        global_max[0] = max_value;
+        global_max[group_id.x] = max_value;
     }
 }
 
@@ -62,19 +67,21 @@ fn single_pass_sum(
     @builtin(local_invocation_id) local_id: vec3<u32>,
     ) {
     let tid: u32 = local_id.x;
+    // One workgroup per segment
+    let segment_start: u32 = group_id.x * softmax_uniform.segment_length;
     // In this first section we can use all 32 threads
-    var elements_left: u32 = softmax_uniform.element_count;
+    var elements_left: u32 = softmax_uniform.segment_length;
     var index: u32 = tid;
     var sum_value: f32 = 0.0;
-    let max_value: f32 = global_max[0];
+    let max_value: f32 = global_max[group_id.x];
     // How do we handle the odd case?
     while (BLOCK_SIZE < elements_left) {
-        sum_value += exp(input[index] - max_value);
+        sum_value += exp(input[segment_start + index] - max_value);
         elements_left -= BLOCK_SIZE;
         index += BLOCK_SIZE;
     }
     if(tid < elements_left) {
-        sum_value += exp(input[index] - max_value);
+        sum_value += exp(input[segment_start + index] - max_value);
     }
 
     shared_data[tid] = sum_value;
@@ -88,7 +95,7 @@ fn single_pass_sum(
             sum_value += shared_data[index];
             index++;
         }
-        global_offset[0] = max_value + log(sum_value);
+        global_offset[group_id.x] = max_value + log(sum_value);
     }
 }
 
@@ -101,6 +108,6 @@ fn map(
     let index: u32 = group_id.x * BLOCK_SIZE + local_id.x;
 
     if (index < softmax_uniform.element_count) {
-        output[index] = exp(input[index] - global_offset[0]);
+        output[index] = exp(input[index] - global_offset[index / softmax_uniform.segment_length]);
     }
 }
\ No newline at end of file
@@ -39,7 +39,7 @@ impl Tensor2D {
     }
 
     #[inline(always)]
-    fn linear_assert(
+    pub fn linear_assert(
         input: &Tensor2D,
         weights: &Tensor2D,
         bias: &Tensor2D,
@@ -98,7 +98,7 @@ impl Tensor2D {
     }
 
     #[inline(always)]
-    fn linear_relu_softmax_assert(
+    pub fn linear_relu_softmax_assert(
         input: &Tensor2D,
         weights: &Tensor2D,
         bias: &Tensor2D,
@@ -248,11 +248,10 @@ impl Tensor2D {
         for row_output in 0..output.row_count {
             for column_output in 0..output.column_count {
                 let mut result: f32 = 0.0;
-                let mut index_input: usize = row_output * input.column_count;
+                let row_start: usize = row_output * input.column_count;
                 let mut index_weights: usize = column_output;
-                for _ in 0..input.column_count {
+                for index_input in row_start..row_start + input.column_count {
                     result += input.data[index_input] * weights.data[index_weights];
-                    index_input += 1;
                     index_weights += weights.column_count;
                 }
 
@@ -360,6 +359,52 @@ impl Tensor2D {
         }
     }
 
+    // The softmax of a slice of values, such as one sample of a batch
+    #[inline(always)]
+    pub fn softmax_slice_inplace(values: &mut [f32]) {
+        let mut max: f32 = f32::NEG_INFINITY;
+        for value in values.iter() {
+            max = max.max(*value);
+        }
+
+        let mut sum: f32 = 0.0;
+        for value in values.iter() {
+            sum += (value - max).exp();
+        }
+
+        let offset: f32 = max + sum.ln();
+
+        for value in values.iter_mut() {
+            *value = (*value - offset).exp();
+        }
+    }
+
+    // A batch of batch_size samples stacked along the rows, where every
+    // sample gets its own softmax instead of one over the whole tensor
+    pub fn softmax_batched_inplace(out: &mut Tensor2D, batch_size: usize) {
+        let element_count: usize = out.len();
+        assert!(
+            0 < batch_size && element_count.is_multiple_of(batch_size),
+            "A tensor with {} elements can't be split into a batch of {} samples",
+            element_count,
+            batch_size
+        );
+
+        for sample in out.data[0..element_count].chunks_exact_mut(element_count / batch_size) {
+            Self::softmax_slice_inplace(sample);
+        }
+    }
+
+    pub fn softmax_batched_preallocated(
+        input: &Tensor2D,
+        output: &mut Tensor2D,
+        batch_size: usize,
+    ) {
+        let element_count: usize = output.len();
+        output.data[0..element_count].copy_from_slice(&input.data[0..element_count]);
+        Self::softmax_batched_inplace(output, batch_size);
+    }
+
     #[inline]
     pub fn linear_local_accumulation_relu(
         input: &Tensor2D,
@@ -372,11 +417,10 @@ impl Tensor2D {
         for row_output in 0..output.row_count {
             for column_output in 0..output.column_count {
                 let mut result: f32 = 0.0;
-                let mut index_input: usize = row_output * input.column_count;
+                let row_start: usize = row_output * input.column_count;
                 let mut index_weights: usize = column_output;
-                for _ in 0..input.column_count {
+                for index_input in row_start..row_start + input.column_count {
                     result += input.data[index_input] * weights.data[index_weights];
-                    index_input += 1;
                     index_weights += weights.column_count;
                 }
                 output.data[row_output * output.column_count + column_output] = result;
@@ -400,11 +444,10 @@ impl Tensor2D {
         for row_output in 0..output.row_count {
             for column_output in 0..output.column_count {
                 let mut result: f32 = 0.0;
-                let mut index_input: usize = row_output * input.column_count;
+                let row_start: usize = row_output * input.column_count;
                 let mut index_weights: usize = column_output;
-                for _ in 0..input.column_count {
+                for index_input in row_start..row_start + input.column_count {
                     result += input.data[index_input] * weights.data[index_weights];
-                    index_input += 1;
                     index_weights += weights.column_count;
                 }
 
@@ -427,11 +470,10 @@ impl Tensor2D {
         for row_output in 0..output.row_count {
             for column_output in 0..output.column_count {
                 let mut result: f32 = 0.0;
-                let mut index_input: usize = row_output * input.column_count;
+                let row_start: usize = row_output * input.column_count;
                 let mut index_weights: usize = column_output;
-                for _ in 0..input.column_count {
+                for index_input in row_start..row_start + input.column_count {
                     result += input.data[index_input] * weights.data[index_weights];
-                    index_input += 1;
                     index_weights += weights.column_count;
                 }
 
@@ -440,10 +482,6 @@ impl Tensor2D {
             }
         }
 
-        for index in 0..(bias.row_count * bias.column_count) {
-            output.data[index] += bias.data[index];
-        }
-
         let mut max: f32 = f32::NEG_INFINITY;
         for index in 0..(bias.row_count * bias.column_count) {
             let result: f32 = (output.data[index] + bias.data[index]).max(0.0);
@@ -477,11 +515,10 @@ impl Tensor2D {
         for row_output in 0..output.row_count {
             for column_output in 0..output.column_count {
                 let mut result: f32 = 0.0;
-                let mut index_input: usize = row_output * input.column_count;
+                let row_start: usize = row_output * input.column_count;
                 let mut index_weights: usize = column_output;
-                for _ in 0..input.column_count {
+                for index_input in row_start..row_start + input.column_count {
                     result += input.data[index_input] * weights.data[index_weights];
-                    index_input += 1;
                     index_weights += weights.column_count;
                 }
 
@@ -505,6 +542,118 @@ impl Tensor2D {
         }
     }
 
+    // Two dimensions are compatible if they are equal or if one of them is 1,
+    // in which case that side is repeated along the dimension. This covers
+    // adding a row vector or a column vector to a matrix, such as a bias.
+    pub fn broadcast_dimensions(left: &Tensor2D, right: &Tensor2D) -> Option<(usize, usize)> {
+        Self::broadcast_shape(
+            (left.row_count, left.column_count),
+            (right.row_count, right.column_count),
+        )
+    }
+
+    pub fn broadcast_shape(left: (usize, usize), right: (usize, usize)) -> Option<(usize, usize)> {
+        let broadcast = |left_count: usize, right_count: usize| -> Option<usize> {
+            if left_count == right_count || right_count == 1 {
+                Some(left_count)
+            } else if left_count == 1 {
+                Some(right_count)
+            } else {
+                None
+            }
+        };
+
+        let row_count: usize = broadcast(left.0, right.0)?;
+        let column_count: usize = broadcast(left.1, right.1)?;
+        Some((row_count, column_count))
+    }
+
+    // The index of the element read at (row, column) of a broadcast output.
+    // A dimension of size 1 is broadcast by always reading index 0 along it.
+    #[inline(always)]
+    pub fn broadcast_index(&self, row: usize, column: usize) -> usize {
+        let row: usize = if self.row_count == 1 { 0 } else { row };
+        let column: usize = if self.column_count == 1 { 0 } else { column };
+        row * self.column_count + column
+    }
+
+    #[inline(always)]
+    fn elementwise_preallocated(
+        left: &Tensor2D,
+        right: &Tensor2D,
+        output: &mut Tensor2D,
+        operation: fn(f32, f32) -> f32,
+    ) {
+        debug_assert_eq!(
+            Self::broadcast_dimensions(left, right),
+            Some((output.row_count, output.column_count)),
+            "\nMismatch - left & right & output\nleft - rows: {} columns: {}.\n right - rows: {} columns: {}.\n out - rows: {} columns: {}.",
+            left.row_count,
+            left.column_count,
+            right.row_count,
+            right.column_count,
+            output.row_count,
+            output.column_count
+        );
+
+        // A dimension of size 1 is broadcast by always reading index 0 along it
+        for row in 0..output.row_count {
+            let left_row: usize = if left.row_count == 1 { 0 } else { row };
+            let right_row: usize = if right.row_count == 1 { 0 } else { row };
+            for column in 0..output.column_count {
+                let left_column: usize = if left.column_count == 1 { 0 } else { column };
+                let right_column: usize = if right.column_count == 1 { 0 } else { column };
+
+                output.data[row * output.column_count + column] = operation(
+                    left.data[left_row * left.column_count + left_column],
+                    right.data[right_row * right.column_count + right_column],
+                );
+            }
+        }
+    }
+
+    fn elementwise(left: &Tensor2D, right: &Tensor2D, operation: fn(f32, f32) -> f32) -> Tensor2D {
+        let (row_count, column_count): (usize, usize) = Self::broadcast_dimensions(left, right)
+            .expect("Tried to apply an elementwise operator to tensors which can't be broadcast");
+        let mut output: Tensor2D = Tensor2D::new(0.0, row_count, column_count);
+
+        Self::elementwise_preallocated(left, right, &mut output, operation);
+
+        output
+    }
+
+    pub fn add(left: &Tensor2D, right: &Tensor2D) -> Tensor2D {
+        Self::elementwise(left, right, |a, b| a + b)
+    }
+
+    pub fn add_preallocated(left: &Tensor2D, right: &Tensor2D, output: &mut Tensor2D) {
+        Self::elementwise_preallocated(left, right, output, |a, b| a + b);
+    }
+
+    pub fn subtract(left: &Tensor2D, right: &Tensor2D) -> Tensor2D {
+        Self::elementwise(left, right, |a, b| a - b)
+    }
+
+    pub fn subtract_preallocated(left: &Tensor2D, right: &Tensor2D, output: &mut Tensor2D) {
+        Self::elementwise_preallocated(left, right, output, |a, b| a - b);
+    }
+
+    pub fn multiply(left: &Tensor2D, right: &Tensor2D) -> Tensor2D {
+        Self::elementwise(left, right, |a, b| a * b)
+    }
+
+    pub fn multiply_preallocated(left: &Tensor2D, right: &Tensor2D, output: &mut Tensor2D) {
+        Self::elementwise_preallocated(left, right, output, |a, b| a * b);
+    }
+
+    pub fn divide(left: &Tensor2D, right: &Tensor2D) -> Tensor2D {
+        Self::elementwise(left, right, |a, b| a / b)
+    }
+
+    pub fn divide_preallocated(left: &Tensor2D, right: &Tensor2D, output: &mut Tensor2D) {
+        Self::elementwise_preallocated(left, right, output, |a, b| a / b);
+    }
+
     // Just for testing
     #[inline(always)]
     pub fn subtraction(left: &Tensor2D, right: &Tensor2D) -> Tensor2D {
@@ -518,6 +667,51 @@ impl Tensor2D {
         output
     }
 
+    // Stacks samples of the same shape along the rows, the first sample on top
+    pub fn stack(samples: &[Tensor2D]) -> Tensor2D {
+        let first: &Tensor2D = samples
+            .first()
+            .expect("Tried to stack an empty batch of tensors");
+        let mut data: Vec<f32> = Vec::<f32>::with_capacity(samples.len() * first.len());
+        for sample in samples {
+            assert!(
+                sample.row_count == first.row_count && sample.column_count == first.column_count,
+                "\nMismatch - can't stack tensors of different shapes\nfirst - rows: {} columns: {}.\n sample - rows: {} columns: {}.",
+                first.row_count,
+                first.column_count,
+                sample.row_count,
+                sample.column_count
+            );
+            data.extend_from_slice(&sample.data[0..sample.len()]);
+        }
+
+        Tensor2D {
+            data,
+            row_count: samples.len() * first.row_count,
+            column_count: first.column_count,
+        }
+    }
+
+    // Splits a tensor stacked with stack back into batch_size samples
+    pub fn unstack(&self, batch_size: usize) -> Vec<Tensor2D> {
+        assert!(
+            0 < batch_size && self.row_count.is_multiple_of(batch_size),
+            "A tensor with {} rows can't be split into a batch of {} samples",
+            self.row_count,
+            batch_size
+        );
+
+        let row_count: usize = self.row_count / batch_size;
+        self.data[0..self.len()]
+            .chunks_exact(row_count * self.column_count)
+            .map(|sample| Tensor2D {
+                data: sample.to_vec(),
+                row_count,
+                column_count: self.column_count,
+            })
+            .collect()
+    }
+
     // Just for testing.
     // Get the sum of all active elements
     // Mostly for verifying correctness
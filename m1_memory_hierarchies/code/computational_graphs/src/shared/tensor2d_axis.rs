@@ -0,0 +1,253 @@
+use super::tensor2d::Tensor2D;
+
+// The axis a softmax or a reduction is computed along.
+// With Rows every row is a vector of its own, so with one sample per row
+// every sample gets its own softmax over its classes. Columns does the same
+// for every column. Global treats the whole tensor as a single vector,
+// which is what Softmax did before it had an axis.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Axis {
+    Global,
+    Rows,
+    Columns,
+}
+
+impl Axis {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Axis::Global => "Global",
+            Axis::Rows => "Rows",
+            Axis::Columns => "Columns",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Global" => Some(Axis::Global),
+            "Rows" => Some(Axis::Rows),
+            "Columns" => Some(Axis::Columns),
+            _ => None,
+        }
+    }
+
+    // A reduction keeps one value per row, one per column or one for the whole tensor
+    pub fn reduced_shape(&self, shape: (usize, usize)) -> (usize, usize) {
+        match self {
+            Axis::Global => (1, 1),
+            Axis::Rows => (shape.0, 1),
+            Axis::Columns => (1, shape.1),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Reduction {
+    Sum,
+    Max,
+    Mean,
+    // The position of the first maximum along the axis, stored as an f32
+    ArgMax,
+}
+
+impl Reduction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Reduction::Sum => "Sum",
+            Reduction::Max => "Max",
+            Reduction::Mean => "Mean",
+            Reduction::ArgMax => "ArgMax",
+        }
+    }
+}
+
+// How the elements of a tensor are split into the segments an axis operator works on.
+// Element e of segment s is found at
+// (s / inner_count) * outer_stride + (s % inner_count) * inner_stride + e * element_stride
+// A reduction writes the value of segment s to element s of its output.
+// The GPU kernels get the same six values in their uniform.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AxisLayout {
+    pub segment_count: usize,
+    pub segment_length: usize,
+    pub inner_count: usize,
+    pub outer_stride: usize,
+    pub inner_stride: usize,
+    pub element_stride: usize,
+}
+
+impl AxisLayout {
+    // The tensor holds batch_size samples stacked along the rows,
+    // and the axis is relative to a single sample
+    pub fn new(row_count: usize, column_count: usize, axis: Axis, batch_size: usize) -> Self {
+        assert!(
+            0 < batch_size && row_count.is_multiple_of(batch_size),
+            "A tensor with {} rows can't be split into a batch of {} samples",
+            row_count,
+            batch_size
+        );
+        let sample_row_count: usize = row_count / batch_size;
+        let sample_length: usize = sample_row_count * column_count;
+
+        match axis {
+            Axis::Global => AxisLayout {
+                segment_count: batch_size,
+                segment_length: sample_length,
+                inner_count: 1,
+                outer_stride: sample_length,
+                inner_stride: 0,
+                element_stride: 1,
+            },
+            Axis::Rows => AxisLayout {
+                segment_count: row_count,
+                segment_length: column_count,
+                inner_count: 1,
+                outer_stride: column_count,
+                inner_stride: 0,
+                element_stride: 1,
+            },
+            Axis::Columns => AxisLayout {
+                segment_count: batch_size * column_count,
+                segment_length: sample_row_count,
+                inner_count: column_count,
+                outer_stride: sample_length,
+                inner_stride: 1,
+                element_stride: column_count,
+            },
+        }
+    }
+
+    #[inline(always)]
+    pub fn index(&self, segment: usize, element: usize) -> usize {
+        (segment / self.inner_count) * self.outer_stride
+            + (segment % self.inner_count) * self.inner_stride
+            + element * self.element_stride
+    }
+}
+
+impl Tensor2D {
+    pub fn reduce(input: &Tensor2D, reduction: Reduction, axis: Axis) -> Tensor2D {
+        let (row_count, column_count): (usize, usize) =
+            axis.reduced_shape((input.row_count, input.column_count));
+        let mut output: Tensor2D = Tensor2D::new(0.0, row_count, column_count);
+
+        Self::reduce_preallocated(input, &mut output, reduction, axis, 1);
+
+        output
+    }
+
+    pub fn reduce_preallocated(
+        input: &Tensor2D,
+        output: &mut Tensor2D,
+        reduction: Reduction,
+        axis: Axis,
+        batch_size: usize,
+    ) {
+        let layout: AxisLayout =
+            AxisLayout::new(input.row_count, input.column_count, axis, batch_size);
+        debug_assert_eq!(
+            layout.segment_count,
+            output.len(),
+            "\nMismatch - reduction & output\ninput - rows: {} columns: {}.\n out - rows: {} columns: {}.",
+            input.row_count,
+            input.column_count,
+            output.row_count,
+            output.column_count
+        );
+
+        for segment in 0..layout.segment_count {
+            let mut sum: f32 = 0.0;
+            let mut max: f32 = f32::NEG_INFINITY;
+            let mut max_element: usize = 0;
+            for element in 0..layout.segment_length {
+                let value: f32 = input.data[layout.index(segment, element)];
+                sum += value;
+                if max < value {
+                    max = value;
+                    max_element = element;
+                }
+            }
+
+            output.data[segment] = match reduction {
+                Reduction::Sum => sum,
+                Reduction::Max => max,
+                Reduction::Mean => sum / layout.segment_length as f32,
+                Reduction::ArgMax => max_element as f32,
+            };
+        }
+    }
+
+    // Subtracting the maximum before taking the exponential keeps it from overflowing.
+    // The logarithm of the softmax is then value - max - ln(sum(exp(value - max))),
+    // which never takes the logarithm of a value which underflowed to 0.
+    fn normalize_along(
+        input: &Tensor2D,
+        output: &mut Tensor2D,
+        axis: Axis,
+        batch_size: usize,
+        logarithm: bool,
+    ) {
+        debug_assert_eq!(
+            (input.row_count, input.column_count),
+            (output.row_count, output.column_count),
+            "\nMismatch - input & output\ninput - rows: {} columns: {}.\n out - rows: {} columns: {}.",
+            input.row_count,
+            input.column_count,
+            output.row_count,
+            output.column_count
+        );
+        let layout: AxisLayout =
+            AxisLayout::new(input.row_count, input.column_count, axis, batch_size);
+
+        for segment in 0..layout.segment_count {
+            let mut max: f32 = f32::NEG_INFINITY;
+            for element in 0..layout.segment_length {
+                max = max.max(input.data[layout.index(segment, element)]);
+            }
+
+            let mut sum: f32 = 0.0;
+            for element in 0..layout.segment_length {
+                sum += (input.data[layout.index(segment, element)] - max).exp();
+            }
+
+            let offset: f32 = max + sum.ln();
+            for element in 0..layout.segment_length {
+                let index: usize = layout.index(segment, element);
+                output.data[index] = if logarithm {
+                    input.data[index] - offset
+                } else {
+                    (input.data[index] - offset).exp()
+                };
+            }
+        }
+    }
+
+    pub fn softmax_along(input: &Tensor2D, axis: Axis) -> Tensor2D {
+        let mut output: Tensor2D = Tensor2D::new(0.0, input.row_count, input.column_count);
+        Self::softmax_along_preallocated(input, &mut output, axis, 1);
+        output
+    }
+
+    pub fn softmax_along_preallocated(
+        input: &Tensor2D,
+        output: &mut Tensor2D,
+        axis: Axis,
+        batch_size: usize,
+    ) {
+        Self::normalize_along(input, output, axis, batch_size, false);
+    }
+
+    pub fn log_softmax_along(input: &Tensor2D, axis: Axis) -> Tensor2D {
+        let mut output: Tensor2D = Tensor2D::new(0.0, input.row_count, input.column_count);
+        Self::log_softmax_along_preallocated(input, &mut output, axis, 1);
+        output
+    }
+
+    pub fn log_softmax_along_preallocated(
+        input: &Tensor2D,
+        output: &mut Tensor2D,
+        axis: Axis,
+        batch_size: usize,
+    ) {
+        Self::normalize_along(input, output, axis, batch_size, true);
+    }
+}
@@ -0,0 +1,264 @@
+#[cfg(test)]
+mod tests {
+    use crate::shared::{
+        tensor2d::Tensor2D,
+        tensor2d_axis::{Axis, Reduction},
+    };
+
+    const ERROR_TOLERANCE: f32 = 0.00001;
+
+    const AXES: [Axis; 3] = [Axis::Global, Axis::Rows, Axis::Columns];
+    const REDUCTIONS: [Reduction; 4] = [
+        Reduction::Sum,
+        Reduction::Max,
+        Reduction::Mean,
+        Reduction::ArgMax,
+    ];
+
+    fn tensor(data: &[f32], row_count: usize, column_count: usize) -> Tensor2D {
+        Tensor2D {
+            data: data.to_vec(),
+            row_count,
+            column_count,
+        }
+    }
+
+    fn assert_tensors_match(expected: &Tensor2D, actual: &Tensor2D) {
+        assert_eq!(expected.row_count, actual.row_count);
+        assert_eq!(expected.column_count, actual.column_count);
+        for (expected_value, actual_value) in expected.data.iter().zip(&actual.data) {
+            assert!(
+                (expected_value - actual_value).abs() < ERROR_TOLERANCE,
+                "\nexpected: {:?}\nactual: {:?}",
+                expected,
+                actual
+            );
+        }
+    }
+
+    fn transpose(input: &Tensor2D) -> Tensor2D {
+        let mut output: Tensor2D = Tensor2D::new(0.0, input.column_count, input.row_count);
+        for row in 0..input.row_count {
+            for column in 0..input.column_count {
+                output.data[column * input.row_count + row] =
+                    input.data[row * input.column_count + column];
+            }
+        }
+        output
+    }
+
+    #[test]
+    fn axis_names() {
+        for axis in AXES {
+            assert_eq!(Axis::from_name(axis.name()), Some(axis));
+        }
+        assert_eq!(Axis::from_name("Diagonal"), None);
+
+        assert_eq!(Axis::Global.reduced_shape((3, 4)), (1, 1));
+        assert_eq!(Axis::Rows.reduced_shape((3, 4)), (3, 1));
+        assert_eq!(Axis::Columns.reduced_shape((3, 4)), (1, 4));
+    }
+
+    #[test]
+    fn reduce() {
+        let input: Tensor2D = tensor(&[1.0, 5.0, 3.0, 4.0, 2.0, 6.0], 2, 3);
+
+        let expected: [(Reduction, Axis, Tensor2D); 12] = [
+            (Reduction::Sum, Axis::Global, tensor(&[21.0], 1, 1)),
+            (Reduction::Sum, Axis::Rows, tensor(&[9.0, 12.0], 2, 1)),
+            (
+                Reduction::Sum,
+                Axis::Columns,
+                tensor(&[5.0, 7.0, 9.0], 1, 3),
+            ),
+            (Reduction::Max, Axis::Global, tensor(&[6.0], 1, 1)),
+            (Reduction::Max, Axis::Rows, tensor(&[5.0, 6.0], 2, 1)),
+            (
+                Reduction::Max,
+                Axis::Columns,
+                tensor(&[4.0, 5.0, 6.0], 1, 3),
+            ),
+            (Reduction::Mean, Axis::Global, tensor(&[3.5], 1, 1)),
+            (Reduction::Mean, Axis::Rows, tensor(&[3.0, 4.0], 2, 1)),
+            (
+                Reduction::Mean,
+                Axis::Columns,
+                tensor(&[2.5, 3.5, 4.5], 1, 3),
+            ),
+            (Reduction::ArgMax, Axis::Global, tensor(&[5.0], 1, 1)),
+            (Reduction::ArgMax, Axis::Rows, tensor(&[1.0, 2.0], 2, 1)),
+            (
+                Reduction::ArgMax,
+                Axis::Columns,
+                tensor(&[1.0, 0.0, 1.0], 1, 3),
+            ),
+        ];
+        for (reduction, axis, expected) in &expected {
+            assert_tensors_match(expected, &Tensor2D::reduce(&input, *reduction, *axis));
+        }
+    }
+
+    #[test]
+    fn argmax_picks_first_maximum() {
+        let input: Tensor2D = tensor(&[2.0, 7.0, 7.0, -1.0, -1.0, -1.0], 2, 3);
+        assert_tensors_match(
+            &tensor(&[1.0, 0.0], 2, 1),
+            &Tensor2D::reduce(&input, Reduction::ArgMax, Axis::Rows),
+        );
+        assert_tensors_match(
+            &tensor(&[1.0], 1, 1),
+            &Tensor2D::reduce(&input, Reduction::ArgMax, Axis::Global),
+        );
+    }
+
+    #[test]
+    fn softmax_global() {
+        for row_count in 1..5 {
+            for column_count in 1..7 {
+                let input: Tensor2D = Tensor2D::new(0.3, row_count, column_count);
+                assert_tensors_match(
+                    &Tensor2D::softmax(&input),
+                    &Tensor2D::softmax_along(&input, Axis::Global),
+                );
+            }
+        }
+    }
+
+    // Every row is normalized on its own, and softmax along the columns
+    // is softmax along the rows of the transpose
+    #[test]
+    fn softmax_rows_and_columns() {
+        for row_count in 1..5 {
+            for column_count in 1..7 {
+                let input: Tensor2D = Tensor2D::new(-0.7, row_count, column_count);
+
+                let rows: Tensor2D = Tensor2D::softmax_along(&input, Axis::Rows);
+                for row in 0..row_count {
+                    let range = row * column_count..(row + 1) * column_count;
+                    let expected: Tensor2D =
+                        Tensor2D::softmax(&tensor(&input.data[range.clone()], 1, column_count));
+                    assert_tensors_match(&expected, &tensor(&rows.data[range], 1, column_count));
+                }
+                let row_sums: Tensor2D = Tensor2D::reduce(&rows, Reduction::Sum, Axis::Rows);
+                assert_tensors_match(&tensor(&vec![1.0; row_count], row_count, 1), &row_sums);
+
+                let columns: Tensor2D = Tensor2D::softmax_along(&input, Axis::Columns);
+                assert_tensors_match(
+                    &transpose(&Tensor2D::softmax_along(&transpose(&input), Axis::Rows)),
+                    &columns,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn log_softmax() {
+        let input: Tensor2D = Tensor2D::new(0.45, 3, 5);
+        for axis in AXES {
+            let mut expected: Tensor2D = Tensor2D::softmax_along(&input, axis);
+            for value in expected.data.iter_mut() {
+                *value = value.ln();
+            }
+            assert_tensors_match(&expected, &Tensor2D::log_softmax_along(&input, axis));
+        }
+    }
+
+    // Taking the logarithm of the softmax would give ln(0) for the smallest values
+    #[test]
+    fn log_softmax_is_stable() {
+        let input: Tensor2D = tensor(&[1000.0, 0.0, -1000.0, 0.0, 0.0, 0.0], 2, 3);
+        let output: Tensor2D = Tensor2D::log_softmax_along(&input, Axis::Rows);
+
+        assert!(output.data.iter().all(|value| value.is_finite()));
+        assert_tensors_match(
+            &tensor(
+                &[
+                    0.0,
+                    -1000.0,
+                    -2000.0,
+                    -(3.0f32.ln()),
+                    -(3.0f32.ln()),
+                    -(3.0f32.ln()),
+                ],
+                2,
+                3,
+            ),
+            &output,
+        );
+
+        let softmax: Tensor2D = Tensor2D::softmax_along(&input, Axis::Rows);
+        assert!(softmax.data.iter().all(|value| value.is_finite()));
+        assert_eq!(softmax.data[0], 1.0);
+    }
+
+    // A stacked batch gives every sample the result it would get on its own
+    #[test]
+    fn batched() {
+        let samples: Vec<Tensor2D> = (0..4)
+            .map(|sample| Tensor2D::new(0.2 - 0.15 * sample as f32, 3, 5))
+            .collect();
+        let stacked: Tensor2D = Tensor2D::stack(&samples);
+        let batch_size: usize = samples.len();
+
+        for axis in AXES {
+            let mut output: Tensor2D = Tensor2D::new(0.0, stacked.row_count, stacked.column_count);
+            Tensor2D::softmax_along_preallocated(&stacked, &mut output, axis, batch_size);
+            for (sample, output) in samples.iter().zip(output.unstack(batch_size)) {
+                assert_tensors_match(&Tensor2D::softmax_along(sample, axis), &output);
+            }
+
+            Tensor2D::log_softmax_along_preallocated(&stacked, &mut output, axis, batch_size);
+            for (sample, output) in samples.iter().zip(output.unstack(batch_size)) {
+                assert_tensors_match(&Tensor2D::log_softmax_along(sample, axis), &output);
+            }
+
+            for reduction in REDUCTIONS {
+                let (row_count, column_count): (usize, usize) = axis.reduced_shape((3, 5));
+                let mut output: Tensor2D = Tensor2D::new(0.0, row_count * batch_size, column_count);
+                Tensor2D::reduce_preallocated(&stacked, &mut output, reduction, axis, batch_size);
+                for (sample, output) in samples.iter().zip(output.unstack(batch_size)) {
+                    assert_tensors_match(&Tensor2D::reduce(sample, reduction, axis), &output);
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn batch_size_has_to_divide_rows() {
+        let input: Tensor2D = Tensor2D::new(0.1, 5, 2);
+        let mut output: Tensor2D = Tensor2D::new(0.0, 5, 2);
+        Tensor2D::softmax_along_preallocated(&input, &mut output, Axis::Rows, 2);
+    }
+
+    // The GPU kernels can't run without a GPU, but the shader can still be validated
+    #[test]
+    fn axis_shader_validates() {
+        let source: &str = include_str!("shaders/axis.wgsl");
+        let module: naga::Module = naga::front::wgsl::parse_str(source)
+            .unwrap_or_else(|error| panic!("{}", error.emit_to_string(source)));
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        )
+        .validate(&module)
+        .unwrap_or_else(|error| panic!("{:?}", error));
+
+        let names: Vec<&str> = module
+            .entry_points
+            .iter()
+            .map(|entry_point| entry_point.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "softmax",
+                "log_softmax",
+                "reduce_sum",
+                "reduce_max",
+                "reduce_mean",
+                "reduce_argmax"
+            ]
+        );
+    }
+}
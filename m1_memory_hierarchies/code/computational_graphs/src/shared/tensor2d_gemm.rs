@@ -0,0 +1,279 @@
+use std::sync::OnceLock;
+
+use wide::f32x8;
+
+use super::tensor2d::Tensor2D;
+
+// A cache-blocked GEMM in the style of BLIS/GotoBLAS.
+// The weights are cut into blocks of KC rows and NC columns, small enough to stay
+// in the L2 cache, and every block is packed into panels of NR columns.
+// A panel is stored as KC rows of NR contiguous values, which replaces the
+// column stride of linear_optimized with a sequential read.
+// The micro kernel computes MR rows by NR columns of the output at a time,
+// keeping the MR accumulators of NR values each in registers for the whole
+// depth of the block. With NR = 8 each accumulator is a single f32x8.
+const MR: usize = 4;
+const NR: usize = 8;
+const KC: usize = 256;
+const NC: usize = 512;
+
+// The micro kernels linear_blocked_inner can be instantiated with
+const SCALAR: u8 = 0;
+const SIMD: u8 = 1;
+const SIMD_AVX2_FMA: u8 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GemmKernel {
+    Scalar,
+    // f32x8 compiled for the target the crate is compiled for,
+    // which is two SSE registers per f32x8 on a default x86_64 build
+    Simd,
+    // 256-bit fused multiply-add, only used if the CPU supports AVX2 and FMA
+    SimdAvx2Fma,
+}
+
+// The CPU features are only checked once
+pub fn detected_gemm_kernel() -> GemmKernel {
+    static KERNEL: OnceLock<GemmKernel> = OnceLock::new();
+    *KERNEL.get_or_init(|| {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+                return GemmKernel::SimdAvx2Fma;
+            }
+            if is_x86_feature_detected!("sse2") {
+                return GemmKernel::Simd;
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            if std::arch::is_aarch64_feature_detected!("neon") {
+                return GemmKernel::Simd;
+            }
+        }
+        GemmKernel::Scalar
+    })
+}
+
+// Packs rows row_start..row_start + row_count and columns column_start..column_start + column_count
+// of the weights into panels of NR columns. The last panel is padded with zeros.
+fn pack_weights(
+    weights: &Tensor2D,
+    row_start: usize,
+    row_count: usize,
+    column_start: usize,
+    column_count: usize,
+    packed: &mut Vec<[f32; NR]>,
+) {
+    let panel_count: usize = column_count.div_ceil(NR);
+    packed.clear();
+    packed.resize(panel_count * row_count, [0.0; NR]);
+
+    for panel in 0..panel_count {
+        let panel_column: usize = column_start + panel * NR;
+        let panel_width: usize = NR.min(column_start + column_count - panel_column);
+        for row in 0..row_count {
+            let index_weights: usize = (row_start + row) * weights.column_count + panel_column;
+            packed[panel * row_count + row][0..panel_width]
+                .copy_from_slice(&weights.data[index_weights..index_weights + panel_width]);
+        }
+    }
+}
+
+// Packs rows row_start..row_start + row_count and columns column_start..column_start + column_count
+// of the input into MR interleaved rows, so the micro kernel reads the MR values
+// it needs for every step sequentially. Missing rows are padded with zeros.
+fn pack_input(
+    input: &Tensor2D,
+    row_start: usize,
+    row_count: usize,
+    column_start: usize,
+    column_count: usize,
+    packed: &mut Vec<[f32; MR]>,
+) {
+    packed.clear();
+    packed.resize(column_count, [0.0; MR]);
+
+    for row in 0..row_count {
+        let index_input: usize = (row_start + row) * input.column_count + column_start;
+        for (values, value) in packed
+            .iter_mut()
+            .zip(&input.data[index_input..index_input + column_count])
+        {
+            values[row] = *value;
+        }
+    }
+}
+
+#[inline(always)]
+fn micro_kernel_scalar(input: &[[f32; MR]], weights: &[[f32; NR]]) -> [[f32; NR]; MR] {
+    let mut accumulators: [[f32; NR]; MR] = [[0.0; NR]; MR];
+    for (inputs, weights) in input.iter().zip(weights) {
+        for row in 0..MR {
+            for lane in 0..NR {
+                accumulators[row][lane] += inputs[row] * weights[lane];
+            }
+        }
+    }
+
+    accumulators
+}
+
+#[inline(always)]
+fn micro_kernel_simd(input: &[[f32; MR]], weights: &[[f32; NR]]) -> [[f32; NR]; MR] {
+    let mut accumulators: [f32x8; MR] = [f32x8::ZERO; MR];
+    for (inputs, weights) in input.iter().zip(weights) {
+        let weights: f32x8 = f32x8::new(*weights);
+        for row in 0..MR {
+            accumulators[row] = f32x8::splat(inputs[row]).mul_add(weights, accumulators[row]);
+        }
+    }
+
+    accumulators.map(|accumulator| accumulator.to_array())
+}
+
+// wide picks its instructions when the crate is compiled, so inside a function with
+// AVX2 and FMA enabled at runtime an f32x8 would still be two SSE registers
+// and a separate multiply and add. This is the same kernel written with the
+// 256-bit fused multiply-add the CPU was found to support.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+unsafe fn micro_kernel_avx2_fma(input: &[[f32; MR]], weights: &[[f32; NR]]) -> [[f32; NR]; MR] {
+    use std::arch::x86_64::{
+        __m256, _mm256_fmadd_ps, _mm256_loadu_ps, _mm256_set1_ps, _mm256_setzero_ps,
+        _mm256_storeu_ps,
+    };
+
+    let mut accumulators: [__m256; MR] = [_mm256_setzero_ps(); MR];
+    for (inputs, weights) in input.iter().zip(weights) {
+        let weights: __m256 = _mm256_loadu_ps(weights.as_ptr());
+        for row in 0..MR {
+            accumulators[row] =
+                _mm256_fmadd_ps(_mm256_set1_ps(inputs[row]), weights, accumulators[row]);
+        }
+    }
+
+    let mut result: [[f32; NR]; MR] = [[0.0; NR]; MR];
+    for row in 0..MR {
+        _mm256_storeu_ps(result[row].as_mut_ptr(), accumulators[row]);
+    }
+    result
+}
+
+// The output starts out as the bias and every block of the weights adds its contribution.
+// The kernel is a constant, which lets the micro kernel be inlined into the caller.
+#[inline(always)]
+fn linear_blocked_inner<const KERNEL: u8>(
+    input: &Tensor2D,
+    weights: &Tensor2D,
+    bias: &Tensor2D,
+    output: &mut Tensor2D,
+) {
+    Tensor2D::linear_assert(input, weights, bias, output);
+
+    let row_count: usize = output.row_count;
+    let column_count: usize = output.column_count;
+    let inner_count: usize = input.column_count;
+    output.data[0..row_count * column_count]
+        .copy_from_slice(&bias.data[0..row_count * column_count]);
+
+    let mut packed_weights: Vec<[f32; NR]> = Vec::<[f32; NR]>::new();
+    let mut packed_input: Vec<[f32; MR]> = Vec::<[f32; MR]>::new();
+    for column_block in (0..column_count).step_by(NC) {
+        let block_columns: usize = NC.min(column_count - column_block);
+        for inner_block in (0..inner_count).step_by(KC) {
+            let block_depth: usize = KC.min(inner_count - inner_block);
+            pack_weights(
+                weights,
+                inner_block,
+                block_depth,
+                column_block,
+                block_columns,
+                &mut packed_weights,
+            );
+
+            for row_start in (0..row_count).step_by(MR) {
+                let block_rows: usize = MR.min(row_count - row_start);
+                pack_input(
+                    input,
+                    row_start,
+                    block_rows,
+                    inner_block,
+                    block_depth,
+                    &mut packed_input,
+                );
+
+                for (panel_index, panel) in packed_weights.chunks_exact(block_depth).enumerate() {
+                    let accumulators: [[f32; NR]; MR] = match KERNEL {
+                        SIMD => micro_kernel_simd(&packed_input, panel),
+                        #[cfg(target_arch = "x86_64")]
+                        // Only reached through linear_blocked_avx2_fma, which checks the CPU
+                        SIMD_AVX2_FMA => unsafe { micro_kernel_avx2_fma(&packed_input, panel) },
+                        _ => micro_kernel_scalar(&packed_input, panel),
+                    };
+
+                    let panel_column: usize = column_block + panel_index * NR;
+                    let panel_width: usize = NR.min(column_count - panel_column);
+                    for (row, accumulator) in accumulators.iter().enumerate().take(block_rows) {
+                        let index_output: usize = (row_start + row) * column_count + panel_column;
+                        for (output, value) in output.data[index_output..index_output + panel_width]
+                            .iter_mut()
+                            .zip(accumulator)
+                        {
+                            *output += value;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+unsafe fn linear_blocked_avx2_fma(
+    input: &Tensor2D,
+    weights: &Tensor2D,
+    bias: &Tensor2D,
+    output: &mut Tensor2D,
+) {
+    linear_blocked_inner::<SIMD_AVX2_FMA>(input, weights, bias, output);
+}
+
+impl Tensor2D {
+    // Uses the fastest kernel the CPU supports
+    pub fn linear_blocked(
+        input: &Tensor2D,
+        weights: &Tensor2D,
+        bias: &Tensor2D,
+        output: &mut Tensor2D,
+    ) {
+        Self::linear_blocked_with_kernel(detected_gemm_kernel(), input, weights, bias, output);
+    }
+
+    pub fn linear_blocked_with_kernel(
+        kernel: GemmKernel,
+        input: &Tensor2D,
+        weights: &Tensor2D,
+        bias: &Tensor2D,
+        output: &mut Tensor2D,
+    ) {
+        match kernel {
+            GemmKernel::Scalar => linear_blocked_inner::<SCALAR>(input, weights, bias, output),
+            GemmKernel::Simd => linear_blocked_inner::<SIMD>(input, weights, bias, output),
+            GemmKernel::SimdAvx2Fma => {
+                #[cfg(target_arch = "x86_64")]
+                {
+                    assert!(
+                        is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma"),
+                        "Tried to use the AVX2 and FMA GEMM kernel on a CPU without AVX2 and FMA"
+                    );
+                    // Safe as we just checked the CPU supports the enabled features
+                    unsafe { linear_blocked_avx2_fma(input, weights, bias, output) }
+                }
+                #[cfg(not(target_arch = "x86_64"))]
+                panic!("The AVX2 and FMA GEMM kernel is only available on x86_64");
+            }
+        }
+    }
+}
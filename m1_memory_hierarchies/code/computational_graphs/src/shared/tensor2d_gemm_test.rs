@@ -0,0 +1,100 @@
+#[cfg(test)]
+mod tests {
+    use crate::shared::{
+        tensor2d::Tensor2D,
+        tensor2d_gemm::{detected_gemm_kernel, GemmKernel},
+    };
+
+    const ERROR_TOLERANCE: f32 = 0.00001;
+
+    // The blocked kernels sum in a different order, so compare relative to the magnitude
+    fn assert_tensors_match(expected: &Tensor2D, actual: &Tensor2D) {
+        assert_eq!(expected.row_count, actual.row_count);
+        assert_eq!(expected.column_count, actual.column_count);
+        for (index, (expected_value, actual_value)) in
+            expected.data.iter().zip(&actual.data).enumerate()
+        {
+            assert!(
+                (expected_value - actual_value).abs()
+                    <= ERROR_TOLERANCE * expected_value.abs().max(1.0),
+                "Mismatch at index {} of a ({}, {}) output, expected {} got {}",
+                index,
+                expected.row_count,
+                expected.column_count,
+                expected_value,
+                actual_value
+            );
+        }
+    }
+
+    // Every kernel which can run on this CPU
+    fn kernels() -> Vec<GemmKernel> {
+        let mut kernels: Vec<GemmKernel> = vec![GemmKernel::Scalar, GemmKernel::Simd];
+        if detected_gemm_kernel() == GemmKernel::SimdAvx2Fma {
+            kernels.push(GemmKernel::SimdAvx2Fma);
+        }
+        kernels
+    }
+
+    fn test_kernels(row_count: usize, inner_count: usize, column_count: usize) {
+        let input: Tensor2D = Tensor2D::new(0.01, row_count, inner_count);
+        let mut weights: Tensor2D = Tensor2D::new(-0.003, inner_count, column_count);
+        // Mix the signs so the errors don't all add up the same way
+        for (index, value) in weights.data.iter_mut().enumerate() {
+            if index % 3 == 0 {
+                *value = -*value;
+            }
+        }
+        let bias: Tensor2D = Tensor2D::new(0.1, row_count, column_count);
+
+        let mut expected: Tensor2D = Tensor2D::new(0.0, row_count, column_count);
+        Tensor2D::linear_preallocated(&input, &weights, &bias, &mut expected);
+
+        for kernel in kernels() {
+            // Garbage in the output has to be overwritten
+            let mut output: Tensor2D = Tensor2D::new(1.0, row_count, column_count);
+            Tensor2D::linear_blocked_with_kernel(kernel, &input, &weights, &bias, &mut output);
+            assert_tensors_match(&expected, &output);
+        }
+
+        let mut output: Tensor2D = Tensor2D::new(0.0, row_count, column_count);
+        Tensor2D::linear_blocked(&input, &weights, &bias, &mut output);
+        assert_tensors_match(&expected, &output);
+    }
+
+    // Sizes around the micro kernel's 4 rows and 8 columns
+    #[test]
+    fn small() {
+        for row_count in 1..10 {
+            for inner_count in [1, 2, 7, 8, 9] {
+                for column_count in 1..18 {
+                    test_kernels(row_count, inner_count, column_count);
+                }
+            }
+        }
+    }
+
+    // Sizes around the 256 deep and 512 wide blocks of the weights
+    #[test]
+    fn block_edges() {
+        for (row_count, inner_count, column_count) in [
+            (5, 255, 9),
+            (5, 256, 9),
+            (5, 257, 9),
+            (3, 600, 17),
+            (6, 20, 511),
+            (6, 20, 512),
+            (6, 20, 513),
+            (9, 300, 530),
+        ] {
+            test_kernels(row_count, inner_count, column_count);
+        }
+    }
+
+    #[test]
+    fn detected_kernel_is_stable() {
+        assert_eq!(detected_gemm_kernel(), detected_gemm_kernel());
+        #[cfg(target_arch = "x86_64")]
+        assert_ne!(detected_gemm_kernel(), GemmKernel::Scalar);
+    }
+}
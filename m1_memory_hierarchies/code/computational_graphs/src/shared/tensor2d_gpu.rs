@@ -1,7 +1,12 @@
 use futures_intrusive::channel::shared::{OneshotReceiver, OneshotSender};
 use wgpu::{util::DeviceExt, Buffer, BufferAsyncError, BufferSlice, BufferView, CommandEncoder};
 
-use super::{gpu_utilities::GPUHandles, tensor2d::Tensor2D};
+use super::{
+    gpu_utilities::GPUHandles,
+    tensor2d::Tensor2D,
+    tensor2d_axis::AxisLayout,
+    tensor2d_precision::{PackedData, PackedTensor2D, Precision},
+};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -138,24 +143,31 @@ impl ReluUniform {
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct SumElements {
-    pub data: [u32; 2],
+pub struct ReductionElements {
+    pub data: [u32; 4],
 }
 
-pub struct SumUniform {
-    pub elements: SumElements,
+// The divisor is stored as the bits of an f32, the last element pads the uniform to 16 bytes
+pub struct ReductionUniform {
+    pub elements: ReductionElements,
     pub storage_buffer: Buffer,
 }
 
-impl SumUniform {
+impl ReductionUniform {
     pub fn new(
         handles: &GPUHandles,
         label: &str,
         element_count: usize,
         workgroup_count: usize,
+        divisor: f32,
     ) -> Self {
-        let elements: SumElements = SumElements {
-            data: [element_count as u32, workgroup_count as u32],
+        let elements: ReductionElements = ReductionElements {
+            data: [
+                element_count as u32,
+                workgroup_count as u32,
+                divisor.to_bits(),
+                0,
+            ],
         };
 
         let storage_buffer: Buffer =
@@ -176,7 +188,7 @@ impl SumUniform {
 
     #[inline(always)]
     pub fn size(&self) -> u64 {
-        std::mem::size_of::<SumElements>() as u64
+        std::mem::size_of::<ReductionElements>() as u64
     }
 
 }
@@ -184,7 +196,9 @@ impl SumUniform {
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct SoftmaxDimensions {
-    pub data: [u32; 1],
+    // element_count and segment_length, every segment of segment_length
+    // elements gets its own softmax
+    pub data: [u32; 2],
 }
 
 pub struct SoftmaxUniform {
@@ -194,8 +208,19 @@ pub struct SoftmaxUniform {
 
 impl SoftmaxUniform {
     pub fn new(handles: &GPUHandles, label: &str, element_count: usize) -> Self {
+        Self::segmented(handles, label, element_count, element_count)
+    }
+
+    // For a batch of samples stacked one after the other, segment_length
+    // is the number of elements in a sample
+    pub fn segmented(
+        handles: &GPUHandles,
+        label: &str,
+        element_count: usize,
+        segment_length: usize,
+    ) -> Self {
         let dimensions: SoftmaxDimensions = SoftmaxDimensions {
-            data: [element_count as u32],
+            data: [element_count as u32, segment_length as u32],
         };
 
         let storage_buffer: Buffer =
@@ -221,6 +246,298 @@ impl SoftmaxUniform {
 
 }
 
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ElementwiseDimensions {
+    pub data: [u32; 6],
+}
+
+pub struct ElementwiseUniform {
+    pub dimensions: ElementwiseDimensions,
+    pub storage_buffer: Buffer,
+}
+
+impl ElementwiseUniform {
+    pub fn new(
+        handles: &GPUHandles,
+        label: &str,
+        left: &Tensor2DGPU,
+        right: &Tensor2DGPU,
+        output: &Tensor2DGPU,
+    ) -> Self {
+        let dimensions: ElementwiseDimensions = ElementwiseDimensions {
+            data: [
+                left.row_count as u32,
+                left.column_count as u32,
+                right.row_count as u32,
+                right.column_count as u32,
+                output.row_count as u32,
+                output.column_count as u32,
+            ],
+        };
+
+        let storage_buffer: Buffer =
+            handles
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(label),
+                    contents: bytemuck::cast_slice(&dimensions.data),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                });
+
+        Self {
+            dimensions,
+            storage_buffer,
+        }
+    }
+
+    #[inline(always)]
+    pub fn size(&self) -> u64 {
+        std::mem::size_of::<ElementwiseDimensions>() as u64
+    }
+}
+
+// The segments of the axis operators, in the order of AxisLayout's fields.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct AxisDimensions {
+    pub data: [u32; 6],
+}
+
+pub struct AxisUniform {
+    pub dimensions: AxisDimensions,
+    pub storage_buffer: Buffer,
+}
+
+impl AxisUniform {
+    pub fn new(handles: &GPUHandles, label: &str, layout: &AxisLayout) -> Self {
+        let dimensions: AxisDimensions = AxisDimensions {
+            data: [
+                layout.segment_count as u32,
+                layout.segment_length as u32,
+                layout.inner_count as u32,
+                layout.outer_stride as u32,
+                layout.inner_stride as u32,
+                layout.element_stride as u32,
+            ],
+        };
+
+        let storage_buffer: Buffer =
+            handles
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(label),
+                    contents: bytemuck::cast_slice(&dimensions.data),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                });
+
+        Self {
+            dimensions,
+            storage_buffer,
+        }
+    }
+
+    #[inline(always)]
+    pub fn size(&self) -> u64 {
+        std::mem::size_of::<AxisDimensions>() as u64
+    }
+}
+
+// The number of tensors in a fused shader depends on how many operators were fused,
+// so the dimensions are a row and column count for every tensor, in binding order.
+// An axis base adds the values of its layout at the end.
+pub struct FusedElementwiseUniform {
+    pub dimensions: Vec<u32>,
+    pub storage_buffer: Buffer,
+}
+
+impl FusedElementwiseUniform {
+    pub fn new(
+        handles: &GPUHandles,
+        label: &str,
+        tensors: &[&Tensor2DGPU],
+        axis_layout: Option<&AxisLayout>,
+    ) -> Self {
+        let mut dimensions: Vec<u32> = Vec::<u32>::with_capacity(tensors.len() * 2 + 6);
+        for tensor in tensors {
+            dimensions.push(tensor.row_count as u32);
+            dimensions.push(tensor.column_count as u32);
+        }
+        if let Some(layout) = axis_layout {
+            dimensions.extend_from_slice(&[
+                layout.segment_count as u32,
+                layout.segment_length as u32,
+                layout.inner_count as u32,
+                layout.outer_stride as u32,
+                layout.inner_stride as u32,
+                layout.element_stride as u32,
+            ]);
+        }
+
+        let storage_buffer: Buffer =
+            handles
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(label),
+                    contents: bytemuck::cast_slice(&dimensions),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                });
+
+        Self {
+            dimensions,
+            storage_buffer,
+        }
+    }
+
+    #[inline(always)]
+    pub fn size(&self) -> u64 {
+        (self.dimensions.len() * std::mem::size_of::<u32>()) as u64
+    }
+}
+
+// Packs elements of element_bits bits into u32 words, lowest bits first
+fn pack_words(elements: impl Iterator<Item = u32>, element_bits: usize) -> Vec<u32> {
+    let elements_per_word: usize = 32 / element_bits;
+    let mut words: Vec<u32> = Vec::<u32>::new();
+    for (index, element) in elements.enumerate() {
+        if index % elements_per_word == 0 {
+            words.push(0);
+        }
+        *words.last_mut().unwrap() |= element << ((index % elements_per_word) * element_bits);
+    }
+    words
+}
+
+// The elements of a PackedTensor2D packed into u32 words, as WGSL has
+// no 8 or 16 bit types. The shaders decode the elements as they read them.
+#[derive(Debug)]
+pub struct PackedTensor2DGPU {
+    pub storage_buffer: Buffer,
+    pub row_count: usize,
+    pub column_count: usize,
+    pub precision: Precision,
+    // Only used by Int8 tensors
+    pub scale: f32,
+    pub zero_point: i32,
+}
+
+impl PackedTensor2DGPU {
+    pub fn from_packed_tensor2d(
+        handles: &GPUHandles,
+        label: &str,
+        tensor: &PackedTensor2D,
+    ) -> Self {
+        let (words, scale, zero_point): (Vec<u32>, f32, i32) = match &tensor.data {
+            PackedData::F32(values) => {
+                (values.iter().map(|value| value.to_bits()).collect(), 1.0, 0)
+            }
+            PackedData::F16(values) | PackedData::BF16(values) => (
+                pack_words(values.iter().map(|value| *value as u32), 16),
+                1.0,
+                0,
+            ),
+            PackedData::Int8 {
+                values,
+                scale,
+                zero_point,
+            } => (
+                pack_words(values.iter().map(|value| *value as u8 as u32), 8),
+                *scale,
+                *zero_point as i32,
+            ),
+        };
+
+        let storage_buffer: Buffer =
+            handles
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(label),
+                    contents: bytemuck::cast_slice(&words),
+                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                });
+
+        Self {
+            storage_buffer,
+            row_count: tensor.row_count,
+            column_count: tensor.column_count,
+            precision: tensor.precision(),
+            scale,
+            zero_point,
+        }
+    }
+
+    // The code linear_packed.wgsl decodes the precision by
+    pub fn precision_code(&self) -> u32 {
+        match self.precision {
+            Precision::F32 => 0,
+            Precision::F16 => 1,
+            Precision::BF16 => 2,
+            Precision::Int8 => 3,
+        }
+    }
+}
+
+// The dimensions of a linear layer with packed parameters, followed by how
+// the weights and bias are decoded, in the order of the struct in linear_packed.wgsl.
+// The scales are f32 bits and the zero points i32 bits.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PackedLinearDimensions {
+    pub data: [u32; 12],
+}
+
+pub struct PackedLinearUniform {
+    pub dimensions: PackedLinearDimensions,
+    pub storage_buffer: Buffer,
+}
+
+impl PackedLinearUniform {
+    pub fn new(
+        handles: &GPUHandles,
+        label: &str,
+        input: &Tensor2DGPU,
+        weights: &PackedTensor2DGPU,
+        bias: &PackedTensor2DGPU,
+        output: &Tensor2DGPU,
+    ) -> Self {
+        let dimensions: PackedLinearDimensions = PackedLinearDimensions {
+            data: [
+                input.row_count as u32,
+                input.column_count as u32,
+                weights.column_count as u32,
+                (bias.row_count * bias.column_count) as u32,
+                output.row_count as u32,
+                output.column_count as u32,
+                weights.precision_code(),
+                bias.precision_code(),
+                weights.scale.to_bits(),
+                weights.zero_point as u32,
+                bias.scale.to_bits(),
+                bias.zero_point as u32,
+            ],
+        };
+
+        let storage_buffer: Buffer =
+            handles
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(label),
+                    contents: bytemuck::cast_slice(&dimensions.data),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                });
+
+        Self {
+            dimensions,
+            storage_buffer,
+        }
+    }
+
+    #[inline(always)]
+    pub fn size(&self) -> u64 {
+        std::mem::size_of::<PackedLinearDimensions>() as u64
+    }
+}
+
 #[derive(Debug)]
 pub struct Tensor2DGPU {
     pub staging_buffer: Buffer,
@@ -351,36 +668,60 @@ impl Tensor2DGPU {
         self.live_data_on_device = true;
     }
 
+    // Overwrites the storage buffer with a tensor of the same shape. The write is
+    // queued and happens before the next submitted command buffer.
+    pub fn upload(&mut self, handles: &GPUHandles, tensor: &Tensor2D) {
+        assert!(
+            tensor.row_count == self.row_count && tensor.column_count == self.column_count,
+            "Tried to upload a ({}, {}) tensor to a ({}, {}) Tensor2DGPU",
+            tensor.row_count, tensor.column_count, self.row_count, self.column_count
+        );
+        handles
+            .queue
+            .write_buffer(&self.storage_buffer, 0, bytemuck::cast_slice(&tensor.data));
+        self.data.data.clone_from(&tensor.data);
+    }
+
     pub async fn retrieve_results(&mut self) {
+        if self.try_retrieve_results().await.is_err() {
+            panic!("Failed to retrieve results from the gpu!")
+        }
+    }
+
+    // Fails if the staging buffer couldn't be mapped
+    pub async fn try_retrieve_results(&mut self) -> Result<(), BufferAsyncError> {
         if !self.live_data_on_device {
             println!("Already retrieved results from GPU, no reason to do it again.");
-            return;
+            return Ok(());
         }
         if self.receiver.is_none() {
             println!("Tried to get_results for a Tensor2DGPU, without having a receiver in place. You are probably calling the functions in the wrong order.");
-            return;
+            return Ok(());
         }
 
         let buffer_slice: BufferSlice = self.staging_buffer.slice(..);
 
         let result: Vec<f32> =
-            if let Some(Ok(())) =
-                self.receiver
+            match self.receiver
                     .take() // Take ownership of the option and leave None in it's place. This is to enforce the fact that this is a oneshot receiver.
                     .expect("Took the receiver from the tensor in Tensor2DGPU::get_results, but the option did not contain a receiver.")
                     .receive().await {
-            let data: BufferView = buffer_slice.get_mapped_range();
-            let result: Vec<f32> = bytemuck::cast_slice(&data).to_vec();
-
-            drop(data);
-            self.staging_buffer.unmap();
-            self.live_data_on_device = false;
-            result
-        } else {
-            panic!("Failed to retrieve results from the gpu!")
+            Some(Ok(())) => {
+                let data: BufferView = buffer_slice.get_mapped_range();
+                let result: Vec<f32> = bytemuck::cast_slice(&data).to_vec();
+
+                drop(data);
+                self.staging_buffer.unmap();
+                self.live_data_on_device = false;
+                result
+            }
+            Some(Err(error)) => return Err(error),
+            // The sender was dropped without mapping the buffer
+            None => return Err(BufferAsyncError),
         };
 
         self.data.data = result;
+        Ok(())
     }
 
     #[inline(always)]
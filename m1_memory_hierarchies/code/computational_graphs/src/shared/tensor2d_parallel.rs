@@ -0,0 +1,189 @@
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use super::tensor2d::Tensor2D;
+
+// A thread count of 0 lets rayon decide, which is one thread per logical core.
+pub fn build_thread_pool(thread_count: usize) -> ThreadPool {
+    ThreadPoolBuilder::new()
+        .num_threads(thread_count)
+        .build()
+        .expect("Failed to build a rayon thread pool")
+}
+
+// The parallel versions of the Tensor2D kernels split the rows of the output
+// across the threads of the current rayon pool. Run them inside
+// ThreadPool::install to control the thread count, otherwise they
+// use the global pool. The linear kernels compute each row like linear_optimized.
+impl Tensor2D {
+    #[inline(always)]
+    fn linear_row(
+        row_output: usize,
+        input: &Tensor2D,
+        weights: &Tensor2D,
+        bias: &Tensor2D,
+        output_row: &mut [f32],
+    ) {
+        let column_count: usize = output_row.len();
+        for (column_output, output) in output_row.iter_mut().enumerate() {
+            let mut result: f32 = 0.0;
+            let row_start: usize = row_output * input.column_count;
+            let mut index_weights: usize = column_output;
+            for index_input in row_start..row_start + input.column_count {
+                result += input.data[index_input] * weights.data[index_weights];
+                index_weights += weights.column_count;
+            }
+
+            *output = result + bias.data[row_output * column_count + column_output];
+        }
+    }
+
+    fn rows_mut(output: &mut Tensor2D) -> rayon::slice::ChunksMut<'_, f32> {
+        let element_count: usize = output.len();
+        output.data[0..element_count].par_chunks_mut(output.column_count)
+    }
+
+    pub fn linear_parallel(
+        input: &Tensor2D,
+        weights: &Tensor2D,
+        bias: &Tensor2D,
+        output: &mut Tensor2D,
+    ) {
+        Self::linear_assert(input, weights, bias, output);
+
+        Self::rows_mut(output)
+            .enumerate()
+            .for_each(|(row_output, output_row)| {
+                Self::linear_row(row_output, input, weights, bias, output_row);
+            });
+    }
+
+    pub fn linear_relu_parallel(
+        input: &Tensor2D,
+        weights: &Tensor2D,
+        bias: &Tensor2D,
+        output: &mut Tensor2D,
+    ) {
+        Self::linear_assert(input, weights, bias, output);
+
+        Self::rows_mut(output)
+            .enumerate()
+            .for_each(|(row_output, output_row)| {
+                Self::linear_row(row_output, input, weights, bias, output_row);
+                for value in output_row.iter_mut() {
+                    *value = value.max(0.0);
+                }
+            });
+    }
+
+    // The softmax is over the whole tensor, so the rows are combined
+    // to find the maximum and the sum before the rows are normalized
+    pub fn linear_relu_softmax_fused_parallel(
+        input: &Tensor2D,
+        weights: &Tensor2D,
+        bias: &Tensor2D,
+        output: &mut Tensor2D,
+    ) {
+        Self::linear_relu_softmax_assert(input, weights, bias, output);
+
+        let max: f32 = Self::rows_mut(output)
+            .enumerate()
+            .map(|(row_output, output_row)| {
+                Self::linear_row(row_output, input, weights, bias, output_row);
+                let mut max: f32 = f32::NEG_INFINITY;
+                for value in output_row.iter_mut() {
+                    *value = value.max(0.0);
+                    max = max.max(*value);
+                }
+                max
+            })
+            .reduce(|| f32::NEG_INFINITY, f32::max);
+
+        Self::softmax_normalize_parallel(output, max);
+    }
+
+    pub fn relu_parallel(input: &Tensor2D, output: &mut Tensor2D) {
+        let column_count: usize = output.column_count;
+        Self::rows_mut(output)
+            .zip(input.data[0..input.len()].par_chunks(column_count))
+            .for_each(|(output_row, input_row)| {
+                for (output, input) in output_row.iter_mut().zip(input_row) {
+                    *output = input.max(0.0);
+                }
+            });
+    }
+
+    pub fn relu_inplace_parallel(data: &mut Tensor2D) {
+        Self::rows_mut(data).for_each(|row| {
+            for value in row.iter_mut() {
+                *value = value.max(0.0);
+            }
+        });
+    }
+
+    fn max_parallel(tensor: &Tensor2D) -> f32 {
+        tensor.data[0..tensor.len()]
+            .par_chunks(tensor.column_count)
+            .map(|row| {
+                row.iter()
+                    .fold(f32::NEG_INFINITY, |max, value| max.max(*value))
+            })
+            .reduce(|| f32::NEG_INFINITY, f32::max)
+    }
+
+    // Turns the tensor into the softmax of itself, given its maximum
+    fn softmax_normalize_parallel(out: &mut Tensor2D, max: f32) {
+        let sum: f32 = out.data[0..out.len()]
+            .par_chunks(out.column_count)
+            .map(|row| row.iter().map(|value| (value - max).exp()).sum::<f32>())
+            .sum();
+
+        let offset: f32 = max + sum.ln();
+
+        Self::rows_mut(out).for_each(|row| {
+            for value in row.iter_mut() {
+                *value = (*value - offset).exp();
+            }
+        });
+    }
+
+    pub fn softmax_parallel(input: &Tensor2D, output: &mut Tensor2D) {
+        let max: f32 = Self::max_parallel(input);
+        let column_count: usize = output.column_count;
+        Self::rows_mut(output)
+            .zip(input.data[0..input.len()].par_chunks(column_count))
+            .for_each(|(output_row, input_row)| output_row.copy_from_slice(input_row));
+
+        Self::softmax_normalize_parallel(output, max);
+    }
+
+    pub fn softmax_inplace_parallel(out: &mut Tensor2D) {
+        let max: f32 = Self::max_parallel(out);
+        Self::softmax_normalize_parallel(out, max);
+    }
+
+    // Each sample of the batch gets its own softmax, so the samples
+    // are split across the threads instead of the rows
+    pub fn softmax_batched_inplace_parallel(out: &mut Tensor2D, batch_size: usize) {
+        let element_count: usize = out.len();
+        assert!(
+            0 < batch_size && element_count.is_multiple_of(batch_size),
+            "A tensor with {} elements can't be split into a batch of {} samples",
+            element_count,
+            batch_size
+        );
+
+        out.data[0..element_count]
+            .par_chunks_exact_mut(element_count / batch_size)
+            .for_each(Self::softmax_slice_inplace);
+    }
+
+    pub fn softmax_batched_parallel(input: &Tensor2D, output: &mut Tensor2D, batch_size: usize) {
+        let column_count: usize = output.column_count;
+        Self::rows_mut(output)
+            .zip(input.data[0..input.len()].par_chunks(column_count))
+            .for_each(|(output_row, input_row)| output_row.copy_from_slice(input_row));
+
+        Self::softmax_batched_inplace_parallel(output, batch_size);
+    }
+}
@@ -0,0 +1,143 @@
+#[cfg(test)]
+mod tests {
+    use rayon::ThreadPool;
+
+    use crate::shared::{tensor2d::Tensor2D, tensor2d_parallel::build_thread_pool};
+
+    const ERROR_TOLERANCE: f32 = 0.00001;
+
+    // The parallel kernels sum in a different order, so compare relative to the magnitude
+    fn assert_tensors_match(expected: &Tensor2D, actual: &Tensor2D) {
+        assert_eq!(expected.row_count, actual.row_count);
+        assert_eq!(expected.column_count, actual.column_count);
+        for (expected_value, actual_value) in expected.data.iter().zip(&actual.data) {
+            assert!(
+                (expected_value - actual_value).abs()
+                    <= ERROR_TOLERANCE * expected_value.abs().max(1.0),
+                "\nexpected: {:?}\nactual: {:?}",
+                expected,
+                actual
+            );
+        }
+    }
+
+    fn thread_pools() -> Vec<ThreadPool> {
+        [1, 2, 3, 8]
+            .iter()
+            .map(|thread_count| build_thread_pool(*thread_count))
+            .collect()
+    }
+
+    fn test_linear(
+        serial: fn(&Tensor2D, &Tensor2D, &Tensor2D, &mut Tensor2D),
+        parallel: fn(&Tensor2D, &Tensor2D, &Tensor2D, &mut Tensor2D),
+    ) {
+        for thread_pool in thread_pools() {
+            for outer_dimension_input in 1..12 {
+                for outer_dimension_weights in [1, 5, 16] {
+                    for inner_dimension in [1, 7, 16] {
+                        let input: Tensor2D =
+                            Tensor2D::new(0.05, outer_dimension_input, inner_dimension);
+                        let weights: Tensor2D =
+                            Tensor2D::new(-0.01, inner_dimension, outer_dimension_weights);
+                        let bias: Tensor2D =
+                            Tensor2D::new(0.1, outer_dimension_input, outer_dimension_weights);
+
+                        let mut expected: Tensor2D =
+                            Tensor2D::new(0.0, outer_dimension_input, outer_dimension_weights);
+                        serial(&input, &weights, &bias, &mut expected);
+
+                        let mut output: Tensor2D =
+                            Tensor2D::new(0.0, outer_dimension_input, outer_dimension_weights);
+                        thread_pool.install(|| parallel(&input, &weights, &bias, &mut output));
+
+                        assert_tensors_match(&expected, &output);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn linear_parallel() {
+        test_linear(Tensor2D::linear_optimized, Tensor2D::linear_parallel);
+    }
+
+    #[test]
+    fn linear_relu_parallel() {
+        test_linear(
+            Tensor2D::linear_optimized_relu,
+            Tensor2D::linear_relu_parallel,
+        );
+    }
+
+    #[test]
+    fn linear_relu_softmax_fused_parallel() {
+        test_linear(
+            Tensor2D::linear_relu_softmax_fused,
+            Tensor2D::linear_relu_softmax_fused_parallel,
+        );
+    }
+
+    fn test_single(serial: fn(&Tensor2D) -> Tensor2D, parallel: fn(&Tensor2D, &mut Tensor2D)) {
+        for thread_pool in thread_pools() {
+            for row_count in 1..12 {
+                for column_count in [1, 3, 16] {
+                    for scale in [-0.5, 0.1, 1.0] {
+                        let input: Tensor2D = Tensor2D::new(scale, row_count, column_count);
+                        let expected: Tensor2D = serial(&input);
+
+                        let mut output: Tensor2D = Tensor2D::new(0.0, row_count, column_count);
+                        thread_pool.install(|| parallel(&input, &mut output));
+
+                        assert_tensors_match(&expected, &output);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn relu_parallel() {
+        test_single(Tensor2D::relu, Tensor2D::relu_parallel);
+        test_single(Tensor2D::relu, |input, output| {
+            output.data.copy_from_slice(&input.data);
+            Tensor2D::relu_inplace_parallel(output);
+        });
+    }
+
+    #[test]
+    fn softmax_parallel() {
+        test_single(Tensor2D::softmax, Tensor2D::softmax_parallel);
+        test_single(Tensor2D::softmax, |input, output| {
+            output.data.copy_from_slice(&input.data);
+            Tensor2D::softmax_inplace_parallel(output);
+        });
+    }
+
+    #[test]
+    fn softmax_batched_parallel() {
+        for thread_pool in thread_pools() {
+            for batch_size in [1, 2, 5] {
+                for (row_count, column_count) in [(1, 3), (2, 16), (3, 1)] {
+                    let input: Tensor2D = Tensor2D::new(0.1, batch_size * row_count, column_count);
+                    let mut expected: Tensor2D = Tensor2D::new(0.0, input.row_count, column_count);
+                    Tensor2D::softmax_batched_preallocated(&input, &mut expected, batch_size);
+
+                    let mut output: Tensor2D = Tensor2D::new(0.0, input.row_count, column_count);
+                    thread_pool.install(|| {
+                        Tensor2D::softmax_batched_parallel(&input, &mut output, batch_size)
+                    });
+                    assert_tensors_match(&expected, &output);
+                }
+            }
+        }
+    }
+
+    // 0 lets rayon pick the thread count
+    #[test]
+    fn build_thread_pool_thread_count() {
+        assert_eq!(build_thread_pool(3).current_num_threads(), 3);
+        assert!(0 < build_thread_pool(0).current_num_threads());
+    }
+}
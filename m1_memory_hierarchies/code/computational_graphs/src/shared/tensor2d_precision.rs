@@ -0,0 +1,356 @@
+use super::tensor2d::Tensor2D;
+
+// The element types a tensor can be stored with. Everything is computed in f32,
+// the smaller types only save memory and bandwidth, which is what matters
+// when the weights no longer fit in the cache.
+// F16 is IEEE 754 half precision, with 5 exponent and 10 mantissa bits.
+// BF16 keeps the 8 exponent bits of f32, so it has the same range, but only 7 mantissa bits.
+// Int8 stores every element as round(value / scale) + zero_point.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Precision {
+    F32,
+    F16,
+    BF16,
+    Int8,
+}
+
+impl Precision {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Precision::F32 => "F32",
+            Precision::F16 => "F16",
+            Precision::BF16 => "BF16",
+            Precision::Int8 => "Int8",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "F32" => Some(Precision::F32),
+            "F16" => Some(Precision::F16),
+            "BF16" => Some(Precision::BF16),
+            "Int8" => Some(Precision::Int8),
+            _ => None,
+        }
+    }
+
+    pub fn element_size(&self) -> usize {
+        match self {
+            Precision::F32 => std::mem::size_of::<f32>(),
+            Precision::F16 | Precision::BF16 => std::mem::size_of::<u16>(),
+            Precision::Int8 => std::mem::size_of::<i8>(),
+        }
+    }
+}
+
+// Rounds to the nearest f16, with ties going to the value with an even mantissa.
+// Values too large for an f16 become infinity and values too small become 0,
+// or one of the subnormals, which have no implicit leading 1.
+pub fn f32_to_f16_bits(value: f32) -> u16 {
+    let bits: u32 = value.to_bits();
+    let sign: u16 = ((bits >> 16) & 0x8000) as u16;
+    let exponent: i32 = ((bits >> 23) & 0xff) as i32;
+    let mantissa: u32 = bits & 0x007f_ffff;
+
+    // Infinity stays infinity, and NaN is kept a NaN by setting the top mantissa bit
+    if exponent == 0xff {
+        let quiet_bit: u16 = if mantissa != 0 { 0x0200 } else { 0 };
+        return sign | 0x7c00 | quiet_bit | (mantissa >> 13) as u16;
+    }
+
+    // The exponent bias is 127 for f32 and 15 for f16
+    let half_exponent: i32 = exponent - 127 + 15;
+    if 0x1f <= half_exponent {
+        return sign | 0x7c00;
+    }
+
+    if half_exponent <= 0 {
+        // Smaller than half of the smallest subnormal
+        if half_exponent < -10 {
+            return sign;
+        }
+        let mantissa: u32 = mantissa | 0x0080_0000;
+        let shift: u32 = (14 - half_exponent) as u32;
+        let half_mantissa: u32 = mantissa >> shift;
+        let remainder: u32 = mantissa & ((1 << shift) - 1);
+        let halfway: u32 = 1 << (shift - 1);
+        let round_up: bool =
+            halfway < remainder || (remainder == halfway && (half_mantissa & 1) == 1);
+        // Rounding up the largest subnormal gives the smallest normal, which has the same bits
+        return sign | (half_mantissa + round_up as u32) as u16;
+    }
+
+    let half: u16 = sign | ((half_exponent as u16) << 10) | (mantissa >> 13) as u16;
+    let remainder: u32 = mantissa & 0x1fff;
+    let round_up: bool = 0x1000 < remainder || (remainder == 0x1000 && (half & 1) == 1);
+    // A carry out of the mantissa increments the exponent, which also rounds to infinity
+    half + round_up as u16
+}
+
+pub fn f16_bits_to_f32(bits: u16) -> f32 {
+    let sign: u32 = ((bits & 0x8000) as u32) << 16;
+    let exponent: u32 = ((bits >> 10) & 0x1f) as u32;
+    let mantissa: u32 = (bits & 0x03ff) as u32;
+
+    match exponent {
+        // Zero and the subnormals, mantissa * 2^-24, which is exact in f32
+        0 => {
+            let magnitude: f32 = mantissa as f32 * 2.0f32.powi(-24);
+            if sign == 0 {
+                magnitude
+            } else {
+                -magnitude
+            }
+        }
+        0x1f => f32::from_bits(sign | 0x7f80_0000 | (mantissa << 13)),
+        _ => f32::from_bits(sign | ((exponent + 127 - 15) << 23) | (mantissa << 13)),
+    }
+}
+
+// bf16 is the top half of an f32, so converting is rounding away the bottom 16 bits.
+// Adding 0x7fff, plus 1 if the kept half is odd, rounds to the nearest with ties to even.
+pub fn f32_to_bf16_bits(value: f32) -> u16 {
+    let bits: u32 = value.to_bits();
+    if value.is_nan() {
+        return ((bits >> 16) | 0x0040) as u16;
+    }
+
+    let rounding_bias: u32 = 0x7fff + ((bits >> 16) & 1);
+    ((bits + rounding_bias) >> 16) as u16
+}
+
+pub fn bf16_bits_to_f32(bits: u16) -> f32 {
+    f32::from_bits((bits as u32) << 16)
+}
+
+// The scale and zero point of an int8 tensor are chosen so the range of the values
+// maps onto -128..=127. The range always includes 0, which keeps 0 exact,
+// so zero padding and ReLU outputs don't pick up an offset.
+fn int8_parameters(data: &[f32]) -> (f32, i8) {
+    let minimum: f32 = data
+        .iter()
+        .fold(0.0f32, |minimum, value| minimum.min(*value));
+    let maximum: f32 = data
+        .iter()
+        .fold(0.0f32, |maximum, value| maximum.max(*value));
+
+    let scale: f32 = (maximum - minimum) / 255.0;
+    // A tensor of zeros can have any scale
+    if scale == 0.0 {
+        return (1.0, 0);
+    }
+
+    let zero_point: f32 = (-128.0 - minimum / scale).round().clamp(-128.0, 127.0);
+    (scale, zero_point as i8)
+}
+
+fn quantize_int8(value: f32, scale: f32, zero_point: i8) -> i8 {
+    ((value / scale).round() + zero_point as f32).clamp(-128.0, 127.0) as i8
+}
+
+fn dequantize_int8(value: i8, scale: f32, zero_point: i8) -> f32 {
+    (value as i32 - zero_point as i32) as f32 * scale
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PackedData {
+    F32(Vec<f32>),
+    F16(Vec<u16>),
+    BF16(Vec<u16>),
+    Int8 {
+        values: Vec<i8>,
+        scale: f32,
+        zero_point: i8,
+    },
+}
+
+// A Tensor2D stored with a smaller element type. It can't be computed with directly,
+// the kernels decode the elements to f32 as they read them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackedTensor2D {
+    pub data: PackedData,
+    pub row_count: usize,
+    pub column_count: usize,
+}
+
+impl PackedTensor2D {
+    pub fn from_tensor2d(tensor: &Tensor2D, precision: Precision) -> Self {
+        let data: PackedData = match precision {
+            Precision::F32 => PackedData::F32(tensor.data.clone()),
+            Precision::F16 => PackedData::F16(
+                tensor
+                    .data
+                    .iter()
+                    .map(|value| f32_to_f16_bits(*value))
+                    .collect(),
+            ),
+            Precision::BF16 => PackedData::BF16(
+                tensor
+                    .data
+                    .iter()
+                    .map(|value| f32_to_bf16_bits(*value))
+                    .collect(),
+            ),
+            Precision::Int8 => {
+                let (scale, zero_point): (f32, i8) = int8_parameters(&tensor.data);
+                PackedData::Int8 {
+                    values: tensor
+                        .data
+                        .iter()
+                        .map(|value| quantize_int8(*value, scale, zero_point))
+                        .collect(),
+                    scale,
+                    zero_point,
+                }
+            }
+        };
+
+        PackedTensor2D {
+            data,
+            row_count: tensor.row_count,
+            column_count: tensor.column_count,
+        }
+    }
+
+    pub fn to_tensor2d(&self) -> Tensor2D {
+        Tensor2D {
+            data: (0..self.len()).map(|index| self.get(index)).collect(),
+            row_count: self.row_count,
+            column_count: self.column_count,
+        }
+    }
+
+    pub fn precision(&self) -> Precision {
+        match self.data {
+            PackedData::F32(_) => Precision::F32,
+            PackedData::F16(_) => Precision::F16,
+            PackedData::BF16(_) => Precision::BF16,
+            PackedData::Int8 { .. } => Precision::Int8,
+        }
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.row_count * self.column_count
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The elements plus the scale and zero point of an int8 tensor
+    pub fn size_in_bytes(&self) -> usize {
+        let parameter_size: usize = match self.data {
+            PackedData::Int8 { .. } => std::mem::size_of::<f32>() + std::mem::size_of::<i8>(),
+            _ => 0,
+        };
+        self.len() * self.precision().element_size() + parameter_size
+    }
+
+    // Decodes a single element
+    #[inline(always)]
+    pub fn get(&self, index: usize) -> f32 {
+        match &self.data {
+            PackedData::F32(values) => values[index],
+            PackedData::F16(values) => f16_bits_to_f32(values[index]),
+            PackedData::BF16(values) => bf16_bits_to_f32(values[index]),
+            PackedData::Int8 {
+                values,
+                scale,
+                zero_point,
+            } => dequantize_int8(values[index], *scale, *zero_point),
+        }
+    }
+
+    // The same as Tensor2D::linear, with the weights and bias read from their packed storage.
+    // The input and output stay f32, and so does the accumulation.
+    pub fn linear(input: &Tensor2D, weights: &PackedTensor2D, bias: &PackedTensor2D) -> Tensor2D {
+        let mut output: Tensor2D = Tensor2D::new(0.0, input.row_count, weights.column_count);
+        Self::linear_preallocated(input, weights, bias, &mut output);
+        output
+    }
+
+    pub fn linear_preallocated(
+        input: &Tensor2D,
+        weights: &PackedTensor2D,
+        bias: &PackedTensor2D,
+        output: &mut Tensor2D,
+    ) {
+        assert_eq!(
+            input.column_count, weights.row_count,
+            "\nMismatch - input & weights\ninput - rows: {} columns: {}.\n weights - rows: {} columns: {}.",
+            input.row_count, input.column_count, weights.row_count, weights.column_count
+        );
+        // A batch of samples stacked along the rows repeats the bias once per sample
+        assert!(
+            output.column_count == bias.column_count
+                && output.row_count.is_multiple_of(bias.row_count),
+            "\nMismatch - bias & output\nbias - rows: {} columns: {}.\n out - rows: {} columns: {}.",
+            bias.row_count,
+            bias.column_count,
+            output.row_count,
+            output.column_count
+        );
+
+        // Matching once per call keeps the decoding of each format in its own loop.
+        // Int8 accumulates value * (q - zero_point) and multiplies by the scale once
+        // per output element instead of once per weight.
+        let weights_scale: f32 = match &weights.data {
+            PackedData::F32(values) => {
+                linear_accumulate(input, weights.column_count, output, |index| values[index]);
+                1.0
+            }
+            PackedData::F16(values) => {
+                linear_accumulate(input, weights.column_count, output, |index| {
+                    f16_bits_to_f32(values[index])
+                });
+                1.0
+            }
+            PackedData::BF16(values) => {
+                linear_accumulate(input, weights.column_count, output, |index| {
+                    bf16_bits_to_f32(values[index])
+                });
+                1.0
+            }
+            PackedData::Int8 {
+                values,
+                scale,
+                zero_point,
+            } => {
+                let zero_point: i32 = *zero_point as i32;
+                linear_accumulate(input, weights.column_count, output, |index| {
+                    (values[index] as i32 - zero_point) as f32
+                });
+                *scale
+            }
+        };
+
+        for index in 0..output.len() {
+            output.data[index] = output.data[index] * weights_scale + bias.get(index % bias.len());
+        }
+    }
+}
+
+// output = input * weights, with every weight decoded by weight(index).
+// The rows of the weights are read sequentially, as in Tensor2D::linear_optimized.
+#[inline(always)]
+fn linear_accumulate<F: Fn(usize) -> f32>(
+    input: &Tensor2D,
+    weights_column_count: usize,
+    output: &mut Tensor2D,
+    weight: F,
+) {
+    for row in 0..input.row_count {
+        let output_row: &mut [f32] =
+            &mut output.data[row * weights_column_count..(row + 1) * weights_column_count];
+        output_row.fill(0.0);
+        for inner in 0..input.column_count {
+            let value: f32 = input.data[row * input.column_count + inner];
+            let weights_row: usize = inner * weights_column_count;
+            for (column, output) in output_row.iter_mut().enumerate() {
+                *output += value * weight(weights_row + column);
+            }
+        }
+    }
+}
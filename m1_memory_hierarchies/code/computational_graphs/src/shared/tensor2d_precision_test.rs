@@ -0,0 +1,220 @@
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::shared::{
+        tensor2d::Tensor2D,
+        tensor2d_precision::{
+            bf16_bits_to_f32, f16_bits_to_f32, f32_to_bf16_bits, f32_to_f16_bits, PackedData,
+            PackedTensor2D, Precision,
+        },
+    };
+
+    const PRECISIONS: [Precision; 4] = [
+        Precision::F32,
+        Precision::F16,
+        Precision::BF16,
+        Precision::Int8,
+    ];
+
+    fn random_tensor(rng: &mut ChaCha8Rng, row_count: usize, column_count: usize) -> Tensor2D {
+        let mut tensor: Tensor2D = Tensor2D::new(0.0, row_count, column_count);
+        for element in &mut tensor.data {
+            *element = rng.gen_range(-2.0..2.0);
+        }
+        tensor
+    }
+
+    #[test]
+    fn f16_values() {
+        let values: [(f32, u16); 12] = [
+            (0.0, 0x0000),
+            (-0.0, 0x8000),
+            (1.0, 0x3c00),
+            (-2.0, 0xc000),
+            (0.333_251_95, 0x3555),
+            (65504.0, 0x7bff),
+            // The largest f16 plus half a step rounds up to infinity
+            (65520.0, 0x7c00),
+            (f32::INFINITY, 0x7c00),
+            (f32::NEG_INFINITY, 0xfc00),
+            // The smallest normal and the smallest subnormal
+            (2.0f32.powi(-14), 0x0400),
+            (2.0f32.powi(-24), 0x0001),
+            // Half of the smallest subnormal is a tie, which rounds to the even 0
+            (2.0f32.powi(-25), 0x0000),
+        ];
+        for (value, bits) in values {
+            assert_eq!(f32_to_f16_bits(value), bits, "{}", value);
+        }
+
+        assert!(f16_bits_to_f32(f32_to_f16_bits(f32::NAN)).is_nan());
+        assert_eq!(f16_bits_to_f32(0x0001), 2.0f32.powi(-24));
+        assert_eq!(f16_bits_to_f32(0xfbff), -65504.0);
+    }
+
+    // With 10 mantissa bits a step at 1.0 is 2^-10, so 1 + 2^-11 is halfway
+    #[test]
+    fn f16_rounds_to_nearest_even() {
+        let step: f32 = 2.0f32.powi(-10);
+        assert_eq!(f32_to_f16_bits(1.0 + step * 0.5), 0x3c00);
+        assert_eq!(f32_to_f16_bits(1.0 + step * 1.5), 0x3c02);
+        assert_eq!(f32_to_f16_bits(1.0 + step * 0.5001), 0x3c01);
+        assert_eq!(f32_to_f16_bits(1.0 + step * 0.4999), 0x3c00);
+
+        // The same in the subnormals, where a step is 2^-24
+        let step: f32 = 2.0f32.powi(-24);
+        assert_eq!(f32_to_f16_bits(step * 2.5), 0x0002);
+        assert_eq!(f32_to_f16_bits(step * 3.5), 0x0004);
+        // Rounding up the largest subnormal gives the smallest normal
+        assert_eq!(f32_to_f16_bits(step * 1023.75), 0x0400);
+    }
+
+    // Every f16 converts to an f32 which converts back to the same f16
+    #[test]
+    fn f16_round_trip_every_value() {
+        for bits in 0..=u16::MAX {
+            let value: f32 = f16_bits_to_f32(bits);
+            if value.is_nan() {
+                assert!(f16_bits_to_f32(f32_to_f16_bits(value)).is_nan());
+            } else {
+                assert_eq!(f32_to_f16_bits(value), bits, "{:#06x}", bits);
+            }
+        }
+    }
+
+    #[test]
+    fn bf16_values() {
+        assert_eq!(f32_to_bf16_bits(1.0), 0x3f80);
+        assert_eq!(f32_to_bf16_bits(-2.0), 0xc000);
+        assert_eq!(f32_to_bf16_bits(f32::MAX), 0x7f80);
+        assert_eq!(f32_to_bf16_bits(f32::INFINITY), 0x7f80);
+        assert!(bf16_bits_to_f32(f32_to_bf16_bits(f32::NAN)).is_nan());
+
+        // With 7 mantissa bits a step at 1.0 is 2^-7
+        let step: f32 = 2.0f32.powi(-7);
+        assert_eq!(f32_to_bf16_bits(1.0 + step * 0.5), 0x3f80);
+        assert_eq!(f32_to_bf16_bits(1.0 + step * 1.5), 0x3f82);
+        assert_eq!(f32_to_bf16_bits(1.0 + step * 0.6), 0x3f81);
+
+        for bits in 0..=u16::MAX {
+            let value: f32 = bf16_bits_to_f32(bits);
+            if !value.is_nan() {
+                assert_eq!(f32_to_bf16_bits(value), bits, "{:#06x}", bits);
+            }
+        }
+    }
+
+    // Rounding to nearest keeps the relative error within half a step
+    #[test]
+    fn relative_error() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(5);
+        for _ in 0..10_000 {
+            let value: f32 = rng.gen_range(-1000.0..1000.0);
+            let f16_error: f32 = (f16_bits_to_f32(f32_to_f16_bits(value)) - value).abs();
+            assert!(f16_error <= value.abs() * 2.0f32.powi(-11));
+            let bf16_error: f32 = (bf16_bits_to_f32(f32_to_bf16_bits(value)) - value).abs();
+            assert!(bf16_error <= value.abs() * 2.0f32.powi(-8));
+        }
+    }
+
+    #[test]
+    fn int8() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(9);
+        let tensor: Tensor2D = random_tensor(&mut rng, 7, 13);
+        let packed: PackedTensor2D = PackedTensor2D::from_tensor2d(&tensor, Precision::Int8);
+
+        let (values, scale): (&Vec<i8>, f32) = match &packed.data {
+            PackedData::Int8 { values, scale, .. } => (values, *scale),
+            data => panic!("Expected int8 data, got {:?}", data),
+        };
+        // The smallest and the largest value use the ends of the range
+        assert_eq!(*values.iter().min().unwrap(), -128);
+        assert_eq!(*values.iter().max().unwrap(), 127);
+
+        let unpacked: Tensor2D = packed.to_tensor2d();
+        for (value, unpacked) in tensor.data.iter().zip(&unpacked.data) {
+            assert!((value - unpacked).abs() <= scale * 0.5 + f32::EPSILON);
+        }
+
+        // 0 is exact, even when every value has the same sign
+        let positive: Tensor2D = Tensor2D::new(0.25, 3, 4);
+        let packed: PackedTensor2D = PackedTensor2D::from_tensor2d(&positive, Precision::Int8);
+        assert_eq!(packed.get(0), 0.0);
+        assert!(matches!(
+            packed.data,
+            PackedData::Int8 {
+                zero_point: -128,
+                ..
+            }
+        ));
+
+        let zeros: Tensor2D = Tensor2D::new(0.0, 2, 2);
+        let packed: PackedTensor2D = PackedTensor2D::from_tensor2d(&zeros, Precision::Int8);
+        assert_eq!(packed.to_tensor2d().data, zeros.data);
+    }
+
+    #[test]
+    fn size_in_bytes() {
+        let tensor: Tensor2D = Tensor2D::new(0.1, 16, 32);
+        let sizes: [usize; 4] = [2048, 1024, 1024, 517];
+        for (precision, size) in PRECISIONS.iter().zip(sizes) {
+            let packed: PackedTensor2D = PackedTensor2D::from_tensor2d(&tensor, *precision);
+            assert_eq!(packed.precision(), *precision);
+            assert_eq!(packed.size_in_bytes(), size, "{}", precision.name());
+        }
+    }
+
+    // The packed kernel gives the f32 linear layer of the decoded weights and bias
+    #[test]
+    fn linear() {
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(13);
+        for (row_count, inner_count, column_count) in [(1, 1, 1), (3, 5, 4), (9, 17, 33)] {
+            let input: Tensor2D = random_tensor(&mut rng, row_count, inner_count);
+            let weights: Tensor2D = random_tensor(&mut rng, inner_count, column_count);
+            let bias: Tensor2D = random_tensor(&mut rng, row_count, column_count);
+
+            for precision in PRECISIONS {
+                let packed_weights: PackedTensor2D =
+                    PackedTensor2D::from_tensor2d(&weights, precision);
+                let packed_bias: PackedTensor2D = PackedTensor2D::from_tensor2d(&bias, precision);
+
+                let expected: Tensor2D = Tensor2D::linear(
+                    &input,
+                    &packed_weights.to_tensor2d(),
+                    &packed_bias.to_tensor2d(),
+                );
+                let output: Tensor2D =
+                    PackedTensor2D::linear(&input, &packed_weights, &packed_bias);
+                assert_eq!(
+                    (expected.row_count, expected.column_count),
+                    (output.row_count, output.column_count)
+                );
+                for (expected, output) in expected.data.iter().zip(&output.data) {
+                    assert!(
+                        (expected - output).abs() < 0.0001,
+                        "{}: expected {} got {}",
+                        precision.name(),
+                        expected,
+                        output
+                    );
+                }
+            }
+        }
+    }
+
+    // The GPU kernel can't run without a GPU, but the shader can still be validated
+    #[test]
+    fn packed_linear_shader_validates() {
+        let source: &str = include_str!("shaders/linear_packed.wgsl");
+        let module: naga::Module = naga::front::wgsl::parse_str(source)
+            .unwrap_or_else(|error| panic!("{}", error.emit_to_string(source)));
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        )
+        .validate(&module)
+        .unwrap_or_else(|error| panic!("{:?}", error));
+    }
+}
@@ -34,7 +34,7 @@ mod tests {
         expected: fn(&Tensor2D, &Tensor2D, &Tensor2D) -> Tensor2D,
         test: fn(&Tensor2D, &Tensor2D, &Tensor2D, &mut Tensor2D),
     ) -> f32 {
-        let mut input: Tensor2D = Tensor2D::new(0.5, outer_dimension_input, inner_dimension);
+        let input: Tensor2D = Tensor2D::new(0.5, outer_dimension_input, inner_dimension);
         let weights: Tensor2D = Tensor2D::new(1.0, inner_dimension, outer_dimension_weights);
         let bias: Tensor2D = Tensor2D::new(0.1, outer_dimension_input, outer_dimension_weights);
 
@@ -42,7 +42,7 @@ mod tests {
 
         let mut output: Tensor2D =
             Tensor2D::new(0.0, outer_dimension_input, outer_dimension_weights);
-        test(&mut input, &weights, &bias, &mut output);
+        test(&input, &weights, &bias, &mut output);
 
         subtract_tensors(&expected_output, &output).sum().abs()
     }
@@ -229,6 +229,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn relu_preallocated() {
         let row_count_max: usize = 10;
         let column_count_max: usize = 10;
@@ -255,6 +256,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn relu_inplace() {
         let row_count_max: usize = 10;
         let column_count_max: usize = 10;
@@ -281,6 +283,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn relu_inplace_inline() {
         let row_count_max: usize = 10;
         let column_count_max: usize = 10;
@@ -330,6 +333,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn softmax_preallocated() {
         let row_count_max: usize = 10;
         let column_count_max: usize = 10;
@@ -356,6 +360,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn softmax_inplace() {
         let row_count_max: usize = 10;
         let column_count_max: usize = 10;
@@ -382,6 +387,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn softmax_inplace_inline() {
         let row_count_max: usize = 10;
         let column_count_max: usize = 10;
@@ -451,4 +457,228 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn linear_relu_softmax_fused_fission() {
+        let outer_dimension_input_max: usize = 10;
+        let outer_dimension_weights_max: usize = 10;
+        let inner_dimension_max: usize = 10;
+
+        for outer_dimension_input in 1..outer_dimension_input_max {
+            for outer_dimension_weights in 1..outer_dimension_weights_max {
+                for inner_dimension in 1..inner_dimension_max {
+                    let input: Tensor2D =
+                        Tensor2D::new(0.5, outer_dimension_input, inner_dimension);
+                    let weights: Tensor2D =
+                        Tensor2D::new(1.0, inner_dimension, outer_dimension_weights);
+                    let bias: Tensor2D =
+                        Tensor2D::new(0.1, outer_dimension_input, outer_dimension_weights);
+
+                    let mut output_not_fused: Tensor2D =
+                        Tensor2D::new(0.0, outer_dimension_input, outer_dimension_weights);
+                    Tensor2D::linear_optimized(
+                        &input,
+                        &weights,
+                        &bias,
+                        &mut output_not_fused,
+                    );
+                    Tensor2D::relu_inplace(&mut output_not_fused);
+                    Tensor2D::softmax_inplace_inline(&mut output_not_fused);
+
+                    let mut output_fused: Tensor2D =
+                        Tensor2D::new(0.0, outer_dimension_input, outer_dimension_weights);
+                    Tensor2D::linear_relu_softmax_fused_fission(
+                        &input,
+                        &weights,
+                        &bias,
+                        &mut output_fused,
+                    );
+
+                    let abs_result_difference: f32 =
+                        subtract_tensors(&output_fused, &output_not_fused)
+                            .sum()
+                            .abs();
+
+                    // We modify the error tolerance here as there is a
+                    // significant difference in the numerical computations
+                    // due to the fusion
+                    assert!(abs_result_difference < 10.0 * ERROR_TOLERANCE);
+                }
+            }
+        }
+    }
+
+    // Reference implementation of broadcasting, reads the elements by index
+    // instead of going through the data directly.
+    fn broadcast_reference(
+        left: &Tensor2D,
+        right: &Tensor2D,
+        operation: fn(f32, f32) -> f32,
+    ) -> Tensor2D {
+        let row_count: usize = left.row_count.max(right.row_count);
+        let column_count: usize = left.column_count.max(right.column_count);
+        let mut out: Tensor2D = Tensor2D::new(0.0, row_count, column_count);
+
+        for row in 0..row_count {
+            for column in 0..column_count {
+                let left_value: f32 = left.data
+                    [(row % left.row_count) * left.column_count + (column % left.column_count)];
+                let right_value: f32 = right.data
+                    [(row % right.row_count) * right.column_count + (column % right.column_count)];
+                out.data[row * column_count + column] = operation(left_value, right_value);
+            }
+        }
+
+        out
+    }
+
+    fn test_elementwise(
+        operation: fn(f32, f32) -> f32,
+        test: fn(&Tensor2D, &Tensor2D) -> Tensor2D,
+        test_preallocated: fn(&Tensor2D, &Tensor2D, &mut Tensor2D),
+    ) {
+        for row_count in 1..6 {
+            for column_count in 1..6 {
+                let matrix: Tensor2D = Tensor2D::new(0.5, row_count, column_count);
+                // Offset by one to avoid dividing by zero
+                let mut row_vector: Tensor2D = Tensor2D::new(0.25, 1, column_count);
+                row_vector.data.iter_mut().for_each(|x| *x += 1.0);
+                let mut column_vector: Tensor2D = Tensor2D::new(0.75, row_count, 1);
+                column_vector.data.iter_mut().for_each(|x| *x += 1.0);
+                let mut same_shape: Tensor2D = Tensor2D::new(0.1, row_count, column_count);
+                same_shape.data.iter_mut().for_each(|x| *x += 1.0);
+                let scalar: Tensor2D = Tensor2D {
+                    data: vec![2.0],
+                    row_count: 1,
+                    column_count: 1,
+                };
+
+                let pairs: [(&Tensor2D, &Tensor2D); 7] = [
+                    (&matrix, &same_shape),
+                    (&matrix, &row_vector),
+                    (&row_vector, &same_shape),
+                    (&matrix, &column_vector),
+                    (&column_vector, &same_shape),
+                    (&matrix, &scalar),
+                    (&column_vector, &row_vector),
+                ];
+
+                for (left, right) in pairs {
+                    let expected: Tensor2D = broadcast_reference(left, right, operation);
+
+                    let output: Tensor2D = test(left, right);
+                    assert_eq!(output.row_count, expected.row_count);
+                    assert_eq!(output.column_count, expected.column_count);
+                    assert!(subtract_tensors(&expected, &output).sum().abs() < ERROR_TOLERANCE);
+
+                    let mut output: Tensor2D =
+                        Tensor2D::new(0.0, expected.row_count, expected.column_count);
+                    test_preallocated(left, right, &mut output);
+                    assert!(subtract_tensors(&expected, &output).sum().abs() < ERROR_TOLERANCE);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn broadcast_dimensions() {
+        let matrix: Tensor2D = Tensor2D::new(0.0, 3, 4);
+        assert_eq!(
+            Tensor2D::broadcast_dimensions(&matrix, &Tensor2D::new(0.0, 1, 4)),
+            Some((3, 4))
+        );
+        assert_eq!(
+            Tensor2D::broadcast_dimensions(&Tensor2D::new(0.0, 3, 1), &matrix),
+            Some((3, 4))
+        );
+        assert_eq!(
+            Tensor2D::broadcast_dimensions(&Tensor2D::new(0.0, 3, 1), &Tensor2D::new(0.0, 1, 4)),
+            Some((3, 4))
+        );
+        assert_eq!(
+            Tensor2D::broadcast_dimensions(&matrix, &Tensor2D::new(0.0, 4, 3)),
+            None
+        );
+        assert_eq!(
+            Tensor2D::broadcast_dimensions(&matrix, &Tensor2D::new(0.0, 2, 4)),
+            None
+        );
+    }
+
+    #[test]
+    fn add() {
+        test_elementwise(|a, b| a + b, Tensor2D::add, Tensor2D::add_preallocated);
+    }
+
+    #[test]
+    fn subtract() {
+        test_elementwise(
+            |a, b| a - b,
+            Tensor2D::subtract,
+            Tensor2D::subtract_preallocated,
+        );
+    }
+
+    #[test]
+    fn multiply() {
+        test_elementwise(
+            |a, b| a * b,
+            Tensor2D::multiply,
+            Tensor2D::multiply_preallocated,
+        );
+    }
+
+    #[test]
+    fn divide() {
+        test_elementwise(
+            |a, b| a / b,
+            Tensor2D::divide,
+            Tensor2D::divide_preallocated,
+        );
+    }
+
+    #[test]
+    fn stack_unstack() {
+        let samples: Vec<Tensor2D> = (1..4)
+            .map(|scale| Tensor2D::new(0.5 * scale as f32, 2, 3))
+            .collect();
+        let stacked: Tensor2D = Tensor2D::stack(&samples);
+        assert_eq!((stacked.row_count, stacked.column_count), (6, 3));
+        assert_eq!(stacked.data[6..12], samples[1].data[..]);
+
+        let unstacked: Vec<Tensor2D> = stacked.unstack(samples.len());
+        assert_eq!(unstacked.len(), samples.len());
+        for (sample, unstacked) in samples.iter().zip(&unstacked) {
+            assert_eq!((unstacked.row_count, unstacked.column_count), (2, 3));
+            assert_eq!(sample.data, unstacked.data);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn stack_different_shapes() {
+        Tensor2D::stack(&[Tensor2D::new(0.5, 2, 3), Tensor2D::new(0.5, 3, 2)]);
+    }
+
+    // Every sample of a batch has to get the softmax it would get on its own
+    #[test]
+    fn softmax_batched() {
+        for batch_size in 1..5 {
+            for (row_count, column_count) in [(1, 1), (1, 7), (4, 3), (5, 40)] {
+                let samples: Vec<Tensor2D> = (0..batch_size)
+                    .map(|sample| Tensor2D::new(0.3 - 0.2 * sample as f32, row_count, column_count))
+                    .collect();
+                let stacked: Tensor2D = Tensor2D::stack(&samples);
+
+                let mut output: Tensor2D = Tensor2D::new(0.0, stacked.row_count, column_count);
+                Tensor2D::softmax_batched_preallocated(&stacked, &mut output, batch_size);
+
+                for (sample, output) in samples.iter().zip(output.unstack(batch_size)) {
+                    let difference: Tensor2D = subtract_tensors(&Tensor2D::softmax(sample), &output);
+                    let abs_difference: f32 = difference.data.iter().map(|x| x.abs()).sum::<f32>();
+                    assert!(abs_difference < ERROR_TOLERANCE);
+                }
+            }
+        }
+    }
 }
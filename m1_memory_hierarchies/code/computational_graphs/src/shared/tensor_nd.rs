@@ -0,0 +1,394 @@
+use std::sync::Arc;
+
+use super::tensor2d::Tensor2D;
+
+// Unlike Tensor2D, a TensorND has any number of dimensions and describes
+// how its elements are laid out in memory with strides and an offset.
+// Element [i, j, k] is found at offset + i * strides[0] + j * strides[1] + k * strides[2].
+//
+// This lets transpose, slice, reshape and broadcast create views which
+// reuse the same data instead of copying it. Transposing just swaps two
+// strides, slicing moves the offset and broadcasting sets a stride to 0,
+// so every index along that dimension reads the same element.
+//
+// The data is reference counted so views can share it. Writing to a tensor
+// which shares its data with another view copies the data first, so a write
+// never shows up in any other view. This is also known as copy-on-write.
+#[derive(Clone, Debug)]
+pub struct TensorND {
+    data: Arc<Vec<f32>>,
+    shape: Vec<usize>,
+    strides: Vec<usize>,
+    offset: usize,
+}
+
+impl TensorND {
+    // Mirrors Tensor2D::new, every element is its index times the scale
+    pub fn new(scale: f32, shape: &[usize]) -> Self {
+        let element_count: usize = shape.iter().product();
+        let mut data: Vec<f32> = Vec::<f32>::with_capacity(element_count);
+        for index in 0..element_count {
+            data.push(index as f32 * scale);
+        }
+
+        Self::from_data(data, shape)
+    }
+
+    // Unlike Tensor2D, we actually enforce that the data matches the shape
+    pub fn from_data(data: Vec<f32>, shape: &[usize]) -> Self {
+        let element_count: usize = shape.iter().product();
+        assert_eq!(
+            data.len(),
+            element_count,
+            "\nMismatch - data.len() & shape\ndata.len(): {}.\n shape: {:?} has {} elements.",
+            data.len(),
+            shape,
+            element_count
+        );
+
+        TensorND {
+            data: Arc::new(data),
+            shape: shape.to_vec(),
+            strides: Self::contiguous_strides(shape),
+            offset: 0,
+        }
+    }
+
+    // The strides of a tensor laid out in row-major order, where the
+    // last dimension is next to each other in memory.
+    // The shape [2, 3, 4] has the strides [12, 4, 1].
+    pub fn contiguous_strides(shape: &[usize]) -> Vec<usize> {
+        let mut strides: Vec<usize> = vec![1; shape.len()];
+        for dimension in (0..shape.len().saturating_sub(1)).rev() {
+            strides[dimension] = strides[dimension + 1] * shape[dimension + 1];
+        }
+        strides
+    }
+
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    pub fn strides(&self) -> &[usize] {
+        &self.strides
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn rank(&self) -> usize {
+        self.shape.len()
+    }
+
+    // The number of elements in the view, not in the underlying data
+    pub fn len(&self) -> usize {
+        self.shape.iter().product()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // True if both tensors are views of the same data
+    pub fn shares_data_with(&self, other: &TensorND) -> bool {
+        Arc::ptr_eq(&self.data, &other.data)
+    }
+
+    // Contiguous means the elements are in row-major order with no gaps,
+    // which is what the Tensor2D kernels expect.
+    // Dimensions of size 1 can have any stride, as it is never used.
+    pub fn is_contiguous(&self) -> bool {
+        let mut expected_stride: usize = 1;
+        for dimension in (0..self.rank()).rev() {
+            if self.shape[dimension] != 1 && self.strides[dimension] != expected_stride {
+                return false;
+            }
+            expected_stride *= self.shape[dimension];
+        }
+        true
+    }
+
+    fn data_index(&self, index: &[usize]) -> usize {
+        assert_eq!(
+            index.len(),
+            self.rank(),
+            "Tried to index a tensor of rank {} with {} indices",
+            self.rank(),
+            index.len()
+        );
+
+        let mut data_index: usize = self.offset;
+        for dimension in 0..self.rank() {
+            assert!(
+                index[dimension] < self.shape[dimension],
+                "Index {:?} is out of bounds for shape {:?}",
+                index,
+                self.shape
+            );
+            data_index += index[dimension] * self.strides[dimension];
+        }
+        data_index
+    }
+
+    pub fn get(&self, index: &[usize]) -> f32 {
+        self.data[self.data_index(index)]
+    }
+
+    // A broadcast dimension reads the same element for every index,
+    // so writing to it would change every element along that dimension.
+    pub fn set(&mut self, index: &[usize], value: f32) {
+        assert!(
+            self.shape
+                .iter()
+                .zip(&self.strides)
+                .all(|(size, stride)| *size == 1 || *stride != 0),
+            "Tried to write to a broadcast view with shape {:?} and strides {:?}. Call to_contiguous() first.",
+            self.shape,
+            self.strides
+        );
+
+        let data_index: usize = self.data_index(index);
+        Arc::make_mut(&mut self.data)[data_index] = value;
+    }
+
+    // All elements in row-major order, following the strides
+    pub fn to_vec(&self) -> Vec<f32> {
+        let element_count: usize = self.len();
+        let mut output: Vec<f32> = Vec::<f32>::with_capacity(element_count);
+        if element_count == 0 {
+            return output;
+        }
+
+        if self.is_contiguous() {
+            output.extend_from_slice(&self.data[self.offset..self.offset + element_count]);
+            return output;
+        }
+
+        // Count through the indices like an odometer, the last dimension ticking fastest
+        let mut index: Vec<usize> = vec![0; self.rank()];
+        let mut data_index: usize = self.offset;
+        for _ in 0..element_count {
+            output.push(self.data[data_index]);
+
+            for dimension in (0..self.rank()).rev() {
+                index[dimension] += 1;
+                data_index += self.strides[dimension];
+                if index[dimension] < self.shape[dimension] {
+                    break;
+                }
+                data_index -= index[dimension] * self.strides[dimension];
+                index[dimension] = 0;
+            }
+        }
+
+        output
+    }
+
+    // Copies the elements into new data laid out in row-major order.
+    pub fn to_contiguous(&self) -> TensorND {
+        Self::from_data(self.to_vec(), &self.shape)
+    }
+
+    // Reorders the dimensions, dimension i of the output is
+    // dimension permutation[i] of the input.
+    pub fn permute(&self, permutation: &[usize]) -> TensorND {
+        let mut is_permutation: bool = permutation.len() == self.rank();
+        for dimension in 0..self.rank() {
+            is_permutation = is_permutation && permutation.contains(&dimension);
+        }
+        assert!(
+            is_permutation,
+            "{:?} is not a permutation of the {} dimensions of the tensor",
+            permutation,
+            self.rank()
+        );
+
+        TensorND {
+            data: self.data.clone(),
+            shape: permutation
+                .iter()
+                .map(|dimension| self.shape[*dimension])
+                .collect(),
+            strides: permutation
+                .iter()
+                .map(|dimension| self.strides[*dimension])
+                .collect(),
+            offset: self.offset,
+        }
+    }
+
+    pub fn transpose(&self, dimension_a: usize, dimension_b: usize) -> TensorND {
+        let mut permutation: Vec<usize> = (0..self.rank()).collect();
+        permutation.swap(dimension_a, dimension_b);
+        self.permute(&permutation)
+    }
+
+    // Keeps the indices start..end along a dimension
+    pub fn slice(&self, dimension: usize, start: usize, end: usize) -> TensorND {
+        assert!(
+            dimension < self.rank(),
+            "Tried to slice dimension {} of a tensor of rank {}",
+            dimension,
+            self.rank()
+        );
+        assert!(
+            start <= end && end <= self.shape[dimension],
+            "Slice {}..{} is out of bounds for dimension {} of shape {:?}",
+            start,
+            end,
+            dimension,
+            self.shape
+        );
+
+        let mut shape: Vec<usize> = self.shape.clone();
+        shape[dimension] = end - start;
+
+        TensorND {
+            data: self.data.clone(),
+            shape,
+            strides: self.strides.clone(),
+            offset: self.offset + start * self.strides[dimension],
+        }
+    }
+
+    // Picks a single index along a dimension and removes that dimension.
+    // Selecting index 2 along dimension 0 of a batch of matrices gives the third matrix.
+    pub fn select(&self, dimension: usize, index: usize) -> TensorND {
+        let mut view: TensorND = self.slice(dimension, index, index + 1);
+        view.shape.remove(dimension);
+        view.strides.remove(dimension);
+        view
+    }
+
+    // Only a contiguous tensor can be reshaped without copying, as the
+    // new strides have to describe the same ordering of the data.
+    // Otherwise the tensor is copied with to_contiguous() first.
+    pub fn reshape(&self, shape: &[usize]) -> TensorND {
+        let element_count: usize = shape.iter().product();
+        assert_eq!(
+            self.len(),
+            element_count,
+            "Tried to reshape a tensor with shape {:?} to {:?}, which has a different number of elements",
+            self.shape,
+            shape
+        );
+
+        let source: TensorND = if self.is_contiguous() {
+            self.clone()
+        } else {
+            self.to_contiguous()
+        };
+
+        TensorND {
+            data: source.data,
+            shape: shape.to_vec(),
+            strides: Self::contiguous_strides(shape),
+            offset: source.offset,
+        }
+    }
+
+    // Follows the same rules as NumPy. The shapes are aligned by their last
+    // dimension, missing leading dimensions are added and dimensions of size 1
+    // are repeated by setting their stride to 0.
+    pub fn broadcast_to(&self, shape: &[usize]) -> TensorND {
+        assert!(
+            self.rank() <= shape.len(),
+            "Can't broadcast a tensor with shape {:?} to the lower rank shape {:?}",
+            self.shape,
+            shape
+        );
+
+        let new_dimensions: usize = shape.len() - self.rank();
+        let mut strides: Vec<usize> = vec![0; shape.len()];
+        for dimension in 0..self.rank() {
+            let size: usize = self.shape[dimension];
+            let target_size: usize = shape[new_dimensions + dimension];
+            if size == target_size {
+                strides[new_dimensions + dimension] = self.strides[dimension];
+            } else {
+                assert!(
+                    size == 1,
+                    "Can't broadcast a tensor with shape {:?} to shape {:?}",
+                    self.shape,
+                    shape
+                );
+            }
+        }
+
+        TensorND {
+            data: self.data.clone(),
+            shape: shape.to_vec(),
+            strides,
+            offset: self.offset,
+        }
+    }
+
+    // Moves the data of the Tensor2D without copying it.
+    // Any data past row_count * column_count is dropped.
+    pub fn from_tensor2d(tensor: Tensor2D) -> Self {
+        let mut data: Vec<f32> = tensor.data;
+        data.truncate(tensor.row_count * tensor.column_count);
+        Self::from_data(data, &[tensor.row_count, tensor.column_count])
+    }
+
+    // Lets the existing Tensor2D kernels work on a TensorND.
+    // Rank 1 tensors become a single row.
+    pub fn to_tensor2d(&self) -> Tensor2D {
+        let (row_count, column_count): (usize, usize) = match self.rank() {
+            1 => (1, self.shape[0]),
+            2 => (self.shape[0], self.shape[1]),
+            _ => panic!(
+                "Tried to convert a tensor with shape {:?} to a Tensor2D. Use select() or reshape() first.",
+                self.shape
+            ),
+        };
+
+        Tensor2D {
+            data: self.to_vec(),
+            row_count,
+            column_count,
+        }
+    }
+
+    // Like to_tensor2d, but reuses the data without copying if nothing else
+    // is viewing it and it is already laid out like a Tensor2D.
+    pub fn into_tensor2d(self) -> Tensor2D {
+        let is_whole_data: bool =
+            self.is_contiguous() && self.offset == 0 && self.len() == self.data.len();
+        if !is_whole_data || self.rank() == 0 || 2 < self.rank() {
+            return self.to_tensor2d();
+        }
+
+        let (row_count, column_count): (usize, usize) = if self.rank() == 1 {
+            (1, self.shape[0])
+        } else {
+            (self.shape[0], self.shape[1])
+        };
+
+        match Arc::try_unwrap(self.data) {
+            Ok(data) => Tensor2D {
+                data,
+                row_count,
+                column_count,
+            },
+            Err(data) => Tensor2D {
+                data: data.as_ref().clone(),
+                row_count,
+                column_count,
+            },
+        }
+    }
+}
+
+impl From<Tensor2D> for TensorND {
+    fn from(tensor: Tensor2D) -> Self {
+        Self::from_tensor2d(tensor)
+    }
+}
+
+impl From<&TensorND> for Tensor2D {
+    fn from(tensor: &TensorND) -> Self {
+        tensor.to_tensor2d()
+    }
+}
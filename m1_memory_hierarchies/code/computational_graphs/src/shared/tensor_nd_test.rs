@@ -0,0 +1,225 @@
+#[cfg(test)]
+mod tests {
+    use crate::shared::{tensor2d::Tensor2D, tensor_nd::TensorND};
+
+    #[test]
+    fn contiguous_strides() {
+        assert_eq!(TensorND::contiguous_strides(&[2, 3, 4]), vec![12, 4, 1]);
+        assert_eq!(TensorND::contiguous_strides(&[5]), vec![1]);
+        assert_eq!(TensorND::contiguous_strides(&[]), Vec::<usize>::new());
+
+        let tensor: TensorND = TensorND::new(1.0, &[2, 3, 4]);
+        assert_eq!(tensor.len(), 24);
+        assert_eq!(tensor.rank(), 3);
+        assert!(tensor.is_contiguous());
+        assert_eq!(tensor.get(&[1, 2, 3]), 23.0);
+        assert_eq!(tensor.get(&[1, 0, 2]), 14.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_data_enforces_length() {
+        TensorND::from_data(vec![0.0; 5], &[2, 3]);
+    }
+
+    #[test]
+    fn transpose() {
+        let tensor: TensorND = TensorND::new(1.0, &[2, 3]);
+        let transposed: TensorND = tensor.transpose(0, 1);
+
+        assert!(transposed.shares_data_with(&tensor));
+        assert!(!transposed.is_contiguous());
+        assert_eq!(transposed.shape(), &[3, 2]);
+        assert_eq!(transposed.strides(), &[1, 3]);
+        assert_eq!(transposed.to_vec(), vec![0.0, 3.0, 1.0, 4.0, 2.0, 5.0]);
+
+        for row in 0..2 {
+            for column in 0..3 {
+                assert_eq!(tensor.get(&[row, column]), transposed.get(&[column, row]));
+            }
+        }
+
+        // Transposing twice gets us back where we started
+        let original: TensorND = transposed.transpose(1, 0);
+        assert!(original.is_contiguous());
+        assert_eq!(original.to_vec(), tensor.to_vec());
+    }
+
+    #[test]
+    fn permute() {
+        let tensor: TensorND = TensorND::new(1.0, &[2, 3, 4]);
+        let permuted: TensorND = tensor.permute(&[2, 0, 1]);
+
+        assert!(permuted.shares_data_with(&tensor));
+        assert_eq!(permuted.shape(), &[4, 2, 3]);
+        for i in 0..2 {
+            for j in 0..3 {
+                for k in 0..4 {
+                    assert_eq!(tensor.get(&[i, j, k]), permuted.get(&[k, i, j]));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn slice() {
+        let tensor: TensorND = TensorND::new(1.0, &[4, 5]);
+
+        let rows: TensorND = tensor.slice(0, 1, 3);
+        assert!(rows.shares_data_with(&tensor));
+        assert!(rows.is_contiguous());
+        assert_eq!(rows.offset(), 5);
+        assert_eq!(rows.shape(), &[2, 5]);
+        assert_eq!(
+            rows.to_vec(),
+            (5..15).map(|x| x as f32).collect::<Vec<f32>>()
+        );
+
+        let columns: TensorND = tensor.slice(1, 2, 4);
+        assert!(columns.shares_data_with(&tensor));
+        assert!(!columns.is_contiguous());
+        assert_eq!(
+            columns.to_vec(),
+            vec![2.0, 3.0, 7.0, 8.0, 12.0, 13.0, 17.0, 18.0]
+        );
+
+        // Slices of slices keep adding to the offset
+        let block: TensorND = rows.slice(1, 2, 4);
+        assert_eq!(block.to_vec(), vec![7.0, 8.0, 12.0, 13.0]);
+
+        let empty: TensorND = tensor.slice(0, 2, 2);
+        assert!(empty.is_empty());
+        assert!(empty.to_vec().is_empty());
+    }
+
+    #[test]
+    fn select() {
+        let batch: TensorND = TensorND::new(1.0, &[3, 2, 2]);
+        let second: TensorND = batch.select(0, 1);
+
+        assert!(second.shares_data_with(&batch));
+        assert_eq!(second.shape(), &[2, 2]);
+        assert_eq!(second.to_vec(), vec![4.0, 5.0, 6.0, 7.0]);
+
+        let column: TensorND = second.select(1, 1);
+        assert_eq!(column.shape(), &[2]);
+        assert_eq!(column.to_vec(), vec![5.0, 7.0]);
+    }
+
+    #[test]
+    fn reshape() {
+        let tensor: TensorND = TensorND::new(1.0, &[2, 3, 4]);
+
+        let reshaped: TensorND = tensor.reshape(&[6, 4]);
+        assert!(reshaped.shares_data_with(&tensor));
+        assert_eq!(reshaped.strides(), &[4, 1]);
+        assert_eq!(reshaped.to_vec(), tensor.to_vec());
+
+        // A contiguous slice can still be reshaped without copying
+        let sliced: TensorND = tensor.slice(0, 1, 2).reshape(&[3, 4]);
+        assert!(sliced.shares_data_with(&tensor));
+        assert_eq!(sliced.get(&[0, 0]), 12.0);
+
+        // A transposed tensor has to be copied first
+        let transposed: TensorND = tensor.transpose(1, 2).reshape(&[24]);
+        assert!(!transposed.shares_data_with(&tensor));
+        assert_eq!(transposed.to_vec(), tensor.transpose(1, 2).to_vec());
+    }
+
+    #[test]
+    #[should_panic]
+    fn reshape_wrong_element_count() {
+        TensorND::new(1.0, &[2, 3]).reshape(&[4, 2]);
+    }
+
+    #[test]
+    fn broadcast_to() {
+        let row: TensorND = TensorND::new(1.0, &[1, 3]);
+        let broadcast: TensorND = row.broadcast_to(&[2, 4, 3]);
+
+        assert!(broadcast.shares_data_with(&row));
+        assert_eq!(broadcast.shape(), &[2, 4, 3]);
+        assert_eq!(broadcast.strides(), &[0, 0, 1]);
+        for i in 0..2 {
+            for j in 0..4 {
+                for k in 0..3 {
+                    assert_eq!(broadcast.get(&[i, j, k]), k as f32);
+                }
+            }
+        }
+
+        let column: TensorND = TensorND::new(1.0, &[3, 1]);
+        assert_eq!(
+            column.broadcast_to(&[3, 2]).to_vec(),
+            vec![0.0, 0.0, 1.0, 1.0, 2.0, 2.0]
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn broadcast_incompatible() {
+        TensorND::new(1.0, &[2, 3]).broadcast_to(&[4, 3]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn write_to_broadcast_view() {
+        let mut broadcast: TensorND = TensorND::new(1.0, &[1, 3]).broadcast_to(&[2, 3]);
+        broadcast.set(&[0, 0], 1.0);
+    }
+
+    // Writing to a view copies the data if it is shared, so the other view is unchanged
+    #[test]
+    fn copy_on_write() {
+        let tensor: TensorND = TensorND::new(1.0, &[2, 2]);
+        let mut transposed: TensorND = tensor.transpose(0, 1);
+
+        transposed.set(&[0, 1], 10.0);
+        assert!(!transposed.shares_data_with(&tensor));
+        assert_eq!(transposed.get(&[0, 1]), 10.0);
+        assert_eq!(tensor.get(&[1, 0]), 2.0);
+    }
+
+    #[test]
+    fn tensor2d_conversions() {
+        let tensor2d: Tensor2D = Tensor2D::new(0.5, 3, 4);
+        let tensor: TensorND = TensorND::from(tensor2d.clone());
+        assert_eq!(tensor.shape(), &[3, 4]);
+        assert_eq!(tensor.to_vec(), tensor2d.data);
+
+        // The transpose is copied into a regular Tensor2D which the kernels can use
+        let transposed: Tensor2D = tensor.transpose(0, 1).to_tensor2d();
+        assert_eq!(transposed.row_count, 4);
+        assert_eq!(transposed.column_count, 3);
+        for row in 0..3 {
+            for column in 0..4 {
+                assert_eq!(
+                    tensor2d.data[row * 4 + column],
+                    transposed.data[column * 3 + row]
+                );
+            }
+        }
+
+        // A batch of inputs can be fed to the existing Tensor2D kernels one at a time
+        let batch: TensorND = TensorND::new(0.1, &[2, 3, 4]);
+        let weights: Tensor2D = Tensor2D::new(0.1, 4, 2);
+        let bias: Tensor2D = Tensor2D::new(0.1, 3, 2);
+        for index in 0..2 {
+            let input: Tensor2D = Tensor2D::from(&batch.select(0, index));
+            let expected_input: Vec<f32> = batch.to_vec()[index * 12..(index + 1) * 12].to_vec();
+            assert_eq!(input.data, expected_input);
+            let output: Tensor2D = Tensor2D::linear(&input, &weights, &bias);
+            assert_eq!(output.row_count, 3);
+            assert_eq!(output.column_count, 2);
+        }
+
+        // Without any other views, the data is moved back without copying
+        let round_trip: Tensor2D = TensorND::from(tensor2d.clone()).into_tensor2d();
+        assert_eq!(round_trip.data, tensor2d.data);
+
+        let moved: Vec<f32> = tensor2d.data.clone();
+        let pointer: *const f32 = moved.as_ptr();
+        let round_trip: Tensor2D = TensorND::from_data(moved, &[3, 4]).into_tensor2d();
+        assert_eq!(round_trip.data.as_ptr(), pointer);
+    }
+}
@@ -1,21 +1,22 @@
-use std::{borrow::Cow, mem};
+use std::mem;
 
 use wgpu::{
-    Queue, 
-    Device, 
-    Adapter, 
-    AdapterInfo, 
-    Instance, 
-    RequestAdapterOptions, 
     ShaderModule, 
     ComputePipeline, 
     BindGroupLayout, 
     BindingResource, 
     BindGroup, 
-    BindGroupEntry, 
     Buffer, util::DeviceExt, CommandEncoder, ComputePass, BufferSlice, BufferView,
 };
 
+// GPUHandles and the functions for setting up the GPU are shared
+// with the other projects. Try hovering your mouse over these types
+// and see what the messages are!
+use gpu_common::{
+    create_bind_group, create_compute_pipeline, create_shader_module, request_gpu, self_test,
+    GPUHandles, GPUOptions,
+};
+
 // We create this struct to send global information (a uniform in graphics API parlance)
 // to all threads. If this were a 2 dimensional example we could also send
 // more dimensional information or whatever else we could think of.
@@ -134,13 +135,18 @@ fn main() {
     // Initialize the env_logger to get usueful messages from wgpu.
     env_logger::init();
 
+    // Which GPU to use and what to ask of it. The WGPU_ADAPTER_NAME,
+    // WGPU_BACKEND and WGPU_POWER_PREF environment variables can
+    // pick a specific GPU.
+    let options: GPUOptions = GPUOptions::from_env();
+
     // Is there a compatible GPU on the system?
     // Use pollster::block_on to block on async functions.
     // Think of it like this - this is a function which
     // uses the GPU. With block_on() we are insisting
     // on waiting until all the interaction with the GPU
     // and the tasks set in motion on the GPU are finished.
-    if !pollster::block_on(self_test()) {
+    if !pollster::block_on(self_test(&options)) {
         panic!("Was unable to confirm that your system is incompatible with this sample!");
     }
 
@@ -151,7 +157,7 @@ fn main() {
     let output: Vec<f32> = vec![0.0; element_count];
 
     // Keep track of the handles to central stuff like device and queue.
-    let handles: GPUHandles = pollster::block_on(initialize_gpu()).expect("Was unsuccesful in creating GPU Handles");
+    let handles: GPUHandles = pollster::block_on(request_gpu(&options)).expect("Was unsuccesful in creating GPU Handles");
 
     // Create our uniform for telling the shader how big the vectors are.
     let uniform: VectorAddUniform = VectorAddUniform::new(&handles, element_count);
@@ -259,139 +265,3 @@ fn main() {
     println!("Results were: {:?}", output.cpu_data);
 
 }
-
-// Try hovering your mouse over these types and see
-// what the messages are!
-pub struct GPUHandles {
-    pub queue: Queue,
-    pub device: Device,
-    pub adapter: Adapter,
-    pub adapter_info: AdapterInfo,
-}
-
-pub async fn self_test() -> bool {
-    println!("Performing self test to check system for compatibility.");
-    // Instantiates instance of wgpu
-    let instance: Instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-        backends: wgpu::Backends::all(),
-        dx12_shader_compiler: Default::default(),
-    });
-
-    // We request an adapter with high performace. In the case of both
-    // an integrated and a dedicated GPU, it should prefer the dedicated
-    // GPU. We don't require a compatible surface, which is what would
-    // allows us to present to screen. We are not doing graphics
-    // so we don't need it.
-    let adapter_request: RequestAdapterOptions = RequestAdapterOptions {
-        power_preference: wgpu::PowerPreference::HighPerformance,
-        compatible_surface: None,
-        force_fallback_adapter: false,
-    };
-
-    // `request_adapter` instantiates the general connection to the GPU
-    let adapter_option: Option<Adapter> = instance.request_adapter(&adapter_request).await;
-
-    match adapter_option {
-        Some(adapter) => {
-            let info: AdapterInfo = adapter.get_info();
-            println!("Found GPU: {:?}", info);
-            true
-        }
-        None => {
-            println!("Failed to find a usable GPU. This framework will only run CPU code.");
-            false
-        }
-    }
-}
-
-pub async fn initialize_gpu() -> Option<GPUHandles> {
-    // Instantiates instance of wgpu
-    let instance: Instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-        backends: wgpu::Backends::all(),
-        dx12_shader_compiler: Default::default(),
-    });
-
-    // `request_adapter` instantiates the general connection to the GPU
-    let adapter: Adapter = instance
-        .request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::HighPerformance,
-            compatible_surface: None, // We aren't doing any graphics
-            force_fallback_adapter: false,
-        })
-        .await
-        .expect("Failed to find a usable GPU!");
-
-    // `request_device` instantiates the feature specific connection to the GPU, defining some parameters,
-    //  `features` being the available features.
-    let (device, queue): (Device, Queue) = adapter
-        .request_device(
-            &wgpu::DeviceDescriptor {
-                label: None,
-                features: wgpu::Features::empty(),
-                limits: wgpu::Limits::default(),
-            },
-            None,
-        )
-        .await
-        .unwrap();
-
-    let adapter_info: AdapterInfo = adapter.get_info();
-
-    let gpu_handles: GPUHandles = GPUHandles {
-        queue,
-        device,
-        adapter,
-        adapter_info,
-    };
-
-    Some(gpu_handles)
-}
-
-// Compile our shader code.
-pub fn create_shader_module(gpu_handles: &GPUHandles, shader: &str) -> ShaderModule {
-    gpu_handles
-        .device
-        .create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: None,
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(shader)),
-        })
-}
-
-// Create a compute pipeline.
-pub fn create_compute_pipeline(
-    gpu_handles: &GPUHandles,
-    module: &ShaderModule,
-    entry_point: &str,
-) -> ComputePipeline {
-    gpu_handles
-        .device
-        .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: None,
-            layout: None,
-            module,
-            entry_point,
-        })
-}
-
-// Create a bind group from a vector
-// f bindings.
-pub fn create_bind_group(
-    gpu_handles: &GPUHandles,
-    bind_group_layout: &BindGroupLayout,
-    to_be_bound: Vec<(u32, BindingResource)>,
-) -> BindGroup {
-    let mut entries: Vec<BindGroupEntry> = vec![];
-
-    for (binding, resource) in to_be_bound {
-        let entry: BindGroupEntry = BindGroupEntry { binding, resource };
-        entries.push(entry);
-    }
-
-    gpu_handles
-        .device
-        .create_bind_group(&wgpu::BindGroupDescriptor {
-            label: None,
-            layout: bind_group_layout,
-            entries: entries.as_slice(),
-        })
-}
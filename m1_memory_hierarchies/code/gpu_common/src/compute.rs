@@ -0,0 +1,55 @@
+use std::borrow::Cow;
+
+use wgpu::{
+    BindGroup, BindGroupEntry, BindGroupLayout, BindingResource, ComputePipeline, ShaderModule,
+};
+
+use crate::gpu_handles::GPUHandles;
+
+// Compile our shader code.
+pub fn create_shader_module(gpu_handles: &GPUHandles, shader: &str) -> ShaderModule {
+    gpu_handles
+        .device
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(shader)),
+        })
+}
+
+// Create a compute pipeline.
+pub fn create_compute_pipeline(
+    gpu_handles: &GPUHandles,
+    module: &ShaderModule,
+    entry_point: &str,
+) -> ComputePipeline {
+    gpu_handles
+        .device
+        .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: None,
+            layout: None,
+            module,
+            entry_point,
+        })
+}
+
+// Create a bind group from a vector of bindings.
+pub fn create_bind_group(
+    gpu_handles: &GPUHandles,
+    bind_group_layout: &BindGroupLayout,
+    to_be_bound: Vec<(u32, BindingResource)>,
+) -> BindGroup {
+    let mut entries: Vec<BindGroupEntry> = vec![];
+
+    for (binding, resource) in to_be_bound {
+        let entry: BindGroupEntry = BindGroupEntry { binding, resource };
+        entries.push(entry);
+    }
+
+    gpu_handles
+        .device
+        .create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: bind_group_layout,
+            entries: entries.as_slice(),
+        })
+}
@@ -0,0 +1,425 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    future::Future,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll, Waker},
+};
+
+use wgpu::{
+    util::DeviceExt, BindGroup, BindGroupLayout, BindGroupLayoutEntry, BindingResource,
+    BindingType, Buffer, BufferAsyncError, BufferBindingType, BufferUsages, BufferView,
+    CommandEncoder, ComputePass, ComputePipeline, PipelineLayout, ShaderModule, ShaderStages,
+    SubmissionIndex,
+};
+
+use crate::{
+    compute::{create_bind_group, create_shader_module},
+    gpu_error::GPUError,
+    gpu_handles::GPUHandles,
+};
+
+// Has to match the shader, var<storage, read> or var<storage, read_write>
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BufferAccess {
+    Read,
+    ReadWrite,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BindingKind {
+    Uniform,
+    Storage(BufferAccess),
+}
+
+// Pipelines are cached by the shader they were compiled from and their entry point
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineKey {
+    pub source_hash: u64,
+    pub entry_point: String,
+}
+
+impl PipelineKey {
+    pub fn new(source: &str, entry_point: &str) -> Self {
+        PipelineKey {
+            source_hash: hash_source(source),
+            entry_point: entry_point.to_string(),
+        }
+    }
+}
+
+pub fn hash_source(source: &str) -> u64 {
+    let mut hasher: DefaultHasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    hasher.finish()
+}
+
+// The bind group layout entries for group 0, sorted by binding. The layout is given
+// explicitly, rather than derived from the shader, so bindings which a specific
+// entry point doesn't use can still be bound.
+pub fn bind_group_layout_entries(layout: &[(u32, BindingKind)]) -> Vec<BindGroupLayoutEntry> {
+    let mut entries: Vec<BindGroupLayoutEntry> = layout
+        .iter()
+        .map(|(binding, kind)| BindGroupLayoutEntry {
+            binding: *binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: match kind {
+                    BindingKind::Uniform => BufferBindingType::Uniform,
+                    BindingKind::Storage(access) => BufferBindingType::Storage {
+                        read_only: *access == BufferAccess::Read,
+                    },
+                },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        })
+        .collect();
+    entries.sort_by_key(|entry| entry.binding);
+    entries
+}
+
+struct CachedPipeline {
+    layout: Vec<(u32, BindingKind)>,
+    bind_group_layout: BindGroupLayout,
+    pipeline: ComputePipeline,
+}
+
+// Keeps compiled shader modules and pipelines around between dispatches, e.g.
+//
+// let output: Vec<f32> = dispatcher
+//     .dispatch(&gpu_handles, include_str!("vector_add.wgsl"), "vector_add")
+//     .with_uniform(0, &uniform)
+//     .with_read(1, &input_a)
+//     .with_read(2, &input_b)
+//     .with_read_write(3, &output)
+//     .with_workgroups(launch_blocks, 1, 1)
+//     .read_back(3)?;
+//
+// Only the first dispatch of a shader and entry point pays for compiling it.
+#[derive(Default)]
+pub struct ComputeDispatcher {
+    modules: HashMap<u64, ShaderModule>,
+    pipelines: HashMap<PipelineKey, CachedPipeline>,
+}
+
+impl ComputeDispatcher {
+    pub fn new() -> Self {
+        ComputeDispatcher::default()
+    }
+
+    pub fn module_count(&self) -> usize {
+        self.modules.len()
+    }
+
+    pub fn pipeline_count(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    pub fn is_cached(&self, source: &str, entry_point: &str) -> bool {
+        self.pipelines
+            .contains_key(&PipelineKey::new(source, entry_point))
+    }
+
+    pub fn clear(&mut self) {
+        self.pipelines.clear();
+        self.modules.clear();
+    }
+
+    pub fn dispatch<'a>(
+        &'a mut self,
+        gpu_handles: &'a GPUHandles,
+        source: &'a str,
+        entry_point: &'a str,
+    ) -> Dispatch<'a> {
+        Dispatch {
+            dispatcher: self,
+            gpu_handles,
+            source,
+            entry_point,
+            uniforms: Vec::new(),
+            buffers: Vec::new(),
+            workgroup_count: (1, 1, 1),
+        }
+    }
+
+    fn pipeline(
+        &mut self,
+        gpu_handles: &GPUHandles,
+        source: &str,
+        entry_point: &str,
+        layout: Vec<(u32, BindingKind)>,
+    ) -> Result<&CachedPipeline, GPUError> {
+        let key: PipelineKey = PipelineKey::new(source, entry_point);
+
+        if !self.pipelines.contains_key(&key) {
+            let module: &ShaderModule = self
+                .modules
+                .entry(key.source_hash)
+                .or_insert_with(|| create_shader_module(gpu_handles, source));
+
+            let bind_group_layout: BindGroupLayout =
+                gpu_handles
+                    .device
+                    .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                        label: Some(entry_point),
+                        entries: &bind_group_layout_entries(&layout),
+                    });
+            let pipeline_layout: PipelineLayout =
+                gpu_handles
+                    .device
+                    .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                        label: Some(entry_point),
+                        bind_group_layouts: &[&bind_group_layout],
+                        push_constant_ranges: &[],
+                    });
+            let pipeline: ComputePipeline =
+                gpu_handles
+                    .device
+                    .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                        label: Some(entry_point),
+                        layout: Some(&pipeline_layout),
+                        module,
+                        entry_point,
+                    });
+
+            self.pipelines.insert(
+                key.clone(),
+                CachedPipeline {
+                    layout: layout.clone(),
+                    bind_group_layout,
+                    pipeline,
+                },
+            );
+        }
+
+        let cached: &CachedPipeline = &self.pipelines[&key];
+        if cached.layout != layout {
+            return Err(GPUError::BindingLayoutMismatch {
+                entry_point: entry_point.to_string(),
+            });
+        }
+        Ok(cached)
+    }
+}
+
+// A single dispatch of a cached pipeline. Bindings are all in group 0.
+pub struct Dispatch<'a> {
+    dispatcher: &'a mut ComputeDispatcher,
+    gpu_handles: &'a GPUHandles,
+    source: &'a str,
+    entry_point: &'a str,
+    uniforms: Vec<(u32, Buffer)>,
+    buffers: Vec<(u32, &'a Buffer, BufferAccess)>,
+    workgroup_count: (u32, u32, u32),
+}
+
+impl<'a> Dispatch<'a> {
+    // Uploads value to a new uniform buffer. U has to match the struct in the shader,
+    // including its padding to 16 bytes.
+    pub fn with_uniform<U: bytemuck::Pod>(mut self, binding: u32, value: &U) -> Self {
+        let buffer: Buffer =
+            self.gpu_handles
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Uniform"),
+                    contents: bytemuck::bytes_of(value),
+                    usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+                });
+        self.uniforms.push((binding, buffer));
+        self
+    }
+
+    pub fn with_read(mut self, binding: u32, buffer: &'a Buffer) -> Self {
+        self.buffers.push((binding, buffer, BufferAccess::Read));
+        self
+    }
+
+    pub fn with_read_write(mut self, binding: u32, buffer: &'a Buffer) -> Self {
+        self.buffers
+            .push((binding, buffer, BufferAccess::ReadWrite));
+        self
+    }
+
+    // The number of workgroups, not threads, in each dimension
+    pub fn with_workgroups(mut self, x: u32, y: u32, z: u32) -> Self {
+        self.workgroup_count = (x, y, z);
+        self
+    }
+
+    pub fn encode(self, encoder: &mut CommandEncoder) -> Result<(), GPUError> {
+        let mut layout: Vec<(u32, BindingKind)> = Vec::new();
+        let mut to_be_bound: Vec<(u32, BindingResource)> = Vec::new();
+        for (binding, buffer) in &self.uniforms {
+            layout.push((*binding, BindingKind::Uniform));
+            to_be_bound.push((*binding, buffer.as_entire_binding()));
+        }
+        for (binding, buffer, access) in &self.buffers {
+            layout.push((*binding, BindingKind::Storage(*access)));
+            to_be_bound.push((*binding, buffer.as_entire_binding()));
+        }
+        layout.sort_by_key(|(binding, _)| *binding);
+
+        let cached: &CachedPipeline =
+            self.dispatcher
+                .pipeline(self.gpu_handles, self.source, self.entry_point, layout)?;
+        let bind_group: BindGroup =
+            create_bind_group(self.gpu_handles, &cached.bind_group_layout, to_be_bound);
+
+        let mut compute_pass: ComputePass =
+            encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some(self.entry_point),
+            });
+        compute_pass.set_pipeline(&cached.pipeline);
+        compute_pass.set_bind_group(0, &bind_group, &[]);
+        let (x, y, z): (u32, u32, u32) = self.workgroup_count;
+        compute_pass.dispatch_workgroups(x, y, z);
+        Ok(())
+    }
+
+    pub fn submit(self) -> Result<SubmissionIndex, GPUError> {
+        let gpu_handles: &GPUHandles = self.gpu_handles;
+        let mut encoder: CommandEncoder = gpu_handles
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.encode(&mut encoder)?;
+        Ok(gpu_handles.queue.submit(Some(encoder.finish())))
+    }
+
+    // Dispatches and blocks until the contents of the read-write buffer at binding are back
+    pub fn read_back<T: bytemuck::Pod>(self, binding: u32) -> Result<Vec<T>, GPUError> {
+        self.read_back_async(binding)?.wait()
+    }
+
+    // Dispatches and returns right away. The readback is a future, which is woken
+    // once the device has been polled past the copy, either by the caller or by
+    // another thread with Maintain::Wait, or it can be waited on with Readback::wait.
+    pub fn read_back_async<T: bytemuck::Pod>(
+        self,
+        binding: u32,
+    ) -> Result<Readback<'a, T>, GPUError> {
+        let gpu_handles: &'a GPUHandles = self.gpu_handles;
+        let buffer: &'a Buffer = match self.buffers.iter().find(|(index, buffer, access)| {
+            *index == binding
+                && *access == BufferAccess::ReadWrite
+                && buffer.usage().contains(BufferUsages::COPY_SRC)
+        }) {
+            Some((_, buffer, _)) => buffer,
+            None => return Err(GPUError::NotReadable { binding }),
+        };
+
+        // The storage buffer can't be mapped, so it's copied to a staging buffer after the dispatch
+        let staging_buffer: Buffer = gpu_handles.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback"),
+            size: buffer.size(),
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder: CommandEncoder = gpu_handles
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.encode(&mut encoder)?;
+        encoder.copy_buffer_to_buffer(buffer, 0, &staging_buffer, 0, buffer.size());
+        gpu_handles.queue.submit(Some(encoder.finish()));
+
+        Ok(Readback::new(gpu_handles, staging_buffer))
+    }
+}
+
+// Filled in by the map callback, which wakes the task waiting on the readback, if any
+#[derive(Default)]
+struct MapState {
+    result: Option<Result<(), BufferAsyncError>>,
+    waker: Option<Waker>,
+}
+
+type SharedMapState = Arc<Mutex<MapState>>;
+
+// The contents of a staging buffer on their way back to the CPU
+pub struct Readback<'a, T> {
+    gpu_handles: &'a GPUHandles,
+    staging_buffer: Buffer,
+    state: SharedMapState,
+    element: PhantomData<fn() -> T>,
+}
+
+impl<'a, T: bytemuck::Pod> Readback<'a, T> {
+    // Starts mapping a buffer with MAP_READ usage. Nothing happens until the device is polled.
+    pub fn new(gpu_handles: &'a GPUHandles, staging_buffer: Buffer) -> Self {
+        let state: SharedMapState = Arc::new(Mutex::new(MapState::default()));
+        let sender: SharedMapState = state.clone();
+        staging_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let waker: Option<Waker> = {
+                    let mut state: MutexGuard<MapState> = sender.lock().unwrap();
+                    state.result = Some(result);
+                    state.waker.take()
+                };
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+            });
+
+        Readback {
+            gpu_handles,
+            staging_buffer,
+            state,
+            element: PhantomData,
+        }
+    }
+
+    // Blocks until the GPU is done with everything submitted so far
+    pub fn wait(self) -> Result<Vec<T>, GPUError> {
+        loop {
+            self.gpu_handles.device.poll(wgpu::Maintain::Wait);
+            let result: Option<Result<(), BufferAsyncError>> =
+                self.state.lock().unwrap().result.take();
+            if let Some(result) = result {
+                return self.read(result);
+            }
+        }
+    }
+
+    fn read(&self, result: Result<(), BufferAsyncError>) -> Result<Vec<T>, GPUError> {
+        match result {
+            Ok(()) => {
+                let data: BufferView = self.staging_buffer.slice(..).get_mapped_range();
+                // We actually receive this data as raw bytes &[u8] so we recast it.
+                let values: Vec<T> = bytemuck::cast_slice(&data).to_vec();
+                drop(data);
+                self.staging_buffer.unmap();
+                Ok(values)
+            }
+            Err(error) => Err(GPUError::BufferMap(error)),
+        }
+    }
+}
+
+// Native wgpu only calls the map callback from inside Device::poll, so awaiting a readback
+// needs someone to poll the device, such as the caller before awaiting it or a thread
+// blocking in Maintain::Wait. The future doesn't poll the device itself.
+impl<'a, T: bytemuck::Pod> Future for Readback<'a, T> {
+    type Output = Result<Vec<T>, GPUError>;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Self::Output> {
+        // The waker is stored under the same lock the callback takes,
+        // so a result arriving in between can't be missed
+        let result: Result<(), BufferAsyncError> = {
+            let mut state: MutexGuard<MapState> = self.state.lock().unwrap();
+            match state.result.take() {
+                Some(result) => result,
+                None => {
+                    state.waker = Some(context.waker().clone());
+                    return Poll::Pending;
+                }
+            }
+        };
+
+        Poll::Ready(self.read(result))
+    }
+}
@@ -0,0 +1,287 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        future::Future,
+        pin::Pin,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        task::{Context, Poll, Wake, Waker},
+        thread,
+    };
+
+    use wgpu::{
+        util::DeviceExt, BindingType, Buffer, BufferBindingType, BufferUsages, CommandEncoder,
+    };
+
+    use crate::{
+        dispatcher::{bind_group_layout_entries, hash_source},
+        request_gpu, BindingKind, BufferAccess, ComputeDispatcher, GPUError, GPUHandles,
+        GPUOptions, PipelineKey, Readback,
+    };
+
+    // output = a * b + c, or just a * b, which leaves c unused
+    const SHADER: &str = "
+struct Dimensions {
+    element_count: u32,
+    not_used: u32,
+    not_used: u32,
+    not_used: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> dimensions: Dimensions;
+
+@group(0) @binding(1)
+var<storage, read> a: array<f32>;
+
+@group(0) @binding(2)
+var<storage, read> b: array<f32>;
+
+@group(0) @binding(3)
+var<storage, read> c: array<f32>;
+
+@group(0) @binding(4)
+var<storage, read_write> output: array<f32>;
+
+@compute @workgroup_size(32, 1, 1)
+fn multiply_add(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index: u32 = global_id.x;
+    if (index < dimensions.element_count) {
+        output[index] = a[index] * b[index] + c[index];
+    }
+}
+
+@compute @workgroup_size(32, 1, 1)
+fn multiply(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index: u32 = global_id.x;
+    if (index < dimensions.element_count) {
+        output[index] = a[index] * b[index];
+    }
+}
+";
+
+    #[repr(C)]
+    #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+    struct Dimensions {
+        element_count: u32,
+        not_used: [u32; 3],
+    }
+
+    fn storage_buffer(gpu_handles: &GPUHandles, data: &[f32]) -> Buffer {
+        gpu_handles
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: bytemuck::cast_slice(data),
+                usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+            })
+    }
+
+    #[test]
+    fn pipeline_keys() {
+        let copy: String = SHADER.chars().collect();
+        assert_eq!(hash_source(SHADER), hash_source(&copy));
+        assert_ne!(hash_source(SHADER), hash_source(&SHADER.replace('*', "-")));
+        assert_eq!(
+            PipelineKey::new(SHADER, "multiply"),
+            PipelineKey::new(SHADER, "multiply")
+        );
+        assert_ne!(
+            PipelineKey::new(SHADER, "multiply"),
+            PipelineKey::new(SHADER, "multiply_add")
+        );
+    }
+
+    #[test]
+    fn layout_entries() {
+        let entries = bind_group_layout_entries(&[
+            (2, BindingKind::Storage(BufferAccess::ReadWrite)),
+            (0, BindingKind::Uniform),
+            (1, BindingKind::Storage(BufferAccess::Read)),
+        ]);
+        let bindings: Vec<u32> = entries.iter().map(|entry| entry.binding).collect();
+        assert_eq!(bindings, vec![0, 1, 2]);
+
+        let types: Vec<BufferBindingType> = entries
+            .iter()
+            .map(|entry| match entry.ty {
+                BindingType::Buffer { ty, .. } => ty,
+                _ => panic!("Only buffers are bound"),
+            })
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                BufferBindingType::Uniform,
+                BufferBindingType::Storage { read_only: true },
+                BufferBindingType::Storage { read_only: false },
+            ]
+        );
+    }
+
+    #[test]
+    fn dispatch() {
+        let gpu_handles: GPUHandles = match pollster::block_on(request_gpu(&GPUOptions::from_env()))
+        {
+            Ok(gpu_handles) => gpu_handles,
+            Err(error) => {
+                println!("Skipping dispatch test: {}", error);
+                return;
+            }
+        };
+
+        let element_count: usize = 100;
+        let a: Vec<f32> = (0..element_count).map(|x| x as f32).collect();
+        let b: Vec<f32> = (0..element_count).map(|x| x as f32 * 0.5).collect();
+        let c: Vec<f32> = (0..element_count).map(|x| x as f32 * -0.25).collect();
+        let dimensions: Dimensions = Dimensions {
+            element_count: element_count as u32,
+            not_used: [0; 3],
+        };
+        let a_buffer: Buffer = storage_buffer(&gpu_handles, &a);
+        let b_buffer: Buffer = storage_buffer(&gpu_handles, &b);
+        let c_buffer: Buffer = storage_buffer(&gpu_handles, &c);
+        let output_buffer: Buffer = storage_buffer(&gpu_handles, &vec![0.0; element_count]);
+        let launch_blocks: u32 = element_count.div_ceil(32) as u32;
+
+        let mut dispatcher: ComputeDispatcher = ComputeDispatcher::new();
+        for entry_point in ["multiply_add", "multiply", "multiply_add"] {
+            let output: Vec<f32> = dispatcher
+                .dispatch(&gpu_handles, SHADER, entry_point)
+                .with_uniform(0, &dimensions)
+                .with_read(1, &a_buffer)
+                .with_read(2, &b_buffer)
+                .with_read(3, &c_buffer)
+                .with_read_write(4, &output_buffer)
+                .with_workgroups(launch_blocks, 1, 1)
+                .read_back(4)
+                .unwrap();
+            for index in 0..element_count {
+                let expected: f32 = if entry_point == "multiply_add" {
+                    a[index] * b[index] + c[index]
+                } else {
+                    a[index] * b[index]
+                };
+                assert_eq!(output[index], expected, "{} at {}", entry_point, index);
+            }
+        }
+        // One module for both entry points, and each pipeline only compiled once
+        assert_eq!(dispatcher.module_count(), 1);
+        assert_eq!(dispatcher.pipeline_count(), 2);
+        assert!(dispatcher.is_cached(SHADER, "multiply"));
+
+        // Swap the operands and read back asynchronously, while another thread polls the device
+        let readback: Readback<f32> = dispatcher
+            .dispatch(&gpu_handles, SHADER, "multiply_add")
+            .with_uniform(0, &dimensions)
+            .with_read(1, &c_buffer)
+            .with_read(2, &b_buffer)
+            .with_read(3, &a_buffer)
+            .with_read_write(4, &output_buffer)
+            .with_workgroups(launch_blocks, 1, 1)
+            .read_back_async::<f32>(4)
+            .unwrap();
+        let output: Vec<f32> = thread::scope(|scope| {
+            scope.spawn(|| gpu_handles.device.poll(wgpu::Maintain::Wait));
+            pollster::block_on(readback)
+        })
+        .unwrap();
+        for index in 0..element_count {
+            assert_eq!(output[index], c[index] * b[index] + a[index]);
+        }
+        assert_eq!(dispatcher.pipeline_count(), 2);
+
+        // Read-only bindings can't be read back
+        let result: Result<Vec<f32>, GPUError> = dispatcher
+            .dispatch(&gpu_handles, SHADER, "multiply")
+            .with_uniform(0, &dimensions)
+            .with_read(1, &a_buffer)
+            .with_read(2, &b_buffer)
+            .with_read(3, &c_buffer)
+            .with_read_write(4, &output_buffer)
+            .read_back(1);
+        assert!(matches!(result, Err(GPUError::NotReadable { binding: 1 })));
+
+        // A cached pipeline can't be dispatched with other bindings
+        let result: Result<Vec<f32>, GPUError> = dispatcher
+            .dispatch(&gpu_handles, SHADER, "multiply")
+            .with_uniform(0, &dimensions)
+            .with_read(1, &a_buffer)
+            .with_read(2, &b_buffer)
+            .with_read_write(4, &output_buffer)
+            .read_back(4);
+        assert!(matches!(
+            result,
+            Err(GPUError::BindingLayoutMismatch { .. })
+        ));
+
+        dispatcher.clear();
+        assert_eq!(dispatcher.module_count(), 0);
+        assert_eq!(dispatcher.pipeline_count(), 0);
+    }
+
+    struct CountingWaker {
+        wake_count: AtomicUsize,
+    }
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.wake_count.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    // A pending readback is woken once by the map callback, instead of waking itself
+    #[test]
+    fn readback_waker() {
+        let gpu_handles: GPUHandles = match pollster::block_on(request_gpu(&GPUOptions::from_env()))
+        {
+            Ok(gpu_handles) => gpu_handles,
+            Err(error) => {
+                println!("Skipping readback_waker test: {}", error);
+                return;
+            }
+        };
+
+        let input: Vec<u32> = (0..64).collect();
+        let buffer: Buffer =
+            gpu_handles
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: None,
+                    contents: bytemuck::cast_slice(&input),
+                    usage: BufferUsages::COPY_SRC,
+                });
+        let staging_buffer: Buffer = gpu_handles.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: buffer.size(),
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder: CommandEncoder = gpu_handles
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_buffer_to_buffer(&buffer, 0, &staging_buffer, 0, buffer.size());
+        gpu_handles.queue.submit(Some(encoder.finish()));
+
+        let mut readback: Readback<u32> = Readback::new(&gpu_handles, staging_buffer);
+        let counter: Arc<CountingWaker> = Arc::new(CountingWaker {
+            wake_count: AtomicUsize::new(0),
+        });
+        let waker: Waker = Waker::from(counter.clone());
+        let mut context: Context = Context::from_waker(&waker);
+
+        // Nothing has polled the device yet, so the map callback can't have run
+        assert!(Pin::new(&mut readback).poll(&mut context).is_pending());
+        assert!(Pin::new(&mut readback).poll(&mut context).is_pending());
+        assert_eq!(counter.wake_count.load(Ordering::SeqCst), 0);
+
+        gpu_handles.device.poll(wgpu::Maintain::Wait);
+        assert_eq!(counter.wake_count.load(Ordering::SeqCst), 1);
+        match Pin::new(&mut readback).poll(&mut context) {
+            Poll::Ready(output) => assert_eq!(output.unwrap(), input),
+            Poll::Pending => panic!("The readback is still pending after the device was waited on"),
+        }
+    }
+}
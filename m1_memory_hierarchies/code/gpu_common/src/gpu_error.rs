@@ -0,0 +1,110 @@
+use std::fmt;
+
+use wgpu::{Backends, BufferAsyncError, Features, RequestDeviceError};
+
+// A limit the adapter couldn't give us
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LimitShortfall {
+    pub name: &'static str,
+    pub requested: u64,
+    pub supported: u64,
+}
+
+#[derive(Debug)]
+pub enum GPUError {
+    // Nothing at all was found for these backends
+    NoAdapter {
+        backends: Backends,
+        software: bool,
+    },
+    // There were adapters, but none had the requested name
+    NoMatchingAdapter {
+        name: String,
+        available: Vec<String>,
+    },
+    MissingFeatures {
+        adapter: String,
+        missing: Features,
+    },
+    UnsupportedLimits {
+        adapter: String,
+        shortfalls: Vec<LimitShortfall>,
+    },
+    RequestDevice(RequestDeviceError),
+    // A cached pipeline was dispatched with different bindings than it was built with
+    BindingLayoutMismatch {
+        entry_point: String,
+    },
+    // Only read-write buffers with COPY_SRC usage can be read back
+    NotReadable {
+        binding: u32,
+    },
+    BufferMap(BufferAsyncError),
+}
+
+impl fmt::Display for GPUError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GPUError::NoAdapter { backends, software } => {
+                let kind: &str = if *software { "software adapter" } else { "GPU" };
+                write!(f, "Failed to find a usable {} for {:?}", kind, backends)
+            }
+            GPUError::NoMatchingAdapter { name, available } => write!(
+                f,
+                "No adapter has a name containing \"{}\", found: {}",
+                name,
+                if available.is_empty() {
+                    "nothing".to_string()
+                } else {
+                    available.join(", ")
+                }
+            ),
+            GPUError::MissingFeatures { adapter, missing } => {
+                write!(f, "{} is missing the features {:?}", adapter, missing)
+            }
+            GPUError::UnsupportedLimits {
+                adapter,
+                shortfalls,
+            } => {
+                write!(f, "{} doesn't support the limits", adapter)?;
+                for (index, shortfall) in shortfalls.iter().enumerate() {
+                    let separator: &str = if index == 0 { ":" } else { "," };
+                    write!(
+                        f,
+                        "{} {} (requested {}, supported {})",
+                        separator, shortfall.name, shortfall.requested, shortfall.supported
+                    )?;
+                }
+                Ok(())
+            }
+            GPUError::RequestDevice(error) => write!(f, "Failed to request a device: {}", error),
+            GPUError::BindingLayoutMismatch { entry_point } => write!(
+                f,
+                "{} was dispatched with different bindings than it was compiled with",
+                entry_point
+            ),
+            GPUError::NotReadable { binding } => write!(
+                f,
+                "Binding {} isn't a read-write buffer with COPY_SRC usage",
+                binding
+            ),
+            GPUError::BufferMap(error) => write!(f, "Failed to map a buffer: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for GPUError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GPUError::RequestDevice(error) => Some(error),
+            GPUError::BufferMap(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<RequestDeviceError> for GPUError {
+    fn from(error: RequestDeviceError) -> Self {
+        GPUError::RequestDevice(error)
+    }
+}
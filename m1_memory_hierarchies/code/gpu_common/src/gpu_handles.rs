@@ -0,0 +1,125 @@
+use wgpu::{
+    Adapter, AdapterInfo, Device, DeviceType, Features, Instance, Limits, Queue,
+    RequestAdapterOptions,
+};
+
+use crate::{
+    gpu_error::GPUError,
+    gpu_options::{find_adapter_by_name, GPUOptions},
+};
+
+// The vendor id Mesa reports for LavaPipe
+pub const LAVAPIPE_VENDOR_ID: usize = 0x10005;
+
+// Try hovering your mouse over these types and see
+// what the messages are!
+pub struct GPUHandles {
+    pub instance: Instance,
+    pub queue: Queue,
+    pub device: Device,
+    pub adapter: Adapter,
+    pub adapter_info: AdapterInfo,
+}
+
+pub fn is_software_adapter(adapter_info: &AdapterInfo) -> bool {
+    adapter_info.device_type == DeviceType::Cpu || adapter_info.vendor == LAVAPIPE_VENDOR_ID
+}
+
+fn create_instance(options: &GPUOptions) -> Instance {
+    wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: options.backends,
+        dx12_shader_compiler: Default::default(),
+    })
+}
+
+// Picks by name if the options have one, otherwise lets wgpu pick by power preference
+pub async fn request_adapter(
+    instance: &Instance,
+    options: &GPUOptions,
+) -> Result<Adapter, GPUError> {
+    if let Some(adapter_name) = &options.adapter_name {
+        let mut adapters: Vec<Adapter> = instance.enumerate_adapters(options.backends).collect();
+        let infos: Vec<AdapterInfo> = adapters.iter().map(|adapter| adapter.get_info()).collect();
+        return match find_adapter_by_name(&infos, adapter_name) {
+            Some(index) => Ok(adapters.swap_remove(index)),
+            None => Err(GPUError::NoMatchingAdapter {
+                name: adapter_name.clone(),
+                available: infos.into_iter().map(|info| info.name).collect(),
+            }),
+        };
+    }
+
+    // In the case of both an integrated and a dedicated GPU, high performance
+    // should prefer the dedicated GPU. We don't require a compatible surface,
+    // which is what would allow us to present to screen.
+    let adapter_request: RequestAdapterOptions = RequestAdapterOptions {
+        power_preference: options.power_preference,
+        compatible_surface: None,
+        force_fallback_adapter: options.use_software_gpu,
+    };
+    instance
+        .request_adapter(&adapter_request)
+        .await
+        .ok_or(GPUError::NoAdapter {
+            backends: options.backends,
+            software: options.use_software_gpu,
+        })
+}
+
+// Finds an adapter and requests a device with the negotiated features and limits
+pub async fn request_gpu(options: &GPUOptions) -> Result<GPUHandles, GPUError> {
+    let instance: Instance = create_instance(options);
+    // `request_adapter` instantiates the general connection to the GPU
+    let adapter: Adapter = request_adapter(&instance, options).await?;
+    let adapter_info: AdapterInfo = adapter.get_info();
+
+    let features: Features = options
+        .negotiate_features(adapter.features())
+        .map_err(|missing| GPUError::MissingFeatures {
+            adapter: adapter_info.name.clone(),
+            missing,
+        })?;
+    let limits: Limits = options
+        .negotiate_limits(&adapter.limits())
+        .map_err(|shortfalls| GPUError::UnsupportedLimits {
+            adapter: adapter_info.name.clone(),
+            shortfalls,
+        })?;
+
+    // `request_device` instantiates the feature specific connection to the GPU
+    let (device, queue): (Device, Queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features,
+                limits,
+            },
+            None,
+        )
+        .await?;
+
+    Ok(GPUHandles {
+        instance,
+        queue,
+        device,
+        adapter,
+        adapter_info,
+    })
+}
+
+pub async fn self_test(options: &GPUOptions) -> bool {
+    println!("Performing self test to check system for compatibility.");
+    let instance: Instance = create_instance(options);
+    match request_adapter(&instance, options).await {
+        Ok(adapter) => {
+            let info: AdapterInfo = adapter.get_info();
+            println!("Found GPU: {:?}", info);
+            true
+        }
+        Err(error) => {
+            println!("{}", error);
+            println!("Failed to find a usable GPU. This framework will only run CPU code.");
+            false
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use wgpu::{AdapterInfo, Backends, Features, Limits, PowerPreference};
+
+use crate::gpu_error::LimitShortfall;
+
+// The same variables wgpu's own examples read, so they work across all of the projects
+pub const BACKEND_VARIABLE: &str = "WGPU_BACKEND";
+pub const POWER_PREFERENCE_VARIABLE: &str = "WGPU_POWER_PREF";
+pub const ADAPTER_NAME_VARIABLE: &str = "WGPU_ADAPTER_NAME";
+
+// Which limits to ask the device for
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum DeviceLimits {
+    // What wgpu guarantees on most desktop GPUs
+    #[default]
+    Default,
+    // Enough for older GPUs and WebGPU
+    Downlevel,
+    // Everything the adapter has. Software adapters don't always reach the defaults
+    Adapter,
+    Custom(Limits),
+}
+
+// Describes which adapter to pick and what to ask of its device.
+// Required features and limits fail with a GPUError if the adapter lacks them,
+// optional features are only enabled if the adapter has them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GPUOptions {
+    pub backends: Backends,
+    pub power_preference: PowerPreference,
+    pub adapter_name: Option<String>,
+    pub use_software_gpu: bool,
+    pub required_features: Features,
+    pub optional_features: Features,
+    pub limits: DeviceLimits,
+}
+
+impl Default for GPUOptions {
+    fn default() -> Self {
+        GPUOptions {
+            backends: Backends::all(),
+            power_preference: PowerPreference::HighPerformance,
+            adapter_name: None,
+            use_software_gpu: false,
+            required_features: Features::empty(),
+            optional_features: Features::empty(),
+            limits: DeviceLimits::Default,
+        }
+    }
+}
+
+impl GPUOptions {
+    pub fn new() -> Self {
+        GPUOptions::default()
+    }
+
+    // The defaults, overridden by WGPU_BACKEND, WGPU_POWER_PREF and WGPU_ADAPTER_NAME
+    pub fn from_env() -> Self {
+        let mut options: GPUOptions = GPUOptions::default();
+        if let Some(backends) = wgpu::util::backend_bits_from_env() {
+            options.backends = backends;
+        }
+        if let Some(power_preference) = wgpu::util::power_preference_from_env() {
+            options.power_preference = power_preference;
+        }
+        if let Ok(name) = std::env::var(ADAPTER_NAME_VARIABLE) {
+            if !name.trim().is_empty() {
+                options.adapter_name = Some(name);
+            }
+        }
+        options
+    }
+
+    pub fn with_backends(mut self, backends: Backends) -> Self {
+        self.backends = backends;
+        self
+    }
+
+    pub fn with_power_preference(mut self, power_preference: PowerPreference) -> Self {
+        self.power_preference = power_preference;
+        self
+    }
+
+    // Picks the first adapter whose name contains this, ignoring case
+    pub fn with_adapter_name(mut self, adapter_name: &str) -> Self {
+        self.adapter_name = Some(adapter_name.to_string());
+        self
+    }
+
+    // Asks for a software adapter, such as LavaPipe or WARP, instead of a GPU.
+    // These don't always reach the default limits, so they get the adapter's limits.
+    pub fn with_software_gpu(mut self, use_software_gpu: bool) -> Self {
+        self.use_software_gpu = use_software_gpu;
+        if use_software_gpu && self.limits == DeviceLimits::Default {
+            self.limits = DeviceLimits::Adapter;
+        }
+        self
+    }
+
+    pub fn with_required_features(mut self, features: Features) -> Self {
+        self.required_features = features;
+        self
+    }
+
+    pub fn with_optional_features(mut self, features: Features) -> Self {
+        self.optional_features = features;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: DeviceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    // The features to request from an adapter which supports supported_features.
+    // Returns the missing features if any of the required features aren't supported.
+    pub fn negotiate_features(&self, supported_features: Features) -> Result<Features, Features> {
+        let missing: Features = self.required_features - supported_features;
+        if missing.is_empty() {
+            Ok(self.required_features | (self.optional_features & supported_features))
+        } else {
+            Err(missing)
+        }
+    }
+
+    // The limits to request from an adapter which supports supported_limits.
+    // Returns every limit which is out of reach.
+    pub fn negotiate_limits(
+        &self,
+        supported_limits: &Limits,
+    ) -> Result<Limits, Vec<LimitShortfall>> {
+        let requested: Limits = match &self.limits {
+            DeviceLimits::Default => Limits::default(),
+            DeviceLimits::Downlevel => Limits::downlevel_defaults(),
+            DeviceLimits::Adapter => supported_limits.clone(),
+            DeviceLimits::Custom(limits) => limits.clone(),
+        };
+
+        let mut shortfalls: Vec<LimitShortfall> = Vec::new();
+        requested.check_limits_with_fail_fn(
+            supported_limits,
+            false,
+            |name, requested, supported| {
+                shortfalls.push(LimitShortfall {
+                    name,
+                    requested,
+                    supported,
+                })
+            },
+        );
+
+        if shortfalls.is_empty() {
+            Ok(requested)
+        } else {
+            Err(shortfalls)
+        }
+    }
+}
+
+// The index of the first adapter whose name contains adapter_name, ignoring case
+pub fn find_adapter_by_name(adapters: &[AdapterInfo], adapter_name: &str) -> Option<usize> {
+    let adapter_name: String = adapter_name.trim().to_lowercase();
+    adapters
+        .iter()
+        .position(|info| info.name.to_lowercase().contains(&adapter_name))
+}
@@ -0,0 +1,150 @@
+#[cfg(test)]
+mod tests {
+    use wgpu::{AdapterInfo, Backend, DeviceType, Features, Limits};
+
+    use crate::{
+        find_adapter_by_name, is_software_adapter, DeviceLimits, GPUError, GPUOptions,
+        LimitShortfall, LAVAPIPE_VENDOR_ID,
+    };
+
+    fn adapter_info(name: &str, vendor: usize, device_type: DeviceType) -> AdapterInfo {
+        AdapterInfo {
+            name: name.to_string(),
+            vendor,
+            device: 0,
+            device_type,
+            driver: "driver".to_string(),
+            driver_info: "1.0".to_string(),
+            backend: Backend::Vulkan,
+        }
+    }
+
+    #[test]
+    fn builder() {
+        let options: GPUOptions = GPUOptions::new()
+            .with_adapter_name("GeForce")
+            .with_required_features(Features::TIMESTAMP_QUERY)
+            .with_limits(DeviceLimits::Downlevel);
+        assert_eq!(options.adapter_name.as_deref(), Some("GeForce"));
+        assert_eq!(options.required_features, Features::TIMESTAMP_QUERY);
+        assert_eq!(options.limits, DeviceLimits::Downlevel);
+        assert!(!options.use_software_gpu);
+
+        // Software adapters get their own limits, unless asked for something else
+        let software: GPUOptions = GPUOptions::new().with_software_gpu(true);
+        assert_eq!(software.limits, DeviceLimits::Adapter);
+        let software: GPUOptions = GPUOptions::new()
+            .with_limits(DeviceLimits::Downlevel)
+            .with_software_gpu(true);
+        assert_eq!(software.limits, DeviceLimits::Downlevel);
+    }
+
+    #[test]
+    fn features() {
+        let options: GPUOptions = GPUOptions::new()
+            .with_required_features(Features::PUSH_CONSTANTS)
+            .with_optional_features(Features::TIMESTAMP_QUERY);
+
+        assert_eq!(
+            options.negotiate_features(Features::PUSH_CONSTANTS),
+            Ok(Features::PUSH_CONSTANTS)
+        );
+        assert_eq!(
+            options.negotiate_features(Features::all()),
+            Ok(Features::PUSH_CONSTANTS | Features::TIMESTAMP_QUERY)
+        );
+        assert_eq!(
+            options.negotiate_features(Features::TIMESTAMP_QUERY),
+            Err(Features::PUSH_CONSTANTS)
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let small: Limits = Limits {
+            max_storage_buffers_per_shader_stage: 6,
+            ..Limits::default()
+        };
+
+        assert_eq!(
+            GPUOptions::new().negotiate_limits(&Limits::default()),
+            Ok(Limits::default())
+        );
+        assert_eq!(
+            GPUOptions::new().negotiate_limits(&small),
+            Err(vec![LimitShortfall {
+                name: "max_storage_buffers_per_shader_stage",
+                requested: 8,
+                supported: 6,
+            }])
+        );
+        assert_eq!(
+            GPUOptions::new()
+                .with_limits(DeviceLimits::Adapter)
+                .negotiate_limits(&small),
+            Ok(small.clone())
+        );
+        assert_eq!(
+            GPUOptions::new()
+                .with_limits(DeviceLimits::Downlevel)
+                .negotiate_limits(&small),
+            Ok(Limits::downlevel_defaults())
+        );
+    }
+
+    #[test]
+    fn adapter_names() {
+        let adapters: Vec<AdapterInfo> = vec![
+            adapter_info("NVIDIA GeForce RTX 4090", 0x10de, DeviceType::DiscreteGpu),
+            adapter_info(
+                "llvmpipe (LLVM 15.0.7, 256 bits)",
+                LAVAPIPE_VENDOR_ID,
+                DeviceType::Cpu,
+            ),
+        ];
+        assert_eq!(find_adapter_by_name(&adapters, "geforce"), Some(0));
+        assert_eq!(find_adapter_by_name(&adapters, " LLVMPIPE "), Some(1));
+        assert_eq!(find_adapter_by_name(&adapters, "Radeon"), None);
+
+        assert!(!is_software_adapter(&adapters[0]));
+        assert!(is_software_adapter(&adapters[1]));
+        assert!(is_software_adapter(&adapter_info(
+            "Microsoft Basic Render Driver",
+            0x1414,
+            DeviceType::Cpu
+        )));
+    }
+
+    #[test]
+    fn error_messages() {
+        let error: GPUError = GPUError::NoMatchingAdapter {
+            name: "Radeon".to_string(),
+            available: vec!["GeForce".to_string(), "llvmpipe".to_string()],
+        };
+        assert_eq!(
+            error.to_string(),
+            "No adapter has a name containing \"Radeon\", found: GeForce, llvmpipe"
+        );
+
+        let error: GPUError = GPUError::UnsupportedLimits {
+            adapter: "llvmpipe".to_string(),
+            shortfalls: vec![
+                LimitShortfall {
+                    name: "max_bind_groups",
+                    requested: 8,
+                    supported: 4,
+                },
+                LimitShortfall {
+                    name: "max_buffer_size",
+                    requested: 512,
+                    supported: 256,
+                },
+            ],
+        };
+        assert_eq!(
+            error.to_string(),
+            "llvmpipe doesn't support the limits: max_bind_groups (requested 8, supported 4), \
+             max_buffer_size (requested 512, supported 256)"
+        );
+    }
+}
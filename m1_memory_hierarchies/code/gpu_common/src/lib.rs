@@ -0,0 +1,19 @@
+// GPU setup and compute helpers shared by the projects in this course,
+// so they don't each carry their own copy.
+pub mod compute;
+pub mod dispatcher;
+pub mod dispatcher_test;
+pub mod gpu_error;
+pub mod gpu_handles;
+pub mod gpu_options;
+pub mod gpu_options_test;
+
+pub use compute::{create_bind_group, create_compute_pipeline, create_shader_module};
+pub use dispatcher::{
+    BindingKind, BufferAccess, ComputeDispatcher, Dispatch, PipelineKey, Readback,
+};
+pub use gpu_error::{GPUError, LimitShortfall};
+pub use gpu_handles::{
+    is_software_adapter, request_adapter, request_gpu, self_test, GPUHandles, LAVAPIPE_VENDOR_ID,
+};
+pub use gpu_options::{find_adapter_by_name, DeviceLimits, GPUOptions};
@@ -1,6 +1,8 @@
 use crate::{
     gpu_vector::GPUVector,
-    utility::{are_vectors_equivalent, mean_square_error, run_compute_shader, GPUHandles, Uniform},
+    utility::{
+        are_vectors_equivalent, mean_square_error, ComputeDispatcher, GPUHandles, UniformElements,
+    },
 };
 
 // The length of filter is assumed to be oddly number, i.e. 1, 3, 5, 7, 9, 11
@@ -39,16 +41,21 @@ fn test_ground_truth() -> bool {
     true
 }
 
-fn run_naive_convolution(handles: &GPUHandles, signal: Vec<f32>, kernel: Vec<f32>) -> Vec<f32> {
+fn run_naive_convolution(
+    handles: &GPUHandles,
+    dispatcher: &mut ComputeDispatcher,
+    signal: Vec<f32>,
+    kernel: Vec<f32>,
+) -> Vec<f32> {
     let signal_size = signal.len();
     let kernel_size = kernel.len();
     let output: Vec<f32> = vec![0.0; signal_size];
 
-    let gpu_signal = GPUVector::new(&handles, signal, "signal", false);
-    let gpu_kernel = GPUVector::new(&handles, kernel, "kernel", false);
-    let mut gpu_output = GPUVector::new(&handles, output, "output", true);
+    let gpu_signal = GPUVector::new(&handles, signal, "signal");
+    let gpu_kernel = GPUVector::new(&handles, kernel, "kernel");
+    let gpu_output = GPUVector::new(&handles, output, "output");
 
-    let uniform: Uniform = Uniform::new(&handles, signal_size, kernel_size, 0, 0);
+    let uniform: UniformElements = UniformElements::new(signal_size, kernel_size, 0, 0);
     let shader_file: &'static str = include_str!("convolution_naive.wgsl");
     let shader_function: &str = "convolution_naive";
 
@@ -57,24 +64,26 @@ fn run_naive_convolution(handles: &GPUHandles, signal: Vec<f32>, kernel: Vec<f32
     let block_size_y: usize = 1;
     let launch_blocks_y: u32 = 1;
 
-    run_compute_shader(
-        handles,
-        block_size_x,
+    println!(
+        "Dispatching {} x blocks of {} threads and {} y blocks of {} threads each for a total of {} threads!",
         launch_blocks_x,
-        block_size_y,
+        block_size_x,
         launch_blocks_y,
-        shader_file,
-        shader_function,
-        &uniform,
-        &gpu_signal,
-        &gpu_kernel,
-        &mut gpu_output,
+        block_size_y,
+        launch_blocks_x as usize * launch_blocks_y as usize * block_size_x * block_size_y
     );
-
-    return gpu_output.cpu_data;
+    dispatcher
+        .dispatch(handles, shader_file, shader_function)
+        .with_uniform(0, &uniform)
+        .with_read(1, &gpu_signal.storage_buffer)
+        .with_read(2, &gpu_kernel.storage_buffer)
+        .with_read_write(3, &gpu_output.storage_buffer)
+        .with_workgroups(launch_blocks_x, launch_blocks_y, 1)
+        .read_back(3)
+        .expect("Failed to run convolution_naive")
 }
 
-pub fn convolution(handles: &GPUHandles) -> bool {
+pub fn convolution(handles: &GPUHandles, dispatcher: &mut ComputeDispatcher) -> bool {
     // A small test to ensure that the convolution_cpu function is actually correct.
     let ground_truth_is_correct: bool = test_ground_truth();
     println!(
@@ -110,14 +119,15 @@ pub fn convolution(handles: &GPUHandles) -> bool {
     // coalesced accessing when loading data into shared memory.
     // What happens when you set the block size to different multiples of 32? Why do you think that is?
     //
-    // HINT - You need a run_compute_shader() call per type of compute shader.
-    // Figure out what the arguments are supposed to be (see vector_add.rs) and
+    // HINT - You need a dispatcher.dispatch() call per type of compute shader.
+    // Figure out what the bindings are supposed to be (see vector_add.rs) and
     // call the correct shader function in the correct shader file.
+    // If you want to add a bias or another input, add another with_read().
     //
 
     //
     // YOUR CODE HERE
-    let data_naive = run_naive_convolution(handles, signal.clone(), filter.clone());
+    let data_naive = run_naive_convolution(handles, dispatcher, signal.clone(), filter.clone());
     let data_shared: Vec<f32> = ground_truth.clone(); // Remove this and replace with your own data
     let data_padded: Vec<f32> = ground_truth.clone(); // Remove this and replace with your own data
                                                       //
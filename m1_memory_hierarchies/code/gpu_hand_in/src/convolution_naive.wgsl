@@ -15,7 +15,7 @@ var<storage, read> signal: array<f32>;
 var<storage, read> kernel: array<f32>;
 
 @group(0) @binding(3)
-var<storage, write> output: array<f32>;
+var<storage, read_write> output: array<f32>;
 
 @compute @workgroup_size(32, 1, 1) 
 fn convolution_naive(
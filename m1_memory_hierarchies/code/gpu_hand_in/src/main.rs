@@ -10,7 +10,7 @@ use crate::convolution::convolution;
 mod matrix_multiplication;
 use crate::matrix_multiplication::matrix_multiplication;
 
-use utility::{self_test, GPUHandles, initialize_gpu};
+use utility::{self_test, ComputeDispatcher, GPUHandles, initialize_gpu};
 
 fn main() {
     // Initialize the env_logger to get usueful messages from wgpu.
@@ -29,8 +29,11 @@ fn main() {
     // Keep track of the handles to central stuff like device and queue.
     let handles: GPUHandles = pollster::block_on(initialize_gpu()).expect("Was unsuccesful in creating GPU Handles");
 
-    assert!(vector_add(&handles));
-    assert!(convolution(&handles));
-    assert!(matrix_multiplication(&handles));
+    // Keeps the compiled shaders around, so each one is only compiled once.
+    let mut dispatcher: ComputeDispatcher = ComputeDispatcher::new();
+
+    assert!(vector_add(&handles, &mut dispatcher));
+    assert!(convolution(&handles, &mut dispatcher));
+    assert!(matrix_multiplication(&handles, &mut dispatcher));
 }
 
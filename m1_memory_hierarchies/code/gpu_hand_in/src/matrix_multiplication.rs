@@ -1,4 +1,4 @@
-use crate::utility::{GPUHandles, mean_square_error, are_vectors_equivalent};
+use crate::utility::{GPUHandles, ComputeDispatcher, mean_square_error, are_vectors_equivalent};
 
 fn matrix_multiplication_cpu(
     left_matrix: &Vec<f32>,
@@ -40,7 +40,7 @@ fn test_ground_truth() -> bool {
     true
 }
 
-pub fn matrix_multiplication(handles: &GPUHandles) -> bool {
+pub fn matrix_multiplication(handles: &GPUHandles, _dispatcher: &mut ComputeDispatcher) -> bool {
     // A small test to ensure that the matrix_multiplication_cpu function is actually correct.
     let ground_truth_is_correct: bool = test_ground_truth();
     println!("Matrix multiplication ground truth function is correct: {}", ground_truth_is_correct);
@@ -82,8 +82,9 @@ pub fn matrix_multiplication(handles: &GPUHandles) -> bool {
     // restriction that the second matrix is transposed? Is the different between the transposed and
     // non-transposed different for the naive and tiled versions different? Why do you think that is?
     //
-    // HINT - You need a run_compute_shader() call per type of compute shader.
-    // Figure out what the arguments are supposed to be (see vector_add.rs) and
+    // HINT - You need a dispatcher.dispatch() call per type of compute shader,
+    // so rename _dispatcher to dispatcher once you start using it.
+    // Figure out what the bindings are supposed to be (see vector_add.rs) and
     // call the correct shader function in the correct shader file.
     //
 
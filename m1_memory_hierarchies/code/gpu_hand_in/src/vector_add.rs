@@ -1,5 +1,5 @@
 
-use crate::{utility::{GPUHandles, mean_square_error, are_vectors_equivalent, Uniform, run_compute_shader}, gpu_vector::GPUVector};
+use crate::{utility::{GPUHandles, ComputeDispatcher, mean_square_error, are_vectors_equivalent, UniformElements}, gpu_vector::GPUVector};
 
 fn vector_add_cpu(input_a: &Vec<f32>, input_b: &Vec<f32>) -> Vec<f32> {
     assert!(input_a.len() == input_b.len());
@@ -13,7 +13,7 @@ fn vector_add_cpu(input_a: &Vec<f32>, input_b: &Vec<f32>) -> Vec<f32> {
     output
 }
 
-pub fn vector_add(handles: &GPUHandles) -> bool {
+pub fn vector_add(handles: &GPUHandles, dispatcher: &mut ComputeDispatcher) -> bool {
     // Setup our CPU-side data
     let element_count: usize = 100;
     let input_a: Vec<f32> = (0..element_count).into_iter().map(|element| element as f32).collect();
@@ -24,15 +24,12 @@ pub fn vector_add(handles: &GPUHandles) -> bool {
 
 
     // Create our uniform for telling the shader how big the vectors are.
-    let uniform: Uniform = Uniform::new(handles, element_count, 0, 0, 0);
+    let uniform: UniformElements = UniformElements::new(element_count, 0, 0, 0);
 
     // Create the GPU vectors.
-    // Note the true at the end of the output vector creation.
-    // This will result in a staging_buffer being created, which we
-    // can read from on the CPU.
-    let input_a: GPUVector = GPUVector::new(&handles, input_a, "input_a", false);
-    let input_b: GPUVector = GPUVector::new(&handles, input_b, "input_b", false);
-    let mut output: GPUVector = GPUVector::new(&handles, output, "output", true);
+    let input_a: GPUVector = GPUVector::new(&handles, input_a, "input_a");
+    let input_b: GPUVector = GPUVector::new(&handles, input_b, "input_b");
+    let mut output: GPUVector = GPUVector::new(&handles, output, "output");
 
     // We will use 32 threads in a work group/warp
     // We are doing this in 1 dimension, but could do it in
@@ -44,20 +41,20 @@ pub fn vector_add(handles: &GPUHandles) -> bool {
     let shader_file: &'static str = include_str!("vector_add.wgsl");
     let shader_function: &str = "vector_add";
 
-    // Reuse this function for the convolution and matrix multiplication tasks
-    run_compute_shader(
-        handles,
-        block_size_x, 
-        launch_blocks_x,
-        block_size_y,
-        launch_blocks_y,
-        shader_file,
-        shader_function,
-        &uniform,
-        &input_a,
-        &input_b,
-        &mut output,
-    );
+    // The dispatcher compiles the shader the first time it sees it and reuses
+    // it afterwards. Each buffer is bound to the slot with the same
+    // @binding number in the shader, read-only or read-write.
+    // Reuse the dispatcher for the convolution and matrix multiplication tasks.
+    println!("Dispatching {} x blocks of {} threads and {} y blocks of {} threads each for a total of {} threads!", launch_blocks_x, block_size_x, launch_blocks_y, block_size_y, launch_blocks_x as usize * launch_blocks_y as usize * block_size_x * block_size_y);
+    output.cpu_data = dispatcher
+        .dispatch(handles, shader_file, shader_function)
+        .with_uniform(0, &uniform)
+        .with_read(1, &input_a.storage_buffer)
+        .with_read(2, &input_b.storage_buffer)
+        .with_read_write(3, &output.storage_buffer)
+        .with_workgroups(launch_blocks_x, launch_blocks_y, 1)
+        .read_back(3) // Blocks until the output is back on the CPU
+        .expect("Failed to run vector_add");
 
     println!("vector_add MSE: {}", mean_square_error(&ground_truth, &output.cpu_data));
     let success: bool = are_vectors_equivalent(&ground_truth, &output.cpu_data);
@@ -7,9 +7,9 @@ use std::{thread, time::Instant};
 use command::Command;
 use control_panel::ControlPanel;
 use crossbeam_channel::{unbounded, Receiver, Sender};
+use gpu_common::{request_gpu, GPUOptions};
 use render_engine::RenderEngine;
 
-use wgpu::{Adapter, AdapterInfo, Device, Instance, Queue};
 use winit::{
     event::{
         ElementState,
@@ -20,6 +20,8 @@ use winit::{
     window::WindowId,
 };
 
+pub use gpu_common::GPUHandles;
+
 // Simple wrapper to handle different window ids.
 struct WindowSelector {
     control_panel_id: u64,
@@ -208,75 +210,10 @@ async fn run_loop(
     });
 }
 
-// A convenience wrapper for interfacing with the GPU.
-pub struct GPUHandles {
-    pub queue: Queue,
-    pub adapter: Adapter,
-    pub instance: Instance,
-    pub device: Device,
-}
-
-impl GPUHandles {
-    pub fn new() -> Self {
-        let instance: Instance = wgpu::Instance::default();
-
-        // You might want to change this to prefer a certain backend or a high power GPU.
-        let adapter: Adapter =
-            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-                ..Default::default()
-            }))
-            .expect("Failed to find an appropriate adapter");
-
-        // Create the logical device and command queue
-        // If you want to run with a webgl backend, you 
-        // can set the limits to one of the downlevels
-        let (device, queue): (Device, Queue) = pollster::block_on(adapter.request_device(
-            &wgpu::DeviceDescriptor {
-                label: None,
-                features: wgpu::Features::empty(),
-                limits: wgpu::Limits::default(),
-            },
-            None,
-        ))
-        .expect("Failed to create device");
-
-        GPUHandles {
-            queue,
-            adapter,
-            instance,
-            device,
-        }
-    }
-}
-
-impl Default for GPUHandles {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 // Checks whether the system has a findable adapter (GPU).
 // Returns false if no adapter is found.
 pub fn self_test() -> bool {
-    println!("Performing self test to check system for compatibility.");
-    let instance: Instance = wgpu::Instance::default();
-
-    // `request_adapter` instantiates the general connection to the GPU
-    let adapter_option: Option<Adapter> =
-        pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()));
-
-    // Handle whether we find a GPU or not.
-    match adapter_option {
-        Some(adapter) => {
-            let info: AdapterInfo = adapter.get_info();
-            println!("Found GPU: {:?}", info);
-            true
-        }
-        None => {
-            println!("Failed to find a usable GPU. This framework will only run CPU code.");
-            false
-        }
-    }
+    pollster::block_on(gpu_common::self_test(&GPUOptions::from_env()))
 }
 
 pub fn run() {
@@ -288,7 +225,12 @@ pub fn run() {
 
         // Actually instantiate the GPU handles we need, like queue, device and adapter.
         // The render engine won't use these, but make its own.
-        let gpu_handles: GPUHandles = GPUHandles::new();
+        // You might want to change the options to prefer a certain backend or GPU,
+        // or, if you want to run with a webgl backend, set the limits to one of the
+        // downlevels. The WGPU_ADAPTER_NAME and WGPU_BACKEND environment variables
+        // also pick the adapter.
+        let gpu_handles: GPUHandles = pollster::block_on(request_gpu(&GPUOptions::from_env()))
+            .expect("Failed to create device");
 
         // Our render and control window sizes, and space between them.
         const RENDER_WINDOW_SIZE: winit::dpi::PhysicalSize<u32> =